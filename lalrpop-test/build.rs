@@ -1,5 +1,10 @@
 extern crate lalrpop;
 
+use lalrpop::builder::{GrammarBuilder, Symbol};
+use std::env;
+use std::fs;
+use std::path::Path;
+
 fn main() {
     lalrpop::Configuration::new()
         .emit_comments(true)
@@ -8,4 +13,317 @@ fn main() {
         .log_debug()
         .process_current_dir()
         .unwrap();
+
+    // a parser generated without a `.lalrpop` file, for `test_grammar_builder`
+    let mut builder = GrammarBuilder::new();
+    builder
+        .use_decl("std::str::FromStr")
+        .nonterminal("Expr", "i32", true)
+        .nonterminal("Factor", "i32", false)
+        .nonterminal("Num", "i32", false);
+    for &(nonterminal, operand, operator, action) in &[
+        ("Expr", "Factor", "\"+\"", "l + r"),
+        ("Expr", "Factor", "\"-\"", "l - r"),
+        ("Factor", "Num", "\"*\"", "l * r"),
+    ] {
+        builder.production(
+            nonterminal,
+            vec![
+                Symbol::nonterminal(nonterminal).named("l"),
+                Symbol::terminal(operator),
+                Symbol::nonterminal(operand).named("r"),
+            ],
+            Some(action),
+        );
+    }
+    builder
+        .production("Expr", vec![Symbol::nonterminal("Factor")], None)
+        .production("Factor", vec![Symbol::nonterminal("Num")], None)
+        .production(
+            "Num",
+            vec![Symbol::terminal(r#"r"[0-9]+""#)],
+            Some("i32::from_str(<>).unwrap()"),
+        );
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(
+        Path::new(&out_dir).join("built_grammar.rs"),
+        builder.generate().unwrap(),
+    )
+    .unwrap();
+
+    // a table-driven parser without bounds or type checks, for
+    // `test_unchecked_tables`
+    let in_dir = Path::new(&out_dir).join("unchecked");
+    fs::create_dir_all(&in_dir).unwrap();
+    fs::write(
+        in_dir.join("unchecked_tables.lalrpop"),
+        r#"
+use std::str::FromStr;
+
+grammar;
+
+pub Expr: i32 = {
+    <l:Expr> "+" <r:Factor> => l + r,
+    Factor,
+};
+
+Factor: i32 = {
+    <l:Factor> "*" <r:Term> => l * r,
+    Term,
+};
+
+Term: i32 = {
+    r"[0-9]+" => i32::from_str(<>).unwrap(),
+    "(" <Expr> ")",
+};
+"#,
+    )
+    .unwrap();
+    lalrpop::Configuration::new()
+        .unchecked_tables(true)
+        .unchecked_downcasts(true)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // a parser that counts the rules it exercises, for `test_coverage`
+    let in_dir = Path::new(&out_dir).join("coverage");
+    fs::create_dir_all(&in_dir).unwrap();
+    fs::write(
+        in_dir.join("coverage.lalrpop"),
+        r#"
+grammar;
+
+pub Expr: i32 = {
+    <l:Expr> "+" <r:Term> => l + r,
+    <l:Expr> "-" <r:Term> => l - r,
+    Term,
+};
+
+Term: i32 = {
+    r"[0-9]+" => <>.parse().unwrap(),
+    "(" <Expr> ")",
+};
+"#,
+    )
+    .unwrap();
+    lalrpop::Configuration::new()
+        .emit_coverage(true)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // a parser that records its shifts and reductions, for `test_trace`
+    let in_dir = Path::new(&out_dir).join("trace");
+    fs::create_dir_all(&in_dir).unwrap();
+    fs::write(
+        in_dir.join("trace.lalrpop"),
+        r#"
+grammar;
+
+pub Expr: i32 = {
+    <l:Expr> "+" <r:Term> => l + r,
+    Term,
+};
+
+Term: i32 = {
+    r"[0-9]+" => <>.parse().unwrap(),
+    "(" <Expr> ")",
+};
+"#,
+    )
+    .unwrap();
+    lalrpop::Configuration::new()
+        .emit_trace(true)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // a parser tuned for `wasm32`, for `test_wasm_profile`
+    let in_dir = Path::new(&out_dir).join("wasm");
+    fs::create_dir_all(&in_dir).unwrap();
+    fs::write(
+        in_dir.join("wasm_profile.lalrpop"),
+        r#"
+grammar;
+
+pub Exprs: Vec<i32> = {
+    <v:(<Expr> ";")*> => v,
+};
+
+Expr: i32 = {
+    <l:Expr> "+" <r:Factor> => l + r,
+    <l:Expr> "-" <r:Factor> => l - r,
+    Factor,
+};
+
+Factor: i32 = {
+    <l:Factor> "*" <r:Term> => l * r,
+    Term,
+};
+
+Term: i32 = {
+    r"[0-9]+" => <>.parse().unwrap(),
+    "(" <Expr> ")",
+    "-" <Term> => -<>,
+};
+"#,
+    )
+    .unwrap();
+    lalrpop::Configuration::new()
+        .set_profile(lalrpop::Profile::Wasm)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // the grammar of `error_messages`, tuned for `wasm32`, for
+    // `test_wasm_error_messages`
+    let in_dir = Path::new(&out_dir).join("wasm_messages");
+    fs::create_dir_all(&in_dir).unwrap();
+    for extension in &["lalrpop", "messages"] {
+        fs::copy(
+            Path::new("src").join("error_messages").with_extension(extension),
+            in_dir.join("wasm_messages").with_extension(extension),
+        )
+        .unwrap();
+    }
+    lalrpop::Configuration::new()
+        .set_profile(lalrpop::Profile::Wasm)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // a parser with `extern "C"` functions, for `test_c_abi`
+    let in_dir = Path::new(&out_dir).join("c_abi");
+    fs::create_dir_all(&in_dir).unwrap();
+    fs::write(
+        in_dir.join("c_abi.lalrpop"),
+        r#"
+grammar;
+
+pub Expr: i32 = {
+    <l:Expr> "+" <r:Term> => l + r,
+    Term,
+};
+
+Term: i32 = {
+    r"[0-9]+" => <>.parse().unwrap(),
+    "(" <Expr> ")",
+};
+
+pub Name: &'input str = r"[a-z]+";
+"#,
+    )
+    .unwrap();
+    lalrpop::Configuration::new()
+        .emit_c_abi(true)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // a parser with bounded stacks, for `test_max_stack_depth`
+    let in_dir = Path::new(&out_dir).join("max_stack_depth");
+    fs::create_dir_all(&in_dir).unwrap();
+    fs::write(
+        in_dir.join("max_stack_depth.lalrpop"),
+        r#"
+use lalrpop_util::StackOverflow;
+
+grammar;
+
+extern {
+    type Error = StackOverflow<usize>;
+}
+
+pub Parens: u32 = {
+    "(" <Parens> ")" => <> + 1,
+    => 0,
+};
+"#,
+    )
+    .unwrap();
+    lalrpop::Configuration::new()
+        .max_stack_depth(20)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // a parser with inline stacks, for `test_inline_stacks`
+    let in_dir = Path::new(&out_dir).join("inline_stacks");
+    fs::create_dir_all(&in_dir).unwrap();
+    fs::write(
+        in_dir.join("inline_stacks.lalrpop"),
+        r#"
+grammar;
+
+pub Parens: u32 = {
+    "(" <Parens> ")" => <> + 1,
+    => 0,
+};
+"#,
+    )
+    .unwrap();
+    lalrpop::Configuration::new()
+        .inline_stacks(true)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // parsers whose tables are in a file of their own, for `test_split_tables`
+    let in_dir = Path::new(&out_dir).join("split");
+    fs::create_dir_all(&in_dir).unwrap();
+    fs::write(
+        in_dir.join("split_tables.lalrpop"),
+        r#"
+grammar;
+
+#[table_driven]
+pub Sum: i32 = {
+    <l:Sum> "+" <r:Product> => l + r,
+    Product,
+};
+
+#[table_driven]
+pub Product: i32 = {
+    <l:Product> "*" <r:Num> => l * r,
+    Num,
+};
+
+#[recursive_ascent]
+pub Num: i32 = r"[0-9]+" => <>.parse().unwrap();
+"#,
+    )
+    .unwrap();
+    lalrpop::Configuration::new()
+        .split_tables(true)
+        .force_build(true)
+        .set_in_dir(&in_dir)
+        .set_out_dir(&out_dir)
+        .process()
+        .unwrap();
+
+    // `cfg_flags.lalrpop` again, with its extensions, for `test_cfg_flags`
+    lalrpop::Configuration::new()
+        .set_flag("extensions", true)
+        .force_build(true)
+        .set_in_dir("src")
+        .set_out_dir(Path::new(&out_dir).join("extended"))
+        .process_file("src/cfg_flags.lalrpop")
+        .unwrap();
 }
@@ -0,0 +1,26 @@
+use std::cell::RefCell;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Expr<'ast, 'input> {
+    Var(&'input str),
+    Add(&'ast Expr<'ast, 'input>, &'ast Expr<'ast, 'input>),
+}
+
+pub struct Arena<T> {
+    data: RefCell<Vec<Box<T>>>,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena {
+            data: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn alloc(&self, value: T) -> &T {
+        let b = Box::new(value);
+        let p: *const T = &*b;
+        self.data.borrow_mut().push(b);
+        unsafe { &*p }
+    }
+}
@@ -0,0 +1,14 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+
+/// An AST whose definition needs the same bounds as the grammar
+/// producing it.
+#[derive(Debug, PartialEq)]
+pub enum Ast<T: FromStr>
+where
+    T::Err: Debug,
+{
+    Leaf(T),
+    Invalid(T::Err),
+    List(Vec<Ast<T>>),
+}
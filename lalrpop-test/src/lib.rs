@@ -1,16 +1,20 @@
 #![cfg_attr(not(test), allow(dead_code, unused_imports))]
 #![allow(unused_doc_comments)]
 
+extern crate ariadne;
+extern crate codespan_reporting;
 extern crate diff;
 #[macro_use]
 extern crate lalrpop_util;
+extern crate serde_json;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::fmt;
 use std::fs;
 use std::path::Path;
 use std::process::Command;
 
-use lalrpop_util::{ErrorRecovery, ParseError};
+use lalrpop_util::{ErrorRecovery, ParseError, Repair};
 
 use crate::util::tok::Tok;
 
@@ -57,6 +61,10 @@ lalrpop_mod!(expr_generic);
 lalrpop_mod!(generics_issue_104);
 mod generics_issue_104_lib;
 
+/// test for bounds written inline, as in `grammar<T: FromStr>`
+lalrpop_mod!(generics_bounds);
+mod generics_bounds_lib;
+
 /// Grammar parameterized by `F` with where clause `where F: for<'a> FnMut(&'a
 /// str)`.
 lalrpop_mod!(where_clause_with_forall);
@@ -130,9 +138,154 @@ lalrpop_mod!(match_section);
 lalrpop_mod!(match_section_byte);
 lalrpop_mod!(match_alternatives);
 
+/// test for named symbols written without angle brackets
+lalrpop_mod!(named_symbols);
+
+#[derive(Debug, PartialEq)]
+pub struct Assign {
+    name: String,
+    value: i32,
+}
+
+/// test for `type` aliases declared in the grammar
+lalrpop_mod!(type_alias);
+
+/// test for `include` of a shared token file
+lalrpop_mod!(include);
+lalrpop_mod!(include_other);
+
+/// test for `#[longest_match]` lexing
+lalrpop_mod!(longest_match);
+
+/// test for `#[repair]` error repair
+lalrpop_mod!(repair);
+lalrpop_mod!(repair_burke_fisher);
+lalrpop_mod!(repair_cost);
+lalrpop_mod!(repair_minimum_cost);
+
 /// regression test for issue #253.
 lalrpop_mod!(partial_parse);
 
+/// test for `#[prefix]` entry points
+lalrpop_mod!(prefix);
+lalrpop_mod!(prefix_tok);
+
+/// test for `#[resumable]` parsers
+lalrpop_mod!(resumable);
+
+/// test for `#[completions]` entry points
+lalrpop_mod!(completions);
+
+/// test for `#[island]` entry points
+lalrpop_mod!(island);
+lalrpop_mod!(island_outer);
+
+/// test for nonterminals with parameters, like `Stmt[in_loop: bool]`
+lalrpop_mod!(parameters);
+
+/// test for nonterminals typed `impl Trait`
+lalrpop_mod!(impl_trait);
+
+/// test for `#[runtime_start]`
+lalrpop_mod!(runtime_start);
+
+/// test for `#[runtime_start]` with an external tokenizer
+lalrpop_mod!(runtime_start_tok);
+
+/// test for `#[events]`
+lalrpop_mod!(events);
+
+/// test for `#[recognize]`
+lalrpop_mod!(recognize);
+
+/// test for `#[cancellable]`
+lalrpop_mod!(cancellable);
+
+/// test for `#[owned]`
+lalrpop_mod!(owned);
+
+/// test for `#[intern]` in the match block
+lalrpop_mod!(intern);
+
+/// test for `#[backtrack]`
+lalrpop_mod!(backtrack);
+
+/// test for `code { .. }` blocks, which use what is generated before them
+lalrpop_mod!(epilogue);
+
+/// test for `when` guards on alternatives
+lalrpop_mod!(guards);
+
+/// test for an arena lifetime with the generated lexer
+lalrpop_mod!(arena_lexer);
+
+/// definitions of the AST, which borrows from both the arena and the input
+mod arena_lexer_ast;
+
+/// test for reparsing the trees of `#[events]` incrementally
+lalrpop_mod!(incremental);
+
+/// test for the whitespace and comments that the lexer skips
+lalrpop_mod!(trivia);
+
+/// test for a parser generated with `lalrpop::builder`, in `build.rs`
+lalrpop_mod!(built_grammar);
+
+/// test for public nonterminals that pick their own code generator
+lalrpop_mod!(mixed_codegen);
+
+/// test for parse tables indexed without bounds checks, in `build.rs`
+lalrpop_mod!(unchecked_tables);
+
+/// test for messages for syntax errors, from `error_messages.messages`
+lalrpop_mod!(error_messages);
+
+/// test for counting the rules a parser exercises, in `build.rs`
+lalrpop_mod!(coverage);
+
+/// test for recording the shifts and reductions of a parse, in `build.rs`
+lalrpop_mod!(trace);
+
+/// test for `extern "C"` functions, in `build.rs`
+lalrpop_mod!(c_abi);
+
+/// test for parsers with bounded stacks, in `build.rs`
+lalrpop_mod!(max_stack_depth);
+
+/// test for stacks that keep their first entries inline, in `build.rs`
+lalrpop_mod!(inline_stacks);
+
+/// test for tables written to a file of their own, in `build.rs`
+lalrpop_mod!(split_tables);
+
+/// test for the `wasm` codegen profile, in `build.rs`
+lalrpop_mod!(wasm_profile);
+
+/// test for messages for syntax errors with the `wasm` profile, in `build.rs`
+lalrpop_mod!(wasm_messages);
+
+/// test for alternatives marked `#[cfg(flag = "..")]`, without the flag
+/// and, from `build.rs`, with it
+lalrpop_mod!(cfg_flags);
+lalrpop_mod!(cfg_flags_extended, "/extended/cfg_flags.rs");
+
+/// test for terminals with fixed ids, from `#[terminal_id]`
+lalrpop_mod!(terminal_ids);
+
+/// test for the parts of a generic parser that are not generic
+lalrpop_mod!(generic_tables);
+
+/// test for locations that are copied rather than cloned, with
+/// `#[copy_location]`
+lalrpop_mod!(copy_location);
+
+/// test for naming `'input` in the types of a grammar with its own lexer,
+/// without declaring it
+lalrpop_mod!(input_lifetime);
+
+/// test for errors that name what they expected, from `#[expected]`
+lalrpop_mod!(expected_names);
+
 /// regression test for issue #278.
 lalrpop_mod!(error_issue_278);
 
@@ -163,6 +316,9 @@ lalrpop_mod!(sp_from_optional);
 
 lalrpop_mod!(nested);
 
+/// test for explaining the errors of the generated lexer
+lalrpop_mod!(invalid_text);
+
 pub fn use_cfg_created_parser() {
     cfg::CreatedParser::new();
 }
@@ -892,6 +1048,24 @@ fn inline_fallible() {
         .is_ok());
 }
 
+#[test]
+fn test_backends_agree() {
+    // these grammars are built with both backends (see `unit_test` in
+    // build.rs), and `parse` panics if the two disagree on the value,
+    // the spans or the error
+    for &input in util::ARITHMETIC_INPUTS {
+        let _ = expr_intern_tok::ExprParser::new().parse(1, input);
+        let _ = unit::ExprParser::new().parse(input);
+        let tokens: Vec<_> = util::tok::tokenize(input)
+            .into_iter()
+            .map(|(_, tok, _)| tok)
+            .collect();
+        let _ = sub::SParser::new().parse(tokens.clone());
+        let _ = expr::ExprParser::new().parse(1, tokens.clone());
+        let _ = expr_lalr::ExprParser::new().parse(1, tokens);
+    }
+}
+
 #[test]
 fn unit_test1() {
     assert!(unit::ExprParser::new().parse("3 + 4 * 5").is_ok());
@@ -912,6 +1086,36 @@ fn generics_issue_104_test1() {
         .is_ok());
 }
 
+#[test]
+fn generics_bounds_test1() {
+    assert_eq!(
+        generics_bounds::ItemsParser::new().parse::<i32>("1 2 3"),
+        Ok(vec![Box::new(1), Box::new(2), Box::new(3)])
+    );
+    assert_eq!(
+        generics_bounds::PairParser::new().parse::<u8>("4"),
+        Ok((4, None))
+    );
+    assert_eq!(
+        generics_bounds::CheckedParser::new().parse::<u8>("5 x"),
+        Ok(vec![Ok(5), "x".parse::<u8>()])
+    );
+}
+
+#[test]
+fn generics_bounds_ast() {
+    use generics_bounds_lib::Ast;
+
+    assert_eq!(
+        generics_bounds::TreeParser::new().parse::<u8>("(1 (x) ())"),
+        Ok(Ast::List(vec![
+            Ast::Leaf(1),
+            Ast::List(vec![Ast::Invalid("x".parse::<u8>().unwrap_err())]),
+            Ast::List(vec![]),
+        ]))
+    );
+}
+
 #[test]
 fn where_clause_with_forall_test1() {
     assert!(where_clause_with_forall::TermParser::new()
@@ -938,6 +1142,38 @@ fn test_match_section() {
         .is_err());
 }
 
+#[test]
+fn test_named_symbols() {
+    assert_eq!(
+        named_symbols::AssignParser::new().parse("x = 1").unwrap(),
+        Assign {
+            name: "x".to_string(),
+            value: 1
+        }
+    );
+}
+
+#[test]
+fn test_type_alias() {
+    assert_eq!(
+        type_alias::NamesParser::new().parse("ab c").unwrap(),
+        vec![(0, "ab".to_string(), 2), (3, "c".to_string(), 4)]
+    );
+}
+
+#[test]
+fn test_include() {
+    assert_eq!(
+        include::QueryParser::new().parse("select foo").unwrap(),
+        "SELECT foo"
+    );
+    assert!(include::QueryParser::new().parse("insert foo").is_err());
+    assert_eq!(
+        include_other::InsertParser::new().parse("INSERT foo").unwrap(),
+        "INSERT foo"
+    );
+}
+
 #[test]
 fn test_match_section_byte() {
     assert!(match_section_byte::QueryParser::new()
@@ -969,6 +1205,1250 @@ fn test_match_alternatives() {
     );
 }
 
+#[test]
+fn test_longest_match() {
+    assert_eq!(
+        longest_match::ItemsParser::new().parse("abc a1 === ="),
+        Ok(vec![
+            "word(abc)".to_string(),
+            "alnum(a1)".to_string(),
+            "==".to_string(),
+            "=".to_string(),
+            "=".to_string(),
+        ])
+    );
+}
+
+fn describe_repairs<T, L, Tok, E>(
+    result: Result<T, ParseError<L, Tok, E>>,
+    repairs: Vec<Repair<L, Tok, E>>,
+) -> (Result<T, String>, Vec<String>)
+where
+    L: fmt::Display,
+    Tok: fmt::Display,
+    E: fmt::Display,
+{
+    let edits = repairs
+        .iter()
+        .flat_map(|r| r.edits.iter().map(|e| e.to_string()))
+        .collect();
+    (result.map_err(|e| e.to_string()), edits)
+}
+
+fn parse_with_repairs(input: &str) -> (Result<Vec<String>, String>, Vec<String>) {
+    let mut repairs = vec![];
+    let result = repair::StmtsParser::new().parse_with_repairs(input, &mut repairs);
+    describe_repairs(result, repairs)
+}
+
+fn parse_with_burke_fisher(input: &str) -> (Result<Vec<String>, String>, Vec<String>) {
+    let mut repairs = vec![];
+    let result = repair_burke_fisher::StmtsParser::new().parse_with_repairs(input, &mut repairs);
+    describe_repairs(result, repairs)
+}
+
+#[test]
+fn test_repair_insert_missing_token() {
+    assert_eq!(
+        parse_with_repairs("let x = 1 let y = (2;"),
+        (
+            Ok(vec!["x = 1".to_string(), "y = (2)".to_string()]),
+            vec!["inserted `;` at 10".to_string(), "inserted `)` at 20".to_string()],
+        )
+    );
+}
+
+#[test]
+fn test_repair_insert_at_eof() {
+    assert_eq!(
+        parse_with_repairs("let x = 1"),
+        (
+            Ok(vec!["x = 1".to_string()]),
+            vec!["inserted `;` at 9".to_string()],
+        )
+    );
+}
+
+#[test]
+fn test_repair_delete_extra_token() {
+    assert_eq!(
+        parse_with_repairs("let x = 1 2;"),
+        (
+            Ok(vec!["x = 1".to_string()]),
+            vec!["deleted `2` at 10:11".to_string()],
+        )
+    );
+}
+
+#[test]
+fn test_repair_gives_up() {
+    // `Id` has no placeholder, and deleting `=` does not help
+    let (result, edits) = parse_with_repairs("let = 1;");
+    assert!(result.is_err());
+    assert!(edits.is_empty());
+
+    // without repairs, the error is reported as usual
+    assert!(repair::StmtsParser::new().parse("let x = 1 2;").is_err());
+}
+
+#[test]
+fn test_burke_fisher_multiple_edits() {
+    // a single insertion does not get past the second `let`
+    assert!(parse_with_repairs("let x = (1 let y = 2;").1.is_empty());
+    assert_eq!(
+        parse_with_burke_fisher("let x = (1 let y = 2;"),
+        (
+            Ok(vec!["x = (1)".to_string(), "y = 2".to_string()]),
+            vec!["inserted `)` at 11".to_string(), "inserted `;` at 11".to_string()],
+        )
+    );
+}
+
+#[test]
+fn test_burke_fisher_replace() {
+    assert_eq!(
+        parse_with_burke_fisher("let x ; 1;"),
+        (
+            Ok(vec!["x = 1".to_string()]),
+            vec!["replaced `;` with `=` at 6:7".to_string()],
+        )
+    );
+}
+
+#[test]
+fn test_burke_fisher_single_edit() {
+    // where one edit is enough, both strategies agree
+    for input in &["let x = 1 let y = (2;", "let x = 1", "let x = 1 2;"] {
+        assert_eq!(parse_with_burke_fisher(input), parse_with_repairs(input));
+    }
+}
+
+#[test]
+fn test_repair_cost() {
+    // inserting `+` and inserting `*` get equally far, and `*` costs more
+    let mut repairs = vec![];
+    let result = repair_cost::StmtsParser::new().parse_with_repairs("let x = 1 2;", &mut repairs);
+    assert_eq!(
+        describe_repairs(result, repairs),
+        (
+            Ok(vec!["x = 1 + 2".to_string()]),
+            vec!["inserted `+` at 10".to_string()],
+        )
+    );
+}
+
+fn parse_with_minimum_cost(input: &str) -> (Result<Vec<String>, String>, Vec<String>) {
+    let mut repairs = vec![];
+    let result = repair_minimum_cost::StmtsParser::new().parse_with_repairs(input, &mut repairs);
+    let messages = repairs.iter().map(|r| r.to_string()).collect();
+    (result.map_err(|e| e.to_string()), messages)
+}
+
+#[test]
+fn test_minimum_cost_repair() {
+    // inserting `+` gets further than deleting `2`, but costs more
+    assert_eq!(
+        parse_with_minimum_cost("let x = 1 2;"),
+        (
+            Ok(vec!["x = 1".to_string()]),
+            vec!["unexpected `2` at 10:11".to_string()],
+        )
+    );
+    assert_eq!(
+        parse_with_minimum_cost("let x = (1 let y = 2;"),
+        (
+            Ok(vec!["x = (1)".to_string(), "y = 2".to_string()]),
+            vec!["expected `)` and `;` before `let`".to_string()],
+        )
+    );
+    assert_eq!(
+        parse_with_minimum_cost("let x = 1"),
+        (
+            Ok(vec!["x = 1".to_string()]),
+            vec!["expected `;` at end of input".to_string()],
+        )
+    );
+}
+
+#[test]
+fn test_parse_prefix() {
+    let parser = prefix::ExprParser::new();
+    assert_eq!(parser.parse_prefix("1 + 2) rest"), Ok((3, 5, ") rest")));
+    assert_eq!(parser.parse_prefix("(1 + 2) + 3"), Ok((6, 11, "")));
+
+    // the rest need not be valid tokens
+    assert_eq!(parser.parse_prefix("1 + (2 + 3) ]"), Ok((6, 11, "]")));
+
+    // no backtracking: `1` would be complete, but `1 +` is not
+    assert!(parser.parse_prefix("1 + )").is_err());
+
+    // `parse` still wants the whole input
+    assert!(parser.parse("1 + 2)").is_err());
+}
+
+#[test]
+fn test_parse_prefix_tokens() {
+    let tokens = util::tok::tokenize("1 + 2, 3");
+    let (value, end, rest) = prefix_tok::ExprParser::new()
+        .parse_prefix(tokens)
+        .unwrap();
+    assert_eq!((value, end), (3, 5));
+    let rest: Vec<_> = rest.map(|t| t.unwrap().1).collect();
+    assert_eq!(rest, vec![Tok::Comma, Tok::Num(3)]);
+}
+
+#[test]
+fn test_parse_prefix_unfused_tokens() {
+    /// Tokens that may not be read again once they have ended, like
+    /// those of a lexer whose iterator is not fused.
+    #[derive(Clone)]
+    struct Unfused<I>(Option<I>);
+
+    impl<I: Iterator> Iterator for Unfused<I> {
+        type Item = I::Item;
+
+        fn next(&mut self) -> Option<I::Item> {
+            let token = self.0.as_mut().expect("read past the end").next();
+            if token.is_none() {
+                self.0 = None;
+            }
+            token
+        }
+    }
+
+    let tokens = Unfused(Some(util::tok::tokenize("1 + 2").into_iter()));
+    let (value, _, mut rest) = prefix_tok::ExprParser::new().parse_prefix(tokens).unwrap();
+    assert_eq!(value, 3);
+    assert!(rest.next().is_none());
+    assert!(rest.next().is_none());
+}
+
+#[test]
+fn test_resumable() {
+    use lalrpop_util::ParseStatus;
+
+    let mut parser = resumable::ExprParser::new().resumable(10);
+    for token in util::tok::tokenize("(1 + 2) * 3") {
+        assert_eq!(parser.feed(token), ParseStatus::Pending);
+    }
+    assert_eq!(parser.finish(), ParseStatus::Accepted(900));
+
+    // a token that cannot continue the input is reported right away
+    let mut parser = resumable::ExprParser::new().resumable(1);
+    assert_eq!(parser.feed((0, Tok::Num(1), 1)), ParseStatus::Pending);
+    assert_eq!(
+        parser.feed((2, Tok::Num(2), 3)),
+        ParseStatus::Error(ParseError::UnrecognizedToken {
+            token: (2, Tok::Num(2), 3),
+            expected: vec![
+                r#"")""#.to_string(),
+                r#""*""#.to_string(),
+                r#""+""#.to_string()
+            ],
+        })
+    );
+
+    // as is a token that is not part of the grammar at all
+    let mut parser = resumable::ExprParser::new().resumable(1);
+    assert!(matches!(
+        parser.feed((0, Tok::Comma, 1)),
+        ParseStatus::Error(ParseError::UnrecognizedToken { .. })
+    ));
+
+    // incomplete input is an error at the end
+    let mut parser = resumable::ExprParser::new().resumable(1);
+    assert_eq!(parser.feed((0, Tok::LParen, 1)), ParseStatus::Pending);
+    assert_eq!(parser.feed((1, Tok::Num(1), 2)), ParseStatus::Pending);
+    assert_eq!(
+        parser.finish(),
+        ParseStatus::Error(ParseError::UnrecognizedEOF {
+            location: 2,
+            expected: vec![r#"")""#.to_string(), r#""+""#.to_string()],
+        })
+    );
+
+    // a snapshot of the parser is not affected by what is fed after it
+    let mut parser = resumable::ExprParser::new().resumable(1);
+    for token in util::tok::tokenize("2 *") {
+        assert_eq!(parser.feed(token), ParseStatus::Pending);
+    }
+    let snapshot = parser.clone();
+    assert!(matches!(
+        parser.feed((4, Tok::RParen, 5)),
+        ParseStatus::Error(ParseError::UnrecognizedToken { .. })
+    ));
+    let mut parser = snapshot.clone();
+    assert_eq!(parser.feed((4, Tok::Num(3), 5)), ParseStatus::Pending);
+    assert_eq!(parser.finish(), ParseStatus::Accepted(6));
+    let mut parser = snapshot;
+    for token in util::tok::tokenize("(4 + 1)") {
+        assert_eq!(parser.feed(token), ParseStatus::Pending);
+    }
+    assert_eq!(parser.finish(), ParseStatus::Accepted(10));
+}
+
+#[test]
+fn test_resumable_checkpoints() {
+    use lalrpop_util::ParseStatus;
+
+    let mut parser = resumable::ExprParser::new().resumable(1);
+    for token in util::tok::tokenize("1 + 2") {
+        assert_eq!(parser.feed(token), ParseStatus::Pending);
+    }
+
+    // `+` reduces `1 + 2`, from below the checkpoint
+    let outer = parser.checkpoint();
+    assert_eq!(parser.feed((6, Tok::Plus, 7)), ParseStatus::Pending);
+    let inner = parser.checkpoint();
+    assert_eq!(parser.feed((8, Tok::Num(4), 9)), ParseStatus::Pending);
+    assert!(matches!(
+        parser.feed((10, Tok::LParen, 11)),
+        ParseStatus::Error(ParseError::UnrecognizedToken { .. })
+    ));
+    parser.rollback(inner);
+    assert_eq!(parser.feed((8, Tok::Num(5), 9)), ParseStatus::Pending);
+    parser.rollback(outer);
+    assert_eq!(parser.feed((6, Tok::Times, 7)), ParseStatus::Pending);
+    let checkpoint = parser.checkpoint();
+    assert_eq!(parser.feed((8, Tok::Num(3), 9)), ParseStatus::Pending);
+    parser.release(checkpoint);
+    assert_eq!(parser.finish(), ParseStatus::Accepted(7));
+
+    // the end of the input can be tried as well, on a clone
+    let mut parser = resumable::ExprParser::new().resumable(1);
+    assert_eq!(parser.feed((0, Tok::Num(1), 1)), ParseStatus::Pending);
+    let checkpoint = parser.checkpoint();
+    assert_eq!(parser.clone().finish(), ParseStatus::Accepted(1));
+    assert_eq!(parser.feed((2, Tok::Plus, 3)), ParseStatus::Pending);
+    parser.rollback(checkpoint);
+    assert_eq!(parser.finish(), ParseStatus::Accepted(1));
+}
+
+#[test]
+fn test_completions() {
+    let parser = completions::ExprParser::new();
+    let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        parser.completions(""),
+        Ok(names(&[r#""(""#, r###"r#"[0-9]+"#"###]))
+    );
+    assert_eq!(
+        parser.completions("1 *"),
+        Ok(names(&[r#""(""#, r###"r#"[0-9]+"#"###]))
+    );
+    assert_eq!(
+        parser.completions("(1"),
+        Ok(names(&[r#"")""#, r#""*""#, r#""+""#]))
+    );
+
+    // a complete expression may be continued, but not closed; the
+    // error for an unexpected token would also list `)` here
+    assert_eq!(parser.completions("1"), Ok(names(&[r#""*""#, r#""+""#])));
+
+    assert!(parser.completions("1 )").is_err());
+}
+
+#[test]
+fn test_parse_island() {
+    // expand `{...}` in a template, resuming after each island
+    let parser = island::ExprParser::new();
+    let template = "sum {1 + 2} and {(3 + 4) + 5}!";
+    let mut output = String::new();
+    let mut offset = 0;
+    while let Some(open) = template[offset..].find('{') {
+        output.push_str(&template[offset..offset + open]);
+        let (value, end) = parser.parse_island(template, offset + open + 1).unwrap();
+        assert_eq!(&template[end..end + 1], "}");
+        output.push_str(&value.to_string());
+        offset = end + 1;
+    }
+    output.push_str(&template[offset..]);
+    assert_eq!(output, "sum 3 and 12!");
+
+    // locations are offsets into the whole input
+    assert_eq!(
+        parser.parse_island("x = {1 + }", 5),
+        Err(ParseError::InvalidToken { location: 9 })
+    );
+}
+
+#[test]
+fn test_parse_islands_in_lexer() {
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    // the island parser decides where each `{...}` ends, and the outer
+    // lexer goes on from there
+    let parse = |input| {
+        let islands = RefCell::new(BTreeMap::new());
+        let parser = island_outer::TemplateParser::new();
+        let lexer = parser.lexer(input).islands("{", |start| {
+            let (value, end) = island::ExprParser::new().parse_island(input, start)?;
+            islands.borrow_mut().insert(end, value);
+            Ok(end)
+        });
+        parser.parse_tokens(&islands, input, lexer)
+    };
+    assert_eq!(
+        parse("sum {1 + 2} and {(3 + 4) + 5}"),
+        Ok("sum 3 and 12".to_string())
+    );
+
+    // errors from the island parser are passed on, with locations in
+    // the whole input
+    assert_eq!(
+        parse("sum {1 + }"),
+        Err(ParseError::InvalidToken { location: 9 })
+    );
+    // the island ends before the `2`, which is not a token of the
+    // outer lexer
+    assert_eq!(
+        parse("sum {1 2}"),
+        Err(ParseError::InvalidToken { location: 7 })
+    );
+}
+
+#[test]
+fn test_nonterminal_parameters() {
+    let parser = parameters::ProgramParser::new();
+    assert_eq!(
+        parser.parse("a; loop { b; break; loop { break; } } break;"),
+        Ok(vec![
+            "a;".to_string(),
+            "loop { b; break; loop { break; } }".to_string(),
+            "<break outside loop>".to_string(),
+        ])
+    );
+}
+
+#[test]
+fn test_impl_trait() {
+    let words = impl_trait::WordsParser::new().parse("a, bc, d").unwrap();
+    assert_eq!(words.collect::<Vec<_>>(), vec!["a", "bc", "d"]);
+
+    assert_eq!(
+        impl_trait::EvensParser::new().parse("1..7 10 11"),
+        Ok(vec![2, 4, 6, 10])
+    );
+}
+
+#[test]
+fn test_runtime_start() {
+    use runtime_start::{ParsedValue, StartSymbol};
+
+    let parse = |start, input| match runtime_start::parse(start, 2, input) {
+        Ok(ParsedValue::Expr(n)) => format!("{}", n),
+        Ok(ParsedValue::Names(names)) => names.join(" "),
+        Err(e) => e.to_string(),
+    };
+    assert_eq!(parse(StartSymbol::Expr, "1 + 2"), "6");
+    assert_eq!(parse(StartSymbol::Names, "a, bc"), "a bc");
+    assert!(parse(StartSymbol::Names, "1 + 2").starts_with("Unrecognized token"));
+}
+
+#[test]
+fn test_runtime_start_tok() {
+    use runtime_start_tok::{ParsedValue, StartSymbol};
+
+    let parse = |start, tokens| {
+        runtime_start_tok::parse(start, tokens).map(|value| match value {
+            ParsedValue::Expr(n) => vec![n],
+            ParsedValue::Group(nums) => nums,
+        })
+    };
+    util::test(|v| parse(StartSymbol::Expr, v), "1 + 2", vec![3]);
+    util::test(|v| parse(StartSymbol::Group, v), "(1 2)", vec![1, 2]);
+}
+
+#[test]
+fn test_parse_events() {
+    use lalrpop_util::lexer::Token as Tok;
+    use lalrpop_util::ParseEvent::*;
+
+    let events = events::ExprParser::new().parse_events("1 + (2)").unwrap();
+    assert_eq!(
+        events,
+        vec![
+            StartNonterminal("Expr"),
+            StartNonterminal("Expr"),
+            StartNonterminal("Term"),
+            Token(0, Tok(0, "1"), 1),
+            EndNonterminal(r##"Term = r#"[0-9]+"#"##),
+            EndNonterminal("Expr = Term"),
+            Token(2, Tok(3, "+"), 3),
+            StartNonterminal("Term"),
+            Token(4, Tok(1, "("), 5),
+            StartNonterminal("Expr"),
+            StartNonterminal("Term"),
+            Token(5, Tok(0, "2"), 6),
+            EndNonterminal(r##"Term = r#"[0-9]+"#"##),
+            EndNonterminal("Expr = Term"),
+            Token(6, Tok(2, ")"), 7),
+            EndNonterminal(r#"Term = "(" Expr ")""#),
+            EndNonterminal(r#"Expr = Expr "+" Term"#),
+        ]
+    );
+
+    assert!(events::ExprParser::new().parse_events("1 +").is_err());
+}
+
+#[test]
+fn test_recognize() {
+    let parser = recognize::ExprParser::new();
+    assert_eq!(parser.recognize("1 + (2 + 3)"), Ok(()));
+    assert_eq!(
+        parser.recognize("1 + (2 +"),
+        Err(ParseError::UnrecognizedEOF {
+            location: 8,
+            expected: vec![r#""(""#.to_string(), r###"r#"[0-9]+"#"###.to_string()],
+        })
+    );
+}
+
+#[test]
+fn test_cancellable() {
+    let parser = cancellable::ExprParser::new();
+    assert_eq!(
+        parser.parse_cancellable("1 + (2 + 3)", &|| false),
+        Ok(Ok(6))
+    );
+
+    // cancel once three tokens have been read
+    let calls = Cell::new(0);
+    let cancelled = || {
+        calls.set(calls.get() + 1);
+        calls.get() > 3
+    };
+    assert_eq!(
+        parser.parse_cancellable("1 + (2 + 3)", &cancelled),
+        Err(lalrpop_util::Cancelled { location: 5 })
+    );
+}
+
+#[test]
+fn test_parse_owned() {
+    let parser = owned::CallParser::new();
+
+    // the result outlives the input, which is made on the fly
+    let call = parser.parse_owned(2, format!("{}({},{},)", "max", 1, 2));
+    assert_eq!(call, Ok(("max".to_string(), vec![2, 4])));
+    assert_eq!(
+        parser.parse_owned(2, "max(1 2)".to_string()),
+        Err(ParseError::UnrecognizedToken {
+            token: (6, "2".to_string(), 7),
+            expected: vec![r#"",""#.to_string()],
+        })
+    );
+}
+
+#[test]
+fn test_intern() {
+    use lalrpop_util::intern::{Symbol, SymbolTable};
+
+    let names = SymbolTable::new();
+    let assignments = intern::AssignmentsParser::new()
+        .parse(&names, "x = y; y = x; z = x;")
+        .unwrap();
+    assert_eq!(
+        assignments,
+        vec![
+            (Symbol(0), Symbol(1)),
+            (Symbol(1), Symbol(0)),
+            (Symbol(2), Symbol(0)),
+        ]
+    );
+    assert_eq!(names.len(), 3);
+    assert_eq!(names.resolve(Symbol(2)).as_deref(), Some("z"));
+}
+
+#[test]
+fn test_epilogue() {
+    assert_eq!(epilogue::sum_or_zero("1 + 2 + 3"), 6);
+    assert_eq!(epilogue::sum_or_zero("1 +"), 0);
+    let error = epilogue::SumParser::new().parse("1 + + 2").unwrap_err();
+    assert_eq!(epilogue::describe(&error), "unexpected `+`");
+}
+
+#[test]
+fn test_backtrack() {
+    let parser = backtrack::StmtParser::new();
+    assert_eq!(parser.parse("x : loop"), Ok("loop x".to_string()));
+    assert_eq!(parser.parse("x : int"), Ok("let x: int".to_string()));
+    assert!(parser.parse("x : y").is_err());
+}
+
+#[test]
+fn test_guards() {
+    let parser = guards::StmtParser::new();
+    let types = ["size_t"];
+    assert_eq!(
+        parser.parse(&types, "size_t * x;"),
+        Ok("declare x as pointer to size_t".to_string())
+    );
+    assert_eq!(
+        parser.parse(&types, "a * b;"),
+        Ok("multiply a by b".to_string())
+    );
+}
+
+#[test]
+fn test_arena_lexer() {
+    use crate::arena_lexer_ast::*;
+    let arena = Arena::new();
+    let parser = arena_lexer::ExprParser::new();
+    let expected = Expr::Add(arena.alloc(Expr::Var("a")), arena.alloc(Expr::Var("b")));
+    assert_eq!(parser.parse(&arena, "a + (b)").unwrap(), &expected);
+}
+
+#[test]
+fn test_incremental_reparse() {
+    use lalrpop_util::incremental::{SyntaxTree, TextEdit};
+    use lalrpop_util::lexer::Token as Tok;
+    use lalrpop_util::ParseEvent;
+
+    type Events = Result<Vec<ParseEvent<usize, usize>>, ParseError<usize, usize, &'static str>>;
+
+    fn parse(nonterminal: &str, text: &str) -> Option<Events> {
+        let events = match nonterminal {
+            "Items" => incremental::ItemsParser::new().parse_events(text),
+            "Block" => incremental::BlockParser::new().parse_events(text),
+            _ => return None,
+        };
+        Some(
+            events
+                .map(|events| {
+                    events
+                        .into_iter()
+                        .map(|e| e.map_token(|Tok(i, _)| i))
+                        .collect()
+                })
+                .map_err(|e| e.map_token(|Tok(i, _)| i)),
+        )
+    }
+    fn full_parse(text: &str) -> SyntaxTree<usize> {
+        SyntaxTree::from_events(parse("Items", text).unwrap().unwrap())
+    }
+
+    let mut tree = full_parse("a { b } c");
+
+    // within the block, only the block is parsed again
+    let mut parsed = vec![];
+    let text = "a { b x } c";
+    let edit = TextEdit {
+        start: 5,
+        end: 5,
+        len: 2,
+    };
+    tree.reparse(edit, text, |nonterminal, text| {
+        parsed.push((nonterminal, text.to_string()));
+        parse(nonterminal, text)
+    })
+    .unwrap();
+    assert_eq!(parsed, vec![("Block", "{ b x }".to_string())]);
+    assert_eq!(tree, full_parse(text));
+
+    // a block that no longer parses on its own takes the whole input
+    let mut parsed = vec![];
+    let text = "a { b x } } c";
+    let edit = TextEdit {
+        start: 8,
+        end: 8,
+        len: 2,
+    };
+    tree.reparse(edit, text, |nonterminal, text| {
+        parsed.push(nonterminal);
+        parse(nonterminal, text)
+    })
+    .unwrap_err();
+    assert_eq!(parsed, vec!["Block", "Item", "Item+", "Item+", "Items"]);
+    assert_eq!(tree, full_parse("a { b x } c"));
+}
+
+#[test]
+fn test_trivia() {
+    use lalrpop_util::incremental::{SyntaxElement, SyntaxTree, WithTrivia};
+    use lalrpop_util::lexer::Token as Tok;
+
+    fn write(tree: &SyntaxTree<WithTrivia<Tok>>, text: &mut String) {
+        for child in &tree.children {
+            match child {
+                SyntaxElement::Token(_, WithTrivia { trivia, token }, _) => {
+                    text.push_str(trivia);
+                    text.push_str(token.1);
+                }
+                SyntaxElement::Tree(tree) => write(tree, text),
+            }
+        }
+    }
+
+    let input = "// items\n(a b) // end of line\n  c\n// the end\n";
+    let tree = SyntaxTree::from_events(trivia::ItemsParser::new().parse_events(input).unwrap());
+    let (tree, trailing) = tree.with_trivia(input);
+    assert_eq!(trailing, "\n// the end\n");
+
+    let mut text = String::new();
+    write(&tree, &mut text);
+    text.push_str(trailing);
+    assert_eq!(text, input);
+}
+
+#[test]
+fn test_on_skipped() {
+    let parser = trivia::ItemsParser::new();
+    let input = "// items\n(a b) // end\n";
+    let mut comments = vec![];
+    let lexer = parser.lexer(input).on_skipped(|start, text, _| {
+        if text.starts_with("//") {
+            comments.push((start, text));
+        }
+    });
+    assert!(parser.parse_tokens(input, lexer).is_ok());
+    assert_eq!(comments, vec![(0, "// items"), (15, "// end")]);
+}
+
+#[test]
+fn test_expect_debug() {
+    use lalrpop_util::expect::{assert_debug_eq, assert_debug_file};
+
+    let tree = lalrpop_util::incremental::SyntaxTree::from_events(
+        events::ExprParser::new().parse_events("1 + 2").unwrap(),
+    );
+    assert_debug_file(&tree, "src/expect/events.txt");
+
+    assert_debug_eq(
+        &tree.children[1],
+        r#"
+Token(
+    2,
+    Token(
+        3,
+        "+",
+    ),
+    3,
+)
+"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "unexpected `Debug` output")]
+fn test_expect_debug_mismatch() {
+    lalrpop_util::expect::assert_debug_eq(&Some(1), "Some(\n    2\n)");
+}
+
+#[test]
+fn test_codespan_diagnostic() {
+    use codespan_reporting::diagnostic::{Label, LabelStyle};
+
+    let text = "1 + )";
+    let error = events::ExprParser::new().parse(text).unwrap_err();
+    let diagnostic = lalrpop_util::codespan::to_diagnostic(&error, (), text);
+    assert_eq!(diagnostic.message, "unrecognized token `)`");
+    assert_eq!(
+        diagnostic.labels,
+        vec![Label::new(LabelStyle::Primary, (), 4..5).with_message("unexpected token")]
+    );
+    assert_eq!(
+        diagnostic.notes,
+        vec![r##"expected one of "(" or r#"[0-9]+"#"##.to_string()]
+    );
+
+    let text = "1 +";
+    let error = events::ExprParser::new().parse(text).unwrap_err();
+    let diagnostic = lalrpop_util::codespan::to_diagnostic(&error, (), text);
+    assert_eq!(diagnostic.message, "unexpected end of input");
+    assert_eq!(diagnostic.labels[0].range, 3..3);
+
+    // the messages of another catalog
+    use lalrpop_util::catalog::{self, Catalog, English, Message};
+    struct German;
+    impl Catalog for German {
+        fn text(&self, message: Message) -> String {
+            match message {
+                Message::UnexpectedEof => "unerwartetes Ende der Eingabe".to_string(),
+                Message::Label(catalog::Label::UnexpectedEof) => "hier".to_string(),
+                Message::Expected { names } => format!("erwartet: {}", names.join(", ")),
+                Message::FromGrammar { text: r#""(""# } => "`(`".to_string(),
+                Message::FromGrammar { .. } => "eine Zahl".to_string(),
+                message => English.text(message),
+            }
+        }
+    }
+    let diagnostic = lalrpop_util::codespan::to_diagnostic_with(&error, (), text, &German);
+    assert_eq!(diagnostic.message, "unerwartetes Ende der Eingabe");
+    assert_eq!(
+        diagnostic.labels,
+        vec![Label::new(LabelStyle::Primary, (), 3..3).with_message("hier")]
+    );
+    assert_eq!(
+        diagnostic.notes,
+        vec!["erwartet: `(`, eine Zahl".to_string()]
+    );
+}
+
+#[test]
+fn test_ariadne_report() {
+    let text = "1 + )";
+    let error = events::ExprParser::new().parse(text).unwrap_err();
+    let report = lalrpop_util::ariadne::to_report(&error, "input", text);
+    let mut out = vec![];
+    report
+        .write(("input", ariadne::Source::from(text)), &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("unrecognized token"), "{}", out);
+    assert!(out.contains("unexpected token"), "{}", out);
+    assert!(out.contains(r#"expected one of "(" or"#), "{}", out);
+}
+
+#[test]
+fn test_serde_parse_error() {
+    let error = events::ExprParser::new()
+        .parse("1 + )")
+        .unwrap_err()
+        .map_token(|token| token.1.to_string());
+    let json = serde_json::to_string(&error).unwrap();
+    assert_eq!(
+        json,
+        r##"{"UnrecognizedToken":{"token":[4,")",5],"expected":["\"(\"","r#\"[0-9]+\"#"]}}"##
+    );
+    let back: ParseError<usize, String, &str> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, error);
+}
+
+#[test]
+fn test_grammar_builder() {
+    let parser = built_grammar::ExprParser::new();
+    assert_eq!(parser.parse("1 + 2 * 3 - 4"), Ok(3));
+    assert!(parser.parse("1 + * 3").is_err());
+}
+
+#[test]
+fn test_mixed_codegen() {
+    assert_eq!(mixed_codegen::SumParser::new().parse("1 + 2 + 3"), Ok(6));
+    let product = mixed_codegen::ProductParser::new();
+    assert_eq!(product.parse("2 * 3 * 4"), Ok(24));
+    assert_eq!(product.parse_prefix("2 * 3 + 4"), Ok((6, 5, "+ 4")));
+}
+
+#[test]
+fn test_expected_names() {
+    fn expected<T: std::fmt::Debug, E: std::fmt::Debug>(
+        result: Result<T, ParseError<usize, E, &str>>,
+    ) -> Vec<String> {
+        match result {
+            Err(ParseError::UnrecognizedToken { expected, .. })
+            | Err(ParseError::UnrecognizedEOF { expected, .. }) => expected,
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    let exprs = expected_names::ExprsParser::new();
+    assert_eq!(exprs.parse("[1, -2, abs 3]"), Ok(vec![1, -2, 3]));
+    assert_eq!(
+        expected(exprs.parse("[1,")),
+        vec!["an expression", r#""]""#]
+    );
+    assert_eq!(expected(exprs.parse("[abs -1]")), vec!["a number"]);
+
+    // the recursive ascent parser names them too
+    let sum = expected_names::SumParser::new();
+    assert_eq!(sum.parse("1 + (2)"), Ok(3));
+    assert_eq!(expected(sum.parse("1 + +")), vec!["an expression"]);
+    assert_eq!(expected(sum.parse("(1")), vec![r#"")""#]);
+}
+
+#[test]
+fn test_parser_trait() {
+    fn parse_all<I, O, E>(
+        parser: &dyn lalrpop_util::Parser<I, O, E>,
+        inputs: Vec<I>,
+    ) -> Result<Vec<O>, E> {
+        inputs.into_iter().map(|input| parser.parse(input)).collect()
+    }
+
+    let sum = mixed_codegen::SumParser::new();
+    assert_eq!(parse_all(&sum, vec!["1 + 2", "3"]), Ok(vec![3, 3]));
+    let product = mixed_codegen::ProductParser::new();
+    assert_eq!(parse_all(&product, vec!["2 * 3"]), Ok(vec![6]));
+
+    let tokens: Vec<_> = util::tok::tokenize("22 - 3")
+        .into_iter()
+        .map(|(_, tok, _)| tok)
+        .collect();
+    assert_eq!(parse_all(&sub::SParser::new(), vec![tokens]), Ok(vec![19]));
+}
+
+#[test]
+fn test_parse_tokens() {
+    let sum = mixed_codegen::SumParser::new();
+    let input = "1 + 2 + 3";
+    let tokens: Vec<_> = sum.lexer(input).collect();
+    assert_eq!(tokens.len(), 5);
+    assert_eq!(sum.parse_tokens(input, tokens.clone()), Ok(6));
+    assert_eq!(sum.parse_tokens(input, tokens[..3].to_vec()), Ok(3));
+
+    // tokens from code that cannot fail need not be wrapped in `Ok`
+    let triples = tokens.into_iter().map(Result::unwrap);
+    assert_eq!(sum.parse_tokens(input, triples), Ok(6));
+
+    let product = mixed_codegen::ProductParser::new();
+    let input = "2 * 3";
+    assert_eq!(product.parse_tokens(input, product.lexer(input)), Ok(6));
+
+    let expr = expr_intern_tok::ExprParser::new();
+    let input = "22 - 3 * 2";
+    let tokens: Vec<_> = expr
+        .lexer(input)
+        .filter(|token| match token {
+            Ok((_, token, _)) => token.1 != "*" && token.1 != "2",
+            Err(_) => true,
+        })
+        .collect();
+    assert_eq!(expr.parse_tokens(1, input, tokens), Ok(19));
+}
+
+#[test]
+fn test_invalid_text() {
+    let parser = invalid_text::ItemsParser::new();
+    assert_eq!(parser.parse(r#"1 "a" /* b */ 2"#), Ok(3));
+
+    let input = r#"1 "abc"#;
+    let location = match parser.parse(input) {
+        Err(ParseError::InvalidToken { location }) => location,
+        result => panic!("unexpected result {:?}", result),
+    };
+    let text = parser.lexer(input).invalid_text(location).unwrap();
+    assert_eq!(text.location, 2);
+    assert_eq!(text.end, input.len());
+    assert_eq!(text.character, '"');
+    assert_eq!(text.started, vec!["STRING"]);
+    assert_eq!(text.expected, vec![('"', '"'), ('0', '9')]);
+
+    let input = "1 /* b";
+    let text = parser.lexer(input).invalid_text(2).unwrap();
+    assert_eq!(text.started, vec![r##"r#"/\\*[^*]*\\*/"#"##]);
+    assert!(parser.lexer(input).invalid_text(input.len()).is_none());
+}
+
+#[test]
+fn test_skip_invalid() {
+    let sum = mixed_codegen::SumParser::new();
+    let input = "1 + $2 + 3 #";
+    assert!(sum.parse(input).is_err());
+
+    let mut errors = vec![];
+    let result = sum.parse_tokens(input, sum.lexer(input).skip_invalid(&mut errors));
+    assert_eq!(result, Ok(6));
+    assert_eq!(
+        errors,
+        vec![
+            ParseError::InvalidToken { location: 4 },
+            ParseError::InvalidToken { location: 11 },
+        ]
+    );
+}
+
+#[test]
+fn test_unchecked_tables() {
+    let parser = unchecked_tables::ExprParser::new();
+    assert_eq!(parser.parse("2 * (3 + 4) + 5"), Ok(19));
+    assert!(parser.parse("2 * (3 + 4").is_err());
+    assert!(parser.parse("2 + + 3").is_err());
+}
+
+#[test]
+fn test_split_tables() {
+    let sum = split_tables::SumParser::new();
+    assert_eq!(sum.parse("1 + 2 * 3 + 4"), Ok(11));
+    assert!(sum.parse("1 + * 2").is_err());
+    assert_eq!(split_tables::ProductParser::new().parse("2 * 3"), Ok(6));
+    assert_eq!(split_tables::NumParser::new().parse("42"), Ok(42));
+}
+
+#[test]
+fn test_error_messages() {
+    let expr = error_messages::ExprParser::new();
+    assert_eq!(expr.parse_with_message("(1 + 2)"), Ok(3));
+    let message = |input| match expr.parse_with_message(input) {
+        Ok(_) => panic!("`{}` parsed", input),
+        Err((_, message)) => message,
+    };
+    assert_eq!(message("((1"), Some("missing `)`"));
+    assert_eq!(message("(1 + 2"), Some("missing `)`"));
+    assert_eq!(message("(+"), Some("expected an expression after `(`"));
+    assert_eq!(message("1 +"), None);
+}
+
+#[test]
+fn test_coverage() {
+    let expr = coverage::ExprParser::new();
+    coverage::COVERAGE.reset();
+    assert_eq!(expr.parse("1 + (2 + 3)"), Ok(6));
+    let report = coverage::COVERAGE.report();
+    assert_eq!(report.count(r#"Expr = Expr "+" Term"#), Some(2));
+    assert_eq!(report.count(r##"Term = r#"[0-9]+"#"##), Some(3));
+    assert_eq!(report.uncovered(), vec![r#"Expr = Expr "-" Term"#]);
+
+    let mut total: lalrpop_util::coverage::CoverageReport = "1 Expr = Expr \"-\" Term\n".parse().unwrap();
+    total.merge(&report);
+    assert!(total.uncovered().is_empty());
+}
+
+#[test]
+fn test_trace() {
+    use lalrpop_util::trace::{Trace, TraceEntry};
+
+    let expr = trace::ExprParser::new();
+    let mut trace = Trace::new();
+    assert_eq!(expr.parse_traced("1 + 2", &mut trace), Ok(3));
+    let entries: Vec<_> = trace.entries().collect();
+    let spans: Vec<_> = entries
+        .iter()
+        .map(|entry| match **entry {
+            TraceEntry::Shift { start, end, .. } => ("shift", start, end),
+            TraceEntry::Reduce { start, end, .. } => ("reduce", start, end),
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        vec![
+            ("shift", 0, 1),
+            ("reduce", 0, 1),
+            ("reduce", 0, 1),
+            ("shift", 2, 3),
+            ("shift", 4, 5),
+            ("reduce", 4, 5),
+            ("reduce", 0, 5),
+        ]
+    );
+    assert_eq!(trace.dropped(), 0);
+    assert_eq!(trace.to_string().lines().count(), 7);
+
+    // a bounded trace keeps the latest entries
+    let mut last = Trace::with_capacity(2);
+    assert_eq!(expr.parse_traced("1 + 2", &mut last), Ok(3));
+    assert_eq!(last.dropped(), 5);
+    assert_eq!(last.entries().collect::<Vec<_>>(), entries[5..].to_vec());
+    assert!(last.to_string().starts_with("dropped 5\n"));
+
+    // a failed parse keeps the entries up to the error
+    trace.clear();
+    assert!(expr.parse_traced("1 + )", &mut trace).is_err());
+    assert_eq!(trace.entries().count(), 4);
+}
+
+#[test]
+fn test_cfg_flags() {
+    let strict = cfg_flags::ExprParser::new();
+    assert_eq!(strict.parse("1 + 2"), Ok(3));
+    assert!(strict.parse("2 * 3").is_err());
+
+    let extended = cfg_flags_extended::ExprParser::new();
+    assert_eq!(extended.parse("1 + 2"), Ok(3));
+    assert_eq!(extended.parse("1 + 2 * 3"), Ok(9));
+}
+
+#[test]
+fn test_terminal_ids() {
+    use terminal_ids::terminal_ids::{ALL, LPAREN, NUM, PLUS};
+    assert_eq!((PLUS, NUM, LPAREN), (1, 2, 10));
+    assert_eq!(ALL, &[("\"+\"", 1), ("NUM", 2), ("\"(\"", 10)]);
+
+    // the ids of the terminals that an error expects
+    let expected = match terminal_ids::ExprParser::new().parse("1 +") {
+        Err(ParseError::UnrecognizedEOF { expected, .. }) => expected,
+        result => panic!("unexpected result {:?}", result),
+    };
+    let mut ids: Vec<_> = expected
+        .iter()
+        .filter_map(|terminal| ALL.iter().find(|&&(t, _)| t == terminal))
+        .map(|&(_, id)| id)
+        .collect();
+    ids.sort();
+    assert_eq!(ids, vec![NUM, LPAREN]);
+}
+
+#[test]
+fn test_generic_tables() {
+    let parser = generic_tables::ListParser::new();
+    let small: Vec<u8> = parser.parse("[1, 300, 3]").unwrap();
+    assert_eq!(small, vec![1, 0, 3]);
+    let large: Vec<u32> = parser.parse("[1, 300, 3]").unwrap();
+    assert_eq!(large, vec![1, 300, 3]);
+    let recovered: Vec<u32> = parser.parse("[1, [, 3]").unwrap();
+    assert_eq!(recovered, vec![1, 0, 3]);
+
+    // the simulations and the state stack are shared by both
+    let source = include_str!(concat!(env!("OUT_DIR"), "/generic_tables.rs"));
+    assert!(source.contains("fn __simulate_reduction(__reduce_index: i8)"));
+    assert!(source.contains("fn __pop_and_goto(__states: &mut alloc::vec::Vec<i8>"));
+
+    // `(<Item> ",")* = (<Item> ",")+` leaves its symbol on the stack
+    assert!(!source.contains("fn __reduce2<"));
+    assert!(!source.contains("__action7::<T>("));
+}
+
+/// A location for `copy_location`, which is `Copy` but not a type that
+/// LALRPOP knows to be.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Pos(usize);
+
+#[test]
+fn test_copy_location() {
+    let tokens: Vec<_> = util::tok::tokenize("-()-")
+        .into_iter()
+        .map(|(l, tok, r)| (Pos(l), tok, Pos(r)))
+        .collect();
+    assert_eq!(
+        copy_location::SpansParser::new().parse(tokens),
+        Ok(vec![(Pos(0), Pos(1)), (Pos(3), Pos(3)), (Pos(6), Pos(7))])
+    );
+
+    let source = include_str!(concat!(env!("OUT_DIR"), "/copy_location.rs"));
+    assert!(!source.contains(".0.clone()"));
+    assert!(!source.contains(".2.clone()"));
+    assert!(!source.contains("lookahead.clone()"));
+}
+
+#[test]
+fn test_input_lifetime() {
+    let input = String::from("\"b\", \"c\"");
+    let tokens = util::tok::tokenize(&input);
+    let strings = input_lifetime::StringsParser::new().parse("a", tokens);
+    assert_eq!(strings, Ok(vec!["a", "b", "c"]));
+}
+
+#[test]
+fn test_max_stack_depth() {
+    let parser = max_stack_depth::ParensParser::new();
+    assert_eq!(parser.parse("((((()))))").unwrap(), 5);
+
+    // the start state and 19 `(` fill the 20 states
+    let input = format!("{}{}", "(".repeat(30), ")".repeat(30));
+    assert_eq!(
+        parser.parse(&input).unwrap_err(),
+        ParseError::User {
+            error: lalrpop_util::StackOverflow { location: 19 }
+        }
+    );
+}
+
+#[test]
+fn test_inline_stacks() {
+    let parser = inline_stacks::ParensParser::new();
+    assert_eq!(parser.parse("((((()))))").unwrap(), 5);
+
+    // deeper than the inline entries, so the stacks move to the heap
+    let input = format!("{}{}", "(".repeat(100), ")".repeat(100));
+    assert_eq!(parser.parse(&input).unwrap(), 100);
+    assert!(parser.parse("(()").is_err());
+}
+
+#[test]
+fn test_c_abi() {
+    use std::ffi::CStr;
+
+    unsafe {
+        let input = "1 + (2 + 3)";
+        let handle = c_abi::c_abi_expr_parse(input.as_ptr(), input.len());
+        assert!(c_abi::c_abi_ok(handle));
+        assert!(c_abi::c_abi_error(handle).is_null());
+        assert_eq!(*(c_abi::c_abi_value(handle) as *const i32), 6);
+        assert_eq!((*handle).value::<i32>(), Some(&6));
+        c_abi::c_abi_free(handle);
+
+        // the value may borrow from the input, which the handle copies
+        let handle = c_abi::c_abi_name_parse("abc".as_ptr(), 3);
+        assert_eq!((*handle).value::<&str>(), Some(&"abc"));
+        c_abi::c_abi_free(handle);
+
+        let input = "1 + + 2";
+        let handle = c_abi::c_abi_expr_parse(input.as_ptr(), input.len());
+        assert!(!c_abi::c_abi_ok(handle));
+        assert!(c_abi::c_abi_value(handle).is_null());
+        let message = CStr::from_ptr(c_abi::c_abi_error(handle)).to_str().unwrap();
+        assert!(message.starts_with("Unrecognized token `+` found at 4:5"));
+        assert_eq!(c_abi::c_abi_error_start(handle), 4);
+        assert_eq!(c_abi::c_abi_error_end(handle), 5);
+        c_abi::c_abi_free(handle);
+
+        let handle = c_abi::c_abi_expr_parse(b"1 + \xff".as_ptr(), 5);
+        assert_eq!(
+            (*handle).error(),
+            Some(("the input is not valid UTF-8", 4, 4))
+        );
+        c_abi::c_abi_free(handle);
+    }
+}
+
+#[test]
+fn test_shared_lexer() {
+    // `Expr` and `Name` both map the tokens of the lexer to terminals
+    // with the one function next to it
+    let source = include_str!(concat!(env!("OUT_DIR"), "/c_abi.rs"));
+    assert_eq!(source.matches("fn token_to_integer(").count(), 1);
+    assert_eq!(source.matches("__intern_token::token_to_integer(token)").count(), 2);
+    assert!(!source.contains("fn __token_to_integer"));
+}
+
+#[test]
+fn test_wasm_profile() {
+    let parser = wasm_profile::ExprsParser::new();
+    assert_eq!(
+        parser.parse("1 + 2 * 3; (1 - 2) * -3; 4;").unwrap(),
+        vec![7, 3, 4]
+    );
+    assert!(parser.parse("").unwrap().is_empty());
+
+    // the errors are where they would be, but list no expected tokens
+    match parser.parse("1 + * 2;") {
+        Err(ParseError::UnrecognizedToken { token, expected }) => {
+            assert_eq!((token.0, token.2), (4, 5));
+            assert!(expected.is_empty());
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    match parser.parse("1 +") {
+        Err(ParseError::UnrecognizedEOF { location, expected }) => {
+            assert_eq!(location, 3);
+            assert!(expected.is_empty());
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    // found after the default reductions of `1`, on the same token
+    match parser.parse("1 2;") {
+        Err(ParseError::UnrecognizedToken { token, .. }) => {
+            assert_eq!((token.0, token.2), (2, 3))
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    let source = include_str!(concat!(env!("OUT_DIR"), "/wasm_profile.rs"));
+    assert!(source.contains("ACTION_CHECK"));
+    assert!(source.contains("ACTION_DEFAULT"));
+    assert!(!source.contains("panic!"));
+}
+
+#[test]
+fn test_wasm_error_messages() {
+    let expr = wasm_messages::ExprParser::new();
+    assert_eq!(expr.parse_with_message("(1 + 2)"), Ok(3));
+    let message = |input| match expr.parse_with_message(input) {
+        Ok(_) => panic!("`{}` parsed", input),
+        Err((_, message)) => message,
+    };
+    assert_eq!(message("((1"), Some("missing `)`"));
+    assert_eq!(message("(1 + 2"), Some("missing `)`"));
+    assert_eq!(message("(+"), Some("expected an expression after `(`"));
+    assert_eq!(message("1 +"), None);
+
+    // default reductions would move the errors out of the states the
+    // messages are for
+    let source = include_str!(concat!(env!("OUT_DIR"), "/wasm_messages.rs"));
+    assert!(!source.contains("ACTION_DEFAULT"));
+}
+
 #[test]
 fn test_mut_name() {
     assert_eq!(
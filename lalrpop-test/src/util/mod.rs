@@ -62,6 +62,24 @@ where
     parse_fn(tokens)
 }
 
+/// Arithmetic expressions, well-formed or not, for
+/// `test_backends_agree` to run through the arithmetic grammars.
+pub const ARITHMETIC_INPUTS: &[&str] = &[
+    "",
+    "1",
+    "22 - 3",
+    "22 - (3 + 5) * 2",
+    "((1))",
+    "1 -",
+    "- 1",
+    "1 2",
+    "(1",
+    "1)",
+    "()",
+    "2 * * 3",
+    "(((((((2 + 3) * 4) - 5) / 6) + 7)",
+];
+
 struct ExpectedDebug<'a>(&'a str);
 
 impl<'a> Debug for ExpectedDebug<'a> {
@@ -0,0 +1,59 @@
+//! Converts a `ParseError` into a report of the `ariadne` crate,
+//! enabled by the `ariadne` feature.
+//!
+//! ```ignore
+//! if let Err(error) = ExprParser::new().parse(text) {
+//!     lalrpop_util::ariadne::to_report(&error, "input", text)
+//!         .eprint(("input", Source::from(text)))?;
+//! }
+//! ```
+
+use core::fmt;
+use core::hash::Hash;
+use core::ops::Range;
+
+use ::ariadne::{Label, Report, ReportKind};
+
+use crate::catalog::{Catalog, English};
+use crate::ParseError;
+
+/// Converts `error`, found in `source`, into an error report for the
+/// source `source_id`, with a label on the offending token and a note
+/// listing the expected tokens.
+pub fn to_report<Id, T, E>(
+    error: &ParseError<usize, T, E>,
+    source_id: Id,
+    source: &str,
+) -> Report<'static, (Id, Range<usize>)>
+where
+    Id: fmt::Debug + Hash + Eq + Clone,
+    T: fmt::Display,
+    E: fmt::Display,
+{
+    to_report_with(error, source_id, source, &English)
+}
+
+/// Like `to_report`, but with the messages of `catalog`.
+pub fn to_report_with<Id, T, E>(
+    error: &ParseError<usize, T, E>,
+    source_id: Id,
+    source: &str,
+    catalog: &dyn Catalog,
+) -> Report<'static, (Id, Range<usize>)>
+where
+    Id: fmt::Debug + Hash + Eq + Clone,
+    T: fmt::Display,
+    E: fmt::Display,
+{
+    let parts = error.diagnostic_parts(source, catalog);
+    let offset = parts.label.as_ref().map_or(0, |(range, _)| range.start);
+    let mut report =
+        Report::build(ReportKind::Error, source_id.clone(), offset).with_message(parts.message);
+    if let Some((range, message)) = parts.label {
+        report = report.with_label(Label::new((source_id, range)).with_message(message));
+    }
+    if let Some(note) = parts.note {
+        report = report.with_note(note);
+    }
+    report.finish()
+}
@@ -0,0 +1,123 @@
+//! The text of the messages that `lalrpop-util` builds for the errors
+//! of generated parsers and for the edits of error repair, so that an
+//! application can word them in another language. A `Catalog` gives
+//! the text of each `Message`; one that translates only some of them
+//! can leave the rest to `English`:
+//!
+//! ```
+//! use lalrpop_util::catalog::{Catalog, English, Message};
+//!
+//! struct French;
+//!
+//! impl Catalog for French {
+//!     fn text(&self, message: Message) -> String {
+//!         match message {
+//!             Message::UnexpectedEof => "fin de fichier inattendue".to_string(),
+//!             Message::FromGrammar { text: r#""(""# } => "une parenthèse".to_string(),
+//!             message => English.text(message),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The catalog is used by the `ErrorFormatter`s of the `format` module
+//! and by the integrations with diagnostic crates. The `Display`
+//! implementations of `ParseError` and `Edit` are meant for
+//! developers, and are always in English.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+
+/// A message, or a part of one, with the texts it is made from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Message<'a> {
+    /// The character at which no token matches, which may be empty at
+    /// the end of the input.
+    InvalidToken { text: &'a str },
+
+    /// The input ended where more was expected.
+    UnexpectedEof,
+
+    /// A token that the parser did not expect.
+    UnrecognizedToken { token: &'a str },
+
+    /// A token after the end of what the parser parses.
+    ExtraToken { token: &'a str },
+
+    /// The tokens that were expected, already named; there is at least
+    /// one.
+    Expected { names: &'a [&'a str] },
+
+    /// The short label that diagnostics put on the text an error is
+    /// about.
+    Label(Label),
+
+    /// Error repair inserted a token.
+    Inserted { token: &'a str },
+
+    /// Error repair skipped a token.
+    Deleted { token: &'a str },
+
+    /// Error repair replaced a token with another.
+    Replaced { token: &'a str, with: &'a str },
+
+    /// Text taken from the grammar: the name of a terminal, the name
+    /// given to a nonterminal with `#[expected]`, or a message from a
+    /// `.messages` file. `English` keeps it as it is.
+    FromGrammar { text: &'a str },
+}
+
+/// The kinds of text that diagnostics label.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Label {
+    InvalidToken,
+    UnexpectedEof,
+    UnexpectedToken,
+    ExtraToken,
+}
+
+/// Gives the text of messages.
+pub trait Catalog {
+    fn text(&self, message: Message) -> String;
+}
+
+/// The catalog with the messages in English, which is used unless
+/// another one is given.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct English;
+
+impl Catalog for English {
+    fn text(&self, message: Message) -> String {
+        match message {
+            Message::InvalidToken { text: "" } => "invalid token".to_string(),
+            Message::InvalidToken { text } => format!("invalid token `{}`", text),
+            Message::UnexpectedEof => "unexpected end of input".to_string(),
+            Message::UnrecognizedToken { token } => format!("unrecognized token `{}`", token),
+            Message::ExtraToken { token } => format!("extra token `{}`", token),
+            Message::Expected { names } => {
+                let mut text = String::new();
+                for (i, name) in names.iter().enumerate() {
+                    let sep = match i {
+                        0 if names.len() == 1 => "expected",
+                        0 => "expected one of",
+                        _ if i < names.len() - 1 => ",",
+                        _ => " or",
+                    };
+                    text.push_str(&format!("{} {}", sep, name));
+                }
+                text
+            }
+            Message::Label(label) => match label {
+                Label::InvalidToken => "invalid token",
+                Label::UnexpectedEof => "unexpected end of input",
+                Label::UnexpectedToken => "unexpected token",
+                Label::ExtraToken => "extra token",
+            }
+            .to_string(),
+            Message::Inserted { token } => format!("inserted `{}`", token),
+            Message::Deleted { token } => format!("deleted `{}`", token),
+            Message::Replaced { token, with } => format!("replaced `{}` with `{}`", token, with),
+            Message::FromGrammar { text } => text.to_string(),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Converts a `ParseError` into a diagnostic of the `codespan-reporting`
+//! crate, enabled by the `codespan-reporting` feature.
+//!
+//! ```ignore
+//! let file = SimpleFile::new("input", text);
+//! if let Err(error) = ExprParser::new().parse(text) {
+//!     let diagnostic = lalrpop_util::codespan::to_diagnostic(&error, (), text);
+//!     let writer = StandardStream::stderr(ColorChoice::Auto);
+//!     term::emit(&mut writer.lock(), &term::Config::default(), &file, &diagnostic)?;
+//! }
+//! ```
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+use crate::catalog::{Catalog, English};
+use crate::ParseError;
+
+/// Converts `error`, found in `source`, into an error diagnostic for
+/// the file `file_id`, with a label on the offending token and a note
+/// listing the expected tokens.
+pub fn to_diagnostic<FileId, T, E>(
+    error: &ParseError<usize, T, E>,
+    file_id: FileId,
+    source: &str,
+) -> Diagnostic<FileId>
+where
+    T: fmt::Display,
+    E: fmt::Display,
+{
+    to_diagnostic_with(error, file_id, source, &English)
+}
+
+/// Like `to_diagnostic`, but with the messages of `catalog`.
+pub fn to_diagnostic_with<FileId, T, E>(
+    error: &ParseError<usize, T, E>,
+    file_id: FileId,
+    source: &str,
+    catalog: &dyn Catalog,
+) -> Diagnostic<FileId>
+where
+    T: fmt::Display,
+    E: fmt::Display,
+{
+    let parts = error.diagnostic_parts(source, catalog);
+    let labels = match parts.label {
+        Some((range, message)) => vec![Label::primary(file_id, range).with_message(message)],
+        None => vec![],
+    };
+    Diagnostic::error()
+        .with_message(parts.message)
+        .with_labels(labels)
+        .with_notes(parts.note.into_iter().collect::<Vec<_>>())
+}
@@ -0,0 +1,122 @@
+//! Grammar coverage, for parsers generated with
+//! `Configuration::emit_coverage`. Such a parser counts how often the
+//! action of each rule runs, in the `COVERAGE` static of the generated
+//! module. After parsing a test corpus, its `report` lists the rules
+//! that the corpus never exercised.
+//!
+//! Reports can be written out with `Display` and read back with
+//! `FromStr`, so that the reports of several test runs can be merged,
+//! for example with `lalrpop --coverage-report`.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The initial count of a rule, for the generated counters.
+#[doc(hidden)]
+#[allow(clippy::declare_interior_mutable_const)]
+pub const UNUSED: AtomicUsize = AtomicUsize::new(0);
+
+/// The counters of a generated parser, one per rule.
+pub struct Coverage {
+    rules: &'static [&'static str],
+    counts: &'static [AtomicUsize],
+}
+
+impl Coverage {
+    #[doc(hidden)]
+    pub const fn new(rules: &'static [&'static str], counts: &'static [AtomicUsize]) -> Self {
+        Coverage { rules, counts }
+    }
+
+    /// Counts a run of the action of rule `rule`.
+    #[doc(hidden)]
+    pub fn record(&self, rule: usize) {
+        self.counts[rule].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Sets the counts back to zero.
+    pub fn reset(&self) {
+        for count in self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// The counts so far.
+    pub fn report(&self) -> CoverageReport {
+        let mut report = CoverageReport::default();
+        for (rule, count) in self.rules.iter().zip(self.counts) {
+            *report.counts.entry(rule.to_string()).or_insert(0) += count.load(Ordering::Relaxed);
+        }
+        report
+    }
+}
+
+/// How often the action of each rule of a grammar ran, by rule, like
+/// `Expr = Expr "+" Term`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    counts: BTreeMap<String, usize>,
+}
+
+impl CoverageReport {
+    /// The number of times the action of `rule` ran, if it is one of
+    /// the rules of the report.
+    pub fn count(&self, rule: &str) -> Option<usize> {
+        self.counts.get(rule).cloned()
+    }
+
+    /// The rules with their counts, in alphabetical order.
+    pub fn rules(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.counts.iter().map(|(rule, &count)| (&rule[..], count))
+    }
+
+    /// The rules whose action never ran.
+    pub fn uncovered(&self) -> Vec<&str> {
+        self.rules()
+            .filter(|&(_, count)| count == 0)
+            .map(|(rule, _)| rule)
+            .collect()
+    }
+
+    /// Adds the counts of `other`, which may be of a parser with other
+    /// rules, to these.
+    pub fn merge(&mut self, other: &CoverageReport) {
+        for (rule, count) in &other.counts {
+            *self.counts.entry(rule.clone()).or_insert(0) += count;
+        }
+    }
+}
+
+/// Writes one line per rule, with the count before the rule.
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (rule, count) in self.rules() {
+            writeln!(f, "{} {}", count, rule)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a report written with `Display`.
+impl FromStr for CoverageReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut report = CoverageReport::default();
+        for line in s.lines().map(str::trim_end).filter(|line| !line.is_empty()) {
+            let mut parts = line.splitn(2, ' ');
+            let count = parts.next().and_then(|count| count.parse::<usize>().ok());
+            match (count, parts.next()) {
+                (Some(count), Some(rule)) => {
+                    *report.counts.entry(rule.to_string()).or_insert(0) += count;
+                }
+                _ => return Err(alloc::format!("expected a count and a rule: `{}`", line)),
+            }
+        }
+        Ok(report)
+    }
+}
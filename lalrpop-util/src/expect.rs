@@ -0,0 +1,93 @@
+//! Snapshot tests for parsers: compare the `{:#?}` of a parse result
+//! with the expected text, given inline or in an expect-file, and print
+//! a line diff when they differ.
+//!
+//! Expect-files are updated ("blessed") instead of compared when the
+//! `LALRPOP_BLESS` environment variable is set, as in
+//! `LALRPOP_BLESS=1 cargo test`; review the changes before committing
+//! them.
+
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
+
+/// The environment variable that makes `assert_debug_file` write the
+/// expect-file instead of comparing with it.
+pub const BLESS_VAR: &str = "LALRPOP_BLESS";
+
+/// Asserts that the `{:#?}` of `actual` is `expected`, printing a line
+/// diff if it is not. Leading and trailing whitespace of `expected` is
+/// ignored, as are trailing commas, which older versions of Rust left
+/// out of multi-line `Debug` output.
+pub fn assert_debug_eq<D: Debug>(actual: &D, expected: &str) {
+    let actual = format!("{:#?}", actual);
+    if let Some(diff) = diff_lines(&actual, expected) {
+        panic!("unexpected `Debug` output:\n{}", diff);
+    }
+}
+
+/// Like `assert_debug_eq`, with the expected text in the file at `path`,
+/// relative to the current directory (for `cargo test`, the root of the
+/// package).
+///
+/// With `LALRPOP_BLESS` set, writes the `{:#?}` of `actual` to the file
+/// instead, creating it if need be.
+pub fn assert_debug_file<D: Debug, P: AsRef<Path>>(actual: &D, path: P) {
+    let path = path.as_ref();
+    let actual = format!("{:#?}\n", actual);
+    if env::var_os(BLESS_VAR).is_some() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .unwrap_or_else(|e| panic!("cannot create `{}`: {}", parent.display(), e));
+        }
+        fs::write(path, actual)
+            .unwrap_or_else(|e| panic!("cannot write `{}`: {}", path.display(), e));
+        return;
+    }
+
+    let expected = fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "cannot read `{}`: {}; to create it, run the test with `{}=1`",
+            path.display(),
+            e,
+            BLESS_VAR
+        )
+    });
+    if let Some(diff) = diff_lines(&actual, &expected) {
+        panic!(
+            "`Debug` output differs from `{}`:\n{}\
+             to update the file, run the test with `{}=1`",
+            path.display(),
+            diff,
+            BLESS_VAR
+        );
+    }
+}
+
+/// Returns the line diff of `actual` and `expected`, with `-` lines
+/// expected but missing and `+` lines unexpected, or `None` if they are
+/// the same.
+fn diff_lines(actual: &str, expected: &str) -> Option<String> {
+    let actual = normalize(actual);
+    let expected = normalize(expected);
+    if actual == expected {
+        return None;
+    }
+
+    let mut out = String::new();
+    for line in diff::lines(&actual, &expected) {
+        match line {
+            diff::Result::Right(r) => out.push_str(&format!("- {}\n", r)),
+            diff::Result::Left(l) => out.push_str(&format!("+ {}\n", l)),
+            diff::Result::Both(l, _) => out.push_str(&format!("  {}\n", l)),
+        }
+    }
+    Some(out)
+}
+
+// Ignore trailing commas in multiline Debug representation, as well as
+// line endings, which git may have changed in an expect-file.
+fn normalize(s: &str) -> String {
+    s.trim().replace("\r\n", "\n").replace(",\n", "\n")
+}
@@ -0,0 +1,182 @@
+//! Support for the `extern "C"` functions of parsers generated with
+//! `Configuration::emit_c_abi`, which let C, C++ or Python parse with
+//! them. For a grammar `calc.lalrpop` with a public nonterminal `Expr`,
+//! the generated module has:
+//!
+//! ```c
+//! ParseHandle *calc_expr_parse(const uint8_t *input, size_t len);
+//! bool calc_ok(const ParseHandle *handle);
+//! const void *calc_value(const ParseHandle *handle);
+//! const char *calc_error(const ParseHandle *handle);
+//! size_t calc_error_start(const ParseHandle *handle);
+//! size_t calc_error_end(const ParseHandle *handle);
+//! void calc_free(ParseHandle *handle);
+//! ```
+//!
+//! The handle owns a copy of the input and the parsed value, which may
+//! borrow from it, until it is freed. The value is opaque to C; Rust
+//! functions of your own can take the handle and read it with
+//! `ParseHandle::value`.
+
+use alloc::boxed::Box;
+use alloc::string::ToString;
+use core::any::Any;
+use core::fmt;
+use core::ptr;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+
+use crate::ParseError;
+
+/// The outcome of a parse, behind the pointer the generated functions
+/// return.
+pub struct ParseHandle {
+    // declared before `input`, which it may borrow from, so that it is
+    // dropped first
+    value: Option<Box<dyn Any>>,
+    error: Option<(CString, usize, usize)>,
+    #[allow(dead_code)]
+    input: Box<str>,
+}
+
+impl ParseHandle {
+    /// The parsed value, if the parse succeeded and it is a `T`. The
+    /// value of a nonterminal whose type borrows from the input, like
+    /// `&'input str`, is a `T<'static>`.
+    pub fn value<T: Any>(&self) -> Option<&T> {
+        self.value.as_ref()?.downcast_ref()
+    }
+
+    /// The message of the error, with the byte offsets of the input it
+    /// is about, if the parse failed. User errors are about no part of
+    /// the input, and have zero offsets.
+    pub fn error(&self) -> Option<(&str, usize, usize)> {
+        let (message, start, end) = self.error.as_ref()?;
+        Some((message.to_str().unwrap_or(""), *start, *end))
+    }
+}
+
+/// Parses the `len` bytes at `input` with `parse`, which gets a copy of
+/// them as a string. Input that is not UTF-8 is an error at the first
+/// invalid byte.
+///
+/// # Safety
+///
+/// `input` must point to `len` readable bytes, or be null if `len` is 0.
+#[doc(hidden)]
+pub unsafe fn parse<T, Tok, E>(
+    input: *const u8,
+    len: usize,
+    parse: impl FnOnce(&'static str) -> Result<T, ParseError<usize, Tok, E>>,
+) -> *mut ParseHandle
+where
+    T: Any,
+    Tok: fmt::Display,
+    E: fmt::Display,
+{
+    let bytes: &[u8] = if len == 0 {
+        &[]
+    } else {
+        core::slice::from_raw_parts(input, len)
+    };
+    let input: Box<str> = match core::str::from_utf8(bytes) {
+        Ok(input) => input.into(),
+        Err(error) => {
+            let offset = error.valid_up_to();
+            return Box::into_raw(Box::new(ParseHandle {
+                value: None,
+                error: Some((message("the input is not valid UTF-8"), offset, offset)),
+                input: "".into(),
+            }));
+        }
+    };
+
+    // the handle keeps `input` for as long as the value
+    let text: &'static str = &*(&*input as *const str);
+    let (value, error) = match parse(text) {
+        Ok(value) => (Some(Box::new(value) as Box<dyn Any>), None),
+        Err(error) => {
+            let (start, end) = match error {
+                ParseError::InvalidToken { location } => {
+                    let range = crate::invalid_char(text, location);
+                    (range.start, range.end)
+                }
+                ParseError::UnrecognizedEOF { location, .. } => (location, location),
+                ParseError::UnrecognizedToken {
+                    token: (start, _, end),
+                    ..
+                }
+                | ParseError::ExtraToken {
+                    token: (start, _, end),
+                } => (start, end),
+                ParseError::User { .. } => (0, 0),
+            };
+            (None, Some((message(&error), start, end)))
+        }
+    };
+    Box::into_raw(Box::new(ParseHandle {
+        value,
+        error,
+        input,
+    }))
+}
+
+fn message<T: fmt::Display + ?Sized>(message: &T) -> CString {
+    CString::new(message.to_string().replace('\0', "\\0")).unwrap()
+}
+
+/// # Safety
+///
+/// `handle` must come from `parse` and not have been freed.
+#[doc(hidden)]
+pub unsafe fn ok(handle: *const ParseHandle) -> bool {
+    (*handle).value.is_some()
+}
+
+/// # Safety
+///
+/// `handle` must come from `parse` and not have been freed.
+#[doc(hidden)]
+pub unsafe fn value(handle: *const ParseHandle) -> *const c_void {
+    match (*handle).value {
+        Some(ref value) => &**value as *const dyn Any as *const c_void,
+        None => ptr::null(),
+    }
+}
+
+/// # Safety
+///
+/// `handle` must come from `parse` and not have been freed.
+#[doc(hidden)]
+pub unsafe fn error(handle: *const ParseHandle) -> *const c_char {
+    match (*handle).error {
+        Some((ref message, _, _)) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// # Safety
+///
+/// `handle` must come from `parse` and not have been freed.
+#[doc(hidden)]
+pub unsafe fn error_start(handle: *const ParseHandle) -> usize {
+    (*handle).error.as_ref().map_or(0, |&(_, start, _)| start)
+}
+
+/// # Safety
+///
+/// `handle` must come from `parse` and not have been freed.
+#[doc(hidden)]
+pub unsafe fn error_end(handle: *const ParseHandle) -> usize {
+    (*handle).error.as_ref().map_or(0, |&(_, _, end)| end)
+}
+
+/// # Safety
+///
+/// `handle` must come from `parse` and not have been freed, or be null.
+#[doc(hidden)]
+pub unsafe fn free(handle: *mut ParseHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
@@ -0,0 +1,159 @@
+//! Formats a `ParseError` as a message for the user, with the line and
+//! column it is at and the tokens that were expected, under names that
+//! the application picks for them, and the edits that error repair
+//! made. The words come from a message catalog, which `catalog` can
+//! replace to translate them; for other changes to the wording of a
+//! part, implement `ErrorFormatter` and override the method for that
+//! part. The rest, such as working out the line and column, still
+//! comes from the default methods.
+//!
+//! ```ignore
+//! const NAMES: &[(&str, &str)] = &[(r#""(""#, "`(`"), (r#"r#"[0-9]+"#"#, "a number")];
+//! if let Err(error) = ExprParser::new().parse(text) {
+//!     eprintln!("{}", DefaultFormatter.format(&error, text, NAMES));
+//! }
+//! ```
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::catalog::{Catalog, English, Message};
+use crate::{Edit, ParseError};
+
+/// A point in the source text: its byte offset, and the line and
+/// column it is on. Lines and columns count from 1, and columns count
+/// characters rather than bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of `offset` in `source`. An offset past the end of
+    /// `source` is taken to be its end, and one inside a character the
+    /// start of that character.
+    pub fn new(source: &str, offset: usize) -> Self {
+        let mut offset = offset.min(source.len());
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Position {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// Turns parse errors into text. Every method has a default, so that
+/// `DefaultFormatter` implements none of them, and other formatters
+/// only those whose wording they change.
+pub trait ErrorFormatter<T, E>
+where
+    T: fmt::Display,
+    E: fmt::Display,
+{
+    /// Formats `error`, found in `source`, as `position: message`,
+    /// followed by the expected tokens, if the error lists any. These
+    /// are given the names that `names` maps them to; the others, the
+    /// text that the catalog gives for their names in the grammar.
+    fn format(
+        &self,
+        error: &ParseError<usize, T, E>,
+        source: &str,
+        names: &[(&str, &str)],
+    ) -> String {
+        let mut text = self.message(error, source);
+        if let Some(&location) = error.location() {
+            let position = self.position(Position::new(source, location));
+            text = format!("{}: {}", position, text);
+        }
+        let expected = match *error {
+            ParseError::UnrecognizedEOF { ref expected, .. }
+            | ParseError::UnrecognizedToken { ref expected, .. } => &expected[..],
+            _ => &[],
+        };
+        let mut named: Vec<String> = Vec::new();
+        for token in expected {
+            let name = match names.iter().find(|&&(from, _)| from == token) {
+                Some(&(_, to)) => to.to_string(),
+                None => self.catalog().text(Message::FromGrammar { text: token }),
+            };
+            if !named.contains(&name) {
+                named.push(name);
+            }
+        }
+        if !named.is_empty() {
+            let named: Vec<&str> = named.iter().map(|name| &name[..]).collect();
+            text = format!("{}; {}", text, self.expected(&named));
+        }
+        text
+    }
+
+    /// Formats `edit`, made by error repair in `source`, as `position:
+    /// edit`, such as "2:8: inserted `;`".
+    fn edit(&self, edit: &Edit<usize, T>, source: &str) -> String {
+        let (location, message) = match *edit {
+            Edit::Insert((location, ref token, _)) => (
+                location,
+                Message::Inserted {
+                    token: &token.to_string(),
+                },
+            ),
+            Edit::Delete((location, ref token, _)) => (
+                location,
+                Message::Deleted {
+                    token: &token.to_string(),
+                },
+            ),
+            Edit::Replace((location, ref token, _), ref with) => (
+                location,
+                Message::Replaced {
+                    token: &token.to_string(),
+                    with: &with.to_string(),
+                },
+            ),
+        };
+        let position = self.position(Position::new(source, location));
+        format!("{}: {}", position, self.catalog().text(message))
+    }
+
+    /// The catalog that the default methods take their words from.
+    fn catalog(&self) -> &dyn Catalog {
+        &English
+    }
+
+    /// What went wrong, without the position or the expected tokens,
+    /// such as "unrecognized token `+`".
+    fn message(&self, error: &ParseError<usize, T, E>, source: &str) -> String {
+        error.describe(source, self.catalog())
+    }
+
+    /// Where the error is, as `line:column`.
+    fn position(&self, position: Position) -> String {
+        format!("{}:{}", position.line, position.column)
+    }
+
+    /// The tokens that were expected, already named, such as "expected
+    /// one of `(` or a number". There is at least one.
+    fn expected(&self, expected: &[&str]) -> String {
+        self.catalog().text(Message::Expected { names: expected })
+    }
+}
+
+/// The formatter with the default wording, in English.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DefaultFormatter;
+
+impl<T, E> ErrorFormatter<T, E> for DefaultFormatter
+where
+    T: fmt::Display,
+    E: fmt::Display,
+{
+}
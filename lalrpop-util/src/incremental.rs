@@ -0,0 +1,267 @@
+//! Concrete syntax trees built from the events of `parse_events`, for
+//! grammars that mark their public nonterminals with `#[events]`,
+//! incremental reparsing of such trees after an edit of the input, and
+//! the trivia, like whitespace and comments, between their tokens.
+//!
+//! To reparse after an edit, `SyntaxTree::reparse` looks for the
+//! smallest nonterminal that encloses the edit and that can be parsed
+//! on its own, usually because it is delimited like a `{ .. }` block.
+//! Only the text of that nonterminal is lexed and parsed again; the
+//! rest of the tree is kept, with its locations moved past the edit.
+
+use alloc::vec::Vec;
+
+use crate::ParseEvent;
+
+/// A nonterminal of the input, along with its children in input order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxTree<T> {
+    /// The name of the nonterminal in the grammar.
+    pub nonterminal: &'static str,
+
+    /// The production the nonterminal was reduced with, like
+    /// `Expr = Expr "+" Term`.
+    pub production: &'static str,
+
+    /// The start of the first token of the nonterminal. A nonterminal
+    /// that matched no tokens starts and ends at the end of the token
+    /// before it.
+    pub start: usize,
+
+    /// The end of the last token of the nonterminal.
+    pub end: usize,
+
+    pub children: Vec<SyntaxElement<T>>,
+}
+
+/// A child of a `SyntaxTree`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyntaxElement<T> {
+    Token(usize, T, usize),
+    Tree(SyntaxTree<T>),
+}
+
+/// A token of a tree along with its trivia: the text before it that is
+/// not part of any token, like the whitespace and comments that
+/// LALRPOP's lexer skips.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WithTrivia<'input, T> {
+    pub trivia: &'input str,
+    pub token: T,
+}
+
+/// An edit of the input: the text in `start..end` was replaced by `len`
+/// bytes of new text.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub len: usize,
+}
+
+impl<T> SyntaxTree<T> {
+    /// Builds the tree of the events returned by `parse_events`.
+    ///
+    /// Panics if the events do not describe a single nonterminal.
+    pub fn from_events<I>(events: I) -> Self
+    where
+        I: IntoIterator<Item = ParseEvent<usize, T>>,
+    {
+        let mut stack: Vec<(&'static str, Vec<SyntaxElement<T>>)> = Vec::new();
+        let mut root = None;
+        let mut location = 0;
+        for event in events {
+            match event {
+                ParseEvent::StartNonterminal(nonterminal) => {
+                    assert!(root.is_none(), "parse events after the root nonterminal");
+                    stack.push((nonterminal, Vec::new()));
+                }
+                ParseEvent::Token(start, token, end) => {
+                    let children = &mut stack.last_mut().expect("token outside a nonterminal").1;
+                    children.push(SyntaxElement::Token(start, token, end));
+                    location = end;
+                }
+                ParseEvent::EndNonterminal(production) => {
+                    let (nonterminal, children) = stack.pop().expect("unbalanced parse events");
+                    let start = children.first().map_or(location, SyntaxElement::start);
+                    let end = children.last().map_or(location, SyntaxElement::end);
+                    let tree = SyntaxTree {
+                        nonterminal,
+                        production,
+                        start,
+                        end,
+                        children,
+                    };
+                    match stack.last_mut() {
+                        Some((_, children)) => children.push(SyntaxElement::Tree(tree)),
+                        None => root = Some(tree),
+                    }
+                }
+            }
+        }
+        assert!(stack.is_empty(), "unbalanced parse events");
+        root.expect("no parse events")
+    }
+
+    /// Updates the tree after `edit`, where `text` is the whole input
+    /// after the edit.
+    ///
+    /// `parse` is called with the name of a nonterminal and the new
+    /// text of one of its occurrences. If that nonterminal can be
+    /// parsed on its own, it returns the result of its `parse_events`,
+    /// and `None` otherwise. It is called for the nonterminals that
+    /// enclose the edit, innermost first, until one parses; for the
+    /// root, it is called with all of `text`, and must not return
+    /// `None`. Only the root reports a syntax error: if some other
+    /// nonterminal does not parse, the edit may have changed the
+    /// nonterminals around it, so the next one out is tried instead.
+    ///
+    /// Parsing a nonterminal on its own must give the same tree as
+    /// parsing it in its surroundings, so offer only those that are
+    /// delimited, like a `{ .. }` block. On error, the tree is left
+    /// unchanged.
+    pub fn reparse<F, E>(&mut self, edit: TextEdit, text: &str, mut parse: F) -> Result<(), E>
+    where
+        F: FnMut(&'static str, &str) -> Option<Result<Vec<ParseEvent<usize, T>>, E>>,
+    {
+        // the nonterminals strictly enclosing the edit, so that their
+        // first and last tokens are where they were
+        let mut path = Vec::new();
+        let mut tree = &*self;
+        while let Some((index, child)) =
+            tree.children
+                .iter()
+                .enumerate()
+                .find_map(|(i, child)| match *child {
+                    SyntaxElement::Tree(ref child)
+                        if child.start < edit.start && edit.end < child.end =>
+                    {
+                        Some((i, child))
+                    }
+                    _ => None,
+                })
+        {
+            path.push(index);
+            tree = child;
+        }
+
+        let delta = edit.len as isize - (edit.end - edit.start) as isize;
+        for depth in (1..=path.len()).rev() {
+            let tree = self.descendant(&path[..depth]);
+            let start = tree.start;
+            let end = (tree.end as isize + delta) as usize;
+            if let Some(Ok(events)) = parse(tree.nonterminal, &text[start..end]) {
+                let mut new_tree = SyntaxTree::from_events(events);
+                new_tree.shift(start as isize);
+                self.splice(&path[..depth], new_tree, delta);
+                return Ok(());
+            }
+        }
+
+        let events = parse(self.nonterminal, text)
+            .expect("the root nonterminal must be parsed by `reparse`")?;
+        *self = SyntaxTree::from_events(events);
+        Ok(())
+    }
+
+    /// Attaches to each token the trivia before it, taken from `input`,
+    /// the text the tree was parsed from, and returns the tree along
+    /// with the trivia after the last token. Writing out the trivia and
+    /// the text of each token in turn, then the trivia returned, gives
+    /// back `input`, so that a formatter can keep the comments. Trivia
+    /// that belongs with the token before it, like a comment at the end
+    /// of a line, can be split off at the first newline.
+    ///
+    /// Reparsing gives tokens without trivia, so attach it to a copy of
+    /// the tree, or again after each edit.
+    pub fn with_trivia(self, input: &str) -> (SyntaxTree<WithTrivia<'_, T>>, &str) {
+        let mut location = 0;
+        let tree = self.attach_trivia(input, &mut location);
+        (tree, &input[location..])
+    }
+
+    fn attach_trivia<'input>(
+        self,
+        input: &'input str,
+        location: &mut usize,
+    ) -> SyntaxTree<WithTrivia<'input, T>> {
+        let children = self
+            .children
+            .into_iter()
+            .map(|child| match child {
+                SyntaxElement::Token(start, token, end) => {
+                    let trivia = &input[*location..start];
+                    *location = end;
+                    SyntaxElement::Token(start, WithTrivia { trivia, token }, end)
+                }
+                SyntaxElement::Tree(tree) => {
+                    SyntaxElement::Tree(tree.attach_trivia(input, location))
+                }
+            })
+            .collect();
+        SyntaxTree {
+            nonterminal: self.nonterminal,
+            production: self.production,
+            start: self.start,
+            end: self.end,
+            children,
+        }
+    }
+
+    fn descendant(&self, path: &[usize]) -> &SyntaxTree<T> {
+        path.iter()
+            .fold(self, |tree, &index| match tree.children[index] {
+                SyntaxElement::Tree(ref child) => child,
+                SyntaxElement::Token(..) => unreachable!(),
+            })
+    }
+
+    /// Replaces the descendant at `path`, which encloses the edit, by
+    /// `new_tree`, moving everything after it by `delta`.
+    fn splice(&mut self, path: &[usize], new_tree: SyntaxTree<T>, delta: isize) {
+        let (&index, rest) = path.split_first().unwrap();
+        self.end = (self.end as isize + delta) as usize;
+        for child in &mut self.children[index + 1..] {
+            child.shift(delta);
+        }
+        match self.children[index] {
+            SyntaxElement::Tree(ref mut child) if rest.is_empty() => *child = new_tree,
+            SyntaxElement::Tree(ref mut child) => child.splice(rest, new_tree, delta),
+            SyntaxElement::Token(..) => unreachable!(),
+        }
+    }
+
+    fn shift(&mut self, delta: isize) {
+        self.start = (self.start as isize + delta) as usize;
+        self.end = (self.end as isize + delta) as usize;
+        for child in &mut self.children {
+            child.shift(delta);
+        }
+    }
+}
+
+impl<T> SyntaxElement<T> {
+    pub fn start(&self) -> usize {
+        match *self {
+            SyntaxElement::Token(start, _, _) => start,
+            SyntaxElement::Tree(ref tree) => tree.start,
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        match *self {
+            SyntaxElement::Token(_, _, end) => end,
+            SyntaxElement::Tree(ref tree) => tree.end,
+        }
+    }
+
+    fn shift(&mut self, delta: isize) {
+        match *self {
+            SyntaxElement::Token(ref mut start, _, ref mut end) => {
+                *start = (*start as isize + delta) as usize;
+                *end = (*end as isize + delta) as usize;
+            }
+            SyntaxElement::Tree(ref mut tree) => tree.shift(delta),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Interning of the text of tokens. A terminal of LALRPOP's own lexer
+//! that is marked `#[intern(with = "names")]` in the `match` block is
+//! passed to `names`, a parameter of the grammar, as it is shifted, and
+//! actions get the `Symbol` it returns instead of the text. Symbols are
+//! small and compare in constant time, and the text of each name is
+//! kept only once, however often it occurs in the input.
+//!
+//! ```ignore
+//! let names = SymbolTable::new();
+//! let program = ProgramParser::new().parse(&names, text)?;
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// The id of an interned text.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(pub u32);
+
+/// Gives the symbols for the texts of interned tokens. The parser only
+/// gets a shared reference, so implementations need interior
+/// mutability, as `SymbolTable` does.
+pub trait Interner {
+    /// The symbol for `text`, which is the same for equal texts.
+    fn intern(&self, text: &str) -> Symbol;
+}
+
+/// An `Interner` that numbers texts in the order they are first seen,
+/// from 0.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    table: RefCell<Table>,
+}
+
+#[derive(Debug, Default)]
+struct Table {
+    symbols: BTreeMap<Rc<str>, Symbol>,
+    texts: Vec<Rc<str>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    /// The symbol for `text`, if it has been interned.
+    pub fn get(&self, text: &str) -> Option<Symbol> {
+        self.table.borrow().symbols.get(text).cloned()
+    }
+
+    /// The text of `symbol`, if it comes from this table.
+    pub fn resolve(&self, symbol: Symbol) -> Option<Rc<str>> {
+        self.table.borrow().texts.get(symbol.0 as usize).cloned()
+    }
+
+    /// The number of texts interned.
+    pub fn len(&self) -> usize {
+        self.table.borrow().texts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Interner for SymbolTable {
+    fn intern(&self, text: &str) -> Symbol {
+        if let Some(symbol) = self.get(text) {
+            return symbol;
+        }
+        let mut table = self.table.borrow_mut();
+        let symbol = Symbol(table.texts.len() as u32);
+        let text: Rc<str> = Rc::from(text);
+        table.texts.push(text.clone());
+        table.symbols.insert(text, symbol);
+        symbol
+    }
+}
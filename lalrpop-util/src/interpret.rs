@@ -0,0 +1,320 @@
+//! Parsing against LR(1) tables built at runtime, with
+//! `lalrpop::build_tables`, rather than with a generated parser: for
+//! tools that load grammars supplied by their users.
+//!
+//! The action code of the grammar is not run; instead, parsing gives the
+//! concrete syntax tree of the input, a `Tree`, whose nodes name the
+//! production they were reduced with. Error recovery (`!`) is not used.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::Debug;
+use core::marker::PhantomData;
+
+#[cfg(feature = "lexer")]
+use crate::lexer::{MatcherBuilder, Token};
+use crate::state_machine::{
+    self, ParseResult, Parser, ParserAction, ParserDefinition, SimulatedReduce, SymbolTriple,
+};
+use crate::ParseError;
+
+/// The tables of one public nonterminal of a grammar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseTables {
+    /// The name of each terminal, as in the `expected` list of a
+    /// `ParseError`, like `"+"` (with the quotes) or `Num`.
+    pub terminals: Vec<String>,
+
+    /// The name of each nonterminal, including those LALRPOP introduces
+    /// for macros and the like, like `Comma<Expr>` or `Expr?`.
+    pub nonterminals: Vec<String>,
+
+    pub productions: Vec<Production>,
+
+    /// The production of the artificial start nonterminal; reducing it
+    /// accepts the input.
+    pub start_production: usize,
+
+    /// The action of each state on each terminal. Parsing starts in
+    /// state 0.
+    pub actions: Vec<Vec<Action>>,
+
+    /// The action of each state at the end of the input.
+    pub eof_actions: Vec<Action>,
+
+    /// The state each state goes to after reducing each nonterminal, if
+    /// it can be reduced there at all.
+    pub gotos: Vec<Vec<Option<usize>>>,
+
+    /// If the grammar has no `extern` token declaration, the
+    /// expressions of the lexer that LALRPOP would generate, in the
+    /// order `lexer::MatcherBuilder` takes them, with the terminal each
+    /// one lexes, or `None` for text to skip.
+    pub lexer: Vec<(String, Option<usize>)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Production {
+    /// The index of the nonterminal in `ParseTables::nonterminals`.
+    pub nonterminal: usize,
+
+    /// The number of symbols of the production.
+    pub len: usize,
+
+    /// The production, like `Expr = Expr "+" Term`.
+    pub text: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    Shift(usize),
+    Reduce(usize),
+    Error,
+}
+
+/// The concrete syntax tree of the input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Tree<L, T> {
+    Token(L, T, L),
+    Node {
+        /// The index of the production in `ParseTables::productions`.
+        production: usize,
+        start: L,
+        end: L,
+        children: Vec<Tree<L, T>>,
+    },
+}
+
+impl ParseTables {
+    /// Returns the index of the terminal named `name`, to map tokens to
+    /// terminals for `parse`.
+    pub fn terminal_index(&self, name: &str) -> Option<usize> {
+        self.terminals.iter().position(|terminal| terminal == name)
+    }
+
+    /// Parses `tokens`, where `terminal` gives the index of the terminal
+    /// of each token, or `None` if it is not one of the grammar's.
+    pub fn parse<L, T, E, I, F>(
+        &self,
+        tokens: I,
+        terminal: F,
+    ) -> Result<Tree<L, T>, ParseError<L, T, E>>
+    where
+        L: Clone + Debug + Default,
+        T: Clone + Debug,
+        I: IntoIterator<Item = Result<(L, T, L), ParseError<L, T, E>>>,
+        F: Fn(&T) -> Option<usize>,
+    {
+        let definition = Interpreter {
+            tables: self,
+            terminal,
+            _marker: PhantomData,
+        };
+        Parser::drive(definition, tokens.into_iter())
+    }
+
+    /// Lexes `input` with the lexer LALRPOP would generate for the
+    /// grammar, and parses it. The lexer is built anew for each call.
+    #[cfg(feature = "lexer")]
+    pub fn parse_str<'input>(
+        &self,
+        input: &'input str,
+    ) -> Result<Tree<usize, Token<'input>>, ParseError<usize, Token<'input>, &'static str>> {
+        let builder = MatcherBuilder::new(
+            self.lexer
+                .iter()
+                .map(|(regex, terminal)| (regex, terminal.is_none())),
+        )
+        .expect("invalid regex in the lexer of the tables");
+        self.parse(builder.matcher(input), |token: &Token| {
+            self.lexer[token.0].1
+        })
+    }
+}
+
+impl<L, T> Tree<L, T> {
+    pub fn start(&self) -> &L {
+        match *self {
+            Tree::Token(ref start, _, _) | Tree::Node { ref start, .. } => start,
+        }
+    }
+
+    pub fn end(&self) -> &L {
+        match *self {
+            Tree::Token(_, _, ref end) | Tree::Node { ref end, .. } => end,
+        }
+    }
+}
+
+struct Interpreter<'t, L, T, E, F> {
+    tables: &'t ParseTables,
+    terminal: F,
+    _marker: PhantomData<fn(L, T) -> E>,
+}
+
+/// A symbol on the stack of the interpreter. Shifted tokens are turned
+/// into trees once they are reduced, since only then are their
+/// locations known.
+enum InterpreterSymbol<L, T> {
+    Token(T),
+    Tree(Tree<L, T>),
+}
+
+impl<'t, L, T, E, F> ParserAction<Interpreter<'t, L, T, E, F>> for Action
+where
+    L: Clone + Debug + Default,
+    T: Clone + Debug,
+    F: Fn(&T) -> Option<usize>,
+{
+    fn as_shift(self) -> Option<usize> {
+        match self {
+            Action::Shift(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    fn as_reduce(self) -> Option<usize> {
+        match self {
+            Action::Reduce(production) => Some(production),
+            _ => None,
+        }
+    }
+
+    fn is_shift(self) -> bool {
+        matches!(self, Action::Shift(_))
+    }
+
+    fn is_reduce(self) -> bool {
+        matches!(self, Action::Reduce(_))
+    }
+
+    fn is_error(self) -> bool {
+        self == Action::Error
+    }
+}
+
+impl<'t, L, T, E, F> ParserDefinition for Interpreter<'t, L, T, E, F>
+where
+    L: Clone + Debug + Default,
+    T: Clone + Debug,
+    F: Fn(&T) -> Option<usize>,
+{
+    type Location = L;
+    type Error = E;
+    type Token = T;
+    type TokenIndex = usize;
+    type Symbol = InterpreterSymbol<L, T>;
+    type Success = Tree<L, T>;
+    type StateIndex = usize;
+    type Action = Action;
+    type ReduceIndex = usize;
+    type NonterminalIndex = usize;
+    type Stack<V> = Vec<V>;
+
+    fn start_location(&self) -> L {
+        L::default()
+    }
+
+    fn start_state(&self) -> usize {
+        0
+    }
+
+    fn token_to_index(&self, token: &T) -> Option<usize> {
+        (self.terminal)(token)
+    }
+
+    fn action(&self, state: usize, token_index: usize) -> Action {
+        self.tables.actions[state][token_index]
+    }
+
+    fn error_action(&self, _: usize) -> Action {
+        Action::Error
+    }
+
+    fn eof_action(&self, state: usize) -> Action {
+        self.tables.eof_actions[state]
+    }
+
+    fn goto(&self, state: usize, nt: usize) -> usize {
+        self.tables.gotos[state][nt].expect("no goto for a reduced nonterminal")
+    }
+
+    fn token_to_symbol(&self, _: usize, token: T) -> Self::Symbol {
+        InterpreterSymbol::Token(token)
+    }
+
+    fn expected_tokens(&self, state: usize) -> Vec<String> {
+        self.tables.actions[state]
+            .iter()
+            .zip(&self.tables.terminals)
+            .filter(|&(&action, _)| action != Action::Error)
+            .map(|(_, terminal)| terminal.clone())
+            .collect()
+    }
+
+    fn uses_error_recovery(&self) -> bool {
+        false
+    }
+
+    fn error_recovery_symbol(&self, _: state_machine::ErrorRecovery<Self>) -> Self::Symbol {
+        unreachable!("error recovery is not used by the interpreter")
+    }
+
+    fn reduce(
+        &mut self,
+        action: usize,
+        start_location: Option<&L>,
+        states: &mut Vec<usize>,
+        symbols: &mut Vec<SymbolTriple<Self>>,
+    ) -> Option<ParseResult<Self>> {
+        let (states_to_pop, nonterminal_produced) = match self.simulate_reduce(action) {
+            SimulatedReduce::Reduce {
+                states_to_pop,
+                nonterminal_produced,
+            } => (states_to_pop, nonterminal_produced),
+            SimulatedReduce::Accept => {
+                return match symbols.pop() {
+                    Some((_, InterpreterSymbol::Tree(tree), _)) => Some(Ok(tree)),
+                    _ => unreachable!("accepted a token"),
+                };
+            }
+        };
+
+        let (start, children, end) = state_machine::pop_reduction(
+            self,
+            states_to_pop,
+            nonterminal_produced,
+            start_location,
+            states,
+            symbols,
+        );
+        let children = children
+            .into_iter()
+            .map(|(start, symbol, end)| match symbol {
+                InterpreterSymbol::Token(token) => Tree::Token(start, token, end),
+                InterpreterSymbol::Tree(tree) => tree,
+            })
+            .collect();
+        let tree = Tree::Node {
+            production: action,
+            start: start.clone(),
+            end: end.clone(),
+            children,
+        };
+        symbols.push((start, InterpreterSymbol::Tree(tree), end));
+        None
+    }
+
+    fn simulate_reduce(&self, action: usize) -> SimulatedReduce<Self> {
+        if action == self.tables.start_production {
+            return SimulatedReduce::Accept;
+        }
+        let production = &self.tables.productions[action];
+        SimulatedReduce::Reduce {
+            states_to_pop: production.len,
+            nonterminal_produced: production.nonterminal,
+        }
+    }
+}
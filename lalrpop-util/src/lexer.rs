@@ -18,6 +18,37 @@ struct RegexEntry {
 pub struct MatcherBuilder {
     regex_set: regex::RegexSet,
     regex_vec: Vec<RegexEntry>,
+    starts: &'static [Start],
+}
+
+/// A token (or text to skip) that the lexer matches, with the ranges
+/// of the characters that it can start with.
+pub type Start = (&'static str, bool, &'static [(char, char)]);
+
+/// What `Matcher::invalid_text` knows about text that no token matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidText {
+    /// The start of the invalid text
+    pub location: usize,
+
+    /// The end of the invalid text: where a token (or text to skip)
+    /// matches again, or the end of the input. It is at least one
+    /// character after `location`.
+    pub end: usize,
+
+    /// The character at `location`
+    pub character: char,
+
+    /// The tokens (and text to skip) that can start with `character`,
+    /// but do not match the text after it; for example a string
+    /// literal that is missing its closing quote. They are named as in
+    /// the `expected` list of a `ParseError`, or, for text to skip, by
+    /// their pattern in the `match` block.
+    pub started: Vec<&'static str>,
+
+    /// The ranges of the characters that a token could have started
+    /// with.
+    pub expected: Vec<(char, char)>,
 }
 
 impl MatcherBuilder {
@@ -49,34 +80,218 @@ impl MatcherBuilder {
         Ok(MatcherBuilder {
             regex_set,
             regex_vec,
+            starts: &[],
         })
     }
+
+    /// Tells the lexer what the tokens can start with, for
+    /// `Matcher::invalid_text`. LALRPOP's generated lexers do this,
+    /// except in the `wasm` profile.
+    pub fn with_starts(mut self, starts: &'static [Start]) -> MatcherBuilder {
+        self.starts = starts;
+        self
+    }
+
     pub fn matcher<'input, 'builder, E>(
         &'builder self,
         s: &'input str,
     ) -> Matcher<'input, 'builder, E> {
         Matcher {
+            input: s,
             text: s,
             consumed: 0,
             regex_set: &self.regex_set,
             regex_vec: &self.regex_vec,
+            starts: self.starts,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Like `matcher`, but starts lexing `s` at byte offset `start`.
+    /// Locations are still offsets into all of `s`.
+    pub fn matcher_at<'input, 'builder, E>(
+        &'builder self,
+        s: &'input str,
+        start: usize,
+    ) -> Matcher<'input, 'builder, E> {
+        Matcher {
+            input: s,
+            text: &s[start..],
+            consumed: start,
+            regex_set: &self.regex_set,
+            regex_vec: &self.regex_vec,
+            starts: self.starts,
             _marker: PhantomData,
         }
     }
 }
 
 pub struct Matcher<'input, 'builder, E> {
+    input: &'input str,
     text: &'input str,
     consumed: usize,
     regex_set: &'builder regex::RegexSet,
     regex_vec: &'builder Vec<RegexEntry>,
+    starts: &'static [Start],
     _marker: PhantomData<fn() -> E>,
 }
 
+// not derived, which would require `E: Clone`
+impl<'input, 'builder, E> Clone for Matcher<'input, 'builder, E> {
+    fn clone(&self) -> Self {
+        Matcher {
+            input: self.input,
+            text: self.text,
+            consumed: self.consumed,
+            regex_set: self.regex_set,
+            regex_vec: self.regex_vec,
+            starts: self.starts,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'input, 'builder, E> Matcher<'input, 'builder, E> {
+    /// Explains the `InvalidToken` error at `location` of the input:
+    /// where the invalid text ends, which character is there, which
+    /// tokens it could have started, and what a token could have
+    /// started with instead. Returns
+    /// `None` if `location` is not the start of a character of the
+    /// input.
+    pub fn invalid_text(&self, location: usize) -> Option<InvalidText> {
+        let text = self.input.get(location..)?;
+        let character = text.chars().next()?;
+        let started = self
+            .starts
+            .iter()
+            .filter(|(_, _, ranges)| {
+                ranges
+                    .iter()
+                    .any(|&(lo, hi)| lo <= character && character <= hi)
+            })
+            .map(|&(name, _, _)| name)
+            .collect();
+        let mut ranges: Vec<(char, char)> = self
+            .starts
+            .iter()
+            .filter(|&&(_, skip, _)| !skip)
+            .flat_map(|&(_, _, ranges)| ranges.iter().copied())
+            .collect();
+        ranges.sort_unstable();
+        let mut expected: Vec<(char, char)> = vec![];
+        for (lo, hi) in ranges {
+            match expected.last_mut() {
+                Some(last) if lo as u32 <= last.1 as u32 + 1 => last.1 = last.1.max(hi),
+                _ => expected.push((lo, hi)),
+            }
+        }
+        Some(InvalidText {
+            location,
+            end: self.invalid_token_end(text, location),
+            character,
+            started,
+            expected,
+        })
+    }
+
+    /// Makes the lexer skip invalid text instead of stopping there:
+    /// each `InvalidToken` error is pushed onto `errors`, and lexing
+    /// goes on after it. Parsing the tokens then reports no errors
+    /// from the lexer, so a parse gets as far as the rest of the input
+    /// allows, which is what an editor wants while the user is typing.
+    pub fn skip_invalid<'errors>(
+        self,
+        errors: &'errors mut Vec<ParseError<usize, Token<'input>, E>>,
+    ) -> SkipInvalid<'input, 'builder, 'errors, E> {
+        SkipInvalid {
+            matcher: self,
+            errors,
+        }
+    }
+
+    /// Calls `skipped` with the start, text and end of each match of
+    /// text to skip, such as whitespace or a comment, as the lexer goes
+    /// past it. Since the parser pulls tokens from the lexer as it goes,
+    /// the callback runs during parsing, so that comments can be
+    /// collected, say to find doc comments, without lexing the input a
+    /// second time.
+    pub fn on_skipped<F>(self, skipped: F) -> OnSkipped<'input, 'builder, F, E>
+    where
+        F: FnMut(usize, &'input str, usize),
+    {
+        OnSkipped {
+            matcher: self,
+            skipped,
+        }
+    }
+
+    /// Hands the input over to `island` after each token whose text is
+    /// `open`, so that another parser can parse what follows it, say
+    /// with `parse_island`. `island` is called with the end of the
+    /// token, and returns the offset at which this lexer goes on,
+    /// typically where the other parser stopped. The token then extends
+    /// to that offset, so that `@R` after it in the grammar is where the
+    /// island ends. An error from `island` is passed on to the parser.
+    pub fn islands<F>(self, open: &'static str, island: F) -> Islands<'input, 'builder, F, E>
+    where
+        F: FnMut(usize) -> Result<usize, ParseError<usize, Token<'input>, E>>,
+    {
+        Islands {
+            matcher: self,
+            open,
+            island,
+        }
+    }
+
+    /// The error for the invalid text at the start of `text`, which is
+    /// at `offset` in the input, moving past it so that lexing can go on
+    /// after it.
+    #[cold]
+    fn skip_invalid_token(
+        &mut self,
+        text: &'input str,
+        offset: usize,
+    ) -> ParseError<usize, Token<'input>, E> {
+        let end = self.invalid_token_end(text, offset);
+        self.text = &text[end - offset..];
+        self.consumed = end;
+        ParseError::InvalidToken { location: offset }
+    }
+
+    /// The end of the invalid text at the start of `text`, which is at
+    /// `offset` in the input: the first place a token (or text to skip)
+    /// matches, but at least one character on.
+    fn invalid_token_end(&self, text: &str, offset: usize) -> usize {
+        let len = text
+            .char_indices()
+            .skip(1)
+            .map(|(i, _)| i)
+            .find(|&i| {
+                let rest = &text[i..];
+                self.regex_set
+                    .matches(rest)
+                    .iter()
+                    .any(|entry| self.regex_vec[entry].regex.find(rest).unwrap().end() > 0)
+            })
+            .unwrap_or(text.len());
+        offset + len
+    }
+}
+
 impl<'input, 'builder, E> Iterator for Matcher<'input, 'builder, E> {
     type Item = Result<(usize, Token<'input>, usize), ParseError<usize, Token<'input>, E>>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.next_token(&mut |_, _, _| ())
+    }
+}
+
+impl<'input, 'builder, E> Matcher<'input, 'builder, E> {
+    /// The next token, passing the text skipped before it to `skipped`.
+    fn next_token(
+        &mut self,
+        skipped: &mut impl FnMut(usize, &'input str, usize),
+    ) -> Option<<Self as Iterator>::Item> {
         loop {
             let text = self.text;
             let start_offset = self.consumed;
@@ -86,9 +301,7 @@ impl<'input, 'builder, E> Iterator for Matcher<'input, 'builder, E> {
             } else {
                 let matches = self.regex_set.matches(text);
                 if !matches.matched_any() {
-                    return Some(Err(ParseError::InvalidToken {
-                        location: start_offset,
-                    }));
+                    return Some(Err(self.skip_invalid_token(text, start_offset)));
                 } else {
                     let mut longest_match = 0;
                     let mut index = 0;
@@ -113,10 +326,9 @@ impl<'input, 'builder, E> Iterator for Matcher<'input, 'builder, E> {
                     // Skip any whitespace matches
                     if skip {
                         if longest_match == 0 {
-                            return Some(Err(ParseError::InvalidToken {
-                                location: start_offset,
-                            }));
+                            return Some(Err(self.skip_invalid_token(text, start_offset)));
                         }
+                        skipped(start_offset, result, end_offset);
                         continue;
                     }
 
@@ -126,3 +338,113 @@ impl<'input, 'builder, E> Iterator for Matcher<'input, 'builder, E> {
         }
     }
 }
+
+/// The lexer returned by `Matcher::skip_invalid`.
+pub struct SkipInvalid<'input, 'builder, 'errors, E> {
+    matcher: Matcher<'input, 'builder, E>,
+    errors: &'errors mut Vec<ParseError<usize, Token<'input>, E>>,
+}
+
+impl<'input, 'builder, 'errors, E> Iterator for SkipInvalid<'input, 'builder, 'errors, E> {
+    type Item = Result<(usize, Token<'input>, usize), ParseError<usize, Token<'input>, E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.matcher.next()? {
+                Err(error @ ParseError::InvalidToken { .. }) => self.errors.push(error),
+                token => return Some(token),
+            }
+        }
+    }
+}
+
+/// The lexer returned by `Matcher::on_skipped`.
+pub struct OnSkipped<'input, 'builder, F, E> {
+    matcher: Matcher<'input, 'builder, E>,
+    skipped: F,
+}
+
+impl<'input, 'builder, F, E> Iterator for OnSkipped<'input, 'builder, F, E>
+where
+    F: FnMut(usize, &'input str, usize),
+{
+    type Item = Result<(usize, Token<'input>, usize), ParseError<usize, Token<'input>, E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.matcher.next_token(&mut self.skipped)
+    }
+}
+
+/// The lexer returned by `Matcher::islands`.
+pub struct Islands<'input, 'builder, F, E> {
+    matcher: Matcher<'input, 'builder, E>,
+    open: &'static str,
+    island: F,
+}
+
+// not derived, which would require `E: Clone`
+impl<'input, 'builder, F: Clone, E> Clone for Islands<'input, 'builder, F, E> {
+    fn clone(&self) -> Self {
+        Islands {
+            matcher: self.matcher.clone(),
+            open: self.open,
+            island: self.island.clone(),
+        }
+    }
+}
+
+impl<'input, 'builder, F, E> Iterator for Islands<'input, 'builder, F, E>
+where
+    F: FnMut(usize) -> Result<usize, ParseError<usize, Token<'input>, E>>,
+{
+    type Item = Result<(usize, Token<'input>, usize), ParseError<usize, Token<'input>, E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, token, end) = match self.matcher.next()? {
+            Ok(triple) => triple,
+            Err(error) => return Some(Err(error)),
+        };
+        if token.1 != self.open {
+            return Some(Ok((start, token, end)));
+        }
+        let resume = match (self.island)(end) {
+            Ok(resume) => resume,
+            Err(error) => return Some(Err(error)),
+        };
+        let input = self.matcher.input;
+        assert!(
+            resume >= end && input.is_char_boundary(resume),
+            "an island cannot resume lexing at {}, after a token ending at {}",
+            resume,
+            end
+        );
+        self.matcher.text = &input[resume..];
+        self.matcher.consumed = resume;
+        Some(Ok((start, Token(token.0, &input[start..resume]), resume)))
+    }
+}
+
+/// The tokens that `parse_tokens` takes: a `Result` of a triple, as
+/// the lexer returns them, or a bare triple, from code that cannot
+/// fail, which is taken to be `Ok`.
+pub trait ToTriple<'input, E> {
+    fn to_triple(
+        self,
+    ) -> Result<(usize, Token<'input>, usize), ParseError<usize, Token<'input>, E>>;
+}
+
+impl<'input, E> ToTriple<'input, E> for (usize, Token<'input>, usize) {
+    fn to_triple(
+        self,
+    ) -> Result<(usize, Token<'input>, usize), ParseError<usize, Token<'input>, E>> {
+        Ok(self)
+    }
+}
+
+impl<'input, E> ToTriple<'input, E>
+    for Result<(usize, Token<'input>, usize), ParseError<usize, Token<'input>, E>>
+{
+    fn to_triple(self) -> Self {
+        self
+    }
+}
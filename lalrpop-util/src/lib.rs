@@ -7,11 +7,32 @@ use core::fmt;
 #[cfg(feature = "std")]
 use std::error::Error;
 
+use crate::catalog::{Catalog, Message};
+
+#[cfg(feature = "ariadne")]
+pub mod ariadne;
+pub mod catalog;
+#[cfg(feature = "codespan-reporting")]
+pub mod codespan;
+pub mod coverage;
+#[cfg(feature = "expect")]
+pub mod expect;
+#[cfg(feature = "std")]
+pub mod ffi;
+pub mod format;
+pub mod incremental;
+pub mod intern;
+pub mod interpret;
 #[cfg(feature = "lexer")]
 pub mod lexer;
 pub mod state_machine;
+pub mod trace;
 
+/// An error reported by a generated parser. With the `serde` feature,
+/// it can be serialized and deserialized whenever its locations,
+/// tokens and user errors can.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ParseError<L, T, E> {
     /// Generated by the parser when it encounters a token (or EOF) it did not
     /// expect.
@@ -86,6 +107,61 @@ impl<L, T, E> ParseError<L, T, E> {
     pub fn map_error<EE>(self, op: impl FnOnce(E) -> EE) -> ParseError<L, T, EE> {
         self.map_intern(|x| x, |x| x, op)
     }
+
+    /// Where the error was found: the start of the text or token it is
+    /// about, or the point in the input where parsing stopped. This is
+    /// `None` for `User` errors; see `location_with` for those.
+    pub fn location(&self) -> Option<&L> {
+        self.location_with(|_| None)
+    }
+
+    /// Like `location`, but asks `user` for the location of a `User`
+    /// error, for errors (such as those of a hand-written lexer) that
+    /// carry one.
+    pub fn location_with<'a>(&'a self, user: impl FnOnce(&'a E) -> Option<&'a L>) -> Option<&'a L> {
+        match *self {
+            ParseError::InvalidToken { ref location }
+            | ParseError::UnrecognizedEOF { ref location, .. } => Some(location),
+            ParseError::UnrecognizedToken {
+                token: (ref start, _, _),
+                ..
+            }
+            | ParseError::ExtraToken {
+                token: (ref start, _, _),
+            } => Some(start),
+            ParseError::User { ref error } => user(error),
+        }
+    }
+
+    /// The start and end of the token the error is about, for an
+    /// unrecognized or extra token. Other errors have no span; for an
+    /// invalid token, only the lexer knows where the invalid text ends
+    /// (see `lexer::Matcher::invalid_text`).
+    pub fn span(&self) -> Option<(&L, &L)> {
+        match *self {
+            ParseError::UnrecognizedToken {
+                token: (ref start, _, ref end),
+                ..
+            }
+            | ParseError::ExtraToken {
+                token: (ref start, _, ref end),
+            } => Some((start, end)),
+            ParseError::InvalidToken { .. }
+            | ParseError::UnrecognizedEOF { .. }
+            | ParseError::User { .. } => None,
+        }
+    }
+}
+
+/// The range of the character at `location` of `source`, which an
+/// `InvalidToken` error is at least about; empty at the end of it.
+fn invalid_char(source: &str, location: usize) -> core::ops::Range<usize> {
+    let start = location.min(source.len());
+    let end = source
+        .get(start..)
+        .and_then(|rest| rest.chars().next())
+        .map_or(start, |c| start + c.len_utf8());
+    start..end
 }
 
 /// Format a list of expected tokens.
@@ -141,6 +217,102 @@ where
     }
 }
 
+/// What the integrations with diagnostic crates show for a
+/// `ParseError`: a message, the span it is about, if any, with a label,
+/// and the expected tokens.
+#[cfg(any(feature = "ariadne", feature = "codespan-reporting"))]
+struct DiagnosticParts {
+    message: String,
+    label: Option<(core::ops::Range<usize>, String)>,
+    note: Option<String>,
+}
+
+impl<T, E> ParseError<usize, T, E>
+where
+    T: fmt::Display,
+    E: fmt::Display,
+{
+    /// What went wrong, for a message about this error in `source`, in
+    /// the words of `catalog`; the integrations with diagnostic crates
+    /// and the default `ErrorFormatter` share this wording.
+    fn describe(&self, source: &str, catalog: &dyn Catalog) -> String {
+        use self::ParseError::*;
+        use alloc::string::ToString;
+        match *self {
+            InvalidToken { location } => {
+                let text = source.get(invalid_char(source, location)).unwrap_or("");
+                catalog.text(Message::InvalidToken { text })
+            }
+            UnrecognizedEOF { .. } => catalog.text(Message::UnexpectedEof),
+            UnrecognizedToken {
+                token: (_, ref token, _),
+                ..
+            } => catalog.text(Message::UnrecognizedToken {
+                token: &token.to_string(),
+            }),
+            ExtraToken {
+                token: (_, ref token, _),
+            } => catalog.text(Message::ExtraToken {
+                token: &token.to_string(),
+            }),
+            User { ref error } => error.to_string(),
+        }
+    }
+
+    /// The parts of a diagnostic for this error in `source`, in the
+    /// words of `catalog`; the spans are kept within it.
+    #[cfg(any(feature = "ariadne", feature = "codespan-reporting"))]
+    fn diagnostic_parts(&self, source: &str, catalog: &dyn Catalog) -> DiagnosticParts {
+        use self::catalog::Label;
+        use self::ParseError::*;
+        let clamp = |location: usize| location.min(source.len());
+        let expected_note = |expected: &[String]| {
+            let names: Vec<String> = expected
+                .iter()
+                .map(|text| catalog.text(Message::FromGrammar { text }))
+                .collect();
+            let names: Vec<&str> = names.iter().map(|name| &name[..]).collect();
+            Some(catalog.text(Message::Expected { names: &names })).filter(|_| !names.is_empty())
+        };
+        let (span, label, note) = match *self {
+            InvalidToken { location } => {
+                (invalid_char(source, location), Label::InvalidToken, None)
+            }
+            UnrecognizedEOF {
+                location,
+                ref expected,
+            } => (
+                clamp(location)..clamp(location),
+                Label::UnexpectedEof,
+                expected_note(expected),
+            ),
+            UnrecognizedToken {
+                token: (start, _, end),
+                ref expected,
+            } => (
+                clamp(start)..clamp(end),
+                Label::UnexpectedToken,
+                expected_note(expected),
+            ),
+            ExtraToken {
+                token: (start, _, end),
+            } => (clamp(start)..clamp(end), Label::ExtraToken, None),
+            User { .. } => {
+                return DiagnosticParts {
+                    message: self.describe(source, catalog),
+                    label: None,
+                    note: None,
+                }
+            }
+        };
+        DiagnosticParts {
+            message: self.describe(source, catalog),
+            label: Some((span, catalog.text(Message::Label(label)))),
+            note,
+        }
+    }
+}
+
 impl<L, T, E> From<E> for ParseError<L, T, E> {
     fn from(error: E) -> Self {
         ParseError::User { error }
@@ -160,11 +332,249 @@ where
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ErrorRecovery<L, T, E> {
     pub error: ParseError<L, T, E>,
     pub dropped_tokens: Vec<(L, T, L)>,
 }
 
+/// The user error of a parser generated with
+/// `Configuration::max_stack_depth` when shifting the token at
+/// `location` would take its stacks past that depth. The error type
+/// of the grammar must convert from it; `&'static str`, the default
+/// one, does.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StackOverflow<L> {
+    /// The start of the token
+    pub location: L,
+}
+
+impl<L> fmt::Display for StackOverflow<L>
+where
+    L: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Input nested too deeply at {}", self.location)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<L> Error for StackOverflow<L> where L: fmt::Debug + fmt::Display {}
+
+impl<L> From<StackOverflow<L>> for &'static str {
+    fn from(_: StackOverflow<L>) -> Self {
+        "input nested too deeply"
+    }
+}
+
+/// Returned by a `parse_cancellable` method when its callback reported
+/// that the parse should stop.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cancelled<L> {
+    /// The end of the last token that was read
+    pub location: L,
+}
+
+impl<L> fmt::Display for Cancelled<L>
+where
+    L: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Parse cancelled at {}", self.location)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<L> Error for Cancelled<L> where L: fmt::Debug + fmt::Display {}
+
+/// The state of a resumable parser after it was fed a token, or
+/// after it was told the input is finished.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParseStatus<T, L, Tok, E> {
+    /// The input so far is a valid prefix; feed more tokens.
+    Pending,
+
+    /// The input was parsed successfully.
+    Accepted(T),
+
+    /// The input cannot be parsed.
+    Error(ParseError<L, Tok, E>),
+}
+
+/// A point in the input that a resumable parser can be rolled back
+/// to, taken by its `checkpoint` method.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub(crate) index: usize,
+    pub(crate) serial: usize,
+}
+
+/// An event in the stream produced by `parse_events`, for grammars
+/// that mark a public nonterminal with `#[events]`. Each nonterminal
+/// appears as a `StartNonterminal`, the events of its children, and a
+/// matching `EndNonterminal`; the children of a nonterminal are listed
+/// in input order.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParseEvent<L, T> {
+    /// The start of a nonterminal, given by its name in the grammar.
+    StartNonterminal(&'static str),
+
+    /// A token of the input.
+    Token(L, T, L),
+
+    /// The end of the last nonterminal that was started, given by the
+    /// production it was reduced with, like `Expr = Expr "+" Term`.
+    EndNonterminal(&'static str),
+}
+
+impl<L, T> ParseEvent<L, T> {
+    /// Converts the token of a `Token` event, for instance to drop the
+    /// borrowed text of the tokens of LALRPOP's own lexer.
+    pub fn map_token<TT>(self, op: impl FnOnce(T) -> TT) -> ParseEvent<L, TT> {
+        match self {
+            ParseEvent::StartNonterminal(nonterminal) => ParseEvent::StartNonterminal(nonterminal),
+            ParseEvent::Token(start, token, end) => ParseEvent::Token(start, op(token), end),
+            ParseEvent::EndNonterminal(production) => ParseEvent::EndNonterminal(production),
+        }
+    }
+}
+
+/// A syntax error that the parser repaired by editing the token
+/// stream, so that parsing could continue. Produced by parsers
+/// generated from grammars annotated with `#[repair]`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Repair<L, T, E> {
+    /// The error that would have been reported without the repair.
+    pub error: ParseError<L, T, E>,
+
+    /// The edits that were applied, in input order.
+    pub edits: Vec<Edit<L, T>>,
+}
+
+impl<L, T, E> Repair<L, T, E>
+where
+    L: PartialEq,
+{
+    /// The token of the input that starts at `location`, if the repair
+    /// knows it: one that an edit removed, or the one it failed on.
+    fn token_at(&self, location: &L) -> Option<&T> {
+        let edited = self.edits.iter().find_map(|edit| match *edit {
+            Edit::Delete((ref start, ref token, _))
+            | Edit::Replace((ref start, ref token, _), _)
+                if start == location =>
+            {
+                Some(token)
+            }
+            _ => None,
+        });
+        edited.or(match self.error {
+            ParseError::UnrecognizedToken {
+                token: (ref start, ref token, _),
+                ..
+            } if start == location => Some(token),
+            _ => None,
+        })
+    }
+}
+
+/// Describes the repair as it would be reported to the user, with a
+/// message for each edit, such as ``expected `)` before `,` ``.
+/// Tokens inserted at the same place share a message.
+impl<L, T, E> fmt::Display for Repair<L, T, E>
+where
+    L: fmt::Display + PartialEq,
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut edits = self.edits.iter().peekable();
+        let mut first = true;
+        while let Some(edit) = edits.next() {
+            if !first {
+                write!(f, "; ")?;
+            }
+            first = false;
+            match *edit {
+                Edit::Insert((ref location, ref token, _)) => {
+                    write!(f, "expected `{}`", token)?;
+                    while let Some(Edit::Insert((next, token, _))) = edits.peek().copied() {
+                        if next != location {
+                            break;
+                        }
+                        write!(f, " and `{}`", token)?;
+                        edits.next();
+                    }
+                    match (self.token_at(location), &self.error) {
+                        (Some(token), _) => write!(f, " before `{}`", token)?,
+                        (None, ParseError::UnrecognizedEOF { location: end, .. })
+                            if end == location =>
+                        {
+                            write!(f, " at end of input")?
+                        }
+                        (None, _) => write!(f, " at {}", location)?,
+                    }
+                }
+                Edit::Delete((ref start, ref token, ref end)) => {
+                    write!(f, "unexpected `{}` at {}:{}", token, start, end)?
+                }
+                Edit::Replace((ref start, ref token, ref end), ref replacement) => write!(
+                    f,
+                    "expected `{}` instead of `{}` at {}:{}",
+                    replacement, token, start, end
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single edit to the token stream made by error repair.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edit<L, T> {
+    /// A token that was missing from the input and was inserted. Its
+    /// start and end are both the location where it was inserted.
+    Insert((L, T, L)),
+
+    /// A token from the input that was skipped.
+    Delete((L, T, L)),
+
+    /// A token from the input that was replaced by another token,
+    /// which takes over its location.
+    Replace((L, T, L), T),
+}
+
+impl<L, T> fmt::Display for Edit<L, T>
+where
+    L: fmt::Display,
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Edit::Insert((ref start, ref token, _)) => {
+                write!(f, "inserted `{}` at {}", token, start)
+            }
+            Edit::Delete((ref start, ref token, ref end)) => {
+                write!(f, "deleted `{}` at {}:{}", token, start, end)
+            }
+            Edit::Replace((ref start, ref token, ref end), ref replacement) => write!(
+                f,
+                "replaced `{}` with `{}` at {}:{}",
+                token, replacement, start, end
+            ),
+        }
+    }
+}
+
+/// The `parse` method of a generated `XParser`, for code that works with
+/// any parser taking `Input` to `Output`. It is implemented by the
+/// parsers of grammars without parameters: `Input` is `&str` when
+/// LALRPOP generates the lexer, and otherwise an `IntoIterator` of
+/// tokens; `Error` is the `ParseError` of the grammar.
+pub trait Parser<Input, Output, Error> {
+    fn parse(&self, input: Input) -> Result<Output, Error>;
+}
+
 /// Define a module using the generated parse from a `.lalrpop` file.
 ///
 /// You have to specify the name of the module and the path of the file
@@ -213,4 +623,318 @@ mod tests {
              Expected one of t1, t2 or t3"
         );
     }
+
+    #[test]
+    fn test_location() {
+        type Error = ParseError<i32, &'static str, (i32, &'static str)>;
+        let token = Error::UnrecognizedToken {
+            token: (1, "t0", 2),
+            expected: vec![],
+        };
+        assert_eq!(token.location(), Some(&1));
+        assert_eq!(token.span(), Some((&1, &2)));
+
+        let eof = Error::UnrecognizedEOF {
+            location: 3,
+            expected: vec![],
+        };
+        assert_eq!(eof.location(), Some(&3));
+        assert_eq!(eof.span(), None);
+
+        let user = Error::User {
+            error: (4, "unterminated string"),
+        };
+        assert_eq!(user.location(), None);
+        assert_eq!(user.location_with(|error| Some(&error.0)), Some(&4));
+        assert_eq!(token.location_with(|error| Some(&error.0)), Some(&1));
+    }
+
+    #[test]
+    fn test_error_formatter() {
+        use format::{DefaultFormatter, ErrorFormatter, Position};
+
+        let source = "let x =\n  (1 + ];";
+        type Error = ParseError<usize, &'static str, &'static str>;
+        let error = Error::UnrecognizedToken {
+            token: (15, "]", 16),
+            expected: vec![r#""(""#.to_string(), "NUM".to_string(), "IDENT".to_string()],
+        };
+        let names = [(r#""(""#, "`(`"), ("NUM", "a value"), ("IDENT", "a value")];
+        assert_eq!(
+            DefaultFormatter.format(&error, source, &names),
+            "2:8: unrecognized token `]`; expected one of `(` or a value"
+        );
+
+        // a formatter that changes the wording keeps the positions
+        struct French;
+        impl ErrorFormatter<&'static str, &'static str> for French {
+            fn message(&self, error: &Error, _: &str) -> String {
+                match *error {
+                    ParseError::UnrecognizedToken {
+                        token: (_, token, _),
+                        ..
+                    } => format!("symbole inattendu `{}`", token),
+                    _ => "erreur de syntaxe".to_string(),
+                }
+            }
+
+            fn position(&self, position: Position) -> String {
+                format!("ligne {}, colonne {}", position.line, position.column)
+            }
+
+            fn expected(&self, expected: &[&str]) -> String {
+                format!("attendu : {}", expected.join(" ou "))
+            }
+        }
+        assert_eq!(
+            French.format(&error, source, &names),
+            "ligne 2, colonne 8: symbole inattendu `]`; attendu : `(` ou a value"
+        );
+
+        // columns count characters, and user errors have no position
+        assert_eq!(
+            Position::new("é\nàb", 5),
+            Position {
+                offset: 5,
+                line: 2,
+                column: 2
+            }
+        );
+        let user = Error::User { error: "too long" };
+        assert_eq!(DefaultFormatter.format(&user, source, &[]), "too long");
+
+        // the edits of error repair
+        let insert = Edit::<usize, &str>::Insert((17, ";", 17));
+        assert_eq!(
+            ErrorFormatter::<_, &str>::edit(&DefaultFormatter, &insert, source),
+            "2:10: inserted `;`"
+        );
+    }
+
+    #[test]
+    fn test_catalog() {
+        use catalog::{Catalog, English, Message};
+        use format::ErrorFormatter;
+
+        struct Spanish;
+        impl Catalog for Spanish {
+            fn text(&self, message: Message) -> String {
+                match message {
+                    Message::UnrecognizedToken { token } => {
+                        format!("símbolo inesperado `{}`", token)
+                    }
+                    Message::Expected { names } => format!("se esperaba {}", names.join(" o ")),
+                    Message::Inserted { token } => format!("se insertó `{}`", token),
+                    Message::FromGrammar { text: "NUM" } => "un número".to_string(),
+                    message => English.text(message),
+                }
+            }
+        }
+
+        // a formatter that only changes the catalog
+        struct Formatter;
+        impl ErrorFormatter<&'static str, &'static str> for Formatter {
+            fn catalog(&self) -> &dyn Catalog {
+                &Spanish
+            }
+        }
+
+        let error = ParseError::UnrecognizedToken::<usize, &str, &str> {
+            token: (2, "+", 3),
+            expected: vec!["NUM".to_string(), r#""(""#.to_string()],
+        };
+        assert_eq!(
+            Formatter.format(&error, "1 +", &[]),
+            r#"1:3: símbolo inesperado `+`; se esperaba un número o "(""#
+        );
+        let insert = Edit::Insert((3, ")", 3));
+        assert_eq!(Formatter.edit(&insert, "1 +"), "1:4: se insertó `)`");
+        let eof = ParseError::UnrecognizedEOF::<usize, &str, &str> {
+            location: 3,
+            expected: vec![],
+        };
+        assert_eq!(
+            Formatter.format(&eof, "1 +", &[]),
+            "1:4: unexpected end of input"
+        );
+    }
+
+    #[test]
+    fn test_edit_display() {
+        let insert = Edit::<i32, &str>::Insert((3, ";", 3));
+        let delete = Edit::<i32, &str>::Delete((4, ")", 5));
+        assert_eq!(format!("{}", insert), "inserted `;` at 3");
+        assert_eq!(format!("{}", delete), "deleted `)` at 4:5");
+        let replace = Edit::<i32, &str>::Replace((6, ",", 7), ";");
+        assert_eq!(format!("{}", replace), "replaced `,` with `;` at 6:7");
+    }
+
+    #[test]
+    fn test_repair_display() {
+        let repair = Repair::<i32, &str, ()> {
+            error: ParseError::UnrecognizedToken {
+                token: (4, ",", 5),
+                expected: vec![],
+            },
+            edits: vec![Edit::Insert((4, ")", 4)), Edit::Replace((8, "]", 9), ";")],
+        };
+        assert_eq!(
+            repair.to_string(),
+            "expected `)` before `,`; expected `;` instead of `]` at 8:9"
+        );
+    }
+
+    #[cfg(feature = "lexer")]
+    #[test]
+    fn test_lexer_invalid_token() {
+        let builder =
+            lexer::MatcherBuilder::new(vec![("^[0-9]+", false), (r"^\s*", true)]).unwrap();
+        let tokens: Vec<Result<_, ParseError<_, _, ()>>> =
+            builder.matcher("1 $é$ 2").take(2).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((0, lexer::Token(0, "1"), 1)),
+                Err(ParseError::InvalidToken { location: 2 }),
+            ]
+        );
+        let matcher = builder.matcher::<()>("1 $é$ 2");
+        assert_eq!(matcher.invalid_text(2).map(|text| text.end), Some(6));
+    }
+
+    #[cfg(feature = "lexer")]
+    #[test]
+    fn test_lexer_skip_invalid() {
+        let builder =
+            lexer::MatcherBuilder::new(vec![("^[0-9]+", false), (r"^\s*", true)]).unwrap();
+        let mut errors: Vec<ParseError<_, _, ()>> = vec![];
+        let tokens: Vec<_> = builder
+            .matcher("1 $é 2 $")
+            .skip_invalid(&mut errors)
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((0, lexer::Token(0, "1"), 1)),
+                Ok((6, lexer::Token(0, "2"), 7)),
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::InvalidToken { location: 2 },
+                ParseError::InvalidToken { location: 8 },
+            ]
+        );
+    }
+
+    #[cfg(feature = "lexer")]
+    #[test]
+    fn test_lexer_islands() {
+        let builder = lexer::MatcherBuilder::new(vec![
+            ("^[a-z]+", false),
+            (r"^\{", false),
+            (r"^\}", false),
+            (r"^\s*", true),
+        ])
+        .unwrap();
+        let input = "a {1 } 2} b";
+        let tokens: Vec<Result<_, ParseError<_, _, ()>>> = builder
+            .matcher(input)
+            .islands("{", |end| Ok(end + input[end..].find('}').unwrap()))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((0, lexer::Token(0, "a"), 1)),
+                Ok((2, lexer::Token(1, "{1 "), 5)),
+                Ok((5, lexer::Token(2, "}"), 6)),
+                Err(ParseError::InvalidToken { location: 7 }),
+                Ok((8, lexer::Token(2, "}"), 9)),
+                Ok((10, lexer::Token(0, "b"), 11)),
+            ]
+        );
+    }
+
+    #[cfg(feature = "lexer")]
+    #[test]
+    fn test_lexer_on_skipped() {
+        let builder =
+            lexer::MatcherBuilder::new(vec![("^[0-9]+", false), (r"^\s*", true)]).unwrap();
+        let mut skipped = vec![];
+        let tokens: Vec<Result<_, ParseError<_, _, ()>>> = builder
+            .matcher("1  2 ")
+            .on_skipped(|start, text, end| skipped.push((start, text, end)))
+            .collect();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(skipped, vec![(1, "  ", 3), (4, " ", 5)]);
+    }
+
+    #[cfg(feature = "lexer")]
+    #[test]
+    fn test_lexer_invalid_text() {
+        let builder = lexer::MatcherBuilder::new(vec![("^[0-9]+", false), ("^\"[^\"]*\"", false)])
+            .unwrap()
+            .with_starts(&[
+                ("NUM", false, &[('0', '9')]),
+                ("STRING", false, &[('"', '"')]),
+            ]);
+        let matcher = builder.matcher::<()>("12 \"a");
+        assert_eq!(
+            matcher.invalid_text(3),
+            Some(lexer::InvalidText {
+                location: 3,
+                end: 5,
+                character: '"',
+                started: vec!["STRING"],
+                expected: vec![('"', '"'), ('0', '9')],
+            })
+        );
+        assert_eq!(matcher.invalid_text(2).unwrap().started, Vec::<&str>::new());
+        assert_eq!(matcher.invalid_text(9), None);
+    }
+
+    static COVERAGE_RULES: &[&str] =
+        &["Expr = Num", "Expr = Expr \"+\" Num", "Num = r#\"[0-9]+\"#"];
+    static COVERAGE_COUNTS: [core::sync::atomic::AtomicUsize; 3] = [coverage::UNUSED; 3];
+
+    #[test]
+    fn test_coverage_report() {
+        let coverage = coverage::Coverage::new(COVERAGE_RULES, &COVERAGE_COUNTS);
+        coverage.record(0);
+        coverage.record(2);
+        coverage.record(2);
+        let report = coverage.report();
+        assert_eq!(report.count("Num = r#\"[0-9]+\"#"), Some(2));
+        assert_eq!(report.uncovered(), vec!["Expr = Expr \"+\" Num"]);
+
+        let text = report.to_string();
+        assert_eq!(
+            text,
+            "0 Expr = Expr \"+\" Num\n1 Expr = Num\n2 Num = r#\"[0-9]+\"#\n"
+        );
+        let mut merged: coverage::CoverageReport = text.parse().unwrap();
+        merged.merge(&"1 Expr = Expr \"+\" Num\n".parse().unwrap());
+        assert!(merged.uncovered().is_empty());
+        assert_eq!(merged.count("Expr = Num"), Some(1));
+
+        coverage.reset();
+        assert_eq!(coverage.report().uncovered().len(), 3);
+        assert!("Expr = Num".parse::<coverage::CoverageReport>().is_err());
+    }
+
+    #[test]
+    fn test_symbol_table() {
+        use intern::{Interner, Symbol, SymbolTable};
+
+        let names = SymbolTable::new();
+        assert_eq!(names.intern("x"), Symbol(0));
+        assert_eq!(names.intern("y"), Symbol(1));
+        assert_eq!(names.intern("x"), Symbol(0));
+        assert_eq!(names.len(), 2);
+        assert_eq!(names.get("y"), Some(Symbol(1)));
+        assert_eq!(names.get("z"), None);
+        assert_eq!(names.resolve(Symbol(1)).as_deref(), Some("y"));
+        assert_eq!(names.resolve(Symbol(2)), None);
+    }
 }
@@ -1,10 +1,34 @@
 #![allow(dead_code)]
 
-use alloc::{string::String, vec, vec::Vec};
+use alloc::{collections::VecDeque, string::String, vec, vec::Vec};
+use core::cell::Cell;
 use core::fmt::Debug;
+use core::iter::Fuse;
+use core::ops::{Deref, DerefMut};
+
+use crate::trace::{Trace, TraceEntry, TraceIndex};
 
 const DEBUG_ENABLED: bool = false;
 
+/// How many tokens following an error the `SingleToken` repair
+/// strategy looks at when comparing candidate repairs.
+const REPAIR_WINDOW: usize = 4;
+
+/// How many tokens following an error the `BurkeFisher` repair
+/// strategy looks at, and how many edits it may combine.
+const BURKE_FISHER_WINDOW: usize = 6;
+const BURKE_FISHER_EDITS: usize = 3;
+
+/// How many tokens following an error the `MinimumCost` repair
+/// strategy must get through, and how many edits it may combine to
+/// do so.
+const MINIMUM_COST_WINDOW: usize = 8;
+const MINIMUM_COST_EDITS: usize = 3;
+
+/// How many tokens, starting with the lookahead, a `#[backtrack]`
+/// grammar tries the actions of a conflicted state on.
+const BACKTRACK_WINDOW: usize = 4;
+
 macro_rules! debug {
     ($($args:expr),* $(,)*) => {
         #[cfg(feature = "std")]
@@ -14,6 +38,20 @@ macro_rules! debug {
     }
 }
 
+/// Stops the program on a path that a generated parser never takes,
+/// for parsers generated with the `wasm` profile: unlike `panic!`, it
+/// formats no message, so the formatting code can be left out of the
+/// binary.
+#[doc(hidden)]
+#[cold]
+#[inline(never)]
+pub fn abort() -> ! {
+    #[cfg(feature = "std")]
+    std::process::abort();
+    #[cfg(not(feature = "std"))]
+    panic!()
+}
+
 pub trait ParserDefinition: Sized {
     /// Represents a location in the input text. If you are using the
     /// default tokenizer, this will be a `usize`.
@@ -56,6 +94,11 @@ pub trait ParserDefinition: Sized {
     /// Identifies a nonterminal.
     type NonterminalIndex: Copy + Clone + Debug;
 
+    /// The stacks the parser keeps its states and symbols on.
+    /// Typically a `Vec`, or, with the `smallvec` feature, an
+    /// `InlineStack`.
+    type Stack<T>: ParserStack<T>;
+
     /// Returns a location representing the "start of the input".
     fn start_location(&self) -> Self::Location;
 
@@ -135,14 +178,134 @@ pub trait ParserDefinition: Sized {
         &mut self,
         reduce_index: Self::ReduceIndex,
         start_location: Option<&Self::Location>,
-        states: &mut Vec<Self::StateIndex>,
-        symbols: &mut Vec<SymbolTriple<Self>>,
+        states: &mut Self::Stack<Self::StateIndex>,
+        symbols: &mut Self::Stack<SymbolTriple<Self>>,
     ) -> Option<ParseResult<Self>>;
 
     /// Returns information about how many states will be popped
     /// during a reduction, and what nonterminal would be produced as
     /// a result.
     fn simulate_reduce(&self, action: Self::ReduceIndex) -> SimulatedReduce<Self>;
+
+    /// Returns the tokens that error repair may insert in the given
+    /// state, along with their indices. These are the expected
+    /// terminals for which a placeholder token can be constructed
+    /// (e.g. `";"`, but not an identifier that carries a value).
+    /// Grammars without `#[repair]` never insert tokens.
+    fn repair_tokens(&self, state: Self::StateIndex) -> Vec<(Self::TokenIndex, Self::Token)> {
+        let _ = state;
+        vec![]
+    }
+
+    /// Returns how hard error repair should look for a repair.
+    fn repair_strategy(&self) -> RepairStrategy {
+        RepairStrategy::SingleToken
+    }
+
+    /// Returns what it costs error repair to insert or delete a token
+    /// of the given terminal. Of the repairs that get equally far,
+    /// the cheapest is made. Grammars give costs with `#[cost]`;
+    /// otherwise every edit costs 1.
+    fn repair_cost(&self, token_index: Self::TokenIndex) -> RepairCost {
+        let _ = token_index;
+        RepairCost::default()
+    }
+
+    /// Returns the index and name of each terminal, for
+    /// `Parser::drive_completions`. Only grammars that ask for
+    /// completions list them.
+    fn terminals(&self) -> Vec<(Self::TokenIndex, &'static str)> {
+        vec![]
+    }
+
+    /// Returns the name of the nonterminal produced by a reduction,
+    /// and the production it reduces, for `Parser::drive_events`.
+    /// Only grammars that ask for events describe their reductions.
+    fn reduction_names(&self, action: Self::ReduceIndex) -> (&'static str, &'static str) {
+        let _ = action;
+        ("", "")
+    }
+
+    /// Returns the actions, other than the one given by `action` (or
+    /// `eof_action` when `token_index` is `None`), that a conflicted
+    /// state permits on the given terminal. Only grammars declared
+    /// with `#[backtrack]`, or with `when` guards, keep conflicts; the
+    /// parser checks the guards, then tries each remaining action over
+    /// the next few tokens before committing to one.
+    fn alternative_actions(
+        &self,
+        state: Self::StateIndex,
+        token_index: Option<Self::TokenIndex>,
+    ) -> Vec<Self::Action> {
+        let _ = (state, token_index);
+        vec![]
+    }
+
+    /// Evaluates the `when` guard of the production reduced by
+    /// `action`, over the symbols on top of `symbols`. Returns `None`
+    /// if the production has no guard.
+    fn guard(&self, action: Self::ReduceIndex, symbols: &[SymbolTriple<Self>]) -> Option<bool> {
+        let _ = (action, symbols);
+        None
+    }
+
+    /// Returns the most states that the parse stack may hold when a
+    /// token is shifted; shifting one more fails with the user error
+    /// from `stack_overflow`. `None` means no limit.
+    fn max_stack_depth(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the user error for a token at `location` that would
+    /// take the parse stack past `max_stack_depth`. Only called if
+    /// that is limited.
+    fn stack_overflow(&self, location: Self::Location) -> Self::Error {
+        let _ = location;
+        unreachable!("the parse stack is not limited")
+    }
+
+    /// Returns the message for a syntax error found in the given state,
+    /// for `Parser::drive_messages`. Only grammars with a `.messages`
+    /// file have messages, and only for the states its sentences reach.
+    fn error_message(&self, state: Self::StateIndex) -> Option<&'static str> {
+        let _ = state;
+        None
+    }
+}
+
+/// How `Parser::drive_repairing` searches for repairs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RepairStrategy {
+    /// Delete the token at the error, or insert one token before it.
+    SingleToken,
+
+    /// Try short sequences of insertions, deletions and replacements
+    /// at the error and at any further errors among the next few
+    /// tokens, in the style of Burke and Fisher.
+    BurkeFisher,
+
+    /// Like `BurkeFisher`, but of the sequences that let the parser
+    /// get through all of the next few tokens, take the one with the
+    /// least cost, however far the others get.
+    MinimumCost,
+}
+
+/// What it costs `Parser::drive_repairing` to insert or delete a
+/// token of some terminal. Replacing a token costs the larger of
+/// deleting it and inserting its replacement.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RepairCost {
+    pub insert: u32,
+    pub delete: u32,
+}
+
+impl Default for RepairCost {
+    fn default() -> Self {
+        RepairCost {
+            insert: 1,
+            delete: 1,
+        }
+    }
 }
 
 pub trait ParserAction<D: ParserDefinition>: Copy + Clone + Debug {
@@ -163,6 +326,214 @@ pub enum SimulatedReduce<D: ParserDefinition> {
     Accept,
 }
 
+/// What the tables say to do in a state on a lookahead, with the
+/// action decoded.
+enum TableAction<S, R> {
+    Shift(S),
+    Reduce(R),
+    Error,
+}
+
+/// The parts of a `ParserDefinition` that the simulations of the
+/// automaton need, which only deal in indices. The simulations are
+/// written against a `dyn Tables`, so that they are compiled once for
+/// each combination of index types (of which there are a handful)
+/// rather than once for each instantiation of a grammar's type
+/// parameters, and each `Parser` only carries the shims below.
+trait Tables<S, T, R, N> {
+    /// The action in `state` on `token_index`, `None` being EOF.
+    fn action(&self, state: S, token_index: Option<T>) -> TableAction<S, R>;
+
+    fn goto(&self, state: S, nonterminal: N) -> S;
+
+    /// How many states `reduce_index` pops and the nonterminal it
+    /// produces, or `None` if it accepts.
+    fn simulate_reduce(&self, reduce_index: R) -> Option<(usize, N)>;
+}
+
+impl<D: ParserDefinition> Tables<D::StateIndex, D::TokenIndex, D::ReduceIndex, D::NonterminalIndex>
+    for D
+{
+    fn action(
+        &self,
+        state: D::StateIndex,
+        token_index: Option<D::TokenIndex>,
+    ) -> TableAction<D::StateIndex, D::ReduceIndex> {
+        let action = match token_index {
+            None => self.eof_action(state),
+            Some(i) => ParserDefinition::action(self, state, i),
+        };
+        if let Some(target_state) = action.as_shift() {
+            TableAction::Shift(target_state)
+        } else if let Some(reduce_index) = action.as_reduce() {
+            TableAction::Reduce(reduce_index)
+        } else {
+            TableAction::Error
+        }
+    }
+
+    fn goto(&self, state: D::StateIndex, nonterminal: D::NonterminalIndex) -> D::StateIndex {
+        ParserDefinition::goto(self, state, nonterminal)
+    }
+
+    fn simulate_reduce(
+        &self,
+        reduce_index: D::ReduceIndex,
+    ) -> Option<(usize, D::NonterminalIndex)> {
+        match ParserDefinition::simulate_reduce(self, reduce_index) {
+            SimulatedReduce::Reduce {
+                states_to_pop,
+                nonterminal_produced,
+            } => Some((states_to_pop, nonterminal_produced)),
+            SimulatedReduce::Accept => None,
+        }
+    }
+}
+
+/// Simulates `reduce_index` on `states`. Returns false, leaving
+/// `states` alone, if it accepts.
+fn simulate_reduction<S: Copy, T, R, N>(
+    tables: &dyn Tables<S, T, R, N>,
+    states: &mut Vec<S>,
+    reduce_index: R,
+) -> bool {
+    match tables.simulate_reduce(reduce_index) {
+        Some((states_to_pop, nonterminal_produced)) => {
+            let states_len = states.len() - states_to_pop;
+            states.truncate(states_len);
+            let top = states[states_len - 1];
+            states.push(tables.goto(top, nonterminal_produced));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Runs the automaton over `token_indices` (where `None` is EOF),
+/// updating `states` as it goes. Returns how many of the tokens were
+/// shifted before hitting an error; accepting at EOF counts as
+/// consuming the EOF.
+fn simulate<S: Copy, T: Copy, R, N>(
+    tables: &dyn Tables<S, T, R, N>,
+    states: &mut Vec<S>,
+    token_indices: &[Option<T>],
+) -> usize {
+    let mut shifted = 0;
+    for &opt_token_index in token_indices {
+        loop {
+            let top = states[states.len() - 1];
+            match tables.action(top, opt_token_index) {
+                TableAction::Shift(target_state) => {
+                    states.push(target_state);
+                    shifted += 1;
+                    break;
+                }
+                TableAction::Reduce(reduce_index) => {
+                    if !simulate_reduction(tables, states, reduce_index) {
+                        return shifted + opt_token_index.is_none() as usize;
+                    }
+                }
+                TableAction::Error => return shifted,
+            }
+        }
+    }
+    shifted
+}
+
+/// True if shifting `error_state` onto `states` lets the parser go on
+/// with `opt_token_index`; see `Parser::accepts`.
+fn accepts<S: Copy + Debug, T: Copy + Debug, R, N>(
+    tables: &dyn Tables<S, T, R, N>,
+    error_state: S,
+    states: &[S],
+    opt_token_index: Option<T>,
+) -> bool {
+    debug!(
+        "\\\\\\+ accepts(error_state={:?}, states={:?}, opt_token_index={:?})",
+        error_state, states, opt_token_index,
+    );
+
+    let mut states = states.to_vec();
+    states.push(error_state);
+    loop {
+        let top = states[states.len() - 1];
+        match tables.action(top, opt_token_index) {
+            // If we encounter an error action, we do **not** accept.
+            TableAction::Error => {
+                debug!("\\\\\\\\ accepts: error");
+                return false;
+            }
+
+            // If we encounter a reduce action, we need to simulate its
+            // effect on the state stack.
+            TableAction::Reduce(reduce_index) => {
+                if !simulate_reduction(tables, &mut states, reduce_index) {
+                    debug!("\\\\\\\\ accepts: reduce accepts!");
+                    return true;
+                }
+            }
+
+            // If we encounter a shift action, we DO accept.
+            TableAction::Shift(_) => {
+                debug!("\\\\\\\\ accepts: shift accepts!");
+                return true;
+            }
+        }
+    }
+}
+
+/// A stack of a parser, of states or of symbols; see
+/// `ParserDefinition::Stack`.
+pub trait ParserStack<T>: Default + Deref<Target = [T]> + DerefMut + Extend<T> {
+    fn push(&mut self, value: T);
+
+    fn pop(&mut self) -> Option<T>;
+
+    fn truncate(&mut self, len: usize);
+}
+
+impl<T> ParserStack<T> for Vec<T> {
+    fn push(&mut self, value: T) {
+        Vec::push(self, value)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        Vec::pop(self)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+}
+
+/// A stack that keeps its first 32 entries inline, so that parsing a
+/// small input allocates nothing for it. Parsers generated with the
+/// `inline_stacks` option use it.
+#[cfg(feature = "smallvec")]
+pub type InlineStack<T> = smallvec::SmallVec<[T; 32]>;
+
+#[cfg(feature = "smallvec")]
+impl<T> ParserStack<T> for InlineStack<T> {
+    fn push(&mut self, value: T) {
+        smallvec::SmallVec::push(self, value)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        smallvec::SmallVec::pop(self)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        smallvec::SmallVec::truncate(self, len)
+    }
+}
+
+/// A stack holding a copy of `values`.
+fn stack_of<T: Clone, S: ParserStack<T>>(values: &[T]) -> S {
+    let mut stack = S::default();
+    stack.extend(values.iter().cloned());
+    stack
+}
+
 // These aliases are an elaborate hack to get around
 // the warnings when you define a type alias like `type Foo<D: Trait>`
 #[doc(hidden)]
@@ -178,9 +549,15 @@ pub type Symbol<D> = <D as ParserDefinition>::Symbol;
 
 pub type ParseError<D> = crate::ParseError<Location<D>, Token<D>, Error<D>>;
 pub type ParseResult<D> = Result<Success<D>, ParseError<D>>;
+pub type PrefixResult<D, I> = Result<(Success<D>, Location<D>, Remaining<I>), ParseError<D>>;
 pub type TokenTriple<D> = (Location<D>, Token<D>, Location<D>);
 pub type SymbolTriple<D> = (Location<D>, Symbol<D>, Location<D>);
 pub type ErrorRecovery<D> = crate::ErrorRecovery<Location<D>, Token<D>, Error<D>>;
+pub type Repair<D> = crate::Repair<Location<D>, Token<D>, Error<D>>;
+pub type Edit<D> = crate::Edit<Location<D>, Token<D>>;
+pub type ParseStatus<D> = crate::ParseStatus<Success<D>, Location<D>, Token<D>, Error<D>>;
+pub type ParseEvent<D> = crate::ParseEvent<Location<D>, Token<D>>;
+type NoTokens<D> = core::iter::Empty<Result<TokenTriple<D>, ParseError<D>>>;
 
 pub struct Parser<D, I>
 where
@@ -188,10 +565,658 @@ where
     I: Iterator<Item = Result<TokenTriple<D>, ParseError<D>>>,
 {
     definition: D,
-    tokens: I,
-    states: Vec<D::StateIndex>,
-    symbols: Vec<SymbolTriple<D>>,
+
+    /// The input, fused: it need not be, and reading ahead (for error
+    /// repair or backtracking) or handing the rest of it back after a
+    /// prefix parse could otherwise ask it for a token after its end.
+    tokens: Fuse<I>,
+    states: D::Stack<D::StateIndex>,
+    symbols: D::Stack<SymbolTriple<D>>,
     last_location: D::Location,
+
+    /// Tokens that were read ahead of the parser (by error repair),
+    /// or pushed back into the input. `None` stands for EOF.
+    lookahead: VecDeque<Option<I::Item>>,
+
+    /// The repairs made so far, if error repair is enabled.
+    repairs: Option<Vec<Repair<D>>>,
+
+    /// True if we should stop at the first token that cannot continue
+    /// a complete parse, rather than report it as an error.
+    prefix: bool,
+
+    /// The state in which the last syntax error was found.
+    error_state: Option<D::StateIndex>,
+
+    /// The checkpoints of a `ResumableParser`, if it has taken any.
+    checkpoints: Option<Checkpoints<D>>,
+
+    /// Where shifts and reductions are recorded, if the parse is
+    /// traced.
+    tracing: Option<Tracing<D>>,
+}
+
+/// The trace of a parser, and how to number the indices of its tables;
+/// they are only known to be numbers where the trace is requested.
+struct Tracing<D: ParserDefinition> {
+    trace: Trace<D::Location>,
+    state: fn(D::StateIndex) -> u32,
+    token: fn(D::TokenIndex) -> u32,
+    reduction: fn(D::ReduceIndex) -> u32,
+}
+
+impl<D: ParserDefinition> Clone for Tracing<D> {
+    fn clone(&self) -> Self {
+        Tracing {
+            trace: self.trace.clone(),
+            state: self.state,
+            token: self.token,
+            reduction: self.reduction,
+        }
+    }
+}
+
+/// The checkpoints of a parser, oldest first. Taking one copies
+/// nothing: the entries of the stacks are only copied into it when a
+/// reduction is about to pop them from below the height they had.
+struct Checkpoints<D: ParserDefinition> {
+    saved: Vec<Saved<D>>,
+
+    /// The number of checkpoints taken so far, to tell a checkpoint
+    /// from an older one that had the same place in `saved`.
+    taken: usize,
+
+    /// Copies a symbol; the symbols are only known to be `Clone`
+    /// where the checkpoint is taken.
+    clone_symbol: fn(&SymbolTriple<D>) -> SymbolTriple<D>,
+}
+
+/// What it takes to put the parser back as it was at a checkpoint.
+struct Saved<D: ParserDefinition> {
+    serial: usize,
+
+    /// The lowest height the symbol stack has had since the
+    /// checkpoint; the entries below it are as they were.
+    floor: usize,
+
+    /// The entries popped from below the height of the stacks at the
+    /// checkpoint, topmost first.
+    popped: Vec<(D::StateIndex, SymbolTriple<D>)>,
+
+    last_location: D::Location,
+    error_state: Option<D::StateIndex>,
+}
+
+impl<D> Clone for Checkpoints<D>
+where
+    D: ParserDefinition,
+    D::Symbol: Clone,
+{
+    fn clone(&self) -> Self {
+        Checkpoints {
+            saved: self
+                .saved
+                .iter()
+                .map(|saved| Saved {
+                    serial: saved.serial,
+                    floor: saved.floor,
+                    popped: saved.popped.clone(),
+                    last_location: saved.last_location.clone(),
+                    error_state: saved.error_state,
+                })
+                .collect(),
+            taken: self.taken,
+            clone_symbol: self.clone_symbol,
+        }
+    }
+}
+
+impl<D, I> Clone for Parser<D, I>
+where
+    D: ParserDefinition + Clone,
+    D::Symbol: Clone,
+    D::Error: Clone,
+    I: Iterator<Item = Result<TokenTriple<D>, ParseError<D>>> + Clone,
+{
+    fn clone(&self) -> Self {
+        Parser {
+            definition: self.definition.clone(),
+            tokens: self.tokens.clone(),
+            states: stack_of(&self.states),
+            symbols: stack_of(&self.symbols),
+            last_location: self.last_location.clone(),
+            lookahead: self.lookahead.clone(),
+            repairs: self.repairs.clone(),
+            prefix: self.prefix,
+            error_state: self.error_state,
+            checkpoints: self.checkpoints.clone(),
+            tracing: self.tracing.clone(),
+        }
+    }
+}
+
+/// A parser that is fed one token at a time, so that it can be
+/// suspended between tokens (e.g. while waiting for more input to
+/// arrive) rather than pulling them from an iterator.
+///
+/// Error recovery only applies at the end of the input: a token that
+/// cannot be parsed is reported by `feed` right away.
+///
+/// If the values of the symbols can be cloned, so can the parser, so
+/// that the caller can take a snapshot of it, try to feed it some
+/// tokens, and go back to the snapshot if they do not parse.
+pub struct ResumableParser<D: ParserDefinition> {
+    parser: Parser<D, NoTokens<D>>,
+}
+
+impl<D> Clone for ResumableParser<D>
+where
+    D: ParserDefinition + Clone,
+    D::Symbol: Clone,
+    D::Error: Clone,
+{
+    fn clone(&self) -> Self {
+        ResumableParser {
+            parser: self.parser.clone(),
+        }
+    }
+}
+
+impl<D: ParserDefinition> ResumableParser<D> {
+    pub fn new(definition: D) -> Self {
+        ResumableParser {
+            parser: Parser::new(definition, core::iter::empty()),
+        }
+    }
+
+    /// Parses the next token of the input. Returns `Pending` once the
+    /// token is shifted; since the parse can only be accepted at the
+    /// end of the input, this never returns `Accepted`. After an
+    /// error, the parser should not be fed any more tokens.
+    pub fn feed(&mut self, token: TokenTriple<D>) -> ParseStatus<D> {
+        let parser = &mut self.parser;
+        parser.last_location = token.2.clone();
+        let token_index = match parser.definition.token_to_index(&token.1) {
+            Some(i) => i,
+            None => {
+                let top_state = parser.top_state();
+                return crate::ParseStatus::Error(
+                    parser.unrecognized_token_error(Some(token), top_state),
+                );
+            }
+        };
+        match parser.step(Some(token), Some(token_index)) {
+            Step::Shifted => crate::ParseStatus::Pending,
+            Step::Done(Ok(_)) => unreachable!("accepted before EOF"),
+            Step::Done(Err(e)) => crate::ParseStatus::Error(e),
+            Step::Error(l) => {
+                let top_state = parser.top_state();
+                crate::ParseStatus::Error(parser.unrecognized_token_error(l, top_state))
+            }
+        }
+    }
+
+    /// Signals the end of the input, returning `Accepted` or `Error`.
+    pub fn finish(mut self) -> ParseStatus<D> {
+        // with no tokens left, this goes straight to the end of input
+        match self.parser.parse() {
+            Ok(value) => crate::ParseStatus::Accepted(value),
+            Err(e) => crate::ParseStatus::Error(e),
+        }
+    }
+}
+
+impl<D> ResumableParser<D>
+where
+    D: ParserDefinition,
+    D::Symbol: Clone,
+{
+    /// Takes a checkpoint of the parser, which `rollback` can put it
+    /// back to. This takes constant time: the stacks of the parser
+    /// are only copied as far as the tokens fed after the checkpoint
+    /// reduce what was on them before it.
+    ///
+    /// Checkpoints nest: rolling back to a checkpoint, or releasing
+    /// it, does the same to the checkpoints taken after it.
+    pub fn checkpoint(&mut self) -> crate::Checkpoint {
+        let parser = &mut self.parser;
+        let checkpoints = parser.checkpoints.get_or_insert_with(|| Checkpoints {
+            saved: vec![],
+            taken: 0,
+            clone_symbol: Clone::clone,
+        });
+        checkpoints.taken += 1;
+        checkpoints.saved.push(Saved {
+            serial: checkpoints.taken,
+            floor: parser.symbols.len(),
+            popped: vec![],
+            last_location: parser.last_location.clone(),
+            error_state: parser.error_state,
+        });
+        crate::Checkpoint {
+            index: checkpoints.saved.len() - 1,
+            serial: checkpoints.taken,
+        }
+    }
+
+    /// Puts the parser back as it was when `checkpoint` was taken, as
+    /// if none of the tokens fed since had been. This also works after
+    /// `feed` reported an error.
+    ///
+    /// # Panics
+    ///
+    /// If `checkpoint` was already rolled back to or released.
+    pub fn rollback(&mut self, checkpoint: crate::Checkpoint) {
+        let parser = &mut self.parser;
+        let saved = Self::take_saved(&mut parser.checkpoints, checkpoint);
+        parser.states.truncate(saved.floor + 1);
+        parser.symbols.truncate(saved.floor);
+        for (state, symbol) in saved.popped.into_iter().rev() {
+            parser.states.push(state);
+            parser.symbols.push(symbol);
+        }
+        parser.last_location = saved.last_location;
+        parser.error_state = saved.error_state;
+    }
+
+    /// Forgets `checkpoint`, when the tokens fed since are there to
+    /// stay, so that the parser no longer copies its stacks for it.
+    ///
+    /// # Panics
+    ///
+    /// As for `rollback`.
+    pub fn release(&mut self, checkpoint: crate::Checkpoint) {
+        Self::take_saved(&mut self.parser.checkpoints, checkpoint);
+    }
+
+    /// Removes `checkpoint`, and the ones taken after it.
+    fn take_saved(
+        checkpoints: &mut Option<Checkpoints<D>>,
+        checkpoint: crate::Checkpoint,
+    ) -> Saved<D> {
+        match *checkpoints {
+            Some(ref mut checkpoints)
+                if checkpoints
+                    .saved
+                    .get(checkpoint.index)
+                    .is_some_and(|saved| saved.serial == checkpoint.serial) =>
+            {
+                checkpoints.saved.truncate(checkpoint.index + 1);
+                checkpoints.saved.pop().unwrap()
+            }
+            _ => panic!("checkpoint was rolled back or released already"),
+        }
+    }
+}
+
+/// Wraps a parser definition so that, instead of running the user's
+/// actions, it produces the `ParseEvent`s of the parse. Each symbol
+/// on the stack holds the events of its subtree.
+///
+/// Error recovery is not used: since the events do not contain any
+/// values, there is nothing to hold the `!` symbol.
+pub struct Events<D: ParserDefinition> {
+    definition: D,
+}
+
+/// A symbol on the stack of `Events`. Shifted tokens are turned into
+/// events once they are reduced, since only then are their locations
+/// known.
+pub enum EventSymbol<D: ParserDefinition> {
+    Token(D::Token),
+    Events(VecDeque<ParseEvent<D>>),
+}
+
+/// Wraps a parser definition so that it only checks whether the input
+/// can be parsed: no actions are run, and the symbols on the stack
+/// hold nothing but their spans. Error recovery is not used.
+pub struct Recognizer<D: ParserDefinition> {
+    definition: D,
+}
+
+/// The action of a definition that wraps another one, like `Events`:
+/// simply the action of the wrapped definition.
+pub struct WrappedAction<D: ParserDefinition>(D::Action);
+
+impl<D: ParserDefinition> Clone for WrappedAction<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D: ParserDefinition> Copy for WrappedAction<D> {}
+
+impl<D: ParserDefinition> Debug for WrappedAction<D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<D, W> ParserAction<W> for WrappedAction<D>
+where
+    D: ParserDefinition,
+    W: ParserDefinition<StateIndex = D::StateIndex, ReduceIndex = D::ReduceIndex>,
+{
+    fn as_shift(self) -> Option<D::StateIndex> {
+        self.0.as_shift()
+    }
+
+    fn as_reduce(self) -> Option<D::ReduceIndex> {
+        self.0.as_reduce()
+    }
+
+    fn is_shift(self) -> bool {
+        self.0.is_shift()
+    }
+
+    fn is_reduce(self) -> bool {
+        self.0.is_reduce()
+    }
+
+    fn is_error(self) -> bool {
+        self.0.is_error()
+    }
+}
+
+/// The methods of a `ParserDefinition` that wraps the one in
+/// `self.definition` and only changes what is on the stack.
+macro_rules! wrapped_definition {
+    () => {
+        type Location = D::Location;
+        type Error = D::Error;
+        type Token = D::Token;
+        type TokenIndex = D::TokenIndex;
+        type StateIndex = D::StateIndex;
+        type Action = WrappedAction<D>;
+        type ReduceIndex = D::ReduceIndex;
+        type NonterminalIndex = D::NonterminalIndex;
+        type Stack<T> = D::Stack<T>;
+
+        fn start_location(&self) -> Self::Location {
+            self.definition.start_location()
+        }
+
+        fn start_state(&self) -> Self::StateIndex {
+            self.definition.start_state()
+        }
+
+        fn token_to_index(&self, token: &Self::Token) -> Option<Self::TokenIndex> {
+            self.definition.token_to_index(token)
+        }
+
+        fn action(&self, state: Self::StateIndex, token_index: Self::TokenIndex) -> Self::Action {
+            WrappedAction(self.definition.action(state, token_index))
+        }
+
+        fn error_action(&self, state: Self::StateIndex) -> Self::Action {
+            WrappedAction(self.definition.error_action(state))
+        }
+
+        fn eof_action(&self, state: Self::StateIndex) -> Self::Action {
+            WrappedAction(self.definition.eof_action(state))
+        }
+
+        fn goto(&self, state: Self::StateIndex, nt: Self::NonterminalIndex) -> Self::StateIndex {
+            self.definition.goto(state, nt)
+        }
+
+        fn expected_tokens(&self, state: Self::StateIndex) -> Vec<String> {
+            self.definition.expected_tokens(state)
+        }
+
+        fn uses_error_recovery(&self) -> bool {
+            false
+        }
+
+        fn error_recovery_symbol(&self, _: ErrorRecovery<Self>) -> Self::Symbol {
+            unreachable!("error recovery is not used by wrapped definitions")
+        }
+
+        fn alternative_actions(
+            &self,
+            state: Self::StateIndex,
+            token_index: Option<Self::TokenIndex>,
+        ) -> Vec<Self::Action> {
+            self.definition
+                .alternative_actions(state, token_index)
+                .into_iter()
+                .map(WrappedAction)
+                .collect()
+        }
+
+        fn simulate_reduce(&self, action: Self::ReduceIndex) -> SimulatedReduce<Self> {
+            match self.definition.simulate_reduce(action) {
+                SimulatedReduce::Reduce {
+                    states_to_pop,
+                    nonterminal_produced,
+                } => SimulatedReduce::Reduce {
+                    states_to_pop,
+                    nonterminal_produced,
+                },
+                SimulatedReduce::Accept => SimulatedReduce::Accept,
+            }
+        }
+    };
+}
+
+/// Performs a reduction of `states_to_pop` symbols to the nonterminal
+/// `produced` on behalf of a wrapped definition: pops the symbols and
+/// their states, and pushes the state we go to. Returns the popped
+/// symbols and the span of the nonterminal; the caller pushes the
+/// symbol for it.
+pub(crate) fn pop_reduction<D: ParserDefinition>(
+    definition: &D,
+    states_to_pop: usize,
+    produced: D::NonterminalIndex,
+    start_location: Option<&D::Location>,
+    states: &mut D::Stack<D::StateIndex>,
+    symbols: &mut D::Stack<SymbolTriple<D>>,
+) -> (D::Location, Vec<SymbolTriple<D>>, D::Location) {
+    let mut children = Vec::with_capacity(states_to_pop);
+    for _ in 0..states_to_pop {
+        children.push(symbols.pop().unwrap());
+    }
+    children.reverse();
+    states.truncate(states.len() - states_to_pop);
+    let (start, end) = match (children.first(), children.last()) {
+        (Some(first), Some(last)) => (first.0.clone(), last.2.clone()),
+        _ => {
+            let location = start_location
+                .cloned()
+                .or_else(|| symbols.last().map(|s| s.2.clone()))
+                .unwrap_or_else(|| definition.start_location());
+            (location.clone(), location)
+        }
+    };
+    let state = definition.goto(*states.last().unwrap(), produced);
+    states.push(state);
+    (start, children, end)
+}
+
+impl<D: ParserDefinition> ParserDefinition for Events<D> {
+    wrapped_definition!();
+
+    type Symbol = EventSymbol<D>;
+    type Success = Vec<ParseEvent<D>>;
+
+    fn token_to_symbol(&self, _: Self::TokenIndex, token: Self::Token) -> Self::Symbol {
+        EventSymbol::Token(token)
+    }
+
+    fn reduce(
+        &mut self,
+        action: Self::ReduceIndex,
+        start_location: Option<&Self::Location>,
+        states: &mut Self::Stack<Self::StateIndex>,
+        symbols: &mut Self::Stack<SymbolTriple<Self>>,
+    ) -> Option<ParseResult<Self>> {
+        let (states_to_pop, nonterminal_produced) = match self.definition.simulate_reduce(action) {
+            SimulatedReduce::Reduce {
+                states_to_pop,
+                nonterminal_produced,
+            } => (states_to_pop, nonterminal_produced),
+            SimulatedReduce::Accept => {
+                return match symbols.pop() {
+                    Some((_, EventSymbol::Events(events), _)) => Some(Ok(Vec::from(events))),
+                    _ => unreachable!("accepted a token"),
+                };
+            }
+        };
+
+        let (start, children, end) = pop_reduction(
+            self,
+            states_to_pop,
+            nonterminal_produced,
+            start_location,
+            states,
+            symbols,
+        );
+        let mut children: Vec<VecDeque<ParseEvent<D>>> = children
+            .into_iter()
+            .map(|(start, symbol, end)| match symbol {
+                EventSymbol::Token(token) => {
+                    let mut events = VecDeque::new();
+                    events.push_back(crate::ParseEvent::Token(start, token, end));
+                    events
+                }
+                EventSymbol::Events(events) => events,
+            })
+            .collect();
+
+        // move the events of the other children into the largest one,
+        // so that long lists are not copied at each reduction
+        let largest = (0..children.len())
+            .max_by_key(|&i| children[i].len())
+            .unwrap_or(0);
+        let mut events = match children.get_mut(largest) {
+            Some(child) => core::mem::take(child),
+            None => VecDeque::new(),
+        };
+        for child in children[..largest].iter_mut().rev() {
+            while let Some(event) = child.pop_back() {
+                events.push_front(event);
+            }
+        }
+        for child in children.iter_mut().skip(largest + 1) {
+            events.append(child);
+        }
+
+        let (nonterminal, production) = self.definition.reduction_names(action);
+        events.push_front(crate::ParseEvent::StartNonterminal(nonterminal));
+        events.push_back(crate::ParseEvent::EndNonterminal(production));
+        symbols.push((start, EventSymbol::Events(events), end));
+        None
+    }
+}
+
+impl<D: ParserDefinition> ParserDefinition for Recognizer<D> {
+    wrapped_definition!();
+
+    type Symbol = ();
+    type Success = ();
+
+    fn token_to_symbol(&self, _: Self::TokenIndex, _: Self::Token) -> Self::Symbol {}
+
+    fn reduce(
+        &mut self,
+        action: Self::ReduceIndex,
+        start_location: Option<&Self::Location>,
+        states: &mut Self::Stack<Self::StateIndex>,
+        symbols: &mut Self::Stack<SymbolTriple<Self>>,
+    ) -> Option<ParseResult<Self>> {
+        match self.definition.simulate_reduce(action) {
+            SimulatedReduce::Reduce {
+                states_to_pop,
+                nonterminal_produced,
+            } => {
+                let (start, _, end) = pop_reduction(
+                    self,
+                    states_to_pop,
+                    nonterminal_produced,
+                    start_location,
+                    states,
+                    symbols,
+                );
+                symbols.push((start, (), end));
+                None
+            }
+            SimulatedReduce::Accept => Some(Ok(())),
+        }
+    }
+}
+
+/// The input left over after `Parser::drive_prefix`: the token the
+/// parser stopped at, followed by the rest of the token iterator.
+pub struct Remaining<I: Iterator> {
+    buffered: VecDeque<Option<I::Item>>,
+    tokens: Fuse<I>,
+}
+
+impl<I: Iterator> Remaining<I> {
+    /// Returns the next token without consuming it.
+    pub fn peek(&mut self) -> Option<&I::Item> {
+        if self.buffered.is_empty() {
+            let token = self.tokens.next();
+            self.buffered.push_back(token);
+        }
+        self.buffered.front().and_then(|token| token.as_ref())
+    }
+}
+
+impl<I: Iterator> Iterator for Remaining<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        match self.buffered.pop_front() {
+            Some(token) => token,
+            None => self.tokens.next(),
+        }
+    }
+}
+
+/// The tokens of `Parser::drive_cancellable`: asks `cancelled` before
+/// handing out each token and, once it says yes, records where in
+/// `stopped_at` and ends with an error so that the parse stops at once.
+struct Cancellable<'c, D: ParserDefinition, I> {
+    tokens: I,
+    cancelled: &'c dyn Fn() -> bool,
+    last_location: Location<D>,
+    stopped_at: &'c Cell<Option<Location<D>>>,
+    done: bool,
+}
+
+impl<'c, D, I> Iterator for Cancellable<'c, D, I>
+where
+    D: ParserDefinition,
+    I: Iterator<Item = Result<TokenTriple<D>, ParseError<D>>>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.done {
+            return None;
+        }
+        if (self.cancelled)() {
+            self.done = true;
+            self.stopped_at.set(Some(self.last_location.clone()));
+            return Some(Err(crate::ParseError::UnrecognizedEOF {
+                location: self.last_location.clone(),
+                expected: vec![],
+            }));
+        }
+        let token = self.tokens.next();
+        if let Some(Ok((_, _, ref end))) = token {
+            self.last_location = end.clone();
+        }
+        token
+    }
+}
+
+/// The outcome of `Parser::step`.
+enum Step<D: ParserDefinition> {
+    Shifted,
+    Done(ParseResult<D>),
+    Error(Option<TokenTriple<D>>),
 }
 
 enum NextToken<D: ParserDefinition> {
@@ -200,22 +1225,219 @@ enum NextToken<D: ParserDefinition> {
     Done(ParseResult<D>),
 }
 
+/// An edit considered by error repair, at a position among the
+/// tokens following the error (0 being the token we failed on).
+enum PlannedEdit<D: ParserDefinition> {
+    Delete(usize),
+    Insert(usize, D::Token),
+    Replace(usize, D::Token),
+}
+
+/// A sequence of edits together with how many of the following
+/// tokens the parser gets through once they are applied, whether that
+/// is all of the tokens searched, and what the edits cost.
+struct Plan<D: ParserDefinition> {
+    progress: usize,
+    complete: bool,
+    cost: u32,
+    edits: Vec<PlannedEdit<D>>,
+}
+
+impl<D: ParserDefinition> PlannedEdit<D> {
+    fn position(&self) -> usize {
+        match *self {
+            PlannedEdit::Delete(p) | PlannedEdit::Insert(p, _) | PlannedEdit::Replace(p, _) => p,
+        }
+    }
+}
+
+impl<D: ParserDefinition> Plan<D> {
+    fn better_than(&self, other: &Plan<D>, strategy: RepairStrategy) -> bool {
+        if strategy == RepairStrategy::MinimumCost && (self.complete || other.complete) {
+            return self.complete
+                && (!other.complete
+                    || self.cost < other.cost
+                    || (self.cost == other.cost && self.progress > other.progress));
+        }
+        self.progress > other.progress
+            || (self.progress == other.progress && self.cost < other.cost)
+    }
+}
+
 impl<D, I> Parser<D, I>
 where
     D: ParserDefinition,
     I: Iterator<Item = Result<TokenTriple<D>, ParseError<D>>>,
 {
-    pub fn drive(definition: D, tokens: I) -> ParseResult<D> {
+    fn new(definition: D, tokens: I) -> Self {
         let last_location = definition.start_location();
         let start_state = definition.start_state();
         Parser {
             definition,
+            tokens: tokens.fuse(),
+            states: stack_of(&[start_state]),
+            symbols: D::Stack::default(),
+            last_location,
+            lookahead: VecDeque::new(),
+            repairs: None,
+            prefix: false,
+            error_state: None,
+            checkpoints: None,
+            tracing: None,
+        }
+    }
+
+    pub fn drive(definition: D, tokens: I) -> ParseResult<D> {
+        Parser::new(definition, tokens).parse()
+    }
+
+    /// Like `drive`, but on a syntax error first tries to repair the
+    /// input by deleting the offending token or inserting a missing
+    /// one. Each repair is appended to `repairs`; errors that cannot
+    /// be repaired fall back to the usual error recovery.
+    pub fn drive_repairing(
+        definition: D,
+        tokens: I,
+        repairs: &mut Vec<Repair<D>>,
+    ) -> ParseResult<D> {
+        let mut parser = Parser::new(definition, tokens);
+        parser.repairs = Some(vec![]);
+        let result = parser.parse();
+        repairs.extend(parser.repairs.take().unwrap_or_default());
+        result
+    }
+
+    /// Like `drive`, but records each token shifted and each reduction
+    /// made in `trace`, after the entries it already has.
+    pub fn drive_traced(definition: D, tokens: I, trace: &mut Trace<Location<D>>) -> ParseResult<D>
+    where
+        D::StateIndex: TraceIndex,
+        D::TokenIndex: TraceIndex,
+        D::ReduceIndex: TraceIndex,
+    {
+        let mut parser = Parser::new(definition, tokens);
+        parser.tracing = Some(Tracing {
+            trace: core::mem::take(trace),
+            state: TraceIndex::trace_index,
+            token: TraceIndex::trace_index,
+            reduction: TraceIndex::trace_index,
+        });
+        let result = parser.parse();
+        if let Some(tracing) = parser.tracing.take() {
+            *trace = tracing.trace;
+        }
+        result
+    }
+
+    /// Like `drive`, but parses only the longest prefix of the input
+    /// that is complete: instead of reporting an error at a token
+    /// that cannot continue the parse, it stops before that token if
+    /// the tokens read so far can be accepted. Note that the parser
+    /// does not backtrack; if it has to reject a token after reading
+    /// past the end of a complete prefix, that is still an error.
+    ///
+    /// Returns the parsed value, the end of the last token that is
+    /// part of it, and the remaining input.
+    pub fn drive_prefix(definition: D, tokens: I) -> PrefixResult<D, I> {
+        let mut parser = Parser::new(definition, tokens);
+        parser.prefix = true;
+        let value = parser.parse()?;
+        let remaining = Remaining {
+            buffered: parser.lookahead,
+            tokens: parser.tokens,
+        };
+        Ok((value, parser.last_location, remaining))
+    }
+
+    /// Like `drive`, but calls `cancelled` before reading each token,
+    /// and stops with `Err(Cancelled)` as soon as it returns `true`.
+    /// Error recovery does not apply to a cancelled parse.
+    pub fn drive_cancellable(
+        definition: D,
+        tokens: I,
+        cancelled: &dyn Fn() -> bool,
+    ) -> Result<ParseResult<D>, crate::Cancelled<Location<D>>> {
+        let last_location = definition.start_location();
+        let stopped_at = Cell::new(None);
+        let tokens = Cancellable::<D, I> {
             tokens,
-            states: vec![start_state],
-            symbols: vec![],
+            cancelled,
             last_location,
+            stopped_at: &stopped_at,
+            done: false,
+        };
+        let result = Parser::new(definition, tokens).parse();
+        match stopped_at.take() {
+            Some(location) => Err(crate::Cancelled { location }),
+            None => Ok(result),
+        }
+    }
+
+    /// Like `drive`, but instead of running the actions, returns the
+    /// events of the parse: a `StartNonterminal` and `EndNonterminal`
+    /// around the events of each nonterminal, and a `Token` for each
+    /// token. Errors are reported as usual, but without error
+    /// recovery.
+    pub fn drive_events(definition: D, tokens: I) -> Result<Vec<ParseEvent<D>>, ParseError<D>> {
+        Parser::drive(Events { definition }, tokens)
+    }
+
+    /// Like `drive`, but only checks whether the tokens can be parsed,
+    /// without running the actions. Errors are reported as usual, but
+    /// without error recovery.
+    pub fn drive_recognize(definition: D, tokens: I) -> Result<(), ParseError<D>> {
+        Parser::drive(Recognizer { definition }, tokens)
+    }
+
+    /// Parses the tokens as the beginning of the input, and returns
+    /// the terminals that could legally come next. Unlike
+    /// `expected_tokens`, this accounts for any reductions that the
+    /// next token would trigger, so every terminal returned can really
+    /// be shifted. Errors in the tokens are reported as usual, but
+    /// without error recovery.
+    pub fn drive_completions(definition: D, tokens: I) -> Result<Vec<String>, ParseError<D>> {
+        let mut parser = Parser::new(definition, tokens);
+        loop {
+            let (lookahead, token_index) = match parser.next_token() {
+                NextToken::FoundToken(l, i) => (l, i),
+                NextToken::EOF => break,
+                NextToken::Done(e) => return e.map(|_| vec![]),
+            };
+            match parser.step(Some(lookahead), Some(token_index)) {
+                Step::Shifted => {}
+                Step::Done(result) => return result.map(|_| vec![]),
+                Step::Error(l) => {
+                    return Err(parser.unrecognized_token_error(l, parser.top_state()))
+                }
+            }
         }
-        .parse()
+        Ok(parser
+            .definition
+            .terminals()
+            .into_iter()
+            .filter(|&(index, _)| parser.simulate(&mut parser.states.to_vec(), &[Some(index)]) == 1)
+            .map(|(_, name)| String::from(name))
+            .collect())
+    }
+
+    /// Like `drive`, but on a syntax error also returns the message
+    /// that the grammar's `.messages` file gives for the state in which
+    /// the error was found, if any. Other errors have no message.
+    pub fn drive_messages(
+        definition: D,
+        tokens: I,
+    ) -> Result<D::Success, (ParseError<D>, Option<&'static str>)> {
+        let mut parser = Parser::new(definition, tokens);
+        parser.parse().map_err(|error| {
+            let message = match error {
+                crate::ParseError::UnrecognizedToken { .. }
+                | crate::ParseError::UnrecognizedEOF { .. } => parser
+                    .error_state
+                    .and_then(|state| parser.definition.error_message(state)),
+                _ => None,
+            };
+            (error, message)
+        })
     }
 
     fn top_state(&self) -> D::StateIndex {
@@ -224,13 +1446,13 @@ where
 
     fn parse(&mut self) -> ParseResult<D> {
         // Outer loop: each time we continue around this loop, we
-        // shift a new token from the input. We break from the loop
-        // when the end of the input is reached (we return early if an
-        // error occurs).
+        // shift a new token from the input, or reach the end of the
+        // input, which has no lookahead and no token index. We return
+        // once the input is accepted or an error occurs.
         'shift: loop {
             let (mut lookahead, mut token_index) = match self.next_token() {
-                NextToken::FoundToken(l, i) => (l, i),
-                NextToken::EOF => return self.parse_eof(),
+                NextToken::FoundToken(l, i) => (Some(l), Some(i)),
+                NextToken::EOF => (None, None),
                 NextToken::Done(e) => return e,
             };
 
@@ -238,68 +1460,111 @@ where
 
             debug!("\\ token_index: {:?}", token_index);
 
-            'inner: loop {
-                let top_state = self.top_state();
-                let action = self.definition.action(top_state, token_index);
-                debug!("\\ action: {:?}", action);
-
-                if let Some(target_state) = action.as_shift() {
-                    debug!("\\ shift to: {:?}", target_state);
-
-                    // Shift and transition to state `action - 1`
-                    let symbol = self.definition.token_to_symbol(token_index, lookahead.1);
-                    self.states.push(target_state);
-                    self.symbols.push((lookahead.0, symbol, lookahead.2));
-                    continue 'shift;
-                } else if let Some(reduce_index) = action.as_reduce() {
-                    debug!("\\ reduce to: {:?}", reduce_index);
-
-                    if let Some(r) = self.reduce(reduce_index, Some(&lookahead.0)) {
-                        return match r {
-                            // we reached eof, but still have lookahead
-                            Ok(_) => Err(crate::ParseError::ExtraToken { token: lookahead }),
-                            Err(e) => Err(e),
-                        };
+            loop {
+                match self.step(lookahead, token_index) {
+                    Step::Shifted => continue 'shift,
+                    Step::Done(result) => return result,
+                    Step::Error(Some(l)) if self.can_stop_prefix() => {
+                        debug!("\\ error -- stopping after prefix");
+                        self.stop_prefix(Ok(l));
+                        lookahead = None;
+                        token_index = None;
                     }
-                } else {
-                    debug!("\\ error -- initiating error recovery!");
-
-                    match self.error_recovery(Some(lookahead), Some(token_index)) {
-                        NextToken::FoundToken(l, i) => {
-                            lookahead = l;
-                            token_index = i;
-                            continue 'inner;
+                    Step::Error(l) => {
+                        debug!("\\ error -- initiating error recovery!");
+
+                        let at_eof = l.is_none();
+                        match self.error_recovery(l, token_index) {
+                            // error repair inserted a token before EOF;
+                            // shift it, and we will find the EOF again
+                            NextToken::FoundToken(token, _) if at_eof && self.repairs.is_some() => {
+                                self.lookahead.push_front(Some(Ok(token)));
+                                continue 'shift;
+                            }
+                            NextToken::FoundToken(..) if at_eof => {
+                                panic!("cannot find token at EOF")
+                            }
+                            NextToken::FoundToken(l, i) => {
+                                lookahead = Some(l);
+                                token_index = Some(i);
+                            }
+                            NextToken::EOF => {
+                                lookahead = None;
+                                token_index = None;
+                            }
+                            NextToken::Done(e) => return e,
                         }
-                        NextToken::EOF => return self.parse_eof(),
-                        NextToken::Done(e) => return e,
                     }
                 }
             }
         }
     }
 
-    /// Invoked when we have no more tokens to consume.
-    fn parse_eof(&mut self) -> ParseResult<D> {
+    /// Performs reductions triggered by `lookahead` until it is
+    /// either shifted or rejected. A `lookahead` of `None` is the end
+    /// of the input, which is never shifted: the parse is done once
+    /// the start symbol is reduced.
+    fn step(
+        &mut self,
+        lookahead: Option<TokenTriple<D>>,
+        token_index: Option<D::TokenIndex>,
+    ) -> Step<D> {
         loop {
             let top_state = self.top_state();
-            let action = self.definition.eof_action(top_state);
-            if let Some(reduce_index) = action.as_reduce() {
-                if let Some(result) =
-                    self.definition
-                        .reduce(reduce_index, None, &mut self.states, &mut self.symbols)
-                {
-                    return result;
+            let action = match token_index {
+                Some(i) => self.definition.action(top_state, i),
+                None => self.definition.eof_action(top_state),
+            };
+            let action = match self.choose_action(top_state, token_index, action) {
+                Some(action) => action,
+                None => return Step::Error(lookahead),
+            };
+            debug!("\\ action: {:?}", action);
+
+            if let Some(target_state) = action.as_shift() {
+                debug!("\\ shift to: {:?}", target_state);
+
+                let lookahead = lookahead.expect("shift at EOF");
+                if let Some(max) = self.definition.max_stack_depth() {
+                    if self.states.len() >= max {
+                        return Step::Done(Err(crate::ParseError::User {
+                            error: self.definition.stack_overflow(lookahead.0),
+                        }));
+                    }
                 }
-            } else {
-                match self.error_recovery(None, None) {
-                    NextToken::FoundToken(..) => panic!("cannot find token at EOF"),
-                    NextToken::Done(e) => return e,
-                    NextToken::EOF => continue,
+
+                // Shift and transition to state `action - 1`
+                let token_index = token_index.unwrap();
+                if let Some(tracing) = &mut self.tracing {
+                    tracing.trace.record(TraceEntry::Shift {
+                        state: (tracing.state)(target_state),
+                        token: (tracing.token)(token_index),
+                        start: lookahead.0.clone(),
+                        end: lookahead.2.clone(),
+                    });
+                }
+                let symbol = self.definition.token_to_symbol(token_index, lookahead.1);
+                self.states.push(target_state);
+                self.symbols.push((lookahead.0, symbol, lookahead.2));
+                return Step::Shifted;
+            } else if let Some(reduce_index) = action.as_reduce() {
+                debug!("\\ reduce to: {:?}", reduce_index);
+
+                if let Some(r) = self.reduce(reduce_index, lookahead.as_ref().map(|l| &l.0)) {
+                    return Step::Done(match (r, lookahead) {
+                        // we reached eof, but still have lookahead
+                        (Ok(_), Some(token)) => Err(crate::ParseError::ExtraToken { token }),
+                        (r, _) => r,
+                    });
                 }
+            } else {
+                return Step::Error(lookahead);
             }
         }
     }
 
+    #[cold]
+    #[inline(never)]
     fn error_recovery(
         &mut self,
         mut opt_lookahead: Option<TokenTriple<D>>,
@@ -309,6 +1574,13 @@ where
             "\\+ error_recovery(opt_lookahead={:?}, opt_token_index={:?})",
             opt_lookahead, opt_token_index,
         );
+        self.error_state = Some(self.top_state());
+
+        if self.repairs.is_some() {
+            if let Some(edits) = self.find_repair(opt_token_index) {
+                return self.apply_repair(edits, opt_lookahead);
+            }
+        }
 
         if !self.definition.uses_error_recovery() {
             debug!("\\ error -- no error recovery!");
@@ -350,7 +1622,7 @@ where
             debug!(
                 "\\\\+ error_recovery: find_state loop, {:?} states = {:?}",
                 self.states.len(),
-                self.states,
+                &self.states[..],
             );
 
             for top in (0..states_len).rev() {
@@ -529,54 +1801,376 @@ where
         states: &[D::StateIndex],
         opt_token_index: Option<D::TokenIndex>,
     ) -> bool {
-        debug!(
-            "\\\\\\+ accepts(error_state={:?}, states={:?}, opt_token_index={:?})",
-            error_state, states, opt_token_index,
-        );
+        accepts(&self.definition, error_state, states, opt_token_index)
+    }
+
+    /// Looks for edits to the input that let the parser get through
+    /// the next few tokens: deleting a token, inserting one of the
+    /// tokens expected at that point, or (for `BurkeFisher` and
+    /// `MinimumCost`) replacing a token. Prefers the edits that get
+    /// furthest, then the cheapest; `MinimumCost` prefers the
+    /// cheapest of those that get through all of the tokens, if any
+    /// do. Returns `None` if no edits let the parser shift at least
+    /// one more token from the input.
+    fn find_repair(
+        &mut self,
+        opt_token_index: Option<D::TokenIndex>,
+    ) -> Option<Vec<PlannedEdit<D>>> {
+        let strategy = self.definition.repair_strategy();
+        let (window, max_edits) = match strategy {
+            RepairStrategy::SingleToken => (REPAIR_WINDOW, 1),
+            RepairStrategy::BurkeFisher => (BURKE_FISHER_WINDOW, BURKE_FISHER_EDITS),
+            RepairStrategy::MinimumCost => (MINIMUM_COST_WINDOW, MINIMUM_COST_EDITS),
+        };
+
+        let mut tokens = vec![opt_token_index];
+        if opt_token_index.is_some() {
+            tokens.extend(self.peek_token_indices(window));
+        }
+
+        let plan = self.search_edits(&self.states, &tokens, 0, max_edits, strategy);
+        if plan.progress == 0 {
+            return None;
+        }
+        Some(plan.edits)
+    }
+
+    /// Finds the best edits to make at `position`, where parsing with
+    /// `states` fails, using at most `max_edits` edits.
+    fn search_edits(
+        &self,
+        states: &[D::StateIndex],
+        tokens: &[Option<D::TokenIndex>],
+        position: usize,
+        max_edits: usize,
+        strategy: RepairStrategy,
+    ) -> Plan<D> {
+        let mut best = Plan {
+            progress: 0,
+            complete: false,
+            cost: 0,
+            edits: vec![],
+        };
+        let replace = strategy != RepairStrategy::SingleToken;
+        let at_eof = tokens[position].is_none();
+        let delete_cost = tokens[position].map_or(0, |t| self.definition.repair_cost(t).delete);
+        if !at_eof {
+            let mut plan =
+                self.continue_edits(states, tokens, position + 1, max_edits - 1, strategy);
+            debug!(
+                "\\\\ repair: deleting at {} makes progress {}",
+                position, plan.progress
+            );
+            plan.cost += delete_cost;
+            plan.edits.insert(0, PlannedEdit::Delete(position));
+            if plan.better_than(&best, strategy) {
+                best = plan;
+            }
+        }
+
+        let top = states[states.len() - 1];
+        for (token_index, token) in self.definition.repair_tokens(top) {
+            let insert_cost = self.definition.repair_cost(token_index).insert;
+            let mut edited_states = states.to_vec();
+            if self.simulate(&mut edited_states, &[Some(token_index)]) == 0 {
+                continue;
+            }
+
+            let mut plan =
+                self.continue_edits(&edited_states, tokens, position, max_edits - 1, strategy);
+            debug!(
+                "\\\\ repair: inserting {:?} at {} makes progress {}",
+                token, position, plan.progress
+            );
+            plan.cost += insert_cost;
+            plan.edits
+                .insert(0, PlannedEdit::Insert(position, token.clone()));
+            if plan.better_than(&best, strategy) {
+                best = plan;
+            }
+
+            if replace && !at_eof {
+                let mut plan = self.continue_edits(
+                    &edited_states,
+                    tokens,
+                    position + 1,
+                    max_edits - 1,
+                    strategy,
+                );
+                debug!(
+                    "\\\\ repair: replacing with {:?} at {} makes progress {}",
+                    token, position, plan.progress
+                );
+                plan.cost += core::cmp::max(insert_cost, delete_cost);
+                plan.edits.insert(0, PlannedEdit::Replace(position, token));
+                if plan.better_than(&best, strategy) {
+                    best = plan;
+                }
+            }
+        }
+
+        best
+    }
 
+    /// Resumes parsing `tokens` at `position` after an edit, making
+    /// further edits at the next error if `max_edits` allows.
+    fn continue_edits(
+        &self,
+        states: &[D::StateIndex],
+        tokens: &[Option<D::TokenIndex>],
+        position: usize,
+        max_edits: usize,
+        strategy: RepairStrategy,
+    ) -> Plan<D> {
         let mut states = states.to_vec();
-        states.push(error_state);
-        loop {
-            let mut states_len = states.len();
-            let top = states[states_len - 1];
-            let action = match opt_token_index {
-                None => self.definition.eof_action(top),
-                Some(i) => self.definition.action(top, i),
-            };
+        let remaining = &tokens[position..];
+        let progress = self.simulate(&mut states, remaining);
+        // stop if we got through the window, or accepted at EOF
+        let stopped_at = position + progress;
+        let mut plan = Plan {
+            progress,
+            complete: stopped_at >= tokens.len(),
+            cost: 0,
+            edits: vec![],
+        };
+        if plan.complete || max_edits == 0 {
+            return plan;
+        }
 
-            // If we encounter an error action, we do **not** accept.
-            if action.is_error() {
-                debug!("\\\\\\\\ accepts: error");
-                return false;
+        let further = self.search_edits(&states, tokens, stopped_at, max_edits, strategy);
+        if further.progress > 0 {
+            plan.progress += further.progress;
+            plan.complete = further.complete;
+            plan.cost = further.cost;
+            plan.edits = further.edits;
+        }
+        plan
+    }
+
+    /// Applies the edits chosen by `find_repair` to the upcoming
+    /// tokens, records them, and returns the first of the edited
+    /// tokens.
+    fn apply_repair(
+        &mut self,
+        edits: Vec<PlannedEdit<D>>,
+        opt_lookahead: Option<TokenTriple<D>>,
+    ) -> NextToken<D> {
+        let error = self.unrecognized_token_error(opt_lookahead.clone(), self.top_state());
+
+        // Take the tokens the edits refer to back out of the lookahead
+        // buffer; `None` is EOF.
+        let last_position = edits.iter().map(PlannedEdit::position).max().unwrap_or(0);
+        let mut window = vec![opt_lookahead];
+        while window.len() <= last_position {
+            match self.lookahead.pop_front() {
+                Some(Some(Ok(token))) => window.push(Some(token)),
+                Some(None) => window.push(None),
+                _ => unreachable!("repair refers to a token that was not classified"),
             }
+        }
 
-            // If we encounter a reduce action, we need to simulate its
-            // effect on the state stack.
-            if let Some(reduce_action) = action.as_reduce() {
-                match self.definition.simulate_reduce(reduce_action) {
-                    SimulatedReduce::Reduce {
-                        states_to_pop,
-                        nonterminal_produced,
-                    } => {
-                        states_len -= states_to_pop;
-                        states.truncate(states_len);
-                        let top = states[states_len - 1];
-                        let next_state = self.definition.goto(top, nonterminal_produced);
-                        states.push(next_state);
+        let mut edited = vec![];
+        let mut recorded = vec![];
+        let mut edits = edits.into_iter().peekable();
+        for (position, opt_token) in window.iter().enumerate() {
+            let mut keep = true;
+            while edits.peek().map(PlannedEdit::position) == Some(position) {
+                match edits.next().unwrap() {
+                    PlannedEdit::Delete(_) => {
+                        let token = opt_token.clone().expect("cannot delete EOF");
+                        debug!("\\\\ repair: deleting {:?}", token);
+                        recorded.push(crate::Edit::Delete(token));
+                        keep = false;
                     }
-
-                    SimulatedReduce::Accept => {
-                        debug!("\\\\\\\\ accepts: reduce accepts!");
-                        return true;
+                    PlannedEdit::Insert(_, token) => {
+                        let location = match *opt_token {
+                            Some(ref t) => t.0.clone(),
+                            None => {
+                                match position.checked_sub(1).and_then(|p| window[p].as_ref()) {
+                                    Some(previous) => previous.2.clone(),
+                                    None => self.last_location.clone(),
+                                }
+                            }
+                        };
+                        debug!("\\\\ repair: inserting {:?} at {:?}", token, location);
+                        let inserted = (location.clone(), token, location);
+                        edited.push(Some(Ok(inserted.clone())));
+                        recorded.push(crate::Edit::Insert(inserted));
+                    }
+                    PlannedEdit::Replace(_, token) => {
+                        let original = opt_token.clone().expect("cannot replace EOF");
+                        debug!("\\\\ repair: replacing {:?} with {:?}", original, token);
+                        let replacement = (original.0.clone(), token.clone(), original.2.clone());
+                        edited.push(Some(Ok(replacement)));
+                        recorded.push(crate::Edit::Replace(original, token));
+                        keep = false;
                     }
                 }
+            }
+            if keep {
+                edited.push(opt_token.clone().map(Ok));
+            }
+        }
+
+        for token in edited.into_iter().rev() {
+            self.lookahead.push_front(token);
+        }
+        if let Some(ref mut repairs) = self.repairs {
+            repairs.push(crate::Repair {
+                error,
+                edits: recorded,
+            });
+        }
+        self.next_token()
+    }
+
+    /// Runs the automaton over `token_indices` (where `None` is EOF)
+    /// without executing any user actions, updating `states` as it
+    /// goes. Returns how many of the tokens were shifted before
+    /// hitting an error; accepting at EOF counts as consuming the EOF.
+    fn simulate(
+        &self,
+        states: &mut Vec<D::StateIndex>,
+        token_indices: &[Option<D::TokenIndex>],
+    ) -> usize {
+        simulate(&self.definition, states, token_indices)
+    }
+
+    /// Picks the action to take in `state` on `token_index` (`None`
+    /// being EOF), given the one from the tables, or returns `None`
+    /// if guards reject it. In a conflicted state, the first
+    /// reduction whose guard holds is taken; if that leaves several
+    /// unguarded actions, they are tried with `backtrack`.
+    fn choose_action(
+        &mut self,
+        state: D::StateIndex,
+        token_index: Option<D::TokenIndex>,
+        action: D::Action,
+    ) -> Option<D::Action> {
+        let alternatives = self.definition.alternative_actions(state, token_index);
+        if alternatives.is_empty() {
+            return match self.guard(action) {
+                Some(false) => None,
+                _ => Some(action),
+            };
+        }
+
+        let mut unguarded = vec![];
+        for candidate in Some(action).into_iter().chain(alternatives) {
+            match self.guard(candidate) {
+                Some(true) => return Some(candidate),
+                Some(false) => {}
+                None => unguarded.push(candidate),
+            }
+        }
+        match unguarded.len() {
+            0 => None,
+            1 => Some(unguarded[0]),
+            _ => Some(self.backtrack(token_index, unguarded)),
+        }
+    }
+
+    /// Evaluates the guard of `action`, if it is a guarded reduction.
+    fn guard(&self, action: D::Action) -> Option<bool> {
+        let reduce_index = action.as_reduce()?;
+        self.definition.guard(reduce_index, &self.symbols)
+    }
+
+    /// Tries each of `candidates` on a copy of the state stack over the
+    /// next `BACKTRACK_WINDOW` tokens, and picks the first to get
+    /// through all of them (or, failing that, furthest). Conflicts met
+    /// during a trial are resolved by the tables alone.
+    fn backtrack(
+        &mut self,
+        token_index: Option<D::TokenIndex>,
+        candidates: Vec<D::Action>,
+    ) -> D::Action {
+        let window: Vec<_> = match token_index {
+            Some(i) => Some(Some(i))
+                .into_iter()
+                .chain(self.peek_token_indices(BACKTRACK_WINDOW - 1))
+                .collect(),
+            None => vec![None],
+        };
+        let (mut best, mut best_progress) = (candidates[0], 0);
+        for (i, candidate) in candidates.into_iter().enumerate() {
+            let progress = self.trial(candidate, &window);
+            if i == 0 || progress > best_progress {
+                best = candidate;
+                best_progress = progress;
+            }
+            if best_progress == window.len() {
+                break;
+            }
+        }
+        debug!("\\ backtracking chose: {:?}", best);
+        best
+    }
+
+    /// Takes `action` on the first token of `window` and simulates the
+    /// rest, on a copy of the state stack. Returns how many tokens of
+    /// `window` were shifted, as `simulate` does.
+    fn trial(&self, action: D::Action, window: &[Option<D::TokenIndex>]) -> usize {
+        let mut states = self.states.to_vec();
+        if let Some(target_state) = action.as_shift() {
+            states.push(target_state);
+            1 + self.simulate(&mut states, &window[1..])
+        } else if let Some(reduce_index) = action.as_reduce() {
+            if simulate_reduction(&self.definition, &mut states, reduce_index) {
+                self.simulate(&mut states, window)
             } else {
-                // If we encounter a shift action, we DO accept.
-                debug!("\\\\\\\\ accepts: shift accepts!");
-                assert!(action.is_shift());
-                return true;
+                window.len()
+            }
+        } else {
+            0
+        }
+    }
+
+    /// True if this is a prefix parse and the input read so far,
+    /// excluding the lookahead, forms a complete parse.
+    fn can_stop_prefix(&self) -> bool {
+        self.prefix && self.simulate(&mut self.states.to_vec(), &[None]) == 1
+    }
+
+    /// Ends a prefix parse before `token`, putting it back into the
+    /// input. The caller should then finish the parse as if at EOF.
+    fn stop_prefix(&mut self, token: I::Item) {
+        self.lookahead.push_front(Some(token));
+        self.last_location = match self.symbols.last() {
+            Some(symbol) => symbol.2.clone(),
+            None => self.definition.start_location(),
+        };
+    }
+
+    /// Reads ahead (without consuming) up to `n` upcoming tokens and
+    /// classifies them. Stops early at EOF, which is included as
+    /// `None`, or at a token we cannot classify.
+    fn peek_token_indices(&mut self, n: usize) -> Vec<Option<D::TokenIndex>> {
+        while self.lookahead.len() < n {
+            match self.lookahead.back() {
+                Some(None) | Some(Some(Err(_))) => break,
+                _ => {
+                    let token = self.tokens.next();
+                    self.lookahead.push_back(token);
+                }
+            }
+        }
+
+        let mut indices = Vec::with_capacity(n);
+        for token in self.lookahead.iter().take(n) {
+            match *token {
+                Some(Ok(ref token)) => match self.definition.token_to_index(&token.1) {
+                    Some(i) => indices.push(Some(i)),
+                    None => break,
+                },
+                Some(Err(_)) => break,
+                None => {
+                    indices.push(None);
+                    break;
+                }
             }
         }
+        indices
     }
 
     fn reduce(
@@ -584,10 +2178,39 @@ where
         action: D::ReduceIndex,
         lookahead_start: Option<&D::Location>,
     ) -> Option<ParseResult<D>> {
-        self.definition
-            .reduce(action, lookahead_start, &mut self.states, &mut self.symbols)
+        let checkpoints = self.checkpoints.as_mut().filter(|c| !c.saved.is_empty());
+        if let Some(checkpoints) = checkpoints {
+            if let SimulatedReduce::Reduce { states_to_pop, .. } =
+                self.definition.simulate_reduce(action)
+            {
+                // copy what is about to be popped from below the checkpoints
+                let height = self.symbols.len() - states_to_pop;
+                for saved in &mut checkpoints.saved {
+                    while saved.floor > height {
+                        saved.floor -= 1;
+                        let symbol = (checkpoints.clone_symbol)(&self.symbols[saved.floor]);
+                        saved.popped.push((self.states[saved.floor + 1], symbol));
+                    }
+                }
+            }
+        }
+        let result =
+            self.definition
+                .reduce(action, lookahead_start, &mut self.states, &mut self.symbols);
+        if let (None, Some(tracing)) = (&result, &mut self.tracing) {
+            let (start, _, end) = self.symbols.last().unwrap();
+            tracing.trace.record(TraceEntry::Reduce {
+                state: (tracing.state)(*self.states.last().unwrap()),
+                reduction: (tracing.reduction)(action),
+                start: start.clone(),
+                end: end.clone(),
+            });
+        }
+        result
     }
 
+    #[cold]
+    #[inline(never)]
     fn unrecognized_token_error(
         &self,
         token: Option<TokenTriple<D>>,
@@ -609,9 +2232,19 @@ where
     /// token index. Classification can fail with an error. If there
     /// are no more tokens, signal EOF.
     fn next_token(&mut self) -> NextToken<D> {
-        let token = match self.tokens.next() {
+        let token = self
+            .lookahead
+            .pop_front()
+            .unwrap_or_else(|| self.tokens.next());
+        let token = match token {
             Some(Ok(v)) => v,
-            Some(Err(e)) => return NextToken::Done(Err(e)),
+            Some(Err(e)) => {
+                if self.can_stop_prefix() {
+                    self.stop_prefix(Err(e));
+                    return NextToken::EOF;
+                }
+                return NextToken::Done(Err(e));
+            }
             None => return NextToken::EOF,
         };
 
@@ -619,6 +2252,10 @@ where
 
         let token_index = match self.definition.token_to_index(&token.1) {
             Some(i) => i,
+            None if self.can_stop_prefix() => {
+                self.stop_prefix(Ok(token));
+                return NextToken::EOF;
+            }
             None => {
                 return NextToken::Done(Err(
                     self.unrecognized_token_error(Some(token), self.top_state())
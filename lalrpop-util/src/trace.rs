@@ -0,0 +1,158 @@
+//! Traces of the actions of a parser, for parsers generated with
+//! `Configuration::emit_trace`. Such a parser has a `parse_traced`
+//! method, which records each token it shifts and each reduction it
+//! makes in a `Trace`, so that a misparse in production can be
+//! examined after the fact:
+//!
+//! ```ignore
+//! let mut trace = Trace::with_capacity(1000);
+//! let result = ExprParser::new().parse_traced(input, &mut trace);
+//! if result.is_err() {
+//!     std::fs::write("parse.trace", trace.to_string())?;
+//! }
+//! ```
+//!
+//! A trace with a capacity keeps only the latest entries, so it can be
+//! left on without using up memory.
+
+use alloc::collections::VecDeque;
+use core::fmt;
+
+/// One action of the parser. States, terminals and reductions are
+/// numbered as in the generated tables; the report written with
+/// `Configuration::emit_report` names the states.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEntry<L> {
+    /// The token at `start..end`, of terminal `token`, was shifted,
+    /// and the parser went to `state`.
+    Shift {
+        state: u32,
+        token: u32,
+        start: L,
+        end: L,
+    },
+
+    /// The symbols at `start..end` were reduced with `reduction`, and
+    /// the parser went to `state`.
+    Reduce {
+        state: u32,
+        reduction: u32,
+        start: L,
+        end: L,
+    },
+}
+
+/// The actions of a parse, oldest first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace<L> {
+    entries: VecDeque<TraceEntry<L>>,
+    capacity: Option<usize>,
+    dropped: usize,
+}
+
+impl<L> Default for Trace<L> {
+    fn default() -> Self {
+        Trace::new()
+    }
+}
+
+impl<L> Trace<L> {
+    /// A trace that keeps every entry.
+    pub fn new() -> Self {
+        Trace {
+            entries: VecDeque::new(),
+            capacity: None,
+            dropped: 0,
+        }
+    }
+
+    /// A trace that keeps the latest `capacity` entries, dropping
+    /// older ones.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Trace {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: Some(capacity),
+            dropped: 0,
+        }
+    }
+
+    /// The entries kept, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry<L>> {
+        self.entries.iter()
+    }
+
+    /// The number of entries dropped to stay within the capacity.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Drops all of the entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.dropped = 0;
+    }
+
+    #[doc(hidden)]
+    pub fn record(&mut self, entry: TraceEntry<L>) {
+        if self.capacity == Some(0) {
+            self.dropped += 1;
+            return;
+        }
+        if self.capacity == Some(self.entries.len()) {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+/// Writes one line per entry, like `shift 12 token 3 at 4:5`, after a
+/// line giving the number of entries dropped, if any.
+impl<L: fmt::Display> fmt::Display for Trace<L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.dropped > 0 {
+            writeln!(f, "dropped {}", self.dropped)?;
+        }
+        for entry in &self.entries {
+            match *entry {
+                TraceEntry::Shift {
+                    state,
+                    token,
+                    ref start,
+                    ref end,
+                } => writeln!(f, "shift {} token {} at {}:{}", state, token, start, end)?,
+                TraceEntry::Reduce {
+                    state,
+                    reduction,
+                    ref start,
+                    ref end,
+                } => writeln!(
+                    f,
+                    "reduce {} reduction {} at {}:{}",
+                    state, reduction, start, end
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The indices of the generated tables, which traces record as
+/// numbers.
+pub trait TraceIndex: Copy {
+    fn trace_index(self) -> u32;
+}
+
+macro_rules! trace_index {
+    ($($t:ty),*) => {
+        $(
+            impl TraceIndex for $t {
+                fn trace_index(self) -> u32 {
+                    self as u32
+                }
+            }
+        )*
+    };
+}
+
+trace_index!(i8, i16, i32, u8, u16, u32, usize);
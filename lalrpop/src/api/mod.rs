@@ -1,6 +1,7 @@
 use crate::build;
+use crate::diagnostic::Diagnostic;
 use crate::log::Level;
-use crate::session::{ColorConfig, Session};
+use crate::session::{ColorConfig, Edition, Profile, Session};
 use std::default::Default;
 use std::env;
 use std::env::current_dir;
@@ -8,6 +9,9 @@ use std::error::Error;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+#[cfg(test)]
+mod test;
+
 /// Configure various aspects of how LALRPOP works.
 /// Intended for use within a `build.rs` script.
 /// To get the default configuration, use `Configuration::new`.
@@ -132,6 +136,154 @@ impl Configuration {
         self
     }
 
+    /// If true, emit a report on the states of the generated parser as
+    /// an HTML page, next to the report file, where the states can be
+    /// searched and followed from one to the next. Default is false.
+    pub fn emit_html_report(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_html_report = val;
+        self
+    }
+
+    /// If true, write the grammar, as normalized for generating the
+    /// parser, to a `.json` file next to the generated code, for tools
+    /// such as highlighters and documentation generators. It has the
+    /// terminals and nonterminals with their types, and the symbols
+    /// and precedence of each production. Default is false.
+    pub fn emit_grammar_json(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_grammar_json = val;
+        self
+    }
+
+    /// If true, table-driven parsers look up their tables without
+    /// bounds checks, which speeds up parsing somewhat. The indices are
+    /// in range by construction, and are still checked when debug
+    /// assertions are enabled. The generated code then contains
+    /// `unsafe` blocks. Default is false.
+    pub fn unchecked_tables(&mut self, val: bool) -> &mut Configuration {
+        self.session.unchecked_tables = val;
+        self
+    }
+
+    /// If true, table-driven parsers take the values of the symbols
+    /// they reduce off their stack without checking that each symbol
+    /// has the expected type, which saves a branch per symbol. The
+    /// types match by construction, and are still checked when debug
+    /// assertions are enabled. The functions of the generated code
+    /// that reduce are then `unsafe fn`s, which only the parser calls.
+    /// Default is false.
+    pub fn unchecked_downcasts(&mut self, val: bool) -> &mut Configuration {
+        self.session.unchecked_downcasts = val;
+        self
+    }
+
+    /// If true, the tables of table-driven parsers are written to a
+    /// second file next to the generated code, named after it with a
+    /// `_tables` suffix (e.g. `calc_tables.rs` for `calc.rs`), which
+    /// the generated code includes with `include!`. The tables are in
+    /// a module of their own there, so they are compiled apart from the
+    /// actions, and an incremental build after an edit to the action
+    /// code need not compile them again. Default is false.
+    pub fn split_tables(&mut self, val: bool) -> &mut Configuration {
+        self.session.split_tables = val;
+        self
+    }
+
+    /// Limits the stacks of table-driven parsers to `depth` states, so
+    /// that deeply nested input fails with a `ParseError::User` rather
+    /// than using up memory. Its error is made from a
+    /// `lalrpop_util::StackOverflow`, which the error type of the
+    /// grammar must convert from; `&'static str`, the default, does.
+    /// Parsers exposed to untrusted input should set this. Recursive-ascent parsers, which are not
+    /// table-driven, are not limited. Default is no limit.
+    pub fn max_stack_depth(&mut self, depth: usize) -> &mut Configuration {
+        self.session.max_stack_depth = Some(depth);
+        self
+    }
+
+    /// If true, table-driven parsers keep the first 32 entries of
+    /// their stacks of states and symbols inline, in a `SmallVec`,
+    /// rather than in a `Vec`, so that parsing a small input allocates
+    /// nothing for them. The generated code then needs the `smallvec`
+    /// feature of `lalrpop-util`. Default is false.
+    pub fn inline_stacks(&mut self, val: bool) -> &mut Configuration {
+        self.session.inline_stacks = val;
+        self
+    }
+
+    /// Stops building a parser that needs more than `count` LR states,
+    /// reporting the nonterminals that account for the most of them,
+    /// instead of spending minutes on a grammar whose states blow up.
+    /// Default is no limit.
+    pub fn max_states(&mut self, count: usize) -> &mut Configuration {
+        self.session.max_states = Some(count);
+        self
+    }
+
+    /// If true, the generated code counts how often the action of each
+    /// rule of the grammar runs, in a `COVERAGE` static of the generated
+    /// module; see `lalrpop_util::coverage`. Meant for measuring how
+    /// much of a grammar a test suite exercises. Default is false.
+    pub fn emit_coverage(&mut self, val: bool) -> &mut Configuration {
+        self.session.coverage = val;
+        self
+    }
+
+    /// If true, table-driven parsers get a `parse_traced` method, which
+    /// takes the same arguments as `parse` and a
+    /// `lalrpop_util::trace::Trace`, and records each token shifted and
+    /// each reduction made in it, with the states, terminals, rules and
+    /// spans involved. Meant for examining misparses after the fact.
+    /// Default is false.
+    pub fn emit_trace(&mut self, val: bool) -> &mut Configuration {
+        self.session.trace = val;
+        self
+    }
+
+    /// If true, the code of each action and reduction in the generated
+    /// code starts with a `// lalrpop-source: file:line:column`
+    /// comment, naming the alternative of the grammar it comes from.
+    /// `lalrpop::translate_locations` (or `lalrpop --map-locations`)
+    /// uses these to turn locations in the generated file, as found in
+    /// panic backtraces and compiler errors, into locations in the
+    /// grammar. Default is false.
+    pub fn emit_source_map(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_source_map = val;
+        self
+    }
+
+    /// If true, the generated code also has `extern "C"` functions that
+    /// parse a byte buffer with each public nonterminal and return an
+    /// opaque handle to the value or the error; see
+    /// `lalrpop_util::ffi`. With the crate built as a `cdylib` or a
+    /// `staticlib`, the parsers can then be used from C, C++ or Python.
+    /// Only grammars that use the generated lexer and have no
+    /// parameters or type parameters get them. Default is false.
+    pub fn emit_c_abi(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_c_abi = val;
+        self
+    }
+
+    /// Generates code for a crate of the given edition: later editions
+    /// get code that passes their idiom lints, like
+    /// `rust_2018_idioms`. Default is `Edition::Rust2015`, whose code
+    /// compiles under any edition.
+    pub fn set_edition(&mut self, edition: Edition) -> &mut Configuration {
+        self.session.edition = edition;
+        self
+    }
+
+    /// Tunes the generated code for a kind of target. With
+    /// `Profile::Wasm`, meant for parsers that run in the browser,
+    /// table-driven parsers keep only the non-error entries of their
+    /// action tables, paths that cannot be taken abort instead of
+    /// formatting a panic message, and the `expected` lists of parse
+    /// errors are left empty, so that the tables of terminal names are
+    /// not needed. Default is `Profile::Default`.
+    pub fn set_profile(&mut self, profile: Profile) -> &mut Configuration {
+        self.session.profile = profile;
+        self
+    }
+
     /// Minimal logs: only for errors that halt progress.
     pub fn log_quiet(&mut self) -> &mut Configuration {
         self.session.log.set_level(Level::Taciturn);
@@ -168,6 +320,31 @@ impl Configuration {
         self
     }
 
+    /// Sets the flag `name`, for alternatives and public nonterminals
+    /// marked `#[cfg(flag = "name")]`, which are left out of the parser
+    /// unless their flag is set. Grammar files can thus be processed
+    /// more than once, with different flags, to generate, say, a strict
+    /// and an extended parser. (Default: no flags are set)
+    pub fn set_flag(&mut self, name: &str, val: bool) -> &mut Configuration {
+        if val {
+            self.session.flags.insert(name.to_string());
+        } else {
+            self.session.flags.remove(name);
+        }
+        self
+    }
+
+    /// Passes errors and warnings to `handler` instead of printing them.
+    /// A grammar with errors then makes processing return an error,
+    /// rather than exit the process.
+    pub fn set_diagnostic_handler<F>(&mut self, handler: F) -> &mut Configuration
+    where
+        F: Fn(&Diagnostic) + 'static,
+    {
+        self.session.diagnostic_handler = Some(Rc::new(handler));
+        self
+    }
+
     /// Enables "unit-testing" configuration. This is only for
     /// lalrpop-test.
     #[doc(hidden)]
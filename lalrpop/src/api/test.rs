@@ -0,0 +1,504 @@
+use super::Configuration;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::session::Edition;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::rc::Rc;
+
+/// Processes the grammar `text` with `config`, returning the generated
+/// code, if any, and the diagnostics it gave.
+fn process(
+    name: &str,
+    text: &str,
+    config: &mut Configuration,
+) -> (Option<String>, Vec<Diagnostic>) {
+    let dir = env::temp_dir().join(format!("lalrpop-api-{}", name));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("grammar.lalrpop");
+    fs::write(&file, text).unwrap();
+
+    let diagnostics = Rc::new(RefCell::new(vec![]));
+    let result = {
+        let diagnostics = diagnostics.clone();
+        config
+            .log_quiet()
+            .force_build(true)
+            .set_diagnostic_handler(move |diagnostic| {
+                diagnostics.borrow_mut().push(diagnostic.clone())
+            })
+            .process_file(&file)
+            .map(|()| fs::read_to_string(file.with_extension("rs")).unwrap())
+    };
+    fs::remove_dir_all(&dir).unwrap();
+
+    let diagnostics = diagnostics.borrow().clone();
+    for diagnostic in &diagnostics {
+        assert_eq!(diagnostic.file, file);
+    }
+    (result.ok(), diagnostics)
+}
+
+#[test]
+fn syntax_error() {
+    let text = "grammar;\npub Expr: () = \"x\" \"y\"";
+    let (code, diagnostics) = process("syntax-error", text, &mut Configuration::new());
+    assert!(code.is_none());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].message, "unexpected end of file");
+    assert_eq!(diagnostics[0].span, text.len()..text.len());
+}
+
+#[test]
+fn ambiguity() {
+    let text = r#"
+grammar;
+
+pub Expr: () = {
+    Expr "+" Expr,
+    "x",
+};
+"#;
+    let (code, diagnostics) = process("ambiguity", text, &mut Configuration::new());
+    assert!(code.is_none());
+    assert!(diagnostics[0]
+        .message
+        .contains("Ambiguous grammar detected"));
+    assert_eq!(&text[diagnostics[0].span.clone()], r#"Expr "+" Expr"#);
+}
+
+#[test]
+fn conflict_in_macro() {
+    let text = r#"
+grammar;
+
+pub Stmts: () = List<Expr>;
+
+List<T>: () = (<Comma<T>> ";")*;
+
+Comma<T>: () = { T, Comma<T> "," Comma<T> };
+
+Expr: () = "x";
+"#;
+    let (code, diagnostics) = process("conflict-in-macro", text, &mut Configuration::new());
+    assert!(code.is_none());
+    // the use in `Stmts`, rather than the one in `List`
+    assert_eq!(&text[diagnostics[0].span.clone()], "List<Expr>");
+    assert!(diagnostics[0]
+        .message
+        .contains("note: `Comma<Expr>` is expanded from the macro defined at"));
+    assert!(diagnostics[0]
+        .message
+        .contains("note: `List<Expr>` is expanded from the macro defined at"));
+}
+
+#[test]
+fn lalr_warning() {
+    let text = "#[LALR]\ngrammar;\npub Expr: () = \"x\";\n";
+    let (code, diagnostics) = process("lalr-warning", text, &mut Configuration::new());
+    assert!(code.is_some());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+}
+
+#[test]
+fn right_recursion_warning() {
+    let text = "grammar;\npub Items: Vec<()> = { \"x\" <v:Items> => v, => vec![] };\n";
+    let (code, diagnostics) = process("right-recursion", text, &mut Configuration::new());
+    assert!(code.is_some());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!(&text[diagnostics[0].span.clone()], "\"x\" <v:Items> => v");
+}
+
+#[test]
+fn shadowed_alternative_warning() {
+    let text =
+        "#[backtrack]\ngrammar;\npub S: () = {\n    \"a\" \"b\",\n    \"a\" \"b\" => (),\n};\n";
+    let (code, diagnostics) = process("shadowed", text, &mut Configuration::new());
+    assert!(code.is_some());
+    // the conflict between the two is reported as well
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics[0].message.contains("the grammar is ambiguous"));
+    assert_eq!(diagnostics[1].severity, Severity::Warning);
+    assert!(diagnostics[1].message.contains(":4:5 instead"));
+    assert_eq!(&text[diagnostics[1].span.clone()], "\"a\" \"b\" => ()");
+}
+
+#[test]
+fn backtracked_conflict_warnings() {
+    let text = "#[backtrack]\ngrammar;\npub S: () = {\n    L \":\" \"l\",\n    V \":\" \"v\",\n};\nL: () = \"x\";\nV: () = \"x\";\n";
+    let (code, diagnostics) = process("backtracked", text, &mut Configuration::new());
+    assert!(code.is_some());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!(&text[diagnostics[0].span.clone()], "\"x\"");
+    let message = &diagnostics[0].message;
+    assert!(message.starts_with("on \":\", this alternative conflicts with"));
+    assert!(message.contains("settles it by trying both"));
+
+    let text = "#[backtrack]\ngrammar;\npub E: () = {\n    E \"+\" E,\n    \"x\",\n};\n";
+    let (code, diagnostics) = process("backtracked-ambiguous", text, &mut Configuration::new());
+    assert!(code.is_some());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(&text[diagnostics[0].span.clone()], "E \"+\" E");
+    assert_eq!(
+        diagnostics[0].message,
+        "on \"+\", this alternative conflicts with shifting it, and the grammar is ambiguous \
+         there: trying both on the next tokens cannot settle it, so the parser takes the first \
+         that gets through them"
+    );
+}
+
+#[test]
+fn unused_parameter_warning() {
+    let text = "grammar(scale: u32);\npub Num: u32 = \"x\" => 1;\n";
+    let (code, diagnostics) = process("unused-parameter", text, &mut Configuration::new());
+    assert!(code.is_some());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert!(diagnostics[0].message.contains("`scale`"));
+    assert_eq!(&text[diagnostics[0].span.clone()], "grammar");
+}
+
+#[test]
+fn max_states() {
+    let text = r#"
+grammar;
+
+pub Expr: () = {
+    Expr "+" Term,
+    Term,
+};
+
+Term: () = {
+    "x",
+    "(" Expr ")",
+};
+"#;
+    let (code, _) = process("max-states-ok", text, Configuration::new().max_states(100));
+    assert!(code.is_some());
+
+    let (code, diagnostics) = process("max-states", text, Configuration::new().max_states(3));
+    assert!(code.is_none());
+    assert_eq!(diagnostics.len(), 1);
+    let message = &diagnostics[0].message;
+    assert!(message.contains("Too many states"));
+    assert!(message.contains("The parser for `Expr` needs more than 3 states"));
+    assert!(message.contains("Expr (2 states)"));
+    assert!(message.contains("Term (1 state)"));
+    assert!(!message.contains("__Expr"));
+    assert_eq!(&text[diagnostics[0].span.clone()], "Expr");
+}
+
+#[test]
+fn edition() {
+    let text = "grammar;\npub Expr: () = \"x\";\n";
+    let (code, _) = process("edition-2015", text, &mut Configuration::new());
+    let code = code.unwrap();
+    assert!(code.contains("extern crate lalrpop_util as __lalrpop_util;"));
+    assert!(code.contains("extern crate core;"));
+
+    let (code, _) = process(
+        "edition-2021",
+        text,
+        Configuration::new().set_edition(Edition::Rust2021),
+    );
+    let code = code.unwrap();
+    assert!(code.contains("use ::lalrpop_util as __lalrpop_util;"));
+    assert!(!code.contains("extern crate core;"));
+}
+
+#[test]
+fn unchecked_tables() {
+    let text = "grammar;\npub Expr: () = \"x\";\n";
+    let (code, _) = process("checked-tables", text, &mut Configuration::new());
+    assert!(!code.unwrap().contains("get_unchecked"));
+
+    let (code, _) = process(
+        "unchecked-tables",
+        text,
+        Configuration::new().unchecked_tables(true),
+    );
+    let code = code.unwrap();
+    assert!(code.contains("unsafe { *__ACTION.get_unchecked(__index) }"));
+    assert!(!code.contains("EOF_ACTION"));
+}
+
+#[test]
+fn unchecked_downcasts() {
+    let text = "grammar;\npub Expr: () = { \"x\", \"(\" Expr \")\" };\n";
+    let (code, _) = process("checked-downcasts", text, &mut Configuration::new());
+    let code = code.unwrap();
+    assert!(!code.contains("unreachable_unchecked"));
+    assert!(!code.contains("unsafe fn"));
+
+    let (code, _) = process(
+        "unchecked-downcasts",
+        text,
+        Configuration::new().unchecked_downcasts(true),
+    );
+    let code = code.unwrap();
+    assert!(code.contains("unsafe { core::hint::unreachable_unchecked() }"));
+    assert!(code.contains("if cfg!(debug_assertions) { __symbol_type_mismatch() }"));
+    assert!(code.contains("/// # Safety"));
+    assert!(code.contains("pub(crate) unsafe fn __reduce<"));
+    assert!(code.contains("// SAFETY: the parser reduces `action` only"));
+}
+
+#[test]
+fn inline_stacks() {
+    let text = "grammar;\npub Expr: () = { \"x\", \"(\" Expr \")\" };\n";
+    let (code, _) = process("vec-stacks", text, &mut Configuration::new());
+    let code = code.unwrap();
+    assert!(code.contains("type Stack<__T> = alloc::vec::Vec<__T>;"));
+    assert!(!code.contains("InlineStack"));
+
+    let (code, _) = process(
+        "inline-stacks",
+        text,
+        Configuration::new().inline_stacks(true),
+    );
+    let code = code.unwrap();
+    assert!(code.contains("type Stack<__T> = __state_machine::InlineStack<__T>;"));
+    assert!(!code.contains("alloc::vec::Vec<i8>"));
+}
+
+#[test]
+fn split_tables() {
+    let text = "grammar;\npub Expr: () = \"x\";\n";
+    let (code, _) = process(
+        "split-tables",
+        text,
+        Configuration::new().split_tables(true),
+    );
+    let code = code.unwrap();
+    assert!(code.contains("use super::__tables__Expr::*;"));
+    assert!(code.contains("include!(\"grammar_tables.rs\");"));
+    assert!(!code.contains("const __ACTION"));
+}
+
+#[test]
+fn emit_coverage() {
+    let text = "grammar;\npub Expr: () = { \"x\", \"(\" Expr \")\" };\n";
+    let (code, _) = process("no-coverage", text, &mut Configuration::new());
+    assert!(!code.unwrap().contains("COVERAGE"));
+
+    let (code, _) = process("coverage", text, Configuration::new().emit_coverage(true));
+    let code = code.unwrap();
+    assert!(code.contains("r###\"Expr = \"x\"\"###,"));
+    assert!(code.contains("r###\"Expr = \"(\" Expr \")\"\"###,"));
+    assert!(code.contains("COVERAGE.record(0);"));
+    assert!(code.contains("COVERAGE.record(1);"));
+}
+
+#[test]
+fn emit_trace() {
+    let text = "grammar;\npub Expr: () = { \"x\", \"(\" Expr \")\" };\n";
+    let (code, _) = process("no-trace", text, &mut Configuration::new());
+    assert!(!code.unwrap().contains("parse_traced"));
+
+    let (code, _) = process("trace", text, Configuration::new().emit_trace(true));
+    let code = code.unwrap();
+    assert!(code.contains("pub fn parse_traced<"));
+    assert!(code.contains("__trace: &mut __lalrpop_util::trace::Trace<usize>,"));
+    assert!(code.contains("__state_machine::Parser::drive_traced("));
+}
+
+#[test]
+fn emit_html_report() {
+    let dir = env::temp_dir().join("lalrpop-api-html-report");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("grammar.lalrpop");
+    fs::write(
+        &file,
+        "grammar;\npub Expr: () = { Expr \"+\" Expr, \"<x>\" };\n",
+    )
+    .unwrap();
+    let result = Configuration::new()
+        .log_quiet()
+        .force_build(true)
+        .set_diagnostic_handler(|_| {})
+        .emit_html_report(true)
+        .process_file(&file);
+    let html = fs::read_to_string(file.with_extension("html"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    // the page is written even though the grammar has conflicts
+    assert!(result.is_err());
+    let html = html.unwrap();
+    assert!(html.contains("<h2>Public nonterminal <code>Expr</code></h2>"));
+    assert!(html.contains("<div class=\"state conflict\" id=\"s0-"));
+    assert!(html.contains("<code>&quot;&lt;x&gt;&quot;</code> shift and goto <a href=\"#s0-"));
+    assert!(html.contains("<h4>Conflicts</h4>"));
+}
+
+#[test]
+fn emit_c_abi() {
+    let dir = env::temp_dir().join("lalrpop-api-c-abi");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("my_lang.lalrpop"),
+        "grammar;\npub MyExpr: () = \"x\";\n",
+    )
+    .unwrap();
+    // with an external lexer, there is no way to parse a buffer
+    fs::write(
+        dir.join("tokens.lalrpop"),
+        "grammar;\nextern { enum char { \"x\" => 'x' } }\npub Expr: () = \"x\";\n",
+    )
+    .unwrap();
+    let mut config = Configuration::new();
+    config.log_quiet().force_build(true).emit_c_abi(true);
+    let result = config
+        .process_file(dir.join("my_lang.lalrpop"))
+        .and_then(|()| config.process_file(dir.join("tokens.lalrpop")));
+    let my_lang = fs::read_to_string(dir.join("my_lang.rs"));
+    let tokens = fs::read_to_string(dir.join("tokens.rs"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(result.is_ok());
+    let my_lang = my_lang.unwrap();
+    assert!(my_lang.contains("pub unsafe extern \"C\" fn my_lang_my_expr_parse("));
+    assert!(my_lang.contains("pub unsafe extern \"C\" fn my_lang_free("));
+    assert!(!tokens.unwrap().contains("extern \"C\""));
+}
+
+#[test]
+fn include_error() {
+    let dir = env::temp_dir().join("lalrpop-api-include-error");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("grammar.lalrpop");
+    fs::write(
+        &file,
+        "grammar;\ninclude \"tokens.txt\";\npub Expr: () = \"x\";\n",
+    )
+    .unwrap();
+    let tokens = "match {\n    \"x\"\n    \"y\"\n}\n";
+    fs::write(dir.join("tokens.txt"), tokens).unwrap();
+    let diagnostics = Rc::new(RefCell::new(vec![]));
+    let result = {
+        let diagnostics = diagnostics.clone();
+        Configuration::new()
+            .log_quiet()
+            .force_build(true)
+            .set_diagnostic_handler(move |diagnostic| {
+                diagnostics.borrow_mut().push(diagnostic.clone())
+            })
+            .process_file(&file)
+    };
+    fs::remove_dir_all(&dir).unwrap();
+
+    // the error is reported against the included file
+    assert!(result.is_err());
+    let diagnostics = diagnostics.borrow();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].file, dir.join("tokens.txt"));
+    assert_eq!(&tokens[diagnostics[0].span.clone()], "\"y\"");
+}
+
+#[test]
+fn stable_variant_names() {
+    let variant = |code: Option<String>, ty: &str| {
+        let suffix = format!("({}),", ty);
+        code.unwrap()
+            .lines()
+            .map(|line| line.trim().to_string())
+            .find(|line| line.starts_with("Variant") && line.ends_with(&suffix))
+            .unwrap()
+    };
+    let before = r#"
+grammar;
+
+pub Expr: i32 = r"[0-9]+" => 0;
+"#;
+    let after = r#"
+grammar;
+
+pub Expr: i32 = {
+    r"[0-9]+" => 0,
+    <w:Word> => w.len() as i32,
+};
+
+Word: String = r"[a-z]+" => <>.to_string();
+"#;
+    let (code, _) = process("variants-before", before, &mut Configuration::new());
+    let (new_code, _) = process("variants-after", after, &mut Configuration::new());
+
+    // the new `String` variant leaves the name of the `i32` one alone
+    assert_eq!(variant(code, "i32"), variant(new_code.clone(), "i32"));
+    variant(new_code, "String");
+}
+
+#[test]
+fn source_map() {
+    let text = r#"
+grammar;
+
+pub Expr: i32 = {
+    <l:Expr> "+" <r:Num> => l + r,
+    Num,
+};
+
+Num: i32 = r"[0-9]+" => <>.parse().unwrap();
+"#;
+    let (code, _) = process(
+        "source-map",
+        text,
+        Configuration::new().emit_source_map(true),
+    );
+    let code = code.unwrap();
+    let file = env::temp_dir()
+        .join("lalrpop-api-source-map")
+        .join("grammar.lalrpop");
+    let num = format!("// lalrpop-source: {}:9:12", file.display());
+    assert!(code.contains(&format!("// lalrpop-source: {}:4:5", file.display())));
+    assert!(code.contains(&num));
+
+    // a panic in the action of `Num` is traced back to the grammar
+    let rs_file = env::temp_dir().join("lalrpop-api-source-map.rs");
+    fs::write(&rs_file, &code).unwrap();
+    let action_line = code
+        .lines()
+        .position(|line| line.trim().ends_with(".parse().unwrap()"))
+        .unwrap()
+        + 1;
+    let panic = format!("panicked at {}:{}:9", rs_file.display(), action_line);
+    let before_marker = format!("{}:1:1", rs_file.display());
+    let translated = crate::translate_locations(&format!("{}\n{}", panic, before_marker));
+    fs::remove_file(&rs_file).unwrap();
+    assert_eq!(
+        translated,
+        format!("panicked at {}:9:12\n{}", file.display(), before_marker)
+    );
+}
+
+#[test]
+fn token_to_symbol() {
+    let text = r#"
+grammar;
+
+extern {
+    enum Tok {
+        "+" => Tok::Plus,
+        Num => Tok::Num(<i32>),
+    }
+}
+
+pub Expr: i32 = {
+    <l:Expr> "+" <r:Num> => l + r,
+    Num,
+};
+"#;
+    let (code, _) = process("token-to-symbol", text, &mut Configuration::new());
+    let code = code.unwrap();
+    let symbol_fn = &code[code.find("fn __token_to_symbol").unwrap()..];
+    let symbol_fn = &symbol_fn[..symbol_fn.find("_ =>").unwrap()];
+
+    // the token is taken apart in the same match that classifies it
+    assert!(!symbol_fn.contains("token_index"));
+    assert_eq!(symbol_fn.matches("match ").count(), 1);
+    assert!(symbol_fn.contains("Tok::Plus if true => __Symbol::"));
+    assert!(symbol_fn.contains("Tok::Num(__tok0) if true => __Symbol::"));
+}
@@ -32,9 +32,15 @@
 
 use crate::grammar::repr as r;
 use crate::rust::RustWrite;
+use crate::source_map;
+use crate::tls::Tls;
 use std::io::{self, Write};
 
 pub fn emit_action_code<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>) -> io::Result<()> {
+    if Tls::session().coverage {
+        emit_coverage_counters(grammar, rust)?;
+    }
+
     for (i, defn) in grammar.action_fn_defns.iter().enumerate() {
         rust!(rust, "");
 
@@ -55,12 +61,63 @@ pub fn emit_action_code<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>)
             r::ActionFnDefnKind::Inline(ref data) => {
                 emit_inline_action_code(grammar, rust, i, defn, data)?
             }
+            r::ActionFnDefnKind::Guard(ref data) => emit_guard_code(grammar, rust, i, data)?,
         }
     }
 
     Ok(())
 }
 
+/// Emits the `COVERAGE` static, with a counter for each of the rules in
+/// `grammar.rules`, which their actions increment.
+fn emit_coverage_counters<W: Write>(
+    grammar: &r::Grammar,
+    rust: &mut RustWrite<W>,
+) -> io::Result<()> {
+    let prefix = &grammar.prefix;
+    rust!(rust, "");
+    rust!(rust, "#[allow(dead_code)]");
+    rust!(
+        rust,
+        "pub static COVERAGE: {p}lalrpop_util::coverage::Coverage = \
+         {p}lalrpop_util::coverage::Coverage::new(&[",
+        p = prefix
+    );
+    for (_, rule) in &grammar.rules {
+        rust!(rust, "r###\"{}\"###,", rule);
+    }
+    rust!(rust, "], &{}COVERAGE_COUNTS);", prefix);
+    rust!(
+        rust,
+        "static {p}COVERAGE_COUNTS: [core::sync::atomic::AtomicUsize; {n}] = \
+         [{p}lalrpop_util::coverage::UNUSED; {n}];",
+        p = prefix,
+        n = grammar.rules.len()
+    );
+    Ok(())
+}
+
+/// With `emit_source_map`, ties the body of action fn `index` to the
+/// alternative of the grammar that it is the action (or guard) of.
+fn emit_source_marker<W: Write>(
+    grammar: &r::Grammar,
+    rust: &mut RustWrite<W>,
+    index: usize,
+) -> io::Result<()> {
+    if !Tls::session().emit_source_map {
+        return Ok(());
+    }
+    let production = grammar
+        .nonterminals
+        .values()
+        .flat_map(|nt| &nt.productions)
+        .find(|p| p.action.index() == index || p.guard.map(|g| g.index()) == Some(index));
+    if let Some(production) = production {
+        rust!(rust, "{}", source_map::marker(production.span));
+    }
+    Ok(())
+}
+
 fn ret_type_string(grammar: &r::Grammar, defn: &r::ActionFnDefn) -> String {
     if defn.fallible {
         format!(
@@ -130,6 +187,55 @@ fn emit_user_action_code<W: Write>(
     .emit()?;
 
     rust!(rust, "{{");
+    emit_source_marker(grammar, rust, index)?;
+    if Tls::session().coverage {
+        let rule = grammar
+            .rules
+            .iter()
+            .position(|&(action, _)| action.index() == index);
+        if let Some(rule) = rule {
+            rust!(rust, "COVERAGE.record({});", rule);
+        }
+    }
+    rust!(rust, "{}", data.code);
+    rust!(rust, "}}");
+    Ok(())
+}
+
+/// Guards look like user actions returning `bool`, except that they
+/// borrow the symbols being reduced, which are still on the stack:
+///
+/// ```
+/// fn __action18(
+///     input: &'input str,
+///     (_, a, _): (&usize, &Foo, &usize),
+/// ) -> bool
+/// ```
+fn emit_guard_code<W: Write>(
+    grammar: &r::Grammar,
+    rust: &mut RustWrite<W>,
+    index: usize,
+    data: &r::UserActionFnDefn,
+) -> io::Result<()> {
+    let loc_type = grammar.types.terminal_loc_type();
+    let arguments: Vec<String> = data
+        .arg_patterns
+        .iter()
+        .zip(&data.arg_types)
+        .map(|(name, ty)| format!("(_, {}, _): (&{}, &{}, &{})", name, loc_type, ty, loc_type))
+        .collect();
+
+    rust.fn_header(
+        &r::Visibility::Priv,
+        format!("{}action{}", grammar.prefix, index),
+    )
+    .with_grammar(grammar)
+    .with_parameters(arguments)
+    .with_return_type("bool")
+    .emit()?;
+
+    rust!(rust, "{{");
+    emit_source_marker(grammar, rust, index)?;
     rust!(rust, "{}", data.code);
     rust!(rust, "}}");
     Ok(())
@@ -169,11 +275,19 @@ fn emit_lookaround_action_code<W: Write>(
             // at EOF, so taker the lookbehind (end of last
             // pushed token); if that is missing too, then
             // supply default.
-            rust!(rust, "{}lookahead.clone()", grammar.prefix);
+            rust!(
+                rust,
+                "{}",
+                grammar.clone_location_behind(&format!("{}lookahead", grammar.prefix))
+            );
         }
         r::LookaroundActionFnDefn::Lookbehind => {
             // take lookbehind or supply default
-            rust!(rust, "{}lookbehind.clone()", grammar.prefix);
+            rust!(
+                rust,
+                "{}",
+                grammar.clone_location_behind(&format!("{}lookbehind", grammar.prefix))
+            );
         }
     }
     rust!(rust, "}}");
@@ -237,6 +351,7 @@ fn emit_inline_action_code<W: Write>(
     .with_return_type(ret_type)
     .emit()?;
     rust!(rust, "{{");
+    emit_source_marker(grammar, rust, index)?;
 
     // For each inlined thing, compute the start/end locations.
     // Do this first so that none of the arguments have been moved
@@ -256,21 +371,19 @@ fn emit_inline_action_code<W: Write>(
 
                     rust!(
                         rust,
-                        "let {}start{} = {}{}.0.clone();",
+                        "let {}start{} = {};",
                         grammar.prefix,
                         temp_counter,
-                        grammar.prefix,
-                        arg_counter
+                        grammar.clone_location(&format!("{}{}.0", grammar.prefix, arg_counter))
                     );
 
                     let last_arg_index = arg_counter + syms.len() - 1;
                     rust!(
                         rust,
-                        "let {}end{} = {}{}.2.clone();",
+                        "let {}end{} = {};",
                         grammar.prefix,
                         temp_counter,
-                        grammar.prefix,
-                        last_arg_index
+                        grammar.clone_location(&format!("{}{}.2", grammar.prefix, last_arg_index))
                     );
                 } else {
                     // If we have no symbols, then `arg_counter`
@@ -282,56 +395,60 @@ fn emit_inline_action_code<W: Write>(
                     if arg_counter > 0 {
                         rust!(
                             rust,
-                            "let {}start{} = {}{}.2.clone();",
+                            "let {}start{} = {};",
                             grammar.prefix,
                             temp_counter,
-                            grammar.prefix,
-                            arg_counter - 1
+                            grammar.clone_location(&format!(
+                                "{}{}.2",
+                                grammar.prefix,
+                                arg_counter - 1
+                            ))
                         );
                     } else if num_flat_args > 0 {
                         rust!(
                             rust,
-                            "let {}start{} = {}{}.0.clone();",
+                            "let {}start{} = {};",
                             grammar.prefix,
                             temp_counter,
-                            grammar.prefix,
-                            arg_counter
+                            grammar.clone_location(&format!("{}{}.0", grammar.prefix, arg_counter))
                         );
                     } else {
                         rust!(
                             rust,
-                            "let {}start{} = {}lookbehind.clone();",
+                            "let {}start{} = {};",
                             grammar.prefix,
                             temp_counter,
-                            grammar.prefix
+                            grammar.clone_location_behind(&format!("{}lookbehind", grammar.prefix))
                         );
                     }
 
                     if arg_counter < num_flat_args {
                         rust!(
                             rust,
-                            "let {}end{} = {}{}.0.clone();",
+                            "let {}end{} = {};",
                             grammar.prefix,
                             temp_counter,
-                            grammar.prefix,
-                            arg_counter
+                            grammar.clone_location(&format!("{}{}.0", grammar.prefix, arg_counter))
                         );
                     } else if num_flat_args > 0 {
                         rust!(
                             rust,
-                            "let {}end{} = {}{}.2.clone();",
+                            "let {}end{} = {};",
                             grammar.prefix,
                             temp_counter,
-                            grammar.prefix,
-                            num_flat_args - 1
+                            grammar.clone_location(&format!(
+                                "{}{}.2",
+                                grammar.prefix,
+                                num_flat_args - 1
+                            ))
                         );
                     } else {
                         rust!(
                             rust,
-                            "let {}end{} = {}lookahead.clone();",
+                            "let {}end{} = {};",
                             grammar.prefix,
                             temp_counter,
-                            grammar.prefix
+                            grammar.clone_location_behind(&format!("{}lookahead", grammar.prefix))
                         );
                     }
                 }
@@ -0,0 +1,136 @@
+//! Reads the `.messages` file of a grammar, which gives hand-written
+//! messages for syntax errors, in the style of Menhir. Each entry is
+//! a paragraph of example sentences, one per line, followed by a
+//! paragraph with the message:
+//!
+//! ```text
+//! # a comment
+//! Expr: "(" "1"
+//! Expr: "(" "1" "+" "2"
+//!
+//! missing `)`
+//! ```
+//!
+//! A sentence names a public nonterminal and the terminals of an input
+//! that it cannot parse. The message is for the syntax errors found in
+//! the state that parsing the sentence ends up in: at its last
+//! terminal, or at the end of the input if the parser gets that far.
+
+use crate::grammar::parse_tree::{MatchMapping, NonterminalString, Span, TerminalString};
+use crate::grammar::repr as r;
+use crate::parser;
+use string_cache::DefaultAtom as Atom;
+
+/// Parses the `.messages` file `text` of `grammar`, returning the span
+/// and message of the first error.
+pub fn parse_messages(
+    grammar: &r::Grammar,
+    text: &str,
+) -> Result<Vec<r::ErrorMessage>, (Span, String)> {
+    // the paragraphs, as lists of lines with their offset
+    let mut paragraphs: Vec<Vec<(usize, &str)>> = vec![];
+    let mut in_paragraph = false;
+    let mut offset = 0;
+    for line in text.split('\n') {
+        let start = offset;
+        offset += line.len() + 1;
+        let line = line.trim_end();
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        if line.trim_start().is_empty() {
+            in_paragraph = false;
+            continue;
+        }
+        if !in_paragraph {
+            paragraphs.push(vec![]);
+            in_paragraph = true;
+        }
+        paragraphs.last_mut().unwrap().push((start, line));
+    }
+
+    let mut entries = vec![];
+    let mut paragraphs = paragraphs.into_iter();
+    while let Some(sentences) = paragraphs.next() {
+        let message = match paragraphs.next() {
+            Some(lines) => lines
+                .iter()
+                .map(|&(_, line)| line.trim_start())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => {
+                let (start, line) = sentences[sentences.len() - 1];
+                return Err((
+                    Span(start, start + line.len()),
+                    "expected a message after the sentences".to_string(),
+                ));
+            }
+        };
+        for (start, line) in sentences {
+            let span = Span(start, start + line.len());
+            let (nonterminal, sentence) =
+                parse_sentence(grammar, line).map_err(|message| (span, message))?;
+            entries.push(r::ErrorMessage {
+                span,
+                start: nonterminal,
+                sentence,
+                message: message.clone(),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Parses a sentence like `Expr: "(" Num`.
+fn parse_sentence(
+    grammar: &r::Grammar,
+    line: &str,
+) -> Result<(NonterminalString, Vec<TerminalString>), String> {
+    let colon = match line.find(':') {
+        Some(colon) => colon,
+        None => return Err("expected a sentence like `Start: \"a\" \"b\"`".to_string()),
+    };
+    let nonterminal = NonterminalString(Atom::from(line[..colon].trim()));
+    if !grammar.start_nonterminals.contains_key(&nonterminal) {
+        return Err(format!("`{}` is not a public nonterminal", nonterminal));
+    }
+
+    let mut terminals = vec![];
+    let mut rest = line[colon + 1..].trim_start();
+    while !rest.is_empty() {
+        let len = terminal_len(rest);
+        let text = &rest[..len];
+        let terminal = match parser::parse_match_mapping(text, 0) {
+            Ok(MatchMapping::Terminal(terminal)) if grammar.terminals.all.contains(&terminal) => {
+                terminal
+            }
+            _ => return Err(format!("`{}` is not a terminal of the grammar", text)),
+        };
+        terminals.push(terminal);
+        rest = rest[len..].trim_start();
+    }
+    Ok((nonterminal, terminals))
+}
+
+/// The length of the terminal at the start of `text`: a string literal,
+/// which may contain spaces, or else everything up to the next space.
+fn terminal_len(text: &str) -> usize {
+    let quoted = if text.starts_with('"') {
+        Some(1)
+    } else if text.starts_with("r\"") {
+        Some(2)
+    } else {
+        None
+    };
+    if let Some(open) = quoted {
+        let mut escaped = false;
+        for (i, c) in text[open..].char_indices() {
+            match c {
+                '\\' if open == 1 => escaped = !escaped,
+                '"' if !escaped => return open + i + 1,
+                _ => escaped = false,
+            }
+        }
+    }
+    text.find(char::is_whitespace).unwrap_or(text.len())
+}
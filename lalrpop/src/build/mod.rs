@@ -1,33 +1,46 @@
 //! Utilies for running in a build script.
 
+use crate::diagnostic::{Diagnostic, Severity};
 use crate::file_text::FileText;
+use crate::grammar::consts::{INLINE, RESUMABLE};
 use crate::grammar::parse_tree as pt;
 use crate::grammar::repr as r;
+use crate::json;
 use crate::lexer::intern_token;
 use crate::lr1;
 use crate::message::builder::InlineBuilder;
 use crate::message::{Content, Message};
 use crate::normalize;
 use crate::parser;
+use crate::railroad;
 use crate::rust::RustWrite;
 use crate::session::{ColorConfig, Session};
+use crate::source_map;
 use crate::tls::Tls;
 use crate::tok;
 use crate::util::Sep;
 use atty;
 use itertools::Itertools;
+use lalrpop_util::interpret::ParseTables;
 use lalrpop_util::ParseError;
 use term;
 use tiny_keccak::{Hasher, Sha3};
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
 use std::fs;
 use std::io::{self, BufRead, Read, Write};
+use std::mem;
+use std::ops::Range;
+use std::panic;
 use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::rc::Rc;
 
 mod action;
 mod fake_term;
+mod messages;
 
 use self::fake_term::FakeTerminal;
 
@@ -39,13 +52,22 @@ const LALRPOP_VERSION_HEADER: &str = concat!(
     "\""
 );
 
+/// Hashes the grammar `file`, along with its `.messages` file if it
+/// has one and the files it includes.
 fn hash_file(file: &Path) -> io::Result<String> {
-    let mut file = fs::File::open(&file)?;
-    let mut file_bytes = Vec::new();
-    file.read_to_end(&mut file_bytes).unwrap();
-
     let mut sha3 = Sha3::v256();
-    sha3.update(&file_bytes);
+    let paths = vec![file.to_path_buf(), messages_file(file)]
+        .into_iter()
+        .chain(included_files(file)?);
+    for (index, path) in paths.enumerate() {
+        if index > 0 && !path.exists() {
+            continue;
+        }
+        let mut file = fs::File::open(path)?;
+        let mut file_bytes = Vec::new();
+        file.read_to_end(&mut file_bytes).unwrap();
+        sha3.update(&file_bytes);
+    }
 
     let mut output = [0u8; 32];
     sha3.finalize(&mut output);
@@ -96,6 +118,44 @@ fn gen_resolve_file(session: &Session, lalrpop_file: &Path, ext: &str) -> io::Re
         .with_extension(ext))
 }
 
+/// The `.messages` file of the grammar `lalrpop_file`, which gives
+/// messages for its syntax errors; see `messages`.
+fn messages_file(lalrpop_file: &Path) -> PathBuf {
+    lalrpop_file.with_extension("messages")
+}
+
+/// The file that the tables of the parsers generated into `rs_file` go
+/// to, with `split_tables`.
+fn tables_file(rs_file: &Path) -> PathBuf {
+    let stem = rs_file.file_stem().unwrap_or_default().to_string_lossy();
+    rs_file.with_file_name(format!("{}_tables.rs", stem))
+}
+
+/// The file that `include` names, in the grammar `lalrpop_file`.
+fn include_path(lalrpop_file: &Path, include: &pt::Include) -> PathBuf {
+    lalrpop_file
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(&*include.path)
+}
+
+/// The files that the grammar `lalrpop_file` includes, if it parses.
+fn included_files(lalrpop_file: &Path) -> io::Result<Vec<PathBuf>> {
+    let text = fs::read_to_string(lalrpop_file)?;
+    let grammar = match parser::parse_grammar(&text) {
+        Ok(grammar) => grammar,
+        Err(_) => return Ok(vec![]),
+    };
+    Ok(grammar
+        .items
+        .iter()
+        .filter_map(|item| match *item {
+            pt::GrammarItem::Include(ref include) => Some(include_path(lalrpop_file, include)),
+            _ => None,
+        })
+        .collect())
+}
+
 fn process_file_into(
     session: Rc<Session>,
     lalrpop_file: &Path,
@@ -103,6 +163,9 @@ fn process_file_into(
     report_file: &Path,
 ) -> io::Result<()> {
     session.emit_rerun_directive(lalrpop_file);
+    if messages_file(lalrpop_file).exists() {
+        session.emit_rerun_directive(&messages_file(lalrpop_file));
+    }
     if session.force_build || needs_rebuild(&lalrpop_file, &rs_file)? {
         log!(
             session,
@@ -114,9 +177,16 @@ fn process_file_into(
             fs::create_dir_all(parent)?;
         }
         remove_old_file(&rs_file)?;
-
-        // Load the LALRPOP source text for this file:
-        let file_text = Rc::new(FileText::from_path(lalrpop_file.to_path_buf())?);
+        remove_old_file(&tables_file(rs_file))?;
+
+        // Load the LALRPOP source text for this file, and parse it,
+        // which also loads the files it includes:
+        let mut file_text = FileText::from_path(lalrpop_file.to_path_buf())?;
+        let grammar = parse_grammar_file(&mut file_text);
+        for path in file_text.included_paths() {
+            session.emit_rerun_directive(path);
+        }
+        let file_text = Rc::new(file_text);
 
         // Store the session and file-text in TLS -- this is not
         // intended to be used in this high-level code, but it gives
@@ -130,8 +200,41 @@ fn process_file_into(
         // generation fails at some point, we don't leave a partial
         // file behind.
         {
-            let grammar = parse_and_normalize_grammar(&session, &file_text)?;
-            let buffer = emit_recursive_ascent(&session, &grammar, &report_file)?;
+            // precedence is expanded away by normalization
+            let tree = match grammar {
+                Ok(ref grammar) if session.emit_grammar_json => Some(grammar.clone()),
+                _ => None,
+            };
+            let mut grammar = match grammar.and_then(|grammar| {
+                normalize::normalize(&session, grammar).map_err(|error| (error.span, error.message))
+            }) {
+                Ok(grammar) => grammar,
+                Err((span, message)) => return Err(report_error(&session, &file_text, span, message)),
+            };
+            if let Some(tree) = tree {
+                let levels = normalize::precedence_levels(&tree);
+                fs::write(
+                    rs_file.with_extension("json"),
+                    json::grammar_json(&grammar, &levels),
+                )?;
+            }
+            for warning in normalize::warnings(&grammar) {
+                report_warning(&session, &file_text, warning.span, warning.message);
+            }
+            let messages_path = messages_file(lalrpop_file);
+            if messages_path.exists() {
+                let messages_text = FileText::from_path(messages_path)?;
+                grammar.error_messages =
+                    match messages::parse_messages(&grammar, messages_text.text()) {
+                        Ok(entries) => entries,
+                        Err((span, message)) => {
+                            return Err(report_error(&session, &messages_text, span, message))
+                        }
+                    };
+            }
+            let tables_file = Some(tables_file(rs_file)).filter(|_| session.split_tables);
+            let buffer =
+                emit_recursive_ascent(&session, &grammar, &report_file, tables_file.as_deref())?;
             let mut output_file = fs::File::create(&rs_file)?;
             writeln!(output_file, "{}", LALRPOP_VERSION_HEADER)?;
             writeln!(output_file, "{}", hash_file(&lalrpop_file)?)?;
@@ -206,50 +309,95 @@ fn lalrpop_files<P: AsRef<Path>>(root_dir: P) -> io::Result<Vec<PathBuf>> {
     Ok(result)
 }
 
-fn parse_and_normalize_grammar(session: &Session, file_text: &FileText) -> io::Result<r::Grammar> {
-    let grammar = match parser::parse_grammar(file_text.text()) {
-        Ok(grammar) => grammar,
-
-        Err(ParseError::InvalidToken { location }) => {
-            let ch = file_text.text()[location..].chars().next().unwrap();
-            report_error(
-                &file_text,
-                pt::Span(location, location),
-                &format!("invalid character `{}`", ch),
-            );
+/// Parses the grammar of `file_text`, with the items of each file it
+/// includes in place of the `include`, returning the span and message
+/// of the first error. The text of the included files is added to
+/// `file_text`, so that the spans in them can be reported.
+fn parse_grammar_file(file_text: &mut FileText) -> Result<pt::Grammar, (pt::Span, String)> {
+    let text = file_text.text().clone();
+    let mut grammar =
+        parser::parse_grammar(&text).map_err(|error| describe_parse_error(&text, error))?;
+
+    let mut items = vec![];
+    for item in mem::take(&mut grammar.items) {
+        let include = match item {
+            pt::GrammarItem::Include(include) => include,
+            item => {
+                items.push(item);
+                continue;
+            }
+        };
+        let path = include_path(file_text.path(), &include);
+        let included = fs::read_to_string(&path).map_err(|error| {
+            (
+                include.span,
+                format!("cannot read `{}`: {}", path.display(), error),
+            )
+        })?;
+        let offset = file_text.include(path, included.clone());
+        let included_items = parser::parse_include(&included, offset).map_err(|error| {
+            // the error is described in terms of the text of the file
+            // (the nested parse of a `=>` mapping can report 0)
+            let error = error
+                .map_location(|location| location.saturating_sub(offset))
+                .map_error(|error| tok::Error {
+                    location: error.location.saturating_sub(offset),
+                    ..error
+                });
+            let (span, message) = describe_parse_error(&included, error);
+            (pt::Span(span.0 + offset, span.1 + offset), message)
+        })?;
+        items.extend(included_items);
+
+        // the prefix must not appear in the included code either
+        while included.contains(&grammar.prefix) {
+            grammar.prefix.push('_');
         }
+    }
+    grammar.items = items;
+    Ok(grammar)
+}
+
+/// Parses and normalizes the grammar `text`, returning the span and
+/// message of the first error.
+fn parse_and_normalize(session: &Session, text: &str) -> Result<r::Grammar, (pt::Span, String)> {
+    let grammar = parser::parse_grammar(text).map_err(|error| describe_parse_error(text, error))?;
+    normalize::normalize(session, grammar).map_err(|error| (error.span, error.message))
+}
 
-        Err(ParseError::UnrecognizedEOF { location, .. }) => {
-            report_error(
-                &file_text,
+/// The span and message of a syntax error in the grammar `text`.
+pub(crate) fn describe_parse_error(text: &str, error: parser::ParseError) -> (pt::Span, String) {
+    match error {
+        ParseError::InvalidToken { location } => {
+            let ch = text[location..].chars().next().unwrap();
+            (
                 pt::Span(location, location),
-                "unexpected end of file",
-            );
+                format!("invalid character `{}`", ch),
+            )
         }
 
-        Err(ParseError::UnrecognizedToken {
+        ParseError::UnrecognizedEOF { location, .. } => (
+            pt::Span(location, location),
+            "unexpected end of file".to_string(),
+        ),
+
+        ParseError::UnrecognizedToken {
             token: (lo, _, hi),
             expected,
-        }) => {
+        } => {
             let _ = expected; // didn't implement this yet :)
-            let text = &file_text.text()[lo..hi];
-            report_error(
-                &file_text,
+            (
                 pt::Span(lo, hi),
-                &format!("unexpected token: `{}`", text),
-            );
+                format!("unexpected token: `{}`", &text[lo..hi]),
+            )
         }
 
-        Err(ParseError::ExtraToken { token: (lo, _, hi) }) => {
-            let text = &file_text.text()[lo..hi];
-            report_error(
-                &file_text,
-                pt::Span(lo, hi),
-                &format!("extra token at end of input: `{}`", text),
-            );
-        }
+        ParseError::ExtraToken { token: (lo, _, hi) } => (
+            pt::Span(lo, hi),
+            format!("extra token at end of input: `{}`", &text[lo..hi]),
+        ),
 
-        Err(ParseError::User { error }) => {
+        ParseError::User { error } => {
             let string = match error.code {
                 tok::ErrorCode::UnrecognizedToken => "unrecognized token",
                 tok::ErrorCode::UnterminatedEscape => "unterminated escape; missing '`'?",
@@ -269,28 +417,72 @@ fn parse_and_normalize_grammar(session: &Session, file_text: &FileText) -> io::R
                 }
             };
 
-            report_error(
-                &file_text,
+            (
                 pt::Span(error.location, error.location + 1),
-                string,
+                string.to_string(),
             )
         }
-    };
-
-    match normalize::normalize(session, grammar) {
-        Ok(grammar) => Ok(grammar),
-        Err(error) => report_error(&file_text, error.span, &error.message),
     }
 }
 
-fn report_error(file_text: &FileText, span: pt::Span, message: &str) -> ! {
-    println!("{} error: {}", file_text.span_str(span), message);
+/// Reports an error in the grammar: to the diagnostic handler if there
+/// is one, returning the error to propagate, and otherwise by printing
+/// it and exiting.
+fn report_error(
+    session: &Session,
+    file_text: &FileText,
+    span: pt::Span,
+    message: String,
+) -> io::Error {
+    if session.diagnostic_handler.is_none() {
+        println!("{} error: {}", file_text.span_str(span), message);
 
-    let out = io::stderr();
-    let mut out = out.lock();
-    file_text.highlight(span, &mut out).unwrap();
+        let out = io::stderr();
+        let mut out = out.lock();
+        file_text.highlight(span, &mut out).unwrap();
 
-    exit(1);
+        exit(1);
+    }
+    report_diagnostics(session, file_text, vec![(span, message)])
+}
+
+/// Reports a warning about the grammar: to the diagnostic handler if
+/// there is one, and otherwise by printing it.
+fn report_warning(session: &Session, file_text: &FileText, span: pt::Span, message: String) {
+    let (located, relative) = file_text.locate(span);
+    let reported = session.emit_diagnostic(Diagnostic {
+        severity: Severity::Warning,
+        file: located.path().to_path_buf(),
+        span: relative.0..relative.1,
+        message: message.clone(),
+    });
+    if !reported {
+        println!("{} warning: {}", file_text.span_str(span), message);
+
+        let out = io::stderr();
+        let mut out = out.lock();
+        file_text.highlight(span, &mut out).unwrap();
+    }
+}
+
+/// Passes `errors` to the diagnostic handler, returning an error that
+/// describes the first one.
+fn report_diagnostics(
+    session: &Session,
+    file_text: &FileText,
+    errors: Vec<(pt::Span, String)>,
+) -> io::Error {
+    let description = format!("{} error: {}", file_text.span_str(errors[0].0), errors[0].1);
+    for (span, message) in errors {
+        let (file_text, span) = file_text.locate(span);
+        session.emit_diagnostic(Diagnostic {
+            severity: Severity::Error,
+            file: file_text.path().to_path_buf(),
+            span: span.0..span.1,
+            message,
+        });
+    }
+    io::Error::new(io::ErrorKind::InvalidData, description)
 }
 
 fn report_messages(messages: Vec<Message>) -> term::Result<()> {
@@ -334,12 +526,149 @@ fn emit_uses<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>) -> io::Res
     rust.write_uses("", grammar)
 }
 
+/// Emits the `terminal_ids` module, with a constant for each id given
+/// with `#[terminal_id]`, and `ALL`, which pairs the terminals, as
+/// written in the `expected` lists of parse errors, with their ids.
+fn emit_terminal_ids<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>) -> io::Result<()> {
+    if grammar.terminal_ids.is_empty() {
+        return Ok(());
+    }
+    rust!(rust, "");
+    rust!(rust, "pub mod terminal_ids {{");
+    for terminal_id in &grammar.terminal_ids {
+        rust!(
+            rust,
+            "pub const {}: u32 = {};",
+            terminal_id.name,
+            terminal_id.id
+        );
+    }
+    rust!(rust, "pub const ALL: &[(&str, u32)] = &[");
+    for terminal_id in &grammar.terminal_ids {
+        rust!(
+            rust,
+            "(r###\"{}\"###, {}),",
+            terminal_id.terminal,
+            terminal_id.id
+        );
+    }
+    rust!(rust, "];");
+    rust!(rust, "}}");
+    Ok(())
+}
+
+/// Appends the terminal overlaps that the lexer resolved by precedence
+/// to the report file.
+fn report_token_overlaps<W: Write>(out: &mut W, intern_token: &pt::InternToken) -> io::Result<()> {
+    if intern_token.dfa.overlaps.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "\nTerminal overlaps")?;
+    writeln!(out, "----------------------------------------")?;
+    for overlap in &intern_token.dfa.overlaps {
+        writeln!(out, "{}", intern_token.describe_overlap(overlap))?;
+    }
+    Ok(())
+}
+
+/// Appends the FIRST and FOLLOW sets of the nonterminals to the report
+/// file, leaving out the start nonterminals that LALRPOP adds and the
+/// inlined ones, which the parser never reduces.
+fn report_first_follow<W: Write>(out: &mut W, grammar: &r::Grammar) -> io::Result<()> {
+    writeln!(out, "\nFirst and follow sets")?;
+    writeln!(out, "----------------------------------------")?;
+    let starts: Vec<_> = grammar.start_nonterminals.values().collect();
+    for (nonterminal, sets) in lr1::first_follow_sets(grammar) {
+        let inlined = grammar.nonterminals[&nonterminal]
+            .annotations
+            .iter()
+            .any(|a| a.id == *INLINE);
+        if inlined || starts.contains(&&nonterminal) {
+            continue;
+        }
+        let mut first: Vec<_> = sets.first.iter().map(|t| t.to_string()).collect();
+        if sets.nullable {
+            first.push("(empty)".to_string());
+        }
+        let mut follow: Vec<_> = sets.follow.iter().map(|t| t.to_string()).collect();
+        if sets.end_of_input {
+            follow.push("EOF".to_string());
+        }
+        writeln!(out, "{}", nonterminal)?;
+        writeln!(out, "    first:  {}", first.join(" "))?;
+        writeln!(out, "    follow: {}", follow.join(" "))?;
+    }
+    Ok(())
+}
+
 fn emit_recursive_ascent(
     session: &Session,
     grammar: &r::Grammar,
     report_file: &Path,
+    tables_file: Option<&Path>,
 ) -> io::Result<Vec<u8>> {
+    match emit_parser(session, grammar, Some(report_file), tables_file) {
+        Ok(buffer) => Ok(buffer),
+        Err(EmitError::Io(error)) => Err(error),
+        Err(EmitError::NoStartSymbols) => {
+            let message = "no public symbols declared in grammar";
+            if session.diagnostic_handler.is_none() {
+                println!("Error: {}", message);
+                exit(1)
+            }
+            let errors = vec![(pt::Span(0, 0), message.to_string())];
+            Err(report_diagnostics(session, &Tls::file_text(), errors))
+        }
+        Err(EmitError::Conflicts(messages)) => {
+            if session.diagnostic_handler.is_none() {
+                let _ = report_messages(messages);
+                exit(1) // FIXME -- propagate up instead of calling `exit`
+            }
+            let errors = messages
+                .iter()
+                .map(|message| (message.span(), message_text(message)))
+                .collect();
+            Err(report_diagnostics(session, &Tls::file_text(), errors))
+        }
+        Err(EmitError::Messages(errors)) => {
+            let messages_text = FileText::from_path(messages_file(Tls::file_text().path()))?;
+            if session.diagnostic_handler.is_none() {
+                let (span, message) = errors.into_iter().next().unwrap();
+                return Err(report_error(session, &messages_text, span, message));
+            }
+            Err(report_diagnostics(session, &messages_text, errors))
+        }
+    }
+}
+
+/// Why `emit_parser` could not generate a parser.
+enum EmitError {
+    Io(io::Error),
+    NoStartSymbols,
+    Conflicts(Vec<Message>),
+    /// Errors in the sentences of the `.messages` file.
+    Messages(Vec<(pt::Span, String)>),
+}
+
+impl From<io::Error> for EmitError {
+    fn from(error: io::Error) -> Self {
+        EmitError::Io(error)
+    }
+}
+
+/// Generates the parser for `grammar`, writing the report of its
+/// states to `report_file`, and its HTML version next to it, if one is
+/// given and the session asks for them. The tables of table-driven
+/// parsers go to `tables_file`, if one is given, which the generated
+/// code includes.
+fn emit_parser(
+    session: &Session,
+    grammar: &r::Grammar,
+    report_file: Option<&Path>,
+    tables_file: Option<&Path>,
+) -> Result<Vec<u8>, EmitError> {
     let mut rust = RustWrite::new(vec![]);
+    let mut tables = RustWrite::new(vec![]);
 
     // We generate a module structure like this:
     //
@@ -362,12 +691,15 @@ fn emit_recursive_ascent(
 
     emit_module_attributes(grammar, &mut rust)?;
     emit_uses(grammar, &mut rust)?;
+    emit_terminal_ids(grammar, &mut rust)?;
 
     if grammar.start_nonterminals.is_empty() {
-        println!("Error: no public symbols declared in grammar");
-        exit(1);
+        return Err(EmitError::NoStartSymbols);
     }
 
+    let mut html_report = lr1::HtmlReport::new();
+    let mut shadowed = BTreeMap::new();
+    let mut backtracked = BTreeSet::new();
     for (user_nt, start_nt) in &grammar.start_nonterminals {
         // We generate these, so there should always be exactly 1
         // production. Otherwise the LR(1) algorithm doesn't know
@@ -383,22 +715,54 @@ fn emit_recursive_ascent(
 
         let _lr1_tls = lr1::Lr1Tls::install(grammar.terminals.clone());
 
-        let lr1result = lr1::build_states(&grammar, start_nt.clone());
-        if session.emit_report {
-            let mut output_report_file = fs::File::create(&report_file)?;
+        let lr1result = match lr1::build_states(grammar, start_nt.clone()) {
+            Err(error) if grammar.backtrack || grammar.uses_guards() => {
+                lr1::accept_conflicts(grammar, start_nt.clone(), error)
+            }
+            lr1result => lr1result,
+        };
+        if let (true, Some(report_file)) = (session.emit_report, report_file) {
+            let mut output_report_file = fs::File::create(report_file)?;
             lr1::generate_report(&mut output_report_file, &lr1result)?;
+            if let Some(ref intern_token) = grammar.intern_token {
+                report_token_overlaps(&mut output_report_file, intern_token)?;
+            }
+            report_first_follow(&mut output_report_file, grammar)?;
+        }
+        if let (true, Some(report_file)) = (session.emit_html_report, report_file) {
+            // written for each nonterminal, so that the page is there
+            // even if a later one has conflicts
+            html_report.add(user_nt, &lr1result);
+            html_report.write(&mut fs::File::create(report_file.with_extension("html"))?)?;
         }
 
         let states = match lr1result {
             Ok(states) => states,
-            Err(error) => {
-                let messages = lr1::report_error(&grammar, &error);
-                let _ = report_messages(messages);
-                exit(1) // FIXME -- propagate up instead of calling `exit`
-            }
+            Err(error) => return Err(EmitError::Conflicts(lr1::report_error(grammar, &error))),
         };
+        lr1::shadowed_alternatives(&states, &mut shadowed);
+        if grammar.backtrack {
+            backtracked.extend(lr1::backtracked_conflicts(grammar, &states));
+        }
+
+        let error_states =
+            lr1::error_states(grammar, user_nt, &states).map_err(EmitError::Messages)?;
+        if let Some(entry) = grammar
+            .error_messages
+            .iter()
+            .find(|entry| entry.start == *user_nt)
+            .filter(|_| grammar.codegen_for(user_nt) != r::LrCodeGeneration::TableDriven)
+        {
+            return Err(EmitError::Messages(vec![(
+                entry.span,
+                format!(
+                    "error messages need the table-driven code generator; mark `{}` #[table_driven]",
+                    user_nt
+                ),
+            )]));
+        }
 
-        match grammar.algorithm.codegen {
+        match grammar.codegen_for(user_nt) {
             r::LrCodeGeneration::RecursiveAscent => lr1::codegen::ascent::compile(
                 &grammar,
                 user_nt.clone(),
@@ -412,8 +776,12 @@ fn emit_recursive_ascent(
                 user_nt.clone(),
                 start_nt.clone(),
                 &states,
-                "super",
                 &mut rust,
+                lr1::codegen::parse_table::TableOptions {
+                    action_module: "super",
+                    error_messages: &error_states,
+                    tables: tables_file.map(|_| &mut tables),
+                },
             )?,
 
             r::LrCodeGeneration::TestAll => lr1::codegen::test_all::compile(
@@ -433,6 +801,59 @@ fn emit_recursive_ascent(
             start_nt,
             user_nt
         );
+        lr1::codegen::parser_trait::compile(grammar, user_nt, start_nt, &mut rust)?;
+        if grammar.nonterminals[user_nt]
+            .annotations
+            .iter()
+            .any(|a| a.id == *RESUMABLE)
+        {
+            rust!(
+                rust,
+                "{}use self::{}parse{}::{}ResumableParser;",
+                grammar.nonterminals[user_nt].visibility,
+                grammar.prefix,
+                start_nt,
+                user_nt
+            );
+        }
+    }
+
+    let file_text = Tls::file_text();
+    for (span, message) in backtracked {
+        report_warning(session, &file_text, span, message);
+    }
+    for (span, shadowing) in shadowed {
+        if let Some(shadowing) = shadowing {
+            let message = format!(
+                "this alternative is never chosen: wherever it applies, the parser picks \
+                 the one at {} instead",
+                file_text.location(shadowing)
+            );
+            let nonterminal = grammar
+                .nonterminals
+                .iter()
+                .find(|(_, data)| data.productions.iter().any(|p| p.span == span));
+            let message = match nonterminal {
+                Some((nonterminal, _)) => grammar.with_expansion_notes(nonterminal, message),
+                None => message,
+            };
+            report_warning(session, &file_text, span, message);
+        }
+    }
+
+    if grammar.runtime_start {
+        lr1::codegen::start_symbol::compile(grammar, &mut rust)?;
+    }
+
+    if session.emit_c_abi {
+        // the functions are named after the grammar file, so that those
+        // of several grammars in a crate do not clash
+        let file_text = Tls::file_text();
+        let name = file_text
+            .path()
+            .file_stem()
+            .map_or_else(|| "grammar".into(), |stem| stem.to_string_lossy());
+        lr1::codegen::c_abi::compile(grammar, &name, &mut rust)?;
     }
 
     if let Some(ref intern_token) = grammar.intern_token {
@@ -444,13 +865,310 @@ fn emit_recursive_ascent(
         );
     }
 
+    if let Some(tables_file) = tables_file {
+        let tables = tables.into_inner();
+        if !tables.is_empty() {
+            let mut file = fs::File::create(tables_file)?;
+            writeln!(file, "{}", LALRPOP_VERSION_HEADER)?;
+            file.write_all(&tables)?;
+            let name = tables_file.file_name().unwrap().to_string_lossy();
+            rust!(rust, "include!({:?});", name);
+        }
+    }
+
     action::emit_action_code(grammar, &mut rust)?;
 
     emit_to_triple_trait(grammar, &mut rust)?;
 
+    emit_code(grammar, &mut rust)?;
+
     Ok(rust.into_inner())
 }
 
+/// The `code { .. }` blocks of the grammar, at the end of the module,
+/// where they can use everything generated before them.
+fn emit_code<W: Write>(grammar: &r::Grammar, rust: &mut RustWrite<W>) -> io::Result<()> {
+    for code in &grammar.code {
+        if Tls::session().emit_source_map {
+            rust!(rust, "{}", source_map::marker(code.span));
+        }
+        rust!(rust, "{}", code.code);
+    }
+    Ok(())
+}
+
+/// An error in a grammar given to `build_tables`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrammarError {
+    /// The byte offsets of the part of the grammar the error is about.
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for GrammarError {}
+
+/// Builds the LR(1) tables of each public nonterminal of the grammar
+/// `text`, as for a generated parser, so that tools can parse against
+/// them at runtime with `lalrpop_util::interpret`. The action code of
+/// the grammar is never compiled, so errors in it go unnoticed.
+///
+/// Returns the first error in the grammar; for a conflict, the message
+/// is the explanation `lalrpop` would print.
+pub fn build_tables(text: &str) -> Result<BTreeMap<String, ParseTables>, GrammarError> {
+    let session = Rc::new(Session::new());
+    let file_text = Rc::new(FileText::new(PathBuf::from("grammar"), text.to_string()));
+    let _tls = Tls::install(session.clone(), file_text);
+
+    let grammar = parse_and_normalize(&session, text).map_err(|(span, message)| GrammarError {
+        span: span.0..span.1,
+        message,
+    })?;
+    if grammar.start_nonterminals.is_empty() {
+        return Err(GrammarError {
+            span: 0..0,
+            message: "no public symbols declared in grammar".to_string(),
+        });
+    }
+
+    // guards are action code too, so they cannot be checked
+    if let Some(production) = grammar
+        .nonterminals
+        .values()
+        .flat_map(|nt| &nt.productions)
+        .find(|production| production.guard.is_some())
+    {
+        return Err(GrammarError {
+            span: production.span.0..production.span.1,
+            message: "`when` guards are not supported at runtime".to_string(),
+        });
+    }
+
+    let mut tables = BTreeMap::new();
+    for (user_nt, start_nt) in &grammar.start_nonterminals {
+        let _lr1_tls = lr1::Lr1Tls::install(grammar.terminals.clone());
+        let states = lr1::build_states(&grammar, start_nt.clone())
+            .map_err(|error| conflict_error(lr1::report_error(&grammar, &error)))?;
+        tables.insert(
+            user_nt.to_string(),
+            lr1::parse_tables(&grammar, start_nt, &states),
+        );
+    }
+    Ok(tables)
+}
+
+/// Compares the LR(1) automata of each public nonterminal of two
+/// versions of a grammar, `old` and `new`, and describes the states
+/// that were added, removed or changed, the conflicts that appeared or
+/// went away, and the productions that are now reduced in other states
+/// or on other lookahead. The description is empty if the automata are
+/// the same.
+///
+/// Returns the first error in either grammar; conflicts are part of
+/// the comparison instead.
+pub fn diff_grammars(old: &str, new: &str) -> Result<String, GrammarError> {
+    let old = automata(old)?;
+    let new = automata(new)?;
+    let nonterminals: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut sections = vec![];
+    for nonterminal in nonterminals {
+        let mut out = vec![];
+        if lr1::write_diff(
+            &mut out,
+            nonterminal,
+            old.get(nonterminal),
+            new.get(nonterminal),
+        )
+        .unwrap()
+        {
+            sections.push(String::from_utf8(out).unwrap());
+        }
+    }
+    Ok(sections.join("\n"))
+}
+
+/// The automaton of each public nonterminal of the grammar `text`, for
+/// `diff_grammars`.
+fn automata(text: &str) -> Result<BTreeMap<String, lr1::Automaton>, GrammarError> {
+    let session = Rc::new(Session::new());
+    let file_text = Rc::new(FileText::new(PathBuf::from("grammar"), text.to_string()));
+    let _tls = Tls::install(session.clone(), file_text);
+
+    let grammar = parse_and_normalize(&session, text).map_err(|(span, message)| GrammarError {
+        span: span.0..span.1,
+        message,
+    })?;
+    let mut automata = BTreeMap::new();
+    for (user_nt, start_nt) in &grammar.start_nonterminals {
+        let _lr1_tls = lr1::Lr1Tls::install(grammar.terminals.clone());
+        let lr1result = lr1::build_states(&grammar, start_nt.clone());
+        automata.insert(
+            user_nt.to_string(),
+            lr1::Automaton::new(&grammar, &lr1result),
+        );
+    }
+    Ok(automata)
+}
+
+/// Sentences that the grammar `text` can parse in more than one way,
+/// found in the conflicts of the parser of each public nonterminal,
+/// as `lr1::write_ambiguities` describes them. The description is
+/// empty if there are no conflicts.
+///
+/// Grammars with `#[backtrack]` or guards keep their conflicts, so
+/// this is how to see what they are; for other grammars, they are the
+/// conflicts that generating the parser reports.
+pub fn ambiguous_sentences(text: &str) -> Result<String, GrammarError> {
+    let session = Rc::new(Session::new());
+    let file_text = Rc::new(FileText::new(PathBuf::from("grammar"), text.to_string()));
+    let _tls = Tls::install(session.clone(), file_text);
+
+    let grammar = parse_and_normalize(&session, text).map_err(|(span, message)| GrammarError {
+        span: span.0..span.1,
+        message,
+    })?;
+    let mut out = String::new();
+    for (user_nt, start_nt) in &grammar.start_nonterminals {
+        let _lr1_tls = lr1::Lr1Tls::install(grammar.terminals.clone());
+        if let Err(error) = lr1::build_states(&grammar, start_nt.clone()) {
+            lr1::write_ambiguities(&mut out, &grammar, user_nt, &error);
+        }
+    }
+    Ok(out)
+}
+
+/// The railroad diagram of each nonterminal of the grammar `text`, by
+/// name, as SVG; see `railroad`. The nonterminals are those of the
+/// normalized grammar, so those generated for macros and `+` are
+/// included, but not the start nonterminals that LALRPOP adds, nor the
+/// nonterminals that are inlined, like those for `?` and `*`.
+pub fn railroad_diagrams(text: &str) -> Result<BTreeMap<String, String>, GrammarError> {
+    let session = Rc::new(Session::new());
+    let file_text = Rc::new(FileText::new(PathBuf::from("grammar"), text.to_string()));
+    let _tls = Tls::install(session.clone(), file_text);
+
+    let grammar = parse_and_normalize(&session, text).map_err(|(span, message)| GrammarError {
+        span: span.0..span.1,
+        message,
+    })?;
+    let starts: Vec<_> = grammar.start_nonterminals.values().collect();
+    Ok(grammar
+        .nonterminals
+        .iter()
+        .filter(|(name, _)| !starts.contains(name))
+        .filter(|(_, data)| !data.annotations.iter().any(|a| a.id == *INLINE))
+        .map(|(name, data)| (name.to_string(), railroad::diagram(data)))
+        .collect())
+}
+
+/// The grammar `text` as JSON, as `Configuration::emit_grammar_json`
+/// writes it.
+pub fn grammar_json(text: &str) -> Result<String, GrammarError> {
+    let session = Rc::new(Session::new());
+    let file_text = Rc::new(FileText::new(PathBuf::from("grammar"), text.to_string()));
+    let _tls = Tls::install(session.clone(), file_text);
+
+    let tree = parser::parse_grammar(text).map_err(|error| {
+        let (span, message) = describe_parse_error(text, error);
+        GrammarError {
+            span: span.0..span.1,
+            message,
+        }
+    })?;
+    let grammar = normalize::normalize(&session, tree.clone()).map_err(|error| GrammarError {
+        span: error.span.0..error.span.1,
+        message: error.message,
+    })?;
+    Ok(json::grammar_json(
+        &grammar,
+        &normalize::precedence_levels(&tree),
+    ))
+}
+
+/// Generates the parser for a grammar that was built in memory, rather
+/// than parsed from a file; its spans are all empty.
+pub(crate) fn emit_built_grammar(grammar: pt::Grammar) -> Result<String, GrammarError> {
+    let session = Rc::new(Session::new());
+    let file_text = Rc::new(FileText::new(PathBuf::from("grammar"), String::new()));
+    let _tls = Tls::install(session.clone(), file_text);
+
+    let grammar = normalize::normalize(&session, grammar).map_err(|error| GrammarError {
+        span: 0..0,
+        message: error.message,
+    })?;
+    emit_to_string(&session, &grammar)
+}
+
+/// True if generating the parser for the grammar `text` panics or, if
+/// `message` is given, fails with an error whose message contains it.
+/// Meant as the test for `minimize_grammar`, when narrowing down a
+/// problem in LALRPOP itself; the panic is still reported by the panic
+/// hook.
+pub fn grammar_fails_with(text: &str, message: Option<&str>) -> bool {
+    let result = panic::catch_unwind(|| {
+        let session = Rc::new(Session::new());
+        let file_text = Rc::new(FileText::new(PathBuf::from("grammar"), text.to_string()));
+        let _tls = Tls::install(session.clone(), file_text);
+
+        let grammar =
+            parse_and_normalize(&session, text).map_err(|(span, message)| GrammarError {
+                span: span.0..span.1,
+                message,
+            })?;
+        emit_to_string(&session, &grammar)
+    });
+    match (result, message) {
+        (Err(_), _) => true,
+        (Ok(Err(error)), Some(message)) => error.message.contains(message),
+        (Ok(_), _) => false,
+    }
+}
+
+/// Generates the parser for `grammar`, without a report, returning the
+/// first error.
+fn emit_to_string(session: &Session, grammar: &r::Grammar) -> Result<String, GrammarError> {
+    match emit_parser(session, grammar, None, None) {
+        Ok(buffer) => Ok(String::from_utf8(buffer).unwrap()),
+        Err(EmitError::Io(error)) => Err(GrammarError {
+            span: 0..0,
+            message: error.to_string(),
+        }),
+        Err(EmitError::NoStartSymbols) => Err(GrammarError {
+            span: 0..0,
+            message: "no public symbols declared in grammar".to_string(),
+        }),
+        Err(EmitError::Conflicts(messages)) => Err(conflict_error(messages)),
+        Err(EmitError::Messages(mut errors)) => {
+            let (span, message) = errors.swap_remove(0);
+            Err(GrammarError {
+                span: span.0..span.1,
+                message,
+            })
+        }
+    }
+}
+
+/// The error for the conflicts explained by `messages`: the first of
+/// them, as `lalrpop` would print it.
+fn conflict_error(mut messages: Vec<Message>) -> GrammarError {
+    let message = messages.swap_remove(0);
+    let span = message.span();
+    GrammarError {
+        span: span.0..span.1,
+        message: message_text(&message),
+    }
+}
+
+/// The text of `message`, as it is printed but without colors.
+fn message_text(message: &Message) -> String {
+    message.emit_to_canvas(80).to_strings().iter().join("\n")
+}
+
 fn write_where_clause<W: Write>(
     where_clauses: &[r::WhereClause],
     to_triple_where_clauses: &Sep<&Vec<r::WhereClause>>,
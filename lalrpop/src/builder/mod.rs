@@ -0,0 +1,353 @@
+//! Builds grammars in memory, for tools that generate parsers without
+//! writing a `.lalrpop` file first. A `GrammarBuilder` collects the same
+//! declarations as a grammar file, with the Rust fragments (types,
+//! patterns and action code) given as strings; `generate` runs it
+//! through the usual normalization, LR(1) construction and code
+//! generation.
+
+use crate::build::{self, GrammarError};
+use crate::grammar::parse_tree as pt;
+use crate::grammar::parse_tree::*;
+use crate::parser;
+use string_cache::DefaultAtom as Atom;
+
+#[cfg(test)]
+mod test;
+
+/// A grammar under construction.
+#[derive(Clone, Debug, Default)]
+pub struct GrammarBuilder {
+    uses: Vec<String>,
+    extern_token: Option<ExternTokenDecl>,
+    nonterminals: Vec<NonterminalDecl>,
+}
+
+/// A symbol of a production: a terminal or nonterminal, possibly with
+/// a name to refer to its value by in the action code.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    kind: SymbolDecl,
+    name: Option<String>,
+    selected: bool,
+}
+
+#[derive(Clone, Debug)]
+enum SymbolDecl {
+    Terminal(String),
+    Nonterminal(String),
+}
+
+#[derive(Clone, Debug)]
+struct ExternTokenDecl {
+    token_type: String,
+    associated_types: Vec<(String, String)>,
+    conversions: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug)]
+struct NonterminalDecl {
+    name: String,
+    type_decl: String,
+    public: bool,
+    productions: Vec<(Vec<Symbol>, Option<String>)>,
+}
+
+impl GrammarBuilder {
+    pub fn new() -> Self {
+        GrammarBuilder::default()
+    }
+
+    /// Adds a `use` to the generated module, given without `use` and
+    /// `;`, as in `std::str::FromStr`.
+    pub fn use_decl(&mut self, path: &str) -> &mut Self {
+        self.uses.push(path.to_string());
+        self
+    }
+
+    /// Makes the parser take tokens of type `token_type` from an
+    /// external lexer, as with an `extern { enum Tok { .. } }` block.
+    /// Without one, LALRPOP generates a lexer for the quoted terminals.
+    pub fn extern_token(&mut self, token_type: &str) -> &mut Self {
+        self.extern_decl().token_type = token_type.to_string();
+        self
+    }
+
+    /// Declares an associated type of the external lexer, like
+    /// `Location` or `Error`.
+    pub fn associated_type(&mut self, name: &str, type_ref: &str) -> &mut Self {
+        self.extern_decl()
+            .associated_types
+            .push((name.to_string(), type_ref.to_string()));
+        self
+    }
+
+    /// Declares the terminal `terminal`, written as in a grammar (like
+    /// `"+"` or `Num`), to match tokens of the external lexer with the
+    /// pattern `pattern`, like `Tok::Num(<i32>)`.
+    pub fn token(&mut self, terminal: &str, pattern: &str) -> &mut Self {
+        self.extern_decl()
+            .conversions
+            .push((terminal.to_string(), pattern.to_string()));
+        self
+    }
+
+    /// Declares the nonterminal `name`, whose value has the type
+    /// `type_decl`. Public nonterminals get a parser of their own.
+    pub fn nonterminal(&mut self, name: &str, type_decl: &str, public: bool) -> &mut Self {
+        self.nonterminals.push(NonterminalDecl {
+            name: name.to_string(),
+            type_decl: type_decl.to_string(),
+            public,
+            productions: vec![],
+        });
+        self
+    }
+
+    /// Adds a production to the nonterminal `nonterminal`, which must
+    /// have been declared. `action` is the code after `=>`; without it,
+    /// the production has the default action.
+    ///
+    /// Panics if `nonterminal` was not declared.
+    pub fn production(
+        &mut self,
+        nonterminal: &str,
+        symbols: Vec<Symbol>,
+        action: Option<&str>,
+    ) -> &mut Self {
+        let decl = self
+            .nonterminals
+            .iter_mut()
+            .find(|decl| decl.name == nonterminal)
+            .unwrap_or_else(|| panic!("undeclared nonterminal `{}`", nonterminal));
+        decl.productions
+            .push((symbols, action.map(|action| action.to_string())));
+        self
+    }
+
+    /// Generates the parser, returning the code of its module, like the
+    /// `.rs` file `lalrpop` generates for a `.lalrpop` file. The errors
+    /// have no span, since there is no grammar text to point to.
+    pub fn generate(&self) -> Result<String, GrammarError> {
+        build::emit_built_grammar(self.to_parse_tree()?)
+    }
+
+    fn extern_decl(&mut self) -> &mut ExternTokenDecl {
+        self.extern_token.get_or_insert_with(|| ExternTokenDecl {
+            token_type: String::new(),
+            associated_types: vec![],
+            conversions: vec![],
+        })
+    }
+
+    fn to_parse_tree(&self) -> Result<Grammar, GrammarError> {
+        let span = Span::default();
+        let mut items: Vec<GrammarItem> = self.uses.iter().cloned().map(GrammarItem::Use).collect();
+
+        if let Some(ref decl) = self.extern_token {
+            let mut associated_types = vec![];
+            for (name, type_ref) in &decl.associated_types {
+                associated_types.push(AssociatedType {
+                    type_span: span,
+                    type_name: Atom::from(&name[..]),
+                    type_ref: parse_fragment(type_ref, parser::parse_type_ref(type_ref))?,
+                });
+            }
+            let enum_token = if decl.token_type.is_empty() {
+                if let Some((terminal, _)) = decl.conversions.first() {
+                    return Err(GrammarError {
+                        span: 0..0,
+                        message: format!(
+                            "the token `{}` is declared, but not the `extern_token` type",
+                            terminal
+                        ),
+                    });
+                }
+                None
+            } else {
+                let mut conversions = vec![];
+                for (terminal, pattern) in &decl.conversions {
+                    conversions.push(Conversion {
+                        span,
+                        from: parse_terminal(terminal)?,
+                        to: parse_fragment(pattern, parser::parse_pattern(pattern, 0))?,
+                        annotations: vec![],
+                    });
+                }
+                Some(EnumToken {
+                    type_name: parse_fragment(
+                        &decl.token_type,
+                        parser::parse_type_ref(&decl.token_type),
+                    )?,
+                    type_span: span,
+                    conversions,
+                })
+            };
+            items.push(GrammarItem::ExternToken(ExternToken {
+                span,
+                associated_types,
+                enum_token,
+            }));
+        }
+
+        for decl in &self.nonterminals {
+            let mut alternatives = vec![];
+            for (symbols, action) in &decl.productions {
+                let symbols = symbols
+                    .iter()
+                    .map(Symbol::to_parse_tree)
+                    .collect::<Result<_, _>>()?;
+                alternatives.push(Alternative {
+                    span,
+                    expr: ExprSymbol { symbols },
+                    condition: None,
+                    guard: None,
+                    action: action.clone().map(ActionKind::User),
+                    annotations: vec![],
+                });
+            }
+            items.push(GrammarItem::Nonterminal(NonterminalData {
+                visibility: if decl.public {
+                    Visibility::Pub(None)
+                } else {
+                    Visibility::Priv
+                },
+                name: NonterminalString(Atom::from(&decl.name[..])),
+                annotations: vec![],
+                span,
+                args: vec![],
+                parameters: vec![],
+                type_decl: Some(parse_fragment(
+                    &decl.type_decl,
+                    parser::parse_type_ref(&decl.type_decl),
+                )?),
+                alternatives,
+                expansion: None,
+            }));
+        }
+
+        Ok(Grammar {
+            prefix: self.prefix(),
+            span,
+            type_parameters: vec![],
+            parameters: vec![],
+            where_clauses: vec![],
+            items,
+            annotations: vec![],
+            module_attributes: vec![],
+        })
+    }
+
+    /// A prefix for the generated names that does not appear in any of
+    /// the names and code the user gave, as `parser::parse_grammar` picks
+    /// one.
+    fn prefix(&self) -> String {
+        let mut fragments: Vec<&str> = self.uses.iter().map(|u| &u[..]).collect();
+        if let Some(ref decl) = self.extern_token {
+            fragments.push(&decl.token_type);
+            for (name, type_ref) in &decl.associated_types {
+                fragments.push(name);
+                fragments.push(type_ref);
+            }
+            for (_, pattern) in &decl.conversions {
+                fragments.push(pattern);
+            }
+        }
+        for decl in &self.nonterminals {
+            fragments.push(&decl.name);
+            fragments.push(&decl.type_decl);
+            for (symbols, action) in &decl.productions {
+                fragments.extend(
+                    symbols
+                        .iter()
+                        .filter_map(|s| s.name.as_ref().map(|n| &n[..])),
+                );
+                fragments.extend(action.as_ref().map(|action| &action[..]));
+            }
+        }
+
+        let mut prefix = "__".to_string();
+        while fragments.iter().any(|fragment| fragment.contains(&prefix)) {
+            prefix.push('_');
+        }
+        prefix
+    }
+}
+
+impl Symbol {
+    /// A terminal, written as in a grammar: `"+"`, `r"[0-9]+"` or, with
+    /// an external lexer, a name like `Num`.
+    pub fn terminal(terminal: &str) -> Self {
+        Symbol {
+            kind: SymbolDecl::Terminal(terminal.to_string()),
+            name: None,
+            selected: false,
+        }
+    }
+
+    pub fn nonterminal(name: &str) -> Self {
+        Symbol {
+            kind: SymbolDecl::Nonterminal(name.to_string()),
+            name: None,
+            selected: false,
+        }
+    }
+
+    /// Binds the value of the symbol to `name` in the action code, as
+    /// in `<name:X>`.
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Selects the symbol for the default action, as in `<X>`.
+    pub fn selected(mut self) -> Self {
+        self.selected = true;
+        self
+    }
+
+    fn to_parse_tree(&self) -> Result<pt::Symbol, GrammarError> {
+        let span = Span::default();
+        let kind = match self.kind {
+            SymbolDecl::Terminal(ref terminal) => match parse_terminal(terminal)? {
+                TerminalString::Bare(id) => SymbolKind::AmbiguousId(id),
+                terminal => SymbolKind::Terminal(terminal),
+            },
+            SymbolDecl::Nonterminal(ref name) => SymbolKind::AmbiguousId(Atom::from(&name[..])),
+        };
+        let symbol = pt::Symbol::new(span, kind);
+        let kind = match (&self.name, self.selected) {
+            (Some(name), _) => SymbolKind::Name(
+                Name {
+                    mutable: false,
+                    name: Atom::from(&name[..]),
+                },
+                Box::new(symbol),
+            ),
+            (None, true) => SymbolKind::Choose(Box::new(symbol)),
+            (None, false) => return Ok(symbol),
+        };
+        Ok(pt::Symbol::new(span, kind))
+    }
+}
+
+fn parse_terminal(terminal: &str) -> Result<TerminalString, GrammarError> {
+    match parse_fragment(terminal, parser::parse_match_mapping(terminal, 0))? {
+        MatchMapping::Terminal(terminal) => Ok(terminal),
+        MatchMapping::Skip => Err(GrammarError {
+            span: 0..0,
+            message: format!("`{}` is not a terminal", terminal),
+        }),
+    }
+}
+
+/// Reports a syntax error in the Rust or grammar fragment `text`.
+fn parse_fragment<T>(text: &str, result: Result<T, parser::ParseError>) -> Result<T, GrammarError> {
+    result.map_err(|error| GrammarError {
+        span: 0..0,
+        message: format!(
+            "in `{}`: {}",
+            text,
+            build::describe_parse_error(text, error).1
+        ),
+    })
+}
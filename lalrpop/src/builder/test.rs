@@ -0,0 +1,116 @@
+use super::{GrammarBuilder, Symbol};
+use crate::build::emit_built_grammar;
+use crate::parser;
+
+/// The parser generated from the grammar `text`, for comparison.
+fn generate_from_text(text: &str) -> String {
+    emit_built_grammar(parser::parse_grammar(text).unwrap()).unwrap()
+}
+
+#[test]
+fn same_as_grammar_text() {
+    let mut builder = GrammarBuilder::new();
+    builder
+        .use_decl("std::str::FromStr")
+        .nonterminal("Expr", "i32", true)
+        .nonterminal("Num", "i32", false)
+        .production(
+            "Expr",
+            vec![
+                Symbol::nonterminal("Expr").named("l"),
+                Symbol::terminal(r#""+""#),
+                Symbol::nonterminal("Num").named("r"),
+            ],
+            Some("l + r"),
+        )
+        .production("Expr", vec![Symbol::nonterminal("Num")], None)
+        .production(
+            "Num",
+            vec![Symbol::terminal(r#"r"[0-9]+""#)],
+            Some("i32::from_str(<>).unwrap()"),
+        );
+
+    assert_eq!(
+        builder.generate().unwrap(),
+        generate_from_text(
+            r#"
+grammar;
+
+use std::str::FromStr;
+
+pub Expr: i32 = {
+    <l:Expr> "+" <r:Num> => l + r,
+    Num,
+};
+
+Num: i32 = r"[0-9]+" => i32::from_str(<>).unwrap();
+"#
+        )
+    );
+}
+
+#[test]
+fn extern_tokens() {
+    let mut builder = GrammarBuilder::new();
+    builder
+        .extern_token("Tok")
+        .associated_type("Location", "usize")
+        .token(r#""(""#, "Tok::LParen")
+        .token(r#"")""#, "Tok::RParen")
+        .token("Num", "Tok::Num(<u32>)")
+        .nonterminal("Term", "u32", true)
+        .production(
+            "Term",
+            vec![
+                Symbol::terminal(r#""(""#),
+                Symbol::nonterminal("Term").selected(),
+                Symbol::terminal(r#"")""#),
+            ],
+            None,
+        )
+        .production("Term", vec![Symbol::terminal("Num")], None);
+
+    assert_eq!(
+        builder.generate().unwrap(),
+        generate_from_text(
+            r#"
+grammar;
+
+extern {
+    type Location = usize;
+    enum Tok {
+        "(" => Tok::LParen,
+        ")" => Tok::RParen,
+        Num => Tok::Num(<u32>),
+    }
+}
+
+pub Term: u32 = {
+    "(" <Term> ")",
+    Num,
+};
+"#
+        )
+    );
+}
+
+#[test]
+fn errors() {
+    let mut builder = GrammarBuilder::new();
+    builder.nonterminal("Expr", "Vec<", true).production(
+        "Expr",
+        vec![Symbol::terminal(r#""x""#)],
+        None,
+    );
+    let error = builder.generate().unwrap_err();
+    assert_eq!(error.message, "in `Vec<`: unexpected end of file");
+
+    let mut builder = GrammarBuilder::new();
+    builder.nonterminal("Expr", "()", true).production(
+        "Expr",
+        vec![Symbol::nonterminal("Term")],
+        None,
+    );
+    let error = builder.generate().unwrap_err();
+    assert_eq!(error.message, "no definition found for `Term`");
+}
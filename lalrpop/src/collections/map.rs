@@ -1,7 +1,5 @@
 use std::collections::BTreeMap;
 
-pub use std::collections::btree_map::Entry;
-
 /// In general, we avoid coding directly against any particular map,
 /// but rather build against `util::Map` (and `util::map` to construct
 /// an instance). This should be a deterministic map, such that two
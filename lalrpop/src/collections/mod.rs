@@ -2,6 +2,6 @@ mod map;
 mod multimap;
 mod set;
 
-pub use self::map::{map, Entry, Map};
+pub use self::map::{map, Map};
 pub use self::multimap::{Collection, Multimap};
 pub use self::set::{set, Set};
@@ -0,0 +1,28 @@
+//! Errors and warnings in a structured form, for build tools and editors
+//! that embed LALRPOP and want to present them themselves. See
+//! `Configuration::set_diagnostic_handler`.
+
+use std::ops::Range;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// The grammar file the diagnostic is about.
+    pub file: PathBuf,
+
+    /// The byte range of the grammar text it points to; empty for
+    /// diagnostics about the grammar as a whole.
+    pub span: Range<usize>,
+
+    /// The message, without ANSI colors. For ambiguities this spans
+    /// several lines, with the example and hints that LALRPOP prints.
+    pub message: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
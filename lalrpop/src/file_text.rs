@@ -2,12 +2,16 @@ use crate::grammar::parse_tree as pt;
 use std::fmt::{Display, Error, Formatter};
 use std::fs::File;
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct FileText {
     path: PathBuf,
     input_str: String,
     newlines: Vec<usize>,
+
+    // the files that the grammar includes, each with the offset that
+    // the spans in its text start at
+    includes: Vec<(usize, FileText)>,
 }
 
 impl FileText {
@@ -33,7 +37,37 @@ impl FileText {
             path,
             input_str,
             newlines: newline_indices,
+            includes: vec![],
+        }
+    }
+
+    /// Adds the text of `path`, a file that the grammar includes,
+    /// returning the offset that the spans in it start at: past the
+    /// end of the text of the grammar and of the files before it, so
+    /// that every span is in exactly one file.
+    pub fn include(&mut self, path: PathBuf, input_str: String) -> usize {
+        let offset = match self.includes.last() {
+            Some((offset, file_text)) => offset + file_text.input_str.len() + 1,
+            None => self.input_str.len() + 1,
+        };
+        self.includes.push((offset, FileText::new(path, input_str)));
+        offset
+    }
+
+    /// The paths of the files that the grammar includes.
+    pub fn included_paths(&self) -> impl Iterator<Item = &Path> {
+        self.includes.iter().map(|(_, file_text)| file_text.path())
+    }
+
+    /// The file that `span` is in, with `span` made relative to its
+    /// text.
+    pub fn locate(&self, span: pt::Span) -> (&FileText, pt::Span) {
+        for (offset, file_text) in self.includes.iter().rev() {
+            if span.0 >= *offset {
+                return (file_text, pt::Span(span.0 - offset, span.1 - offset));
+            }
         }
+        (self, span)
     }
 
     #[cfg(test)]
@@ -41,16 +75,21 @@ impl FileText {
         Self::new(PathBuf::from("test.lalrpop"), String::from(""))
     }
 
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     pub fn text(&self) -> &String {
         &self.input_str
     }
 
     pub fn span_str(&self, span: pt::Span) -> String {
-        let (start_line, start_col) = self.line_col(span.0);
-        let (end_line, end_col) = self.line_col(span.1);
+        let (file_text, span) = self.locate(span);
+        let (start_line, start_col) = file_text.line_col(span.0);
+        let (end_line, end_col) = file_text.line_col(span.1);
         format!(
             "{}:{}:{}: {}:{}",
-            self.path.display(),
+            file_text.path.display(),
             start_line + 1,
             start_col + 1,
             end_line + 1,
@@ -58,6 +97,14 @@ impl FileText {
         )
     }
 
+    /// The file, line and column where `span` starts, as
+    /// `path:line:column`.
+    pub fn location(&self, span: pt::Span) -> String {
+        let (file_text, span) = self.locate(span);
+        let (line, col) = file_text.line_col(span.0);
+        format!("{}:{}:{}", file_text.path.display(), line + 1, col + 1)
+    }
+
     fn line_col(&self, pos: usize) -> (usize, usize) {
         let num_lines = self.newlines.len();
         let line = (0..num_lines)
@@ -87,15 +134,16 @@ impl FileText {
     }
 
     pub fn highlight(&self, span: pt::Span, out: &mut dyn Write) -> io::Result<()> {
-        let (start_line, start_col) = self.line_col(span.0);
-        let (end_line, end_col) = self.line_col(span.1);
+        let (file_text, span) = self.locate(span);
+        let (start_line, start_col) = file_text.line_col(span.0);
+        let (end_line, end_col) = file_text.line_col(span.1);
 
         // (*) use `saturating_sub` since the start line could be the newline
         // itself, in which case we'll call it column zero
 
         // span is within one line:
         if start_line == end_line {
-            let text = self.line_text(start_line);
+            let text = file_text.line_text(start_line);
             writeln!(out, "  {}", text)?;
 
             if end_col - start_col <= 1 {
@@ -111,7 +159,7 @@ impl FileText {
             }
         } else {
             // span is across many lines, find the maximal width of any of those
-            let line_strs: Vec<_> = (start_line..=end_line).map(|i| self.line_text(i)).collect();
+            let line_strs: Vec<_> = (start_line..=end_line).map(|i| file_text.line_text(i)).collect();
             let max_len = line_strs.iter().map(|l| l.len()).max().unwrap();
             writeln!(
                 out,
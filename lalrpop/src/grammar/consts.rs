@@ -19,11 +19,106 @@ pub const CFG: &str = "cfg";
 /// Annotation to request LALR.
 pub const LALR: &str = "LALR";
 
-/// Annotation to request recursive-ascent-style code generation.
+/// Annotation to request table-driven code generation, for the whole
+/// grammar or for a public nonterminal.
 pub const TABLE_DRIVEN: &str = "table_driven";
 
-/// Annotation to request recursive-ascent-style code generation.
+/// Annotation to request recursive-ascent-style code generation, for the
+/// whole grammar or for a public nonterminal.
 pub const RECURSIVE_ASCENT: &str = "recursive_ascent";
 
 /// Annotation to request test-all-style code generation.
 pub const TEST_ALL: &str = "test_all";
+
+/// Annotation to request that the generated lexer resolve overlapping
+/// terminals by declaration order alone.
+pub const LONGEST_MATCH: &str = "longest_match";
+
+/// Annotation to request that the generated parser repair syntax
+/// errors by inserting or deleting tokens.
+pub const REPAIR: &str = "repair";
+
+/// Annotation to request a `parse_prefix` entry point for a public
+/// nonterminal.
+pub const PREFIX: &str = "prefix";
+
+/// Annotation to request a parser for a public nonterminal that is
+/// fed one token at a time.
+pub const RESUMABLE: &str = "resumable";
+
+/// Annotation to request that the resumable parser of a public
+/// nonterminal implement `Clone`, and take checkpoints.
+pub const CLONEABLE: &str = "cloneable";
+
+/// Annotation to request a `completions` entry point for a public
+/// nonterminal.
+pub const COMPLETIONS: &str = "completions";
+
+/// Annotation to request a `parse_island` entry point for a public
+/// nonterminal, for parsing a region embedded in other input.
+pub const ISLAND: &str = "island";
+
+/// Annotation to request a `parse_events` entry point for a public
+/// nonterminal, which returns the events of the parse instead of
+/// running the actions.
+pub const EVENTS: &str = "events";
+
+/// Annotation to request a `recognize` entry point for a public
+/// nonterminal, which checks the input without running the actions.
+pub const RECOGNIZE: &str = "recognize";
+
+/// Annotation to request a `parse_cancellable` entry point for a
+/// public nonterminal, whose parse can be stopped part way through.
+pub const CANCELLABLE: &str = "cancellable";
+
+/// Annotation to request a `parse` fn that takes the public
+/// nonterminal to parse as an argument.
+pub const RUNTIME_START: &str = "runtime_start";
+
+/// Annotation to request that conflicts be resolved at runtime, by
+/// trying each action over the next few tokens.
+pub const BACKTRACK: &str = "backtrack";
+
+/// Annotation to declare that the location type of an `extern` lexer
+/// is `Copy`, so that the generated code copies locations rather than
+/// cloning them.
+pub const COPY_LOCATION: &str = "copy_location";
+
+/// Annotation to give a terminal a fixed id, as in
+/// `#[terminal_id(PLUS = "1")] "+"`, in the `match` block or the
+/// `extern` token enum.
+pub const TERMINAL_ID: &str = "terminal_id";
+
+/// Annotation to give error repair the cost of inserting or deleting a
+/// terminal, as in `#[cost(insert = "5")] "fn"`, in the `match` block
+/// or the `extern` token enum.
+pub const COST: &str = "cost";
+
+/// Annotation to give a nonterminal a name, as in
+/// `#[expected(name = "an expression")]`, that errors list in place of
+/// the terminals that can start it.
+pub const EXPECTED: &str = "expected";
+
+/// Annotation to request a `parse_owned` entry point for a public
+/// nonterminal, which takes the input as a `String`, so that the
+/// result does not borrow from the caller.
+pub const OWNED: &str = "owned";
+
+/// Annotation to intern the text of a terminal of the match block, as
+/// in `#[intern(with = "names")] r"[a-z]+" => IDENT`, so that actions
+/// get a `Symbol` from the grammar parameter `names` instead.
+pub const INTERN: &str = "intern";
+
+/// The argument of `#[repair]` that selects the repair strategy.
+pub const REPAIR_STRATEGY_ARG: &str = "strategy";
+
+/// The arguments of `#[cost]` that give the cost of inserting and of
+/// deleting the terminal.
+pub const COST_INSERT_ARG: &str = "insert";
+pub const COST_DELETE_ARG: &str = "delete";
+
+/// The argument of `#[expected]` that gives the name.
+pub const EXPECTED_NAME_ARG: &str = "name";
+
+/// The argument of `#[intern]` that names the interner.
+pub const INTERN_WITH_ARG: &str = "with";
@@ -65,6 +65,7 @@ impl FreeVariables for repr::TypeRepr {
                 free_type(type_parameters, type_parameter)
             }
             repr::TypeRepr::Lifetime(l) => free_lifetime(type_parameters, l),
+            repr::TypeRepr::Dyn(bounds) => bounds.free_variables(type_parameters),
             repr::TypeRepr::Ref {
                 lifetime, referent, ..
             } => lifetime
@@ -77,6 +78,7 @@ impl FreeVariables for repr::TypeRepr {
                 path,
                 parameters,
                 ret,
+                bound,
             } => path
                 .free_variables(type_parameters)
                 .into_iter()
@@ -89,6 +91,7 @@ impl FreeVariables for repr::TypeRepr {
                     ret.iter()
                         .flat_map(|ret| ret.free_variables(type_parameters)),
                 )
+                .chain(bound.iter().flat_map(|l| free_lifetime(type_parameters, l)))
                 .filter(|tp| !forall.contains(tp))
                 .collect(),
         }
@@ -160,7 +163,7 @@ impl<T: FreeVariables> FreeVariables for parse_tree::TypeBoundParameter<T> {
         match self {
             parse_tree::TypeBoundParameter::Lifetime(l) => free_lifetime(type_parameters, l),
             parse_tree::TypeBoundParameter::TypeParameter(t) => t.free_variables(type_parameters),
-            parse_tree::TypeBoundParameter::Associated(..) => vec![],
+            parse_tree::TypeBoundParameter::Associated(_, t) => t.free_variables(type_parameters),
         }
     }
 }
@@ -1,10 +1,13 @@
 //! The "parse-tree" is what is produced by the parser. We use it do
 //! some pre-expansion and so forth before creating the proper AST.
 
-use crate::grammar::consts::{INPUT_LIFETIME, LALR, RECURSIVE_ASCENT, TABLE_DRIVEN, TEST_ALL};
+use crate::grammar::consts::{
+    BACKTRACK, COPY_LOCATION, INPUT_LIFETIME, LALR, LONGEST_MATCH, RECURSIVE_ASCENT, REPAIR,
+    RUNTIME_START, TABLE_DRIVEN, TEST_ALL,
+};
 use crate::grammar::pattern::Pattern;
 use crate::grammar::repr::{self as r, NominalTypeRepr, TypeRepr};
-use crate::lexer::dfa::DFA;
+use crate::lexer::dfa::{Overlap, DFA};
 use crate::message::builder::InlineBuilder;
 use crate::message::Content;
 use crate::tls::Tls;
@@ -50,6 +53,36 @@ pub enum GrammarItem {
     InternToken(InternToken),
     Nonterminal(NonterminalData),
     Use(String),
+    Include(Include),
+    TypeAlias(TypeAlias),
+    Code(Code),
+}
+
+/// `include "tokens.lalrpop";`, which the items of another file take
+/// the place of once the grammar is parsed; see `build::parse_grammar_file`.
+/// `type Spanned<T> = (usize, T, usize);`, which is emitted into the
+/// generated module, so that nonterminal types can use it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeAlias {
+    pub span: Span,
+    pub name: Atom,
+    pub type_parameters: Vec<TypeParameter>,
+    pub type_ref: TypeRef,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Include {
+    /// the path of the file, relative to the directory of the grammar
+    pub path: Atom,
+    pub span: Span,
+}
+
+/// `code { .. }`, whose contents are emitted as they are at the end of
+/// the generated module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Code {
+    pub code: String,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -87,8 +120,8 @@ pub struct MatchContents {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MatchItem {
     CatchAll(Span),
-    Unmapped(MatchSymbol, Span),
-    Mapped(MatchSymbol, MatchMapping, Span),
+    Unmapped(MatchSymbol, Span, Vec<Annotation>),
+    Mapped(MatchSymbol, MatchMapping, Span, Vec<Annotation>),
 }
 
 impl MatchItem {
@@ -102,8 +135,29 @@ impl MatchItem {
     pub fn span(&self) -> Span {
         match *self {
             MatchItem::CatchAll(span) => span,
-            MatchItem::Unmapped(_, span) => span,
-            MatchItem::Mapped(_, _, span) => span,
+            MatchItem::Unmapped(_, span, _) => span,
+            MatchItem::Mapped(_, _, span, _) => span,
+        }
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        match *self {
+            MatchItem::CatchAll(_) => &[],
+            MatchItem::Unmapped(_, _, ref annotations) => annotations,
+            MatchItem::Mapped(_, _, _, ref annotations) => annotations,
+        }
+    }
+
+    /// The terminal that the grammar uses for the input this item
+    /// matches, if it is not skipped.
+    pub fn terminal(&self) -> Option<TerminalString> {
+        match *self {
+            MatchItem::CatchAll(_) => None,
+            MatchItem::Unmapped(ref sym, _, _) => Some(TerminalString::Literal(sym.clone())),
+            MatchItem::Mapped(_, MatchMapping::Terminal(ref terminal), _, _) => {
+                Some(terminal.clone())
+            }
+            MatchItem::Mapped(_, MatchMapping::Skip, _, _) => None,
         }
     }
 }
@@ -142,6 +196,36 @@ pub struct InternToken {
     /// grammar. Sorted by order of increasing precedence.
     pub match_entries: Vec<MatchEntry>,
     pub dfa: DFA,
+
+    /// True if the grammar requested `#[longest_match]`, in which case
+    /// the precedence of each match entry reflects declaration order
+    /// only.
+    pub longest_match: bool,
+}
+
+impl InternToken {
+    /// Explains an overlap between two match entries that the DFA
+    /// resolved by precedence, e.g. why `"class"` is lexed as a
+    /// keyword rather than as `r"[a-z]+"`.
+    pub fn describe_overlap(&self, overlap: &Overlap) -> String {
+        let winner = &self.match_entries[overlap.winner.index()];
+        let loser = &self.match_entries[overlap.loser.index()];
+        let reason = if self.longest_match {
+            "it is declared first"
+        } else if winner.precedence / 2 != loser.precedence / 2 {
+            "it appears in an earlier `match` group"
+        } else {
+            "literals take precedence over regular expressions"
+        };
+        format!(
+            "the terminals `{}` and `{}` both match {:?}; `{}` is preferred because {}",
+            winner.match_literal,
+            loser.match_literal,
+            overlap.example,
+            winner.match_literal,
+            reason
+        )
+    }
 }
 
 /// In `token_check`, as we prepare to generate a tokenizer, we
@@ -209,6 +293,7 @@ pub struct Conversion {
     pub span: Span,
     pub from: TerminalString,
     pub to: Pattern<TypeRef>,
+    pub annotations: Vec<Annotation>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -243,6 +328,10 @@ pub enum TypeRef {
         types: Vec<TypeRef>,
     },
 
+    // `impl Trait + 'a` ==> only legal as the type of a nonterminal,
+    // whose values are then boxed as `dyn Trait + 'a`
+    Impl(Vec<TypeBound<TypeRef>>),
+
     // 'x ==> only should appear within nominal types, but what do we care
     Lifetime(Lifetime),
 
@@ -257,6 +346,7 @@ pub enum TypeRef {
         path: Path,
         parameters: Vec<TypeRef>,
         ret: Option<Box<TypeRef>>,
+        bound: Option<Lifetime>, // `+ 'a`, only produced during normalization
     },
 }
 
@@ -326,6 +416,38 @@ impl<T> TypeBound<T> {
             },
         }
     }
+
+    pub fn try_map<F, U, E>(&self, mut f: F) -> Result<TypeBound<U>, E>
+    where
+        F: FnMut(&T) -> Result<U, E>,
+    {
+        Ok(match *self {
+            TypeBound::Lifetime(ref l) => TypeBound::Lifetime(l.clone()),
+            TypeBound::Fn {
+                ref forall,
+                ref path,
+                ref parameters,
+                ref ret,
+            } => TypeBound::Fn {
+                forall: forall.clone(),
+                path: path.clone(),
+                parameters: parameters.iter().map(&mut f).collect::<Result<_, _>>()?,
+                ret: ret.as_ref().map(&mut f).transpose()?,
+            },
+            TypeBound::Trait {
+                ref forall,
+                ref path,
+                ref parameters,
+            } => TypeBound::Trait {
+                forall: forall.clone(),
+                path: path.clone(),
+                parameters: parameters
+                    .iter()
+                    .map(|p| p.try_map(&mut f))
+                    .collect::<Result<_, _>>()?,
+            },
+        })
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -351,6 +473,19 @@ impl<T> TypeBoundParameter<T> {
             }
         }
     }
+
+    pub fn try_map<F, U, E>(&self, mut f: F) -> Result<TypeBoundParameter<U>, E>
+    where
+        F: FnMut(&T) -> Result<U, E>,
+    {
+        Ok(match *self {
+            TypeBoundParameter::Lifetime(ref l) => TypeBoundParameter::Lifetime(l.clone()),
+            TypeBoundParameter::TypeParameter(ref t) => TypeBoundParameter::TypeParameter(f(t)?),
+            TypeBoundParameter::Associated(ref id, ref t) => {
+                TypeBoundParameter::Associated(id.clone(), f(t)?)
+            }
+        })
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -389,8 +524,58 @@ pub struct NonterminalData {
     pub annotations: Vec<Annotation>,
     pub span: Span,
     pub args: Vec<NonterminalString>, // macro arguments
+    pub parameters: Vec<Parameter>,   // values supplied by each use, as in `X[a]`
     pub type_decl: Option<TypeRef>,
     pub alternatives: Vec<Alternative>,
+    pub expansion: Option<Expansion>, // if LALRPOP made this up
+}
+
+/// Where a nonterminal that LALRPOP made up comes from: the expansion
+/// of a macro, like `Comma<Expr>`, or of a symbol like `Expr?`,
+/// `Expr*`, `Expr+` or `(Expr ",")`. Its `span` is that of the first
+/// use of the macro or symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expansion {
+    /// the span of the name of the macro, where it is defined, if this
+    /// is the expansion of a macro
+    pub definition: Option<Span>,
+    /// the nonterminal that first uses the macro or symbol, which is
+    /// itself an expansion if the use is within another one
+    pub used_in: NonterminalString,
+}
+
+impl Expansion {
+    /// Notes that trace `nonterminal`, if LALRPOP made it up, back
+    /// through the nonterminals it was expanded for to one that the
+    /// user wrote, in the manner of rustc's macro backtraces. Each note
+    /// comes with the span of the use that it is about; `lookup` gives
+    /// the span and expansion of a nonterminal.
+    pub fn trace<'a, F>(mut nonterminal: &'a NonterminalString, lookup: F) -> Vec<(Span, String)>
+    where
+        F: Fn(&NonterminalString) -> Option<(Span, &'a Option<Expansion>)>,
+    {
+        let mut notes = vec![];
+        while let Some((span, &Some(ref expansion))) = lookup(nonterminal) {
+            let file_text = Tls::file_text();
+            let origin = match expansion.definition {
+                Some(definition) => format!(
+                    "the macro defined at {}, for its use",
+                    file_text.location(definition)
+                ),
+                None => String::from("its use"),
+            };
+            let note = format!(
+                "note: `{}` is expanded from {} at {} in `{}`",
+                nonterminal,
+                origin,
+                file_text.location(span),
+                expansion.used_in
+            );
+            notes.push((span, note));
+            nonterminal = &expansion.used_in;
+        }
+        notes
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -407,7 +592,10 @@ pub struct Alternative {
     pub expr: ExprSymbol,
 
     // if C, only legal in macros
-    pub condition: Option<Condition>,
+    pub condition: Option<ConditionExpr>,
+
+    // when { expr }
+    pub guard: Option<String>,
 
     // => { code }
     pub action: Option<ActionKind>,
@@ -423,6 +611,32 @@ pub enum ActionKind {
     Lookbehind,
 }
 
+/// The condition of an alternative of a macro: tests of the macro's
+/// arguments, combined with `!`, `&&` and `||`. A test of membership,
+/// `X in ["a", "b"]`, is parsed as `X == "a" || X == "b"`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConditionExpr {
+    Test(Condition),
+    Not(Box<ConditionExpr>),
+    And(Box<ConditionExpr>, Box<ConditionExpr>),
+    Or(Box<ConditionExpr>, Box<ConditionExpr>),
+}
+
+impl ConditionExpr {
+    /// The tests that the condition is made of.
+    pub fn tests(&self) -> Vec<&Condition> {
+        match *self {
+            ConditionExpr::Test(ref test) => vec![test],
+            ConditionExpr::Not(ref c) => c.tests(),
+            ConditionExpr::And(ref l, ref r) | ConditionExpr::Or(ref l, ref r) => {
+                let mut tests = l.tests();
+                tests.extend(r.tests());
+                tests
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Condition {
     pub span: Span,
@@ -469,6 +683,9 @@ pub enum SymbolKind {
     // foo<..>
     Macro(MacroSymbol),
 
+    // foo[..]
+    Call(CallSymbol),
+
     // X+, X?, X*
     Repeat(Box<RepeatSymbol>),
 
@@ -611,6 +828,14 @@ pub struct MacroSymbol {
     pub args: Vec<Symbol>,
 }
 
+/// A use of a nonterminal that declares parameters, with the values
+/// to pass for them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallSymbol {
+    pub name: NonterminalString,
+    pub args: Vec<Path>,
+}
+
 impl TerminalString {
     pub fn quoted(i: Atom) -> TerminalString {
         TerminalString::Literal(TerminalLiteral::Quoted(i))
@@ -677,6 +902,9 @@ impl GrammarItem {
             GrammarItem::MatchToken(..) => None,
             GrammarItem::ExternToken(..) => None,
             GrammarItem::InternToken(..) => None,
+            GrammarItem::Include(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::Code(..) => None,
         }
     }
 
@@ -687,6 +915,9 @@ impl GrammarItem {
             GrammarItem::MatchToken(ref d) => Some(d),
             GrammarItem::ExternToken(..) => None,
             GrammarItem::InternToken(..) => None,
+            GrammarItem::Include(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::Code(..) => None,
         }
     }
 
@@ -697,6 +928,9 @@ impl GrammarItem {
             GrammarItem::MatchToken(..) => None,
             GrammarItem::ExternToken(ref d) => Some(d),
             GrammarItem::InternToken(..) => None,
+            GrammarItem::Include(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::Code(..) => None,
         }
     }
 
@@ -707,6 +941,9 @@ impl GrammarItem {
             GrammarItem::MatchToken(..) => None,
             GrammarItem::ExternToken(..) => None,
             GrammarItem::InternToken(ref d) => Some(d),
+            GrammarItem::Include(..) => None,
+            GrammarItem::TypeAlias(..) => None,
+            GrammarItem::Code(..) => None,
         }
     }
 }
@@ -953,6 +1190,7 @@ impl Display for SymbolKind {
             SymbolKind::Nonterminal(ref s) => write!(fmt, "{}", s),
             SymbolKind::AmbiguousId(ref s) => write!(fmt, "{}", s),
             SymbolKind::Macro(ref m) => write!(fmt, "{}", m),
+            SymbolKind::Call(ref c) => write!(fmt, "{}", c),
             SymbolKind::Repeat(ref r) => write!(fmt, "{}", r),
             SymbolKind::Choose(ref s) => write!(fmt, "<{}>", s),
             SymbolKind::Name(ref n, ref s) => write!(fmt, "{}:{}", n, s),
@@ -1025,6 +1263,12 @@ impl Display for MacroSymbol {
     }
 }
 
+impl Display for CallSymbol {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "{}[{}]", self.name, Sep(", ", &self.args))
+    }
+}
+
 impl Display for TypeParameter {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         match *self {
@@ -1055,6 +1299,7 @@ impl Display for TypeRef {
                 ref path,
                 ref types,
             } => write!(fmt, "dyn {}<{}>", path, Sep(", ", types)),
+            TypeRef::Impl(ref bounds) => write!(fmt, "impl {}", Sep(" + ", bounds)),
             TypeRef::Lifetime(ref s) => write!(fmt, "{}", s),
             TypeRef::Id(ref s) => write!(fmt, "{}", s),
             TypeRef::OfSymbol(ref s) => write!(fmt, "`{}`", s),
@@ -1083,6 +1328,7 @@ impl Display for TypeRef {
                 ref path,
                 ref parameters,
                 ref ret,
+                ref bound,
             } => {
                 write!(fmt, "dyn ")?;
                 if !forall.is_empty() {
@@ -1092,6 +1338,9 @@ impl Display for TypeRef {
                 if let Some(ret) = ret {
                     write!(fmt, " -> {}", ret)?;
                 }
+                if let Some(bound) = bound {
+                    write!(fmt, " + {}", bound)?;
+                }
                 Ok(())
             }
         }
@@ -1121,6 +1370,12 @@ impl TypeRef {
                 types: vec![],
             }),
             TypeRef::OfSymbol(_) => unreachable!("OfSymbol produced by parser"),
+            TypeRef::Impl(ref bounds) => TypeRepr::boxed_dyn(
+                bounds
+                    .iter()
+                    .map(|bound| bound.map(TypeRef::type_repr))
+                    .collect(),
+            ),
             TypeRef::Ref {
                 ref lifetime,
                 mutable,
@@ -1142,11 +1397,13 @@ impl TypeRef {
                 ref path,
                 ref parameters,
                 ref ret,
+                ref bound,
             } => TypeRepr::Fn {
                 forall: forall.clone(),
                 path: path.clone(),
                 parameters: parameters.iter().map(TypeRef::type_repr).collect(),
                 ret: ret.as_ref().map(|t| Box::new(TypeRef::type_repr(t))),
+                bound: bound.clone(),
             },
         }
     }
@@ -1181,6 +1438,13 @@ impl Path {
         }
     }
 
+    pub fn boxed() -> Path {
+        Path {
+            absolute: false,
+            ids: vec![Atom::from("alloc"), Atom::from("boxed"), Atom::from("Box")],
+        }
+    }
+
     pub fn option() -> Path {
         Path {
             absolute: false,
@@ -1211,6 +1475,16 @@ pub fn read_algorithm(annotations: &[Annotation], algorithm: &mut r::Algorithm)
             algorithm.codegen = r::LrCodeGeneration::RecursiveAscent;
         } else if annotation.id == Atom::from(TEST_ALL) {
             algorithm.codegen = r::LrCodeGeneration::TestAll;
+        } else if annotation.id == *LONGEST_MATCH {
+            // lexer policy, handled in `token_check`
+        } else if annotation.id == *REPAIR {
+            // error repair, handled in `lower`
+        } else if annotation.id == *RUNTIME_START {
+            // extra entry point, handled in `lower`
+        } else if annotation.id == *BACKTRACK {
+            // conflict resolution, handled in `lower`
+        } else if annotation.id == *COPY_LOCATION {
+            // location copies, handled in `lower`
         } else {
             panic!(
                 "validation permitted unknown annotation: {:?}",
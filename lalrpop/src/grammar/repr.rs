@@ -3,31 +3,59 @@
 //! representation incrementally.
 
 use crate::collections::{map, Map};
+use crate::grammar::consts::{RECURSIVE_ASCENT, TABLE_DRIVEN};
 use crate::grammar::free_variables::FreeVariables;
 use crate::grammar::pattern::Pattern;
 use crate::message::Content;
 use std::fmt::{Debug, Display, Error, Formatter};
+use std::str::FromStr;
 use string_cache::DefaultAtom as Atom;
 use crate::util::Sep;
 
 // These concepts we re-use wholesale
 pub use crate::grammar::parse_tree::{
-    Annotation, InternToken, Lifetime, Name, NonterminalString, Path, Span, TerminalLiteral,
-    TerminalString, TypeBound, TypeParameter, Visibility,
+    Annotation, Code, Expansion, InternToken, Lifetime, Name, NonterminalString, Path, Span,
+    TerminalLiteral, TerminalString, TypeBound, TypeParameter, Visibility,
 };
 
+/// The primitive types, which are all `Copy`.
+const COPY_PRIMITIVES: &[&str] = &[
+    "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32",
+    "u64", "u128", "usize",
+];
+
 #[derive(Clone, Debug)]
 pub struct Grammar {
     // a unique prefix that can be appended to identifiers to ensure
     // that they do not conflict with any action strings
     pub prefix: String,
 
+    // the span of the `grammar` declaration
+    pub span: Span,
+
     // algorithm user requested for this parser
     pub algorithm: Algorithm,
 
     // true if the grammar mentions the `!` terminal anywhere
     pub uses_error_recovery: bool,
 
+    // how the parser should repair syntax errors, if the user asked
+    // for it with `#[repair]`
+    pub repair: Option<RepairStrategy>,
+
+    // true if the user asked for a `parse` fn that takes the start
+    // symbol as an argument, with `#[runtime_start]`
+    pub runtime_start: bool,
+
+    // true if conflicts are left in the tables, to be resolved at
+    // runtime by trying each action, with `#[backtrack]`
+    pub backtrack: bool,
+
+    // true if locations are `Copy`, so the generated code copies them
+    // rather than calling `clone`: either the location type is one
+    // that is known to be, or the user said so with `#[copy_location]`
+    pub copy_location: bool,
+
     // these are the nonterminals that were declared to be public; the
     // key is the user's name for the symbol, the value is the
     // artificial symbol we introduce, which will always have a single
@@ -37,6 +65,13 @@ pub struct Grammar {
     // the "use foo;" statements that the user declared
     pub uses: Vec<String>,
 
+    // the "type Foo<T> = ...;" aliases that the user declared
+    pub type_aliases: Vec<TypeAlias>,
+
+    // the "code { ... }" blocks that the user declared, emitted at the
+    // end of the module
+    pub code: Vec<Code>,
+
     // type parameters declared on the grammar, like `grammar<T>;`
     pub type_parameters: Vec<TypeParameter>,
 
@@ -58,6 +93,50 @@ pub struct Grammar {
     pub conversions: Map<TerminalString, Pattern<TypeRepr>>,
     pub types: Types,
     pub module_attributes: Vec<String>,
+
+    // the entries of the grammar's `.messages` file, if it has one
+    pub error_messages: Vec<ErrorMessage>,
+
+    // the rule of each user action, like `Expr = Expr "+" Term`, for
+    // coverage reports; see `Session::coverage`
+    pub rules: Vec<(ActionFn, String)>,
+
+    // the ids given to terminals with `#[terminal_id]`, by id
+    pub terminal_ids: Vec<TerminalId>,
+
+    // the costs given to terminals with `#[cost]`, for error repair
+    pub repair_costs: Map<TerminalString, RepairCost>,
+}
+
+/// An id that the user gave a terminal, as `#[terminal_id(name = "id")]`,
+/// so that tools outside of Rust can refer to it by a number that
+/// stays the same as the grammar changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerminalId {
+    /// the name of the constant for the id in the generated code
+    pub name: Atom,
+    pub terminal: TerminalString,
+    pub id: u32,
+}
+
+/// A `type` alias declared in the grammar, which is emitted into the
+/// generated module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeAlias {
+    pub name: Atom,
+    pub type_parameters: Vec<TypeParameter>,
+    pub type_repr: TypeRepr,
+}
+
+/// An entry of a `.messages` file: `message` is for the syntax errors
+/// found in the state that parsing `sentence` as `start` ends up in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorMessage {
+    /// where the sentence is in the `.messages` file
+    pub span: Span,
+    pub start: NonterminalString,
+    pub sentence: Vec<TerminalString>,
+    pub message: String,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -90,6 +169,7 @@ pub struct NonterminalData {
     pub span: Span,
     pub annotations: Vec<Annotation>,
     pub productions: Vec<Production>,
+    pub expansion: Option<Expansion>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -105,6 +185,51 @@ pub enum LrCodeGeneration {
     TestAll,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RepairStrategy {
+    /// Insert or delete a single token at the point of the error.
+    SingleToken,
+
+    /// Search short sequences of insertions, deletions and
+    /// replacements over the next few tokens.
+    BurkeFisher,
+
+    /// Search like `BurkeFisher`, but take the cheapest of the
+    /// sequences that get through all of the tokens searched.
+    MinimumCost,
+}
+
+impl FromStr for RepairStrategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<RepairStrategy, ()> {
+        match s {
+            "single_token" => Ok(RepairStrategy::SingleToken),
+            "burke_fisher" => Ok(RepairStrategy::BurkeFisher),
+            "minimum_cost" => Ok(RepairStrategy::MinimumCost),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What it costs error repair to insert or delete a token of a
+/// terminal, as given with `#[cost(insert = "5")]`. Edits that are not
+/// given a cost cost 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RepairCost {
+    pub insert: u32,
+    pub delete: u32,
+}
+
+impl Default for RepairCost {
+    fn default() -> Self {
+        RepairCost {
+            insert: 1,
+            delete: 1,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Parameter {
     pub name: Atom,
@@ -118,6 +243,8 @@ pub struct Production {
     pub nonterminal: NonterminalString,
     pub symbols: Vec<Symbol>,
     pub action: ActionFn,
+    // the `when { .. }` guard, if any; a `Guard` action fn
+    pub guard: Option<ActionFn>,
     pub span: Span,
 }
 
@@ -139,6 +266,7 @@ pub enum ActionFnDefnKind {
     User(UserActionFnDefn),
     Inline(InlineActionFnDefn),
     Lookaround(LookaroundActionFnDefn),
+    Guard(UserActionFnDefn),
 }
 
 /// An action fn written by a user.
@@ -195,15 +323,26 @@ pub enum TypeRepr {
         referent: Box<TypeRepr>,
     },
     TraitObject(NominalTypeRepr),
+    // `dyn Trait + 'a`, the boxed form of an `impl Trait` nonterminal
+    Dyn(Vec<TypeBound<TypeRepr>>),
     Fn {
         forall: Vec<TypeParameter>,
         path: Path,
         parameters: Vec<TypeRepr>,
         ret: Option<Box<TypeRepr>>,
+        bound: Option<Lifetime>,
     },
 }
 
 impl TypeRepr {
+    /// `alloc::boxed::Box<dyn B1 + B2 ...>`
+    pub fn boxed_dyn(bounds: Vec<TypeBound<TypeRepr>>) -> Self {
+        TypeRepr::Nominal(NominalTypeRepr {
+            path: Path::boxed(),
+            types: vec![TypeRepr::Dyn(bounds)],
+        })
+    }
+
     pub fn from_parameter(tp: &TypeParameter) -> Self {
         match tp {
             TypeParameter::Lifetime(l) => TypeRepr::Lifetime(l.clone()),
@@ -221,6 +360,23 @@ impl TypeRepr {
         }
     }
 
+    /// True if this is a type that is known to be `Copy`, without
+    /// looking at its definition: a primitive type, a shared reference,
+    /// or a tuple of those.
+    pub fn is_copy(&self) -> bool {
+        match *self {
+            TypeRepr::Tuple(ref types) => types.iter().all(TypeRepr::is_copy),
+            TypeRepr::Nominal(ref data) => {
+                data.types.is_empty()
+                    && !data.path.absolute
+                    && data.path.ids.len() == 1
+                    && COPY_PRIMITIVES.contains(&&*data.path.ids[0])
+            }
+            TypeRepr::Ref { mutable, .. } => !mutable,
+            _ => false,
+        }
+    }
+
     pub fn usize() -> TypeRepr {
         TypeRepr::Nominal(NominalTypeRepr {
             path: Path::usize(),
@@ -235,6 +391,13 @@ impl TypeRepr {
         })
     }
 
+    pub fn bool() -> TypeRepr {
+        TypeRepr::Nominal(NominalTypeRepr {
+            path: Path::from_id(Atom::from("bool")),
+            types: vec![],
+        })
+    }
+
     pub fn bottom_up(&self, op: &mut impl FnMut(TypeRepr) -> TypeRepr) -> Self {
         let result = match self {
             TypeRepr::Tuple(types) => {
@@ -267,16 +430,24 @@ impl TypeRepr {
                     types: types.iter().map(|t| t.bottom_up(op)).collect(),
                 })
             }
+            TypeRepr::Dyn(bounds) => TypeRepr::Dyn(
+                bounds
+                    .iter()
+                    .map(|bound| bound.map(|t| t.bottom_up(op)))
+                    .collect(),
+            ),
             TypeRepr::Fn {
                 forall,
                 path,
                 parameters,
                 ret,
+                bound,
             } => TypeRepr::Fn {
                 forall: forall.clone(),
                 path: path.clone(),
                 parameters: parameters.iter().map(|t| t.bottom_up(op)).collect(),
                 ret: ret.as_ref().map(|t| Box::new(t.bottom_up(op))),
+                bound: bound.clone(),
             },
         };
         op(result)
@@ -310,6 +481,7 @@ impl TypeRepr {
             | TypeRepr::Nominal { .. }
             | TypeRepr::Associated { .. }
             | TypeRepr::TraitObject { .. }
+            | TypeRepr::Dyn { .. }
             | TypeRepr::Fn { .. } => t,
 
             TypeRepr::Lifetime(l) => {
@@ -367,6 +539,7 @@ pub struct Types {
     nonterminal_types: Map<NonterminalString, TypeRepr>,
     parse_error_type: TypeRepr,
     error_recovery_type: TypeRepr,
+    repair_type: TypeRepr,
 }
 
 impl Types {
@@ -385,6 +558,7 @@ impl Types {
             // the following two will be overwritten later
             parse_error_type: TypeRepr::Tuple(vec![]),
             error_recovery_type: TypeRepr::Tuple(vec![]),
+            repair_type: TypeRepr::Tuple(vec![]),
         };
 
         let args = vec![
@@ -410,6 +584,16 @@ impl Types {
                     Atom::from("ErrorRecovery"),
                 ],
             },
+            types: args.clone(),
+        });
+        types.repair_type = TypeRepr::Nominal(NominalTypeRepr {
+            path: Path {
+                absolute: false,
+                ids: vec![
+                    Atom::from(format!("{}lalrpop_util", prefix)),
+                    Atom::from("Repair"),
+                ],
+            },
             types: args,
         });
         types
@@ -478,6 +662,10 @@ impl Types {
         &self.error_recovery_type
     }
 
+    pub fn repair_type(&self) -> &TypeRepr {
+        &self.repair_type
+    }
+
     /// Returns a type `(L, T, L)` where L is the location type and T
     /// is the token type.
     pub fn triple_type(&self) -> TypeRepr {
@@ -504,6 +692,16 @@ impl Display for WhereClause {
     }
 }
 
+impl Display for TypeAlias {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "type {}", self.name)?;
+        if !self.type_parameters.is_empty() {
+            write!(fmt, "<{}>", Sep(", ", &self.type_parameters))?;
+        }
+        write!(fmt, " = {}", self.type_repr)
+    }
+}
+
 impl Display for Parameter {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         write!(fmt, "{}: {}", self.name, self.ty)
@@ -542,11 +740,13 @@ impl Display for TypeRepr {
                 ref referent,
             } => write!(fmt, "&{} mut {}", l, referent),
             TypeRepr::TraitObject(ref data) => write!(fmt, "dyn {}", data),
+            TypeRepr::Dyn(ref bounds) => write!(fmt, "dyn {}", Sep(" + ", bounds)),
             TypeRepr::Fn {
                 ref forall,
                 ref path,
                 ref parameters,
                 ref ret,
+                ref bound,
             } => {
                 write!(fmt, "dyn ")?;
                 if !forall.is_empty() {
@@ -556,6 +756,9 @@ impl Display for TypeRepr {
                 if let Some(ret) = ret {
                     write!(fmt, " -> {}", ret)?;
                 }
+                if let Some(bound) = bound {
+                    write!(fmt, " + {}", bound)?;
+                }
                 Ok(())
             }
         }
@@ -658,7 +861,9 @@ impl Debug for ActionFnDefn {
 impl ActionFnDefn {
     fn to_fn_string(&self, name: &str) -> String {
         match self.kind {
-            ActionFnDefnKind::User(ref data) => data.to_fn_string(self, name),
+            ActionFnDefnKind::User(ref data) | ActionFnDefnKind::Guard(ref data) => {
+                data.to_fn_string(self, name)
+            }
             ActionFnDefnKind::Inline(ref data) => data.to_fn_string(name),
             ActionFnDefnKind::Lookaround(ref data) => format!("{:?}", data),
         }
@@ -716,6 +921,24 @@ impl Grammar {
         }
     }
 
+    /// The notes that trace `nonterminal` back to the user's grammar,
+    /// if LALRPOP made it up; see `Expansion::trace`.
+    pub fn expansion_trace(&self, nonterminal: &NonterminalString) -> Vec<(Span, String)> {
+        Expansion::trace(nonterminal, |nt| {
+            self.nonterminals
+                .get(nt)
+                .map(|data| (data.span, &data.expansion))
+        })
+    }
+
+    /// `message`, about `nonterminal`, followed by the notes of its
+    /// `expansion_trace`, a line each.
+    pub fn with_expansion_notes(&self, nonterminal: &NonterminalString, message: String) -> String {
+        self.expansion_trace(nonterminal)
+            .into_iter()
+            .fold(message, |message, (_, note)| message + "\n" + &note)
+    }
+
     pub fn user_parameter_refs(&self) -> String {
         let mut result = String::new();
         for parameter in &self.parameters {
@@ -728,6 +951,63 @@ impl Grammar {
         self.action_fn_defns[f.index()].fallible
     }
 
+    /// Whether `f` hands back its one argument unchanged, like the
+    /// action of `A = B` or `A = <b:B> => b`.
+    pub fn action_is_identity(&self, f: ActionFn) -> bool {
+        let defn = &self.action_fn_defns[f.index()];
+        match defn.kind {
+            ActionFnDefnKind::User(ref data) => {
+                !defn.fallible
+                    && data.arg_types.len() == 1
+                    && data.arg_types[0] == defn.ret_type
+                    && data.code.trim() == &*data.arg_patterns[0].name
+            }
+            _ => false,
+        }
+    }
+
+    /// The code for a location taken out of `place`, like `__sym0.0`:
+    /// `place` itself if locations are `Copy`, or else a clone of it.
+    pub fn clone_location(&self, place: &str) -> String {
+        if self.copy_location {
+            place.to_string()
+        } else {
+            format!("{}.clone()", place)
+        }
+    }
+
+    /// Like `clone_location`, for a location behind the reference
+    /// `reference`, like `__lookahead`.
+    pub fn clone_location_behind(&self, reference: &str) -> String {
+        if self.copy_location {
+            format!("*{}", reference)
+        } else {
+            format!("{}.clone()", reference)
+        }
+    }
+
+    /// The code generator for the public nonterminal `user_nt`: the one
+    /// its `#[table_driven]` or `#[recursive_ascent]` annotation asks
+    /// for, if any, or else the grammar's.
+    pub fn codegen_for(&self, user_nt: &NonterminalString) -> LrCodeGeneration {
+        for annotation in &self.nonterminals[user_nt].annotations {
+            if annotation.id == *TABLE_DRIVEN {
+                return LrCodeGeneration::TableDriven;
+            } else if annotation.id == *RECURSIVE_ASCENT {
+                return LrCodeGeneration::RecursiveAscent;
+            }
+        }
+        self.algorithm.codegen.clone()
+    }
+
+    /// True if any production has a `when { .. }` guard.
+    pub fn uses_guards(&self) -> bool {
+        self.nonterminals
+            .values()
+            .flat_map(|nt| &nt.productions)
+            .any(|p| p.guard.is_some())
+    }
+
     pub fn non_lifetime_type_parameters(&self) -> Vec<&TypeParameter> {
         self.type_parameters
             .iter()
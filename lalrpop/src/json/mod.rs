@@ -0,0 +1,152 @@
+//! The normalized grammar as JSON, for tools that want to know about
+//! a grammar without parsing `.lalrpop` files themselves: highlighters,
+//! documentation generators and the like.
+//!
+//! The document has the public nonterminals under `start`, and every
+//! terminal and nonterminal with its Rust type. Each production lists
+//! its symbols, as `{"terminal": ..}` or `{"nonterminal": ..}`, and the
+//! byte offsets of the alternative it comes from. Macros and `+` are
+//! expanded, and `?` and `*` inlined, as for the parser; the levels of
+//! a rule with `#[precedence]` are separate nonterminals, and their
+//! productions have the `precedence` level and associativity they were
+//! annotated with. The `first` and `follow` terminals of each
+//! nonterminal are listed too, with `nullable` if it can be empty and
+//! `end_of_input` if the input can end after it.
+
+use crate::collections::Map;
+use crate::grammar::consts::INLINE;
+use crate::grammar::parse_tree::{Span, TerminalLiteral, TerminalString, Visibility};
+use crate::grammar::repr::{Grammar, Symbol};
+use crate::lr1;
+use crate::normalize::Assoc;
+use std::fmt::Write;
+
+#[cfg(test)]
+mod test;
+
+/// `grammar` as JSON; `levels` gives the precedence of alternatives,
+/// by their span, as `normalize::precedence_levels` works it out.
+pub fn grammar_json(grammar: &Grammar, levels: &Map<Span, (u32, Assoc)>) -> String {
+    let starts: Vec<_> = grammar.start_nonterminals.values().collect();
+
+    let mut json = String::new();
+    json.push_str("{\n  \"start\": [");
+    for (index, name) in grammar.start_nonterminals.keys().enumerate() {
+        let _ = write!(json, "{}{}", comma(index, ", "), string(name));
+    }
+    json.push_str("],\n  \"terminals\": [");
+    for (index, terminal) in grammar.terminals.all.iter().enumerate() {
+        let (kind, text) = match *terminal {
+            TerminalString::Literal(TerminalLiteral::Quoted(ref s)) => ("literal", &s[..]),
+            TerminalString::Literal(TerminalLiteral::Regex(ref s)) => ("regex", &s[..]),
+            TerminalString::Bare(ref s) => ("bare", &s[..]),
+            TerminalString::Error => ("error", "error"),
+        };
+        let _ = write!(
+            json,
+            "{}\n    {{\"name\": {}, \"kind\": \"{}\", \"text\": {}, \"type\": {}}}",
+            comma(index, ","),
+            string(terminal),
+            kind,
+            string(text),
+            string(grammar.types.terminal_type(terminal))
+        );
+    }
+    json.push_str("\n  ],\n  \"nonterminals\": [");
+    let nonterminals = grammar
+        .nonterminals
+        .iter()
+        .filter(|(name, _)| !starts.contains(name))
+        .filter(|(_, data)| !data.annotations.iter().any(|a| a.id == *INLINE));
+    let first_follow = lr1::first_follow_sets(grammar);
+    for (index, (name, data)) in nonterminals.enumerate() {
+        let public = match data.visibility {
+            Visibility::Priv => false,
+            Visibility::Pub(_) | Visibility::PubIn(_) => true,
+        };
+        let _ = write!(
+            json,
+            "{}\n    {{\n      \"name\": {},\n      \"public\": {},\n      \"type\": {},\n      \
+             \"span\": [{}, {}],",
+            comma(index, ","),
+            string(name),
+            public,
+            string(grammar.types.nonterminal_type(name)),
+            data.span.0,
+            data.span.1
+        );
+        let sets = &first_follow[name];
+        let _ = write!(
+            json,
+            "\n      \"first\": [{}],\n      \"nullable\": {},\n      \
+             \"follow\": [{}],\n      \"end_of_input\": {},",
+            terminals(&sets.first),
+            sets.nullable,
+            terminals(&sets.follow),
+            sets.end_of_input
+        );
+        json.push_str("\n      \"productions\": [");
+        for (index, production) in data.productions.iter().enumerate() {
+            let _ = write!(json, "{}\n        {{\"symbols\": [", comma(index, ","));
+            for (index, symbol) in production.symbols.iter().enumerate() {
+                let (kind, name) = match *symbol {
+                    Symbol::Terminal(ref t) => ("terminal", string(t)),
+                    Symbol::Nonterminal(ref nt) => ("nonterminal", string(nt)),
+                };
+                let _ = write!(json, "{}{{\"{}\": {}}}", comma(index, ", "), kind, name);
+            }
+            let _ = write!(
+                json,
+                "], \"span\": [{}, {}]",
+                production.span.0, production.span.1
+            );
+            if let Some(&(level, assoc)) = levels.get(&production.span) {
+                let _ = write!(
+                    json,
+                    ", \"precedence\": {{\"level\": {}, \"assoc\": \"{}\"}}",
+                    level, assoc
+                );
+            }
+            json.push('}');
+        }
+        json.push_str("\n      ]\n    }");
+    }
+    json.push_str("\n  ]\n}\n");
+    json
+}
+
+/// What goes before the item at `index` of a list: nothing for the
+/// first, and `separator` for the others.
+fn comma(index: usize, separator: &'static str) -> &'static str {
+    if index == 0 {
+        ""
+    } else {
+        separator
+    }
+}
+
+/// The names of `terminals`, as the items of a JSON array.
+fn terminals(terminals: &[TerminalString]) -> String {
+    let names: Vec<_> = terminals.iter().map(string).collect();
+    names.join(", ")
+}
+
+/// `text` as a JSON string.
+fn string<T: ToString + ?Sized>(text: &T) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.to_string().chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
@@ -0,0 +1,70 @@
+use crate::build::grammar_json;
+
+#[test]
+fn productions() {
+    let json = grammar_json(
+        r#"
+grammar;
+
+pub Expr: u32 = {
+    <l:Expr> "+" <r:Num> => l + r,
+    Num,
+};
+
+Num: u32 = r"[0-9]+" => <>.parse().unwrap();
+"#,
+    )
+    .unwrap();
+    assert!(json.starts_with("{\n  \"start\": [\"Expr\"],\n"));
+    assert!(json
+        .contains(r#"{"name": "\"+\"", "kind": "literal", "text": "+", "type": "&'input str"}"#));
+    assert!(json.contains(
+        r##"{"name": "r#\"[0-9]+\"#", "kind": "regex", "text": "[0-9]+", "type": "&'input str"}"##
+    ));
+    assert!(json.contains(
+        "      \"name\": \"Expr\",\n      \"public\": true,\n      \"type\": \"u32\",\n"
+    ));
+    assert!(json.contains("      \"name\": \"Num\",\n      \"public\": false,\n"));
+    assert!(json.contains(
+        "      \"first\": [\"r#\\\"[0-9]+\\\"#\"],\n      \"nullable\": false,\n      \
+         \"follow\": [\"\\\"+\\\"\"],\n      \"end_of_input\": true,\n"
+    ));
+    assert!(json.contains(
+        r#"{"symbols": [{"nonterminal": "Expr"}, {"terminal": "\"+\""}, {"nonterminal": "Num"}], "span": [33, 62]}"#
+    ));
+    assert!(!json.contains("__Expr"));
+}
+
+#[test]
+fn precedence() {
+    let json = grammar_json(
+        r#"
+grammar;
+
+pub Expr: () = {
+    #[precedence(level = "0")]
+    "x",
+    #[precedence(level = "1")] #[assoc(side = "left")]
+    Expr "*" Expr,
+    Expr "/" Expr,
+    #[precedence(level = "2")] #[assoc(side = "right")]
+    Expr "^" Expr,
+};
+"#,
+    )
+    .unwrap();
+    assert!(json.contains(r#""precedence": {"level": 0, "assoc": "all"}"#));
+    assert!(json.contains(
+        r#"{"symbols": [{"nonterminal": "Expr1"}, {"terminal": "\"/\""}, {"nonterminal": "Expr0"}], "span": [146, 159], "precedence": {"level": 1, "assoc": "left"}}"#
+    ));
+    assert!(json.contains(r#""precedence": {"level": 2, "assoc": "right"}"#));
+    // the productions that lead to the level below have none
+    assert!(json.contains(r#"{"symbols": [{"nonterminal": "Expr1"}], "span": [15, 19]}"#));
+}
+
+#[test]
+fn escaping() {
+    let json = grammar_json("grammar; pub A: () = \"\\\\\" \"\\\"\";").unwrap();
+    assert!(json.contains(r#""text": "\\""#));
+    assert!(json.contains(r#""text": "\"""#));
+}
@@ -19,6 +19,21 @@ mod overlap;
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DFA {
     pub states: Vec<State>,
+
+    /// Pairs of regexs that can match the same string, where one was
+    /// preferred over the other because of its precedence.
+    pub overlaps: Vec<Overlap>,
+}
+
+/// Records that both `winner` and `loser` match `example`; the DFA
+/// accepts `winner` in that case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Overlap {
+    pub winner: NFAIndex,
+    pub loser: NFAIndex,
+
+    /// The shortest string we found that is matched by both regexs.
+    pub example: String,
 }
 
 #[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
@@ -32,8 +47,12 @@ pub enum DFAConstructionError {
     },
 
     /// Either of the two regexs listed could match, and they have equal
-    /// priority.
-    Ambiguity { match0: NFAIndex, match1: NFAIndex },
+    /// priority. `example` is a string matched by both.
+    Ambiguity {
+        match0: NFAIndex,
+        match1: NFAIndex,
+        example: String,
+    },
 }
 
 pub fn build_dfa(
@@ -109,11 +128,19 @@ impl<'nfa> DFABuilder<'nfa> {
     fn build(&self) -> Result<DFA, DFAConstructionError> {
         let mut kernel_set = KernelSet::new();
         let mut states = vec![];
+        let mut overlaps: Vec<Overlap> = vec![];
+
+        // the shortest string leading to each state; states are
+        // visited in breadth-first order, so the first path we find
+        // to a state is as short as any other
+        let mut examples = vec![String::new()];
 
         let start_state_index = self.start_state(&mut kernel_set);
         assert_eq!(start_state_index, START);
 
         while let Some(item_set) = kernel_set.next() {
+            let example = examples[states.len()].clone();
+
             // collect all the specific tests we expect from any of
             // the items in this state
             let tests: Set<Test> = item_set
@@ -159,28 +186,43 @@ impl<'nfa> DFABuilder<'nfa> {
                     return Err(DFAConstructionError::Ambiguity {
                         match0: best_nfa,
                         match1: next_nfa,
+                        example,
                     });
                 }
+                for &(_, loser) in &all_accepts[..all_accepts.len() - 1] {
+                    let seen = overlaps
+                        .iter()
+                        .any(|o| o.winner == best_nfa && o.loser == loser);
+                    if !seen {
+                        overlaps.push(Overlap {
+                            winner: best_nfa,
+                            loser,
+                            example: example.clone(),
+                        });
+                    }
+                }
                 Kind::Accepts(best_nfa)
             };
 
             // for each specific test, find what happens if we see a
             // character matching that test
-            let mut test_edges: Vec<(Test, DFAStateIndex)> = tests
-                .iter()
-                .map(|&test| {
-                    let items: Vec<_> = item_set
-                        .items
-                        .iter()
-                        .filter_map(|&item| self.accept_test(item, test))
-                        .collect();
-
-                    // at least one of those items should accept this test
-                    assert!(!items.is_empty());
-
-                    (test, kernel_set.add_state(self.transitive_closure(items)))
-                })
-                .collect();
+            let mut test_edges: Vec<(Test, DFAStateIndex)> = Vec::with_capacity(tests.len());
+            for &test in &tests {
+                let items: Vec<_> = item_set
+                    .items
+                    .iter()
+                    .filter_map(|&item| self.accept_test(item, test))
+                    .collect();
+
+                // at least one of those items should accept this test
+                assert!(!items.is_empty());
+
+                let target = kernel_set.add_state(self.transitive_closure(items));
+                if target.0 == examples.len() {
+                    examples.push(format!("{}{}", example, test.example_char()));
+                }
+                test_edges.push((test, target));
+            }
 
             test_edges.sort();
 
@@ -199,6 +241,9 @@ impl<'nfa> DFABuilder<'nfa> {
             assert!(item_set.items.is_empty() || !other_transitions.is_empty());
 
             let other_edge = kernel_set.add_state(self.transitive_closure(other_transitions));
+            if other_edge.0 == examples.len() {
+                examples.push(format!("{}{}", example, other_char(&tests)));
+            }
 
             let state = State {
                 item_set,
@@ -210,7 +255,7 @@ impl<'nfa> DFABuilder<'nfa> {
             states.push(state);
         }
 
-        Ok(DFA { states })
+        Ok(DFA { states, overlaps })
     }
 
     fn start_state(&self, kernel_set: &mut DFAKernelSet) -> DFAStateIndex {
@@ -275,6 +320,16 @@ impl<'nfa> DFABuilder<'nfa> {
     }
 }
 
+/// Picks a character that satisfies none of `tests`, preferring
+/// printable ASCII so that examples stay readable.
+fn other_char(tests: &[Test]) -> char {
+    (0x20..0x7F)
+        .chain(0xA0..=0x10FFFF)
+        .filter_map(std::char::from_u32)
+        .find(|&ch| !tests.iter().any(|test| test.contains_char(ch)))
+        .unwrap_or('\u{FFFD}')
+}
+
 impl Kernel for DFAItemSet {
     type Index = DFAStateIndex;
 
@@ -1,5 +1,5 @@
 use crate::lexer::dfa::interpret::interpret;
-use crate::lexer::dfa::{self, DFAConstructionError, NFAIndex, Precedence, DFA};
+use crate::lexer::dfa::{self, DFAConstructionError, NFAIndex, Overlap, Precedence, DFA};
 use crate::lexer::re;
 
 pub fn dfa(inputs: &[(&str, Precedence)]) -> Result<DFA, DFAConstructionError> {
@@ -75,3 +75,30 @@ fn alternatives_contraction() {
     assert_eq!(interpret(&dfa, "abcd"), Some((NFAIndex(0), "abcd")));
     assert_eq!(interpret(&dfa, "123"), None);
 }
+
+#[test]
+fn ambiguity_example() {
+    match dfa(&[(r#"[a-z]+"#, P0), (r#"[a-z0-9]+"#, P0)]) {
+        Err(DFAConstructionError::Ambiguity { example, .. }) => assert_eq!(example, "a"),
+        r => panic!("expected ambiguity, got {:?}", r),
+    }
+}
+
+#[test]
+fn overlap_examples() {
+    let dfa = dfa(&[
+        (r#"class"#, P1),                  // 0
+        (r#"[a-zA-Z_][a-zA-Z0-9_]*"#, P0), // 1
+        (r#"[0-9]+"#, P0),                 // 2
+    ])
+    .unwrap();
+
+    assert_eq!(
+        dfa.overlaps,
+        vec![Overlap {
+            winner: NFAIndex(0),
+            loser: NFAIndex(1),
+            example: "class".to_string(),
+        }]
+    );
+}
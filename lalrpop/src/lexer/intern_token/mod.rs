@@ -1,9 +1,11 @@
 //! Generates an iterator type `Matcher` that looks roughly like
 
-use crate::grammar::parse_tree::{InternToken, MatchMapping};
-use crate::grammar::repr::{Grammar, TerminalLiteral};
+use crate::grammar::parse_tree::{InternToken, MatchEntry, MatchMapping};
+use crate::grammar::repr::{Grammar, LrCodeGeneration, TerminalLiteral, TerminalString};
 use crate::lexer::re;
 use crate::rust::RustWrite;
+use crate::session::Profile;
+use crate::tls::Tls;
 use std::io::{self, Write};
 
 pub fn compile<W: Write>(
@@ -17,6 +19,7 @@ pub fn compile<W: Write>(
     rust!(out, "mod {}intern_token {{", prefix);
     rust!(out, "#![allow(unused_imports)]");
     out.write_uses("super::", &grammar)?;
+    rust!(out, "use self::{}lalrpop_util::lexer::Token;", prefix);
     rust!(
         out,
         "pub fn new_builder() -> {}lalrpop_util::lexer::MatcherBuilder {{",
@@ -24,31 +27,10 @@ pub fn compile<W: Write>(
     );
 
     // create a vector of rust string literals with the text of each
-    // regular expression
-    let regex_strings = intern_token
-        .match_entries
-        .iter()
-        .map(|match_entry| {
-            (
-                match match_entry.match_literal {
-                    TerminalLiteral::Quoted(ref s) => re::parse_literal(&s),
-                    TerminalLiteral::Regex(ref s) => re::parse_regex(&s).unwrap(),
-                },
-                match match_entry.user_name {
-                    MatchMapping::Terminal(_) => false,
-                    MatchMapping::Skip => true,
-                },
-            )
-        })
-        .map(|(regex, skip)| {
-            // make sure all regex are anchored at the beginning of the input
-            (format!("^({})", regex), skip)
-        })
-        .map(|(regex_str, skip)| {
-            // create a rust string with text of the regex; the Debug impl
-            // will add quotes and escape
-            (format!("{:?}", regex_str), skip)
-        });
+    // regular expression; the Debug impl will add quotes and escape
+    let regex_strings = regexes(intern_token)
+        .into_iter()
+        .map(|(regex_str, skip)| (format!("{:?}", regex_str), skip));
 
     let mut contains_skip = false;
 
@@ -66,11 +48,98 @@ pub fn compile<W: Write>(
 
     rust!(
         out,
-        "{p}lalrpop_util::lexer::MatcherBuilder::new({p}strs.iter().copied()).unwrap()",
+        "let {p}builder = {p}lalrpop_util::lexer::MatcherBuilder::new({p}strs.iter().copied()).unwrap();",
         p = prefix
     );
 
+    // what each token can start with, for `Matcher::invalid_text`; the
+    // `wasm` profile leaves out the names, as it does for errors
+    if Tls::session().profile == Profile::Wasm {
+        rust!(out, "{}builder", prefix);
+    } else {
+        rust!(out, "{}builder.with_starts(&[", prefix);
+        for match_entry in &intern_token.match_entries {
+            let (name, skip) = match match_entry.user_name {
+                MatchMapping::Terminal(ref t) => (t.to_string(), false),
+                MatchMapping::Skip => (match_entry.match_literal.to_string(), true),
+            };
+            let ranges: Vec<String> = re::first_chars(&regex(match_entry))
+                .into_iter()
+                .map(|(lo, hi)| format!("({:?}, {:?})", lo, hi))
+                .collect();
+            rust!(
+                out,
+                "(r###\"{}\"###, {}, &[{}]),",
+                name,
+                skip,
+                ranges.join(", ")
+            );
+        }
+        rust!(out, "])");
+    }
+
     rust!(out, "}}"); // fn
+
+    // the terminals, and so their indices, are the same for every
+    // parser of the file, so the table-driven ones share this
+    let table_driven = grammar
+        .start_nonterminals
+        .keys()
+        .any(|nt| grammar.codegen_for(nt) != LrCodeGeneration::RecursiveAscent);
+    if table_driven {
+        write_token_to_integer_fn(grammar, out)?;
+    }
+
     rust!(out, "}}"); // mod
     Ok(())
 }
+
+/// Writes `token_to_integer`, which gives the index of the terminal
+/// that a token of the lexer is, as the `ParserDefinition` of a
+/// table-driven parser wants.
+fn write_token_to_integer_fn<W: Write>(grammar: &Grammar, out: &mut RustWrite<W>) -> io::Result<()> {
+    let prefix = &grammar.prefix;
+
+    rust!(
+        out,
+        "pub fn token_to_integer({}token: &Token<'_>) -> Option<usize> {{",
+        prefix
+    );
+    rust!(out, "match *{}token {{", prefix);
+    for (terminal, index) in grammar.terminals.all.iter().zip(0..) {
+        if *terminal == TerminalString::Error {
+            continue;
+        }
+        let pattern = grammar.pattern(terminal).map(&mut |_| "_");
+        rust!(out, "{} => Some({}),", pattern, index);
+    }
+    rust!(out, "_ => None,");
+    rust!(out, "}}");
+    rust!(out, "}}");
+    Ok(())
+}
+
+/// The regular expressions of the lexer, anchored at the beginning of
+/// the input, each with whether it matches text to skip. They are in
+/// the order of `intern_token.match_entries`.
+pub fn regexes(intern_token: &InternToken) -> Vec<(String, bool)> {
+    intern_token
+        .match_entries
+        .iter()
+        .map(|match_entry| {
+            let skip = match match_entry.user_name {
+                MatchMapping::Terminal(_) => false,
+                MatchMapping::Skip => true,
+            };
+            (format!("^({})", regex(match_entry)), skip)
+        })
+        .collect()
+}
+
+/// The regular expression of `match_entry`.
+fn regex(match_entry: &MatchEntry) -> re::Regex {
+    match match_entry.match_literal {
+        TerminalLiteral::Quoted(ref s) => re::parse_literal(s),
+        TerminalLiteral::Regex(ref s) => re::parse_regex(s).unwrap(),
+    }
+}
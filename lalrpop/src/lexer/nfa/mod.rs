@@ -8,6 +8,7 @@ use regex_syntax::hir::{
     RepetitionKind, RepetitionRange,
 };
 use std::char;
+use std::collections::VecDeque;
 use std::fmt::{Debug, Error as FmtError, Formatter};
 use std::usize;
 
@@ -134,6 +135,44 @@ impl NFA {
         self.states[from.0].kind == StateKind::Reject
     }
 
+    /// The shortest string that this NFA accepts, if there is one,
+    /// for examples in diagnostics. The characters are picked as by
+    /// `Test::example_char`.
+    pub fn example(&self) -> Option<String> {
+        // a breadth-first search, in which the `Noop` edges cost
+        // nothing and so go to the front of the queue
+        let mut examples: Vec<Option<String>> = vec![None; self.states.len()];
+        let mut queue = VecDeque::new();
+        queue.push_back((START, String::new()));
+        while let Some((state, example)) = queue.pop_front() {
+            if examples[state.0].is_some() {
+                continue;
+            }
+            if self.is_accepting_state(state) {
+                return Some(example);
+            }
+            examples[state.0] = Some(example.clone());
+            for edge in self.edges::<Noop>(state) {
+                queue.push_front((edge.to, example.clone()));
+            }
+            // lowercase letters first, as `example_char` prefers them
+            let mut edges: Vec<_> = self.edges::<Test>(state).collect();
+            edges.sort_by_key(|edge| "a0A ".find(edge.label.example_char()).unwrap_or(4));
+            for edge in &edges {
+                queue.push_back((edge.to, format!("{}{}", example, edge.label.example_char())));
+            }
+            let other = (0x20..0x7F)
+                .filter_map(char::from_u32)
+                .find(|&c| !edges.iter().any(|edge| edge.label.contains_char(c)));
+            if let Some(other) = other {
+                for edge in self.edges::<Other>(state) {
+                    queue.push_back((edge.to, format!("{}{}", example, other)));
+                }
+            }
+        }
+        None
+    }
+
     ///////////////////////////////////////////////////////////////////////////
     // Private methods for building an NFA
 
@@ -573,6 +612,18 @@ impl Test {
     pub fn is_empty(self) -> bool {
         self.start == self.end
     }
+
+    /// Some character matched by this test, used when building
+    /// example strings for diagnostics. Prefers a lowercase letter or
+    /// digit when the range contains one.
+    pub fn example_char(self) -> char {
+        ['a', '0', 'A', ' ']
+            .iter()
+            .cloned()
+            .find(|&c| self.contains_char(c))
+            .or_else(|| (self.start..self.end).filter_map(char::from_u32).next())
+            .unwrap_or('\u{FFFD}')
+    }
 }
 
 impl From<ClassUnicodeRange> for Test {
@@ -81,6 +81,35 @@ fn max_range() {
     assert_eq!(interpret(&nfa, "ac"), None);
 }
 
+#[test]
+fn exact_range() {
+    let num = re::parse_regex(r#"[0-9]{3}"#).unwrap();
+    let nfa = NFA::from_re(&num).unwrap();
+    assert_eq!(interpret(&nfa, "12"), None);
+    assert_eq!(interpret(&nfa, "123"), Some("123"));
+    assert_eq!(interpret(&nfa, "1234"), Some("123"));
+}
+
+#[test]
+fn min_range() {
+    let num = re::parse_regex(r#"a{2,}"#).unwrap();
+    let nfa = NFA::from_re(&num).unwrap();
+    assert_eq!(interpret(&nfa, "a"), None);
+    assert_eq!(interpret(&nfa, "aa"), Some("aa"));
+    assert_eq!(interpret(&nfa, "aaaaa"), Some("aaaaa"));
+}
+
+#[test]
+fn bounded_range_in_concat() {
+    // an IPv4 address
+    let num = re::parse_regex(r#"[0-9]{1,3}(\.[0-9]{1,3}){3}"#).unwrap();
+    let nfa = NFA::from_re(&num).unwrap();
+    assert_eq!(interpret(&nfa, "10.0.0.1"), Some("10.0.0.1"));
+    assert_eq!(interpret(&nfa, "192.168.1.255:80"), Some("192.168.1.255"));
+    assert_eq!(interpret(&nfa, "1.2.3"), None);
+    assert_eq!(interpret(&nfa, "1234.2.3.4"), None);
+}
+
 #[test]
 fn literal() {
     let num = re::parse_regex(r#"(?i:aBCdeF)"#).unwrap();
@@ -157,3 +186,17 @@ fn issue_101() {
     let num = re::parse_regex(r#"(1|0?)"#).unwrap();
     NFA::from_re(&num).unwrap();
 }
+
+#[test]
+fn examples() {
+    let example = |regex: &str| {
+        NFA::from_re(&re::parse_regex(regex).unwrap())
+            .unwrap()
+            .example()
+    };
+    assert_eq!(example(r#"[a-zA-Z_][a-zA-Z0-9_]*"#), Some("a".to_string()));
+    assert_eq!(example(r#"[0-9]+"#), Some("0".to_string()));
+    assert_eq!(example(r#"(ab)*c?"#), Some("".to_string()));
+    assert_eq!(example(r#"x{3}|yz"#), Some("yz".to_string()));
+    assert_eq!(example(r#"/\*[^*]*\*/"#), Some("/**/".to_string()));
+}
@@ -1,6 +1,6 @@
 //! A parser and representation of regular expressions.
 
-use regex_syntax::hir::Hir;
+use regex_syntax::hir::{Class, Hir, HirKind, Literal, RepetitionKind, RepetitionRange};
 use regex_syntax::{self, Error, Parser};
 
 #[cfg(test)]
@@ -22,3 +22,72 @@ pub fn parse_regex(s: &str) -> Result<Regex, RegexError> {
     let expr = Parser::new().parse(s)?;
     Ok(expr)
 }
+
+/// The characters that text matching `regex` can start with, as sorted
+/// and disjoint ranges. Anchors and word boundaries match no text, so
+/// they are looked through.
+pub fn first_chars(regex: &Regex) -> Vec<(char, char)> {
+    let mut ranges = first(regex).0;
+    ranges.sort_unstable();
+    let mut merged: Vec<(char, char)> = vec![];
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start as u32 <= last.1 as u32 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// The first characters of `regex`, and whether it can match the empty
+/// string (in which case what follows it can come first too).
+fn first(regex: &Regex) -> (Vec<(char, char)>, bool) {
+    match *regex.kind() {
+        HirKind::Empty | HirKind::Anchor(_) | HirKind::WordBoundary(_) => (vec![], true),
+        HirKind::Literal(Literal::Unicode(c)) => (vec![(c, c)], false),
+        HirKind::Literal(Literal::Byte(b)) => (vec![(b as char, b as char)], false),
+        HirKind::Class(Class::Unicode(ref class)) => {
+            (class.iter().map(|r| (r.start(), r.end())).collect(), false)
+        }
+        HirKind::Class(Class::Bytes(ref class)) => (
+            class
+                .iter()
+                .map(|r| (r.start() as char, r.end() as char))
+                .collect(),
+            false,
+        ),
+        HirKind::Group(ref group) => first(&group.hir),
+        HirKind::Repetition(ref repetition) => {
+            let (ranges, nullable) = first(&repetition.hir);
+            let optional = match repetition.kind {
+                RepetitionKind::ZeroOrOne | RepetitionKind::ZeroOrMore => true,
+                RepetitionKind::OneOrMore => false,
+                RepetitionKind::Range(RepetitionRange::Exactly(n))
+                | RepetitionKind::Range(RepetitionRange::AtLeast(n))
+                | RepetitionKind::Range(RepetitionRange::Bounded(n, _)) => n == 0,
+            };
+            (ranges, nullable || optional)
+        }
+        HirKind::Concat(ref regexes) => {
+            let mut ranges = vec![];
+            for regex in regexes {
+                let (first_ranges, nullable) = first(regex);
+                ranges.extend(first_ranges);
+                if !nullable {
+                    return (ranges, false);
+                }
+            }
+            (ranges, true)
+        }
+        HirKind::Alternation(ref regexes) => {
+            let mut ranges = vec![];
+            let mut nullable = false;
+            for regex in regexes {
+                let (first_ranges, first_nullable) = first(regex);
+                ranges.extend(first_ranges);
+                nullable |= first_nullable;
+            }
+            (ranges, nullable)
+        }
+    }
+}
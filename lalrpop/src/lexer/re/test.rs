@@ -9,3 +9,13 @@ fn parse_unclosed_group() {
 fn alt_oom() {
     parse_regex(r"(%%|[^%])+").unwrap();
 }
+
+#[test]
+fn first_chars_of_regex() {
+    let first = |s: &str| first_chars(&parse_regex(s).unwrap());
+    assert_eq!(first(r#""[^"]*""#), vec![('"', '"')]);
+    assert_eq!(first(r"[0-9]+|-[0-9]+"), vec![('-', '-'), ('0', '9')]);
+    assert_eq!(first(r"a?b"), vec![('a', 'b')]);
+    assert_eq!(first(r"(?:x{0,2})[a-c]"), vec![('a', 'c'), ('x', 'x')]);
+    assert_eq!(first_chars(&parse_literal("+=")), vec![('+', '+')]);
+}
@@ -19,6 +19,8 @@ extern crate petgraph;
 extern crate regex_syntax;
 extern crate regex;
 extern crate string_cache;
+#[cfg(feature = "syn")]
+extern crate syn;
 extern crate term;
 extern crate tiny_keccak;
 extern crate unicode_xid;
@@ -37,16 +39,22 @@ mod log;
 
 mod api;
 mod build;
+pub mod builder;
 mod collections;
+mod diagnostic;
 mod file_text;
 mod grammar;
+mod json;
 mod kernel_set;
 mod lexer;
 mod lr1;
 mod message;
+mod minimize;
 mod normalize;
 mod parser;
+mod railroad;
 mod session;
+mod source_map;
 mod tls;
 mod tok;
 mod util;
@@ -59,4 +67,12 @@ mod test_util;
 pub use crate::api::process_root;
 pub use crate::api::process_root_unconditionally;
 pub use crate::api::Configuration;
+pub use crate::build::{
+    ambiguous_sentences, build_tables, diff_grammars, grammar_fails_with, grammar_json,
+    railroad_diagrams, GrammarError,
+};
+pub use crate::diagnostic::{Diagnostic, Severity};
+pub use crate::minimize::minimize_grammar;
+pub use crate::session::{Edition, Profile};
+pub use crate::source_map::translate_locations;
 use ascii_canvas::style;
@@ -0,0 +1,197 @@
+//! Sentences that show the ambiguities of a grammar. The conflicts of
+//! a grammar that is ambiguous are reported with examples made of
+//! symbols, some of them nonterminals; here each nonterminal of such an
+//! example is replaced by the shortest input it derives, and each
+//! terminal by text that it matches, to give input that can be fed to
+//! the parser. This is mostly of use for grammars that accept their
+//! conflicts, with `#[backtrack]` or guards, to check that the parser
+//! picks the parse that was meant.
+
+use crate::collections::{map, Map};
+use crate::grammar::repr::*;
+use crate::lexer::nfa::NFA;
+use crate::lexer::re;
+use crate::lr1::core::LR1TableConstructionError;
+use crate::lr1::error::ambiguous_examples;
+use crate::lr1::example::{Example, ExampleSymbol};
+use crate::tls::Tls;
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+#[cfg(test)]
+mod test;
+
+/// Describes the ambiguities in the conflicts of `error`, found while
+/// building the parser for `user_nt`: for each, a sentence that can be
+/// parsed in two ways, and those two ways, with each reduction in
+/// brackets. Conflicts that are not ambiguities are listed as well.
+pub fn write_ambiguities(
+    out: &mut String,
+    grammar: &Grammar,
+    user_nt: &NonterminalString,
+    error: &LR1TableConstructionError,
+) {
+    let file_text = Tls::file_text();
+    let mut sentences = Sentences::new(grammar);
+    let mut seen = vec![];
+    for (production, examples) in ambiguous_examples(grammar, error) {
+        let location = file_text.location(production.span);
+        let (action, reduce) = match examples {
+            Some(examples) => examples,
+            None => {
+                let line = format!(
+                    "Conflict in `{}`, at {}: no ambiguous sentence found; the parser may \
+                     need more than one token of lookahead here.\n",
+                    user_nt, location
+                );
+                if !seen.contains(&line) {
+                    writeln!(out, "{}", line).unwrap();
+                    seen.push(line);
+                }
+                continue;
+            }
+        };
+        let sentence = sentences.sentence(&reduce);
+        let reduced = sentences.parse(&reduce);
+        let other = sentences.parse(&action);
+        let description = format!(
+            "Ambiguity in `{}`, at {}:\n    {}\ncan be parsed as\n    {}\nor as\n    {}\n",
+            user_nt, location, sentence, reduced, other
+        );
+        if !seen.contains(&description) {
+            writeln!(out, "{}", description).unwrap();
+            seen.push(description);
+        }
+    }
+}
+
+/// Turns the symbols of examples into input text.
+struct Sentences {
+    /// The shortest sentence that each nonterminal derives, if it
+    /// derives any.
+    shortest: Map<NonterminalString, Vec<TerminalString>>,
+
+    /// The text for each terminal.
+    texts: Map<TerminalString, String>,
+}
+
+impl Sentences {
+    fn new(grammar: &Grammar) -> Self {
+        // the shortest sentences only ever get shorter, so this
+        // settles once a round finds nothing shorter
+        let mut shortest: Map<NonterminalString, Vec<TerminalString>> = map();
+        loop {
+            let mut changed = false;
+            for (nonterminal, data) in &grammar.nonterminals {
+                for production in &data.productions {
+                    let sentence = production
+                        .symbols
+                        .iter()
+                        .map(|symbol| match *symbol {
+                            Symbol::Terminal(TerminalString::Error) => None,
+                            Symbol::Terminal(ref terminal) => Some(vec![terminal.clone()]),
+                            Symbol::Nonterminal(ref nonterminal) => {
+                                shortest.get(nonterminal).cloned()
+                            }
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .map(|parts| parts.concat());
+                    if let Some(sentence) = sentence {
+                        let shorter = shortest
+                            .get(nonterminal)
+                            .is_none_or(|old| sentence.len() < old.len());
+                        if shorter {
+                            shortest.insert(nonterminal.clone(), sentence);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        Sentences {
+            shortest,
+            texts: map(),
+        }
+    }
+
+    /// The text of each symbol of `example`; empty for the epsilon
+    /// symbols that stand for empty reductions.
+    fn texts(&mut self, example: &Example) -> Vec<Vec<String>> {
+        example
+            .symbols
+            .iter()
+            .map(|symbol| match *symbol {
+                ExampleSymbol::Symbol(Symbol::Terminal(ref terminal)) => vec![self.text(terminal)],
+                ExampleSymbol::Symbol(Symbol::Nonterminal(ref nonterminal)) => {
+                    match self.shortest.get(nonterminal).cloned() {
+                        Some(sentence) => sentence.iter().map(|t| self.text(t)).collect(),
+                        None => vec![format!("<{}>", nonterminal)],
+                    }
+                }
+                ExampleSymbol::Epsilon => vec![],
+            })
+            .collect()
+    }
+
+    /// The input of `example`, with its tokens separated by spaces.
+    fn sentence(&mut self, example: &Example) -> String {
+        self.texts(example).concat().join(" ")
+    }
+
+    /// The input of `example`, with each of its reductions in
+    /// brackets that start with the name of the nonterminal.
+    fn parse(&mut self, example: &Example) -> String {
+        let texts = self.texts(example);
+
+        // the reductions are nested, and listed from the smallest to
+        // the largest; of two that cover the same symbols, the one
+        // listed first is inside the other
+        let reductions: Vec<_> = example.reductions.iter().enumerate().collect();
+        let mut pieces: Vec<String> = vec![];
+        for (position, text) in texts.iter().enumerate().chain(Some((texts.len(), &vec![]))) {
+            let closing = reductions
+                .iter()
+                .filter(|&&(_, r)| r.end == position && r.start < position)
+                .count();
+            for _ in 0..closing {
+                match pieces.last_mut() {
+                    Some(piece) => piece.push(']'),
+                    None => pieces.push("]".to_string()),
+                }
+            }
+            let mut opening: Vec<_> = reductions
+                .iter()
+                .filter(|&&(_, r)| r.start == position && r.end > position)
+                .collect();
+            opening.sort_by_key(|&&(index, r)| Reverse((r.end, index)));
+            for &&(_, reduction) in &opening {
+                pieces.push(format!("[{}", reduction.nonterminal));
+            }
+            pieces.extend(text.iter().cloned());
+        }
+        pieces.join(" ")
+    }
+
+    /// Text that `terminal` matches: the literal itself, the shortest
+    /// match of a regex, and otherwise the name of the terminal.
+    fn text(&mut self, terminal: &TerminalString) -> String {
+        if let Some(text) = self.texts.get(terminal) {
+            return text.clone();
+        }
+        let text = match *terminal {
+            TerminalString::Literal(TerminalLiteral::Quoted(ref text)) => text.to_string(),
+            TerminalString::Literal(TerminalLiteral::Regex(ref regex)) => re::parse_regex(regex)
+                .ok()
+                .and_then(|regex| NFA::from_re(&regex).ok())
+                .and_then(|nfa| nfa.example())
+                .unwrap_or_else(|| terminal.to_string()),
+            TerminalString::Bare(_) | TerminalString::Error => terminal.to_string(),
+        };
+        self.texts.insert(terminal.clone(), text.clone());
+        text
+    }
+}
@@ -0,0 +1,55 @@
+use crate::build::ambiguous_sentences;
+
+#[test]
+fn backtracking_expressions() {
+    let text = ambiguous_sentences(
+        r#"
+#[backtrack] grammar;
+
+pub Expr: () = {
+    Expr "+" Expr,
+    r"[0-9]+",
+};
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        text,
+        r#"Ambiguity in `Expr`, at grammar:5:5:
+    0 + 0 + 0
+can be parsed as
+    [Expr [Expr 0 + 0] + 0]
+or as
+    [Expr 0 + [Expr 0 + 0]]
+
+"#
+    );
+}
+
+#[test]
+fn not_ambiguous() {
+    let text = ambiguous_sentences(
+        r#"
+grammar;
+
+pub S: () = {
+    A "x" "y",
+    B "x" "z",
+};
+A: () = "a";
+B: () = "a";
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        text,
+        "Conflict in `S`, at grammar:8:9: no ambiguous sentence found; the parser may \
+         need more than one token of lookahead here.\n\n"
+    );
+}
+
+#[test]
+fn no_conflicts() {
+    let text = ambiguous_sentences(r#"grammar; pub S: () = "a" S?;"#).unwrap();
+    assert_eq!(text, "");
+}
@@ -26,6 +26,11 @@ fn build_lr1_states_legacy<'grammar>(
 type ConstructionFunction<'grammar> =
     fn(&'grammar Grammar, NonterminalString) -> LR1Result<'grammar>;
 
+/// Whether `count` states are more than the session allows.
+pub fn exceeds_max_states(count: usize) -> bool {
+    Tls::session().max_states.is_some_and(|max| count > max)
+}
+
 pub fn use_lane_table() -> bool {
     match env::var("LALRPOP_LANE_TABLE") {
         Ok(ref s) => s != "disabled",
@@ -52,6 +57,20 @@ pub fn build_lr1_states<'grammar>(
     }
 }
 
+/// Builds the canonical LR(1) states, keeping any conflicts, for
+/// grammars that resolve them at runtime with `#[backtrack]`. Fails
+/// only if there are more states than `max_states` allows.
+pub fn build_conflicted_lr1_states<'grammar>(
+    grammar: &'grammar Grammar,
+    start: NonterminalString,
+) -> LR1Result<'grammar> {
+    let lr1: LR<'grammar, TokenSet> = LR::new(grammar, start, TokenSet::eof());
+    match lr1.build_states() {
+        Err(error) if error.exceeds_max_states() => Err(error),
+        Ok(states) | Err(TableConstructionError { states, .. }) => Ok(states),
+    }
+}
+
 pub fn build_lr0_states<'grammar>(
     grammar: &'grammar Grammar,
     start: NonterminalString,
@@ -169,6 +188,14 @@ impl<'grammar, L: LookaheadBuild> LR<'grammar, L> {
             // extract a new state
             states.push(this_state);
 
+            if exceeds_max_states(states.len()) {
+                log!(session, Verbose, "{} states created, stopping.", states.len());
+                return Err(TableConstructionError {
+                    states,
+                    conflicts: vec![],
+                });
+            }
+
             if self.permit_early_stop && session.stop_after(conflicts.len()) {
                 log!(
                     session,
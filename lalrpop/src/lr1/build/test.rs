@@ -168,6 +168,56 @@ grammar;
     random_test(&grammar, &states, nt("S"));
 }
 
+#[test]
+fn state_numbering() {
+    let _tls = Tls::test();
+
+    let grammar = normalized_grammar(
+        r#"
+grammar;
+    extern { enum Tok { "-" => .., "N" => .., "(" => .., ")" => .. } }
+
+    S: () =
+        E => ();
+
+    E: () = {
+        E "-" T => (),
+        T => ()
+    };
+
+    T: () = {
+        "N" => (),
+        "(" E ")" => ()
+    };
+"#,
+    );
+
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let states = crate::lr1::build_states(&grammar, nt("S")).unwrap();
+
+    // the states with gotos come first...
+    let with_gotos = states.iter().take_while(|s| !s.gotos.is_empty()).count();
+    assert!(states[with_gotos..].iter().all(|s| s.gotos.is_empty()));
+
+    // ...and each group is in breadth-first order from the initial state
+    let mut order = vec![StateIndex(0)];
+    let mut next = 0;
+    while next < order.len() {
+        let state = &states[order[next].0];
+        for &successor in state.shifts.values().chain(state.gotos.values()) {
+            if !order.contains(&successor) {
+                order.push(successor);
+            }
+        }
+        next += 1;
+    }
+    assert_eq!(order.len(), states.len());
+    let (mut first, mut second): (Vec<_>, Vec<_>) =
+        order.into_iter().partition(|s| s.0 < with_gotos);
+    first.append(&mut second);
+    assert_eq!(first, (0..states.len()).map(StateIndex).collect::<Vec<_>>());
+}
+
 #[test]
 fn shift_reduce_conflict1() {
     let _tls = Tls::test();
@@ -1,6 +1,7 @@
 //! Mega naive LALR(1) generation algorithm.
 
 use crate::collections::{map, Map, Multimap};
+use crate::diagnostic::{Diagnostic, Severity};
 use crate::grammar::repr::*;
 use crate::lr1::build;
 use crate::lr1::core::*;
@@ -33,8 +34,18 @@ pub fn build_lalr_states<'grammar>(
     // With lane table, there is no reason to do state collapse
     // for LALR. In fact, LALR is pointless!
     if build::use_lane_table() {
-        println!("Warning: Now that the new lane-table algorithm is the default,");
-        println!("         #[lalr] mode has no effect and can be removed.");
+        let reported = Tls::session().emit_diagnostic(Diagnostic {
+            severity: Severity::Warning,
+            file: Tls::file_text().path().to_path_buf(),
+            span: 0..0,
+            message: "now that the new lane-table algorithm is the default, \
+                      #[lalr] mode has no effect and can be removed"
+                .to_string(),
+        });
+        if !reported {
+            println!("Warning: Now that the new lane-table algorithm is the default,");
+            println!("         #[lalr] mode has no effect and can be removed.");
+        }
         return Ok(lr_states);
     }
 
@@ -11,11 +11,13 @@ use crate::lr1::core::*;
 use crate::lr1::lookahead::Token;
 use crate::lr1::state_graph::StateGraph;
 use crate::rust::RustWrite;
+use crate::source_map;
 use crate::tls::Tls;
 use crate::util::{Escape, Sep};
 use std::io::{self, Write};
 
-use super::base::CodeGenerator;
+use super::base::{CodeGenerator, CodegenOptions};
+use super::expected::ExpectedNames;
 
 pub fn compile<'grammar, W: Write>(
     grammar: &'grammar Grammar,
@@ -49,6 +51,10 @@ struct RecursiveAscent<'ascent, 'grammar> {
     nonterminal_type_params: Vec<TypeParameter>,
 
     nonterminal_where_clauses: Vec<WhereClause>,
+
+    /// the named nonterminals, which errors list in place of the
+    /// terminals that start them
+    expected_names: ExpectedNames,
 }
 
 /// Tracks the suffix of the stack (that is, top-most elements) that any
@@ -141,13 +147,16 @@ impl<'ascent, 'grammar, W: Write>
             start_symbol,
             states,
             out,
-            false,
-            action_module,
+            CodegenOptions {
+                action_module,
+                repeatable: false,
+            },
             RecursiveAscent {
                 graph,
                 state_inputs,
                 nonterminal_type_params,
                 nonterminal_where_clauses,
+                expected_names: ExpectedNames::new(grammar),
             },
         )
     }
@@ -214,10 +223,25 @@ impl<'ascent, 'grammar, W: Write>
     // input as `Foo`. An error is reported if the entire input is not
     // consumed.
     fn write_start_fn(&mut self) -> io::Result<()> {
-        let phantom_data = self.phantom_data_expr();
         self.start_parser_fn()?;
         self.define_tokens()?;
+        self.write_start_fn_body()?;
+
+        if self.grammar.intern_token.is_some() {
+            rust!(self.out, "}}"); // fn parse
+            rust!(self.out, "");
+            self.start_parse_tokens_method()?;
+            self.define_given_tokens()?;
+            self.write_start_fn_body()?;
+        }
+
+        self.end_parser_fn()
+    }
 
+    /// Writes the body of `parse` (and `parse_tokens`), once `tokens`
+    /// is defined.
+    fn write_start_fn_body(&mut self) -> io::Result<()> {
+        let phantom_data = self.phantom_data_expr();
         self.next_token("lookahead", "tokens")?;
         rust!(
             self.out,
@@ -253,8 +277,7 @@ impl<'ascent, 'grammar, W: Write>
         // nothing else should be possible
         rust!(self.out, "_ => unreachable!(),");
         rust!(self.out, "}}");
-
-        self.end_parser_fn()
+        Ok(())
     }
 
     /// Writes the function that corresponds to a given state. This
@@ -368,15 +391,27 @@ impl<'ascent, 'grammar, W: Write>
         // if we hit this, the next token is not recognized, so generate an error
         rust!(self.out, "_ => {{");
         // The terminals which would have resulted in a successful parse in this state
-        let successful_terminals = self.grammar.terminals.all.iter().filter(|&terminal| {
-            this_state.shifts.contains_key(terminal)
-                || this_state
-                    .reductions
-                    .iter()
-                    .any(|&(ref t, _)| t.contains(&Token::Terminal(terminal.clone())))
-        });
+        let successful_terminals: Vec<_> = self
+            .grammar
+            .terminals
+            .all
+            .iter()
+            .filter(|&terminal| {
+                this_state.shifts.contains_key(terminal)
+                    || this_state
+                        .reductions
+                        .iter()
+                        .any(|&(ref t, _)| t.contains(&Token::Terminal(terminal.clone())))
+            })
+            .cloned()
+            .collect();
+        let (names, successful_terminals) =
+            self.custom.expected_names.collapse(&successful_terminals);
 
         rust!(self.out, "let {}expected = alloc::vec![", self.prefix);
+        for name in names {
+            rust!(self.out, "{:?}.to_string(),", name);
+        }
         for terminal in successful_terminals {
             rust!(self.out, "r###\"{}\"###.to_string(),", terminal);
         }
@@ -404,19 +439,23 @@ impl<'ascent, 'grammar, W: Write>
         if fixed > 0 {
             rust!(
                 self.out,
-                "let {}location = {}sym{}.2.clone();",
+                "let {}location = {};",
                 self.prefix,
-                self.prefix,
-                stack_suffix.len() - 1
+                self.grammar.clone_location(&format!(
+                    "{}sym{}.2",
+                    self.prefix,
+                    stack_suffix.len() - 1
+                ))
             );
         } else if optional > 0 {
             rust!(self.out, "let {}location = ", self.prefix);
             for index in (0..optional).rev() {
                 rust!(
                     self.out,
-                    "{}sym{}.as_ref().map(|sym| sym.2.clone()).unwrap_or_else(|| {{",
+                    "{}sym{}.as_ref().map(|sym| {}).unwrap_or_else(|| {{",
                     self.prefix,
-                    index
+                    index,
+                    self.grammar.clone_location("sym.2")
                 );
             }
             rust!(self.out, "Default::default()");
@@ -839,6 +878,10 @@ impl<'ascent, 'grammar, W: Write>
     ) -> io::Result<()> {
         let loc_type = self.types.terminal_loc_type();
 
+        if Tls::session().emit_source_map {
+            rust!(self.out, "{}", source_map::marker(production.span));
+        }
+
         let (optional, fixed) = stack_suffix.optional_fixed_lens();
         let production_inputs = StackSuffix {
             all: &production.symbols,
@@ -853,11 +896,16 @@ impl<'ascent, 'grammar, W: Write>
         if let (Some(first_sym), Some(last_sym)) = (transfer_syms.first(), transfer_syms.last()) {
             rust!(
                 self.out,
-                "let {}start = {}.0.clone();",
+                "let {}start = {};",
+                self.prefix,
+                self.grammar.clone_location(&format!("{}.0", first_sym))
+            );
+            rust!(
+                self.out,
+                "let {}end = {};",
                 self.prefix,
-                first_sym
+                self.grammar.clone_location(&format!("{}.2", last_sym))
             );
-            rust!(self.out, "let {}end = {}.2.clone();", self.prefix, last_sym);
         } else if stack_suffix.len() > 0 {
             // we pop no symbols, so grab from the top of the stack
             // (unless we are in the start state)
@@ -865,17 +913,21 @@ impl<'ascent, 'grammar, W: Write>
             if !stack_suffix.fixed().is_empty() {
                 rust!(
                     self.out,
-                    "let {p}start = {p}lookahead.as_ref().map(|o| o.0.clone()).unwrap_or_else(|| {p}sym{top}.2.clone());",
+                    "let {p}start = {p}lookahead.as_ref().map(|o| {}).unwrap_or_else(|| {});",
+                    self.grammar.clone_location("o.0"),
+                    self.grammar
+                        .clone_location(&format!("{}sym{}.2", self.prefix, top)),
                     p = self.prefix,
-                    top = top
                 );
             } else {
                 // top of stack is optional; should not have been popped yet tho
                 rust!(
                     self.out,
-                    "let {p}start = {p}lookahead.as_ref().map(|o| o.0.clone()).unwrap_or_else(|| {p}sym{top}.as_ref().unwrap().2.clone());",
+                    "let {p}start = {p}lookahead.as_ref().map(|o| {}).unwrap_or_else(|| {});",
+                    self.grammar.clone_location("o.0"),
+                    self.grammar
+                        .clone_location(&format!("{}sym{}.as_ref().unwrap().2", self.prefix, top)),
                     p = self.prefix,
-                    top = top
                 );
             }
             rust!(self.out, "let {p}end = {p}start;", p = self.prefix);
@@ -1,6 +1,7 @@
 //! Base helper routines for a code generator.
 
 use crate::collections::Set;
+use crate::grammar::consts::{INPUT_LIFETIME, INPUT_PARAMETER, OWNED};
 use crate::grammar::free_variables::FreeVariables;
 use crate::grammar::repr::*;
 use crate::lr1::core::*;
@@ -43,6 +44,15 @@ pub struct CodeGenerator<'codegen, 'grammar: 'codegen, W: Write + 'codegen, C> {
     pub repeatable: bool,
 }
 
+/// How a `CodeGenerator` writes its code, apart from the grammar and
+/// its states.
+pub struct CodegenOptions<'a> {
+    /// where to find the action routines (typically `super`)
+    pub action_module: &'a str,
+
+    pub repeatable: bool,
+}
+
 impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
     pub fn new(
         grammar: &'grammar Grammar,
@@ -50,8 +60,7 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
         start_symbol: NonterminalString,
         states: &'codegen [LR1State<'grammar>],
         out: &'codegen mut RustWrite<W>,
-        repeatable: bool,
-        action_module: &str,
+        options: CodegenOptions<'_>,
         custom: C,
     ) -> Self {
         CodeGenerator {
@@ -63,8 +72,8 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
             start_symbol,
             out,
             custom,
-            repeatable,
-            action_module: action_module.to_string(),
+            repeatable: options.repeatable,
+            action_module: options.action_module.to_string(),
         }
     }
 
@@ -200,48 +209,13 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
     }
 
     pub fn start_parser_fn(&mut self) -> io::Result<()> {
-        let parse_error_type = self.types.parse_error_type();
-
-        let (type_parameters, parameters, mut where_clauses);
-
-        let intern_token = self.grammar.intern_token.is_some();
-        if intern_token {
-            // if we are generating the tokenizer, we just need the
-            // input, and that has already been added as one of the
-            // user parameters
-            type_parameters = vec![];
-            parameters = vec![];
-            where_clauses = vec![];
-        } else {
-            // otherwise, we need an iterator of type `TOKENS`
-            let mut user_type_parameters = String::new();
-            for type_parameter in &self.grammar.type_parameters {
-                user_type_parameters.push_str(&format!("{}, ", type_parameter));
-            }
-            type_parameters = vec![
-                format!(
-                    "{}TOKEN: {}ToTriple<{}>",
-                    self.prefix, self.prefix, user_type_parameters,
-                ),
-                format!(
-                    "{}TOKENS: IntoIterator<Item={}TOKEN>",
-                    self.prefix, self.prefix
-                ),
-            ];
-            parameters = vec![format!("{}tokens0: {}TOKENS", self.prefix, self.prefix)];
-            where_clauses = vec![];
-
-            if self.repeatable {
-                where_clauses.push(format!("{}TOKENS: Clone", self.prefix));
-            }
-        }
-
         rust!(
             self.out,
             "{}struct {}Parser {{",
             self.grammar.nonterminals[&self.start_symbol].visibility,
             self.user_start_symbol
         );
+        let intern_token = self.grammar.intern_token.is_some();
         if intern_token {
             rust!(
                 self.out,
@@ -277,28 +251,194 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
         rust!(self.out, "}}"); // new()
         rust!(self.out, "");
 
+        if intern_token {
+            self.write_lexer_fn()?;
+        }
+
+        let success_type = self.types.nonterminal_type(&self.start_symbol).to_string();
+        self.start_parse_method("parse", vec![], success_type)
+    }
+
+    /// Emits the header of a method on the `Parser` struct that takes
+    /// the grammar parameters, then the input, then `extra_parameters`,
+    /// and returns `success_type` or a parse error. `start_parser_fn`
+    /// uses this for `parse`; backends may use it for further entry
+    /// points.
+    pub fn start_parse_method(
+        &mut self,
+        name: &str,
+        extra_parameters: Vec<String>,
+        success_type: String,
+    ) -> io::Result<()> {
+        let return_type = format!(
+            "Result<{}, {}>",
+            success_type,
+            self.types.parse_error_type()
+        );
+        self.start_parse_method_returning(name, extra_parameters, return_type)
+    }
+
+    /// Like `start_parse_method`, for a method that returns
+    /// `return_type`.
+    pub fn start_parse_method_returning(
+        &mut self,
+        name: &str,
+        extra_parameters: Vec<String>,
+        return_type: String,
+    ) -> io::Result<()> {
+        let (type_parameters, parameters, where_clauses) =
+            Self::token_parameters(self.grammar, self.repeatable);
+
         rust!(self.out, "#[allow(dead_code)]");
         self.out
             .fn_header(
                 &self.grammar.nonterminals[&self.start_symbol].visibility,
-                "parse".to_owned(),
+                name.to_owned(),
             )
             .with_parameters(Some("&self".to_owned()))
             .with_grammar(self.grammar)
             .with_type_parameters(type_parameters)
             .with_parameters(parameters)
+            .with_parameters(extra_parameters)
+            .with_return_type(return_type)
+            .with_where_clauses(where_clauses)
+            .emit()?;
+        rust!(self.out, "{{");
+
+        Ok(())
+    }
+
+    /// Emits `lexer`, which returns the tokenizer that `parse` runs on
+    /// `input`, for callers that want to look at or adjust the tokens
+    /// before handing them to `parse_tokens`.
+    fn write_lexer_fn(&mut self) -> io::Result<()> {
+        let error_type = self.types.error_type();
+        let input_type = self
+            .grammar
+            .parameters
+            .iter()
+            .find(|parameter| &*parameter.name == INPUT_PARAMETER)
+            .map(|parameter| parameter.ty.clone())
+            .expect("no `input` parameter with an internal tokenizer");
+        let (type_parameters, mut where_clauses) = Self::filter_type_parameters_and_where_clauses(
+            self.grammar,
+            vec![input_type.clone(), error_type.clone()],
+        );
+        // a bound like `T::Err: Debug` has no free variables, but is not
+        // well-formed without `T`
+        where_clauses.retain(|wc| !wc.free_variables(&type_parameters).is_empty());
+        rust!(self.out, "#[allow(dead_code)]");
+        self.out
+            .fn_header(
+                &self.grammar.nonterminals[&self.start_symbol].visibility,
+                "lexer".to_owned(),
+            )
+            .with_type_parameters(type_parameters)
+            .with_parameters(Some("&self".to_owned()))
+            .with_parameters(Some(format!("input: {}", input_type)))
             .with_return_type(format!(
-                "Result<{}, {}>",
-                self.types.nonterminal_type(&self.start_symbol),
-                parse_error_type
+                "{}lalrpop_util::lexer::Matcher<{}, '_, {}>",
+                self.prefix, INPUT_LIFETIME, error_type
             ))
             .with_where_clauses(where_clauses)
             .emit()?;
         rust!(self.out, "{{");
+        rust!(self.out, "self.builder.matcher(input)");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        Ok(())
+    }
+
+    /// Emits the header of `parse_tokens`, which, when we generate the
+    /// tokenizer, parses tokens the caller supplies instead (from
+    /// `lexer` or a lexer of their own). These may be bare triples or
+    /// `Result`s of them, as `lalrpop_util::lexer::ToTriple` allows.
+    /// `input` is still taken, as action code may refer to it.
+    pub fn start_parse_tokens_method(&mut self) -> io::Result<()> {
+        let success_type = self.types.nonterminal_type(&self.start_symbol).to_string();
+        let parse_error_type = self.types.parse_error_type();
+        let mut where_clauses = vec![];
+        if self.repeatable {
+            where_clauses.push(format!("{}TOKENS: Clone", self.prefix));
+        }
+
+        rust!(self.out, "#[allow(dead_code)]");
+        self.out
+            .fn_header(
+                &self.grammar.nonterminals[&self.start_symbol].visibility,
+                "parse_tokens".to_owned(),
+            )
+            .with_parameters(Some("&self".to_owned()))
+            .with_grammar(self.grammar)
+            .with_type_parameters(vec![
+                format!(
+                    "{p}TOKEN: {p}lalrpop_util::lexer::ToTriple<{l}, {e}>",
+                    p = self.prefix,
+                    l = INPUT_LIFETIME,
+                    e = self.types.error_type()
+                ),
+                format!("{p}TOKENS: IntoIterator<Item={p}TOKEN>", p = self.prefix),
+            ])
+            .with_parameters(Some(format!(
+                "{}tokens0: {}TOKENS",
+                self.prefix, self.prefix
+            )))
+            .with_return_type(format!("Result<{}, {}>", success_type, parse_error_type))
+            .with_where_clauses(where_clauses)
+            .emit()?;
+        rust!(self.out, "{{");
+        Ok(())
+    }
 
+    /// Defines `tokens` from the tokens given to `parse_tokens`.
+    pub fn define_given_tokens(&mut self) -> io::Result<()> {
+        rust!(
+            self.out,
+            "let mut {p}tokens = {p}tokens0.into_iter().map({p}lalrpop_util::lexer::ToTriple::to_triple);",
+            p = self.prefix
+        );
         Ok(())
     }
 
+    /// The type parameters, parameters and where-clauses that a parse
+    /// method takes for its tokens, after the grammar parameters. This
+    /// is an associated fn so that the `parse` fn of `#[runtime_start]`
+    /// can take the same.
+    pub fn token_parameters(
+        grammar: &Grammar,
+        repeatable: bool,
+    ) -> (Vec<String>, Vec<String>, Vec<String>) {
+        let prefix = &grammar.prefix;
+        if grammar.intern_token.is_some() {
+            // if we are generating the tokenizer, we just need the
+            // input, and that has already been added as one of the
+            // user parameters
+            return (vec![], vec![], vec![]);
+        }
+
+        // otherwise, we need an iterator of type `TOKENS`
+        let type_parameters = vec![
+            format!("{}TOKEN: {}", prefix, Self::to_triple_trait(grammar)),
+            format!("{}TOKENS: IntoIterator<Item={}TOKEN>", prefix, prefix),
+        ];
+        let parameters = vec![format!("{}tokens0: {}TOKENS", prefix, prefix)];
+        let mut where_clauses = vec![];
+        if repeatable {
+            where_clauses.push(format!("{}TOKENS: Clone", prefix));
+        }
+        (type_parameters, parameters, where_clauses)
+    }
+
+    /// The `ToTriple` trait that the tokens given to `parse` must
+    /// implement, when we do not generate the tokenizer.
+    pub fn to_triple_trait(grammar: &Grammar) -> String {
+        let mut user_type_parameters = String::new();
+        for type_parameter in &grammar.type_parameters {
+            user_type_parameters.push_str(&format!("{}, ", type_parameter));
+        }
+        format!("{}ToTriple<{}>", grammar.prefix, user_type_parameters)
+    }
+
     pub fn define_tokens(&mut self) -> io::Result<()> {
         if self.grammar.intern_token.is_some() {
             // if we are generating the tokenizer, create a matcher as our input iterator
@@ -334,10 +474,96 @@ impl<'codegen, 'grammar, W: Write, C> CodeGenerator<'codegen, 'grammar, W, C> {
 
     pub fn end_parser_fn(&mut self) -> io::Result<()> {
         rust!(self.out, "}}"); // fn
+        if self.parses_owned() {
+            rust!(self.out, "");
+            self.write_parse_owned_fn()?;
+        }
         rust!(self.out, "}}"); // impl
         Ok(())
     }
 
+    /// True if the user asked for a `parse_owned` entry point for this
+    /// start symbol.
+    fn parses_owned(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *OWNED)
+    }
+
+    /// Emits `parse_owned`, which takes the input as a `String` and
+    /// hands it to `parse`. The tokens in an error are turned into the
+    /// text they matched, so that neither the result nor the error
+    /// borrows the input, and the caller can drop it. Type checking
+    /// has made sure that the type of the start symbol does not borrow
+    /// it either.
+    fn write_parse_owned_fn(&mut self) -> io::Result<()> {
+        let input_lifetime = TypeParameter::Lifetime(Lifetime::input());
+        let type_parameters: Vec<_> = self
+            .grammar
+            .type_parameters
+            .iter()
+            .filter(|tp| **tp != input_lifetime)
+            .cloned()
+            .collect();
+        let where_clauses: Vec<_> = self
+            .grammar
+            .where_clauses
+            .iter()
+            .filter(|wc| {
+                !wc.free_variables(&self.grammar.type_parameters)
+                    .contains(&input_lifetime)
+            })
+            .cloned()
+            .collect();
+        let parameters: Vec<_> = self
+            .grammar
+            .parameters
+            .iter()
+            .filter(|parameter| &*parameter.name != INPUT_PARAMETER)
+            .collect();
+        let arguments: Vec<_> = self
+            .grammar
+            .parameters
+            .iter()
+            .map(|parameter| {
+                if &*parameter.name == INPUT_PARAMETER {
+                    "&input".to_string()
+                } else {
+                    parameter.name.to_string()
+                }
+            })
+            .collect();
+        let return_type = format!(
+            "Result<{}, {}lalrpop_util::ParseError<usize, alloc::string::String, {}>>",
+            self.types.nonterminal_type(&self.start_symbol),
+            self.prefix,
+            self.types.error_type()
+        );
+
+        rust!(self.out, "#[allow(dead_code)]");
+        self.out
+            .fn_header(
+                &self.grammar.nonterminals[&self.start_symbol].visibility,
+                "parse_owned".to_owned(),
+            )
+            .with_type_parameters(type_parameters)
+            .with_parameters(Some("&self".to_owned()))
+            .with_parameters(parameters)
+            .with_parameters(Some("input: alloc::string::String".to_owned()))
+            .with_return_type(return_type)
+            .with_where_clauses(where_clauses)
+            .emit()?;
+        rust!(self.out, "{{");
+        rust!(
+            self.out,
+            "self.parse({}).map_err(|e| e.map_token(|t| alloc::string::String::from(t.1)))",
+            Sep(", ", &arguments)
+        );
+        rust!(self.out, "}}"); // fn
+        Ok(())
+    }
+
     /// Returns phantom data type that captures the user-declared type
     /// parameters in a phantom-data. This helps with ensuring that
     /// all type parameters are constrained, even if they are not
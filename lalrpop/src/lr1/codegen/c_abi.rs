@@ -0,0 +1,101 @@
+//! Emits the `extern "C"` functions of `Configuration::emit_c_abi`: one
+//! that parses a byte buffer for each public nonterminal, and those
+//! that read and free the handle it returns, all named after the
+//! grammar, like `calc_expr_parse` and `calc_free` for `calc.lalrpop`:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub unsafe extern "C" fn calc_expr_parse(
+//!     input: *const u8,
+//!     len: usize,
+//! ) -> *mut __lalrpop_util::ffi::ParseHandle {
+//!     __lalrpop_util::ffi::parse(input, len, |input| ExprParser::new().parse(input))
+//! }
+//! ```
+//!
+//! Grammars with an external lexer, parameters or type parameters get
+//! none, since there is no way to call their `parse` from a buffer.
+
+use crate::grammar::parse_tree::TypeParameter;
+use crate::grammar::repr::Grammar;
+use crate::rust::RustWrite;
+use std::io::{self, Write};
+
+pub fn compile<W: Write>(grammar: &Grammar, name: &str, out: &mut RustWrite<W>) -> io::Result<()> {
+    let callable = grammar.intern_token.is_some()
+        && grammar.parameters.len() == 1
+        && grammar
+            .type_parameters
+            .iter()
+            .all(|tp| matches!(tp, TypeParameter::Lifetime(_)));
+    if !callable {
+        return Ok(());
+    }
+
+    let prefix = &grammar.prefix;
+    let name = symbol_name(name);
+    let handle = format!("{}lalrpop_util::ffi::ParseHandle", prefix);
+
+    for user_nt in grammar.start_nonterminals.keys() {
+        rust!(out, "");
+        rust!(out, "#[no_mangle]");
+        rust!(
+            out,
+            "pub unsafe extern \"C\" fn {}_{}_parse(input: *const u8, len: usize) -> *mut {} {{",
+            name,
+            symbol_name(&user_nt.to_string()),
+            handle
+        );
+        rust!(
+            out,
+            "{}lalrpop_util::ffi::parse(input, len, |input| {}Parser::new().parse(input))",
+            prefix,
+            user_nt
+        );
+        rust!(out, "}}");
+    }
+
+    let accessors = [
+        ("ok", "const", "bool"),
+        ("value", "const", "*const ::std::os::raw::c_void"),
+        ("error", "const", "*const ::std::os::raw::c_char"),
+        ("error_start", "const", "usize"),
+        ("error_end", "const", "usize"),
+        ("free", "mut", "()"),
+    ];
+    for (accessor, mutability, return_type) in &accessors {
+        rust!(out, "");
+        rust!(out, "#[no_mangle]");
+        rust!(
+            out,
+            "pub unsafe extern \"C\" fn {}_{}(handle: *{} {}) -> {} {{",
+            name,
+            accessor,
+            mutability,
+            handle,
+            return_type
+        );
+        rust!(out, "{}lalrpop_util::ffi::{}(handle)", prefix, accessor);
+        rust!(out, "}}");
+    }
+    Ok(())
+}
+
+/// `name` in snake case, with anything but letters and digits replaced
+/// by `_`, so `MyExpr` becomes `my_expr` and `Expr+` becomes `expr_`.
+fn symbol_name(name: &str) -> String {
+    let mut symbol = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !symbol.ends_with('_') {
+                symbol.push('_');
+            }
+            symbol.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            symbol.push(c);
+        } else {
+            symbol.push('_');
+        }
+    }
+    symbol
+}
@@ -0,0 +1,96 @@
+//! The names that errors give for what the parser expected. A
+//! nonterminal marked `#[expected(name = "an expression")]` stands for
+//! all the terminals that can start it, so that an error can say it
+//! expected "an expression" rather than listing each of them.
+
+use crate::collections::{set, Set};
+use crate::grammar::consts::{EXPECTED, EXPECTED_NAME_ARG};
+use crate::grammar::repr::{Grammar, TerminalString};
+use crate::lr1::first_follow_sets;
+
+pub struct ExpectedNames {
+    /// The name of each named nonterminal, and the terminals that can
+    /// start it.
+    named: Vec<(String, Set<TerminalString>)>,
+}
+
+impl ExpectedNames {
+    pub fn new(grammar: &Grammar) -> Self {
+        let names: Vec<_> = grammar
+            .nonterminals
+            .iter()
+            .filter_map(|(nonterminal, data)| {
+                let annotation = data.annotations.iter().find(|a| a.id == *EXPECTED)?;
+                match annotation.arg {
+                    Some((ref arg, ref name)) if *arg == *EXPECTED_NAME_ARG => {
+                        Some((nonterminal.clone(), name.clone()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        if names.is_empty() {
+            return ExpectedNames { named: vec![] };
+        }
+
+        let first_follow = first_follow_sets(grammar);
+        let named = names
+            .into_iter()
+            .map(|(nonterminal, name)| {
+                let first = first_follow[&nonterminal].first.iter().cloned().collect();
+                (name, first)
+            })
+            .collect();
+        ExpectedNames { named }
+    }
+
+    /// True if no nonterminal has a name, so that errors list the
+    /// terminals alone.
+    pub fn is_empty(&self) -> bool {
+        self.named.is_empty()
+    }
+
+    /// Splits `expected`, the terminals that a state accepts, into the
+    /// names of the nonterminals that cover them and the terminals that
+    /// are left. A nonterminal covers the terminals that can start it
+    /// when the state accepts all of them; the state need not go to it,
+    /// as when it is about to reduce what comes before. The names are
+    /// picked greedily, the one covering the most terminals not yet
+    /// covered first, which gives a short list if not always the
+    /// shortest one.
+    pub fn collapse(&self, expected: &[TerminalString]) -> (Vec<String>, Vec<TerminalString>) {
+        let accepted: Set<_> = expected.iter().cloned().collect();
+        let mut candidates: Vec<_> = self
+            .named
+            .iter()
+            .filter(|&&(_, ref first)| first.is_subset(&accepted))
+            .collect();
+
+        let mut covered = set();
+        let mut names = vec![];
+        loop {
+            let best = candidates
+                .iter()
+                .enumerate()
+                .map(|(index, &&(_, ref first))| (first.difference(&covered).count(), index))
+                .max_by_key(|&(count, index)| (count, std::cmp::Reverse(index)));
+            match best {
+                Some((count, index)) if count > 0 => {
+                    let (ref name, ref first) = *candidates.remove(index);
+                    covered.extend(first.iter().cloned());
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let rest = expected
+            .iter()
+            .filter(|terminal| !covered.contains(*terminal))
+            .cloned()
+            .collect();
+        (names, rest)
+    }
+}
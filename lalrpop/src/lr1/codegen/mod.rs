@@ -1,4 +1,8 @@
 pub mod ascent;
 mod base;
+mod expected;
+pub mod c_abi;
 pub mod parse_table;
+pub mod parser_trait;
+pub mod start_symbol;
 pub mod test_all;
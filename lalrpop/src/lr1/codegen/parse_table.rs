@@ -1,10 +1,18 @@
 //! A compiler from an LR(1) table to a traditional table driven parser.
 
-use crate::collections::{Entry, Map, Set};
+use crate::collections::{Map, Set};
+use crate::grammar::consts::{
+    CANCELLABLE, CLONEABLE, COMPLETIONS, EVENTS, INPUT_LIFETIME, ISLAND, PREFIX, RECOGNIZE,
+    RESUMABLE,
+};
+use crate::grammar::parse_tree::MatchMapping;
+use crate::grammar::pattern::PatternKind;
 use crate::grammar::repr::*;
 use crate::lr1::core::*;
 use crate::lr1::lookahead::Token;
 use crate::rust::RustWrite;
+use crate::session::Profile;
+use crate::source_map;
 use crate::tls::Tls;
 use crate::util::Sep;
 use itertools::Itertools;
@@ -12,27 +20,64 @@ use std::fmt;
 use std::io::{self, Write};
 use std::rc::Rc;
 use string_cache::DefaultAtom as Atom;
+use tiny_keccak::{Hasher, Sha3};
 
-use super::base::CodeGenerator;
+use super::base::{CodeGenerator, CodegenOptions};
+use super::expected::ExpectedNames;
 
 const DEBUG_PRINT: bool = false;
 
+/// How `compile` writes a table-driven parser, apart from the grammar
+/// and its states.
+pub struct TableOptions<'a, 'grammar, W: Write> {
+    /// where to find the action routines (typically `super`)
+    pub action_module: &'a str,
+
+    /// the states that have a message from the `.messages` file
+    pub error_messages: &'a [(StateIndex, &'grammar str)],
+
+    /// where to write the tables, if to a file of their own (see
+    /// `write_tables_mod`)
+    pub tables: Option<&'a mut RustWrite<W>>,
+}
+
 pub fn compile<'grammar, W: Write>(
     grammar: &'grammar Grammar,
     user_start_symbol: NonterminalString,
     start_symbol: NonterminalString,
     states: &[LR1State<'grammar>],
-    action_module: &str,
     out: &mut RustWrite<W>,
+    options: TableOptions<'_, 'grammar, W>,
 ) -> io::Result<()> {
+    let TableOptions {
+        action_module,
+        error_messages,
+        tables,
+    } = options;
+    let split_tables = tables.is_some();
+    if let Some(tables) = tables {
+        let mut table_driven = CodeGenerator::new_table_driven(
+            grammar,
+            user_start_symbol.clone(),
+            start_symbol.clone(),
+            states,
+            error_messages,
+            action_module,
+            tables,
+        );
+        table_driven.write_tables_mod()?;
+    }
+
     let mut table_driven = CodeGenerator::new_table_driven(
         grammar,
         user_start_symbol,
         start_symbol,
         states,
+        error_messages,
         action_module,
         out,
     );
+    table_driven.custom.split_tables = split_tables;
     table_driven.write()
 }
 
@@ -74,6 +119,16 @@ struct TableDriven<'grammar> {
     variant_names: Map<Symbol, String>,
     variants: Map<TypeRepr, String>,
     reduce_functions: Set<usize>,
+
+    /// the states that have a message from the `.messages` file
+    error_messages: Vec<(StateIndex, &'grammar str)>,
+
+    /// true if the tables are in a module of their own, written to
+    /// another file (see `write_tables_mod`)
+    split_tables: bool,
+
+    /// the visibility of the functions that look up the tables
+    table_visibility: &'static str,
 }
 
 impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDriven<'grammar>> {
@@ -82,6 +137,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         user_start_symbol: NonterminalString,
         start_symbol: NonterminalString,
         states: &'ascent [LR1State<'grammar>],
+        error_messages: &[(StateIndex, &'grammar str)],
         action_module: &str,
         out: &'ascent mut RustWrite<W>,
     ) -> Self {
@@ -98,7 +154,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         let machine = Rc::new(MachineParameters::new(grammar));
 
         // Assign each production a unique index to use as the values for reduce
-        // actions in the ACTION and EOF_ACTION tables.
+        // actions in the ACTION table.
         let reduce_indices: Map<&'grammar Production, usize> = grammar
             .nonterminals
             .values()
@@ -125,8 +181,10 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             start_symbol,
             states,
             out,
-            false,
-            action_module,
+            CodegenOptions {
+                action_module,
+                repeatable: false,
+            },
             TableDriven {
                 symbol_type_params,
                 symbol_where_clauses,
@@ -137,6 +195,9 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
                 variant_names: Map::new(),
                 variants: Map::new(),
                 reduce_functions: Set::new(),
+                error_messages: error_messages.to_vec(),
+                split_tables: false,
+                table_visibility: "",
             },
         )
     }
@@ -144,15 +205,27 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
     fn write(&mut self) -> io::Result<()> {
         self.write_parse_mod(|this| {
             this.write_value_type_defn()?;
-            this.write_parse_table()?;
+            if this.custom.split_tables {
+                rust!(
+                    this.out,
+                    "use super::{}tables{}::*;",
+                    this.prefix,
+                    this.start_symbol
+                );
+            } else {
+                this.write_parse_table()?;
+            }
             this.write_machine_definition()?;
             this.write_token_to_integer_fn()?;
             this.write_token_to_symbol_fn()?;
             this.write_simulate_reduce_fn()?;
+            this.write_guard_fn()?;
+            this.write_reduction_names()?;
+            this.write_repair_tokens_fn()?;
             this.write_parser_fn()?;
             this.write_accepts_fn()?;
+            this.emit_pop_macro()?;
             this.emit_reduce_actions()?;
-            this.emit_downcast_fns()?;
             this.emit_reduce_action_functions()?;
             Ok(())
         })
@@ -171,6 +244,9 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         let machine_type_parameters = Sep(", ", &machine.type_parameters);
         let machine_where_clauses = Sep(", ", &machine.where_clauses);
 
+        if self.is_cloneable() {
+            rust!(self.out, "#[derive(Clone)]");
+        }
         rust!(
             self.out,
             "pub(crate) struct {p}StateMachine<{mtp}>",
@@ -212,6 +288,12 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         rust!(self.out, "type Action = {t};", t = state_type);
         rust!(self.out, "type ReduceIndex = {t};", t = state_type);
         rust!(self.out, "type NonterminalIndex = usize;");
+        rust!(
+            self.out,
+            "type Stack<{p}T> = {};",
+            self.stack_type(format!("{}T", self.prefix)),
+            p = self.prefix,
+        );
 
         rust!(self.out, "");
         rust!(self.out, "#[inline]");
@@ -231,12 +313,21 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             self.out,
             "fn token_to_index(&self, token: &Self::Token) -> Option<usize> {{"
         );
-        rust!(
-            self.out,
-            "{p}token_to_integer(token, {phantom})",
-            p = self.prefix,
-            phantom = phantom_data_expr,
-        );
+        if self.grammar.intern_token.is_some() {
+            rust!(
+                self.out,
+                "{}::{p}intern_token::token_to_integer(token)",
+                self.action_module,
+                p = self.prefix,
+            );
+        } else {
+            rust!(
+                self.out,
+                "{p}token_to_integer(token, {phantom})",
+                p = self.prefix,
+                phantom = phantom_data_expr,
+            );
+        }
         rust!(self.out, "}}");
 
         rust!(self.out, "");
@@ -271,7 +362,12 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             "fn eof_action(&self, state: {state_type}) -> {state_type} {{",
             state_type = state_type,
         );
-        rust!(self.out, "{p}EOF_ACTION[state as usize]", p = self.prefix,);
+        rust!(
+            self.out,
+            "{p}action(state, {num_term})",
+            p = self.prefix,
+            num_term = self.grammar.terminals.all.len(),
+        );
         rust!(self.out, "}}");
 
         rust!(self.out, "");
@@ -287,17 +383,18 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         rust!(self.out, "");
         rust!(
             self.out,
-            "fn token_to_symbol(&self, token_index: usize, token: Self::Token) -> Self::Symbol {{"
+            "fn token_to_symbol(&self, _: usize, token: Self::Token) -> Self::Symbol {{"
         );
         rust!(
             self.out,
-            "{p}token_to_symbol(token_index, token, {phantom})",
+            "{p}token_to_symbol(token, {phantom})",
             p = self.prefix,
             phantom = phantom_data_expr,
         );
         rust!(self.out, "}}");
 
         rust!(self.out, "");
+        rust!(self.out, "#[cold]");
         rust!(
             self.out,
             "fn expected_tokens(&self, state: {state_type}) -> alloc::vec::Vec<alloc::string::String> {{",
@@ -313,7 +410,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         rust!(self.out, "}}");
 
         rust!(self.out, "");
-        rust!(self.out, "#[inline]");
+        rust!(self.out, "#[cold]");
         rust!(self.out, "fn error_recovery_symbol(");
         rust!(self.out, "&self,");
         rust!(
@@ -332,10 +429,8 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
                 e = error_variant
             );
         } else {
-            rust!(
-                self.out,
-                "panic!(\"error recovery not enabled for this grammar\")"
-            )
+            let panic = self.unreachable("panic!(\"error recovery not enabled for this grammar\")");
+            rust!(self.out, "{}", panic)
         }
         rust!(self.out, "}}");
 
@@ -344,21 +439,29 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         rust!(self.out, "&mut self,");
         rust!(self.out, "action: {state_type},", state_type = state_type);
         rust!(self.out, "start_location: Option<&Self::Location>,");
+        rust!(self.out, "states: &mut {},", self.stack_type(state_type));
         rust!(
             self.out,
-            "states: &mut alloc::vec::Vec<{state_type}>,",
-            state_type = state_type
-        );
-        rust!(
-            self.out,
-            "symbols: &mut alloc::vec::Vec<{p}state_machine::SymbolTriple<Self>>,",
-            p = self.prefix,
+            "symbols: &mut {},",
+            self.stack_type(format!("{}state_machine::SymbolTriple<Self>", self.prefix)),
         );
         rust!(
             self.out,
             ") -> Option<{p}state_machine::ParseResult<Self>> {{",
             p = self.prefix,
         );
+        let unchecked_downcasts = Tls::session().unchecked_downcasts;
+        if unchecked_downcasts {
+            rust!(
+                self.out,
+                "// SAFETY: the parser reduces `action` only when the symbols on top of"
+            );
+            rust!(
+                self.out,
+                "// `symbols` are the ones its states say, which have the types popped."
+            );
+            rust!(self.out, "unsafe {{");
+        }
         rust!(self.out, "{p}reduce(", p = self.prefix);
         for Parameter { name, .. } in self.grammar.parameters.iter() {
             rust!(self.out, "self.{},", name);
@@ -369,6 +472,9 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         rust!(self.out, "symbols,");
         rust!(self.out, "{},", phantom_data_expr);
         rust!(self.out, ")");
+        if unchecked_downcasts {
+            rust!(self.out, "}}");
+        }
         rust!(self.out, "}}");
 
         rust!(self.out, "");
@@ -378,30 +484,222 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             p = self.prefix,
             state_type = state_type,
         );
-        if self.grammar.uses_error_recovery {
+        if self.simulates_reduce() {
+            rust!(
+                self.out,
+                "match {p}simulate_reduction(action) {{",
+                p = self.prefix
+            );
+            rust!(
+                self.out,
+                "Some((states_to_pop, nonterminal_produced)) => \
+                 {p}state_machine::SimulatedReduce::Reduce {{ states_to_pop, nonterminal_produced }},",
+                p = self.prefix
+            );
+            rust!(
+                self.out,
+                "None => {p}state_machine::SimulatedReduce::Accept,",
+                p = self.prefix
+            );
+            rust!(self.out, "}}");
+        } else {
+            let panic = self.unreachable("panic!(\"error recovery not enabled for this grammar\")");
+            rust!(self.out, "{}", panic)
+        }
+        rust!(self.out, "}}");
+
+        if self.grammar.repair.is_some() {
+            rust!(self.out, "");
+            rust!(
+                self.out,
+                "fn repair_tokens(&self, state: {state_type}) -> alloc::vec::Vec<(usize, Self::Token)> {{",
+                state_type = state_type,
+            );
             rust!(
                 self.out,
-                "{p}simulate_reduce(action, {phantom})",
+                "{p}repair_tokens(state, {phantom})",
                 p = self.prefix,
                 phantom = phantom_data_expr,
             );
-        } else {
+            rust!(self.out, "}}");
+        }
+
+        if let Some(depth) = Tls::session().max_stack_depth {
+            rust!(self.out, "");
+            rust!(self.out, "#[inline]");
+            rust!(self.out, "fn max_stack_depth(&self) -> Option<usize> {{");
+            rust!(self.out, "Some({})", depth);
+            rust!(self.out, "}}");
+
+            rust!(self.out, "");
+            rust!(self.out, "#[cold]");
             rust!(
                 self.out,
-                "panic!(\"error recovery not enabled for this grammar\")"
-            )
+                "fn stack_overflow(&self, location: Self::Location) -> Self::Error {{"
+            );
+            rust!(
+                self.out,
+                "From::from({}lalrpop_util::StackOverflow {{ location }})",
+                self.prefix
+            );
+            rust!(self.out, "}}");
+        }
+
+        if !self.custom.error_messages.is_empty() {
+            rust!(self.out, "");
+            rust!(self.out, "#[cold]");
+            rust!(
+                self.out,
+                "fn error_message(&self, state: {state_type}) -> Option<&'static str> {{",
+                state_type = state_type,
+            );
+            rust!(self.out, "match state {{");
+            for &(state, message) in &self.custom.error_messages {
+                rust!(self.out, "{} => Some({:?}),", state.0, message);
+            }
+            rust!(self.out, "_ => None,");
+            rust!(self.out, "}}");
+            rust!(self.out, "}}");
+        }
+
+        let strategy = match self.grammar.repair {
+            Some(RepairStrategy::BurkeFisher) => Some("BurkeFisher"),
+            Some(RepairStrategy::MinimumCost) => Some("MinimumCost"),
+            Some(RepairStrategy::SingleToken) | None => None,
+        };
+        if let Some(strategy) = strategy {
+            rust!(self.out, "");
+            rust!(
+                self.out,
+                "fn repair_strategy(&self) -> {p}state_machine::RepairStrategy {{",
+                p = self.prefix,
+            );
+            rust!(
+                self.out,
+                "{p}state_machine::RepairStrategy::{s}",
+                p = self.prefix,
+                s = strategy,
+            );
+            rust!(self.out, "}}");
+        }
+
+        if self.grammar.repair.is_some() && !self.grammar.repair_costs.is_empty() {
+            self.write_repair_cost_fn()?;
+        }
+
+        if self.has_completions() {
+            rust!(self.out, "");
+            rust!(
+                self.out,
+                "fn terminals(&self) -> alloc::vec::Vec<(usize, &'static str)> {{"
+            );
+            rust!(self.out, "alloc::vec![");
+            for (terminal, index) in self.grammar.terminals.all.iter().zip(0..) {
+                if *terminal == TerminalString::Error {
+                    continue;
+                }
+                rust!(self.out, "({}, r###\"{}\"###),", index, terminal);
+            }
+            rust!(self.out, "]");
+            rust!(self.out, "}}");
+        }
+
+        if self.grammar.uses_guards() {
+            rust!(self.out, "");
+            rust!(
+                self.out,
+                "fn guard(&self, action: {state_type}, symbols: &[{p}state_machine::SymbolTriple<Self>]) -> Option<bool> {{",
+                p = self.prefix,
+                state_type = state_type,
+            );
+            rust!(self.out, "{p}guard(", p = self.prefix);
+            for Parameter { name, .. } in self.grammar.parameters.iter() {
+                rust!(self.out, "self.{},", name);
+            }
+            rust!(self.out, "action,");
+            rust!(self.out, "symbols,");
+            rust!(self.out, "{},", phantom_data_expr);
+            rust!(self.out, ")");
+            rust!(self.out, "}}");
+        }
+
+        let alternatives = self.alternative_actions();
+        if !alternatives.is_empty() {
+            rust!(self.out, "");
+            rust!(
+                self.out,
+                "fn alternative_actions(&self, state: {state_type}, token_index: Option<usize>) -> alloc::vec::Vec<{state_type}> {{",
+                state_type = state_type,
+            );
+            rust!(self.out, "match (state, token_index) {{");
+            for (state, token_index, actions) in alternatives {
+                let token_index = match token_index {
+                    Some(index) => format!("Some({})", index),
+                    None => "None".to_string(),
+                };
+                rust!(
+                    self.out,
+                    "({}, {}) => alloc::vec![{}],",
+                    state,
+                    token_index,
+                    Sep(", ", &actions)
+                );
+            }
+            rust!(self.out, "_ => alloc::vec![],");
+            rust!(self.out, "}}");
+            rust!(self.out, "}}");
+        }
+
+        if self.emits_events() {
+            rust!(self.out, "");
+            rust!(
+                self.out,
+                "fn reduction_names(&self, action: {state_type}) -> (&'static str, &'static str) {{",
+                state_type = state_type,
+            );
+            rust!(
+                self.out,
+                "{p}REDUCTION_NAMES[action as usize]",
+                p = self.prefix
+            );
+            rust!(self.out, "}}");
         }
-        rust!(self.out, "}}");
 
         rust!(self.out, "}}");
 
         Ok(())
     }
 
+    /// Writes the tables of `write_parse_table` to a module of their
+    /// own, next to the parser's module, which uses them from there.
+    /// For `split_tables`, the module goes into a file of its own, so
+    /// that the tables, which change only with the states, are
+    /// compiled apart from the actions.
+    fn write_tables_mod(&mut self) -> io::Result<()> {
+        self.custom.table_visibility = "pub(super) ";
+        rust!(self.out, "");
+        rust!(self.out, "#[cfg_attr(rustfmt, rustfmt_skip)]");
+        rust!(
+            self.out,
+            "mod {}tables{} {{",
+            self.prefix,
+            self.start_symbol
+        );
+        rust!(self.out, "#![allow(non_snake_case, clippy::all)]");
+        rust!(self.out, "");
+        rust!(self.out, "extern crate alloc;");
+        self.write_parse_table()?;
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
     fn write_value_type_defn(&mut self) -> io::Result<()> {
         // sometimes some of the variants are not used, particularly
         // if we are generating multiple parsers from the same file:
         rust!(self.out, "#[allow(dead_code)]");
+        if self.is_cloneable() {
+            rust!(self.out, "#[derive(Clone)]");
+        }
         rust!(
             self.out,
             "pub(crate) enum {}Symbol<{}>",
@@ -422,116 +720,71 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         // make one variant per terminal
         for term in &self.grammar.terminals.all {
             let ty = self.types.terminal_type(term).clone();
-            let len = self.custom.variants.len();
-            let name = match self.custom.variants.entry(ty.clone()) {
-                Entry::Occupied(entry) => entry.into_mut(),
-                Entry::Vacant(entry) => {
-                    let name = format!("Variant{}", len);
-
-                    rust!(self.out, "{}({}),", name, ty);
-                    entry.insert(name)
-                }
-            };
-
+            let name = self.write_variant(ty)?;
             self.custom
                 .variant_names
-                .insert(Symbol::Terminal(term.clone()), name.clone());
+                .insert(Symbol::Terminal(term.clone()), name);
         }
 
         // make one variant per nonterminal
         for nt in self.grammar.nonterminals.keys() {
             let ty = self.types.nonterminal_type(nt).clone();
-            let len = self.custom.variants.len();
-            let name = match self.custom.variants.entry(ty.clone()) {
-                Entry::Occupied(entry) => entry.into_mut(),
-                Entry::Vacant(entry) => {
-                    let name = format!("Variant{}", len);
-
-                    rust!(self.out, "{}({}),", name, ty);
-                    entry.insert(name)
-                }
-            };
-
+            let name = self.write_variant(ty)?;
             self.custom
                 .variant_names
-                .insert(Symbol::Nonterminal(nt.clone()), name.clone());
+                .insert(Symbol::Nonterminal(nt.clone()), name);
         }
 
         rust!(self.out, "}}");
         Ok(())
     }
 
-    fn write_parse_table(&mut self) -> io::Result<()> {
-        let state_type = self.custom.state_type;
-
-        // The table is a two-dimensional matrix indexed first by state
-        // and then by the terminal index. The value is described above.
-        rust!(
-            self.out,
-            "const {}ACTION: &[{}] = &[",
-            self.prefix,
-            state_type
-        );
-
-        for (index, state) in self.states.iter().enumerate() {
-            rust!(self.out, "// State {}", index);
-
-            if Tls::session().emit_comments {
-                for item in state.items.vec.iter() {
-                    rust!(self.out, "//     {:?}", item);
-                }
-            }
-
-            // Write an action for each terminal (either shift, reduce, or error).
-            let custom = &self.custom;
-            let iterator = self.grammar.terminals.all.iter().map(|terminal| {
-                if let Some(new_state) = state.shifts.get(&terminal) {
-                    (
-                        new_state.0 as i32 + 1,
-                        Comment::Goto(Token::Terminal(terminal.clone()), new_state.0),
-                    )
-                } else {
-                    Self::write_reduction(custom, state, &Token::Terminal(terminal.clone()))
-                }
-            });
-            self.out.write_table_row(iterator)?
+    /// Returns the name of the variant of `Symbol` holding a `ty`,
+    /// writing that variant the first time `ty` is seen.
+    ///
+    /// The name is derived from a hash of the type alone, so adding,
+    /// removing or renaming other symbols does not rename the variant
+    /// and the generated code changes no more than it must. On the
+    /// (unlikely) chance that two types hash alike, the later one gets
+    /// a numeric suffix.
+    fn write_variant(&mut self, ty: TypeRepr) -> io::Result<String> {
+        if let Some(name) = self.custom.variants.get(&ty) {
+            return Ok(name.clone());
         }
 
-        rust!(self.out, "];");
-
-        rust!(
-            self.out,
-            "fn {p}action(state: {state_type}, integer: usize) -> {state_type} {{",
-            p = self.prefix,
-            state_type = state_type,
-        );
+        let mut sha3 = Sha3::v256();
+        sha3.update(ty.to_string().as_bytes());
+        let mut hash = [0u8; 32];
+        sha3.finalize(&mut hash);
+        let base = format!("Variant{:02x}", hash[..4].iter().format(""));
+
+        let taken: Set<&String> = self.custom.variants.values().collect();
+        let name = (1..)
+            .map(|n| match n {
+                1 => base.clone(),
+                _ => format!("{}_{}", base, n),
+            })
+            .find(|name| !taken.contains(name))
+            .unwrap();
 
-        rust!(
-            self.out,
-            "{p}ACTION[(state as usize) * {num_term} + integer]",
-            p = self.prefix,
-            num_term = self.grammar.terminals.all.len(),
-        );
+        rust!(self.out, "{}({}),", name, ty);
+        self.custom.variants.insert(ty, name.clone());
+        Ok(name)
+    }
 
-        rust!(self.out, "}}");
+    fn write_parse_table(&mut self) -> io::Result<()> {
+        let state_type = self.custom.state_type;
 
-        // Actions on EOF. Indexed just by state.
-        rust!(
-            self.out,
-            "const {}EOF_ACTION: &[{}] = &[",
-            self.prefix,
-            self.custom.state_type
-        );
-        for (index, state) in self.states.iter().enumerate() {
-            rust!(self.out, "// State {}", index);
-            let reduction = Self::write_reduction(&self.custom, state, &Token::EOF);
-            self.out.write_table_row(Some(reduction))?;
+        if Tls::session().profile == Profile::Wasm {
+            self.write_compressed_action_table()?;
+        } else {
+            self.write_action_table()?;
         }
-        rust!(self.out, "];");
 
         rust!(
             self.out,
-            "fn {}goto(state: {state_type}, nt: usize) -> {state_type} {{",
+            "{}fn {}goto(state: {state_type}, nt: usize) -> {state_type} {{",
+            self.custom.table_visibility,
             self.prefix,
             state_type = state_type,
         );
@@ -561,36 +814,302 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         Ok(())
     }
 
-    fn emit_goto_match<'a, 'k, K: 'k, K2: 'k, T>(
-        out: &mut RustWrite<W>,
-        k_name: &str,
-        iter: impl IntoIterator<Item = &'k K>,
-        k2_name: &str,
-        iter2: impl IntoIterator<Item = &'k K2> + Clone,
-        mut state_lookup: impl FnMut(&'k K, &'k K2) -> (Option<i32>, Comment<'a, T>),
-    ) -> io::Result<()>
-    where
-        T: fmt::Display,
-    {
-        let emit_comments = Tls::session().emit_comments;
+    fn write_action_table(&mut self) -> io::Result<()> {
+        let state_type = self.custom.state_type;
 
-        rust!(out, "match {} {{", k_name);
+        // The table is a two-dimensional matrix indexed first by state
+        // and then by the terminal index, with EOF as the last terminal.
+        // The value is described above.
+        rust!(
+            self.out,
+            "const {}ACTION: &[{}] = &[",
+            self.prefix,
+            state_type
+        );
 
+        for (index, state) in self.states.iter().enumerate() {
+            rust!(self.out, "// State {}", index);
+
+            if Tls::session().emit_comments {
+                for item in state.items.vec.iter() {
+                    rust!(self.out, "//     {:?}", item);
+                }
+            }
+
+            // Write an action for each terminal (either shift, reduce, or
+            // error), and for EOF (either reduce or error).
+            let custom = &self.custom;
+            let iterator = self
+                .grammar
+                .terminals
+                .all
+                .iter()
+                .map(|terminal| Self::write_action(custom, state, terminal))
+                .chain(Some(Self::write_reduction(custom, state, &Token::EOF)));
+            self.out.write_table_row(iterator)?
+        }
+
+        rust!(self.out, "];");
+
+        rust!(
+            self.out,
+            "{vis}fn {p}action(state: {state_type}, integer: usize) -> {state_type} {{",
+            vis = self.custom.table_visibility,
+            p = self.prefix,
+            state_type = state_type,
+        );
+
+        let index = format!(
+            "(state as usize) * {columns} + integer",
+            columns = self.grammar.terminals.all.len() + 1,
+        );
+        let entry = self.table_entry("ACTION", &index);
+        rust!(self.out, "{}", entry);
+
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    /// Writes the action table of the `wasm` profile, which keeps only
+    /// the entries that are not errors. The row of each state is placed
+    /// at an offset, `ACTION_BASE`, in one array, such that its entries
+    /// fall into the gaps between those of the rows before it, and
+    /// `ACTION_CHECK` records the state that each entry belongs to.
+    ///
+    /// Where the parser need not find errors as soon as possible (see
+    /// `uses_default_reductions`), each state also has a default
+    /// action in `ACTION_DEFAULT`: its most common reduction, which is
+    /// taken for the entries that are left out, so that those equal to
+    /// it are left out along with the errors. An error is then only
+    /// found after the default reductions, but still on the same token.
+    fn write_compressed_action_table(&mut self) -> io::Result<()> {
+        let state_type = self.custom.state_type;
+        let columns = self.grammar.terminals.all.len() + 1;
+        let defaults = self.uses_default_reductions();
+
+        // the start symbol is only reduced on EOF, by accepting; taken
+        // on another token, it would report that token as extra
+        let accepts: Vec<i32> = self
+            .grammar
+            .productions_for(&self.start_symbol)
+            .iter()
+            .map(|p| -(self.custom.reduce_indices[p] as i32 + 1))
+            .collect();
+
+        let mut actions: Vec<i32> = vec![];
+        let mut check: Vec<i32> = vec![];
+        let mut bases = vec![];
+        let mut default_actions = vec![];
+        for state in self.states {
+            let row: Vec<i32> = self
+                .grammar
+                .terminals
+                .all
+                .iter()
+                .map(|terminal| Self::write_action(&self.custom, state, terminal).0)
+                .chain(Some(
+                    Self::write_reduction(&self.custom, state, &Token::EOF).0,
+                ))
+                .collect();
+            let mut counts: Vec<(i32, usize)> = vec![];
+            for &action in row.iter().filter(|&&a| a < 0 && !accepts.contains(&a)) {
+                match counts.iter_mut().find(|&&mut (a, _)| a == action) {
+                    Some(count) => count.1 += 1,
+                    None => counts.push((action, 1)),
+                }
+            }
+            let default = match counts.iter().rev().max_by_key(|&&(_, count)| count) {
+                Some(&(action, _)) if defaults => action,
+                _ => 0,
+            };
+            default_actions.push(default);
+
+            let row: Vec<(usize, i32)> = row
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, action)| action != 0 && action != default)
+                .collect();
+            let base = (0..)
+                .find(|&base| {
+                    row.iter()
+                        .all(|&(i, _)| check.get(base + i).copied().unwrap_or(-1) == -1)
+                })
+                .unwrap();
+            // every terminal (and EOF) of every state must index into
+            // the array
+            if actions.len() < base + columns {
+                actions.resize(base + columns, 0);
+                check.resize(base + columns, -1);
+            }
+            for (i, action) in row {
+                actions[base + i] = action;
+                check[base + i] = state.index.0 as i32;
+            }
+            bases.push(base as i32);
+        }
+
+        let base_type = if actions.len() <= u16::MAX as usize {
+            "u16"
+        } else {
+            "u32"
+        };
+        let mut tables = vec![
+            ("ACTION", state_type, actions),
+            ("ACTION_CHECK", state_type, check),
+            ("ACTION_BASE", base_type, bases),
+        ];
+        if defaults {
+            tables.push(("ACTION_DEFAULT", state_type, default_actions));
+        }
+        for (table, ty, values) in tables {
+            rust!(self.out, "const {}{}: &[{}] = &[", self.prefix, table, ty);
+            for chunk in values.chunks(16) {
+                rust!(self.out, "{},", Sep(", ", &chunk.to_vec()));
+            }
+            rust!(self.out, "];");
+        }
+
+        rust!(
+            self.out,
+            "{vis}fn {p}action(state: {state_type}, integer: usize) -> {state_type} {{",
+            vis = self.custom.table_visibility,
+            p = self.prefix,
+            state_type = state_type,
+        );
+        let base = self.table_entry("ACTION_BASE", "state as usize");
+        rust!(
+            self.out,
+            "let {}index = {} as usize + integer;",
+            self.prefix,
+            base
+        );
+        let index = format!("{}index", self.prefix);
+        let check = self.table_entry("ACTION_CHECK", &index);
+        let entry = self.table_entry("ACTION", &index);
+        let default = if defaults {
+            self.table_entry("ACTION_DEFAULT", "state as usize")
+        } else {
+            String::from("0")
+        };
+        rust!(
+            self.out,
+            "if {} == state {{ {} }} else {{ {} }}",
+            check,
+            entry,
+            default
+        );
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    /// The action of `state` on `terminal`: a shift, a reduction, or 0
+    /// for an error.
+    fn write_action<'s>(
+        custom: &TableDriven<'grammar>,
+        state: &'s LR1State,
+        terminal: &'s TerminalString,
+    ) -> (i32, Comment<'s, Token>) {
+        let token = Token::Terminal(terminal.clone());
+        let shift = state
+            .shifts
+            .get(terminal)
+            .filter(|_| !Self::reduces_guarded(state, &token));
+        if let Some(new_state) = shift {
+            (
+                new_state.0 as i32 + 1,
+                Comment::Goto(Token::Terminal(terminal.clone()), new_state.0),
+            )
+        } else {
+            Self::write_reduction(custom, state, &token)
+        }
+    }
+
+    /// The expression for a path the parser never takes: `panic`, or,
+    /// in the `wasm` profile, an abort that formats no message.
+    fn unreachable(&self, panic: &str) -> String {
+        match Tls::session().profile {
+            Profile::Default => panic.to_string(),
+            Profile::Wasm => format!("{}state_machine::abort()", self.prefix),
+        }
+    }
+
+    /// The expression for entry `index` of the table `table`. With
+    /// `unchecked_tables`, the bounds check is left to debug builds.
+    fn table_entry(&self, table: &str, index: &str) -> String {
+        if Tls::session().unchecked_tables {
+            format!(
+                "{{ let {p}index = {i}; debug_assert!({p}index < {p}{t}.len()); \
+                 unsafe {{ *{p}{t}.get_unchecked({p}index) }} }}",
+                p = self.prefix,
+                t = table,
+                i = index,
+            )
+        } else {
+            format!("{p}{t}[{i}]", p = self.prefix, t = table, i = index)
+        }
+    }
+
+    fn emit_goto_match<'a, 'k, K: 'k, K2: 'k, T>(
+        out: &mut RustWrite<W>,
+        k_name: &str,
+        iter: impl IntoIterator<Item = &'k K>,
+        k2_name: &str,
+        iter2: impl IntoIterator<Item = &'k K2> + Clone,
+        mut state_lookup: impl FnMut(&'k K, &'k K2) -> (Option<i32>, Comment<'a, T>),
+    ) -> io::Result<()>
+    where
+        T: fmt::Display,
+    {
+        let emit_comments = Tls::session().emit_comments;
+
+        rust!(out, "match {} {{", k_name);
+
+        // The parser only looks up the gotos that exist, so keys whose
+        // columns agree wherever both have a goto can share an arm, and
+        // the merged column is emitted once
+        let mut columns: Vec<(Vec<usize>, Vec<(Option<i32>, Comment<'a, T>)>)> = Vec::new();
         for (k_index, k) in iter.into_iter().enumerate() {
-            let iter = iter2
+            let column: Vec<_> = iter2
                 .clone()
                 .into_iter()
                 .map(|k2| state_lookup(k, k2))
+                .collect();
+            let gotos: Vec<(usize, i32)> = column
+                .iter()
                 .enumerate()
-                // Group consecutive indices so we can compress then as a..=b
-                .group_by(|(_, (next_state, _))| *next_state);
-            let mut row = Vec::new();
-            row.extend(&iter);
+                .filter_map(|(index, &(next_state, _))| next_state.map(|next| (index, next)))
+                .collect();
 
             // If the row was all errors we don't need to emit it
-            if row.len() == 1 && row[0].0.is_none() {
+            if gotos.is_empty() {
                 continue;
             }
+            let compatible = columns.iter_mut().find(|(_, merged)| {
+                gotos
+                    .iter()
+                    .all(|&(index, next)| merged[index].0.is_none_or(|n| n == next))
+            });
+            match compatible {
+                Some((k_indices, merged)) => {
+                    k_indices.push(k_index);
+                    for (entry, new_entry) in merged.iter_mut().zip(column) {
+                        if entry.0.is_none() {
+                            *entry = new_entry;
+                        }
+                    }
+                }
+                None => columns.push((vec![k_index], column)),
+            }
+        }
+
+        for (k_indices, column) in columns {
+            let iter = column
+                .into_iter()
+                .enumerate()
+                // Group consecutive indices so we can compress then as a..=b
+                .group_by(|(_, (next_state, _))| *next_state);
+            let mut row = Vec::new();
+            row.extend(&iter);
 
             row.sort_by_key(|(next_state, _)| *next_state);
 
@@ -624,10 +1143,11 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
                 })
                 .collect();
 
+            let k_pattern = k_indices.iter().format(" | ");
             if variants.len() == 1 {
-                rust!(out, "{} => {},", k_index, variants[0].0);
+                rust!(out, "{} => {},", k_pattern, variants[0].0);
             } else {
-                rust!(out, "{} => match {} {{", k_index, k2_name);
+                rust!(out, "{} => match {} {{", k_pattern, k2_name);
 
                 for (i, (next_state, ranges, comment)) in variants.iter().enumerate() {
                     if i == largest_variant_index {
@@ -667,12 +1187,13 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         state: &'s LR1State,
         token: &Token,
     ) -> (i32, Comment<'s, Token>) {
+        // in a conflicted state, guarded reductions are tried first
         let reduction = state
             .reductions
             .iter()
             .filter(|&&(ref t, _)| t.contains(token))
             .map(|&(_, p)| p)
-            .next();
+            .min_by_key(|p| p.guard.is_none());
         if let Some(production) = reduction {
             let action = custom.reduce_indices[production];
             (
@@ -685,17 +1206,585 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         }
     }
 
+    /// True if `state` reduces a production with a guard on `token`.
+    fn reduces_guarded(state: &LR1State, token: &Token) -> bool {
+        state
+            .reductions
+            .iter()
+            .any(|&(ref t, p)| p.guard.is_some() && t.contains(token))
+    }
+
     fn write_parser_fn(&mut self) -> io::Result<()> {
+        self.start_parser_fn()?;
+        self.define_tokens()?;
+        self.write_drive_call("drive", &[])?;
+
+        if self.grammar.intern_token.is_some() {
+            rust!(self.out, "}}"); // fn parse
+            rust!(self.out, "");
+            self.start_parse_tokens_method()?;
+            self.define_given_tokens()?;
+            self.write_drive_call("drive", &[])?;
+        }
+
+        if self.grammar.repair.is_some() {
+            rust!(self.out, "}}"); // fn parse
+            rust!(self.out, "");
+
+            let repairs = format!(
+                "{p}repairs: &mut alloc::vec::Vec<{t}>",
+                p = self.prefix,
+                t = self.types.repair_type(),
+            );
+            let success_type = self.types.nonterminal_type(&self.start_symbol).to_string();
+            self.start_parse_method("parse_with_repairs", vec![repairs], success_type)?;
+            self.define_tokens()?;
+            let repairs = format!("{p}repairs", p = self.prefix);
+            self.write_drive_call("drive_repairing", &[repairs])?;
+        }
+
+        if !self.custom.error_messages.is_empty() {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            let return_type = format!(
+                "Result<{}, ({}, Option<&'static str>)>",
+                self.types.nonterminal_type(&self.start_symbol),
+                self.types.parse_error_type(),
+            );
+            self.start_parse_method_returning("parse_with_message", vec![], return_type)?;
+            self.define_tokens()?;
+            self.write_drive_call("drive_messages", &[])?;
+        }
+
+        if self.parses_prefix() {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            self.write_parse_prefix_fn()?;
+        }
+
+        if self.parses_island() {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            self.write_parse_island_fn()?;
+        }
+
+        if self.is_resumable() {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            self.write_resumable_fn()?;
+        }
+
+        if self.emits_events() {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            let events_type = format!(
+                "alloc::vec::Vec<{p}lalrpop_util::ParseEvent<{l}, {t}>>",
+                p = self.prefix,
+                l = self.types.terminal_loc_type(),
+                t = self.types.terminal_token_type(),
+            );
+            self.start_parse_method("parse_events", vec![], events_type)?;
+            self.define_tokens()?;
+            self.write_drive_call("drive_events", &[])?;
+        }
+
+        if self.recognizes() {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            self.start_parse_method("recognize", vec![], "()".to_string())?;
+            self.define_tokens()?;
+            self.write_drive_call("drive_recognize", &[])?;
+        }
+
+        if self.is_cancellable() {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            let cancelled = format!("{p}cancelled: &dyn Fn() -> bool", p = self.prefix);
+            let return_type = format!(
+                "Result<Result<{}, {}>, {}lalrpop_util::Cancelled<{}>>",
+                self.types.nonterminal_type(&self.start_symbol),
+                self.types.parse_error_type(),
+                self.prefix,
+                self.types.terminal_loc_type(),
+            );
+            self.start_parse_method_returning("parse_cancellable", vec![cancelled], return_type)?;
+            self.define_tokens()?;
+            let cancelled = format!("{p}cancelled", p = self.prefix);
+            self.write_drive_call("drive_cancellable", &[cancelled])?;
+        }
+
+        if Tls::session().trace {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            let trace = format!(
+                "{p}trace: &mut {p}lalrpop_util::trace::Trace<{l}>",
+                p = self.prefix,
+                l = self.types.terminal_loc_type(),
+            );
+            let success_type = self.types.nonterminal_type(&self.start_symbol).to_string();
+            self.start_parse_method("parse_traced", vec![trace], success_type)?;
+            self.define_tokens()?;
+            let trace = format!("{p}trace", p = self.prefix);
+            self.write_drive_call("drive_traced", &[trace])?;
+        }
+
+        if self.has_completions() {
+            rust!(self.out, "}}"); // previous fn
+            rust!(self.out, "");
+            self.start_parse_method(
+                "completions",
+                vec![],
+                "alloc::vec::Vec<alloc::string::String>".to_string(),
+            )?;
+            self.define_tokens()?;
+            self.write_drive_call("drive_completions", &[])?;
+        }
+
+        self.end_parser_fn()?;
+
+        if self.is_resumable() {
+            self.write_resumable_parser()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `resumable`, which creates a parser that is fed tokens
+    /// one at a time. It is generic over the same parameters as the
+    /// state machine, so that the parser it returns can be named.
+    fn write_resumable_fn(&mut self) -> io::Result<()> {
+        let machine = self.custom.machine.clone();
         let phantom_data_expr = self.phantom_data_expr();
 
-        self.start_parser_fn()?;
+        rust!(self.out, "#[allow(dead_code)]");
+        self.out
+            .fn_header(
+                &self.grammar.nonterminals[&self.start_symbol].visibility,
+                "resumable".to_owned(),
+            )
+            .with_parameters(Some("&self".to_owned()))
+            .with_type_parameters(&machine.type_parameters)
+            .with_parameters(
+                machine
+                    .fields
+                    .iter()
+                    .map(|param| format!("{}: {}", param.name, param.ty)),
+            )
+            .with_return_type(format!(
+                "{}ResumableParser<{}>",
+                self.user_start_symbol,
+                Sep(", ", &machine.type_parameters)
+            ))
+            .with_where_clauses(&machine.where_clauses)
+            .emit()?;
+        rust!(self.out, "{{");
+        rust!(self.out, "{}ResumableParser {{", self.user_start_symbol);
+        rust!(
+            self.out,
+            "parser: {p}state_machine::ResumableParser::new({p}StateMachine {{",
+            p = self.prefix
+        );
+        for Parameter { name, .. } in &machine.fields {
+            rust!(self.out, "{},", name);
+        }
+        rust!(
+            self.out,
+            "{p}phantom: {phantom},",
+            p = self.prefix,
+            phantom = phantom_data_expr,
+        );
+        rust!(self.out, "}}),");
+        rust!(self.out, "}}");
 
-        self.define_tokens()?;
+        Ok(())
+    }
+
+    /// Writes the parser returned by `resumable`, which wraps the
+    /// state machine so that it can be stored by the caller.
+    fn write_resumable_parser(&mut self) -> io::Result<()> {
+        let visibility = &self.grammar.nonterminals[&self.start_symbol].visibility;
+        let machine = self.custom.machine.clone();
+        let machine_type_parameters = Sep(", ", &machine.type_parameters);
+        let machine_where_clauses = Sep(", ", &machine.where_clauses);
+        let status_type = format!(
+            "{p}lalrpop_util::ParseStatus<{t}, {l}, {tok}, {e}>",
+            p = self.prefix,
+            t = self.types.nonterminal_type(&self.start_symbol),
+            l = self.types.terminal_loc_type(),
+            tok = self.types.terminal_token_type(),
+            e = self.types.error_type(),
+        );
 
+        rust!(self.out, "");
+        if self.is_cloneable() {
+            rust!(self.out, "#[derive(Clone)]");
+        }
+        rust!(
+            self.out,
+            "{vis}struct {s}ResumableParser<{mtp}>",
+            vis = visibility,
+            s = self.user_start_symbol,
+            mtp = machine_type_parameters,
+        );
+        rust!(self.out, "where {mwc}", mwc = machine_where_clauses);
+        rust!(self.out, "{{");
         rust!(
             self.out,
-            "{p}state_machine::Parser::drive(",
+            "parser: {p}state_machine::ResumableParser<{p}StateMachine<{mtp}>>,",
             p = self.prefix,
+            mtp = machine_type_parameters,
+        );
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+
+        rust!(
+            self.out,
+            "impl<{mtp}> {s}ResumableParser<{mtp}>",
+            s = self.user_start_symbol,
+            mtp = machine_type_parameters,
+        );
+        rust!(self.out, "where {mwc}", mwc = machine_where_clauses);
+        rust!(self.out, "{{");
+        rust!(
+            self.out,
+            "{vis}fn feed(&mut self, token: {triple}) -> {status} {{",
+            vis = visibility,
+            triple = self.types.triple_type(),
+            status = status_type,
+        );
+        rust!(self.out, "self.parser.feed(token)");
+        rust!(self.out, "}}");
+        rust!(self.out, "");
+        rust!(
+            self.out,
+            "{vis}fn finish(self) -> {status} {{",
+            vis = visibility,
+            status = status_type,
+        );
+        rust!(self.out, "self.parser.finish()");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+
+        if self.is_cloneable() {
+            self.write_checkpoint_fns()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `checkpoint`, `rollback` and `release` for a cloneable
+    /// resumable parser. They copy the values of the symbols, so the
+    /// type parameters of the grammar must be `Clone` for them.
+    fn write_checkpoint_fns(&mut self) -> io::Result<()> {
+        let visibility = &self.grammar.nonterminals[&self.start_symbol].visibility;
+        let machine = self.custom.machine.clone();
+        let clone_bounds = machine
+            .type_parameters
+            .iter()
+            .filter_map(|param| match *param {
+                TypeParameter::Id(ref id) => Some(format!("{}: Clone", id)),
+                TypeParameter::Lifetime(_) => None,
+            });
+        let where_clauses: Vec<String> = machine
+            .where_clauses
+            .iter()
+            .map(|clause| clause.to_string())
+            .chain(clone_bounds)
+            .collect();
+
+        rust!(self.out, "");
+        rust!(
+            self.out,
+            "impl<{mtp}> {s}ResumableParser<{mtp}>",
+            s = self.user_start_symbol,
+            mtp = Sep(", ", &machine.type_parameters),
+        );
+        rust!(self.out, "where {}", Sep(", ", &where_clauses));
+        rust!(self.out, "{{");
+        rust!(
+            self.out,
+            "{vis}fn checkpoint(&mut self) -> {p}lalrpop_util::Checkpoint {{",
+            vis = visibility,
+            p = self.prefix,
+        );
+        rust!(self.out, "self.parser.checkpoint()");
+        rust!(self.out, "}}");
+        for name in &["rollback", "release"] {
+            rust!(self.out, "");
+            rust!(
+                self.out,
+                "{vis}fn {name}(&mut self, checkpoint: {p}lalrpop_util::Checkpoint) {{",
+                vis = visibility,
+                name = name,
+                p = self.prefix,
+            );
+            rust!(self.out, "self.parser.{}(checkpoint)", name);
+            rust!(self.out, "}}");
+        }
+        rust!(self.out, "}}");
+
+        Ok(())
+    }
+
+    /// True if the user asked for a `completions` entry point for
+    /// this start symbol.
+    fn has_completions(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *COMPLETIONS)
+    }
+
+    /// True if the user asked for a `parse_events` entry point for
+    /// this start symbol.
+    fn emits_events(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *EVENTS)
+    }
+
+    /// True if the user asked for a `recognize` entry point for this
+    /// start symbol.
+    fn recognizes(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *RECOGNIZE)
+    }
+
+    /// True if the user asked for a `parse_cancellable` entry point
+    /// for this start symbol.
+    fn is_cancellable(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *CANCELLABLE)
+    }
+
+    /// The actions of the conflicted states of a `#[backtrack]` or
+    /// guarded grammar other than the one in the tables, in the order
+    /// they are tried. The tables hold the first guarded reduction, if
+    /// any, then the shift, then the first other reduction (see
+    /// `write_parse_table`). Each entry is a state, a terminal index
+    /// (`None` for EOF) and the actions.
+    fn alternative_actions(&self) -> Vec<(usize, Option<usize>, Vec<i32>)> {
+        if !self.grammar.backtrack && !self.grammar.uses_guards() {
+            return vec![];
+        }
+
+        let tokens: Vec<(Option<usize>, Token)> = self
+            .grammar
+            .terminals
+            .all
+            .iter()
+            .zip(0..)
+            .map(|(terminal, index)| (Some(index), Token::Terminal(terminal.clone())))
+            .chain(Some((None, Token::EOF)))
+            .collect();
+
+        let mut alternatives = vec![];
+        for (index, state) in self.states.iter().enumerate() {
+            for &(token_index, ref token) in &tokens {
+                let shift = match *token {
+                    Token::Terminal(ref terminal) => state.shifts.get(terminal),
+                    _ => None,
+                };
+                let reductions = state
+                    .reductions
+                    .iter()
+                    .filter(|&(t, _)| t.contains(token))
+                    .map(|&(_, p)| p);
+                let (guarded, unguarded): (Vec<_>, Vec<_>) =
+                    reductions.partition(|p| p.guard.is_some());
+                let reduce = |p: &Production| -(self.custom.reduce_indices[p] as i32 + 1);
+                let actions: Vec<i32> = guarded
+                    .into_iter()
+                    .map(reduce)
+                    .chain(shift.map(|new_state| new_state.0 as i32 + 1))
+                    .chain(unguarded.into_iter().map(reduce))
+                    .skip(1)
+                    .collect();
+                if !actions.is_empty() {
+                    alternatives.push((index, token_index, actions));
+                }
+            }
+        }
+        alternatives
+    }
+
+    /// Writes the table of the names that `parse_events` gives each
+    /// reduction: the nonterminal produced, and the production.
+    fn write_reduction_names(&mut self) -> io::Result<()> {
+        if !self.emits_events() {
+            return Ok(());
+        }
+
+        rust!(
+            self.out,
+            "const {p}REDUCTION_NAMES: &[(&str, &str)] = &[",
+            p = self.prefix
+        );
+        for production in self
+            .grammar
+            .nonterminals
+            .values()
+            .flat_map(|nt| &nt.productions)
+        {
+            rust!(
+                self.out,
+                "(r###\"{nt}\"###, r###\"{nt} = {symbols}\"###),",
+                nt = production.nonterminal,
+                symbols = Sep(" ", &production.symbols),
+            );
+        }
+        rust!(self.out, "];");
+        Ok(())
+    }
+
+    /// True if the user asked for a resumable parser for this start
+    /// symbol.
+    fn is_resumable(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *RESUMABLE)
+    }
+
+    /// True if the user asked for the resumable parser for this start
+    /// symbol, and so the symbols and the state machine, to be `Clone`.
+    fn is_cloneable(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *CLONEABLE)
+    }
+
+    /// Writes `parse_prefix`, which parses the longest complete
+    /// prefix of the input and returns the rest: as a string slice if
+    /// we generate the tokenizer, or else as an iterator of tokens.
+    fn write_parse_prefix_fn(&mut self) -> io::Result<()> {
+        let success_type = self.types.nonterminal_type(&self.start_symbol);
+        let loc_type = self.types.terminal_loc_type();
+
+        if self.grammar.intern_token.is_some() {
+            let return_type = format!("({}, {}, &{} str)", success_type, loc_type, INPUT_LIFETIME);
+            self.start_parse_method("parse_prefix", vec![], return_type)?;
+            self.define_tokens()?;
+            let end = format!("{p}end", p = self.prefix);
+            self.write_drive_prefix_offset(&end)?;
+            rust!(
+                self.out,
+                "Ok(({p}value, {p}end, &input[{p}offset..]))",
+                p = self.prefix
+            );
+        } else {
+            let to_triple = format!(
+                "fn({p}TOKEN) -> Result<{triple}, {error}>",
+                p = self.prefix,
+                triple = self.types.triple_type(),
+                error = self.types.parse_error_type(),
+            );
+            let return_type = format!(
+                "({}, {}, {p}state_machine::Remaining<core::iter::Map<<{p}TOKENS as IntoIterator>::IntoIter, {f}>>)",
+                success_type,
+                loc_type,
+                p = self.prefix,
+                f = to_triple,
+            );
+            self.start_parse_method("parse_prefix", vec![], return_type)?;
+            rust!(
+                self.out,
+                "let {p}tokens = {p}tokens0.into_iter().map(<{p}TOKEN as {t}>::to_triple as {f});",
+                p = self.prefix,
+                t = Self::to_triple_trait(self.grammar),
+                f = to_triple,
+            );
+            self.write_drive_call("drive_prefix", &[])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `parse_island`, which parses a prefix of the input
+    /// starting at a given offset, and returns the offset at which
+    /// the surrounding input resumes. Only used when we generate the
+    /// tokenizer.
+    fn write_parse_island_fn(&mut self) -> io::Result<()> {
+        let return_type = format!(
+            "({}, usize)",
+            self.types.nonterminal_type(&self.start_symbol)
+        );
+        let start = format!("{p}start: usize", p = self.prefix);
+        self.start_parse_method("parse_island", vec![start], return_type)?;
+        rust!(
+            self.out,
+            "let mut {p}tokens = self.builder.matcher_at(input, {p}start);",
+            p = self.prefix
+        );
+        self.write_drive_prefix_offset("_")?;
+        rust!(self.out, "Ok(({p}value, {p}offset))", p = self.prefix);
+        Ok(())
+    }
+
+    /// Drives a prefix parse over the generated tokenizer, binding
+    /// the value, the end of the prefix (to `end`), and the offset at
+    /// which the rest of the input starts.
+    fn write_drive_prefix_offset(&mut self, end: &str) -> io::Result<()> {
+        rust!(
+            self.out,
+            "let ({p}value, {end}, mut {p}rest) = ",
+            p = self.prefix,
+            end = end,
+        );
+        self.write_drive_call("drive_prefix", &[])?;
+        rust!(self.out, "?;");
+        rust!(
+            self.out,
+            "let {p}offset = match {p}rest.peek() {{",
+            p = self.prefix
+        );
+        rust!(
+            self.out,
+            "Some(&Ok(({p}start, _, _))) => {p}start,",
+            p = self.prefix
+        );
+        rust!(
+            self.out,
+            "Some(&Err({p}lalrpop_util::ParseError::InvalidToken {{ location }})) => location,",
+            p = self.prefix
+        );
+        rust!(self.out, "_ => input.len(),");
+        rust!(self.out, "}};");
+        Ok(())
+    }
+
+    /// True if the user asked for a `parse_island` entry point for
+    /// this start symbol.
+    fn parses_island(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *ISLAND)
+    }
+
+    /// True if the user asked for a `parse_prefix` entry point for
+    /// this start symbol.
+    fn parses_prefix(&self) -> bool {
+        self.grammar.nonterminals[&self.user_start_symbol]
+            .annotations
+            .iter()
+            .any(|a| a.id == *PREFIX)
+    }
+
+    fn write_drive_call(&mut self, drive_fn: &str, extra_args: &[String]) -> io::Result<()> {
+        let phantom_data_expr = self.phantom_data_expr();
+
+        rust!(
+            self.out,
+            "{p}state_machine::Parser::{f}(",
+            p = self.prefix,
+            f = drive_fn,
         );
         rust!(self.out, "{p}StateMachine {{", p = self.prefix);
         for Parameter { name, .. } in &self.grammar.parameters {
@@ -709,12 +1798,181 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         );
         rust!(self.out, "}},");
         rust!(self.out, "{p}tokens,", p = self.prefix);
+        for arg in extra_args {
+            rust!(self.out, "{},", arg);
+        }
         rust!(self.out, ")");
 
-        self.end_parser_fn()
+        Ok(())
+    }
+
+    /// Error repair inserts placeholder tokens that carry no user
+    /// data, so it can only insert terminals whose token we can build
+    /// from scratch. Returns an expression for that token, if so.
+    fn repair_token_expr(&self, terminal: &TerminalString) -> Option<String> {
+        let pattern = self.grammar.pattern(terminal);
+        if let Some(ref intern_token) = self.grammar.intern_token {
+            // the action code sees the matched text, so only literal
+            // terminals have an obvious placeholder
+            let text = intern_token.match_entries.iter().find_map(|entry| {
+                match (&entry.user_name, &entry.match_literal) {
+                    (MatchMapping::Terminal(t), TerminalLiteral::Quoted(s)) if t == terminal => {
+                        Some(s.clone())
+                    }
+                    _ => None,
+                }
+            })?;
+            Some(pattern.map(&mut |_| format!("{:?}", &*text)).to_string())
+        } else if is_constructible(&pattern.kind) {
+            Some(pattern.to_string())
+        } else {
+            None
+        }
+    }
+
+    fn write_repair_tokens_fn(&mut self) -> io::Result<()> {
+        if self.grammar.repair.is_none() {
+            return Ok(());
+        }
+        let token_type = self.types.terminal_token_type();
+
+        let parameters = vec![
+            format!(
+                "{p}state: {state_type}",
+                p = self.prefix,
+                state_type = self.custom.state_type,
+            ),
+            format!("_: {}", self.phantom_data_type()),
+        ];
+
+        self.out
+            .fn_header(
+                &Visibility::Priv,
+                format!("{p}repair_tokens", p = self.prefix),
+            )
+            .with_type_parameters(&self.grammar.type_parameters)
+            .with_where_clauses(&self.grammar.where_clauses)
+            .with_parameters(parameters)
+            .with_return_type(format!("alloc::vec::Vec<(usize, {})>", token_type))
+            .emit()?;
+        rust!(self.out, "{{");
+
+        rust!(self.out, "#[allow(unused_mut)]");
+        rust!(
+            self.out,
+            "let mut {p}tokens = alloc::vec::Vec::new();",
+            p = self.prefix
+        );
+        for (terminal, index) in self.grammar.terminals.all.iter().zip(0..) {
+            if *terminal == TerminalString::Error {
+                continue;
+            }
+            if let Some(expr) = self.repair_token_expr(terminal) {
+                rust!(
+                    self.out,
+                    "if {p}action({p}state, {index}) != 0 {{",
+                    p = self.prefix,
+                    index = index,
+                );
+                rust!(
+                    self.out,
+                    "{p}tokens.push(({index}, {expr}));",
+                    p = self.prefix,
+                    index = index,
+                    expr = expr,
+                );
+                rust!(self.out, "}}");
+            }
+        }
+        rust!(self.out, "{p}tokens", p = self.prefix);
+        rust!(self.out, "}}");
+
+        Ok(())
+    }
+
+    /// Writes `repair_cost`, which looks the costs given with `#[cost]`
+    /// up in a table with an entry for each terminal.
+    fn write_repair_cost_fn(&mut self) -> io::Result<()> {
+        let costs: Vec<String> = self
+            .grammar
+            .terminals
+            .all
+            .iter()
+            .map(|terminal| {
+                let cost = self
+                    .grammar
+                    .repair_costs
+                    .get(terminal)
+                    .cloned()
+                    .unwrap_or_default();
+                format!("({}, {})", cost.insert, cost.delete)
+            })
+            .collect();
+
+        rust!(self.out, "");
+        rust!(
+            self.out,
+            "fn repair_cost(&self, token_index: usize) -> {p}state_machine::RepairCost {{",
+            p = self.prefix,
+        );
+        rust!(
+            self.out,
+            "const {p}REPAIR_COSTS: &[(u32, u32)] = &[{costs}];",
+            p = self.prefix,
+            costs = costs.join(", "),
+        );
+        rust!(
+            self.out,
+            "let (insert, delete) = {p}REPAIR_COSTS[token_index];",
+            p = self.prefix,
+        );
+        rust!(
+            self.out,
+            "{p}state_machine::RepairCost {{ insert, delete }}",
+            p = self.prefix,
+        );
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    /// The `simulate_reduce` function is needed by error recovery, by
+    /// error repair, by prefix and island parsing and by completions,
+    /// which all try out tokens before committing, by backtracking,
+    /// which tries out actions, by events and recognition, which
+    /// reduce without running the actions, and by the checkpoints of
+    /// cloneable parsers, which save what a reduction pops.
+    fn simulates_reduce(&self) -> bool {
+        self.grammar.uses_error_recovery
+            || self.grammar.repair.is_some()
+            || self.grammar.backtrack
+            || self.parses_prefix()
+            || self.parses_island()
+            || self.has_completions()
+            || self.emits_events()
+            || self.recognizes()
+            || self.is_cloneable()
+    }
+
+    /// True if the compressed action table gives each state a default
+    /// reduction. Taking it in place of an error is only harmless for a
+    /// parser that goes ahead with whatever the table says: anything
+    /// that tries out tokens (see `simulates_reduce`) or weighs guards
+    /// needs to see the errors where they are, and so do the messages
+    /// of a `.messages` file, which are looked up by the state in which
+    /// the error is found.
+    fn uses_default_reductions(&self) -> bool {
+        Tls::session().profile == Profile::Wasm
+            && !self.simulates_reduce()
+            && !self.grammar.uses_guards()
+            && self.custom.error_messages.is_empty()
     }
 
     fn write_token_to_integer_fn(&mut self) -> io::Result<()> {
+        // the lexer we generate has one, shared by all of the parsers
+        if self.grammar.intern_token.is_some() {
+            return Ok(());
+        }
+
         let token_type = self.types.terminal_token_type();
 
         let parameters = vec![
@@ -761,12 +2019,16 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         Ok(())
     }
 
+    /// Writes `token_to_symbol`, which builds the symbol for a token
+    /// that is being shifted. This matches the token against the
+    /// patterns of the terminals directly, in the same order as
+    /// `token_to_integer`, rather than first going by its index and
+    /// then matching it again to take it apart.
     fn write_token_to_symbol_fn(&mut self) -> io::Result<()> {
         let symbol_type = self.symbol_type();
         let token_type = self.types.terminal_token_type();
 
         let parameters = vec![
-            format!("{p}token_index: usize", p = self.prefix,),
             format!(
                 "{p}token: {token_type}",
                 p = self.prefix,
@@ -787,80 +2049,38 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             .emit()?;
         rust!(self.out, "{{");
 
-        rust!(self.out, "match {p}token_index {{", p = self.prefix,);
-
-        let mut token_to_symbol_mapping = Vec::new();
+        rust!(self.out, "match {p}token {{", p = self.prefix);
 
-        for (index, terminal) in self.grammar.terminals.all.iter().enumerate() {
+        for terminal in &self.grammar.terminals.all {
             if *terminal == TerminalString::Error {
                 continue;
             }
             let variant_name = self.variant_name_for_symbol(&Symbol::Terminal(terminal.clone()));
-            let pattern = self.grammar.pattern(terminal);
-
-            match token_to_symbol_mapping
-                .iter_mut()
-                .find(|(other_variant_name, _)| *other_variant_name == variant_name)
-            {
-                None => token_to_symbol_mapping.push((variant_name, vec![(index, pattern)])),
-                Some((_, indices)) => indices.push((index, pattern)),
-            }
-        }
-
-        for (variant_name, indices) in token_to_symbol_mapping {
             let mut pattern_names = vec![];
-            let mut first = true;
-            let patterns = indices
-                .iter()
-                .map(|(_, pattern)| {
-                    let mut has_patterns = false;
-                    let mut name_index = 0;
-                    let pattern = pattern.map(&mut |_| {
-                        has_patterns = true;
-                        let name = format!("{}tok{}", self.prefix, name_index);
-                        name_index += 1;
-                        if first {
-                            pattern_names.push(name.clone());
-                        }
-                        name
-                    });
-                    first = false;
-
-                    format!("{}", pattern)
-                })
-                .collect::<Vec<_>>();
+            let pattern = self.grammar.pattern(terminal).map(&mut |_| {
+                let name = format!("{}tok{}", self.prefix, pattern_names.len());
+                pattern_names.push(name.clone());
+                name
+            });
 
-            if !pattern_names.is_empty() {
-                rust!(
-                    self.out,
-                    "{} => match {}token {{",
-                    indices.iter().map(|(index, _)| index).format(" | "),
-                    self.prefix
-                );
-                rust!(
-                    self.out,
-                    "{patterns} if true => {p}Symbol::{variant_name}({open}{pattern_names}{close}),",
-                    patterns = patterns.iter().format(" | "),
-                    p = self.prefix,
-                    variant_name = variant_name,
-                    open = if pattern_names.len() > 1 { "(" } else { "" },
-                    close = if pattern_names.len() > 1 { ")" } else { "" },
-                    pattern_names = pattern_names.join(", "),
-                );
-                rust!(self.out, "_ => unreachable!(),");
-                rust!(self.out, "}},");
-            } else {
-                rust!(
-                    self.out,
-                    "{indices} => {p}Symbol::{variant_name}({p}token),",
-                    indices = indices.iter().map(|(index, _)| index).format(" | "),
-                    p = self.prefix,
-                    variant_name = variant_name,
-                )
-            }
+            // a pattern without bindings keeps the whole token
+            let value = match pattern_names.len() {
+                0 => format!("{}token", self.prefix),
+                1 => pattern_names[0].clone(),
+                _ => format!("({})", pattern_names.join(", ")),
+            };
+            rust!(
+                self.out,
+                "{pattern} if true => {p}Symbol::{variant_name}({value}),",
+                pattern = pattern,
+                p = self.prefix,
+                variant_name = variant_name,
+                value = value,
+            );
         }
 
-        rust!(self.out, "_ => unreachable!(),");
+        let panic = self.unreachable("unreachable!()");
+        rust!(self.out, "_ => {},", panic);
 
         rust!(self.out, "}}");
         rust!(self.out, "}}");
@@ -877,21 +2097,28 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             format!("{}action: {}", self.prefix, self.custom.state_type),
             format!("{}lookahead_start: Option<&{}>", self.prefix, loc_type),
             format!(
-                "{}states: &mut alloc::vec::Vec<{}>",
-                self.prefix, self.custom.state_type,
+                "{}states: &mut {}",
+                self.prefix,
+                self.stack_type(self.custom.state_type),
             ),
             format!(
-                "{}symbols: &mut alloc::vec::Vec<{}>",
-                self.prefix, spanned_symbol_type,
+                "{}symbols: &mut {}",
+                self.prefix,
+                self.stack_type(spanned_symbol_type),
             ),
             format!("_: {}", self.phantom_data_type()),
         ];
 
+        let unchecked_downcasts = Tls::session().unchecked_downcasts;
+        if unchecked_downcasts {
+            self.emit_reduce_safety_doc()?;
+        }
         self.out
             .fn_header(
                 &Visibility::Pub(Some(Path::from_id(Atom::from("crate")))),
                 format!("{}reduce", self.prefix),
             )
+            .with_unsafe(unchecked_downcasts)
             .with_grammar(self.grammar)
             .with_parameters(parameters)
             .with_return_type(format!(
@@ -924,15 +2151,31 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             let is_fallible = self.grammar.action_is_fallible(production.action);
             let reduce_stack_space = !is_fallible && production.nonterminal != self.start_symbol;
 
-            if reduce_stack_space {
+            if self.reduces_in_place(production) {
+                rust!(self.out, "// {:?}", production);
+                rust!(
+                    self.out,
+                    "(1, {})",
+                    self.nonterminal_index(&production.nonterminal)
+                );
+            } else if reduce_stack_space {
                 self.custom.reduce_functions.insert(index);
                 let phantom_data_expr = self.phantom_data_expr();
+                if unchecked_downcasts {
+                    rust!(
+                        self.out,
+                        "// SAFETY: the caller vouches for `{}symbols`.",
+                        self.prefix
+                    );
+                }
                 rust!(
                     self.out,
-                    "{p}reduce{}({}{p}lookahead_start, {p}symbols, {})",
+                    "{}{p}reduce{}({}{p}lookahead_start, {p}symbols, {}){}",
+                    if unchecked_downcasts { "unsafe { " } else { "" },
                     index,
                     self.grammar.user_parameter_refs(),
                     phantom_data_expr,
+                    if unchecked_downcasts { " }" } else { "" },
                     p = self.prefix
                 );
             } else {
@@ -941,12 +2184,60 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
 
             rust!(self.out, "}}");
         }
+        let panic = self.unreachable(&format!(
+            "panic!(\"invalid action code {{}}\", {}action)",
+            self.prefix
+        ));
+        rust!(self.out, "_ => {}", panic);
+        rust!(self.out, "}};");
+
         rust!(
             self.out,
-            "_ => panic!(\"invalid action code {{}}\", {}action)",
-            self.prefix
+            "{p}pop_and_goto({p}states, {p}pop_states, {p}nonterminal);",
+            p = self.prefix
+        );
+        rust!(self.out, "None");
+        rust!(self.out, "}}");
+
+        self.emit_pop_and_goto_fn()
+    }
+
+    /// Whether reducing `production` can leave the symbol stack alone:
+    /// its action hands back its one symbol unchanged, as `<>` does, and
+    /// the value is stored in the same variant of `__Symbol` for the
+    /// nonterminal, so the symbol on top of the stack, with its
+    /// locations, already is the nonterminal. Only the states change.
+    /// Not with coverage, which counts the calls of the action.
+    fn reduces_in_place(&self, production: &Production) -> bool {
+        !Tls::session().coverage
+            && production.symbols.len() == 1
+            && production.nonterminal != self.start_symbol
+            && self.grammar.action_is_identity(production.action)
+            && self.variant_name_for_symbol(&production.symbols[0])
+                == self.variant_name_for_symbol(&Symbol::Nonterminal(
+                    production.nonterminal.clone(),
+                ))
+    }
+
+    /// The index of `nonterminal` in the GOTO table.
+    fn nonterminal_index(&self, nonterminal: &NonterminalString) -> usize {
+        self.custom
+            .all_nonterminals
+            .iter()
+            .position(|x| x == nonterminal)
+            .unwrap()
+    }
+
+    /// The state stack half of a reduction, which only deals in
+    /// indices, so it is not generic, unlike `__reduce`.
+    fn emit_pop_and_goto_fn(&mut self) -> io::Result<()> {
+        rust!(
+            self.out,
+            "fn {p}pop_and_goto({p}states: &mut {stack_type}, \
+             {p}pop_states: usize, {p}nonterminal: usize) {{",
+            p = self.prefix,
+            stack_type = self.stack_type(self.custom.state_type),
         );
-        rust!(self.out, "}};");
 
         // pop the consumed states from the stack
         rust!(
@@ -980,7 +2271,6 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             );
         }
         rust!(self.out, "{p}states.push({p}next_state);", p = self.prefix,);
-        rust!(self.out, "None");
         rust!(self.out, "}}");
         Ok(())
     }
@@ -1009,17 +2299,23 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         let parameters = vec![
             format!("{}lookahead_start: Option<&{}>", self.prefix, loc_type),
             format!(
-                "{}symbols: &mut alloc::vec::Vec<{}>",
-                self.prefix, spanned_symbol_type,
+                "{}symbols: &mut {}",
+                self.prefix,
+                self.stack_type(spanned_symbol_type),
             ),
             format!("_: {}", self.phantom_data_type()),
         ];
 
+        let unchecked_downcasts = Tls::session().unchecked_downcasts;
+        if unchecked_downcasts {
+            self.emit_reduce_safety_doc()?;
+        }
         self.out
             .fn_header(
                 &Visibility::Pub(Some(Path::from_id(Atom::from("crate")))),
                 format!("{}reduce{}", self.prefix, index),
             )
+            .with_unsafe(unchecked_downcasts)
             .with_grammar(self.grammar)
             .with_parameters(parameters)
             .with_return_type("(usize, usize)")
@@ -1028,8 +2324,28 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         Ok(())
     }
 
+    /// With `unchecked_downcasts`, the reduce functions do not check
+    /// the types of the symbols they pop, so they are `unsafe`.
+    fn emit_reduce_safety_doc(&mut self) -> io::Result<()> {
+        rust!(self.out, "/// # Safety");
+        rust!(self.out, "///");
+        rust!(
+            self.out,
+            "/// The symbols on top of `{}symbols` must have the types that the",
+            self.prefix
+        );
+        rust!(
+            self.out,
+            "/// reduction pops, as they do when the parser reduces."
+        );
+        Ok(())
+    }
+
     fn emit_reduce_action(&mut self, production: &Production) -> io::Result<()> {
         rust!(self.out, "// {:?}", production);
+        if Tls::session().emit_source_map {
+            rust!(self.out, "{}", source_map::marker(production.span));
+        }
 
         // Pop each of the symbols and their associated states.
         if production.symbols.len() > 1 {
@@ -1047,12 +2363,10 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             let name = self.variant_name_for_symbol(symbol);
             rust!(
                 self.out,
-                "let {}sym{} = {}pop_{}({}symbols);",
-                self.prefix,
+                "let {p}sym{} = {p}pop!({p}symbols, {});",
                 index,
-                self.prefix,
                 name,
-                self.prefix
+                p = self.prefix
             );
         }
         let transfer_syms: Vec<_> = (0..production.symbols.len())
@@ -1063,25 +2377,42 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         // identify the "start" and "end" location for this production; this
         // is typically the start of the first symbol and end of the last symbol we are
         // reducing; but in the case of an empty production, it will come from the
-        // lookahead
+        // lookahead. The start symbol is not pushed, so its locations are only needed
+        // for the arguments of an empty production.
+        let accepts = production.nonterminal == self.start_symbol;
         if let (Some(first_sym), Some(last_sym)) = (transfer_syms.first(), transfer_syms.last()) {
-            rust!(
-                self.out,
-                "let {}start = {}.0.clone();",
-                self.prefix,
-                first_sym
-            );
-            rust!(self.out, "let {}end = {}.2.clone();", self.prefix, last_sym);
+            if !accepts {
+                rust!(
+                    self.out,
+                    "let {}start = {};",
+                    self.prefix,
+                    self.grammar.clone_location(&format!("{}.0", first_sym))
+                );
+                rust!(
+                    self.out,
+                    "let {}end = {};",
+                    self.prefix,
+                    self.grammar.clone_location(&format!("{}.2", last_sym))
+                );
+            }
         } else {
             // we pop no symbols, so grab from the top of the stack
             // (unless we are in the start state, in which case the
             // stack will be empty)
             rust!(
                 self.out,
-                "let {p}start = {p}lookahead_start.cloned().or_else(|| {p}symbols.last().map(|s| s.2.clone())).unwrap_or_default();",
+                "let {p}start = {p}lookahead_start.{}().or_else(|| {p}symbols.last().map(|s| {})).unwrap_or_default();",
+                if self.grammar.copy_location { "copied" } else { "cloned" },
+                self.grammar.clone_location("s.2"),
                 p = self.prefix,
             );
-            rust!(self.out, "let {p}end = {p}start.clone();", p = self.prefix,);
+            rust!(
+                self.out,
+                "let {}end = {};",
+                self.prefix,
+                self.grammar
+                    .clone_location(&format!("{}start", self.prefix))
+            );
         }
 
         let transfered_syms = transfer_syms.len();
@@ -1124,7 +2455,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         }
 
         // if this is the final state, return it
-        if production.nonterminal == self.start_symbol {
+        if accepts {
             rust!(self.out, "return Some(Ok({}nt));", self.prefix);
             return Ok(());
         }
@@ -1141,12 +2472,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
 
         // produce the index that we will use to extract the next state
         // from GOTO array
-        let index = self
-            .custom
-            .all_nonterminals
-            .iter()
-            .position(|x| *x == production.nonterminal)
-            .unwrap();
+        let index = self.nonterminal_index(&production.nonterminal);
         rust!(
             self.out,
             "({len}, {index})",
@@ -1161,100 +2487,157 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         self.custom.variant_names[s].clone()
     }
 
-    fn emit_downcast_fns(&mut self) -> io::Result<()> {
+    /// Writes `pop!`, which pops a symbol off the stack and takes the
+    /// value out of the given variant of `Symbol`. One macro serves all
+    /// of the variants, so the generated code does not grow with the
+    /// number of types in the grammar. With `unchecked_downcasts`, the
+    /// check that the variant matches is left to debug builds, and the
+    /// reduce functions that pop are `unsafe`.
+    fn emit_pop_macro(&mut self) -> io::Result<()> {
+        rust!(self.out, "#[cold]");
         rust!(self.out, "#[inline(never)]");
         rust!(self.out, "fn {}symbol_type_mismatch() -> ! {{", self.prefix);
-        rust!(self.out, "panic!(\"symbol type mismatch\")");
+        let panic = self.unreachable("panic!(\"symbol type mismatch\")");
+        rust!(self.out, "{}", panic);
         rust!(self.out, "}}");
 
-        for (ty, name) in self.custom.variants.clone() {
-            self.emit_downcast_fn(&name, ty)?;
-        }
-
-        Ok(())
-    }
-
-    fn emit_downcast_fn(&mut self, variant_name: &str, variant_ty: TypeRepr) -> io::Result<()> {
-        let spanned_symbol_type = self.spanned_symbol_type();
-
-        rust!(self.out, "fn {}pop_{}<", self.prefix, variant_name);
-        for type_parameter in &self.custom.symbol_type_params {
-            rust!(self.out, "  {},", type_parameter);
+        rust!(self.out, "#[allow(unused_macros)]");
+        rust!(self.out, "macro_rules! {}pop {{", self.prefix);
+        rust!(self.out, "($symbols:expr, $variant:ident) => {{{{");
+        if DEBUG_PRINT {
+            rust!(self.out, "println!(\"pop_{{}}\", stringify!($variant));");
         }
-        rust!(self.out, ">(");
+        rust!(self.out, "match $symbols.pop() {{");
         rust!(
             self.out,
-            "{}symbols: &mut alloc::vec::Vec<{}>",
-            self.prefix,
-            spanned_symbol_type,
+            "Some(({p}l, {p}Symbol::$variant({p}v), {p}r)) => ({p}l, {p}v, {p}r),",
+            p = self.prefix
         );
-        rust!(self.out, ") -> {}", self.types.spanned_type(variant_ty));
-
-        if !self.custom.symbol_where_clauses.is_empty() {
+        if Tls::session().unchecked_downcasts {
+            rust!(self.out, "_ => {{");
             rust!(
                 self.out,
-                " where {}",
-                Sep(", ", &self.custom.symbol_where_clauses)
+                "if cfg!(debug_assertions) {{ {}symbol_type_mismatch() }}",
+                self.prefix
             );
+            rust!(
+                self.out,
+                "// SAFETY: only the `unsafe` reduce functions pop, and their"
+            );
+            rust!(
+                self.out,
+                "// callers vouch that the symbol is there, of this variant."
+            );
+            rust!(self.out, "unsafe {{ core::hint::unreachable_unchecked() }}");
+            rust!(self.out, "}}");
+        } else {
+            rust!(self.out, "_ => {}symbol_type_mismatch(),", self.prefix);
         }
-
-        rust!(self.out, " {{");
-
-        if DEBUG_PRINT {
-            rust!(self.out, "println!(\"pop_{}\");", variant_name);
-        }
-        rust!(self.out, "match {}symbols.pop() {{", self.prefix);
-        rust!(
-            self.out,
-            "Some(({}l, {}Symbol::{}({}v), {}r)) => ({}l, {}v, {}r),",
-            self.prefix,
-            self.prefix,
-            variant_name,
-            self.prefix,
-            self.prefix,
-            self.prefix,
-            self.prefix,
-            self.prefix
-        );
-        rust!(self.out, "_ => {}symbol_type_mismatch()", self.prefix);
         rust!(self.out, "}}");
-
+        rust!(self.out, "}}}};");
         rust!(self.out, "}}");
 
         Ok(())
     }
 
-    fn write_simulate_reduce_fn(&mut self) -> io::Result<()> {
-        if !self.grammar.uses_error_recovery {
+    /// Writes the function that evaluates the guard of a reduction, by
+    /// borrowing the symbols to be reduced from the top of the stack.
+    fn write_guard_fn(&mut self) -> io::Result<()> {
+        if !self.grammar.uses_guards() {
             return Ok(());
         }
-        let state_type = self.custom.state_type;
 
         let parameters = vec![
             format!(
-                "{p}reduce_index: {state_type}",
+                "{p}action: {state_type}",
                 p = self.prefix,
-                state_type = state_type,
+                state_type = self.custom.state_type,
+            ),
+            format!(
+                "{p}symbols: &[{t}]",
+                p = self.prefix,
+                t = self.spanned_symbol_type(),
             ),
             format!("_: {}", self.phantom_data_type()),
         ];
 
         self.out
-            .fn_header(
-                &Visibility::Priv,
-                format!("{p}simulate_reduce", p = self.prefix),
-            )
-            .with_type_parameters(&self.custom.machine.type_parameters)
-            .with_where_clauses(&self.custom.machine.where_clauses)
+            .fn_header(&Visibility::Priv, format!("{p}guard", p = self.prefix))
+            .with_grammar(self.grammar)
             .with_parameters(parameters)
-            .with_return_type(format!(
-                "{p}state_machine::SimulatedReduce<{p}StateMachine<{mtp}>>",
-                p = self.prefix,
-                mtp = Sep(", ", &self.custom.machine.type_parameters),
-            ))
+            .with_return_type("Option<bool>")
             .emit()?;
         rust!(self.out, "{{");
 
+        rust!(self.out, "match {p}action {{", p = self.prefix);
+        for (production, index) in self
+            .grammar
+            .nonterminals
+            .values()
+            .flat_map(|nt| &nt.productions)
+            .zip(0..)
+        {
+            let guard = match production.guard {
+                Some(guard) => guard,
+                None => continue,
+            };
+            rust!(self.out, "{} => {{", index);
+            let len = production.symbols.len();
+            for (i, symbol) in production.symbols.iter().enumerate() {
+                rust!(
+                    self.out,
+                    "let {p}sym{i} = match &{p}symbols[{p}symbols.len() - {depth}] {{",
+                    p = self.prefix,
+                    i = i,
+                    depth = len - i,
+                );
+                rust!(
+                    self.out,
+                    "(l, {p}Symbol::{v}(v), r) => (l, v, r),",
+                    p = self.prefix,
+                    v = self.variant_name_for_symbol(symbol),
+                );
+                rust!(self.out, "_ => {p}symbol_type_mismatch(),", p = self.prefix);
+                rust!(self.out, "}};");
+            }
+            let args: Vec<_> = (0..len)
+                .map(|i| format!("{}sym{}", self.prefix, i))
+                .collect();
+            rust!(
+                self.out,
+                "Some({}::{}action{}::<{}>({}{}))",
+                self.action_module,
+                self.prefix,
+                guard.index(),
+                Sep(", ", &self.grammar.non_lifetime_type_parameters()),
+                self.grammar.user_parameter_refs(),
+                Sep(", ", &args)
+            );
+            rust!(self.out, "}}");
+        }
+        rust!(self.out, "_ => None,");
+        rust!(self.out, "}}");
+        rust!(self.out, "}}");
+        Ok(())
+    }
+
+    /// The reductions of the tables, for `simulate_reduce`: how many
+    /// states each pops and the index of the nonterminal it produces, or
+    /// `None` for that of the start symbol, which accepts. It only deals
+    /// in indices, so it is not generic, unlike the `ParserDefinition`
+    /// that wraps it.
+    fn write_simulate_reduce_fn(&mut self) -> io::Result<()> {
+        if !self.simulates_reduce() {
+            return Ok(());
+        }
+
+        rust!(
+            self.out,
+            "fn {p}simulate_reduction({p}reduce_index: {state_type}) -> Option<(usize, usize)> {{",
+            p = self.prefix,
+            state_type = self.custom.state_type,
+        );
+
         rust!(self.out, "match {p}reduce_index {{", p = self.prefix,);
         for (production, index) in self
             .grammar
@@ -1269,12 +2652,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
 
             // if we just reduced the start symbol, that is also an accept criteria
             if production.nonterminal == self.start_symbol {
-                rust!(
-                    self.out,
-                    "{index} => {p}state_machine::SimulatedReduce::Accept,",
-                    index = index,
-                    p = self.prefix,
-                );
+                rust!(self.out, "{} => None,", index);
             } else {
                 let num_symbols = production.symbols.len();
                 let nt = self
@@ -1291,26 +2669,15 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
                         production
                     );
                 }
-                rust!(
-                    self.out,
-                    "{p}state_machine::SimulatedReduce::Reduce {{",
-                    p = self.prefix,
-                );
-                rust!(
-                    self.out,
-                    "states_to_pop: {num_symbols},",
-                    num_symbols = num_symbols,
-                );
-                rust!(self.out, "nonterminal_produced: {nt},", nt = nt);
-                rust!(self.out, "}}");
+                rust!(self.out, "Some(({}, {}))", num_symbols, nt);
                 rust!(self.out, "}}");
             }
         }
-        rust!(
-            self.out,
-            "_ => panic!(\"invalid reduction index {{}}\", {}reduce_index)",
-            self.prefix,
-        );
+        let panic = self.unreachable(&format!(
+            "panic!(\"invalid reduction index {{}}\", {}reduce_index)",
+            self.prefix
+        ));
+        rust!(self.out, "_ => {}", panic);
         rust!(self.out, "}}"); // end match
 
         rust!(self.out, "}}");
@@ -1349,7 +2716,6 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             return Ok(());
         }
 
-        let phantom_data_expr = self.phantom_data_expr();
         let parameters = vec![
             format!(
                 "{p}error_state: {typ}",
@@ -1362,12 +2728,10 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
                 typ = self.custom.state_type
             ),
             format!("{p}opt_integer: Option<usize>", p = self.prefix),
-            format!("_: {}", self.phantom_data_type()),
         ];
 
         self.out
             .fn_header(&Visibility::Priv, format!("{}accepts", self.prefix))
-            .with_grammar(self.grammar)
             .with_parameters(parameters)
             .with_return_type("bool")
             .emit()?;
@@ -1413,22 +2777,13 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
             );
         }
 
+        // EOF is the last column of the action table
         rust!(
             self.out,
-            "let {p}action = match {p}opt_integer {{",
-            p = self.prefix
-        );
-        rust!(
-            self.out,
-            "None => {p}EOF_ACTION[{p}top as usize],",
-            p = self.prefix
-        );
-        rust!(
-            self.out,
-            "Some({p}integer) => {p}action({p}top, {p}integer),",
+            "let {p}action = {p}action({p}top, {p}opt_integer.unwrap_or({num_term}));",
             p = self.prefix,
+            num_term = self.grammar.terminals.all.len(),
         );
-        rust!(self.out, "}};"); // end `match`
 
         // If we encounter an error action, we do **not** accept.
         rust!(
@@ -1448,22 +2803,11 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         // effect on the state stack.
         rust!(
             self.out,
-            "let ({p}to_pop, {p}nt) = match {p}simulate_reduce(-({p}action + 1), {pde}) {{",
-            p = self.prefix,
-            pde = phantom_data_expr,
-        );
-        rust!(
-            self.out,
-            "{p}state_machine::SimulatedReduce::Reduce {{",
-            p = self.prefix,
-        );
-        rust!(self.out, "states_to_pop, nonterminal_produced",);
-        rust!(self.out, "}} => (states_to_pop, nonterminal_produced),",);
-        rust!(
-            self.out,
-            "{p}state_machine::SimulatedReduce::Accept => return true,",
+            "let ({p}to_pop, {p}nt) = match {p}simulate_reduction(-({p}action + 1)) {{",
             p = self.prefix,
         );
+        rust!(self.out, "Some(reduction) => reduction,");
+        rust!(self.out, "None => return true,");
         rust!(self.out, "}};");
 
         rust!(self.out, "{p}states_len -= {p}to_pop;", p = self.prefix);
@@ -1517,21 +2861,102 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         format!("({},{},{})", loc_type, self.symbol_type(), loc_type)
     }
 
+    /// The type of the stacks of the parser, holding `element`s.
+    fn stack_type(&self, element: impl fmt::Display) -> String {
+        if Tls::session().inline_stacks {
+            format!("{}state_machine::InlineStack<{}>", self.prefix, element)
+        } else {
+            format!("alloc::vec::Vec<{}>", element)
+        }
+    }
+
     fn emit_expected_tokens_fn(&mut self) -> io::Result<()> {
-        rust!(
-            self.out,
-            "fn {p}expected_tokens({p}state: {}) -> alloc::vec::Vec<alloc::string::String> {{",
-            self.custom.state_type,
-            p = self.prefix,
-        );
+        if Tls::session().profile == Profile::Wasm {
+            // errors list no expected tokens, so that neither the names
+            // of the terminals nor the sets of them are needed
+            rust!(self.out, "#[cold]");
+            rust!(self.out, "#[inline(never)]");
+            rust!(
+                self.out,
+                "{}fn {p}expected_tokens(_: {}) -> alloc::vec::Vec<alloc::string::String> {{",
+                self.custom.table_visibility,
+                self.custom.state_type,
+                p = self.prefix,
+            );
+            rust!(self.out, "alloc::vec::Vec::new()");
+            rust!(self.out, "}}");
+            return Ok(());
+        }
 
-        rust!(self.out, "const {}TERMINAL: &[&str] = &[", self.prefix);
         let all_terminals = if self.grammar.uses_error_recovery {
             // Subtract one to exlude the error terminal
             &self.grammar.terminals.all[..self.grammar.terminals.all.len() - 1]
         } else {
             &self.grammar.terminals.all
         };
+
+        // For each state, a bitset of the terminals with a non-error
+        // action, so that we need not decode the action table when
+        // reporting an error; those covered by a named nonterminal are
+        // left out, and the name listed instead.
+        let expected_names = ExpectedNames::new(self.grammar);
+        let words = all_terminals.len().div_ceil(64);
+        let mut state_names = vec![];
+        rust!(
+            self.out,
+            "const {}EXPECTED: &[[u64; {}]] = &[",
+            self.prefix,
+            words
+        );
+        for (index, state) in self.states.iter().enumerate() {
+            let expected: Vec<_> = all_terminals
+                .iter()
+                .filter(|&terminal| {
+                    let token = Token::Terminal(terminal.clone());
+                    let (reduction, _) = Self::write_reduction(&self.custom, state, &token);
+                    state.shifts.contains_key(terminal) || reduction != 0
+                })
+                .cloned()
+                .collect();
+            let (names, expected) = expected_names.collapse(&expected);
+            state_names.push(names);
+            let mut bits = vec![0u64; words];
+            for (i, terminal) in all_terminals.iter().enumerate() {
+                if expected.contains(terminal) {
+                    bits[i / 64] |= 1 << (i % 64);
+                }
+            }
+            let row: Vec<_> = bits.iter().map(|w| format!("{:#x}", w)).collect();
+            rust!(self.out, "[{}], // State {}", Sep(", ", &row), index);
+        }
+        rust!(self.out, "];");
+
+        if !expected_names.is_empty() {
+            rust!(
+                self.out,
+                "const {}EXPECTED_NAMES: &[&[&str]] = &[",
+                self.prefix
+            );
+            for (index, names) in state_names.iter().enumerate() {
+                let names: Vec<_> = names.iter().map(|name| format!("{:?}", name)).collect();
+                rust!(self.out, "&[{}], // State {}", Sep(", ", &names), index);
+            }
+            rust!(self.out, "];");
+        }
+
+        // only called on errors, so kept out of line, away from the
+        // parser's main loop
+        rust!(self.out, "#[cold]");
+        rust!(self.out, "#[inline(never)]");
+        rust!(
+            self.out,
+            "{}fn {p}expected_tokens({p}state: {}) -> alloc::vec::Vec<alloc::string::String> {{",
+            self.custom.table_visibility,
+            self.custom.state_type,
+            p = self.prefix,
+        );
+
+        rust!(self.out, "const {}TERMINAL: &[&str] = &[", self.prefix);
         for terminal in all_terminals {
             // Three # should hopefully be enough to prevent any
             // reasonable terminal from escaping the literal
@@ -1542,25 +2967,61 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TableDrive
         // Grab any terminals in the current state which would have resulted in a successful parse
         rust!(
             self.out,
-            "{}TERMINAL.iter().enumerate().filter_map(|(index, terminal)| {{",
+            "let {p}expected = &{p}EXPECTED[{p}state as usize];",
+            p = self.prefix
+        );
+        let names = if expected_names.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "{p}EXPECTED_NAMES[{p}state as usize].iter().map(|name| \
+                 alloc::string::ToString::to_string(name)).chain(",
+                p = self.prefix
+            )
+        };
+        rust!(
+            self.out,
+            "{}{}TERMINAL.iter().enumerate().filter_map(|(index, terminal)| {{",
+            names,
             self.prefix,
         );
         rust!(
             self.out,
-            "let next_state = {p}action({p}state, index);",
+            "if {p}expected[index / 64] & (1 << (index % 64)) == 0 {{",
             p = self.prefix
         );
-        rust!(self.out, "if next_state == 0 {{");
         rust!(self.out, "None");
         rust!(self.out, "}} else {{");
         rust!(self.out, "Some(alloc::string::ToString::to_string(terminal))");
         rust!(self.out, "}}");
-        rust!(self.out, "}}).collect()");
+        if expected_names.is_empty() {
+            rust!(self.out, "}}).collect()");
+        } else {
+            rust!(self.out, "}})).collect()");
+        }
         rust!(self.out, "}}");
         Ok(())
     }
 }
 
+/// True if the pattern binds nothing, so that it can be used as an
+/// expression constructing the token it matches.
+fn is_constructible<T>(pattern: &PatternKind<T>) -> bool {
+    match *pattern {
+        PatternKind::Enum(_, ref pats)
+        | PatternKind::Tuple(ref pats)
+        | PatternKind::TupleStruct(_, ref pats) => pats.iter().all(|p| is_constructible(&p.kind)),
+        PatternKind::Struct(_, ref fields, dotdot) => {
+            !dotdot && fields.iter().all(|f| is_constructible(&f.pattern.kind))
+        }
+        PatternKind::Path(_)
+        | PatternKind::Usize(_)
+        | PatternKind::CharLiteral(_)
+        | PatternKind::String(_) => true,
+        PatternKind::Underscore | PatternKind::DotDot | PatternKind::Choose(_) => false,
+    }
+}
+
 struct MachineParameters {
     type_parameters: Vec<TypeParameter>,
     fields: Vec<Parameter>,
@@ -0,0 +1,109 @@
+//! Emits the impl of `lalrpop_util::Parser` for the `XParser` of a
+//! public nonterminal, which forwards to its `parse` method:
+//!
+//! ```ignore
+//! impl<'input> __lalrpop_util::Parser<&'input str, Expr, ParseError<..>> for ExprParser {
+//!     fn parse(&self, input: &'input str) -> Result<Expr, ParseError<..>> {
+//!         ExprParser::parse(self, input)
+//!     }
+//! }
+//! ```
+//!
+//! Grammars with parameters get no impl, since `parse` then takes more
+//! than the input.
+
+use crate::grammar::repr::{Grammar, LrCodeGeneration, NonterminalString};
+use crate::rust::RustWrite;
+use crate::util::Sep;
+use std::io::{self, Write};
+
+use super::base::CodeGenerator;
+
+pub fn compile<W: Write>(
+    grammar: &Grammar,
+    user_nt: &NonterminalString,
+    start_nt: &NonterminalString,
+    out: &mut RustWrite<W>,
+) -> io::Result<()> {
+    let prefix = &grammar.prefix;
+    let output_type = grammar.types.nonterminal_type(start_nt);
+    let error_type = grammar.types.parse_error_type();
+
+    // every type parameter must appear in the trait, or the impl is
+    // rejected for leaving it unconstrained
+    let (used_type_parameters, _) =
+        CodeGenerator::<W, ()>::filter_type_parameters_and_where_clauses(
+            grammar,
+            vec![output_type.clone(), error_type.clone()],
+        );
+    if used_type_parameters.len() != grammar.type_parameters.len() {
+        return Ok(());
+    }
+
+    let mut type_parameters: Vec<String> = grammar
+        .type_parameters
+        .iter()
+        .map(|tp| tp.to_string())
+        .collect();
+    let mut where_clauses: Vec<String> = grammar
+        .where_clauses
+        .iter()
+        .map(|wc| wc.to_string())
+        .collect();
+    let input_type = if grammar.intern_token.is_some() {
+        // the `input` parameter that `token_check` added
+        match *grammar.parameters {
+            [ref input] => input.ty.to_string(),
+            _ => return Ok(()),
+        }
+    } else {
+        if !grammar.parameters.is_empty() {
+            return Ok(());
+        }
+        let user_type_parameters: String = grammar
+            .type_parameters
+            .iter()
+            .map(|tp| format!("{}, ", tp))
+            .collect();
+        type_parameters.push(format!(
+            "{}TOKEN: {}ToTriple<{}>",
+            prefix, prefix, user_type_parameters
+        ));
+        type_parameters.push(format!(
+            "{}TOKENS: IntoIterator<Item={}TOKEN>",
+            prefix, prefix
+        ));
+        if grammar.codegen_for(user_nt) == LrCodeGeneration::TestAll {
+            where_clauses.push(format!("{}TOKENS: Clone", prefix));
+        }
+        format!("{}TOKENS", prefix)
+    };
+
+    rust!(out, "");
+    rust!(
+        out,
+        "impl<{}> {}lalrpop_util::Parser<{}, {}, {}> for {}Parser",
+        Sep(", ", &type_parameters),
+        prefix,
+        input_type,
+        output_type,
+        error_type,
+        user_nt
+    );
+    if !where_clauses.is_empty() {
+        rust!(out, "where {}", Sep(", ", &where_clauses));
+    }
+    rust!(out, "{{");
+    rust!(
+        out,
+        "fn parse(&self, {}input: {}) -> Result<{}, {}> {{",
+        prefix,
+        input_type,
+        output_type,
+        error_type
+    );
+    rust!(out, "{}Parser::parse(self, {}input)", user_nt, prefix);
+    rust!(out, "}}");
+    rust!(out, "}}");
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Emits the `parse` fn requested by `#[runtime_start]`, which picks
+//! one of the public nonterminals to parse at runtime:
+//!
+//! ```ignore
+//! pub enum StartSymbol { Expr, Stmts }
+//! pub enum ParsedValue { Expr(Expr), Stmts(Vec<Stmt>) }
+//! pub fn parse(start: StartSymbol, input: &str) -> Result<ParsedValue, ParseError<..>>
+//! ```
+//!
+//! `parse` simply dispatches to the `XParser` of the chosen symbol.
+
+use crate::grammar::repr::{Grammar, LrCodeGeneration, Path, Visibility};
+use crate::rust::RustWrite;
+use crate::util::Sep;
+use std::io::{self, Write};
+use string_cache::DefaultAtom as Atom;
+
+use super::base::CodeGenerator;
+
+pub fn compile<W: Write>(grammar: &Grammar, out: &mut RustWrite<W>) -> io::Result<()> {
+    let prefix = &grammar.prefix;
+    let symbols: Vec<_> = grammar.start_nonterminals.iter().collect();
+
+    // if the public nonterminals disagree, fall back to the crate
+    let visibility = {
+        let first = &grammar.nonterminals[symbols[0].0].visibility;
+        if symbols
+            .iter()
+            .all(|(user_nt, _)| grammar.nonterminals[user_nt].visibility == *first)
+        {
+            first.clone()
+        } else {
+            Visibility::Pub(Some(Path::from_id(Atom::from("crate"))))
+        }
+    };
+
+    rust!(out, "");
+    rust!(out, "#[allow(non_camel_case_types, dead_code)]");
+    rust!(out, "#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]");
+    rust!(out, "{}enum StartSymbol {{", visibility);
+    for (user_nt, _) in &symbols {
+        rust!(out, "{},", user_nt);
+    }
+    rust!(out, "}}");
+
+    let (type_parameters, where_clauses) =
+        CodeGenerator::<W, ()>::filter_type_parameters_and_where_clauses(
+            grammar,
+            symbols
+                .iter()
+                .map(|(_, start_nt)| grammar.types.nonterminal_type(start_nt).clone()),
+        );
+    let value_type = if type_parameters.is_empty() {
+        "ParsedValue".to_string()
+    } else {
+        format!("ParsedValue<{}>", Sep(", ", &type_parameters))
+    };
+
+    rust!(out, "");
+    rust!(out, "#[allow(non_camel_case_types, dead_code)]");
+    rust!(out, "{}enum {}", visibility, value_type);
+    if !where_clauses.is_empty() {
+        rust!(out, "where {}", Sep(", ", &where_clauses));
+    }
+    rust!(out, "{{");
+    for (user_nt, start_nt) in &symbols {
+        rust!(
+            out,
+            "{}({}),",
+            user_nt,
+            grammar.types.nonterminal_type(start_nt)
+        );
+    }
+    rust!(out, "}}");
+
+    // the same parameters as the `parse` method of each `XParser`
+    let (token_type_parameters, token_parameters, token_where_clauses) =
+        CodeGenerator::<W, ()>::token_parameters(
+            grammar,
+            grammar.algorithm.codegen == LrCodeGeneration::TestAll,
+        );
+    let arguments: Vec<String> = grammar
+        .parameters
+        .iter()
+        .map(|p| p.name.to_string())
+        .chain(
+            token_parameters
+                .iter()
+                .map(|p| p.split(':').next().unwrap().to_string()),
+        )
+        .collect();
+
+    rust!(out, "");
+    rust!(out, "#[allow(dead_code)]");
+    out.fn_header(&visibility, "parse".to_owned())
+        .with_parameters(Some(format!("{}start: StartSymbol", prefix)))
+        .with_grammar(grammar)
+        .with_type_parameters(token_type_parameters)
+        .with_parameters(token_parameters)
+        .with_return_type(format!(
+            "Result<{}, {}>",
+            value_type,
+            grammar.types.parse_error_type()
+        ))
+        .with_where_clauses(token_where_clauses)
+        .emit()?;
+    rust!(out, "{{");
+    rust!(out, "match {}start {{", prefix);
+    for (user_nt, _) in &symbols {
+        rust!(
+            out,
+            "StartSymbol::{0} => {0}Parser::new().parse({1}).map(ParsedValue::{0}),",
+            user_nt,
+            Sep(", ", &arguments)
+        );
+    }
+    rust!(out, "}}");
+    rust!(out, "}}");
+    Ok(())
+}
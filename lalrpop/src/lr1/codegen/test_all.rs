@@ -8,7 +8,7 @@ use crate::rust::RustWrite;
 use std::io::{self, Write};
 use crate::util::Sep;
 
-use super::base::CodeGenerator;
+use super::base::{CodeGenerator, CodegenOptions};
 
 pub fn compile<'grammar, W: Write>(
     grammar: &'grammar Grammar,
@@ -38,8 +38,10 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
             start_symbol,
             states,
             out,
-            true,
-            "super",
+            CodegenOptions {
+                action_module: "super",
+                repeatable: true,
+            },
             TestAll,
         )
     }
@@ -75,8 +77,12 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
                 this.user_start_symbol.clone(),
                 this.start_symbol.clone(),
                 this.states,
-                "super::super::super",
                 this.out,
+                super::parse_table::TableOptions {
+                    action_module: "super::super::super",
+                    error_messages: &[],
+                    tables: None,
+                },
             )?;
             rust!(this.out, "{}", pub_use);
             rust!(this.out, "}}");
@@ -91,26 +97,49 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
         if self.grammar.intern_token.is_some() {
             rust!(self.out, "let _ = self.builder;");
         }
-        // parse input using both methods:
-        self.call_delegate("ascent")?;
-        self.call_delegate("parse_table")?;
-
-        // check that result is the same either way:
-        rust!(
-            self.out,
-            "assert_eq!({}ascent, {}parse_table);",
-            self.prefix,
-            self.prefix
-        );
+        self.write_delegate_calls("parse")?;
 
-        rust!(self.out, "return {}ascent;", self.prefix);
+        if self.grammar.intern_token.is_some() {
+            rust!(self.out, "}}"); // fn parse
+            rust!(self.out, "");
+            self.start_parse_tokens_method()?;
+            self.write_delegate_calls("parse_tokens")?;
+        }
 
         self.end_parser_fn()?;
 
         Ok(())
     }
 
-    fn call_delegate(&mut self, delegate: &str) -> io::Result<()> {
+    fn write_delegate_calls(&mut self, method: &str) -> io::Result<()> {
+        // parse input using both methods:
+        self.call_delegate("ascent", method)?;
+        self.call_delegate("parse_table", method)?;
+
+        // check that result is the same either way, naming the input
+        // when it is text:
+        if self.grammar.intern_token.is_some() && method == "parse" {
+            rust!(
+                self.out,
+                "assert_eq!({p}ascent, {p}parse_table, \
+                 \"the recursive ascent and table-driven parsers disagree on {{:?}}\", input);",
+                p = self.prefix,
+            );
+        } else {
+            rust!(
+                self.out,
+                "assert_eq!({p}ascent, {p}parse_table, \
+                 \"the recursive ascent and table-driven parsers disagree\");",
+                p = self.prefix,
+            );
+        }
+
+        rust!(self.out, "return {}ascent;", self.prefix);
+        Ok(())
+    }
+
+    fn call_delegate(&mut self, delegate: &str, method: &str) -> io::Result<()> {
+        let parse_tokens = method == "parse_tokens";
         let non_lifetimes: Vec<_> = self
             .grammar
             .type_parameters
@@ -124,7 +153,7 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
         let parameters = if non_lifetimes.is_empty() {
             String::new()
         } else {
-            if self.grammar.intern_token.is_some() {
+            if self.grammar.intern_token.is_some() && !parse_tokens {
                 format!("::<{}>", Sep(", ", &non_lifetimes))
             } else {
                 format!("::<{}, _, _>", Sep(", ", &non_lifetimes))
@@ -132,18 +161,19 @@ impl<'ascent, 'grammar, W: Write> CodeGenerator<'ascent, 'grammar, W, TestAll> {
         };
         rust!(
             self.out,
-            "let {}{} = {}{}::{}Parser::new().parse{}(",
+            "let {}{} = {}{}::{}Parser::new().{}{}(",
             self.prefix,
             delegate,
             self.prefix,
             delegate,
             self.user_start_symbol,
+            method,
             parameters
         );
         for parameter in &self.grammar.parameters {
             rust!(self.out, "{},", parameter.name);
         }
-        if self.grammar.intern_token.is_none() {
+        if self.grammar.intern_token.is_none() || parse_tokens {
             rust!(self.out, "{}tokens0.clone(),", self.prefix);
         }
         rust!(self.out, ");");
@@ -173,10 +173,19 @@ pub struct TableConstructionError<'grammar, L: Lookahead> {
     // configured to terminate early.
     pub states: Vec<State<'grammar, L>>,
 
-    // Conflicts (non-empty) found in those states.
+    // Conflicts found in those states; empty if construction stopped
+    // because the grammar needs more states than `max_states` allows.
     pub conflicts: Vec<Conflict<'grammar, L>>,
 }
 
+impl<'grammar, L: Lookahead> TableConstructionError<'grammar, L> {
+    /// Whether construction stopped because of `max_states` rather than
+    /// because of conflicts.
+    pub fn exceeds_max_states(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
 pub type LR0TableConstructionError<'grammar> = TableConstructionError<'grammar, Nil>;
 pub type LR1TableConstructionError<'grammar> = TableConstructionError<'grammar, TokenSet>;
 pub type LRResult<'grammar, L> =
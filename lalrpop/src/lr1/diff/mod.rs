@@ -0,0 +1,228 @@
+//! Compares the LR(1) automata of two versions of a grammar, so that a
+//! change to the grammar can be reviewed by its effect on the parser.
+//!
+//! State indices change whenever a state is added or removed, so states
+//! are matched by their kernel items, written out as LR(0) items like
+//! `Expr = Expr (*) "+" Term`. What a state does is then written out
+//! as one line per lookahead, like `on "+": shift`, and two versions of
+//! a state differ if those lines do.
+
+use crate::collections::{Map, Set};
+use crate::grammar::repr::{Grammar, NonterminalString, Production, Symbol};
+use crate::lr1::core::*;
+use crate::util::Sep;
+use std::io::{self, Write};
+
+#[cfg(test)]
+mod test;
+
+/// The automaton of one public nonterminal, with grammar-independent
+/// names for its states, conflicts and reductions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Automaton {
+    /// the actions of each state, by its kernel items; states of a
+    /// canonical LR(1) automaton that share their kernel are merged
+    states: Map<Vec<String>, Set<String>>,
+
+    /// each conflict, with the first kernel item of its state
+    conflicts: Set<String>,
+
+    /// the states and lookaheads in which each production is reduced
+    reductions: Map<String, Set<String>>,
+}
+
+impl Automaton {
+    pub fn new(grammar: &Grammar, lr1result: &LR1Result<'_>) -> Self {
+        let (states, conflicts) = match lr1result {
+            Ok(states) => (&states[..], &[][..]),
+            Err(error) => (&error.states[..], &error.conflicts[..]),
+        };
+
+        let mut automaton = Automaton::default();
+        for state in states {
+            let kernel = kernel(state);
+            let mut actions = Set::new();
+            for terminal in state.shifts.keys() {
+                actions.insert(format!("on {}: shift", terminal));
+            }
+            for (lookahead, production) in &state.reductions {
+                // the start production is ours, not the user's
+                let start = grammar
+                    .start_nonterminals
+                    .values()
+                    .any(|start| *start == production.nonterminal);
+                for token in lookahead.iter() {
+                    actions.insert(format!("on {}: reduce `{}`", token, rule(production)));
+                    if start {
+                        continue;
+                    }
+                    automaton
+                        .reductions
+                        .entry(rule(production))
+                        .or_insert_with(Set::new)
+                        .insert(format!("on {} in `{}`", token, kernel[0]));
+                }
+            }
+            for nonterminal in state.gotos.keys() {
+                actions.insert(format!("goto on {}", nonterminal));
+            }
+            automaton
+                .states
+                .entry(kernel)
+                .or_insert_with(Set::new)
+                .extend(actions);
+        }
+
+        for conflict in conflicts {
+            let other = match conflict.action {
+                Action::Shift(ref terminal, _) => format!("shift {}", terminal),
+                Action::Reduce(production) => format!("reduce `{}`", rule(production)),
+            };
+            let lookahead: Vec<_> = conflict.lookahead.iter().collect();
+            automaton.conflicts.insert(format!(
+                "in `{}`, on {}: reduce `{}` or {}",
+                kernel(&states[conflict.state.0])[0],
+                Sep(" ", &lookahead),
+                rule(conflict.production),
+                other
+            ));
+        }
+        automaton
+    }
+}
+
+/// Writes the differences between the automata `old` and `new` of the
+/// public nonterminal `nonterminal`, which may only exist in one of the
+/// versions. Returns false, writing nothing, if there are none.
+pub fn write_diff<W: Write>(
+    out: &mut W,
+    nonterminal: &str,
+    old: Option<&Automaton>,
+    new: Option<&Automaton>,
+) -> io::Result<bool> {
+    let (old, new) = match (old, new) {
+        (Some(old), Some(new)) if old == new => return Ok(false),
+        (Some(old), Some(new)) => (old, new),
+        (None, None) => return Ok(false),
+        (None, Some(_)) => {
+            writeln!(out, "Public nonterminal `{}` was added", nonterminal)?;
+            return Ok(true);
+        }
+        (Some(_), None) => {
+            writeln!(out, "Public nonterminal `{}` was removed", nonterminal)?;
+            return Ok(true);
+        }
+    };
+
+    writeln!(out, "Public nonterminal `{}`", nonterminal)?;
+    writeln!(out, "----------------------------------------")?;
+    writeln!(
+        out,
+        "{} states before, {} after",
+        old.states.len(),
+        new.states.len()
+    )?;
+
+    for (kernel, actions) in &new.states {
+        match old.states.get(kernel) {
+            None => {
+                writeln!(out, "\nAdded state:")?;
+                write_kernel(out, kernel)?;
+                for action in actions {
+                    writeln!(out, "      {}", action)?;
+                }
+            }
+            Some(old_actions) if old_actions != actions => {
+                writeln!(out, "\nChanged state:")?;
+                write_kernel(out, kernel)?;
+                for action in old_actions.difference(actions) {
+                    writeln!(out, "    - {}", action)?;
+                }
+                for action in actions.difference(old_actions) {
+                    writeln!(out, "    + {}", action)?;
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    for kernel in old.states.keys() {
+        if !new.states.contains_key(kernel) {
+            writeln!(out, "\nRemoved state:")?;
+            write_kernel(out, kernel)?;
+        }
+    }
+
+    let added: Vec<_> = new.conflicts.difference(&old.conflicts).collect();
+    let resolved: Vec<_> = old.conflicts.difference(&new.conflicts).collect();
+    write_list(out, "New conflicts", &added)?;
+    write_list(out, "Resolved conflicts", &resolved)?;
+
+    let changed: Vec<_> = old
+        .reductions
+        .keys()
+        .chain(new.reductions.keys())
+        .collect::<Set<_>>()
+        .into_iter()
+        .filter(|production| old.reductions.get(*production) != new.reductions.get(*production))
+        .collect();
+    write_list(
+        out,
+        "Productions reduced in other states or on other lookahead",
+        &changed,
+    )?;
+
+    Ok(true)
+}
+
+fn write_kernel<W: Write>(out: &mut W, kernel: &[String]) -> io::Result<()> {
+    for item in kernel {
+        writeln!(out, "    {}", item)?;
+    }
+    Ok(())
+}
+
+fn write_list<W: Write>(out: &mut W, heading: &str, lines: &[&String]) -> io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "\n{}:", heading)?;
+    for line in lines {
+        writeln!(out, "    {}", line)?;
+    }
+    Ok(())
+}
+
+/// The kernel items of `state`: those that no other item of the state
+/// predicts, which is to say those with the dot after a symbol, and the
+/// item of the start symbol in the start state.
+fn kernel(state: &LR1State<'_>) -> Vec<String> {
+    let predicted: Set<&NonterminalString> = state
+        .items
+        .vec
+        .iter()
+        .filter_map(|item| match item.production.symbols.get(item.index) {
+            Some(Symbol::Nonterminal(nonterminal)) => Some(nonterminal),
+            _ => None,
+        })
+        .collect();
+    let mut kernel: Vec<String> = state
+        .items
+        .vec
+        .iter()
+        .filter(|item| item.index > 0 || !predicted.contains(&item.production.nonterminal))
+        .map(|item| format!("{:?}", Item::lr0(item.production, item.index)))
+        .collect();
+    kernel.sort();
+    kernel.dedup();
+    kernel
+}
+
+fn rule(production: &Production) -> String {
+    format!(
+        "{} = {}",
+        production.nonterminal,
+        Sep(" ", &production.symbols)
+    )
+    .trim_end()
+    .to_string()
+}
@@ -0,0 +1,62 @@
+use crate::build::diff_grammars;
+
+const SUM: &str = r#"
+grammar;
+pub Expr: () = {
+    Expr "+" Term,
+    Term,
+};
+Term: () = "x";
+"#;
+
+#[test]
+fn same_grammar() {
+    assert_eq!(diff_grammars(SUM, SUM).unwrap(), "");
+}
+
+#[test]
+fn added_operator() {
+    let product = SUM.replace(
+        "Term: () = \"x\";",
+        "Term: () = { Term \"*\" \"x\", \"x\" };",
+    );
+    let diff = diff_grammars(SUM, &product).unwrap();
+    assert!(diff.starts_with(
+        "Public nonterminal `Expr`\n\
+         ----------------------------------------\n\
+         6 states before, 8 after\n"
+    ));
+    assert!(diff.contains(
+        "\nAdded state:\n    \
+         Term = Term \"*\" (*) \"x\"\n      \
+         on \"x\": shift\n"
+    ));
+    assert!(diff.contains(
+        "\nChanged state:\n    \
+         Term = \"x\" (*)\n    \
+         + on \"*\": reduce `Term = \"x\"`\n"
+    ));
+    assert!(diff.contains("\nRemoved state:\n    Expr = Term (*)\n"));
+    assert!(diff.ends_with(
+        "\nProductions reduced in other states or on other lookahead:\n    \
+         Term = \"x\"\n    \
+         Term = Term \"*\" \"x\"\n"
+    ));
+}
+
+#[test]
+fn new_conflict() {
+    let ambiguous = SUM.replace("Expr \"+\" Term", "Expr \"+\" Expr");
+    let conflict = "    in `Expr = Expr \"+\" Expr (*)`, on \"+\": \
+                    reduce `Expr = Expr \"+\" Expr` or shift \"+\"\n";
+    let diff = diff_grammars(SUM, &ambiguous).unwrap();
+    assert!(diff.contains(&format!("\nNew conflicts:\n{}", conflict)));
+    let diff = diff_grammars(&ambiguous, SUM).unwrap();
+    assert!(diff.contains(&format!("\nResolved conflicts:\n{}", conflict)));
+}
+
+#[test]
+fn added_nonterminal() {
+    let diff = diff_grammars(SUM, &SUM.replace("Term: ()", "pub Term: ()")).unwrap();
+    assert_eq!(diff, "Public nonterminal `Term` was added\n");
+}
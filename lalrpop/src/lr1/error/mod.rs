@@ -1,14 +1,14 @@
 //! Error reporting. For now very stupid and simplistic.
 
-use crate::collections::{set, Set};
+use crate::collections::{map, set, Map, Set};
 use crate::grammar::repr::*;
 use itertools::Itertools;
 use crate::lr1::core::*;
 use crate::lr1::example::{Example, ExampleStyles, ExampleSymbol};
 use crate::lr1::first::FirstSets;
-use crate::lr1::lookahead::{Token, TokenSet};
+use crate::lr1::lookahead::{Lookahead, Token, TokenSet};
 use crate::lr1::trace::Tracer;
-use crate::message::builder::{BodyCharacter, Builder, Character, MessageBuilder};
+use crate::message::builder::{BodyCharacter, Builder, Character, InlineBuilder, MessageBuilder};
 use crate::message::Message;
 use crate::tls::Tls;
 
@@ -16,10 +16,180 @@ use crate::tls::Tls;
 mod test;
 
 pub fn report_error(grammar: &Grammar, error: &LR1TableConstructionError) -> Vec<Message> {
+    if error.exceeds_max_states() {
+        return vec![report_too_many_states(grammar, &error.states)];
+    }
     let mut cx = ErrorReportingCx::new(grammar, &error.states, &error.conflicts);
     cx.report_errors()
 }
 
+/// For each conflict of `error` on each token, the production that it
+/// reduces and, if the conflict is an ambiguity, the two ways of
+/// deriving the same symbols: with the other action, and with that
+/// reduction.
+pub fn ambiguous_examples<'grammar>(
+    grammar: &'grammar Grammar,
+    error: &LR1TableConstructionError<'grammar>,
+) -> Vec<(&'grammar Production, Option<(Example, Example)>)> {
+    let mut cx = ErrorReportingCx::new(grammar, &error.states, &error.conflicts);
+    token_conflicts(&error.conflicts)
+        .iter()
+        .map(|conflict| {
+            let examples = match cx.classify(conflict) {
+                ConflictClassification::Ambiguity { action, reduce }
+                | ConflictClassification::Precedence {
+                    shift: action,
+                    reduce,
+                    ..
+                } => Some((action, reduce)),
+                _ => None,
+            };
+            (conflict.production, examples)
+        })
+        .collect()
+}
+
+/// Describes the conflicts that a `#[backtrack]` grammar keeps in
+/// `states`, those without a `when` guard, for a warning at the
+/// alternative that each would reduce: on which tokens, against which
+/// other action, and whether the grammar is ambiguous there, in which
+/// case trying both actions on the next tokens cannot settle it.
+pub fn backtracked_conflicts<'grammar>(
+    grammar: &'grammar Grammar,
+    states: &[LR1State<'grammar>],
+) -> Vec<(Span, String)> {
+    let guarded = |production: &Production| production.guard.is_some();
+    let conflicts: Vec<_> = states
+        .iter()
+        .flat_map(|state| TokenSet::conflicts(state))
+        .filter(|conflict| {
+            !guarded(conflict.production)
+                && !matches!(conflict.action, Action::Reduce(p) if guarded(p))
+        })
+        .collect();
+    let file_text = Tls::file_text();
+    let mut cx = ErrorReportingCx::new(grammar, states, &conflicts);
+
+    // the tokens of each conflict, grouped by the message without them
+    let mut tokens: Map<(Span, String, bool), Vec<String>> = map();
+    for conflict in token_conflicts(&conflicts) {
+        let other = match conflict.action {
+            Action::Shift(..) => "shifting it".to_string(),
+            Action::Reduce(production) => {
+                format!("the alternative at {}", file_text.location(production.span))
+            }
+        };
+        // the examples do not tell apart two alternatives with the
+        // same symbols, which are ambiguous all the same
+        let ambiguous = match conflict.action {
+            Action::Reduce(production)
+                if production.nonterminal == conflict.production.nonterminal
+                    && production.symbols == conflict.production.symbols =>
+            {
+                true
+            }
+            _ => matches!(
+                cx.classify(&conflict),
+                ConflictClassification::Ambiguity { .. }
+                    | ConflictClassification::Precedence { .. }
+            ),
+        };
+        let token = match conflict.lookahead {
+            Token::Terminal(ref terminal) => terminal.to_string(),
+            Token::EOF => "the end of the input".to_string(),
+            Token::Error => "an error".to_string(),
+        };
+        let tokens = tokens
+            .entry((conflict.production.span, other, ambiguous))
+            .or_insert_with(Vec::new);
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+
+    tokens
+        .into_iter()
+        .map(|((span, other, ambiguous), tokens)| {
+            let message = if ambiguous {
+                format!(
+                    "on {}, this alternative conflicts with {}, and the grammar is ambiguous \
+                     there: trying both on the next tokens cannot settle it, so the parser \
+                     takes the first that gets through them",
+                    tokens.join(" or "),
+                    other
+                )
+            } else {
+                format!(
+                    "on {}, this alternative conflicts with {}; `#[backtrack]` keeps the \
+                     conflict, and the parser settles it by trying both on the next tokens",
+                    tokens.join(" or "),
+                    other
+                )
+            };
+            (span, message)
+        })
+        .collect()
+}
+
+/// How many nonterminals `report_too_many_states` lists.
+const TOO_MANY_STATES_LISTED: usize = 5;
+
+/// Reports that building the parser stopped at `states`, listing the
+/// nonterminals that the most states are in the middle of parsing: the
+/// ones whose rules, or macro instantiations, multiply the states.
+fn report_too_many_states(grammar: &Grammar, states: &[LR1State]) -> Message {
+    let (user_nt, start_nt) = grammar
+        .start_nonterminals
+        .iter()
+        .find(|&(_, start_nt)| {
+            states[0].items.vec.iter().any(|item| item.production.nonterminal == *start_nt)
+        })
+        .unwrap();
+    let mut counts: Map<&NonterminalString, usize> = map();
+    for state in states {
+        let nonterminals: Set<_> = state
+            .items
+            .vec
+            .iter()
+            .filter(|item| item.index > 0 && item.production.nonterminal != *start_nt)
+            .map(|item| &item.production.nonterminal)
+            .collect();
+        for nonterminal in nonterminals {
+            *counts.entry(nonterminal).or_insert(0) += 1;
+        }
+    }
+    let counts = counts
+        .into_iter()
+        .sorted_by(|a, b| b.1.cmp(&a.1))
+        .take(TOO_MANY_STATES_LISTED);
+
+    let mut builder = MessageBuilder::new(grammar.nonterminals[user_nt].span)
+        .heading()
+        .text("Too many states")
+        .end()
+        .body()
+        .begin_lines()
+        .wrap_text(format!(
+            "The parser for `{}` needs more than {} states, the most that `max_states` \
+             allows. The nonterminals that the most states are in the middle of are:",
+            user_nt,
+            Tls::session().max_states.unwrap_or(0),
+        ))
+        .indented();
+    for (nonterminal, count) in counts {
+        let states = if count == 1 { "state" } else { "states" };
+        builder = builder.text(format!("{} ({} {})", nonterminal, count, states));
+    }
+    builder
+        .end()
+        .wrap_text(
+            "Factoring out what the alternatives of these nonterminals have in \
+             common, or inlining fewer nonterminals into them, usually takes fewer states.",
+        )
+        .end()
+        .end()
+}
+
 struct ErrorReportingCx<'cx, 'grammar: 'cx> {
     grammar: &'grammar Grammar,
     first_sets: FirstSets,
@@ -94,6 +264,11 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
     }
 
     fn report_error(&mut self, conflict: &TokenConflict<'grammar>) -> Message {
+        let message = self.report_error_classified(conflict);
+        self.with_expansion_trace(message, conflict)
+    }
+
+    fn report_error_classified(&mut self, conflict: &TokenConflict<'grammar>) -> Message {
         match self.classify(conflict) {
             ConflictClassification::Ambiguity { action, reduce } => {
                 self.report_error_ambiguity(conflict, action, reduce)
@@ -398,6 +573,44 @@ impl<'cx, 'grammar> ErrorReportingCx<'cx, 'grammar> {
             .end()
     }
 
+    /// Adds to `message` where the nonterminals of `conflict` come from,
+    /// if LALRPOP made them up, and has it cite the outermost use,
+    /// which is in a rule of the user's, rather than the expansion.
+    fn with_expansion_trace(
+        &self,
+        message: Message,
+        conflict: &TokenConflict<'grammar>,
+    ) -> Message {
+        let mut nonterminals = vec![&conflict.production.nonterminal];
+        if let Action::Reduce(production) = conflict.action {
+            nonterminals.push(&production.nonterminal);
+        }
+
+        let mut notes: Vec<String> = vec![];
+        let mut span = None;
+        for (index, nonterminal) in nonterminals.into_iter().enumerate() {
+            let trace = self.grammar.expansion_trace(nonterminal);
+            if index == 0 {
+                span = trace.last().map(|&(span, _)| span);
+            }
+            for (_, note) in trace {
+                if !notes.contains(&note) {
+                    notes.push(note);
+                }
+            }
+        }
+
+        if notes.is_empty() {
+            return message;
+        }
+        let span = span.unwrap_or(conflict.production.span);
+        let mut builder = InlineBuilder::new().begin_lines();
+        for note in notes {
+            builder = builder.wrap_text(note);
+        }
+        message.with_note(span, builder.end().end())
+    }
+
     /// Naive error reporting. This is a fallback path which (I think)
     /// never actually executes.
     fn report_error_naive(&self, conflict: &TokenConflict<'grammar>) -> Message {
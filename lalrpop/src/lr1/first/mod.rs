@@ -3,6 +3,7 @@
 use crate::collections::{map, Map};
 use crate::grammar::repr::*;
 use crate::lr1::lookahead::{Token, TokenSet};
+use crate::lr1::tls::Lr1Tls;
 
 #[cfg(test)]
 mod test;
@@ -57,16 +58,10 @@ impl FirstSets {
                             // empty.
                         }
                         Some(set) => {
-                            for lookahead in set.iter() {
-                                match lookahead {
-                                    Token::EOF => {
-                                        empty_prod = true;
-                                    }
-                                    Token::Error | Token::Terminal(_) => {
-                                        result.insert(lookahead);
-                                    }
-                                }
-                            }
+                            // `result` has no EOF of its own yet, so
+                            // any EOF comes from `set`
+                            result.union_with(set);
+                            empty_prod = result.take_eof();
                         }
                     }
                     if !empty_prod {
@@ -82,6 +77,12 @@ impl FirstSets {
         result
     }
 
+    /// The FIRST set of `nt`, with EOF if it may derive epsilon, as
+    /// for `first0`.
+    pub fn first_set(&self, nt: &NonterminalString) -> TokenSet {
+        self.map.get(nt).cloned().unwrap_or_else(TokenSet::new)
+    }
+
     pub fn first1(&self, symbols: &[Symbol], lookahead: &TokenSet) -> TokenSet {
         let mut set = self.first0(symbols);
 
@@ -95,3 +96,81 @@ impl FirstSets {
         set
     }
 }
+
+/// The terminals that can follow each nonterminal. EOF follows the
+/// start nonterminals, and whatever they end with.
+pub struct FollowSets {
+    map: Map<NonterminalString, TokenSet>,
+}
+
+impl FollowSets {
+    pub fn new(grammar: &Grammar, first_sets: &FirstSets) -> FollowSets {
+        let mut this = FollowSets { map: map() };
+        for start in grammar.start_nonterminals.values() {
+            this.map.insert(start.clone(), TokenSet::eof());
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in grammar.nonterminals.values().flat_map(|p| &p.productions) {
+                let follow = this.follow_set(&production.nonterminal);
+                for (index, symbol) in production.symbols.iter().enumerate() {
+                    if let Symbol::Nonterminal(ref nt) = *symbol {
+                        let set = first_sets.first1(&production.symbols[index + 1..], &follow);
+                        let follow_set = this.map.entry(nt.clone()).or_insert_with(TokenSet::new);
+                        changed |= follow_set.union_with(&set);
+                    }
+                }
+            }
+        }
+        this
+    }
+
+    pub fn follow_set(&self, nt: &NonterminalString) -> TokenSet {
+        self.map.get(nt).cloned().unwrap_or_else(TokenSet::new)
+    }
+}
+
+/// The FIRST and FOLLOW sets of a nonterminal, for reports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FirstFollow {
+    pub first: Vec<TerminalString>,
+
+    /// True if the nonterminal may derive the empty string.
+    pub nullable: bool,
+
+    pub follow: Vec<TerminalString>,
+
+    /// True if the end of the input may follow the nonterminal.
+    pub end_of_input: bool,
+}
+
+/// The FIRST and FOLLOW sets of each nonterminal of `grammar`.
+pub fn first_follow_sets(grammar: &Grammar) -> Map<NonterminalString, FirstFollow> {
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let first_sets = FirstSets::new(grammar);
+    let follow_sets = FollowSets::new(grammar, &first_sets);
+    let terminals = |set: &TokenSet| -> Vec<TerminalString> {
+        set.iter()
+            .filter_map(|token| match token {
+                Token::Terminal(terminal) => Some(terminal),
+                Token::EOF | Token::Error => None,
+            })
+            .collect()
+    };
+    grammar
+        .nonterminals
+        .keys()
+        .map(|nt| {
+            let first = first_sets.first_set(nt);
+            let follow = follow_sets.follow_set(nt);
+            let sets = FirstFollow {
+                first: terminals(&first),
+                nullable: first.contains_eof(),
+                follow: terminals(&follow),
+                end_of_input: follow.contains_eof(),
+            };
+            (nt.clone(), sets)
+        })
+        .collect()
+}
@@ -1,4 +1,4 @@
-use super::FirstSets;
+use super::{first_follow_sets, FirstSets};
 use crate::grammar::repr::*;
 use crate::lr1::lookahead::Token::EOF;
 use crate::lr1::lookahead::{Token, TokenSet};
@@ -91,3 +91,31 @@ fn basic_first0() {
 
     assert_eq!(first0(&first_sets, &[nt("X")]), vec![la("E")]);
 }
+
+#[test]
+fn first_and_follow() {
+    let grammar = normalized_grammar(
+        r#"
+    grammar;
+    pub A = B "C" D;
+    B: Option<u32> = {
+        "D" => Some(1),
+        => None
+    };
+    D = "E"?;
+"#,
+    );
+    let sets = first_follow_sets(&grammar);
+
+    let b = &sets[&NonterminalString(Atom::from("B"))];
+    assert_eq!(b.first, vec![TerminalString::quoted(Atom::from("D"))]);
+    assert!(b.nullable);
+    assert_eq!(b.follow, vec![TerminalString::quoted(Atom::from("C"))]);
+    assert!(!b.end_of_input);
+
+    let d = &sets[&NonterminalString(Atom::from("D"))];
+    assert_eq!(d.first, vec![TerminalString::quoted(Atom::from("E"))]);
+    assert!(d.nullable);
+    assert!(d.follow.is_empty());
+    assert!(d.end_of_input);
+}
@@ -58,6 +58,12 @@ impl<'grammar> LaneTableConstruct<'grammar> {
                     // algorithm.
                     states
                 }
+                Err(error) if error.exceeds_max_states() => {
+                    return Err(TableConstructionError {
+                        states: self.promote_lr0_states(error.states),
+                        conflicts: vec![],
+                    });
+                }
                 Err(TableConstructionError { states, .. }) => states,
             }
         };
@@ -73,6 +79,13 @@ impl<'grammar> LaneTableConstruct<'grammar> {
             }
 
             match self.resolve_inconsistencies(&mut states, StateIndex(i)) {
+                // splitting states to resolve them adds states
+                Ok(()) if build::exceeds_max_states(states.len()) => {
+                    return Err(TableConstructionError {
+                        states,
+                        conflicts: vec![],
+                    });
+                }
                 Ok(()) => {}
                 Err(_) => {
                     // We failed because of irreconcilable conflicts
@@ -202,10 +202,14 @@ impl TokenSet {
         self.bit_set.insert(bit)
     }
 
+    /// Adds the tokens of `set`, returning true if any were new. This
+    /// is word by word, without counting the tokens of either set.
     pub fn union_with(&mut self, set: &TokenSet) -> bool {
-        let len = self.len();
+        if set.bit_set.is_subset(&self.bit_set) {
+            return false;
+        }
         self.bit_set.union_with(&set.bit_set);
-        self.len() != len
+        true
     }
 
     pub fn intersection(&self, set: &TokenSet) -> TokenSet {
@@ -239,6 +243,10 @@ impl TokenSet {
         !self.is_disjoint(other)
     }
 
+    pub fn is_subset(&self, other: &TokenSet) -> bool {
+        self.bit_set.is_subset(&other.bit_set)
+    }
+
     pub fn iter(&self) -> TokenSetIter<'_> {
         TokenSetIter {
             bit_set: self.bit_set.iter(),
@@ -0,0 +1,104 @@
+//! Finds the state that each sentence of a `.messages` file ends up
+//! in, so that the generated parser can give the message of the
+//! sentence for errors in that state.
+
+use crate::collections::Map;
+use crate::grammar::repr::*;
+use crate::lr1::core::{LR1State, StateIndex};
+use crate::lr1::lookahead::Token;
+use crate::util::Sep;
+
+#[cfg(test)]
+mod test;
+
+/// The states that have a message, with their message.
+pub type ErrorStates<'grammar> = Vec<(StateIndex, &'grammar str)>;
+
+/// The states of `states`, built for the public nonterminal `user_nt`,
+/// that have a message, with their message. Returns the span and
+/// message of each sentence that is accepted, has an error before its
+/// end, or ends in the same state as a sentence with another message.
+pub fn error_states<'grammar>(
+    grammar: &'grammar Grammar,
+    user_nt: &NonterminalString,
+    states: &[LR1State<'grammar>],
+) -> Result<ErrorStates<'grammar>, Vec<(Span, String)>> {
+    let mut messages: Map<StateIndex, &ErrorMessage> = Map::new();
+    let mut errors = vec![];
+    for entry in grammar
+        .error_messages
+        .iter()
+        .filter(|entry| entry.start == *user_nt)
+    {
+        let state = match error_state(states, &entry.sentence) {
+            Ok(state) => state,
+            Err(message) => {
+                errors.push((entry.span, message));
+                continue;
+            }
+        };
+        match messages.get(&state) {
+            Some(other) if other.message != entry.message => errors.push((
+                entry.span,
+                format!(
+                    "this sentence ends in the same state as `{}: {}`, which has another message",
+                    other.start,
+                    Sep(" ", &other.sentence)
+                ),
+            )),
+            Some(_) => {}
+            None => {
+                messages.insert(state, entry);
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(messages
+        .into_iter()
+        .map(|(state, entry)| (state, &entry.message[..]))
+        .collect())
+}
+
+/// Parses `sentence` and returns the state in which the parser finds
+/// an error, which must be at its last terminal or at the end of the
+/// input after it.
+fn error_state(states: &[LR1State<'_>], sentence: &[TerminalString]) -> Result<StateIndex, String> {
+    let mut stack = vec![StateIndex(0)];
+    let tokens = sentence
+        .iter()
+        .cloned()
+        .map(Token::Terminal)
+        .chain(Some(Token::EOF));
+    for (index, token) in tokens.enumerate() {
+        loop {
+            let state = &states[stack.last().unwrap().0];
+            if let Token::Terminal(ref terminal) = token {
+                if let Some(&next) = state.shifts.get(terminal) {
+                    stack.push(next);
+                    break;
+                }
+            }
+            let production = match state.reductions.iter().find(|r| r.0.contains(&token)) {
+                Some(&(_, production)) => production,
+                None if index + 1 >= sentence.len() => return Ok(state.index),
+                None => {
+                    return Err(format!(
+                        "the parser finds an error at `{}`, before the end of this sentence",
+                        sentence[index]
+                    ))
+                }
+            };
+            stack.truncate(stack.len() - production.symbols.len());
+            let top = &states[stack.last().unwrap().0];
+            match top.gotos.get(&production.nonterminal) {
+                Some(&next) => stack.push(next),
+                // reducing the start symbol: the input is accepted
+                None => return Err("this sentence is accepted by the parser".to_string()),
+            }
+        }
+    }
+    unreachable!("the end of the input is either accepted or an error")
+}
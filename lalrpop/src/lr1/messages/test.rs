@@ -0,0 +1,106 @@
+use crate::grammar::repr::*;
+use crate::lr1::build_states;
+use crate::lr1::tls::Lr1Tls;
+use crate::test_util::normalized_grammar;
+use crate::tls::Tls;
+use string_cache::DefaultAtom as Atom;
+
+use super::error_states;
+
+fn nt(t: &str) -> NonterminalString {
+    NonterminalString(Atom::from(t))
+}
+
+fn term(t: &str) -> TerminalString {
+    TerminalString::quoted(Atom::from(t))
+}
+
+/// Gives the grammar the message `message` for each of `sentences`,
+/// on the nonterminal `Expr`, and returns the messages by state.
+fn messages(sentences: &[(&[&str], &str)]) -> Result<Vec<String>, Vec<String>> {
+    let _tls = Tls::test();
+    let mut grammar = normalized_grammar(
+        r#"
+grammar;
+pub Expr: () = {
+    "(" Expr ")",
+    "(" Expr "," Expr ")",
+    "x",
+};
+"#,
+    );
+    grammar.error_messages = sentences
+        .iter()
+        .map(|&(sentence, message)| ErrorMessage {
+            span: Span(0, 0),
+            start: nt("Expr"),
+            sentence: sentence.iter().map(|t| term(t)).collect(),
+            message: message.to_string(),
+        })
+        .collect();
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = grammar.start_nonterminals[&nt("Expr")].clone();
+    let states = build_states(&grammar, start_nt).unwrap();
+    match error_states(&grammar, &nt("Expr"), &states) {
+        Ok(states) => Ok(states
+            .into_iter()
+            .map(|(_, message)| message.to_string())
+            .collect()),
+        Err(errors) => Err(errors.into_iter().map(|(_, message)| message).collect()),
+    }
+}
+
+#[test]
+fn distinct_states() {
+    let mut result = messages(&[
+        (&["("], "expected an expression"),
+        (&["(", "x"], "missing `)`"),
+    ])
+    .unwrap();
+    result.sort();
+    assert_eq!(result, vec!["expected an expression", "missing `)`"]);
+}
+
+#[test]
+fn same_state_same_message() {
+    let result = messages(&[
+        (&["(", "x"], "missing `)`"),
+        (&["(", "(", "x"], "missing `)`"),
+    ]);
+    assert_eq!(result, Ok(vec!["missing `)`".to_string()]));
+}
+
+#[test]
+fn same_state_other_message() {
+    let result = messages(&[
+        (&["(", "x"], "missing `)`"),
+        (&["(", "(", "x"], "unclosed `(`"),
+    ]);
+    assert_eq!(
+        result,
+        Err(vec![
+            "this sentence ends in the same state as `Expr: \"(\" \"x\"`, which has another message"
+                .to_string()
+        ])
+    );
+}
+
+#[test]
+fn accepted() {
+    let result = messages(&[(&["(", "x", ")"], "oops")]);
+    assert_eq!(
+        result,
+        Err(vec!["this sentence is accepted by the parser".to_string()])
+    );
+}
+
+#[test]
+fn early_error() {
+    let result = messages(&[(&[")", "x"], "oops")]);
+    assert_eq!(
+        result,
+        Err(vec![
+            "the parser finds an error at `\")\"`, before the end of this sentence".to_string()
+        ])
+    );
+}
@@ -1,27 +1,41 @@
 //! Naive LR(1) generation algorithm.
 
 use crate::grammar::repr::*;
+use crate::lr1::lookahead::{Lookahead, TokenSet};
 
+mod ambiguity;
 mod build;
 mod build_lalr;
 pub mod codegen;
 mod core;
+mod diff;
 mod error;
 mod example;
 mod first;
 mod lane_table;
 mod lookahead;
+mod messages;
 mod report;
+mod shadowed;
 mod state_graph;
+mod tables;
 mod tls;
 mod trace;
+use std::collections::VecDeque;
 use std::io::{self, Write};
 
 #[cfg(test)]
 mod interpret;
 
+pub use self::ambiguity::write_ambiguities;
 pub use self::core::{LR1Result, LR1TableConstructionError};
-pub use self::error::report_error;
+pub use self::error::{backtracked_conflicts, report_error};
+pub use self::diff::{write_diff, Automaton};
+pub use self::first::first_follow_sets;
+pub use self::messages::error_states;
+pub use self::report::HtmlReport;
+pub use self::shadowed::shadowed_alternatives;
+pub use self::tables::parse_tables;
 pub use self::tls::Lr1Tls;
 
 pub fn build_states<'grammar>(
@@ -39,6 +53,49 @@ pub fn build_states<'grammar>(
     Ok(lr1_states)
 }
 
+/// Retries a failed construction for a grammar that resolves some
+/// conflicts at runtime: all of them with `#[backtrack]`, otherwise
+/// those involving a production with a `when` guard. Returns the
+/// states with those conflicts left in, or an error listing the
+/// others. The states of LALR grammars are complete even on failure;
+/// otherwise, since lane table construction gives up early, we fall
+/// back to the canonical LR(1) states.
+pub fn accept_conflicts<'grammar>(
+    grammar: &'grammar Grammar,
+    start: NonterminalString,
+    error: LR1TableConstructionError<'grammar>,
+) -> LR1Result<'grammar> {
+    if error.exceeds_max_states() {
+        return Err(error);
+    }
+    let mut lr1_states = if grammar.algorithm.lalr {
+        error.states
+    } else {
+        build::build_conflicted_lr1_states(grammar, start)?
+    };
+
+    let guarded = |production: &Production| production.guard.is_some();
+    let conflicts: Vec<_> = lr1_states
+        .iter()
+        .flat_map(|s| TokenSet::conflicts(s))
+        .filter(|conflict| {
+            !grammar.backtrack
+                && !guarded(conflict.production)
+                && !matches!(conflict.action, core::Action::Reduce(p) if guarded(p))
+        })
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(core::TableConstructionError {
+            states: lr1_states,
+            conflicts,
+        });
+    }
+
+    rewrite_state_indices(grammar, &mut lr1_states);
+
+    Ok(lr1_states)
+}
+
 pub fn generate_report<'grammar, W: Write + 'grammar>(
     out: &'grammar mut W,
     lr1result: &LR1Result<'grammar>,
@@ -47,7 +104,9 @@ pub fn generate_report<'grammar, W: Write + 'grammar>(
 }
 
 /// By packing all states which start a reduction we can generate a smaller goto table as any
-/// states not starting a reduction will not need a row
+/// states not starting a reduction will not need a row. Within each group, states are numbered
+/// in breadth-first order from the initial state, so that the states the parser moves to from
+/// any one state are numbered close together, and so are their rows in the tables.
 fn rewrite_state_indices(grammar: &Grammar, states: &mut [core::LR1State]) {
     let mut start_states = vec![false; states.len()];
     for (index, state) in states.iter_mut().enumerate() {
@@ -61,8 +120,27 @@ fn rewrite_state_indices(grammar: &Grammar, states: &mut [core::LR1State]) {
         }
     }
 
-    // Since the sort is stable and we put starting states first, the initial state is still 0
-    states.sort_by_key(|state| !start_states[state.index.0]);
+    // Unreachable states, if there are any, keep their order after the others
+    let mut rank: Vec<usize> = (states.len()..2 * states.len()).collect();
+    let mut queue = VecDeque::new();
+    if !states.is_empty() {
+        rank[0] = 0;
+        queue.push_back(0);
+    }
+    let mut next_rank = 1;
+    while let Some(index) = queue.pop_front() {
+        let state = &states[index];
+        for successor in state.shifts.values().chain(state.gotos.values()) {
+            if rank[successor.0] >= states.len() {
+                rank[successor.0] = next_rank;
+                next_rank += 1;
+                queue.push_back(successor.0);
+            }
+        }
+    }
+
+    // The initial state has a goto and comes first in breadth-first order, so it is still 0
+    states.sort_by_key(|state| (!start_states[state.index.0], rank[state.index.0]));
 
     let mut state_rewrite = vec![0; states.len()];
     for (new_index, state) in states.iter_mut().enumerate() {
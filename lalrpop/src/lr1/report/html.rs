@@ -0,0 +1,275 @@
+//! A report on the automata of a grammar as a single HTML page, for
+//! grammars with too many states to read through the text report. Each
+//! public nonterminal gets a section with its states, which link to the
+//! states they shift or go to and to those that lead to them. States
+//! with conflicts are highlighted, and a search box filters the states
+//! by their text. The page has no outside dependencies.
+
+use crate::collections::*;
+use crate::grammar::repr::*;
+use crate::lr1::core::*;
+use crate::lr1::lookahead::TokenSet;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+static HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>LALRPOP automaton report</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+code, .items { font-family: monospace; }
+.state { border: 1px solid #ccc; border-radius: 4px; margin: 1em 0; padding: 0 1em; }
+.state:target { border-color: #36c; box-shadow: 0 0 0 2px #36c; }
+.state.conflict { background: #fff0f0; border-color: #c33; }
+.state h3 { margin: 0.5em 0; }
+.lookahead { color: #777; padding-left: 2em; }
+.conflicts { color: #c33; }
+ul { list-style: none; padding-left: 1em; }
+.hidden { display: none; }
+</style>
+</head>
+<body>
+<h1>LALRPOP automaton report</h1>
+"#;
+
+static TAIL: &str = r#"<script>
+document.querySelectorAll("section").forEach(function (section) {
+    var search = section.querySelector(".search");
+    var conflicts = section.querySelector(".only-conflicts");
+    function filter() {
+        var query = search.value.toLowerCase();
+        section.querySelectorAll(".state").forEach(function (state) {
+            var shown = state.textContent.toLowerCase().indexOf(query) >= 0
+                && (!conflicts.checked || state.classList.contains("conflict"));
+            state.classList.toggle("hidden", !shown);
+        });
+    }
+    search.addEventListener("input", filter);
+    conflicts.addEventListener("change", filter);
+});
+window.addEventListener("hashchange", function () {
+    var state = document.getElementById(location.hash.slice(1));
+    if (state) {
+        state.classList.remove("hidden");
+    }
+});
+</script>
+</body>
+</html>
+"#;
+
+/// The sections of the page, one per public nonterminal.
+#[derive(Default)]
+pub struct HtmlReport {
+    sections: Vec<String>,
+}
+
+impl HtmlReport {
+    pub fn new() -> Self {
+        HtmlReport::default()
+    }
+
+    /// Adds the section of the public nonterminal `nonterminal`, whose
+    /// automaton is `lr1result`.
+    pub fn add(&mut self, nonterminal: &NonterminalString, lr1result: &LR1Result<'_>) {
+        let (states, conflicts) = match lr1result {
+            Ok(states) => (&states[..], &[][..]),
+            Err(error) => (&error.states[..], &error.conflicts[..]),
+        };
+        let id = self.sections.len();
+
+        let mut conflict_map: Map<StateIndex, Vec<&LR1Conflict<'_>>> = Map::new();
+        for conflict in conflicts {
+            conflict_map
+                .entry(conflict.state)
+                .or_default()
+                .push(conflict);
+        }
+        let mut predecessors: Map<StateIndex, Set<StateIndex>> = Map::new();
+        for state in states {
+            for &next in state.shifts.values().chain(state.gotos.values()) {
+                predecessors.entry(next).or_default().insert(state.index);
+            }
+        }
+
+        let mut html = String::new();
+        let _ = writeln!(
+            html,
+            "<section>\n<h2>Public nonterminal <code>{}</code></h2>",
+            escape(nonterminal)
+        );
+        let _ = write!(html, "<p>{} states", states.len());
+        if !conflict_map.is_empty() {
+            let _ = write!(html, ", {} conflicts, in states", conflicts.len());
+            for state in conflict_map.keys() {
+                let _ = write!(html, " {}", link(id, *state));
+            }
+        }
+        let _ = writeln!(html, "</p>");
+        let _ = writeln!(
+            html,
+            "<p><input class=\"search\" type=\"search\" placeholder=\"Search states\"> \
+             <label><input class=\"only-conflicts\" type=\"checkbox\"> only conflicts</label></p>"
+        );
+
+        for state in states {
+            let state_conflicts = conflict_map.get(&state.index);
+            let _ = writeln!(
+                html,
+                "<div class=\"state{}\" id=\"{}\">\n<h3>State {}</h3>",
+                if state_conflicts.is_some() {
+                    " conflict"
+                } else {
+                    ""
+                },
+                anchor(id, state.index),
+                state.index
+            );
+
+            let _ = writeln!(html, "<table class=\"items\">");
+            for item in &state.items.vec {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td class=\"lookahead\">{}</td></tr>",
+                    escape(&item_text(item)),
+                    escape(&lookahead_text(&item.lookahead))
+                );
+            }
+            let _ = writeln!(html, "</table>");
+
+            if !state.shifts.is_empty() {
+                let _ = writeln!(html, "<h4>Shifts</h4>\n<ul>");
+                for (terminal, &next) in &state.shifts {
+                    let _ = writeln!(
+                        html,
+                        "<li><code>{}</code> shift and goto {}</li>",
+                        escape(terminal),
+                        link(id, next)
+                    );
+                }
+                let _ = writeln!(html, "</ul>");
+            }
+            if !state.reductions.is_empty() {
+                let _ = writeln!(html, "<h4>Reductions</h4>\n<ul>");
+                for (lookahead, production) in &state.reductions {
+                    let _ = writeln!(
+                        html,
+                        "<li><code>{}</code> <span class=\"lookahead\">{}</span></li>",
+                        escape(&production_text(production)),
+                        escape(&lookahead_text(lookahead))
+                    );
+                }
+                let _ = writeln!(html, "</ul>");
+            }
+            if !state.gotos.is_empty() {
+                let _ = writeln!(html, "<h4>Gotos</h4>\n<ul>");
+                for (nonterminal, &next) in &state.gotos {
+                    let _ = writeln!(
+                        html,
+                        "<li><code>{}</code> goto {}</li>",
+                        escape(nonterminal),
+                        link(id, next)
+                    );
+                }
+                let _ = writeln!(html, "</ul>");
+            }
+            if let Some(previous) = predecessors.get(&state.index) {
+                let _ = write!(html, "<p>Reached from");
+                for &previous in previous {
+                    let _ = write!(html, " {}", link(id, previous));
+                }
+                let _ = writeln!(html, "</p>");
+            }
+            if let Some(state_conflicts) = state_conflicts {
+                let _ = writeln!(html, "<h4>Conflicts</h4>\n<ul class=\"conflicts\">");
+                for conflict in state_conflicts {
+                    let other = match conflict.action {
+                        Action::Shift(ref terminal, next) => {
+                            format!(
+                                "shift <code>{}</code> ({})",
+                                escape(terminal),
+                                link(id, next)
+                            )
+                        }
+                        Action::Reduce(production) => format!(
+                            "reduce <code>{}</code>",
+                            escape(&production_text(production))
+                        ),
+                    };
+                    let _ = writeln!(
+                        html,
+                        "<li>on <code>{}</code>: reduce <code>{}</code> or {}</li>",
+                        escape(&lookahead_text(&conflict.lookahead)),
+                        escape(&production_text(conflict.production)),
+                        other
+                    );
+                }
+                let _ = writeln!(html, "</ul>");
+            }
+            let _ = writeln!(html, "</div>");
+        }
+        let _ = writeln!(html, "</section>");
+        self.sections.push(html);
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(HEAD.as_bytes())?;
+        for section in &self.sections {
+            out.write_all(section.as_bytes())?;
+        }
+        out.write_all(TAIL.as_bytes())
+    }
+}
+
+fn anchor(section: usize, state: StateIndex) -> String {
+    format!("s{}-{}", section, state.0)
+}
+
+fn link(section: usize, state: StateIndex) -> String {
+    format!("<a href=\"#{}\">{}</a>", anchor(section, state), state.0)
+}
+
+fn item_text(item: &LR1Item<'_>) -> String {
+    let symbols = &item.production.symbols;
+    let mut text = format!("{} ->", item.production.nonterminal);
+    for symbol in &symbols[..item.index] {
+        let _ = write!(text, " {}", symbol);
+    }
+    text.push_str(" .");
+    for symbol in &symbols[item.index..] {
+        let _ = write!(text, " {}", symbol);
+    }
+    text
+}
+
+fn production_text(production: &Production) -> String {
+    let mut text = format!("{} ->", production.nonterminal);
+    for symbol in &production.symbols {
+        let _ = write!(text, " {}", symbol);
+    }
+    text
+}
+
+fn lookahead_text(lookahead: &TokenSet) -> String {
+    lookahead
+        .iter()
+        .map(|token| token.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape<T: ToString + ?Sized>(text: &T) -> String {
+    let mut escaped = String::new();
+    for c in text.to_string().chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -6,6 +6,10 @@ use std::io::{self, Write};
 
 use super::lookahead::*;
 
+mod html;
+
+pub use self::html::HtmlReport;
+
 pub fn generate_report<'grammar, W: Write + 'grammar>(
     out: &'grammar mut W,
     lr1result: &LR1Result<'grammar>,
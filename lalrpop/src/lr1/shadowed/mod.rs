@@ -0,0 +1,51 @@
+//! Finds the alternatives that the parser never picks. This happens in
+//! the conflicted states that `#[backtrack]` keeps, when two alternatives
+//! of a nonterminal have the same symbols, as with `"a" "b"` written
+//! twice with different actions: reducing either leaves the same stack
+//! behind, so the trials cannot tell them apart, and the one tried first
+//! always wins.
+//!
+//! Only alternatives with identical symbols are compared. One that merely
+//! matches a subset of what another does, such as `"a" B` beside
+//! `"a" B?`, is not reported: the two reduce different symbols, so the
+//! trials can still tell them apart.
+
+use crate::collections::Map;
+use crate::grammar::repr::*;
+use crate::lr1::core::LR1State;
+use crate::lr1::lookahead::TokenSet;
+
+#[cfg(test)]
+mod test;
+
+/// Records in `alternatives`, for the span of each alternative that
+/// `states` reduce, `None` if the parser can pick it in some state,
+/// and otherwise `Some` span of an alternative that it always picks
+/// instead. Call it for the states of each public nonterminal in turn.
+pub fn shadowed_alternatives(states: &[LR1State], alternatives: &mut Map<Span, Option<Span>>) {
+    for state in states {
+        for (index, &(ref lookahead, production)) in state.reductions.iter().enumerate() {
+            // the guarded reductions are tried before the others
+            let mut covered = TokenSet::new();
+            let mut shadowing = None;
+            for &(ref earlier_lookahead, earlier) in &state.reductions[..index] {
+                if earlier.guard.is_none()
+                    && production.guard.is_none()
+                    && earlier.span != production.span
+                    && earlier.nonterminal == production.nonterminal
+                    && earlier.symbols == production.symbols
+                    && earlier_lookahead.is_intersecting(lookahead)
+                {
+                    covered.union_with(earlier_lookahead);
+                    shadowing = shadowing.or(Some(earlier.span));
+                }
+            }
+            let shadowed = shadowing.filter(|_| lookahead.is_subset(&covered));
+
+            let entry = alternatives.entry(production.span).or_insert(shadowed);
+            if shadowed.is_none() {
+                *entry = None;
+            }
+        }
+    }
+}
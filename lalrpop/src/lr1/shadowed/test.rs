@@ -0,0 +1,61 @@
+use crate::collections::Map;
+use crate::grammar::repr::*;
+use crate::lr1::tls::Lr1Tls;
+use crate::lr1::{accept_conflicts, build_states};
+use crate::test_util::normalized_grammar;
+use crate::tls::Tls;
+use string_cache::DefaultAtom as Atom;
+
+use super::shadowed_alternatives;
+
+/// The alternatives of `text` that are never picked, with the one
+/// picked instead.
+fn shadowed(text: &str) -> Vec<(&str, &str)> {
+    let _tls = Tls::test();
+    let grammar = normalized_grammar(text);
+    let _lr1_tls = Lr1Tls::install(grammar.terminals.clone());
+    let start_nt = grammar.start_nonterminals[&NonterminalString(Atom::from("S"))].clone();
+    let states = build_states(&grammar, start_nt.clone())
+        .or_else(|error| accept_conflicts(&grammar, start_nt, error))
+        .unwrap();
+    let mut alternatives = Map::new();
+    shadowed_alternatives(&states, &mut alternatives);
+    alternatives
+        .into_iter()
+        .filter_map(|(span, shadowing)| {
+            shadowing.map(|shadowing| (&text[span.0..span.1], &text[shadowing.0..shadowing.1]))
+        })
+        .collect()
+}
+
+#[test]
+fn duplicate_alternative() {
+    let text = r#"#[backtrack] grammar; pub S: () = { "a" "b", "c", "a" "b" => () };"#;
+    assert_eq!(shadowed(text), vec![(r#""a" "b" => () "#, r#""a" "b""#)]);
+}
+
+#[test]
+fn optional_symbol() {
+    // whichever `"a" B` is tried first, `"a" B?` is still picked for `"a"`
+    let text = r#"#[backtrack] grammar; pub S: () = { "a" B?, "a" B }; B: () = "b";"#;
+    assert!(shadowed(text).is_empty());
+    let text = r#"#[backtrack] grammar; pub S: () = { "a" B, "a" B? }; B: () = "b";"#;
+    assert!(shadowed(text).is_empty());
+}
+
+#[test]
+fn different_lengths() {
+    let text = r#"
+#[backtrack] grammar;
+pub S: () = { X "x", Y };
+X: () = "a";
+Y: () = "a" "x";
+"#;
+    assert!(shadowed(text).is_empty());
+}
+
+#[test]
+fn without_conflicts() {
+    let text = r#"grammar; pub S: () = { "a" S, "b" };"#;
+    assert!(shadowed(text).is_empty());
+}
@@ -0,0 +1,129 @@
+//! Converts LR(1) states into the tables of `lalrpop_util::interpret`,
+//! which parses against them at runtime instead of with generated code.
+
+use crate::collections::Map;
+use crate::grammar::parse_tree::{InternToken, MatchMapping};
+use crate::grammar::repr::*;
+use crate::lexer::intern_token;
+use crate::lr1::core::LR1State;
+use crate::lr1::lookahead::Token;
+use crate::util::Sep;
+use lalrpop_util::interpret::{self, Action, ParseTables};
+
+#[cfg(test)]
+mod test;
+
+pub fn parse_tables(
+    grammar: &Grammar,
+    start_nt: &NonterminalString,
+    states: &[LR1State<'_>],
+) -> ParseTables {
+    // the interpreter does not recover from errors, so it never sees `!`
+    let terminals: Vec<&TerminalString> = grammar
+        .terminals
+        .all
+        .iter()
+        .filter(|&terminal| *terminal != TerminalString::Error)
+        .collect();
+    let nonterminals: Map<&NonterminalString, usize> =
+        grammar.nonterminals.keys().zip(0..).collect();
+    let productions: Vec<&Production> = grammar
+        .nonterminals
+        .values()
+        .flat_map(|nt| &nt.productions)
+        .collect();
+    let reduce_indices: Map<&Production, usize> = productions.iter().cloned().zip(0..).collect();
+
+    let action = |state: &LR1State<'_>, token: &Token| {
+        if let Token::Terminal(ref terminal) = *token {
+            if let Some(next) = state.shifts.get(terminal) {
+                return Action::Shift(next.0);
+            }
+        }
+        state
+            .reductions
+            .iter()
+            .find(|&(lookahead, _)| lookahead.contains(token))
+            .map_or(Action::Error, |&(_, production)| {
+                Action::Reduce(reduce_indices[production])
+            })
+    };
+
+    ParseTables {
+        terminals: terminals
+            .iter()
+            .map(|terminal| terminal.to_string())
+            .collect(),
+        nonterminals: nonterminals.keys().map(|nt| nt.to_string()).collect(),
+        productions: productions
+            .iter()
+            .map(|production| interpret::Production {
+                nonterminal: nonterminals[&production.nonterminal],
+                len: production.symbols.len(),
+                text: format!(
+                    "{} = {}",
+                    production.nonterminal,
+                    Sep(" ", &production.symbols)
+                ),
+            })
+            .collect(),
+        start_production: reduce_indices[&grammar.productions_for(start_nt)[0]],
+        actions: states
+            .iter()
+            .map(|state| {
+                terminals
+                    .iter()
+                    .map(|&terminal| action(state, &Token::Terminal(terminal.clone())))
+                    .collect()
+            })
+            .collect(),
+        eof_actions: states
+            .iter()
+            .map(|state| action(state, &Token::EOF))
+            .collect(),
+        gotos: states
+            .iter()
+            .map(|state| {
+                nonterminals
+                    .keys()
+                    .map(|&nt| state.gotos.get(nt).map(|next| next.0))
+                    .collect()
+            })
+            .collect(),
+        lexer: match grammar.intern_token {
+            Some(ref intern_token) => lexer(intern_token, &terminals),
+            None => vec![],
+        },
+    }
+}
+
+/// The regular expressions of the generated lexer, each with the index
+/// of the terminal it lexes, or `None` for text to skip.
+fn lexer(
+    intern_token: &InternToken,
+    terminals: &[&TerminalString],
+) -> Vec<(String, Option<usize>)> {
+    let mut entries: Vec<_> = intern_token::regexes(intern_token)
+        .into_iter()
+        .zip(&intern_token.match_entries)
+        .map(|((regex, _), entry)| {
+            let terminal = match entry.user_name {
+                MatchMapping::Terminal(ref name) => {
+                    Some(terminals.iter().position(|&t| t == name).unwrap())
+                }
+                MatchMapping::Skip => None,
+            };
+            (regex, terminal)
+        })
+        .collect();
+
+    // like the generated lexer, skip whitespace unless told otherwise
+    if !intern_token
+        .match_entries
+        .iter()
+        .any(|entry| entry.user_name == MatchMapping::Skip)
+    {
+        entries.push((r"^(\s*)".to_string(), None));
+    }
+    entries
+}
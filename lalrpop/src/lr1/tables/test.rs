@@ -0,0 +1,123 @@
+use crate::build_tables;
+use lalrpop_util::interpret::{ParseTables, Tree};
+use lalrpop_util::ParseError;
+use std::fmt::Display;
+
+fn sexp<L, T: Display>(tables: &ParseTables, tree: &Tree<L, T>) -> String {
+    match *tree {
+        Tree::Token(_, ref token, _) => token.to_string(),
+        Tree::Node {
+            production,
+            ref children,
+            ..
+        } => {
+            let nonterminal = tables.productions[production].nonterminal;
+            let mut s = format!("({}", tables.nonterminals[nonterminal]);
+            for child in children {
+                s.push(' ');
+                s.push_str(&sexp(tables, child));
+            }
+            s.push(')');
+            s
+        }
+    }
+}
+
+const CALCULATOR: &str = r#"
+grammar;
+
+pub Expr: i32 = {
+    <l:Expr> "+" <r:Term> => l + r,
+    Term,
+};
+
+Term: i32 = {
+    <l:Term> "*" <r:Num> => l * r,
+    Num,
+};
+
+Num: i32 = r"[0-9]+" => i32::from_str(<>).unwrap();
+"#;
+
+#[cfg(feature = "lexer")]
+#[test]
+fn parse_with_generated_lexer() {
+    let tables = build_tables(CALCULATOR).unwrap();
+    let expr = &tables["Expr"];
+
+    let tree = expr.parse_str("1 + 2 * 3").unwrap();
+    assert_eq!(
+        sexp(expr, &tree),
+        "(Expr (Expr (Term (Num 1))) + (Term (Term (Num 2)) * (Num 3)))"
+    );
+    assert_eq!((*tree.start(), *tree.end()), (0, 9));
+
+    match expr.parse_str("1 + * 3") {
+        Err(ParseError::UnrecognizedToken {
+            token: (4, _, 5),
+            expected,
+        }) => assert_eq!(expected, vec![r###"r#"[0-9]+"#"###.to_string()]),
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn parse_extern_tokens() {
+    let tables = build_tables(
+        r#"
+grammar;
+
+extern {
+    enum Tok {
+        "(" => Tok::LParen,
+        ")" => Tok::RParen,
+        "x" => Tok::X,
+    }
+}
+
+pub Parens: () = {
+    "(" Parens ")",
+    "x",
+};
+"#,
+    )
+    .unwrap();
+    let parens = &tables["Parens"];
+
+    let terminals: Vec<usize> = ["\"(\"", "\"x\"", "\")\""]
+        .iter()
+        .map(|name| parens.terminal_index(name).unwrap())
+        .collect();
+    let tokens = vec!['(', 'x', ')']
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| Ok::<_, ParseError<usize, char, ()>>((i, c, i + 1)));
+    let tree = parens
+        .parse(tokens, |&c| match c {
+            '(' => Some(terminals[0]),
+            'x' => Some(terminals[1]),
+            ')' => Some(terminals[2]),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(sexp(parens, &tree), "(Parens ( (Parens x) ))");
+}
+
+#[test]
+fn conflict() {
+    let text = r#"
+grammar;
+
+pub Expr: () = {
+    Expr "+" Expr,
+    "x",
+};
+"#;
+    let error = build_tables(text).unwrap_err();
+    assert!(
+        error.message.contains("Ambiguous grammar detected"),
+        "{}",
+        error.message
+    );
+    assert_eq!(&text[error.span], r#"Expr "+" Expr"#);
+}
@@ -22,6 +22,7 @@ macro_rules! production {
             nonterminal: nt!($x),
             symbols: syms![$($y),*],
             action: ActionFn::new(0),
+            guard: None,
             span: Span(0, 0)
         }
     }
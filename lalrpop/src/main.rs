@@ -1,20 +1,30 @@
 extern crate lalrpop;
+extern crate lalrpop_util;
 extern crate pico_args;
 
 use std::ffi::OsString;
-use std::io::Write;
+use std::fs;
+use std::io::{Read, Write};
+use std::panic;
 use std::path::{Path, PathBuf};
 use std::process;
 use std::str::FromStr;
 
 use pico_args::Arguments;
 
-use lalrpop::Configuration;
+use lalrpop::{Configuration, Edition, Profile};
+use lalrpop_util::coverage::CoverageReport;
 
 static VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
 const USAGE: &str = "
 Usage: lalrpop [options] <inputs>...
+       lalrpop --coverage-report <reports>...
+       lalrpop --map-locations
+       lalrpop --diff <old> <new>
+       lalrpop --railroad <grammar> <dir>
+       lalrpop --minimize <grammar> [<message>]
+       lalrpop --ambiguities <grammar>
        lalrpop --help
        lalrpop (-V | --version)
 
@@ -25,11 +35,36 @@ Options:
                          Valid values: quiet, info, verbose, debug.
     -o, --out-dir DIR    Sets the directory in which to output the .rs file(s).
     --features FEATURES  Comma separated list of features for conditional compilation.
+    --flags FLAGS        Comma separated list of flags for `#[cfg(flag = \"..\")]`.
+    --edition EDITION    Set the Rust edition of the generated code. (Default: 2015)
+                         Valid values: 2015, 2018, 2021.
+    --profile PROFILE    Set what the generated code is tuned for. (Default: default)
+                         Valid values: default, wasm.
     -f, --force          Force execution, even if the .lalrpop file is older than the .rs file.
     -c, --color          Force colorful output, even if this is not a TTY.
     --no-whitespace      Removes redundant whitespace from the generated file. (Default: false)
     --comments           Enable comments in the generated code.
     --report             Generate report files.
+    --html-report        Generate report files as HTML pages too.
+    --emit WHAT          Also write WHAT next to the .rs file.
+                         Valid values: grammar-json.
+    --unchecked-tables   Index the parse tables without bounds checks in release builds.
+    --unchecked-downcasts
+                         Pop symbols without checking their types in release builds.
+    --split-tables       Write the parse tables to a second .rs file, included by the first.
+    --max-stack-depth N  Fail to parse input that nests deeper than N parser states.
+    --inline-stacks      Keep small parser stacks off the heap, with the smallvec feature.
+    --max-states N       Give up on grammars whose parsers need more than N states.
+    --coverage           Count how often the action of each rule runs.
+    --coverage-report    Merge the given coverage reports and list the rules never exercised.
+    --trace              Generate a `parse_traced` method that records each shift and reduce.
+    --c-abi              Generate `extern \"C\"` functions for parsing from other languages.
+    --source-map         Mark the generated code with the grammar locations it comes from.
+    --map-locations      Rewrite the generated-code locations in stdin into grammar locations.
+    --diff               Compare the parsers of two versions of a grammar.
+    --railroad           Write a railroad diagram of each nonterminal, as SVG.
+    --minimize           Shrink a grammar that makes LALRPOP panic, or fail with the given message.
+    --ambiguities        Print input that the grammar can parse in two ways, for each conflict.
 ";
 
 #[derive(Debug)]
@@ -37,13 +72,34 @@ struct Args {
     arg_inputs: Vec<OsString>,
     flag_out_dir: Option<PathBuf>,
     flag_features: Option<String>,
+    flag_flags: Option<String>,
+    flag_edition: Option<Edition>,
+    flag_profile: Option<Profile>,
     flag_level: Option<LevelFlag>,
+    flag_emit: Option<EmitFlag>,
     flag_help: bool,
     flag_force: bool,
     flag_color: bool,
     flag_comments: bool,
     flag_no_whitespace: bool,
     flag_report: bool,
+    flag_html_report: bool,
+    flag_unchecked_tables: bool,
+    flag_unchecked_downcasts: bool,
+    flag_split_tables: bool,
+    flag_max_stack_depth: Option<usize>,
+    flag_inline_stacks: bool,
+    flag_max_states: Option<usize>,
+    flag_coverage: bool,
+    flag_coverage_report: bool,
+    flag_trace: bool,
+    flag_c_abi: bool,
+    flag_source_map: bool,
+    flag_map_locations: bool,
+    flag_diff: bool,
+    flag_railroad: bool,
+    flag_minimize: bool,
+    flag_ambiguities: bool,
     flag_version: bool,
 }
 
@@ -70,17 +126,54 @@ impl FromStr for LevelFlag {
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum EmitFlag {
+    GrammarJson,
+}
+
+impl FromStr for EmitFlag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grammar-json" => Ok(EmitFlag::GrammarJson),
+            x => Err(format!("Unknown output {}", x)),
+        }
+    }
+}
+
 fn parse_args(mut args: Arguments) -> Result<Args, Box<dyn std::error::Error>> {
     Ok(Args {
         flag_out_dir: args.opt_value_from_fn(["-o", "--out-dir"], PathBuf::from_str)?,
         flag_features: args.opt_value_from_str("--features")?,
+        flag_flags: args.opt_value_from_str("--flags")?,
+        flag_edition: args.opt_value_from_str("--edition")?,
+        flag_profile: args.opt_value_from_str("--profile")?,
         flag_level: args.opt_value_from_fn(["-l", "--level"], LevelFlag::from_str)?,
+        flag_emit: args.opt_value_from_fn("--emit", EmitFlag::from_str)?,
         flag_help: args.contains(["-h", "--help"]),
         flag_force: args.contains(["-f", "--force"]),
         flag_color: args.contains(["-c", "--color"]),
         flag_comments: args.contains("--comments"),
         flag_no_whitespace: args.contains("--no-whitespace"),
         flag_report: args.contains("--report"),
+        flag_html_report: args.contains("--html-report"),
+        flag_unchecked_tables: args.contains("--unchecked-tables"),
+        flag_unchecked_downcasts: args.contains("--unchecked-downcasts"),
+        flag_split_tables: args.contains("--split-tables"),
+        flag_max_stack_depth: args.opt_value_from_str("--max-stack-depth")?,
+        flag_inline_stacks: args.contains("--inline-stacks"),
+        flag_max_states: args.opt_value_from_str("--max-states")?,
+        flag_coverage: args.contains("--coverage"),
+        flag_coverage_report: args.contains("--coverage-report"),
+        flag_trace: args.contains("--trace"),
+        flag_c_abi: args.contains("--c-abi"),
+        flag_source_map: args.contains("--source-map"),
+        flag_map_locations: args.contains("--map-locations"),
+        flag_diff: args.contains("--diff"),
+        flag_railroad: args.contains("--railroad"),
+        flag_minimize: args.contains("--minimize"),
+        flag_ambiguities: args.contains("--ambiguities"),
         flag_version: args.contains(["-V", "--version"]),
         arg_inputs: args.finish(),
     })
@@ -106,6 +199,86 @@ fn main1() -> Result<(), Box<dyn std::error::Error>> {
         process::exit(0);
     }
 
+    if args.flag_coverage_report {
+        return coverage_report(&args.arg_inputs);
+    }
+
+    if args.flag_map_locations {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        write!(stdout, "{}", lalrpop::translate_locations(&text))?;
+        return Ok(());
+    }
+
+    if args.flag_diff {
+        if args.arg_inputs.len() != 2 {
+            writeln!(stderr, "Error: --diff takes two grammar files.")?;
+            process::exit(1);
+        }
+        let old = fs::read_to_string(&args.arg_inputs[0])?;
+        let new = fs::read_to_string(&args.arg_inputs[1])?;
+        match lalrpop::diff_grammars(&old, &new) {
+            Ok(diff) => write!(stdout, "{}", diff)?,
+            Err(err) => {
+                writeln!(stderr, "Error: {}", err)?;
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if args.flag_railroad {
+        if args.arg_inputs.len() != 2 {
+            writeln!(stderr, "Error: --railroad takes a grammar and a directory.")?;
+            process::exit(1);
+        }
+        return railroad(&args.arg_inputs[0], &args.arg_inputs[1]);
+    }
+
+    if args.flag_minimize {
+        if args.arg_inputs.is_empty() || args.arg_inputs.len() > 2 {
+            writeln!(
+                stderr,
+                "Error: --minimize takes a grammar and, optionally, a message."
+            )?;
+            process::exit(1);
+        }
+        let text = fs::read_to_string(&args.arg_inputs[0])?;
+        let message = args
+            .arg_inputs
+            .get(1)
+            .map(|m| m.to_string_lossy().into_owned());
+        if !lalrpop::grammar_fails_with(&text, message.as_deref()) {
+            writeln!(stderr, "Error: the grammar does not show the problem.")?;
+            process::exit(1);
+        }
+        // the grammar is built many times, and every panic is expected
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let minimal = lalrpop::minimize_grammar(&text, |text| {
+            lalrpop::grammar_fails_with(text, message.as_deref())
+        });
+        panic::set_hook(hook);
+        write!(stdout, "{}", minimal)?;
+        return Ok(());
+    }
+
+    if args.flag_ambiguities {
+        if args.arg_inputs.len() != 1 {
+            writeln!(stderr, "Error: --ambiguities takes one grammar file.")?;
+            process::exit(1);
+        }
+        let text = fs::read_to_string(&args.arg_inputs[0])?;
+        match lalrpop::ambiguous_sentences(&text) {
+            Ok(sentences) => write!(stdout, "{}", sentences)?,
+            Err(err) => {
+                writeln!(stderr, "Error: {}", err)?;
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     let mut config = Configuration::new();
 
     match args.flag_level.unwrap_or(LevelFlag::Info) {
@@ -135,6 +308,54 @@ fn main1() -> Result<(), Box<dyn std::error::Error>> {
         config.emit_report(true);
     }
 
+    if args.flag_html_report {
+        config.emit_html_report(true);
+    }
+
+    if let Some(EmitFlag::GrammarJson) = args.flag_emit {
+        config.emit_grammar_json(true);
+    }
+
+    if args.flag_unchecked_tables {
+        config.unchecked_tables(true);
+    }
+
+    if args.flag_unchecked_downcasts {
+        config.unchecked_downcasts(true);
+    }
+
+    if args.flag_split_tables {
+        config.split_tables(true);
+    }
+
+    if let Some(depth) = args.flag_max_stack_depth {
+        config.max_stack_depth(depth);
+    }
+
+    if args.flag_inline_stacks {
+        config.inline_stacks(true);
+    }
+
+    if let Some(count) = args.flag_max_states {
+        config.max_states(count);
+    }
+
+    if args.flag_coverage {
+        config.emit_coverage(true);
+    }
+
+    if args.flag_trace {
+        config.emit_trace(true);
+    }
+
+    if args.flag_c_abi {
+        config.emit_c_abi(true);
+    }
+
+    if args.flag_source_map {
+        config.emit_source_map(true);
+    }
+
     if args.arg_inputs.is_empty() {
         writeln!(
             stderr,
@@ -151,6 +372,20 @@ fn main1() -> Result<(), Box<dyn std::error::Error>> {
         config.set_features(flag_features.split(',').map(String::from));
     }
 
+    if let Some(ref flag_flags) = args.flag_flags {
+        for flag in flag_flags.split(',') {
+            config.set_flag(flag, true);
+        }
+    }
+
+    if let Some(edition) = args.flag_edition {
+        config.set_edition(edition);
+    }
+
+    if let Some(profile) = args.flag_profile {
+        config.set_profile(profile);
+    }
+
     for arg in args.arg_inputs {
         let arg = Path::new(&arg);
         match config.process_file(arg) {
@@ -170,6 +405,58 @@ fn main1() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Merges the coverage reports `reports`, written from the `COVERAGE`
+/// of parsers generated with `--coverage`, and prints the rules that
+/// none of them exercised.
+fn coverage_report(reports: &[OsString]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut merged = CoverageReport::default();
+    for path in reports {
+        let report: CoverageReport = fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| format!("in `{}`: {}", Path::new(path).display(), err))?;
+        merged.merge(&report);
+    }
+
+    let uncovered = merged.uncovered();
+    println!(
+        "{} of {} rules covered",
+        merged.rules().count() - uncovered.len(),
+        merged.rules().count()
+    );
+    for rule in uncovered {
+        println!("never exercised: {}", rule);
+    }
+    Ok(())
+}
+
+/// Writes the railroad diagrams of `grammar` to `dir`, one file per
+/// nonterminal, named after it with the characters that are not valid
+/// in an identifier replaced with `_`.
+fn railroad(grammar: &OsString, dir: &OsString) -> Result<(), Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(grammar)?;
+    let diagrams = lalrpop::railroad_diagrams(&text)
+        .map_err(|err| format!("in `{}`: {}", Path::new(grammar).display(), err))?;
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir)?;
+    let mut names = vec![];
+    for (nonterminal, svg) in diagrams {
+        let stem: String = nonterminal
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        // `Expr?` and `Expr*` would otherwise both be `Expr_`
+        let mut name = stem.clone();
+        let mut count = 1;
+        while names.contains(&name) {
+            count += 1;
+            name = format!("{}{}", stem, count);
+        }
+        fs::write(dir.join(&name).with_extension("svg"), svg)?;
+        names.push(name);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -221,6 +508,34 @@ mod test {
         assert_eq!(args.arg_inputs, ["file.lalrpop"]);
     }
 
+    #[test]
+    fn test_usage_flags() {
+        let args = parse_args_vec(&vec!["--flags", "extensions", "file.lalrpop"]);
+        assert_eq!(args.flag_flags, Some("extensions".into()));
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_edition() {
+        let args = parse_args_vec(&vec!["--edition", "2018", "file.lalrpop"]);
+        assert_eq!(args.flag_edition, Some(Edition::Rust2018));
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_profile() {
+        let args = parse_args_vec(&vec!["--profile", "wasm", "file.lalrpop"]);
+        assert_eq!(args.flag_profile, Some(Profile::Wasm));
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_emit() {
+        let args = parse_args_vec(&vec!["--emit", "grammar-json", "file.lalrpop"]);
+        assert_eq!(args.flag_emit, Some(EmitFlag::GrammarJson));
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
     #[test]
     fn test_usage_emit_whitespace() {
         let args = parse_args_vec(&vec!["--no-whitespace", "file.lalrpop"]);
@@ -228,6 +543,118 @@ mod test {
         assert_eq!(args.arg_inputs, ["file.lalrpop"]);
     }
 
+    #[test]
+    fn test_usage_html_report() {
+        let args = parse_args_vec(&vec!["--html-report", "file.lalrpop"]);
+        assert!(args.flag_html_report);
+        assert!(!args.flag_report);
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_unchecked_tables() {
+        let args = parse_args_vec(&vec!["--unchecked-tables", "file.lalrpop"]);
+        assert!(args.flag_unchecked_tables);
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_unchecked_downcasts() {
+        let args = parse_args_vec(&vec!["--unchecked-downcasts", "file.lalrpop"]);
+        assert!(args.flag_unchecked_downcasts);
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_split_tables() {
+        let args = parse_args_vec(&vec!["--split-tables", "file.lalrpop"]);
+        assert!(args.flag_split_tables);
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_max_stack_depth() {
+        let args = parse_args_vec(&vec!["--max-stack-depth", "100", "file.lalrpop"]);
+        assert_eq!(args.flag_max_stack_depth, Some(100));
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_inline_stacks() {
+        let args = parse_args_vec(&vec!["--inline-stacks", "file.lalrpop"]);
+        assert!(args.flag_inline_stacks);
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_max_states() {
+        let args = parse_args_vec(&vec!["--max-states", "5000", "file.lalrpop"]);
+        assert_eq!(args.flag_max_states, Some(5000));
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_coverage() {
+        let args = parse_args_vec(&vec!["--coverage", "file.lalrpop"]);
+        assert!(args.flag_coverage);
+        assert!(!args.flag_coverage_report);
+        let args = parse_args_vec(&vec!["--coverage-report", "a.txt", "b.txt"]);
+        assert!(args.flag_coverage_report);
+        assert_eq!(args.arg_inputs, ["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_usage_trace() {
+        let args = parse_args_vec(&vec!["--trace", "file.lalrpop"]);
+        assert!(args.flag_trace);
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_c_abi() {
+        let args = parse_args_vec(&vec!["--c-abi", "file.lalrpop"]);
+        assert!(args.flag_c_abi);
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_source_map() {
+        let args = parse_args_vec(&vec!["--source-map", "file.lalrpop"]);
+        assert!(args.flag_source_map);
+        assert!(!args.flag_map_locations);
+        let args = parse_args_vec(&vec!["--map-locations"]);
+        assert!(args.flag_map_locations);
+        assert!(args.arg_inputs.is_empty());
+    }
+
+    #[test]
+    fn test_usage_diff() {
+        let args = parse_args_vec(&vec!["--diff", "old.lalrpop", "new.lalrpop"]);
+        assert!(args.flag_diff);
+        assert_eq!(args.arg_inputs, ["old.lalrpop", "new.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_minimize() {
+        let args = parse_args_vec(&vec!["--minimize", "file.lalrpop", "Ambiguous"]);
+        assert!(args.flag_minimize);
+        assert_eq!(args.arg_inputs, ["file.lalrpop", "Ambiguous"]);
+    }
+
+    #[test]
+    fn test_usage_ambiguities() {
+        let args = parse_args_vec(&vec!["--ambiguities", "file.lalrpop"]);
+        assert!(args.flag_ambiguities);
+        assert_eq!(args.arg_inputs, ["file.lalrpop"]);
+    }
+
+    #[test]
+    fn test_usage_railroad() {
+        let args = parse_args_vec(&vec!["--railroad", "file.lalrpop", "diagrams"]);
+        assert!(args.flag_railroad);
+        assert_eq!(args.arg_inputs, ["file.lalrpop", "diagrams"]);
+    }
+
     #[test]
     fn test_usage_level() {
         assert_eq!(
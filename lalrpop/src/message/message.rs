@@ -1,5 +1,6 @@
 use ascii_canvas::AsciiView;
 use crate::grammar::parse_tree::Span;
+use crate::message::vert::Vert;
 use crate::message::Content;
 use std::cmp;
 use std::fmt::{Debug, Error, Formatter};
@@ -42,6 +43,20 @@ impl Message {
             body,
         }
     }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The message citing `span` instead, with `note` as the last
+    /// paragraph of its body.
+    pub fn with_note(self, span: Span, note: Box<dyn Content>) -> Self {
+        Message {
+            span,
+            heading: self.heading,
+            body: Box::new(Vert::new(vec![self.body, note], 2)),
+        }
+    }
 }
 
 impl Content for Message {
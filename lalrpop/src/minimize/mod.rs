@@ -0,0 +1,204 @@
+//! Delta debugging of grammars: shrinking a grammar that shows some
+//! problem to a small one that still shows it, for bug reports.
+//!
+//! The grammar is shrunk by deleting text: first whole nonterminals,
+//! then alternatives, trying to delete large groups of them at once
+//! and smaller ones when that makes the problem go away, until nothing
+//! more can be deleted. A nonterminal never loses its last
+//! alternative, but deleting a nonterminal can leave uses of it
+//! behind; the test decides whether such a grammar still counts.
+
+use crate::grammar::parse_tree as pt;
+use crate::parser;
+use crate::tok::{Tok, Tokenizer};
+use std::ops::Range;
+
+#[cfg(test)]
+mod test;
+
+/// A piece of the grammar that can be deleted. The pieces of the same
+/// `group` are the alternatives of one nonterminal, which must keep at
+/// least one of them.
+struct Cut {
+    group: Option<usize>,
+    range: Range<usize>,
+}
+
+/// Shrinks the grammar `text` for as long as `failing` says that the
+/// smaller grammar still shows the problem, and returns the smallest
+/// one found. If `text` itself does not show it, it is returned as is.
+pub fn minimize_grammar<F>(text: &str, mut failing: F) -> String
+where
+    F: FnMut(&str) -> bool,
+{
+    let mut text = text.to_string();
+    if !failing(&text) {
+        return text;
+    }
+    loop {
+        let len = text.len();
+        text = shrink(text, nonterminal_cuts, &mut failing);
+        text = shrink(text, alternative_cuts, &mut failing);
+        if text.len() == len {
+            return text;
+        }
+    }
+}
+
+/// Deletes as many of the `cuts` of `text` as `failing` allows,
+/// in the manner of the ddmin algorithm: the cuts are split into
+/// chunks, and whenever deleting a chunk keeps the problem, that is
+/// kept and the cuts are worked out afresh; when no chunk can go, the
+/// chunks are made smaller, down to single cuts.
+fn shrink<F>(mut text: String, cuts: fn(&str) -> Vec<Cut>, failing: &mut F) -> String
+where
+    F: FnMut(&str) -> bool,
+{
+    let mut chunks = 2;
+    loop {
+        let all = cuts(&text);
+        if all.is_empty() {
+            return text;
+        }
+        chunks = chunks.min(all.len());
+        let size = all.len().div_ceil(chunks);
+        let smaller = (0..all.len())
+            .step_by(size)
+            .filter_map(|start| delete(&text, &all, start..(start + size).min(all.len())))
+            .find(|smaller| failing(smaller));
+        match smaller {
+            Some(smaller) => {
+                text = smaller;
+                chunks = (chunks - 1).max(2);
+            }
+            None if chunks == all.len() => return text,
+            None => chunks = (chunks * 2).min(all.len()),
+        }
+    }
+}
+
+/// `text` without the cuts of `all` in `chosen`, unless that would take
+/// all the alternatives of a nonterminal.
+fn delete(text: &str, all: &[Cut], chosen: Range<usize>) -> Option<String> {
+    for cut in &all[chosen.clone()] {
+        if let Some(group) = cut.group {
+            let kept = all
+                .iter()
+                .enumerate()
+                .any(|(i, other)| other.group == Some(group) && !chosen.contains(&i));
+            if !kept {
+                return None;
+            }
+        }
+    }
+
+    // the cuts of neighbouring alternatives can overlap
+    let mut ranges: Vec<Range<usize>> = all[chosen].iter().map(|cut| cut.range.clone()).collect();
+    ranges.sort_by_key(|range| range.start);
+    let mut result = String::new();
+    let mut end = 0;
+    for range in ranges {
+        if range.start > end {
+            result.push_str(&text[end..range.start]);
+        }
+        end = end.max(range.end);
+    }
+    result.push_str(&text[end..]);
+    Some(result)
+}
+
+/// The tokens of `text`, and its parse tree, if it is a grammar.
+fn parse(text: &str) -> Option<(Vec<(usize, Tok<'_>, usize)>, pt::Grammar)> {
+    let tokens = Tokenizer::new(text, 0)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    let grammar = parser::parse_grammar(text).ok()?;
+    Some((tokens, grammar))
+}
+
+/// Each nonterminal, from the end of the item before it (so taking
+/// any comments with it) to its own end.
+fn nonterminal_cuts(text: &str) -> Vec<Cut> {
+    let (tokens, grammar) = match parse(text) {
+        Some(parsed) => parsed,
+        None => return vec![],
+    };
+
+    // the offsets at which the top-level items end
+    let mut ends = vec![0];
+    let mut depth = 0;
+    for (i, &(_, ref tok, hi)) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).map(|&(_, ref tok, _)| tok);
+        match *tok {
+            Tok::LeftBrace | Tok::LeftBracket | Tok::LeftParen => depth += 1,
+            Tok::RightBrace | Tok::RightBracket | Tok::RightParen => depth -= 1,
+            _ => {}
+        }
+        let ends_item = depth == 0
+            && match *tok {
+                Tok::Semi | Tok::Code(_) => true,
+                // `match { .. } else { .. }` and `X = { .. };` go on
+                Tok::RightBrace => next != Some(&Tok::Semi) && next != Some(&Tok::Else),
+                _ => false,
+            };
+        if ends_item {
+            ends.push(hi);
+        }
+    }
+
+    grammar
+        .items
+        .iter()
+        .filter_map(pt::GrammarItem::as_nonterminal)
+        .filter_map(|data| {
+            let start = ends.iter().rev().find(|&&end| end <= data.span.0)?;
+            let end = ends.iter().find(|&&end| end > data.span.0)?;
+            Some(Cut {
+                group: None,
+                range: *start..*end,
+            })
+        })
+        .collect()
+}
+
+/// Each alternative of the nonterminals that have more than one,
+/// together with the comma after it (or, for the last one, before it).
+fn alternative_cuts(text: &str) -> Vec<Cut> {
+    let (tokens, grammar) = match parse(text) {
+        Some(parsed) => parsed,
+        None => return vec![],
+    };
+
+    // an alternative starts at the `#` of its first annotation, if any
+    let start = |alternative: &pt::Alternative| match alternative.annotations.first() {
+        Some(annotation) => tokens
+            .iter()
+            .rev()
+            .find(|&&(lo, ref tok, _)| *tok == Tok::Hash && lo < annotation.id_span.0)
+            .map_or(alternative.span.0, |&(lo, _, _)| lo),
+        None => alternative.span.0,
+    };
+
+    let mut cuts = vec![];
+    let nonterminals = grammar
+        .items
+        .iter()
+        .filter_map(pt::GrammarItem::as_nonterminal);
+    for (group, data) in nonterminals.enumerate() {
+        let alternatives = &data.alternatives;
+        if alternatives.len() < 2 {
+            continue;
+        }
+        for (i, alternative) in alternatives.iter().enumerate() {
+            let range = match alternatives.get(i + 1) {
+                Some(next) => start(alternative)..start(next),
+                None => alternatives[i - 1].span.1..alternative.span.1,
+            };
+            cuts.push(Cut {
+                group: Some(group),
+                range,
+            });
+        }
+    }
+    cuts
+}
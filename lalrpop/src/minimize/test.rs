@@ -0,0 +1,88 @@
+use super::minimize_grammar;
+use crate::build::grammar_fails_with;
+
+#[test]
+fn keeps_what_the_test_needs() {
+    let text = r#"
+grammar;
+
+// not needed
+pub A: () = { "a", "b" };
+
+pub Expr: () = {
+    "x",
+    #[precedence(level = "1")]
+    Bad,
+    "y" "z",
+};
+
+Bad: () = "bad";
+"#;
+    let minimal = minimize_grammar(text, |text| {
+        text.contains("Bad: ()") && text.contains("Bad,") && text.contains("grammar;")
+    });
+    assert_eq!(
+        minimal,
+        r#"
+grammar;
+
+pub Expr: () = {
+    #[precedence(level = "1")]
+    Bad,
+};
+
+Bad: () = "bad";
+"#
+    );
+}
+
+#[test]
+fn shrinks_an_ambiguity() {
+    let text = r#"
+grammar;
+
+pub Stmt: () = {
+    Expr ";",
+    "let" Id "=" Expr ";",
+};
+
+Expr: () = {
+    Expr "+" Expr,
+    Expr "*" Term,
+    Term,
+};
+
+Term: () = {
+    Id,
+    "(" Expr ")",
+};
+
+Id: () = r"[a-z]+";
+"#;
+    let failing = |text: &str| grammar_fails_with(text, Some("Ambiguous grammar detected"));
+    assert!(failing(text));
+    let minimal = minimize_grammar(text, failing);
+    assert!(failing(&minimal));
+    assert_eq!(
+        minimal,
+        r#"
+grammar;
+
+pub Stmt: () = {
+    "let" Id "=" Expr ";",
+};
+
+Expr: () = {
+    Expr "+" Expr,
+};
+
+Id: () = r"[a-z]+";
+"#
+    );
+}
+
+#[test]
+fn passing_grammar_is_kept() {
+    let text = "grammar;\npub A: () = { \"a\", \"b\" };\n";
+    assert_eq!(minimize_grammar(text, |_| false), text);
+}
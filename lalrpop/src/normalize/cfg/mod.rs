@@ -0,0 +1,45 @@
+//! Removes the alternatives marked `#[cfg(..)]` whose condition does
+//! not hold, before anything else looks at them. A condition is either
+//! `feature = "name"`, which holds if the Cargo feature `name` is
+//! enabled, or `flag = "name"`, which holds if the flag was set with
+//! `Configuration::set_flag`. This lets one grammar generate, say, a
+//! strict and an extended parser in the same build:
+//!
+//!     Expr: Box<Expr> = {
+//!         Expr "+" Term => ...,
+//!         #[cfg(flag = "extensions")]
+//!         Expr "^" Term => ...,
+//!         Term,
+//!     };
+
+use crate::grammar::consts::CFG;
+use crate::grammar::parse_tree::*;
+use crate::session::Session;
+
+#[cfg(test)]
+mod test;
+
+pub fn remove_inactive_alternatives(session: &Session, mut grammar: Grammar) -> Grammar {
+    for item in &mut grammar.items {
+        if let GrammarItem::Nonterminal(ref mut data) = *item {
+            data.alternatives
+                .retain(|alternative| active(session, &alternative.annotations));
+        }
+    }
+    grammar
+}
+
+/// Whether all the `#[cfg(..)]` conditions among `annotations` hold.
+pub fn active(session: &Session, annotations: &[Annotation]) -> bool {
+    annotations
+        .iter()
+        .filter(|annotation| annotation.id == *CFG)
+        .all(|annotation| match annotation.arg {
+            Some((ref key, ref name)) if key == "feature" => session
+                .features
+                .iter()
+                .any(|features| features.contains(name)),
+            Some((ref key, ref name)) if key == "flag" => session.flags.contains(name),
+            _ => false,
+        })
+}
@@ -0,0 +1,68 @@
+use crate::grammar::parse_tree::{Grammar, GrammarItem};
+use crate::normalize::normalize;
+use crate::parser;
+use crate::session::Session;
+
+static GRAMMAR: &str = r#"
+grammar;
+pub Expr: () = {
+    Expr "+" Term,
+    #[cfg(flag = "extensions")]
+    Expr "^" Term,
+    #[cfg(feature = "legacy")]
+    Expr "plus" Term,
+    Term,
+};
+Term: () = "x";
+"#;
+
+/// The number of alternatives of `Expr` left with `session`.
+fn alternatives(session: &Session) -> usize {
+    let grammar: Grammar = parser::parse_grammar(GRAMMAR).unwrap();
+    let grammar = super::remove_inactive_alternatives(session, grammar);
+    grammar
+        .items
+        .iter()
+        .filter_map(GrammarItem::as_nonterminal)
+        .find(|data| data.name.0 == *"Expr")
+        .unwrap()
+        .alternatives
+        .len()
+}
+
+#[test]
+fn flags() {
+    let mut session = Session::new();
+    assert_eq!(alternatives(&session), 2);
+    session.flags.insert("extensions".to_string());
+    assert_eq!(alternatives(&session), 3);
+    session.flags.insert("other".to_string());
+    assert_eq!(alternatives(&session), 3);
+}
+
+#[test]
+fn features() {
+    let mut session = Session::new();
+    session.features = Some(Some("legacy".to_string()).into_iter().collect());
+    assert_eq!(alternatives(&session), 3);
+    // features and flags are separate
+    session.flags.insert("legacy".to_string());
+    assert_eq!(alternatives(&session), 3);
+}
+
+#[test]
+fn normalized() {
+    let terminals = |session: &Session| {
+        let grammar = normalize(session, parser::parse_grammar(GRAMMAR).unwrap()).unwrap();
+        grammar
+            .terminals
+            .all
+            .iter()
+            .map(|terminal| terminal.to_string())
+            .collect::<Vec<_>>()
+    };
+    let mut session = Session::new();
+    assert_eq!(terminals(&session), vec!["\"+\"", "\"x\""]);
+    session.flags.insert("extensions".to_string());
+    assert_eq!(terminals(&session), vec!["\"+\"", "\"^\"", "\"x\""]);
+}
@@ -0,0 +1,73 @@
+//! Boxes the values of nonterminals declared with an `impl Trait` type.
+//! A nonterminal like
+//!
+//!     Evens: impl Iterator<Item = u32> = ...
+//!
+//! is stored (and, if public, returned) as
+//! `Box<dyn Iterator<Item = u32> + 'input>`, so each alternative may
+//! produce a different concrete type.
+
+use super::norm_util;
+use super::{NormError, NormResult};
+
+use crate::grammar::parse_tree::*;
+
+#[cfg(test)]
+mod test;
+
+pub fn box_impl_types(mut grammar: Grammar) -> NormResult<Grammar> {
+    let lifetime = norm_util::input_lifetime(&grammar);
+    for item in &mut grammar.items {
+        if let GrammarItem::Nonterminal(ref mut data) = *item {
+            box_nonterminal(&lifetime, data)?;
+        }
+    }
+    Ok(grammar)
+}
+
+fn box_nonterminal(lifetime: &Option<Lifetime>, data: &mut NonterminalData) -> NormResult<()> {
+    let bounds = match data.type_decl {
+        Some(TypeRef::Impl(ref mut bounds)) => bounds,
+        _ => return Ok(()),
+    };
+
+    // without a lifetime, `dyn Trait` would have to be `'static`
+    let has_lifetime = bounds
+        .iter()
+        .any(|bound| matches!(*bound, TypeBound::Lifetime(_)));
+    if let (false, Some(lifetime)) = (has_lifetime, lifetime) {
+        bounds.push(TypeBound::Lifetime(lifetime.clone()));
+    }
+
+    for alternative in &mut data.alternatives {
+        alternative.action = Some(match alternative.action.take() {
+            None => ActionKind::User("alloc::boxed::Box::new(<>)".to_string()),
+            Some(ActionKind::User(code)) => {
+                ActionKind::User(format!("alloc::boxed::Box::new({})", argument(&code)))
+            }
+            Some(ActionKind::Fallible(code)) => ActionKind::Fallible(format!(
+                "match {{ {} }} {{ Ok(v) => Ok(alloc::boxed::Box::new(v)), Err(e) => Err(e) }}",
+                code
+            )),
+            Some(ActionKind::Lookahead) | Some(ActionKind::Lookbehind) => return_err!(
+                alternative.span,
+                "`=>@L` and `=>@R` produce a location, not a value of type `{}`",
+                data.type_decl.as_ref().unwrap()
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// `code` as the argument of a call. Action code is an expression,
+/// since a `;` would end the alternative, so it needs no block, which
+/// rustc would warn about; but it may end in a `//` comment, which
+/// would comment out the closing parenthesis.
+fn argument(code: &str) -> String {
+    if code.contains("//") {
+        format!("{}\n", code)
+    } else {
+        code.to_string()
+    }
+}
@@ -0,0 +1,89 @@
+use crate::grammar::parse_tree::{ActionKind, Grammar, GrammarItem, NonterminalData};
+use crate::normalize::resolve::resolve;
+use crate::normalize::NormResult;
+use crate::parser;
+use crate::test_util;
+
+fn box_grammar(grammar: &str) -> NormResult<Grammar> {
+    let parsed_grammar = parser::parse_grammar(grammar).expect("parse grammar");
+    let parsed_grammar = resolve(parsed_grammar).expect("resolve");
+    super::box_impl_types(parsed_grammar)
+}
+
+fn nonterminal<'g>(grammar: &'g Grammar, name: &str) -> &'g NonterminalData {
+    grammar
+        .items
+        .iter()
+        .filter_map(GrammarItem::as_nonterminal)
+        .find(|data| data.name.0 == *name)
+        .unwrap()
+}
+
+#[test]
+fn boxed_actions() {
+    let grammar = box_grammar(
+        r#"
+grammar;
+    pub Digits: impl Iterator<Item = char> = {
+        <s:r"[0-9]+"> => s.chars(),
+        "none" =>? Ok(None.into_iter()),
+        Digits,
+        "x" => "x".chars() // a comment
+    };
+"#,
+    )
+    .unwrap();
+
+    let digits = nonterminal(&grammar, "Digits");
+    assert_eq!(
+        digits.type_decl.as_ref().unwrap().to_string(),
+        "impl Iterator<Item = char> + 'input"
+    );
+    let actions: Vec<_> = digits
+        .alternatives
+        .iter()
+        .map(|alt| alt.action.clone().unwrap())
+        .collect();
+    assert_eq!(
+        actions,
+        vec![
+            ActionKind::User("alloc::boxed::Box::new(s.chars())".to_string()),
+            ActionKind::Fallible(
+                "match { Ok(None.into_iter()) } \
+                 { Ok(v) => Ok(alloc::boxed::Box::new(v)), Err(e) => Err(e) }"
+                    .to_string()
+            ),
+            ActionKind::User("alloc::boxed::Box::new(<>)".to_string()),
+            ActionKind::User("alloc::boxed::Box::new(\"x\".chars() // a comment\n)".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn explicit_lifetime() {
+    let grammar = box_grammar(
+        r#"
+grammar<'a>;
+    extern { enum Tok<'a> { X => Tok::X(<&'a str>) } }
+    pub Words: impl Iterator<Item = &'a str> + 'a = X => <>.split(' ');
+"#,
+    )
+    .unwrap();
+
+    let words = nonterminal(&grammar, "Words");
+    assert_eq!(
+        words.type_decl.as_ref().unwrap().to_string(),
+        "impl Iterator<Item = &'a str> + 'a"
+    );
+}
+
+#[test]
+fn lookahead_action() {
+    let grammar = r#"grammar; pub Digits: impl Iterator<Item = char> = "x" =>@L;"#;
+    let err = box_grammar(grammar).unwrap_err();
+    test_util::check_norm_err(
+        r#"`=>@L` and `=>@R` produce a location, not a value of type `impl Iterator<Item = char> \+ 'input`"#,
+        r#"                                                  ~~~~~~~~ "#,
+        err,
+    );
+}
@@ -109,11 +109,14 @@ impl<'grammar> NonterminalGraph<'grammar> {
             }
             WalkState::Visited => Ok(()),
             WalkState::Visiting => {
-                return_err!(
-                    self.grammar.nonterminals[&nt].span,
+                let message = format!(
                     "cyclic inline directive: `{}` would have to be inlined into itself",
                     nt
                 );
+                Err(NormError {
+                    message: self.grammar.with_expansion_notes(nt, message),
+                    span: self.grammar.nonterminals[&nt].span,
+                })
             }
         }
     }
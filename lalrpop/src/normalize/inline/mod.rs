@@ -23,9 +23,12 @@ fn inline_nt(grammar: &mut Grammar, inline_nt: &NonterminalString) {
         let mut new_action_fn_defns = vec![];
 
         for into_production in &data.productions {
-            if !into_production
-                .symbols
-                .contains(&Symbol::Nonterminal(inline_nt.clone()))
+            // a guard borrows the values of the symbols, so we cannot
+            // run the action of an inlined symbol before it
+            if into_production.guard.is_some()
+                || !into_production
+                    .symbols
+                    .contains(&Symbol::Nonterminal(inline_nt.clone()))
             {
                 new_productions.push(into_production.clone());
                 continue;
@@ -115,6 +118,7 @@ impl<'a> Inliner<'a> {
                 span: self.into_production.span,
                 symbols: prod_symbols,
                 action: action_fn,
+                guard: None,
             });
         } else {
             let next_symbol = &into_symbols[0];
@@ -0,0 +1,129 @@
+//! Interns the text of the terminals that the match block marks with
+//! `#[intern(with = "names")]`. Each use of such a terminal, say
+//! `IDENT`, including those in the arguments of macros, is replaced by
+//! a use of the nonterminal
+//!
+//!     #[inline] __intern<IDENT>: __lalrpop_util::intern::Symbol =
+//!         IDENT => __lalrpop_util::intern::Interner::intern(&*names, <>);
+//!
+//! so that the token itself still carries the text, but actions get
+//! the symbol that the grammar parameter `names` gives for it.
+
+use super::NormResult;
+
+use crate::collections::Map;
+use crate::grammar::consts::{INLINE, INTERN};
+use crate::grammar::parse_tree::*;
+use string_cache::DefaultAtom as Atom;
+
+#[cfg(test)]
+mod test;
+
+pub fn intern_terminals(mut grammar: Grammar) -> NormResult<Grammar> {
+    // the terminals to intern, with the parameter to intern them with
+    // and the span of the item of the match block
+    let interned: Vec<(TerminalString, String, Span)> = grammar
+        .match_token()
+        .into_iter()
+        .flat_map(|match_token| &match_token.contents)
+        .flat_map(|contents| &contents.items)
+        .filter_map(|item| {
+            let annotation = item.annotations().iter().find(|a| a.id == *INTERN)?;
+            let (_, ref with) = *annotation.arg.as_ref()?;
+            Some((item.terminal()?, with.clone(), item.span()))
+        })
+        .collect();
+
+    if interned.is_empty() {
+        return Ok(grammar);
+    }
+
+    let nonterminals: Map<TerminalString, NonterminalString> = interned
+        .iter()
+        .map(|&(ref terminal, _, _)| {
+            let name = format!("{}intern<{}>", grammar.prefix, terminal);
+            (terminal.clone(), NonterminalString(Atom::from(name)))
+        })
+        .collect();
+
+    for item in &mut grammar.items {
+        if let GrammarItem::Nonterminal(ref mut data) = *item {
+            for alternative in &mut data.alternatives {
+                for symbol in &mut alternative.expr.symbols {
+                    replace_terminals(&nonterminals, symbol);
+                }
+            }
+        }
+    }
+
+    let lalrpop_util = Atom::from(format!("{}lalrpop_util", grammar.prefix));
+    for (terminal, with, span) in interned {
+        let action = format!("{}::intern::Interner::intern(&*{}, <>)", lalrpop_util, with);
+        let data = NonterminalData {
+            visibility: Visibility::Priv,
+            name: nonterminals[&terminal].clone(),
+            annotations: vec![Annotation {
+                id_span: span,
+                id: Atom::from(INLINE),
+                arg: None,
+            }],
+            span,
+            args: vec![],
+            parameters: vec![],
+            type_decl: Some(TypeRef::Nominal {
+                path: Path {
+                    absolute: false,
+                    ids: vec![
+                        lalrpop_util.clone(),
+                        Atom::from("intern"),
+                        Atom::from("Symbol"),
+                    ],
+                },
+                types: vec![],
+            }),
+            alternatives: vec![Alternative {
+                span,
+                expr: ExprSymbol {
+                    symbols: vec![Symbol::new(span, SymbolKind::Terminal(terminal))],
+                },
+                condition: None,
+                guard: None,
+                action: Some(ActionKind::User(action)),
+                annotations: vec![],
+            }],
+            expansion: None,
+        };
+        grammar.items.push(GrammarItem::Nonterminal(data));
+    }
+
+    Ok(grammar)
+}
+
+fn replace_terminals(nonterminals: &Map<TerminalString, NonterminalString>, symbol: &mut Symbol) {
+    match symbol.kind {
+        SymbolKind::Terminal(ref terminal) => {
+            if let Some(nonterminal) = nonterminals.get(terminal) {
+                symbol.kind = SymbolKind::Nonterminal(nonterminal.clone());
+            }
+        }
+        SymbolKind::Expr(ExprSymbol { ref mut symbols })
+        | SymbolKind::Macro(MacroSymbol {
+            args: ref mut symbols,
+            ..
+        }) => {
+            for symbol in symbols {
+                replace_terminals(nonterminals, symbol);
+            }
+        }
+        SymbolKind::Repeat(ref mut repeat) => replace_terminals(nonterminals, &mut repeat.symbol),
+        SymbolKind::Choose(ref mut symbol) | SymbolKind::Name(_, ref mut symbol) => {
+            replace_terminals(nonterminals, symbol)
+        }
+        SymbolKind::AmbiguousId(_)
+        | SymbolKind::Nonterminal(_)
+        | SymbolKind::Call(_)
+        | SymbolKind::Lookahead
+        | SymbolKind::Lookbehind
+        | SymbolKind::Error => {}
+    }
+}
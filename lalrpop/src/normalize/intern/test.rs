@@ -0,0 +1,60 @@
+use crate::grammar::parse_tree::{ActionKind, Grammar, GrammarItem, NonterminalData, SymbolKind};
+use crate::normalize::resolve::resolve;
+use crate::parser;
+
+fn intern_grammar(grammar: &str) -> Grammar {
+    let parsed_grammar = parser::parse_grammar(grammar).expect("parse grammar");
+    let parsed_grammar = resolve(parsed_grammar).expect("resolve");
+    super::intern_terminals(parsed_grammar).unwrap()
+}
+
+fn nonterminal<'g>(grammar: &'g Grammar, name: &str) -> &'g NonterminalData {
+    grammar
+        .items
+        .iter()
+        .filter_map(GrammarItem::as_nonterminal)
+        .find(|data| data.name.0 == *name)
+        .unwrap()
+}
+
+#[test]
+fn interned_uses() {
+    let grammar = intern_grammar(
+        r#"
+grammar(names: &SymbolTable);
+    match {
+        #[intern(with = "names")] r"[a-z]+" => IDENT,
+        "=",
+    }
+    pub Let: (Symbol, Symbol) = <a:IDENT> "=" <b:IDENT> => (a, b);
+"#,
+    );
+
+    let uses: Vec<_> = nonterminal(&grammar, "Let").alternatives[0]
+        .expr
+        .symbols
+        .iter()
+        .map(|symbol| symbol.to_string())
+        .collect();
+    assert_eq!(
+        uses,
+        vec!["a:__intern<IDENT>", r#""=""#, "b:__intern<IDENT>"]
+    );
+
+    let intern = nonterminal(&grammar, "__intern<IDENT>");
+    assert_eq!(
+        intern.type_decl.as_ref().unwrap().to_string(),
+        "__lalrpop_util::intern::Symbol"
+    );
+    let alternative = &intern.alternatives[0];
+    match alternative.expr.symbols[0].kind {
+        SymbolKind::Terminal(ref terminal) => assert_eq!(terminal.to_string(), "IDENT"),
+        ref kind => panic!("expected the terminal, found {:?}", kind),
+    }
+    assert_eq!(
+        alternative.action,
+        Some(ActionKind::User(
+            "__lalrpop_util::intern::Interner::intern(&*names, <>)".to_string()
+        ))
+    );
+}
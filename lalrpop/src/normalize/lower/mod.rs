@@ -2,7 +2,10 @@
 //!
 
 use crate::collections::{map, Map};
-use crate::grammar::consts::CFG;
+use crate::grammar::consts::{
+    BACKTRACK, CANCELLABLE, COMPLETIONS, COPY_LOCATION, COST, COST_INSERT_ARG, EVENTS, ISLAND,
+    PREFIX, RECOGNIZE, REPAIR, RESUMABLE, RUNTIME_START, TERMINAL_ID,
+};
 use crate::grammar::parse_tree as pt;
 use crate::grammar::parse_tree::{
     read_algorithm, GrammarItem, InternToken, Lifetime, MatchMapping, Name, NonterminalString,
@@ -10,9 +13,11 @@ use crate::grammar::parse_tree::{
 };
 use crate::grammar::pattern::{Pattern, PatternKind};
 use crate::grammar::repr as r;
+use crate::normalize::cfg;
 use crate::normalize::norm_util::{self, Symbols};
 use crate::normalize::NormResult;
 use crate::session::Session;
+use crate::util::Sep;
 use string_cache::DefaultAtom as Atom;
 
 pub fn lower(session: &Session, grammar: pt::Grammar, types: r::Types) -> NormResult<r::Grammar> {
@@ -49,7 +54,11 @@ impl<'s> LowerState<'s> {
         let start_symbols = self.synthesize_start_symbols(&grammar);
 
         let mut uses = vec![];
+        let mut code = vec![];
+        let mut type_aliases = vec![];
         let mut token_span = None;
+        let mut terminal_ids = vec![];
+        let mut repair_costs = map();
         let internal_token_path = Path {
             absolute: false,
             ids: vec![Atom::from("Token")],
@@ -61,10 +70,33 @@ impl<'s> LowerState<'s> {
                     uses.push(data);
                 }
 
-                pt::GrammarItem::MatchToken(_) => {
+                pt::GrammarItem::Code(data) => {
+                    code.push(data);
+                }
+
+                pt::GrammarItem::Include(..) => {}
+
+                pt::GrammarItem::TypeAlias(data) => {
+                    type_aliases.push(r::TypeAlias {
+                        name: data.name,
+                        type_parameters: data.type_parameters,
+                        type_repr: data.type_ref.type_repr(),
+                    });
+                }
+
+                pt::GrammarItem::MatchToken(data) => {
                     // The declarations in the match token are handled
                     // fully by the `token_check` when it constructs the
-                    //  `InternToken` -- there is nothing left to do here.
+                    //  `InternToken` -- there is nothing left to do here
+                    // but collect the ids of the terminals.
+                    for item in data.contents.iter().flat_map(|contents| &contents.items) {
+                        if let Some(terminal) = item.terminal() {
+                            terminal_ids.extend(terminal_id(terminal.clone(), item.annotations()));
+                            if let Some(cost) = repair_cost(item.annotations()) {
+                                repair_costs.insert(terminal, cost);
+                            }
+                        }
+                    }
                 }
 
                 pt::GrammarItem::InternToken(data) => {
@@ -117,6 +149,15 @@ impl<'s> LowerState<'s> {
                                     conversion.to.map(&mut |t| t.type_repr()),
                                 )
                             }));
+                        for conversion in &enum_token.conversions {
+                            terminal_ids.extend(terminal_id(
+                                conversion.from.clone(),
+                                &conversion.annotations,
+                            ));
+                            if let Some(cost) = repair_cost(&conversion.annotations) {
+                                repair_costs.insert(conversion.from.clone(), cost);
+                            }
+                        }
                     }
                 }
 
@@ -128,12 +169,17 @@ impl<'s> LowerState<'s> {
                         .map(|alt| {
                             let nt_type = self.types.nonterminal_type(nt_name).clone();
                             let symbols = self.symbols(&alt.expr.symbols);
+                            let guard = alt
+                                .guard
+                                .clone()
+                                .map(|code| self.guard_fn(&alt.expr, &symbols, code));
                             let action = self.action_kind(nt_type, &alt.expr, &symbols, alt.action);
                             r::Production {
                                 nonterminal: nt_name.clone(),
                                 span: alt.span,
                                 symbols,
                                 action,
+                                guard,
                             }
                         })
                         .collect();
@@ -145,6 +191,7 @@ impl<'s> LowerState<'s> {
                             annotations: nt.annotations,
                             span: nt.span,
                             productions,
+                            expansion: nt.expansion,
                         },
                     );
                 }
@@ -168,9 +215,40 @@ impl<'s> LowerState<'s> {
 
         let mut algorithm = r::Algorithm::default();
 
+        // the strategy was checked in `prevalidate`
+        let repair = grammar
+            .annotations
+            .iter()
+            .find(|a| a.id == *REPAIR)
+            .map(|a| match a.arg {
+                Some((_, ref strategy)) => strategy.parse().unwrap(),
+                None => r::RepairStrategy::SingleToken,
+            });
+
+        let table_driven_only = self.nonterminals.values().any(|nt| {
+            nt.annotations.iter().any(|a| {
+                a.id == *PREFIX
+                    || a.id == *RESUMABLE
+                    || a.id == *COMPLETIONS
+                    || a.id == *ISLAND
+                    || a.id == *EVENTS
+                    || a.id == *RECOGNIZE
+                    || a.id == *CANCELLABLE
+            }) || nt.productions.iter().any(|p| p.guard.is_some())
+        });
+
+        let backtrack = grammar.annotations.iter().any(|a| a.id == *BACKTRACK);
+        let copy_location = grammar.annotations.iter().any(|a| a.id == *COPY_LOCATION)
+            || self.types.terminal_loc_type().is_copy();
+
         // FIXME Error recovery only works for parse tables so temporarily only generate parse tables for
         // testing
-        if self.session.unit_test && !self.uses_error_recovery {
+        if self.session.unit_test
+            && !self.uses_error_recovery
+            && repair.is_none()
+            && !backtrack
+            && !table_driven_only
+        {
             algorithm.codegen = r::LrCodeGeneration::TestAll;
         }
 
@@ -189,12 +267,39 @@ impl<'s> LowerState<'s> {
         all_terminals.sort();
 
         let terminal_bits: Map<_, _> = all_terminals.iter().cloned().zip(0..).collect();
+        terminal_ids.sort_by_key(|terminal_id: &r::TerminalId| terminal_id.id);
+
+        // inlining moves user actions into other productions, so we
+        // name them after the production they come from now
+        let rules = self
+            .nonterminals
+            .values()
+            .filter(|nt| !start_symbols.values().any(|start| *start == nt.name))
+            .flat_map(|nt| &nt.productions)
+            .filter(|p| {
+                matches!(
+                    self.action_fn_defns[p.action.index()].kind,
+                    r::ActionFnDefnKind::User(_)
+                )
+            })
+            .map(|p| {
+                let rule = format!("{} = {}", p.nonterminal, Sep(" ", &p.symbols));
+                (p.action, rule.trim_end().to_string())
+            })
+            .collect();
 
         Ok(r::Grammar {
             uses_error_recovery: self.uses_error_recovery,
+            repair,
+            runtime_start: grammar.annotations.iter().any(|a| a.id == *RUNTIME_START),
+            backtrack,
+            copy_location,
             prefix: self.prefix,
+            span: grammar.span,
             start_nonterminals: start_symbols,
             uses,
+            type_aliases,
+            code,
             action_fn_defns: self.action_fn_defns,
             nonterminals: self.nonterminals,
             conversions: self.conversions.into_iter().collect(),
@@ -210,6 +315,10 @@ impl<'s> LowerState<'s> {
                 bits: terminal_bits,
             },
             module_attributes: grammar.module_attributes,
+            error_messages: vec![],
+            rules,
+            terminal_ids,
+            repair_costs,
         })
     }
 
@@ -245,6 +354,7 @@ impl<'s> LowerState<'s> {
                     nonterminal: fake_name.clone(),
                     symbols,
                     action: action_fn,
+                    guard: None,
                     span: nt.span,
                 };
                 self.nonterminals.insert(
@@ -255,6 +365,7 @@ impl<'s> LowerState<'s> {
                         annotations: vec![],
                         span: nt.span,
                         productions: vec![production],
+                        expansion: None,
                     },
                 );
                 (nt.name.clone(), fake_name)
@@ -315,6 +426,23 @@ impl<'s> LowerState<'s> {
         }
     }
 
+    /// Lowers a `when { .. }` guard like an action that returns
+    /// `bool`, except that it borrows the symbols instead of taking
+    /// them.
+    fn guard_fn(
+        &mut self,
+        expr: &pt::ExprSymbol,
+        symbols: &[r::Symbol],
+        code: String,
+    ) -> r::ActionFn {
+        let guard = self.action_fn(r::TypeRepr::bool(), false, expr, symbols, Some(code));
+        let defn = &mut self.action_fn_defns[guard.index()];
+        if let r::ActionFnDefnKind::User(ref data) = defn.kind {
+            defn.kind = r::ActionFnDefnKind::Guard(data.clone());
+        }
+        guard
+    }
+
     fn lookahead_action_fn(&mut self) -> r::ActionFn {
         let action_fn_defn = r::ActionFnDefn {
             fallible: false,
@@ -467,6 +595,7 @@ impl<'s> LowerState<'s> {
             }
 
             pt::SymbolKind::Macro(..)
+            | pt::SymbolKind::Call(..)
             | pt::SymbolKind::Repeat(..)
             | pt::SymbolKind::Expr(..)
             | pt::SymbolKind::AmbiguousId(_)
@@ -506,17 +635,39 @@ where
     result
 }
 
-fn cfg_active(session: &Session, nt: &pt::NonterminalData) -> bool {
-    let cfg_atom = Atom::from(CFG);
-    nt.annotations
+/// The id that `annotations` give `terminal`, with `#[terminal_id]`;
+/// `prevalidate` checks that it is a number.
+fn terminal_id(terminal: TerminalString, annotations: &[pt::Annotation]) -> Option<r::TerminalId> {
+    annotations
         .iter()
-        .filter(|ann| ann.id == cfg_atom)
-        .all(|ann| {
-            ann.arg.as_ref().map_or(false, |&(_, ref feature)| {
-                session
-                    .features
-                    .as_ref()
-                    .map_or(false, |features| features.contains(feature))
+        .find(|annotation| annotation.id == *TERMINAL_ID)
+        .and_then(|annotation| annotation.arg.as_ref())
+        .and_then(|(name, id)| {
+            Some(r::TerminalId {
+                name: name.clone(),
+                terminal,
+                id: id.parse().ok()?,
             })
         })
 }
+
+/// The costs that `annotations` give a terminal, with `#[cost]`, if
+/// any; `prevalidate` checks that they are numbers.
+fn repair_cost(annotations: &[pt::Annotation]) -> Option<r::RepairCost> {
+    let mut cost = None;
+    for annotation in annotations.iter().filter(|a| a.id == *COST) {
+        let cost = cost.get_or_insert_with(r::RepairCost::default);
+        match annotation.arg {
+            Some((ref name, ref value)) if *name == *COST_INSERT_ARG => {
+                cost.insert = value.parse().unwrap()
+            }
+            Some((_, ref value)) => cost.delete = value.parse().unwrap(),
+            None => {}
+        }
+    }
+    cost
+}
+
+fn cfg_active(session: &Session, nt: &pt::NonterminalData) -> bool {
+    cfg::active(session, &nt.annotations)
+}
@@ -1,8 +1,9 @@
 use crate::grammar::consts::INLINE;
 use crate::grammar::parse_tree::{
-    ActionKind, Alternative, Annotation, Condition, ConditionOp, ExprSymbol, Grammar, GrammarItem,
-    MacroSymbol, Name, NonterminalData, NonterminalString, Path, RepeatOp, RepeatSymbol, Span,
-    Symbol, SymbolKind, TerminalLiteral, TerminalString, TypeRef, Visibility,
+    ActionKind, Alternative, Annotation, Condition, ConditionExpr, ConditionOp, Expansion,
+    ExprSymbol, Grammar, GrammarItem, MacroSymbol, Name, NonterminalData, NonterminalString, Path,
+    RepeatOp, RepeatSymbol, Span, Symbol, SymbolKind, TerminalLiteral, TerminalString, TypeRef,
+    Visibility,
 };
 use crate::normalize::norm_util::{self, Symbols};
 use crate::normalize::resolve;
@@ -41,6 +42,10 @@ struct MacroExpander {
     macro_defs: HashMap<NonterminalString, NonterminalData>,
     expansion_set: HashSet<NonterminalString>,
     expansion_stack: Vec<Symbol>,
+    // the nonterminal whose alternatives are being replaced, and the
+    // one that each expansion was first found in
+    replacing: Option<NonterminalString>,
+    uses: HashMap<NonterminalString, NonterminalString>,
 }
 
 impl MacroExpander {
@@ -49,6 +54,8 @@ impl MacroExpander {
             macro_defs,
             expansion_stack: Vec::new(),
             expansion_set: HashSet::new(),
+            replacing: None,
+            uses: HashMap::new(),
         }
     }
 
@@ -99,11 +106,15 @@ impl MacroExpander {
             GrammarItem::ExternToken(..) => {}
             GrammarItem::InternToken(..) => {}
             GrammarItem::Use(..) => {}
+            GrammarItem::Include(..) => {}
+            GrammarItem::TypeAlias(..) => {}
+            GrammarItem::Code(..) => {}
             GrammarItem::Nonterminal(ref mut data) => {
                 // Should not encounter macro definitions here,
                 // they've already been siphoned off.
                 assert!(!data.is_macro_def());
 
+                self.replacing = Some(data.name.clone());
                 for alternative in &mut data.alternatives {
                     self.replace_symbols(&mut alternative.expr.symbols);
                 }
@@ -133,7 +144,10 @@ impl MacroExpander {
             SymbolKind::Repeat(ref mut repeat) => {
                 self.replace_symbol(&mut repeat.symbol);
             }
-            SymbolKind::Terminal(_) | SymbolKind::Nonterminal(_) | SymbolKind::Error => {
+            SymbolKind::Terminal(_)
+            | SymbolKind::Nonterminal(_)
+            | SymbolKind::Call(_)
+            | SymbolKind::Error => {
                 return;
             }
             SymbolKind::Choose(ref mut sym) | SymbolKind::Name(_, ref mut sym) => {
@@ -151,7 +165,10 @@ impl MacroExpander {
             kind: SymbolKind::Nonterminal(key.clone()),
         };
         let to_expand = mem::replace(symbol, replacement);
-        if self.expansion_set.insert(key) {
+        if self.expansion_set.insert(key.clone()) {
+            if let Some(ref replacing) = self.replacing {
+                self.uses.insert(key, replacing.clone());
+            }
             self.expansion_stack.push(to_expand);
         }
     }
@@ -200,22 +217,36 @@ impl MacroExpander {
                 span,
                 expr: self.macro_expand_expr_symbol(&args, &alternative.expr),
                 condition: None,
+                guard: alternative.guard.clone(),
                 action: alternative.action.clone(),
                 annotations: alternative.annotations.clone(),
             });
         }
 
+        let expansion = self.expansion(&msym_name, Some(mdef.span));
         Ok(GrammarItem::Nonterminal(NonterminalData {
             visibility: mdef.visibility.clone(),
             span,
             name: msym_name,
             annotations: mdef.annotations.clone(),
             args: vec![],
+            parameters: vec![],
             type_decl,
             alternatives,
+            expansion,
         }))
     }
 
+    /// Where the nonterminal `name` comes from: the nonterminal that
+    /// first uses it, and `definition`, the macro that it expands, if
+    /// any.
+    fn expansion(&self, name: &NonterminalString, definition: Option<Span>) -> Option<Expansion> {
+        self.uses.get(name).map(|used_in| Expansion {
+            definition,
+            used_in: used_in.clone(),
+        })
+    }
+
     fn macro_expand_type_refs(
         &self,
         args: &HashMap<NonterminalString, SymbolKind>,
@@ -269,11 +300,18 @@ impl MacroExpander {
                 path: path.clone(),
                 types: self.macro_expand_type_refs(args, types),
             },
+            TypeRef::Impl(ref bounds) => TypeRef::Impl(
+                bounds
+                    .iter()
+                    .map(|bound| bound.map(|t| self.macro_expand_type_ref(args, t)))
+                    .collect(),
+            ),
             TypeRef::Fn {
                 ref forall,
                 ref path,
                 ref parameters,
                 ref ret,
+                ref bound,
             } => TypeRef::Fn {
                 forall: forall.clone(),
                 path: path.clone(),
@@ -281,6 +319,7 @@ impl MacroExpander {
                 ret: ret
                     .as_ref()
                     .map(|t| Box::new(self.macro_expand_type_ref(args, t))),
+                bound: bound.clone(),
             },
         }
     }
@@ -288,29 +327,57 @@ impl MacroExpander {
     fn evaluate_cond(
         &self,
         args: &HashMap<NonterminalString, SymbolKind>,
-        opt_cond: &Option<Condition>,
+        opt_cond: &Option<ConditionExpr>,
     ) -> NormResult<bool> {
-        if let Some(ref c) = *opt_cond {
-            match args[&c.lhs] {
-                SymbolKind::Terminal(TerminalString::Literal(TerminalLiteral::Quoted(ref lhs))) => {
-                    match c.op {
-                        ConditionOp::Equals => Ok(lhs == &c.rhs),
-                        ConditionOp::NotEquals => Ok(lhs != &c.rhs),
-                        ConditionOp::Match => self.re_match(c.span, lhs, &c.rhs),
-                        ConditionOp::NotMatch => Ok(!self.re_match(c.span, lhs, &c.rhs)?),
-                    }
-                }
-                ref lhs => {
-                    return_err!(
-                        c.span,
-                        "invalid condition LHS `{}`, expected a string literal, not `{}`",
-                        c.lhs,
-                        lhs
-                    );
+        match *opt_cond {
+            Some(ref c) => self.evaluate(args, c),
+            None => Ok(true),
+        }
+    }
+
+    // both sides of `&&` and `||` are evaluated, so that a mistake in
+    // either is reported whatever the arguments are
+    fn evaluate(
+        &self,
+        args: &HashMap<NonterminalString, SymbolKind>,
+        cond: &ConditionExpr,
+    ) -> NormResult<bool> {
+        match *cond {
+            ConditionExpr::Test(ref c) => self.evaluate_test(args, c),
+            ConditionExpr::Not(ref c) => Ok(!self.evaluate(args, c)?),
+            ConditionExpr::And(ref l, ref r) => {
+                let l = self.evaluate(args, l)?;
+                Ok(self.evaluate(args, r)? && l)
+            }
+            ConditionExpr::Or(ref l, ref r) => {
+                let l = self.evaluate(args, l)?;
+                Ok(self.evaluate(args, r)? || l)
+            }
+        }
+    }
+
+    fn evaluate_test(
+        &self,
+        args: &HashMap<NonterminalString, SymbolKind>,
+        c: &Condition,
+    ) -> NormResult<bool> {
+        match args[&c.lhs] {
+            SymbolKind::Terminal(TerminalString::Literal(TerminalLiteral::Quoted(ref lhs))) => {
+                match c.op {
+                    ConditionOp::Equals => Ok(lhs == &c.rhs),
+                    ConditionOp::NotEquals => Ok(lhs != &c.rhs),
+                    ConditionOp::Match => self.re_match(c.span, lhs, &c.rhs),
+                    ConditionOp::NotMatch => Ok(!self.re_match(c.span, lhs, &c.rhs)?),
                 }
             }
-        } else {
-            Ok(true)
+            ref lhs => {
+                return_err!(
+                    c.span,
+                    "invalid condition LHS `{}`, expected a string literal, not `{}`",
+                    c.lhs,
+                    lhs
+                );
+            }
         }
     }
 
@@ -360,6 +427,7 @@ impl MacroExpander {
                 name: msym.name.clone(),
                 args: self.macro_expand_symbols(args, &msym.args),
             }),
+            SymbolKind::Call(ref c) => SymbolKind::Call(c.clone()),
             SymbolKind::Repeat(ref r) => SymbolKind::Repeat(Box::new(RepeatSymbol {
                 op: r.op,
                 symbol: self.macro_expand_symbol(args, &r.symbol),
@@ -390,6 +458,7 @@ impl MacroExpander {
 
     fn expand_expr_symbol(&mut self, span: Span, expr: ExprSymbol) -> NormResult<GrammarItem> {
         let name = NonterminalString(Atom::from(expr.canonical_form()));
+        let expansion = self.expansion(&name, None);
 
         let (action, ty_ref) =
             match norm_util::analyze_expr(&expr) {
@@ -420,14 +489,17 @@ impl MacroExpander {
             name,
             annotations: inline(span),
             args: vec![],
+            parameters: vec![],
             type_decl: Some(ty_ref),
             alternatives: vec![Alternative {
                 span,
                 expr,
                 condition: None,
+                guard: None,
                 action,
                 annotations: Vec::new(),
             }],
+            expansion,
         }))
     }
 
@@ -440,6 +512,7 @@ impl MacroExpander {
         repeat: RepeatSymbol,
     ) -> NormResult<GrammarItem> {
         let name = NonterminalString(Atom::from(repeat.canonical_form()));
+        let expansion = self.expansion(&name, None);
         let v = Atom::from("v");
         let e = Atom::from("e");
 
@@ -464,6 +537,7 @@ impl MacroExpander {
                     name,
                     annotations: inline(span),
                     args: vec![],
+                    parameters: vec![],
                     type_decl: Some(ty_ref),
                     alternatives: vec![
                         // X* =
@@ -471,6 +545,7 @@ impl MacroExpander {
                             span,
                             expr: ExprSymbol { symbols: vec![] },
                             condition: None,
+                            guard: None,
                             action: action("alloc::vec![]"),
                             annotations: vec![],
                         },
@@ -490,10 +565,12 @@ impl MacroExpander {
                                 )],
                             },
                             condition: None,
+                            guard: None,
                             action: action("v"),
                             annotations: vec![],
                         },
                     ],
+                    expansion,
                 }))
             }
 
@@ -510,6 +587,7 @@ impl MacroExpander {
                     name: name.clone(),
                     annotations: vec![],
                     args: vec![],
+                    parameters: vec![],
                     type_decl: Some(ty_ref),
                     alternatives: vec![
                         // X+ = X
@@ -519,6 +597,7 @@ impl MacroExpander {
                                 symbols: vec![repeat.symbol.clone()],
                             },
                             condition: None,
+                            guard: None,
                             action: action("alloc::vec![<>]"),
                             annotations: vec![],
                         },
@@ -547,10 +626,12 @@ impl MacroExpander {
                                 ],
                             },
                             condition: None,
+                            guard: None,
                             action: action("{ let mut v = v; v.push(e); v }"),
                             annotations: vec![],
                         },
                     ],
+                    expansion,
                 }))
             }
 
@@ -567,6 +648,7 @@ impl MacroExpander {
                     name,
                     annotations: inline(span),
                     args: vec![],
+                    parameters: vec![],
                     type_decl: Some(ty_ref),
                     alternatives: vec![
                         // X? = X => Some(<>)
@@ -576,6 +658,7 @@ impl MacroExpander {
                                 symbols: vec![repeat.symbol.clone()],
                             },
                             condition: None,
+                            guard: None,
                             action: action("Some(<>)"),
                             annotations: vec![],
                         },
@@ -584,10 +667,12 @@ impl MacroExpander {
                             span,
                             expr: ExprSymbol { symbols: vec![] },
                             condition: None,
+                            guard: None,
                             action: action("None"),
                             annotations: vec![],
                         },
                     ],
+                    expansion,
                 }))
             }
         }
@@ -600,20 +685,24 @@ impl MacroExpander {
         action: ActionKind,
     ) -> NormResult<GrammarItem> {
         let name = NonterminalString(Atom::from(name));
+        let expansion = self.expansion(&name, None);
         Ok(GrammarItem::Nonterminal(NonterminalData {
             visibility: Visibility::Priv,
             span,
             name,
             annotations: inline(span),
             args: vec![],
+            parameters: vec![],
             type_decl: None,
             alternatives: vec![Alternative {
                 span,
                 expr: ExprSymbol { symbols: vec![] },
                 condition: None,
+                guard: None,
                 action: Some(action),
                 annotations: vec![],
             }],
+            expansion,
         }))
     }
 }
@@ -1,8 +1,28 @@
+use crate::grammar::parse_tree::{Expansion, Grammar, GrammarItem, NonterminalString, Span};
 use crate::parser;
 use crate::test_util::compare;
+use string_cache::DefaultAtom as Atom;
 
 use super::expand_macros;
 
+/// `grammar`, with the nonterminals of `expansions` marked as expanded
+/// for their use in the given nonterminals; those with names like
+/// `Comma<"Id">` are expansions of macros.
+fn expanded(mut grammar: Grammar, expansions: &[(&str, &str)]) -> Grammar {
+    for item in &mut grammar.items {
+        if let GrammarItem::Nonterminal(ref mut data) = *item {
+            if let Some(&(_, used_in)) = expansions.iter().find(|&&(nt, _)| *data.name.0 == *nt) {
+                let is_macro = data.name.0.starts_with(char::is_alphabetic);
+                data.expansion = Some(Expansion {
+                    definition: if is_macro { Some(Span(0, 0)) } else { None },
+                    used_in: NonterminalString(Atom::from(used_in)),
+                });
+            }
+        }
+    }
+    grammar
+}
+
 #[test]
 fn test_comma() {
     let grammar = parser::parse_grammar(
@@ -52,7 +72,52 @@ grammar;
     )
     .unwrap();
 
-    compare(actual, expected);
+    let expansions = [
+        (r#"Comma<"Id">"#, "Ids"),
+        (r#""Id"?"#, r#"Comma<"Id">"#),
+        (r#"(<"Id"> ",")*"#, r#"Comma<"Id">"#),
+        (r#"(<"Id"> ",")"#, r#"Comma<"Id">"#),
+        (r#"(<"Id"> ",")+"#, r#"(<"Id"> ",")*"#),
+    ];
+    compare(actual, expanded(expected, &expansions));
+}
+
+#[test]
+fn test_tuple_group() {
+    let grammar = parser::parse_grammar(
+        r#"
+grammar;
+    Pair = ("Id" "=" "Num")?;
+"#,
+    )
+    .unwrap();
+
+    let actual = expand_macros(grammar).unwrap();
+
+    let expected = parser::parse_grammar(
+        r##"
+grammar;
+    Pair = `("Id" "=" "Num")?`;
+
+    #[inline]
+    `("Id" "=" "Num")?`: core::option::Option<#`("Id" "=" "Num")`#> = {
+        `("Id" "=" "Num")` => Some(<>),
+        => None
+    };
+
+    #[inline]
+    `("Id" "=" "Num")`: (#"Id"#, #"="#, #"Num"#) = {
+        "Id" "=" "Num" => (<>),
+    };
+"##,
+    )
+    .unwrap();
+
+    let expansions = [
+        (r#"("Id" "=" "Num")?"#, "Pair"),
+        (r#"("Id" "=" "Num")"#, "Pair"),
+    ];
+    compare(actual, expanded(expected, &expansions));
 }
 
 #[test]
@@ -90,7 +155,54 @@ grammar;
     )
     .unwrap();
 
-    compare(actual, expected);
+    let expansions = [
+        (r#"Expr<"A*C">"#, "Expr1"),
+        (r#"Expr<"AAC">"#, "Expr2"),
+        (r#"Expr<"ABC">"#, "Expr3"),
+    ];
+    compare(actual, expanded(expected, &expansions));
+}
+
+#[test]
+fn test_if_combined() {
+    let grammar = parser::parse_grammar(
+        r#"
+grammar;
+    Expr<E> = {
+       "A" if E in ["+", "-"],
+       "B" if E != "+" && !(E ~~ "^[*/]$"),
+       "C" if E == "*" || E == "/",
+    };
+
+    Expr1 = Expr<"+">;
+    Expr2 = Expr<"/">;
+    Expr3 = Expr<"%">;
+"#,
+    )
+    .unwrap();
+
+    let actual = expand_macros(grammar).unwrap();
+
+    let expected = parser::parse_grammar(
+        r#"
+grammar;
+    Expr1 = `Expr<"+">`;
+    Expr2 = `Expr<"/">`;
+    Expr3 = `Expr<"%">`;
+
+    `Expr<"%">` = { "B" };
+    `Expr<"/">` = { "C" };
+    `Expr<"+">` = { "A" };
+"#,
+    )
+    .unwrap();
+
+    let expansions = [
+        (r#"Expr<"+">"#, "Expr1"),
+        (r#"Expr<"/">"#, "Expr2"),
+        (r#"Expr<"%">"#, "Expr3"),
+    ];
+    compare(actual, expanded(expected, &expansions));
 }
 
 #[test]
@@ -114,5 +226,5 @@ fn test_lookahead() {
     )
     .unwrap();
 
-    compare(actual, expected);
+    compare(actual, expanded(expected, &[("@L", "Expr")]));
 }
@@ -2,10 +2,13 @@
 //! be converted to the more canonical form. This is done as a series of
 //! passes, each contained in their own module below.
 
+use crate::collections::Map;
 use crate::grammar::parse_tree as pt;
 use crate::grammar::repr as r;
 use crate::session::Session;
 
+pub use self::precedence::Assoc;
+
 pub type NormResult<T> = Result<T, NormError>;
 
 #[derive(Clone, Debug)]
@@ -33,6 +36,21 @@ pub fn normalize_without_validating(grammar: pt::Grammar) -> NormResult<r::Gramm
     normalize_helper(&Session::new(), grammar, false)
 }
 
+/// Finds the rules of `grammar` that are better written another way,
+/// returning a warning about each; see `right_recursion` and
+/// `unused_parameters`.
+pub fn warnings(grammar: &r::Grammar) -> Vec<NormError> {
+    let mut warnings = right_recursion::find(grammar);
+    warnings.extend(unused_parameters::find(grammar));
+    warnings
+}
+
+/// The precedence level and associativity of each alternative of
+/// `grammar` that has them, by its span; see `precedence::levels`.
+pub fn precedence_levels(grammar: &pt::Grammar) -> Map<pt::Span, (u32, Assoc)> {
+    precedence::levels(grammar)
+}
+
 fn normalize_helper(
     session: &Session,
     grammar: pt::Grammar,
@@ -51,14 +69,39 @@ fn lower_helper(session: &Session, grammar: pt::Grammar, validate: bool) -> Norm
             prevalidate::validate(&grammar)?;
         }
     );
+    let grammar = profile!(
+        session,
+        "Conditional alternatives",
+        cfg::remove_inactive_alternatives(session, grammar)
+    );
     let grammar = profile!(session, "Grammar resolution", resolve::resolve(grammar)?);
+    let grammar = profile!(
+        session,
+        "Interning terminals",
+        intern::intern_terminals(grammar)?
+    );
     let grammar = profile!(session, "Precedence expansion", precedence::expand_precedence(grammar)?);
     let grammar = profile!(
         session,
         "Macro expansion",
         macro_expand::expand_macros(grammar)?
     );
+    let grammar = profile!(
+        session,
+        "Boxing impl types",
+        impl_trait::box_impl_types(grammar)?
+    );
+    let grammar = profile!(
+        session,
+        "Parameter desugaring",
+        parameters::desugar_parameters(grammar)?
+    );
     let grammar = profile!(session, "Token check", token_check::validate(grammar)?);
+    if let Some(intern_token) = grammar.intern_token() {
+        for overlap in &intern_token.dfa.overlaps {
+            log!(session, Verbose, "{}", intern_token.describe_overlap(overlap));
+        }
+    }
     let types = profile!(session, "Infer types", tyinfer::infer_types(&grammar)?);
     let grammar = profile!(session, "Lowering", lower::lower(session, grammar, types)?);
     Ok(grammar)
@@ -69,9 +112,16 @@ fn lower_helper(session: &Session, grammar: pt::Grammar, validate: bool) -> Norm
 // Check most safety conditions.
 mod prevalidate;
 
+// Remove the alternatives whose `#[cfg(..)]` does not hold.
+mod cfg;
+
 // Resolve identifiers into terminals/nonterminals etc.
 mod resolve;
 
+// Replaces the uses of the terminals marked `#[intern]` with inline
+// nonterminals that intern their text.
+mod intern;
+
 mod precedence;
 
 // Expands macros and expressions
@@ -89,6 +139,16 @@ mod precedence;
 // may occur.
 mod macro_expand;
 
+// Stores the values of nonterminals declared as `impl Trait` in a
+// `Box<dyn Trait>`.
+mod impl_trait;
+
+// Turns nonterminals with parameters into nonterminals producing
+// closures, and uses like `<x:X[a]>` into calls of those closures.
+//
+// AFTER THIS POINT: No more parameters or `X[..]` symbols.
+mod parameters;
+
 // Check if there is an extern token and all terminals have have a
 // conversion; if no extern token, synthesize an intern token.
 mod token_check;
@@ -106,6 +166,12 @@ mod lower;
 // Inline nonterminals that have requested it.
 mod inline;
 
+// Not a pass: warns about lists built by right recursion.
+mod right_recursion;
+
+// Not a pass: warns about parameters of the grammar that are never used.
+mod unused_parameters;
+
 ///////////////////////////////////////////////////////////////////////////
 // Shared routines
 
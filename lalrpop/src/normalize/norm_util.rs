@@ -1,4 +1,7 @@
-use crate::grammar::parse_tree::{ActionKind, Alternative, ExprSymbol, Name, Symbol, SymbolKind};
+use crate::grammar::parse_tree::{
+    ActionKind, Alternative, ExprSymbol, Grammar, GrammarItem, Lifetime, Name, Symbol, SymbolKind,
+    TypeBound, TypeBoundParameter, TypeParameter, TypeRef,
+};
 
 #[derive(Debug)]
 pub enum AlternativeAction<'a> {
@@ -132,3 +135,154 @@ mod test {
         );
     }
 }
+
+/// The lifetime of values borrowed from the input: `'input` when LALRPOP
+/// generates the tokenizer or the grammar names it, otherwise the first
+/// lifetime parameter of the grammar, if any. Boxed values that may
+/// capture such borrows (closures, `dyn Trait`) are bounded by it.
+pub fn input_lifetime(grammar: &Grammar) -> Option<Lifetime> {
+    let extern_tokens = grammar
+        .items
+        .iter()
+        .filter_map(GrammarItem::as_extern_token)
+        .any(|data| data.enum_token.is_some());
+    if !extern_tokens || names_input_lifetime(grammar) {
+        return Some(Lifetime::input());
+    }
+    grammar.type_parameters.iter().find_map(|tp| match *tp {
+        TypeParameter::Lifetime(ref l) => Some(l.clone()),
+        TypeParameter::Id(_) => None,
+    })
+}
+
+/// Whether `'input` is declared by, or named in the types of, `grammar`.
+/// With an external tokenizer, it need not be declared to be named;
+/// see `token_check`.
+pub fn names_input_lifetime(grammar: &Grammar) -> bool {
+    let input = TypeParameter::Lifetime(Lifetime::input());
+    if grammar.type_parameters.contains(&input) {
+        return true;
+    }
+
+    let names_input = |ty: &TypeRef| {
+        let mut lifetimes = vec![];
+        collect_lifetimes(ty, &[], &mut lifetimes);
+        lifetimes.iter().any(|&l| *l == Lifetime::input())
+    };
+    let mut named = grammar.parameters.iter().any(|p| names_input(&p.ty));
+    for item in &grammar.items {
+        match *item {
+            GrammarItem::ExternToken(ref data) => {
+                named |= data
+                    .associated_types
+                    .iter()
+                    .any(|a| names_input(&a.type_ref));
+                if let Some(ref enum_token) = data.enum_token {
+                    named |= names_input(&enum_token.type_name);
+                    for conversion in &enum_token.conversions {
+                        conversion
+                            .to
+                            .for_each_binding(&mut |ty| named |= names_input(ty));
+                    }
+                }
+            }
+            GrammarItem::Nonterminal(ref data) => {
+                named |= data.type_decl.iter().any(names_input);
+            }
+            _ => {}
+        }
+    }
+    named
+}
+
+/// Collects the lifetimes in `ty` that are not bound by an enclosing
+/// `for<..>`.
+pub fn collect_lifetimes<'ty>(
+    ty: &'ty TypeRef,
+    binder: &[TypeParameter],
+    out: &mut Vec<&'ty Lifetime>,
+) {
+    match *ty {
+        TypeRef::Tuple(ref tys)
+        | TypeRef::Nominal { types: ref tys, .. }
+        | TypeRef::TraitObject { types: ref tys, .. } => {
+            for ty in tys {
+                collect_lifetimes(ty, binder, out);
+            }
+        }
+        TypeRef::Slice(ref ty) => collect_lifetimes(ty, binder, out),
+        TypeRef::Ref {
+            ref lifetime,
+            ref referent,
+            ..
+        } => {
+            if let Some(ref lifetime) = *lifetime {
+                collect_lifetime(lifetime, binder, out);
+            }
+            collect_lifetimes(referent, binder, out);
+        }
+        TypeRef::Impl(ref bounds) => {
+            for bound in bounds {
+                match *bound {
+                    TypeBound::Lifetime(ref lifetime) => collect_lifetime(lifetime, binder, out),
+                    TypeBound::Fn {
+                        ref forall,
+                        ref parameters,
+                        ref ret,
+                        ..
+                    } => {
+                        let binder: Vec<_> = binder.iter().chain(forall).cloned().collect();
+                        for ty in parameters.iter().chain(ret) {
+                            collect_lifetimes(ty, &binder, out);
+                        }
+                    }
+                    TypeBound::Trait {
+                        ref forall,
+                        ref parameters,
+                        ..
+                    } => {
+                        let binder: Vec<_> = binder.iter().chain(forall).cloned().collect();
+                        for parameter in parameters {
+                            match *parameter {
+                                TypeBoundParameter::Lifetime(ref lifetime) => {
+                                    collect_lifetime(lifetime, &binder, out)
+                                }
+                                TypeBoundParameter::TypeParameter(ref ty)
+                                | TypeBoundParameter::Associated(_, ref ty) => {
+                                    collect_lifetimes(ty, &binder, out)
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        TypeRef::Lifetime(ref lifetime) => collect_lifetime(lifetime, binder, out),
+        TypeRef::Id(_) | TypeRef::OfSymbol(_) => {}
+        TypeRef::Fn {
+            ref forall,
+            ref parameters,
+            ref ret,
+            ref bound,
+            ..
+        } => {
+            let binder: Vec<_> = binder.iter().chain(forall).cloned().collect();
+            for ty in parameters.iter().chain(ret.as_deref()) {
+                collect_lifetimes(ty, &binder, out);
+            }
+            if let Some(ref lifetime) = *bound {
+                collect_lifetime(lifetime, &binder, out);
+            }
+        }
+    }
+}
+
+fn collect_lifetime<'ty>(
+    lifetime: &'ty Lifetime,
+    binder: &[TypeParameter],
+    out: &mut Vec<&'ty Lifetime>,
+) {
+    if !binder.contains(&TypeParameter::Lifetime(lifetime.clone())) {
+        out.push(lifetime);
+    }
+}
@@ -0,0 +1,167 @@
+//! Desugars nonterminals that declare parameters. A definition like
+//!
+//!     Body[in_loop: bool]: Ast = ...
+//!
+//! becomes an ordinary nonterminal whose value is a closure taking
+//! `in_loop`, and every use `<b:Body[true]>` binds `b` to the result
+//! of calling that closure with `true` before the action runs.
+
+use super::norm_util;
+use super::{NormError, NormResult};
+
+use crate::collections::Map;
+use crate::grammar::parse_tree::*;
+use crate::util::Sep;
+use string_cache::DefaultAtom as Atom;
+
+#[cfg(test)]
+mod test;
+
+pub fn desugar_parameters(mut grammar: Grammar) -> NormResult<Grammar> {
+    let parameters: Map<NonterminalString, Vec<Parameter>> = grammar
+        .items
+        .iter()
+        .filter_map(GrammarItem::as_nonterminal)
+        .filter(|data| !data.parameters.is_empty())
+        .map(|data| (data.name.clone(), data.parameters.clone()))
+        .collect();
+
+    if parameters.is_empty() {
+        return Ok(grammar);
+    }
+
+    let bound = norm_util::input_lifetime(&grammar);
+    for item in &mut grammar.items {
+        if let GrammarItem::Nonterminal(ref mut data) = *item {
+            desugar_nonterminal(&parameters, &bound, data)?;
+        }
+    }
+
+    Ok(grammar)
+}
+
+fn desugar_nonterminal(
+    parameters: &Map<NonterminalString, Vec<Parameter>>,
+    bound: &Option<Lifetime>,
+    data: &mut NonterminalData,
+) -> NormResult<()> {
+    for alternative in &mut data.alternatives {
+        let prelude = desugar_calls(parameters, &mut alternative.expr.symbols)?;
+
+        if !data.parameters.is_empty() {
+            let code = match alternative.action {
+                None => "(<>)".to_string(),
+                Some(ActionKind::User(ref code)) => code.clone(),
+                Some(ActionKind::Fallible(_)) => return_err!(
+                    alternative.span,
+                    "fallible actions (`=>?`) are not supported in `{}`, which declares parameters",
+                    data.name
+                ),
+                Some(ActionKind::Lookahead) | Some(ActionKind::Lookbehind) => return_err!(
+                    alternative.span,
+                    "`=>@L` and `=>@R` are not supported in `{}`, which declares parameters",
+                    data.name
+                ),
+            };
+            let params: Vec<String> = data
+                .parameters
+                .iter()
+                .map(|p| format!("{}: {}", p.name, p.ty))
+                .collect();
+            alternative.action = Some(ActionKind::User(format!(
+                "alloc::boxed::Box::new(move |{}| {{ {}{} }})",
+                params.join(", "),
+                prelude,
+                code
+            )));
+        } else if !prelude.is_empty() {
+            alternative.action = match alternative.action.take() {
+                Some(ActionKind::User(code)) => {
+                    Some(ActionKind::User(format!("{{ {}{} }}", prelude, code)))
+                }
+                Some(ActionKind::Fallible(code)) => {
+                    Some(ActionKind::Fallible(format!("{{ {}{} }}", prelude, code)))
+                }
+                action => action,
+            };
+        }
+    }
+
+    if !data.parameters.is_empty() {
+        let ret = data.type_decl.take().unwrap(); // checked by prevalidate
+        data.type_decl = Some(TypeRef::Nominal {
+            path: Path::boxed(),
+            types: vec![TypeRef::Fn {
+                forall: vec![],
+                path: Path::from_id(Atom::from("FnOnce")),
+                parameters: data.parameters.iter().map(|p| p.ty.clone()).collect(),
+                ret: Some(Box::new(ret)),
+                bound: bound.clone(),
+            }],
+        });
+        data.parameters = vec![];
+    }
+
+    Ok(())
+}
+
+/// Replaces each `<x:X[a, b]>` among `symbols` with a plain `<x:X>`,
+/// returning the statements that turn `x` into the value of `X`.
+fn desugar_calls(
+    parameters: &Map<NonterminalString, Vec<Parameter>>,
+    symbols: &mut [Symbol],
+) -> NormResult<String> {
+    let mut prelude = String::new();
+    for symbol in symbols {
+        check_uses(parameters, symbol)?;
+
+        if let SymbolKind::Name(ref mut name, ref mut sym) = symbol.kind {
+            let call = match sym.kind {
+                SymbolKind::Call(ref call) => call.clone(),
+                _ => continue,
+            };
+            let expected = match parameters.get(&call.name) {
+                Some(params) => params.len(),
+                None => return_err!(sym.span, "`{}` does not declare any parameters", call.name),
+            };
+            if call.args.len() != expected {
+                return_err!(
+                    sym.span,
+                    "wrong number of arguments to `{}`: expected {}, found {}",
+                    call.name,
+                    expected,
+                    call.args.len()
+                );
+            }
+
+            let mutable = if name.mutable { "mut " } else { "" };
+            prelude.push_str(&format!(
+                "let {}{} = {}({}); ",
+                mutable,
+                name.name,
+                name.name,
+                Sep(", ", &call.args)
+            ));
+            name.mutable = false;
+            sym.kind = SymbolKind::Nonterminal(call.name);
+        }
+    }
+    Ok(prelude)
+}
+
+/// Reports uses of a nonterminal with parameters that do not supply them.
+fn check_uses(
+    parameters: &Map<NonterminalString, Vec<Parameter>>,
+    symbol: &Symbol,
+) -> NormResult<()> {
+    match symbol.kind {
+        SymbolKind::Nonterminal(ref id) if parameters.contains_key(id) => return_err!(
+            symbol.span,
+            "`{}` declares parameters, which must be supplied at each use, as in `<x:{}[..]>`",
+            id,
+            id
+        ),
+        SymbolKind::Choose(ref sym) | SymbolKind::Name(_, ref sym) => check_uses(parameters, sym),
+        _ => Ok(()),
+    }
+}
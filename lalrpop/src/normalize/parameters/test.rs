@@ -0,0 +1,124 @@
+use crate::grammar::parse_tree::{ActionKind, Grammar, GrammarItem, NonterminalData};
+use crate::normalize::macro_expand::expand_macros;
+use crate::normalize::resolve::resolve;
+use crate::normalize::NormResult;
+use crate::parser;
+use crate::test_util;
+
+fn desugar_grammar(grammar: &str) -> NormResult<Grammar> {
+    let parsed_grammar = parser::parse_grammar(grammar).expect("parse grammar");
+    let parsed_grammar = resolve(parsed_grammar).expect("resolve");
+    let parsed_grammar = expand_macros(parsed_grammar).expect("expand macros");
+    super::desugar_parameters(parsed_grammar)
+}
+
+fn check_err(expected_err: &str, grammar: &str, span: &str) {
+    let err = desugar_grammar(grammar).unwrap_err();
+    test_util::check_norm_err(expected_err, span, err);
+}
+
+fn nonterminal<'g>(grammar: &'g Grammar, name: &str) -> &'g NonterminalData {
+    grammar
+        .items
+        .iter()
+        .filter_map(GrammarItem::as_nonterminal)
+        .find(|data| data.name.0 == *name)
+        .unwrap()
+}
+
+fn actions(data: &NonterminalData) -> Vec<String> {
+    data.alternatives
+        .iter()
+        .map(|alt| match alt.action {
+            Some(ActionKind::User(ref code)) => code.clone(),
+            ref action => panic!("unexpected action {:?}", action),
+        })
+        .collect()
+}
+
+#[test]
+fn closures_and_calls() {
+    let grammar = desugar_grammar(
+        r#"
+grammar;
+    pub Top: u32 = <mut b:Body[true]> => b;
+    Body[in_loop: bool]: u32 = {
+        "x" => if in_loop { 1 } else { 0 },
+        "(" <b:Body[in_loop]> ")" => b,
+    };
+"#,
+    )
+    .unwrap();
+
+    let top = nonterminal(&grammar, "Top");
+    assert_eq!(actions(top), vec!["{ let mut b = b(true); b }"]);
+
+    let body = nonterminal(&grammar, "Body");
+    assert!(body.parameters.is_empty());
+    assert_eq!(
+        body.type_decl.as_ref().unwrap().to_string(),
+        "alloc::boxed::Box<dyn FnOnce(bool) -> u32 + 'input>"
+    );
+    assert_eq!(
+        actions(body),
+        vec![
+            "alloc::boxed::Box::new(move |in_loop: bool| { if in_loop { 1 } else { 0 } })",
+            "alloc::boxed::Box::new(move |in_loop: bool| { let b = b(in_loop); b })",
+        ]
+    );
+}
+
+#[test]
+fn extern_tokens_without_lifetime() {
+    let grammar = desugar_grammar(
+        r#"
+grammar;
+    extern { enum Tok { X => Tok::X } }
+    pub Top: u32 = <b:Body[ONE]> => b;
+    Body[n: u32]: u32 = X => n;
+"#,
+    )
+    .unwrap();
+
+    let body = nonterminal(&grammar, "Body");
+    assert_eq!(
+        body.type_decl.as_ref().unwrap().to_string(),
+        "alloc::boxed::Box<dyn FnOnce(u32) -> u32>"
+    );
+}
+
+#[test]
+fn use_without_arguments() {
+    check_err(
+        r#"`Body` declares parameters, which must be supplied at each use"#,
+        r#"grammar; pub Top: u32 = <Body>; Body[n: u32]: u32 = "x" => n;"#,
+        r#"                         ~~~~                                "#,
+    );
+}
+
+#[test]
+fn wrong_number_of_arguments() {
+    check_err(
+        r#"wrong number of arguments to `Body`: expected 1, found 2"#,
+        r#"grammar; pub Top: u32 = <b:Body[ONE, TWO]> => b; Body[n: u32]: u32 = "x" => n;"#,
+        r#"                           ~~~~~~~~~~~~~~                                     "#,
+    );
+}
+
+#[test]
+fn arguments_without_parameters() {
+    check_err(
+        r#"`Body` does not declare any parameters"#,
+        r#"grammar; pub Top: u32 = <b:Body[ONE]> => b; Body: u32 = "x" => 1; X[n: u32]: u32 = "y" => n;"#,
+        r#"                           ~~~~~~~~~                                                      "#,
+    );
+}
+
+#[test]
+fn fallible_action() {
+    check_err(
+        r#"fallible actions \(`=>\?`\) are not supported in `Body`"#,
+        r#"grammar; pub Top: u32 = <b:Body[ONE]> => b; Body[n: u32]: u32 = "x" =>? Ok(n);"#,
+        r#"                                                                ~~~~~~~~~~~~~ "#,
+    );
+}
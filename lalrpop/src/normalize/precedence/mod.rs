@@ -9,9 +9,10 @@
 //! For concrete examples, see the [`test`](../tests/index.html) module.
 use super::resolve;
 use super::NormResult;
+use crate::collections::Map;
 use crate::grammar::parse_tree::{
-    Alternative, ExprSymbol, Grammar, GrammarItem, NonterminalData, NonterminalString, Symbol,
-    SymbolKind,
+    Alternative, ExprSymbol, Grammar, GrammarItem, NonterminalData, NonterminalString, Span,
+    Symbol, SymbolKind,
 };
 use std::fmt;
 use std::str::FromStr;
@@ -114,6 +115,18 @@ impl fmt::Display for ParseAssocError {
     }
 }
 
+impl fmt::Display for Assoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Assoc::Left => "left",
+            Assoc::Right => "right",
+            Assoc::NonAssoc => "none",
+            Assoc::FullyAssoc => "all",
+        }
+        .fmt(f)
+    }
+}
+
 impl FromStr for Assoc {
     type Err = ParseAssocError;
 
@@ -162,6 +175,62 @@ pub fn has_prec_annot(non_term: &NonterminalData) -> bool {
         .unwrap_or(false)
 }
 
+/// The precedence level and associativity of each alternative of the rules with precedence
+/// annotations, by the span of the alternative. The productions that precedence expansion makes
+/// of an alternative keep its span, so this recovers what the annotations said.
+///
+/// `grammar` must have passed prevalidation.
+pub fn levels(grammar: &Grammar) -> Map<Span, (u32, Assoc)> {
+    let mut levels = Map::new();
+    for item in &grammar.items {
+        match *item {
+            GrammarItem::Nonterminal(ref d) if has_prec_annot(d) => {
+                d.alternatives
+                    .iter()
+                    .fold((0, Assoc::default()), |last, alt| {
+                        let level = alternative_level(alt, last);
+                        levels.insert(alt.span, level);
+                        level
+                    });
+            }
+            _ => {}
+        }
+    }
+    levels
+}
+
+/// The precedence level and associativity of `alt`, given those of the alternative before it.
+fn alternative_level(alt: &Alternative, (last_lvl, last_assoc): (u32, Assoc)) -> (u32, Assoc) {
+    // All the following unsafe `unwrap()`, `panic!()`, etc. should never panic thanks to
+    // prevalidation. Prevalidation ensures, beside that the first alternative is annotated with
+    // a precedence level, that each precedence annotation has an argument which
+    // is parsable as an integer, and that each optional assoc annotation which a parsable
+    // `Assoc`.
+
+    // If there is a new precedence association, the associativity is reset to the default
+    // one (that is, `FullyAssoc`), instead of using the last one encountered.
+    let (lvl, last_assoc) = alt
+        .annotations
+        .iter()
+        .find(|ann| ann.id == Atom::from(PREC_ANNOT))
+        .map(|ann| {
+            (
+                ann.arg.as_ref().unwrap().1.parse().unwrap(),
+                Assoc::default(),
+            )
+        })
+        .unwrap_or((last_lvl, last_assoc));
+
+    let assoc = alt
+        .annotations
+        .iter()
+        .find(|ann| ann.id == Atom::from(ASSOC_ANNOT))
+        .map(|ann| ann.arg.as_ref().unwrap().1.parse().unwrap())
+        .unwrap_or(last_assoc);
+
+    (lvl, assoc)
+}
+
 /// Expand a rule with precedence annotations. As it implies to generate new rules, return a vector
 /// of grammar items.
 fn expand_nonterm(mut nonterm: NonterminalData) -> NormResult<Vec<GrammarItem>> {
@@ -172,36 +241,11 @@ fn expand_nonterm(mut nonterm: NonterminalData) -> NormResult<Vec<GrammarItem>>
         // Thanks to prevalidation, the first alternative must have a precedence annotation that
         // will set last_lvl to an initial value
         (0, Assoc::default()),
-        |(last_lvl, last_assoc): (u32, Assoc), mut alt| {
-            // All the following unsafe `unwrap()`, `panic!()`, etc. should never panic thanks to
-            // prevalidation. Prevalidation ensures, beside that the first alternative is annotated with
-            // a precedence level, that each precedence annotation has an argument which
-            // is parsable as an integer, and that each optional assoc annotation which a parsable
-            // `Assoc`.
-
-            // Extract precedence and associativity annotations
-
-            // If there is a new precedence association, the associativity is reset to the default
-            // one (that is, `FullyAssoc`), instead of using the last one encountered.
-            let (lvl, last_assoc) = alt
-                .annotations
-                .iter()
-                .position(|ann| ann.id == Atom::from(PREC_ANNOT))
-                .map(|index| {
-                    let (_, val) = alt.annotations.remove(index).arg.unwrap();
-                    (val.parse().unwrap(), Assoc::default())
-                })
-                .unwrap_or((last_lvl, last_assoc));
-
-            let assoc = alt
-                .annotations
-                .iter()
-                .position(|ann| ann.id == Atom::from(ASSOC_ANNOT))
-                .map(|index| {
-                    let (_, val) = alt.annotations.remove(index).arg.unwrap();
-                    val.parse().unwrap()
-                })
-                .unwrap_or(last_assoc);
+        |last, mut alt| {
+            let (lvl, assoc) = alternative_level(&alt, last);
+            alt.annotations.retain(|ann| {
+                ann.id != Atom::from(PREC_ANNOT) && ann.id != Atom::from(ASSOC_ANNOT)
+            });
 
             alts_with_ann.push((lvl, assoc, alt));
             lvls.push(lvl);
@@ -282,6 +326,7 @@ fn expand_nonterm(mut nonterm: NonterminalData) -> NormResult<Vec<GrammarItem>>
                         }],
                     },
                     condition: None,
+                    guard: None,
                     action: None,
                     annotations: vec![],
                 });
@@ -293,8 +338,10 @@ fn expand_nonterm(mut nonterm: NonterminalData) -> NormResult<Vec<GrammarItem>>
                 annotations: nonterm.annotations.clone(),
                 span: nonterm.span,
                 args: nonterm.args.clone(), // macro arguments
+                parameters: nonterm.parameters.clone(),
                 type_decl: nonterm.type_decl.clone(),
                 alternatives,
+                expansion: None,
             })
         });
 
@@ -372,6 +419,7 @@ fn replace_symbol<'a>(
         }
         SymbolKind::Terminal(_)
         | SymbolKind::Nonterminal(_)
+        | SymbolKind::Call(_)
         | SymbolKind::Error
         | SymbolKind::Lookahead
         | SymbolKind::Lookbehind => subst,
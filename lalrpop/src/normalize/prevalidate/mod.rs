@@ -49,6 +49,11 @@ impl<'grammar> Validator<'grammar> {
             Atom::from(TABLE_DRIVEN),
             Atom::from(RECURSIVE_ASCENT),
             Atom::from(TEST_ALL),
+            Atom::from(LONGEST_MATCH),
+            Atom::from(REPAIR),
+            Atom::from(RUNTIME_START),
+            Atom::from(BACKTRACK),
+            Atom::from(COPY_LOCATION),
         ];
         for annotation in &self.grammar.annotations {
             if !allowed_names.contains(&annotation.id) {
@@ -60,10 +65,79 @@ impl<'grammar> Validator<'grammar> {
             }
         }
 
+        if let Some(repair) = self.grammar.annotations.iter().find(|a| a.id == *REPAIR) {
+            if let Some((ref name, ref value)) = repair.arg {
+                if *name != *REPAIR_STRATEGY_ARG {
+                    return_err!(
+                        repair.id_span,
+                        "invalid argument `{}` for repair annotation, expected `{}`",
+                        name,
+                        REPAIR_STRATEGY_ARG
+                    );
+                }
+                if value.parse::<r::RepairStrategy>().is_err() {
+                    return_err!(
+                        repair.id_span,
+                        "could not parse the repair strategy `{}`, expected `single_token`, `burke_fisher` or `minimum_cost`",
+                        value
+                    );
+                }
+            }
+
+            self.validate_table_driven(None, repair)?;
+        }
+
+        if let Some(backtrack) = self.grammar.annotations.iter().find(|a| a.id == *BACKTRACK) {
+            self.validate_table_driven(None, backtrack)?;
+
+            // a resumable parser cannot read ahead to try the actions
+            let resumable = self
+                .grammar
+                .items
+                .iter()
+                .filter_map(GrammarItem::as_nonterminal)
+                .flat_map(|data| &data.annotations)
+                .find(|a| a.id == *RESUMABLE);
+            if let Some(resumable) = resumable {
+                return_err!(
+                    resumable.id_span,
+                    "`#[{}]` cannot be combined with `#[{}]`",
+                    RESUMABLE,
+                    BACKTRACK
+                );
+            }
+        }
+
+        self.validate_token_annotations()?;
+
+        for parameter in &self.grammar.parameters {
+            self.validate_lifetimes(&parameter.ty, self.grammar.span)?;
+        }
+
         for item in &self.grammar.items {
             match *item {
                 GrammarItem::Use(..) => {}
 
+                GrammarItem::Code(..) => {}
+
+                // the items of the file take its place when the grammar is
+                // read from a file, so this one was not
+                GrammarItem::Include(ref data) => {
+                    return_err!(data.span, "`include` is only supported in grammar files");
+                }
+
+                GrammarItem::TypeAlias(ref data) => {
+                    let duplicate = self.grammar.items.iter().any(|item| match *item {
+                        GrammarItem::TypeAlias(ref other) => {
+                            other.name == data.name && other.span.0 < data.span.0
+                        }
+                        _ => false,
+                    });
+                    if duplicate {
+                        return_err!(data.span, "type alias `{}` already specified", data.name);
+                    }
+                }
+
                 GrammarItem::MatchToken(ref data) => {
                     if data.span != self.match_token.unwrap().span {
                         return_err!(data.span, "multiple match definitions are not permitted");
@@ -129,9 +203,42 @@ impl<'grammar> Validator<'grammar> {
                     if data.visibility.is_pub() && !data.args.is_empty() {
                         return_err!(data.span, "macros cannot be marked public");
                     }
+                    if !data.parameters.is_empty() {
+                        self.validate_parameters(data)?;
+                    }
+                    if let Some(ref ty) = data.type_decl {
+                        self.validate_lifetimes(ty, data.span)?;
+                    }
                     let inline_annotation = Atom::from(INLINE);
                     let cfg_annotation = Atom::from(CFG);
-                    let known_annotations = [inline_annotation.clone(), cfg_annotation.clone()];
+                    let prefix_annotation = Atom::from(PREFIX);
+                    let resumable_annotation = Atom::from(RESUMABLE);
+                    let cloneable_annotation = Atom::from(CLONEABLE);
+                    let completions_annotation = Atom::from(COMPLETIONS);
+                    let island_annotation = Atom::from(ISLAND);
+                    let events_annotation = Atom::from(EVENTS);
+                    let recognize_annotation = Atom::from(RECOGNIZE);
+                    let cancellable_annotation = Atom::from(CANCELLABLE);
+                    let table_driven_annotation = Atom::from(TABLE_DRIVEN);
+                    let recursive_ascent_annotation = Atom::from(RECURSIVE_ASCENT);
+                    let expected_annotation = Atom::from(EXPECTED);
+                    let owned_annotation = Atom::from(OWNED);
+                    let known_annotations = [
+                        inline_annotation.clone(),
+                        cfg_annotation.clone(),
+                        prefix_annotation.clone(),
+                        resumable_annotation.clone(),
+                        cloneable_annotation.clone(),
+                        completions_annotation.clone(),
+                        island_annotation.clone(),
+                        events_annotation.clone(),
+                        recognize_annotation.clone(),
+                        cancellable_annotation.clone(),
+                        table_driven_annotation.clone(),
+                        recursive_ascent_annotation.clone(),
+                        expected_annotation.clone(),
+                        owned_annotation.clone(),
+                    ];
                     let mut found_annotations = set();
                     for annotation in &data.annotations {
                         if !known_annotations.contains(&annotation.id) {
@@ -151,15 +258,119 @@ impl<'grammar> Validator<'grammar> {
                                 annotation.id_span,
                                 "public items cannot be marked #[inline]"
                             );
+                        } else if annotation.id == island_annotation {
+                            if !data.visibility.is_pub() {
+                                return_err!(
+                                    annotation.id_span,
+                                    "only public items can be marked #[island]"
+                                );
+                            }
+                            let enum_token =
+                                self.extern_token.and_then(|data| data.enum_token.as_ref());
+                            if enum_token.is_some() {
+                                return_err!(
+                                    annotation.id_span,
+                                    "#[island] requires the tokenizer to be generated by LALRPOP; \
+                                     with an `extern` token type, use #[prefix] instead"
+                                );
+                            }
+                            self.validate_table_driven(Some(data), annotation)?;
+                        } else if annotation.id == owned_annotation {
+                            if !data.visibility.is_pub() {
+                                return_err!(
+                                    annotation.id_span,
+                                    "only public items can be marked #[owned]"
+                                );
+                            }
+                            let enum_token =
+                                self.extern_token.and_then(|data| data.enum_token.as_ref());
+                            if enum_token.is_some() {
+                                return_err!(
+                                    annotation.id_span,
+                                    "#[owned] requires the tokenizer to be generated by LALRPOP"
+                                );
+                            }
+                        } else if annotation.id == prefix_annotation
+                            || annotation.id == completions_annotation
+                            || annotation.id == events_annotation
+                            || annotation.id == recognize_annotation
+                            || annotation.id == cancellable_annotation
+                        {
+                            if !data.visibility.is_pub() {
+                                return_err!(
+                                    annotation.id_span,
+                                    "only public items can be marked #[{}]",
+                                    annotation.id
+                                );
+                            }
+                            self.validate_table_driven(Some(data), annotation)?;
+                        } else if annotation.id == resumable_annotation {
+                            if !data.visibility.is_pub() {
+                                return_err!(
+                                    annotation.id_span,
+                                    "only public items can be marked #[resumable]"
+                                );
+                            }
+                            let enum_token =
+                                self.extern_token.and_then(|data| data.enum_token.as_ref());
+                            if enum_token.is_none() {
+                                return_err!(
+                                    annotation.id_span,
+                                    "#[resumable] requires the token type to be defined in an `extern` block"
+                                );
+                            }
+                            self.validate_table_driven(Some(data), annotation)?;
+                        } else if annotation.id == cloneable_annotation {
+                            // only the resumable parser is kept by the caller
+                            let resumable = data
+                                .annotations
+                                .iter()
+                                .any(|a| a.id == resumable_annotation);
+                            if !resumable {
+                                return_err!(
+                                    annotation.id_span,
+                                    "#[cloneable] requires the item to be marked #[resumable]"
+                                );
+                            }
+                        } else if annotation.id == table_driven_annotation
+                            || annotation.id == recursive_ascent_annotation
+                        {
+                            if !data.visibility.is_pub() {
+                                return_err!(
+                                    annotation.id_span,
+                                    "only public items can be marked #[{}]",
+                                    annotation.id
+                                );
+                            }
+                            if found_annotations.contains(&table_driven_annotation)
+                                && found_annotations.contains(&recursive_ascent_annotation)
+                            {
+                                return_err!(
+                                    annotation.id_span,
+                                    "`#[{}]` cannot be combined with `#[{}]`",
+                                    TABLE_DRIVEN,
+                                    RECURSIVE_ASCENT
+                                );
+                            }
+                        } else if annotation.id == expected_annotation {
+                            match annotation.arg {
+                                Some((ref name, ref value)) if *name == *EXPECTED_NAME_ARG => {
+                                    if value.is_empty() {
+                                        return_err!(
+                                            annotation.id_span,
+                                            "the name given by #[expected] cannot be empty"
+                                        );
+                                    }
+                                }
+                                _ => return_err!(
+                                    annotation.id_span,
+                                    "#[expected] needs a name, as in `#[expected({} = \"an expression\")]`",
+                                    EXPECTED_NAME_ARG
+                                ),
+                            }
                         } else if annotation.id == cfg_annotation {
                             if data.visibility.is_pub() {
-                                match annotation.arg {
-                                    Some((ref name, _)) if name == "feature" => (),
-                                    _ => return_err!(
-                                        annotation.id_span,
-                                        r#"`cfg` annotations must have a `feature = "my_feature" argument"#
-                                    ),
-                                }
+                                self.validate_cfg(annotation)?;
                             } else {
                                 return_err!(
                                     annotation.id_span,
@@ -171,6 +382,11 @@ impl<'grammar> Validator<'grammar> {
 
                     self.validate_precedence(&data.alternatives)?;
 
+                    let guarded = data.alternatives.iter().find(|a| a.guard.is_some());
+                    if let Some(alternative) = guarded {
+                        self.validate_guard(data, alternative)?;
+                    }
+
                     for alternative in &data.alternatives {
                         self.validate_alternative(alternative)?;
                     }
@@ -181,6 +397,170 @@ impl<'grammar> Validator<'grammar> {
         Ok(())
     }
 
+    /// Checks that the grammar does not request a code generator other
+    /// than the table-driven one, which is the only one supporting
+    /// `annotation`, for the nonterminal `data` or, without one, for any
+    /// nonterminal.
+    fn validate_table_driven(
+        &self,
+        data: Option<&'grammar NonterminalData>,
+        annotation: &Annotation,
+    ) -> NormResult<()> {
+        if let Some(other) = self.other_codegen(data) {
+            return_err!(
+                annotation.id_span,
+                "`#[{}]` is only supported by table-driven parsers, not `#[{}]`",
+                annotation.id,
+                other.id
+            );
+        }
+        Ok(())
+    }
+
+    /// The annotation requesting a code generator other than the
+    /// table-driven one for the nonterminal `data`, which can override
+    /// the grammar's with its own annotation, or, without one, for any
+    /// nonterminal.
+    fn other_codegen(
+        &self,
+        data: Option<&'grammar NonterminalData>,
+    ) -> Option<&'grammar Annotation> {
+        let grammar_codegen = self
+            .grammar
+            .annotations
+            .iter()
+            .find(|a| a.id == *RECURSIVE_ASCENT || a.id == *TEST_ALL);
+        match data {
+            Some(data) => {
+                let own_codegen = data
+                    .annotations
+                    .iter()
+                    .find(|a| a.id == *TABLE_DRIVEN || a.id == *RECURSIVE_ASCENT);
+                match own_codegen {
+                    Some(own) if own.id == *TABLE_DRIVEN => None,
+                    Some(own) => Some(own),
+                    None => grammar_codegen,
+                }
+            }
+            None => grammar_codegen.or_else(|| {
+                self.grammar
+                    .items
+                    .iter()
+                    .filter_map(GrammarItem::as_nonterminal)
+                    .flat_map(|data| &data.annotations)
+                    .find(|a| a.id == *RECURSIVE_ASCENT)
+            }),
+        }
+    }
+
+    /// Checks an alternative with a `when { .. }` guard, which borrows
+    /// the symbols on the stack when the table-driven parser is about to
+    /// reduce it.
+    fn validate_guard(&self, data: &NonterminalData, alternative: &Alternative) -> NormResult<()> {
+        if data.annotations.iter().any(|a| a.id == *INLINE) {
+            return_err!(
+                alternative.span,
+                "alternatives of `#[inline]` items cannot have a `when` guard"
+            );
+        }
+        if !data.parameters.is_empty() {
+            return_err!(
+                alternative.span,
+                "alternatives of items that declare parameters cannot have a `when` guard"
+            );
+        }
+        if let Some(other) = self.other_codegen(None) {
+            return_err!(
+                alternative.span,
+                "`when` guards are only supported by table-driven parsers, not `#[{}]`",
+                other.id
+            );
+        }
+        // these parsers never build the values the guard inspects
+        let no_values = self
+            .grammar
+            .items
+            .iter()
+            .filter_map(GrammarItem::as_nonterminal)
+            .flat_map(|data| &data.annotations)
+            .find(|a| a.id == *EVENTS || a.id == *RECOGNIZE);
+        if let Some(annotation) = no_values {
+            return_err!(
+                annotation.id_span,
+                "`#[{}]` cannot be combined with `when` guards",
+                annotation.id
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that the lifetimes in `ty`, such as the `'ast` of an arena
+    /// in `&'ast Expr<'ast>`, are declared like `grammar<'ast>`, since
+    /// every generated signature carries them.
+    fn validate_lifetimes(&self, ty: &TypeRef, span: Span) -> NormResult<()> {
+        let mut lifetimes = vec![];
+        norm_util::collect_lifetimes(ty, &[], &mut lifetimes);
+        for lifetime in lifetimes {
+            let declared = self
+                .grammar
+                .type_parameters
+                .contains(&TypeParameter::Lifetime(lifetime.clone()))
+                || *lifetime == Lifetime::statik()
+                || lifetime.is_anonymous()
+                // declared implicitly if need be; see `token_check`
+                || *lifetime == Lifetime::input();
+            if !declared {
+                return_err!(
+                    span,
+                    "undeclared lifetime `{}`; declare it as a type parameter, as in `grammar<{}>`",
+                    lifetime,
+                    lifetime
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a nonterminal like `X[a: T]: U = ...`, whose value is
+    /// computed only once its uses supply the parameters.
+    fn validate_parameters(&self, data: &NonterminalData) -> NormResult<()> {
+        if data.visibility.is_pub() {
+            return_err!(data.span, "public items cannot declare parameters");
+        }
+        if !data.args.is_empty() {
+            return_err!(data.span, "macros cannot declare parameters");
+        }
+        if data.type_decl.is_none() {
+            return_err!(
+                data.span,
+                "nonterminals with parameters must declare their type, as in `{}[..]: T`",
+                data.name
+            );
+        }
+        let with_precedence = data.alternatives.iter().any(|alt| {
+            alt.annotations.iter().any(|ann| {
+                *ann.id == *precedence::PREC_ANNOT || *ann.id == *precedence::ASSOC_ANNOT
+            })
+        });
+        if with_precedence {
+            return_err!(
+                data.span,
+                "nonterminals with parameters cannot use precedence annotations"
+            );
+        }
+        let mut names = set();
+        for parameter in &data.parameters {
+            if !names.insert(parameter.name.clone()) {
+                return_err!(
+                    data.span,
+                    "multiple parameters named `{}` are not permitted",
+                    parameter.name
+                );
+            }
+        }
+        Ok(())
+    }
+
     fn validate_precedence(&self, alternatives: &Vec<Alternative>) -> NormResult<()> {
         let with_precedence = alternatives.iter().any(|alt| {
             alt.annotations.iter().any(|ann| {
@@ -270,11 +650,12 @@ impl<'grammar> Validator<'grammar> {
     }
 
     fn validate_alternative(&self, alternative: &Alternative) -> NormResult<()> {
-        self.validate_expr(&alternative.expr)?;
+        self.validate_expr(&alternative.expr, true)?;
 
         let allowed_names = vec![
             Atom::from(precedence::PREC_ANNOT),
             Atom::from(precedence::ASSOC_ANNOT),
+            Atom::from(CFG),
         ];
 
         for annotation in &alternative.annotations {
@@ -285,6 +666,9 @@ impl<'grammar> Validator<'grammar> {
                     annotation.id
                 );
             }
+            if annotation.id == *CFG {
+                self.validate_cfg(annotation)?;
+            }
         }
 
         match norm_util::analyze_expr(&alternative.expr) {
@@ -318,9 +702,205 @@ impl<'grammar> Validator<'grammar> {
         Ok(())
     }
 
-    fn validate_expr(&self, expr: &ExprSymbol) -> NormResult<()> {
+    /// Checks the annotations of the items of the match block and of the
+    /// conversions of the extern token enum: `#[terminal_id]` and
+    /// `#[cost]`, which either can have, and `#[intern]`, which only the
+    /// items of the match block can have.
+    fn validate_token_annotations(&self) -> NormResult<()> {
+        let match_items = self
+            .match_token
+            .into_iter()
+            .flat_map(|match_token| &match_token.contents)
+            .flat_map(|contents| &contents.items)
+            .map(|item| (item.annotations(), item.terminal().is_some(), true));
+        let conversions = self
+            .extern_token
+            .and_then(|extern_token| extern_token.enum_token.as_ref())
+            .into_iter()
+            .flat_map(|enum_token| &enum_token.conversions)
+            .map(|conversion| (&conversion.annotations[..], true, false));
+
+        let mut names = set();
+        let mut ids = set();
+        for (annotations, has_terminal, in_match) in match_items.chain(conversions) {
+            let mut found = set();
+            for annotation in annotations {
+                if annotation.id != *TERMINAL_ID
+                    && annotation.id != *COST
+                    && (annotation.id != *INTERN || !in_match)
+                {
+                    return_err!(
+                        annotation.id_span,
+                        "unrecognized annotation `{}`",
+                        annotation.id
+                    );
+                }
+                // a terminal may have one `#[cost]` for each argument
+                let arg = match annotation.arg {
+                    Some((ref name, _)) if annotation.id == *COST => Some(name.clone()),
+                    _ => None,
+                };
+                if !found.insert((annotation.id.clone(), arg)) {
+                    return_err!(
+                        annotation.id_span,
+                        "duplicate annotation `{}`",
+                        annotation.id
+                    );
+                }
+                if annotation.id == *INTERN {
+                    self.validate_intern(annotation, has_terminal)?;
+                    continue;
+                }
+                if annotation.id == *COST {
+                    self.validate_cost(annotation, has_terminal)?;
+                    continue;
+                }
+                if !has_terminal {
+                    return_err!(
+                        annotation.id_span,
+                        "input that is skipped has no terminal to give an id"
+                    );
+                }
+                let (name, id) = match annotation.arg {
+                    Some((ref name, ref id)) => (name, id),
+                    None => return_err!(
+                        annotation.id_span,
+                        r#"`{}` annotations must name the id and give its value, as in `{}(PLUS = "1")`"#,
+                        TERMINAL_ID,
+                        TERMINAL_ID
+                    ),
+                };
+                let id = match id.parse::<u32>() {
+                    Ok(id) => id,
+                    Err(_) => return_err!(
+                        annotation.id_span,
+                        "the id `{}` of a terminal must be a number",
+                        id
+                    ),
+                };
+                if name == "ALL" {
+                    return_err!(
+                        annotation.id_span,
+                        "`ALL` is the list of all terminal ids, and cannot name one"
+                    );
+                }
+                if !names.insert(name.clone()) {
+                    return_err!(
+                        annotation.id_span,
+                        "there is already a terminal id named `{}`",
+                        name
+                    );
+                }
+                if !ids.insert(id) {
+                    return_err!(
+                        annotation.id_span,
+                        "another terminal already has the id `{}`",
+                        id
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a `#[cost(insert = "5")]` annotation, which only error
+    /// repair makes use of.
+    fn validate_cost(&self, annotation: &Annotation, has_terminal: bool) -> NormResult<()> {
+        if !has_terminal {
+            return_err!(
+                annotation.id_span,
+                "input that is skipped has no terminal to give a cost"
+            );
+        }
+        if !self.grammar.annotations.iter().any(|a| a.id == *REPAIR) {
+            return_err!(
+                annotation.id_span,
+                "`#[{}]` is only used by error repair, which needs `#[{}]` on the grammar",
+                COST,
+                REPAIR
+            );
+        }
+        let value = match annotation.arg {
+            Some((ref name, ref value))
+                if *name == *COST_INSERT_ARG || *name == *COST_DELETE_ARG =>
+            {
+                value
+            }
+            _ => return_err!(
+                annotation.id_span,
+                r#"`{}` annotations must give the cost of inserting or deleting the terminal, as in `{}({} = "5")`"#,
+                COST,
+                COST,
+                COST_INSERT_ARG
+            ),
+        };
+        if value.parse::<u32>().is_err() {
+            return_err!(
+                annotation.id_span,
+                "the cost `{}` of an edit must be a number",
+                value
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks an `#[intern(with = "names")]` annotation, which must
+    /// name a parameter of the grammar.
+    fn validate_intern(&self, annotation: &Annotation, has_terminal: bool) -> NormResult<()> {
+        if !has_terminal {
+            return_err!(
+                annotation.id_span,
+                "input that is skipped has no terminal to intern"
+            );
+        }
+        let parameter = match annotation.arg {
+            Some((ref name, ref parameter)) if *name == *INTERN_WITH_ARG => parameter,
+            _ => return_err!(
+                annotation.id_span,
+                r#"`{}` annotations must name the grammar parameter to intern with, as in `{}({} = "names")`"#,
+                INTERN,
+                INTERN,
+                INTERN_WITH_ARG
+            ),
+        };
+        if !self
+            .grammar
+            .parameters
+            .iter()
+            .any(|p| &*p.name == parameter)
+        {
+            return_err!(
+                annotation.id_span,
+                "the grammar has no parameter `{}` to intern with",
+                parameter
+            );
+        }
+        Ok(())
+    }
+
+    fn validate_cfg(&self, annotation: &Annotation) -> NormResult<()> {
+        match annotation.arg {
+            Some((ref name, _)) if name == "feature" || name == "flag" => Ok(()),
+            _ => return_err!(
+                annotation.id_span,
+                r#"`cfg` annotations must have a `feature = "my_feature"` or `flag = "my_flag"` argument"#
+            ),
+        }
+    }
+
+    fn validate_expr(&self, expr: &ExprSymbol, top_level: bool) -> NormResult<()> {
         for symbol in &expr.symbols {
-            self.validate_symbol(symbol)?;
+            match symbol.kind {
+                // `<x:X[a]>` is only meaningful here, where the action
+                // can bind `x` to the value computed from `a`
+                SymbolKind::Name(_, ref sym) if top_level => {
+                    if let SymbolKind::Call(_) = sym.kind {
+                        continue;
+                    }
+                    self.validate_symbol(sym)?;
+                }
+                _ => self.validate_symbol(symbol)?,
+            }
         }
 
         let chosen: Vec<&Symbol> = expr
@@ -366,15 +946,31 @@ impl<'grammar> Validator<'grammar> {
     fn validate_symbol(&self, symbol: &Symbol) -> NormResult<()> {
         match symbol.kind {
             SymbolKind::Expr(ref expr) => {
-                self.validate_expr(expr)?;
+                self.validate_expr(expr, false)?;
             }
             SymbolKind::AmbiguousId(_) => { /* see resolve */ }
             SymbolKind::Terminal(_) => { /* see postvalidate! */ }
             SymbolKind::Nonterminal(_) => { /* see resolve */ }
+            SymbolKind::Call(_) => {
+                return_err!(
+                    symbol.span,
+                    "parameters can only be supplied to a named symbol at the top level \
+                     of an alternative, as in `<x:{}>`",
+                    symbol
+                );
+            }
             SymbolKind::Error => {
                 let mut algorithm = r::Algorithm::default();
                 read_algorithm(&self.grammar.annotations, &mut algorithm);
-                if algorithm.codegen == r::LrCodeGeneration::RecursiveAscent {
+                let recursive_ascent_nonterminal = self
+                    .grammar
+                    .items
+                    .iter()
+                    .filter_map(GrammarItem::as_nonterminal)
+                    .any(|data| data.annotations.iter().any(|a| a.id == *RECURSIVE_ASCENT));
+                if algorithm.codegen == r::LrCodeGeneration::RecursiveAscent
+                    || recursive_ascent_nonterminal
+                {
                     return_err!(
                         symbol.span,
                         "error recovery is not yet supported by recursive ascent parsers"
@@ -34,6 +34,15 @@ fn dup_assoc_type() {
     );
 }
 
+#[test]
+fn dup_type_alias() {
+    check_err(
+        r#"type alias `Foo` already specified"#,
+        r#"grammar; type Foo = i32; type Foo<T> = T; X: Foo = "x";"#,
+        r#"                              ~~~                      "#,
+    );
+}
+
 #[test]
 fn lookahead_without_loc_type() {
     check_err(
@@ -61,6 +70,174 @@ fn unrecognized_annotation() {
     );
 }
 
+#[test]
+fn repair_with_recursive_ascent() {
+    check_err(
+        r#"`#\[repair\]` is only supported by table-driven parsers, not `#\[recursive_ascent\]`"#,
+        r#"#[repair] #[recursive_ascent] grammar; Term = ();"#,
+        r#"  ~~~~~~                                         "#,
+    );
+}
+
+#[test]
+fn repair_with_recursive_ascent_nonterminal() {
+    check_err(
+        r#"`#\[repair\]` is only supported by table-driven parsers, not `#\[recursive_ascent\]`"#,
+        r#"#[repair] grammar; #[recursive_ascent] pub Term = ();"#,
+        r#"  ~~~~~~                                             "#,
+    );
+}
+
+#[test]
+fn private_codegen_annotation() {
+    check_err(
+        r#"only public items can be marked #\[recursive_ascent\]"#,
+        r#"grammar; #[recursive_ascent] Term = ();"#,
+        r#"           ~~~~~~~~~~~~~~~~            "#,
+    );
+}
+
+#[test]
+fn conflicting_codegen_annotations() {
+    check_err(
+        r#"`#\[table_driven\]` cannot be combined with `#\[recursive_ascent\]`"#,
+        r#"grammar; #[table_driven] #[recursive_ascent] pub Term = ();"#,
+        r#"                           ~~~~~~~~~~~~~~~~                "#,
+    );
+}
+
+#[test]
+fn prefix_with_recursive_ascent_nonterminal() {
+    check_err(
+        r#"`#\[prefix\]` is only supported by table-driven parsers, not `#\[recursive_ascent\]`"#,
+        r#"grammar; #[recursive_ascent] #[prefix] pub Term = ();"#,
+        r#"                               ~~~~~~              "#,
+    );
+}
+
+#[test]
+fn prefix_with_table_driven_nonterminal() {
+    let grammar = r#"#[recursive_ascent] grammar; #[table_driven] #[prefix] pub Term = ();"#;
+    super::validate(&parser::parse_grammar(grammar).unwrap()).unwrap();
+}
+
+#[test]
+fn repair_unknown_strategy() {
+    check_err(
+        r#"could not parse the repair strategy `guess`, expected `single_token`, `burke_fisher` or `minimum_cost`"#,
+        r#"#[repair(strategy = "guess")] grammar; Term = ();"#,
+        r#"  ~~~~~~~~~~~~~~~~~~~~~~~~~~                     "#,
+    );
+}
+
+#[test]
+fn private_cancellable_annotation() {
+    check_err(
+        r#"only public items can be marked #\[cancellable\]"#,
+        r#"grammar; #[cancellable] Term = ();"#,
+        r#"           ~~~~~~~~~~~            "#,
+    );
+}
+
+#[test]
+fn private_prefix_annotation() {
+    check_err(
+        r#"only public items can be marked #\[prefix\]"#,
+        r#"grammar; #[prefix] Term = ();"#,
+        r#"           ~~~~~~            "#,
+    );
+}
+
+#[test]
+fn events_with_test_all() {
+    check_err(
+        r#"`#\[events\]` is only supported by table-driven parsers, not `#\[test_all\]`"#,
+        r#"#[test_all] grammar; #[events] pub Term = ();"#,
+        r#"                       ~~~~~~                "#,
+    );
+}
+
+#[test]
+fn backtrack_with_resumable() {
+    check_err(
+        r#"`#\[resumable\]` cannot be combined with `#\[backtrack\]`"#,
+        r#"#[backtrack] grammar; #[resumable] pub Term = ();"#,
+        r#"                        ~~~~~~~~~                "#,
+    );
+}
+
+#[test]
+fn undeclared_lifetime_in_type() {
+    check_err(
+        r#"undeclared lifetime `'ast`; declare it as a type parameter, as in `grammar<'ast>`"#,
+        r#"grammar; Expr: &'ast Expr<'ast, 'input> = "x" => panic!();"#,
+        r#"         ~~~~                                               "#,
+    );
+}
+
+#[test]
+fn undeclared_lifetime_in_parameter() {
+    check_err(
+        r#"undeclared lifetime `'ast`"#,
+        r#"grammar(arena: &'ast Arena); Expr = "x";"#,
+        r#"~~~~~~~                                 "#,
+    );
+}
+
+#[test]
+fn guard_in_inline() {
+    check_err(
+        r#"alternatives of `#\[inline\]` items cannot have a `when` guard"#,
+        r#"grammar; #[inline] X = { "a" when { true } };"#,
+        r#"                         ~~~~~~~~~~~~~~~~~   "#,
+    );
+}
+
+#[test]
+fn guard_with_recognize() {
+    check_err(
+        r#"`#\[recognize\]` cannot be combined with `when` guards"#,
+        r#"grammar; #[recognize] pub X = { "a" when { true } };"#,
+        r#"           ~~~~~~~~~                                "#,
+    );
+}
+
+#[test]
+fn resumable_without_extern_tokens() {
+    check_err(
+        r#"#\[resumable\] requires the token type to be defined in an `extern` block"#,
+        r#"grammar; #[resumable] pub Term = ();"#,
+        r#"           ~~~~~~~~~             "#,
+    );
+}
+
+#[test]
+fn cloneable_without_resumable() {
+    check_err(
+        r#"#\[cloneable\] requires the item to be marked #\[resumable\]"#,
+        r#"grammar; extern { enum Tok { "a" => Tok::A } } #[cloneable] pub Term = "a";"#,
+        r#"                                                 ~~~~~~~~~                   "#,
+    );
+}
+
+#[test]
+fn expected_without_name() {
+    check_err(
+        r#"#\[expected\] needs a name, as in `#\[expected\(name = "an expression"\)\]`"#,
+        r#"grammar; pub Term = Num; #[expected] Num = "0";"#,
+        r#"                           ~~~~~~~~             "#,
+    );
+}
+
+#[test]
+fn island_with_extern_tokens() {
+    check_err(
+        r#"#\[island\] requires the tokenizer to be generated by LALRPOP; with an `extern` token type, use #\[prefix\] instead"#,
+        r#"grammar; extern { enum Tok { "x" => Tok::X } } #[island] pub Term = "x";"#,
+        r#"                                                 ~~~~~~                 "#,
+    );
+}
+
 #[test]
 fn duplicate_annotation() {
     check_err(
@@ -231,3 +408,129 @@ fn first_level_assoc() {
         r#"                                             ~~~~~~~~~~~~~~~~~~             "#,
     );
 }
+
+#[test]
+fn public_parameters() {
+    check_err(
+        r#"public items cannot declare parameters"#,
+        r#"grammar; pub Body[n: u32]: u32 = "x" => n;"#,
+        r#"             ~~~~                          "#,
+    );
+}
+
+#[test]
+fn parameters_without_type() {
+    check_err(
+        r#"nonterminals with parameters must declare their type, as in `Body\[..\]: T`"#,
+        r#"grammar; Body[n: u32] = "x" => n;"#,
+        r#"         ~~~~                     "#,
+    );
+}
+
+#[test]
+fn nested_call() {
+    check_err(
+        r#"parameters can only be supplied to a named symbol at the top level of an alternative"#,
+        r#"grammar; pub Top = (<b:Body[ONE]> ",")*; Body[n: u32]: u32 = "x" => n;"#,
+        r#"                       ~~~~~~~~~                                    "#,
+    );
+}
+
+#[test]
+fn alternative_cfg_without_flag() {
+    check_err(
+        r#"`cfg` annotations must have a `feature = "my_feature"` or `flag = "my_flag"` argument"#,
+        r#"grammar; Term = { "x", #[cfg(foo = "y")] "y" };"#,
+        r#"                         ~~~~~~~~~~~~~~       "#,
+    );
+}
+
+#[test]
+fn terminal_id_not_a_number() {
+    check_err(
+        r#"the id `one` of a terminal must be a number"#,
+        r#"grammar; match { #[terminal_id(PLUS = "one")] "+" } Term = "+";"#,
+        r#"                   ~~~~~~~~~~~~~~~~~~~~~~~~~                "#,
+    );
+}
+
+#[test]
+fn duplicate_terminal_id() {
+    check_err(
+        r#"another terminal already has the id `1`"#,
+        r#"grammar; match { #[terminal_id(A = "1")] "a", #[terminal_id(B = "1")] "b" } Term = "a";"#,
+        r#"                                                ~~~~~~~~~~~~~~~~~~~~                   "#,
+    );
+}
+
+#[test]
+fn skipped_terminal_id() {
+    check_err(
+        r#"input that is skipped has no terminal to give an id"#,
+        r#"grammar; match { #[terminal_id(WS = "1")] r"\s*" => { }, _ } Term = "a";"#,
+        r#"                   ~~~~~~~~~~~~~~~~~~~~~                                 "#,
+    );
+}
+
+#[test]
+fn owned_with_extern_tokens() {
+    check_err(
+        r#"#\[owned\] requires the tokenizer to be generated by LALRPOP"#,
+        r#"grammar; extern { enum Tok { "x" => Tok::X } } #[owned] pub Term = "x";"#,
+        r#"                                                 ~~~~~                 "#,
+    );
+}
+
+#[test]
+fn intern_without_parameter() {
+    check_err(
+        r#"the grammar has no parameter `names` to intern with"#,
+        r#"grammar; match { #[intern(with = "names")] r"[a-z]+" } Term = r"[a-z]+";"#,
+        r#"                   ~~~~~~~~~~~~~~~~~~~~~~                                "#,
+    );
+}
+
+#[test]
+fn intern_in_extern_token() {
+    check_err(
+        r#"unrecognized annotation `intern`"#,
+        r#"grammar(n: &N); extern { enum Tok { #[intern(with = "n")] "x" => Tok::X } } Term = "x";"#,
+        r#"                                      ~~~~~~~~~~~~~~~~~~                                 "#,
+    );
+}
+
+#[test]
+fn cost_without_repair() {
+    check_err(
+        r#"`#\[cost\]` is only used by error repair, which needs `#\[repair\]` on the grammar"#,
+        r#"grammar; match { #[cost(insert = "2")] "+" } Term = "+";"#,
+        r#"                   ~~~~~~~~~~~~~~~~~~                "#,
+    );
+}
+
+#[test]
+fn cost_unknown_edit() {
+    check_err(
+        r#"`cost` annotations must give the cost of inserting or deleting the terminal"#,
+        r#"#[repair] grammar; match { #[cost(replace = "2")] "+" } Term = "+";"#,
+        r#"                             ~~~~~~~~~~~~~~~~~~~                "#,
+    );
+}
+
+#[test]
+fn cost_not_a_number() {
+    check_err(
+        r#"the cost `two` of an edit must be a number"#,
+        r#"#[repair] grammar; match { #[cost(insert = "two")] "+" } Term = "+";"#,
+        r#"                             ~~~~~~~~~~~~~~~~~~~~                "#,
+    );
+}
+
+#[test]
+fn duplicate_cost() {
+    check_err(
+        r#"duplicate annotation `cost`"#,
+        r#"#[repair] grammar; match { #[cost(insert = "2")] #[cost(insert = "3")] "+" } Term = "+";"#,
+        r#"                                                   ~~~~~~~~~~~~~~~~~~                "#,
+    );
+}
@@ -49,7 +49,7 @@ fn resolve_in_place(grammar: &mut Grammar) -> NormResult<()> {
             .flat_map(|match_token| &match_token.contents)
             .flat_map(|match_contents| &match_contents.items)
             .filter_map(|item| match *item {
-                MatchItem::Mapped(_, MatchMapping::Terminal(TerminalString::Bare(ref id)), _) => {
+                MatchItem::Mapped(_, MatchMapping::Terminal(TerminalString::Bare(ref id)), _, _) => {
                     Some((item.span(), id.clone(), Def::Terminal))
                 }
                 _ => None,
@@ -122,6 +122,9 @@ impl Validator {
         for item in &mut grammar.items {
             match *item {
                 GrammarItem::Use(..) => {}
+                GrammarItem::Include(..) => {}
+                GrammarItem::TypeAlias(..) => {}
+                GrammarItem::Code(..) => {}
                 GrammarItem::MatchToken(..) => {}
                 GrammarItem::InternToken(..) => {}
                 GrammarItem::ExternToken(..) => {}
@@ -165,7 +168,8 @@ impl Validator {
         scope: &ScopeChain,
         alternative: &mut Alternative,
     ) -> NormResult<()> {
-        if let Some(ref condition) = alternative.condition {
+        let conditions = alternative.condition.iter().flat_map(|c| c.tests());
+        for condition in conditions {
             let def = self.validate_id(scope, condition.span, &condition.lhs.0)?;
             match def {
                 Def::MacroArg => { /* OK */ }
@@ -249,6 +253,22 @@ impl Validator {
                     self.validate_symbol(scope, arg)?;
                 }
             }
+            SymbolKind::Call(ref csym) => {
+                let def = self.validate_id(scope, symbol.span, &csym.name.0)?;
+                match def {
+                    Def::Nonterminal(0) => {
+                        // OK
+                    }
+                    Def::Terminal | Def::Nonterminal(_) | Def::MacroArg => {
+                        return_err!(
+                            symbol.span,
+                            "`{}` is a {}, not a nonterminal",
+                            csym.name,
+                            def.description()
+                        );
+                    }
+                }
+            }
             SymbolKind::Repeat(ref mut repeat) => {
                 self.validate_symbol(scope, &mut repeat.symbol)?;
             }
@@ -0,0 +1,74 @@
+//! Finds the lists that are built by right recursion, like
+//!
+//!     Items: Vec<Item> = {
+//!         <i:Item> <mut v:Items> => { v.insert(0, i); v },
+//!         => vec![],
+//!     };
+//!
+//! Every item of such a list is shifted before any of them is
+//! reduced, so the parse stacks grow with the length of the list,
+//! whereas the left-recursive rule that `Item*` expands to reduces
+//! each item as soon as it is parsed.
+
+use super::NormError;
+use crate::collections::set;
+use crate::grammar::repr::*;
+use crate::util::Sep;
+
+#[cfg(test)]
+mod test;
+
+pub fn find(grammar: &Grammar) -> Vec<NormError> {
+    let mut warnings = vec![];
+    let mut spans = set();
+    for (nt, data) in &grammar.nonterminals {
+        if !is_vec(grammar.types.nonterminal_type(nt)) {
+            continue;
+        }
+
+        // the list must end somewhere, in an alternative that does not
+        // refer to it
+        let refers_to = |production: &Production| {
+            production.symbols.iter().any(|s| *s == Symbol::Nonterminal(nt.clone()))
+        };
+        if data.productions.iter().all(refers_to) {
+            continue;
+        }
+
+        for production in &data.productions {
+            let (last, item) = match production.symbols.split_last() {
+                Some((last, item)) => (last, item),
+                None => continue,
+            };
+            if *last != Symbol::Nonterminal(nt.clone())
+                || item.is_empty()
+                || item.iter().any(|s| *s == *last)
+            {
+                continue;
+            }
+
+            // the expansions of a macro all share the span of its rule
+            if !spans.insert(production.span) {
+                continue;
+            }
+            let message = format!(
+                "`{}` builds a list by right recursion, so the parse stacks grow with \
+                 its length; the left-recursive `({})*` reduces each item as it is parsed",
+                nt,
+                Sep(" ", &item.to_vec())
+            );
+            warnings.push(NormError {
+                message: grammar.with_expansion_notes(nt, message),
+                span: production.span,
+            });
+        }
+    }
+    warnings
+}
+
+fn is_vec(ty: &TypeRepr) -> bool {
+    match *ty {
+        TypeRepr::Nominal(ref data) => data.path.ids.last().is_some_and(|id| &**id == "Vec"),
+        _ => false,
+    }
+}
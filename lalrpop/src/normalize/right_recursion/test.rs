@@ -0,0 +1,34 @@
+use crate::test_util::{check_norm_err, normalized_grammar};
+
+use super::find;
+
+#[test]
+fn right_recursive_list() {
+    let grammar = r#"grammar; Items: Vec<()> = { "x" "," <v:Items> => v, => vec![] };"#;
+    let mut warnings = find(&normalized_grammar(grammar));
+    assert_eq!(warnings.len(), 1);
+    check_norm_err(
+        r#"`Items` builds a list by right recursion.*the left-recursive `\("x" ","\)\*`"#,
+        r#"                            ~~~~~~~~~~~~~~~~~~~~~~                           "#,
+        warnings.pop().unwrap(),
+    );
+}
+
+#[test]
+fn left_recursive_list() {
+    let grammar = r#"grammar; Items: Vec<()> = { <v:Items> "x" => v, => vec![] };"#;
+    assert!(find(&normalized_grammar(grammar)).is_empty());
+}
+
+#[test]
+fn right_associative_operator() {
+    // only lists are built by right recursion by mistake
+    let grammar = r#"grammar; Expr: () = { "x" "^" Expr, "x" };"#;
+    assert!(find(&normalized_grammar(grammar)).is_empty());
+}
+
+#[test]
+fn repeats() {
+    let grammar = r#"grammar; Items: Vec<()> = { <v:("x" ",")*> => vec![] };"#;
+    assert!(find(&normalized_grammar(grammar)).is_empty());
+}
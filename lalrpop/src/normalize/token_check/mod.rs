@@ -5,6 +5,7 @@
 //! are only used in `if` conditions; we use string literals for
 //! those, but they do not have to have a defined conversion.
 
+use super::norm_util;
 use super::{NormError, NormResult};
 
 use crate::collections::{Map, Set};
@@ -57,7 +58,9 @@ pub fn validate(mut grammar: Grammar) -> NormResult<Grammar> {
 
     match mode {
         TokenMode::Extern { .. } => {
-            // If using an external tokenizer, we're all done at this point.
+            // If using an external tokenizer, we're all done at this point,
+            // but for declaring `'input` if the types borrow from it.
+            declare_input_lifetime(&mut grammar);
         }
         TokenMode::Internal { match_block } => {
             // Otherwise, construct the `InternToken` item.
@@ -68,6 +71,20 @@ pub fn validate(mut grammar: Grammar) -> NormResult<Grammar> {
     Ok(grammar)
 }
 
+/// With an external tokenizer, `'input` may be named in the types of
+/// the grammar without being declared, as it may be with LALRPOP's own.
+/// If it is, it is declared here as the first lifetime of the grammar.
+fn declare_input_lifetime(grammar: &mut Grammar) {
+    let input_lifetime = TypeParameter::Lifetime(Lifetime::input());
+    if grammar.type_parameters.contains(&input_lifetime) {
+        return;
+    }
+
+    if norm_util::names_input_lifetime(grammar) {
+        grammar.type_parameters.insert(0, input_lifetime);
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////
 // Validation phase -- this phase walks the grammar and visits all
 // terminals. If using an external set of tokens, it checks that all
@@ -129,7 +146,7 @@ impl MatchBlock {
                 let precedence = match_token.contents.len() - idx;
                 for item in &mc.items {
                     match *item {
-                        MatchItem::Unmapped(ref sym, span) => {
+                        MatchItem::Unmapped(ref sym, span, _) => {
                             match_block.add_match_entry(
                                 precedence,
                                 sym.clone(),
@@ -137,7 +154,7 @@ impl MatchBlock {
                                 span,
                             )?;
                         }
-                        MatchItem::Mapped(ref sym, ref user, span) => {
+                        MatchItem::Mapped(ref sym, ref user, span, _) => {
                             match_block.add_match_entry(
                                 precedence,
                                 sym.clone(),
@@ -219,6 +236,9 @@ impl<'grammar> Validator<'grammar> {
         for item in &self.grammar.items {
             match *item {
                 GrammarItem::Use(..) => {}
+                GrammarItem::Include(..) => {}
+                GrammarItem::TypeAlias(..) => {}
+                GrammarItem::Code(..) => {}
                 GrammarItem::MatchToken(..) => {}
                 GrammarItem::ExternToken(_) => {}
                 GrammarItem::InternToken(_) => {}
@@ -267,6 +287,9 @@ impl<'grammar> Validator<'grammar> {
             SymbolKind::Macro(..) => {
                 panic!("macro not removed: {:?}", symbol);
             }
+            SymbolKind::Call(..) => {
+                panic!("parameters not removed: {:?}", symbol);
+            }
         }
 
         Ok(())
@@ -323,6 +346,19 @@ fn construct(grammar: &mut Grammar, match_block: MatchBlock) -> NormResult<()> {
         ..
     } = match_block;
 
+    // Under `#[longest_match]`, the lexer ignores `match` groups and
+    // the literal/regex distinction: it only ever takes the longest
+    // match, and ties go to whichever terminal was declared first.
+    // `match_entries` is still in declaration order here, so derive
+    // the precedence from the position.
+    let longest_match = grammar.annotations.iter().any(|a| a.id == *LONGEST_MATCH);
+    if longest_match {
+        let len = match_entries.len();
+        for (index, match_entry) in match_entries.iter_mut().enumerate() {
+            match_entry.precedence = len - index;
+        }
+    }
+
     // Sort match entries by order of increasing precedence.
     match_entries.sort();
 
@@ -369,22 +405,31 @@ fn construct(grammar: &mut Grammar, match_block: MatchBlock) -> NormResult<()> {
                 feature
             )
         }
-        Err(DFAConstructionError::Ambiguity { match0, match1 }) => {
+        Err(DFAConstructionError::Ambiguity {
+            match0,
+            match1,
+            example,
+        }) => {
             let literal0 = &match_entries[match0.index()].match_literal;
             let literal1 = &match_entries[match1.index()].match_literal;
-            // FIXME(#88) -- it'd be nice to give an example here
             return_err!(
                 spans[literal0],
-                "ambiguity detected between the terminal `{}` and the terminal `{}`",
+                "ambiguity detected between the terminal `{}` and the terminal `{}`; \
+                 both match {:?}",
                 literal0,
-                literal1
+                literal1,
+                example
             )
         }
     };
 
     grammar
         .items
-        .push(GrammarItem::InternToken(InternToken { match_entries, dfa }));
+        .push(GrammarItem::InternToken(InternToken {
+            match_entries,
+            dfa,
+            longest_match,
+        }));
 
     // we need to inject a `'input` lifetime and `input: &'input str` parameter as well:
 
@@ -136,6 +136,21 @@ fn match_precedence() {
     );
 }
 
+/// Under `#[longest_match]`, overlapping regexes are not an error;
+/// declaration order breaks ties, even against a quoted literal.
+#[test]
+fn longest_match_declaration_order() {
+    check_intern_token(
+        r#"#[longest_match] grammar; X = r"\w+" r"[0-9]+" "class" "=" "==";"#,
+        vec![
+            ("123", r##"Some((r#"\\w+"#, "123"))"##),
+            ("class", r##"Some((r#"\\w+"#, "class"))"##),
+            ("==", r#"Some(("==", "=="))"#),
+            ("=", r#"Some(("=", "="))"#),
+        ],
+    );
+}
+
 /// Test that, without a `catch-all`, using unrecognized literals is an error.
 #[test]
 fn invalid_match_literal() {
@@ -201,3 +216,37 @@ fn same_literal_twice() {
         r#"                                          ~~~~~~~~~~~~~~~~ "#,
     );
 }
+
+#[test]
+fn overlap_description() {
+    let grammar =
+        validate_grammar(r#"grammar; match { r"[a-z]+" } else { "class" } X = "class" r"[a-z]+";"#)
+            .expect("validate");
+    let intern_token = grammar.intern_token().expect("intern_token");
+    let descriptions: Vec<_> = intern_token
+        .dfa
+        .overlaps
+        .iter()
+        .map(|overlap| intern_token.describe_overlap(overlap))
+        .collect();
+    assert_eq!(
+        descriptions,
+        vec![
+            r##"the terminals `r#"[a-z]+"#` and `"class"` both match "class"; `r#"[a-z]+"#` is preferred because it appears in an earlier `match` group"##
+        ]
+    );
+}
+
+#[test]
+fn extern_input_lifetime() {
+    let grammar = validate_grammar(
+        r#"grammar; extern { enum Tok<'input> { Id => Tok::Id(<&'input str>) } } X = Id;"#,
+    )
+    .expect("validate");
+    assert_eq!(grammar.type_parameters.len(), 1);
+    assert_eq!(grammar.type_parameters[0].to_string(), "'input");
+
+    let grammar = validate_grammar(r#"grammar; extern { enum Tok { Id => Tok::Id } } X = Id;"#)
+        .expect("validate");
+    assert!(grammar.type_parameters.is_empty());
+}
@@ -0,0 +1,249 @@
+//! The types of action code that is simple enough to tell them from,
+//! so that the nonterminals with such actions can leave out their type.
+//! The code is parsed with `syn`, so this needs the `syn` feature.
+//!
+//! The type is known when the value of the action is
+//!
+//! - a struct literal, `Foo { .. }`, or a tuple struct, `Foo(..)`;
+//! - an enum variant, `Expr::Nil`, `Expr::Num(..)` or
+//!   `Expr::Bin { .. }`, which gives the type of the enum, `Expr`;
+//! - `Foo::new(..)` or `Foo::default()`, for a `Foo` that is not one of
+//!   the generic types of the standard library;
+//! - a literal: a string, a character, a boolean, or a number with a
+//!   suffix, like `0u32`;
+//! - a cast, `x as u64`;
+//! - `format!(..)`, or a call of `to_string`, `len`, `count`,
+//!   `is_empty`, or of `unwrap` or `expect` on `parse::<T>()`.
+//!
+//! The value of a block is that of its last expression, and that of a
+//! fallible action is what it returns in `Ok(..)`.
+
+use crate::grammar::parse_tree::{Lifetime, Path, TypeRef};
+use string_cache::DefaultAtom as Atom;
+use syn::{Expr, GenericArgument, Lit, PathArguments, Stmt, Type};
+
+#[cfg(test)]
+mod test;
+
+/// Generic types of the standard library, which `Foo::new()` does not
+/// give all of.
+const GENERIC_TYPES: &[&str] = &[
+    "Arc",
+    "BTreeMap",
+    "BTreeSet",
+    "BinaryHeap",
+    "Box",
+    "Cell",
+    "HashMap",
+    "HashSet",
+    "LinkedList",
+    "Option",
+    "Rc",
+    "RefCell",
+    "Result",
+    "Vec",
+    "VecDeque",
+];
+
+/// The variants of the prelude, which are not types of their own.
+const PRELUDE_VARIANTS: &[&str] = &["Some", "None", "Ok", "Err"];
+
+/// The type of the value of `code`, the action of an alternative, if it
+/// can be told from the code alone.
+pub fn action_type(code: &str, fallible: bool) -> Option<TypeRef> {
+    // `<>` is not Rust, but it stands for values or for fields, which
+    // a name does as well
+    let expr: Expr = syn::parse_str(&code.replace("<>", "__")).ok()?;
+    if !fallible {
+        return expr_type(&expr);
+    }
+    match *value(&expr) {
+        Expr::Call(ref call) if is_ident(&call.func, "Ok") && call.args.len() == 1 => {
+            expr_type(&call.args[0])
+        }
+        _ => None,
+    }
+}
+
+/// The expression that gives the value of `expr`, looking through
+/// parentheses and blocks.
+fn value(expr: &Expr) -> &Expr {
+    match *expr {
+        Expr::Paren(ref e) => value(&e.expr),
+        Expr::Group(ref e) => value(&e.expr),
+        Expr::Block(ref e) if e.label.is_none() => match e.block.stmts.last() {
+            Some(&Stmt::Expr(ref last, None)) => value(last),
+            _ => expr,
+        },
+        _ => expr,
+    }
+}
+
+fn expr_type(expr: &Expr) -> Option<TypeRef> {
+    match *value(expr) {
+        Expr::Struct(ref e) if e.qself.is_none() => constructed_type(&e.path),
+        Expr::Call(ref e) => match *e.func {
+            Expr::Path(ref p) if p.qself.is_none() => constructed_type(&p.path),
+            _ => None,
+        },
+        // a unit variant, `Expr::Nil`, rather than a constant, `Limits::MAX`
+        Expr::Path(ref e) if e.qself.is_none() && e.path.segments.len() > 1 => {
+            let last = e.path.segments.last()?.ident.to_string();
+            let is_variant = last.starts_with(char::is_uppercase)
+                && (last.len() == 1 || last.contains(char::is_lowercase));
+            if is_variant {
+                constructed_type(&e.path)
+            } else {
+                None
+            }
+        }
+        Expr::Lit(ref e) => literal_type(&e.lit),
+        Expr::Cast(ref e) => type_ref(&e.ty),
+        Expr::Macro(ref e) if e.mac.path.is_ident("format") => Some(nominal(&["String"])),
+        Expr::MethodCall(ref e) => match &*e.method.to_string() {
+            "to_string" => Some(nominal(&["String"])),
+            "len" | "count" => Some(nominal(&["usize"])),
+            "is_empty" => Some(nominal(&["bool"])),
+            "unwrap" | "expect" => match *value(&e.receiver) {
+                Expr::MethodCall(ref parse) if parse.method == "parse" => {
+                    let turbofish = parse.turbofish.as_ref()?;
+                    match turbofish.args.first() {
+                        Some(&GenericArgument::Type(ref ty)) if turbofish.args.len() == 1 => {
+                            type_ref(ty)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The type of the value that `path` constructs, as the path of a
+/// struct literal or of a function that is called.
+fn constructed_type(path: &syn::Path) -> Option<TypeRef> {
+    if path.segments.iter().any(|s| !s.arguments.is_none()) {
+        return None;
+    }
+    let ids: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+    let is_type = |id: &String| id.starts_with(char::is_uppercase);
+    let (last, prefix) = ids.split_last()?;
+    let ty = match prefix.last() {
+        // `Enum::Variant`, `Type::new`
+        Some(owner) if is_type(owner) => {
+            if !is_type(last) && last != "new" && last != "default" {
+                return None;
+            }
+            if GENERIC_TYPES.contains(&&owner[..]) {
+                return None;
+            }
+            prefix
+        }
+        _ if is_type(last) && !PRELUDE_VARIANTS.contains(&&last[..]) => &ids[..],
+        _ => return None,
+    };
+    Some(TypeRef::Nominal {
+        path: Path {
+            absolute: path.leading_colon.is_some(),
+            ids: ty.iter().map(|id| Atom::from(&id[..])).collect(),
+        },
+        types: vec![],
+    })
+}
+
+fn literal_type(lit: &Lit) -> Option<TypeRef> {
+    match *lit {
+        Lit::Str(_) => Some(TypeRef::Ref {
+            lifetime: Some(Lifetime::statik()),
+            mutable: false,
+            referent: Box::new(nominal(&["str"])),
+        }),
+        Lit::Char(_) => Some(nominal(&["char"])),
+        Lit::Bool(_) => Some(nominal(&["bool"])),
+        Lit::Byte(_) => Some(nominal(&["u8"])),
+        Lit::Int(ref int) if !int.suffix().is_empty() => Some(nominal(&[int.suffix()])),
+        Lit::Float(ref float) if !float.suffix().is_empty() => Some(nominal(&[float.suffix()])),
+        _ => None,
+    }
+}
+
+/// `ty` as LALRPOP writes types, if it is a path, a reference, a tuple
+/// or a slice of such.
+fn type_ref(ty: &Type) -> Option<TypeRef> {
+    match *ty {
+        Type::Paren(ref ty) => type_ref(&ty.elem),
+        Type::Tuple(ref tuple) => Some(TypeRef::Tuple(
+            tuple.elems.iter().map(type_ref).collect::<Option<_>>()?,
+        )),
+        Type::Slice(ref slice) => Some(TypeRef::Slice(Box::new(type_ref(&slice.elem)?))),
+        Type::Reference(ref reference) => Some(TypeRef::Ref {
+            lifetime: reference
+                .lifetime
+                .as_ref()
+                .map(|lifetime| Lifetime(Atom::from(format!("'{}", lifetime.ident)))),
+            mutable: reference.mutability.is_some(),
+            referent: Box::new(type_ref(&reference.elem)?),
+        }),
+        Type::Path(ref ty) if ty.qself.is_none() => {
+            let last = ty.path.segments.last()?;
+            if ty
+                .path
+                .segments
+                .iter()
+                .rev()
+                .skip(1)
+                .any(|s| !s.arguments.is_none())
+            {
+                return None;
+            }
+            let types = match last.arguments {
+                PathArguments::None => vec![],
+                PathArguments::AngleBracketed(ref args) => args
+                    .args
+                    .iter()
+                    .map(|arg| match *arg {
+                        GenericArgument::Type(ref ty) => type_ref(ty),
+                        GenericArgument::Lifetime(ref lifetime) => Some(TypeRef::Lifetime(
+                            Lifetime(Atom::from(format!("'{}", lifetime.ident))),
+                        )),
+                        _ => None,
+                    })
+                    .collect::<Option<_>>()?,
+                PathArguments::Parenthesized(_) => return None,
+            };
+            Some(TypeRef::Nominal {
+                path: Path {
+                    absolute: ty.path.leading_colon.is_some(),
+                    ids: ty
+                        .path
+                        .segments
+                        .iter()
+                        .map(|s| Atom::from(s.ident.to_string()))
+                        .collect(),
+                },
+                types,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn nominal(ids: &[&str]) -> TypeRef {
+    TypeRef::Nominal {
+        path: Path {
+            absolute: false,
+            ids: ids.iter().map(|&id| Atom::from(id)).collect(),
+        },
+        types: vec![],
+    }
+}
+
+fn is_ident(expr: &Expr, name: &str) -> bool {
+    match *expr {
+        Expr::Path(ref p) => p.qself.is_none() && p.path.is_ident(name),
+        _ => false,
+    }
+}
@@ -0,0 +1,64 @@
+use super::action_type;
+
+fn ty(code: &str) -> Option<String> {
+    action_type(code, false).map(|ty| ty.to_string())
+}
+
+#[test]
+fn constructors() {
+    assert_eq!(ty("Stmt { name, value }").as_deref(), Some("Stmt"));
+    assert_eq!(ty("ast::Stmt { <> }").as_deref(), Some("ast::Stmt"));
+    assert_eq!(ty("Wrapper(x)").as_deref(), Some("Wrapper"));
+    assert_eq!(ty("Expr::Num(<>)").as_deref(), Some("Expr"));
+    assert_eq!(ty("Expr::Nil").as_deref(), Some("Expr"));
+    assert_eq!(
+        ty("ast::Expr::Bin { l, op, r }").as_deref(),
+        Some("ast::Expr")
+    );
+    assert_eq!(ty("Table::new(rows)").as_deref(), Some("Table"));
+    assert_eq!(ty("String::default()").as_deref(), Some("String"));
+}
+
+#[test]
+fn unknown_constructors() {
+    assert_eq!(ty("Some(x)"), None);
+    assert_eq!(ty("Vec::new()"), None);
+    assert_eq!(ty("Table::from_rows(rows)"), None);
+    assert_eq!(ty("make_table(rows)"), None);
+    assert_eq!(ty("Foo::<T>::new()"), None);
+    assert_eq!(ty("Limits::MAX"), None);
+    assert_eq!(ty("Table::new"), None);
+}
+
+#[test]
+fn literals_and_casts() {
+    assert_eq!(ty(r#""text""#).as_deref(), Some("&'static str"));
+    assert_eq!(ty("0u32").as_deref(), Some("u32"));
+    assert_eq!(ty("1.5f64").as_deref(), Some("f64"));
+    assert_eq!(ty("true").as_deref(), Some("bool"));
+    assert_eq!(ty("0"), None);
+    assert_eq!(ty("n as u64").as_deref(), Some("u64"));
+    assert_eq!(ty("s as &'input str").as_deref(), Some("&'input str"));
+    assert_eq!(ty("f as fn(u8) -> u8"), None);
+}
+
+#[test]
+fn methods() {
+    assert_eq!(ty("<>.to_string()").as_deref(), Some("String"));
+    assert_eq!(ty(r#"format!("{}{}", a, b)"#).as_deref(), Some("String"));
+    assert_eq!(ty("v.len()").as_deref(), Some("usize"));
+    assert_eq!(ty("<>.parse::<i64>().unwrap()").as_deref(), Some("i64"));
+    assert_eq!(ty("<>.parse().unwrap()"), None);
+}
+
+#[test]
+fn blocks_and_fallible_actions() {
+    let code = "{ let mut v = v; v.push(e); Items(v) }";
+    assert_eq!(ty(code).as_deref(), Some("Items"));
+    let code = "{ check(&s)?; Ok(Name(s)) }";
+    assert_eq!(
+        action_type(code, true).map(|ty| ty.to_string()).as_deref(),
+        Some("Name")
+    );
+    assert_eq!(action_type("s.parse().map_err(From::from)", true), None);
+}
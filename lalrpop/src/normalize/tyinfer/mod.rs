@@ -1,21 +1,70 @@
 use super::norm_util::{self, AlternativeAction, Symbols};
 use super::{NormError, NormResult};
 
-use crate::grammar::consts::{ERROR, LOCATION};
+use crate::grammar::consts::{ERROR, INPUT_PARAMETER, LOCATION, OWNED};
+use crate::grammar::free_variables::FreeVariables;
 use crate::grammar::parse_tree::{
-    ActionKind, Alternative, Grammar, GrammarItem, Lifetime, MatchMapping, NonterminalData,
-    NonterminalString, Path, Span, SymbolKind, TypeParameter, TypeRef,
+    ActionKind, Alternative, Expansion, Grammar, GrammarItem, Lifetime, MatchMapping,
+    NonterminalData, NonterminalString, Path, Span, SymbolKind, TypeParameter, TypeRef,
 };
 use crate::grammar::repr::{NominalTypeRepr, TypeRepr, Types};
 use std::collections::{HashMap, HashSet};
 use string_cache::DefaultAtom as Atom;
 
+#[cfg(feature = "syn")]
+mod action_code;
 #[cfg(test)]
 mod test;
 
 pub fn infer_types(grammar: &Grammar) -> NormResult<Types> {
     let inferencer = TypeInferencer::new(&grammar)?;
-    inferencer.infer_types()
+    let types = inferencer.infer_types()?;
+    check_owned(grammar, &types)?;
+    Ok(types)
+}
+
+/// `parse_owned` drops the input before it returns, so nothing that it
+/// returns or takes, other than the input, may borrow it.
+fn check_owned(grammar: &Grammar, types: &Types) -> NormResult<()> {
+    let input_lifetime = TypeParameter::Lifetime(Lifetime::input());
+    let borrows_input = |ty: &TypeRepr| {
+        ty.free_variables(&grammar.type_parameters)
+            .contains(&input_lifetime)
+    };
+
+    for data in grammar.items.iter().filter_map(GrammarItem::as_nonterminal) {
+        let annotation = match data.annotations.iter().find(|a| a.id == *OWNED) {
+            Some(annotation) => annotation,
+            None => continue,
+        };
+        let ty = types.nonterminal_type(&data.name);
+        if borrows_input(ty) {
+            return_err!(
+                annotation.id_span,
+                "#[owned] requires the type of `{}`, `{}`, not to borrow the input",
+                data.name,
+                ty
+            );
+        }
+        let error_type = types.error_type();
+        if borrows_input(&error_type) {
+            return_err!(
+                annotation.id_span,
+                "#[owned] requires the error type, `{}`, not to borrow the input",
+                error_type
+            );
+        }
+        for parameter in &grammar.parameters {
+            if &*parameter.name != INPUT_PARAMETER && borrows_input(&parameter.ty.type_repr()) {
+                return_err!(
+                    annotation.id_span,
+                    "#[owned] requires the grammar parameter `{}` not to borrow the input",
+                    parameter.name
+                );
+            }
+        }
+    }
+    Ok(())
 }
 
 struct TypeInferencer<'grammar> {
@@ -30,6 +79,7 @@ struct NT<'grammar> {
     span: Span,
     type_decl: &'grammar Option<TypeRef>,
     alternatives: &'grammar Vec<Alternative>,
+    expansion: &'grammar Option<Expansion>,
 }
 
 impl<'grammar> TypeInferencer<'grammar> {
@@ -163,11 +213,8 @@ impl<'grammar> TypeInferencer<'grammar> {
 
         let nt = self.nonterminals[&id];
         if self.stack.contains(&id) {
-            return_err!(
-                nt.span,
-                "cannot infer type of `{}` because it references itself",
-                id
-            );
+            let message = format!("cannot infer type of `{}` because it references itself", id);
+            return Err(self.error(id, nt.span, message));
         }
 
         let ty = self.push(id, |this| {
@@ -180,9 +227,9 @@ impl<'grammar> TypeInferencer<'grammar> {
             // (yet).
             let mut alternative_types = vec![];
             let mut alternative_errors = vec![];
-            for alt in nt.alternatives.iter() {
-                match this.alternative_type(alt) {
-                    Ok(t) => alternative_types.push(t),
+            for (index, alt) in nt.alternatives.iter().enumerate() {
+                match this.alternative_type(id, alt) {
+                    Ok(t) => alternative_types.push((index, alt, t)),
                     Err(e) => alternative_errors.push(e),
                 }
             }
@@ -196,41 +243,63 @@ impl<'grammar> TypeInferencer<'grammar> {
                     None => {
                         // if nothing succeeded, and nothing errored,
                         // must have been nothing to start with
-                        return_err!(
-                            nt.span,
+                        let message = format!(
                             "nonterminal `{}` has no alternatives and hence parse cannot succeed",
                             id
                         );
+                        return Err(this.error(id, nt.span, message));
                     }
                 }
             }
 
-            // otherwise, check that all the cases where we had success agree
-            for ((ty, alt), i) in alternative_types[1..]
-                .iter()
-                .zip(&nt.alternatives[1..])
-                .zip(1..)
-            {
-                if &alternative_types[0] != ty {
-                    return_err!(
-                        alt.span,
-                        "type of alternative #{} is `{}`, \
-                         but type of first alternative is `{}`",
-                        i + 1,
-                        ty,
-                        alternative_types[0]
-                    );
-                }
+            // otherwise, check that all the cases where we had success
+            // agree, up to the way that their types are spelled
+            let mut ty = alternative_types[0].2.clone();
+            for &(_, alt, ref alt_ty) in &alternative_types[1..] {
+                ty = match unify(&ty, alt_ty) {
+                    Some(ty) => ty,
+                    None => {
+                        let mut message = format!(
+                            "cannot infer the type of `{}`, because the types of its \
+                             alternatives do not agree:",
+                            id
+                        );
+                        for &(index, _, ref alt_ty) in &alternative_types {
+                            message.push_str(&format!(
+                                "\n  alternative #{} has type `{}`",
+                                index + 1,
+                                alt_ty
+                            ));
+                        }
+                        return Err(this.error(id, alt.span, message));
+                    }
+                };
             }
 
             // and use that type
-            Ok(alternative_types.pop().unwrap())
+            Ok(ty)
         })?;
 
         self.types.add_type(id.clone(), ty.clone());
         Ok(ty)
     }
 
+    /// An error about the nonterminal `id`, which, if LALRPOP made it
+    /// up, notes where it comes from.
+    fn error(&self, id: &NonterminalString, span: Span, message: String) -> NormError {
+        let notes = Expansion::trace(id, |nt| {
+            self.nonterminals
+                .get(nt)
+                .map(|data| (data.span, data.expansion))
+        });
+        NormError {
+            message: notes
+                .into_iter()
+                .fold(message, |message, (_, note)| message + "\n" + &note),
+            span,
+        }
+    }
+
     fn push<F, R>(&mut self, id: &NonterminalString, f: F) -> NormResult<R>
     where
         F: FnOnce(&mut TypeInferencer) -> NormResult<R>,
@@ -286,6 +355,12 @@ impl<'grammar> TypeInferencer<'grammar> {
                 referent: Box::new(self.type_ref(referent)?),
             }),
             TypeRef::OfSymbol(ref symbol) => self.symbol_type(symbol),
+            TypeRef::Impl(ref bounds) => Ok(TypeRepr::boxed_dyn(
+                bounds
+                    .iter()
+                    .map(|bound| bound.try_map(|t| self.type_ref(t)))
+                    .collect::<Result<_, _>>()?,
+            )),
             TypeRef::TraitObject {
                 ref path,
                 ref types,
@@ -304,6 +379,7 @@ impl<'grammar> TypeInferencer<'grammar> {
                 ref path,
                 ref parameters,
                 ref ret,
+                ref bound,
             } => Ok(TypeRepr::Fn {
                 forall: forall.clone(),
                 path: path.clone(),
@@ -315,18 +391,22 @@ impl<'grammar> TypeInferencer<'grammar> {
                     Some(ret) => Some(self.type_ref(ret).map(Box::new)?),
                     None => None,
                 },
+                bound: bound.clone(),
             }),
         }
     }
 
-    fn alternative_type(&mut self, alt: &Alternative) -> NormResult<TypeRepr> {
+    fn alternative_type(
+        &mut self,
+        id: &NonterminalString,
+        alt: &Alternative,
+    ) -> NormResult<TypeRepr> {
         match norm_util::analyze_action(alt) {
-            AlternativeAction::User(&ActionKind::User(_))
-            | AlternativeAction::User(&ActionKind::Fallible(_)) => {
-                return_err!(
-                    alt.span,
-                    "cannot infer types if there is custom action code"
-                );
+            AlternativeAction::User(&ActionKind::User(ref code)) => {
+                self.action_type(id, alt, code, false)
+            }
+            AlternativeAction::User(&ActionKind::Fallible(ref code)) => {
+                self.action_type(id, alt, code, true)
             }
 
             AlternativeAction::User(&ActionKind::Lookahead)
@@ -335,12 +415,11 @@ impl<'grammar> TypeInferencer<'grammar> {
             }
 
             AlternativeAction::Default(Symbols::Named(ref syms)) => {
-                return_err!(
-                    alt.span,
+                let message = format!(
                     "cannot infer types in the presence of named symbols like `{}:{}`",
-                    syms[0].1,
-                    syms[0].2
+                    syms[0].1, syms[0].2
                 );
+                Err(self.error(id, alt.span, message))
             }
 
             AlternativeAction::Default(Symbols::Anon(syms)) => {
@@ -353,6 +432,26 @@ impl<'grammar> TypeInferencer<'grammar> {
         }
     }
 
+    /// The type of `code`, the action of `alt`, if the `syn` feature can
+    /// tell it; see `action_code`.
+    #[cfg_attr(not(feature = "syn"), allow(unused_variables))]
+    fn action_type(
+        &mut self,
+        id: &NonterminalString,
+        alt: &Alternative,
+        code: &str,
+        fallible: bool,
+    ) -> NormResult<TypeRepr> {
+        #[cfg(feature = "syn")]
+        {
+            if let Some(ty) = action_code::action_type(code, fallible) {
+                return self.type_ref(&ty);
+            }
+        }
+        let message = String::from("cannot infer types if there is custom action code");
+        Err(self.error(id, alt.span, message))
+    }
+
     fn symbol_type(&mut self, symbol: &SymbolKind) -> NormResult<TypeRepr> {
         match *symbol {
             SymbolKind::Terminal(ref id) => Ok(self.types.terminal_type(id).clone()),
@@ -364,6 +463,7 @@ impl<'grammar> TypeInferencer<'grammar> {
             SymbolKind::Repeat(..)
             | SymbolKind::Expr(..)
             | SymbolKind::Macro(..)
+            | SymbolKind::Call(..)
             | SymbolKind::AmbiguousId(..)
             | SymbolKind::Lookahead
             | SymbolKind::Lookbehind => {
@@ -379,10 +479,92 @@ impl<'grammar> NT<'grammar> {
             span: data.span,
             type_decl: &data.type_decl,
             alternatives: &data.alternatives,
+            expansion: &data.expansion,
         }
     }
 }
 
+/// The type that `a` and `b` both are, if they are the same type,
+/// perhaps spelled differently: `alloc::vec::Vec<T>`, which `T*`
+/// produces, is `Vec<T>`, `core::option::Option<T>` from `T?` is
+/// `Option<T>`, and `&str` is `&'input str`. Tuples, slices and
+/// references agree if what they contain does.
+fn unify(a: &TypeRepr, b: &TypeRepr) -> Option<TypeRepr> {
+    if a == b {
+        return Some(a.clone());
+    }
+    match (a, b) {
+        (&TypeRepr::Tuple(ref a), &TypeRepr::Tuple(ref b)) => {
+            Some(TypeRepr::Tuple(unify_all(a, b)?))
+        }
+        (&TypeRepr::Slice(ref a), &TypeRepr::Slice(ref b)) => {
+            Some(TypeRepr::Slice(Box::new(unify(a, b)?)))
+        }
+        (&TypeRepr::Nominal(ref a), &TypeRepr::Nominal(ref b)) if same_path(&a.path, &b.path) => {
+            Some(TypeRepr::Nominal(NominalTypeRepr {
+                path: a.path.clone(),
+                types: unify_all(&a.types, &b.types)?,
+            }))
+        }
+        (
+            &TypeRepr::Ref {
+                lifetime: ref a_lifetime,
+                mutable: a_mutable,
+                referent: ref a_referent,
+            },
+            &TypeRepr::Ref {
+                lifetime: ref b_lifetime,
+                mutable: b_mutable,
+                referent: ref b_referent,
+            },
+        ) if a_mutable == b_mutable => {
+            let lifetime = match (a_lifetime, b_lifetime) {
+                (Some(a), Some(b)) if a != b => return None,
+                (Some(_), _) => a_lifetime.clone(),
+                (None, _) => b_lifetime.clone(),
+            };
+            Some(TypeRepr::Ref {
+                lifetime,
+                mutable: a_mutable,
+                referent: Box::new(unify(a_referent, b_referent)?),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn unify_all(a: &[TypeRepr], b: &[TypeRepr]) -> Option<Vec<TypeRepr>> {
+    if a.len() != b.len() {
+        return None;
+    }
+    a.iter().zip(b).map(|(a, b)| unify(a, b)).collect()
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    a == b || prelude_name(a).is_some_and(|name| prelude_name(b) == Some(name))
+}
+
+/// The name of the type of the standard library that `path` refers
+/// to, if it is one of the prelude's that LALRPOP's own code spells
+/// out in full, like `alloc::vec::Vec`.
+fn prelude_name(path: &Path) -> Option<&str> {
+    const PRELUDE: &[(&str, &str, &str)] = &[
+        ("alloc", "vec", "Vec"),
+        ("alloc", "boxed", "Box"),
+        ("alloc", "string", "String"),
+        ("core", "option", "Option"),
+        ("core", "result", "Result"),
+    ];
+    let ids: Vec<&str> = path.ids.iter().map(|id| &**id).collect();
+    PRELUDE
+        .iter()
+        .find_map(|&(krate, module, name)| match ids[..] {
+            [id] if !path.absolute && id == name => Some(name),
+            [k, m, id] if (k == krate || k == "std") && m == module && id == name => Some(name),
+            _ => None,
+        })
+}
+
 fn maybe_tuple(v: Vec<TypeRepr>) -> TypeRepr {
     if v.len() == 1 {
         v.into_iter().next().unwrap()
@@ -4,6 +4,7 @@ use crate::normalize::macro_expand::expand_macros;
 use crate::normalize::token_check;
 use crate::normalize::tyinfer::infer_types;
 use crate::parser;
+use crate::tls::Tls;
 use string_cache::DefaultAtom as Atom;
 
 fn type_repr(s: &str) -> TypeRepr {
@@ -206,6 +207,89 @@ grammar;
     assert!(infer_types(&actual).is_err());
 }
 
+#[test]
+fn unify_spellings() {
+    compare(
+        r#"
+grammar;
+    extern { enum Tok { "a" => .., "b" => .. } }
+    X = { "a"*, Y };
+    Y: Vec<Tok> = "b" => vec![<>];
+    P = { ("a" "b"?), (<"a"> <Z>) };
+    Z: Option<Tok> = "b" => Some(<>);
+"#,
+        vec![
+            ("X", "alloc::vec::Vec<Tok>"),
+            ("P", "(Tok, core::option::Option<Tok>)"),
+        ],
+    )
+}
+
+#[test]
+fn alternative_types_disagree() {
+    let grammar = parser::parse_grammar(
+        r#"
+grammar;
+    extern { enum Tok { "a" => .., "b" => .. } }
+    X = { Y, "a"*, Z };
+    Y: Vec<i32> = "b" => vec![];
+    Z: Vec<Tok> = "b" => vec![<>];
+"#,
+    )
+    .unwrap();
+
+    let actual = expand_macros(grammar).unwrap();
+    let err = infer_types(&actual).unwrap_err();
+    assert_eq!(
+        err.message,
+        "cannot infer the type of `X`, because the types of its alternatives do not agree:\n  \
+         alternative #1 has type `Vec<i32>`\n  \
+         alternative #2 has type `alloc::vec::Vec<Tok>`\n  \
+         alternative #3 has type `Vec<Tok>`"
+    );
+}
+
+#[cfg(feature = "syn")]
+#[test]
+fn action_code() {
+    compare(
+        r#"
+grammar;
+    extern { enum Tok { "a" => .., "b" => .. } }
+    Stmt = {
+        "a" => Stmt::Nil,
+        <Name> "b" => Stmt::Call(<>),
+    };
+    Name = "a" => Name(<>);
+    Count = "b"+ => <>.len();
+"#,
+        vec![("Stmt", "Stmt"), ("Name", "Name"), ("Count", "usize")],
+    )
+}
+
+#[test]
+fn macro_expansion_notes() {
+    let text = r#"
+grammar;
+    extern { enum Tok { "x" => .. } }
+    pub S = Outer<"x">;
+    Outer<T> = Inner<T>;
+    Inner<T> = <v:T> => v;
+"#;
+    let _tls = Tls::test_string(text);
+    let grammar = parser::parse_grammar(text).unwrap();
+    let actual = expand_macros(grammar).unwrap();
+    let err = infer_types(&actual).unwrap_err();
+    assert_eq!(
+        err.message,
+        "cannot infer types if there is custom action code\n\
+         note: `Inner<\"x\">` is expanded from the macro defined at tmp.txt:6:5, \
+         for its use at tmp.txt:5:16 in `Outer<\"x\">`\n\
+         note: `Outer<\"x\">` is expanded from the macro defined at tmp.txt:5:5, \
+         for its use at tmp.txt:4:13 in `S`"
+    );
+}
+
 #[test]
 fn custom_token() {
     compare(
@@ -242,3 +326,21 @@ grammar;
         )],
     )
 }
+
+#[test]
+fn owned_borrows_input() {
+    let grammar = parser::parse_grammar(
+        r#"
+grammar;
+    #[owned] pub Name = r"[a-z]+";
+"#,
+    )
+    .unwrap();
+
+    let actual = token_check::validate(expand_macros(grammar).unwrap()).unwrap();
+    let err = infer_types(&actual).unwrap_err();
+    assert_eq!(
+        err.message,
+        "#[owned] requires the type of `Name`, `&'input str`, not to borrow the input"
+    );
+}
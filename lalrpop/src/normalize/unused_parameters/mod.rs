@@ -0,0 +1,126 @@
+//! Finds the parameters and type parameters of the grammar, as in
+//!
+//!     grammar<'a, T>(scope: &'a Scope<T>);
+//!
+//! that nothing refers to: no action, no type of a terminal or
+//! nonterminal and no `extern` declaration. They are still threaded
+//! through the generated parser, where they only show up as warnings
+//! about unused variables in code the user did not write.
+
+use super::NormError;
+use crate::grammar::consts::{INPUT_LIFETIME, INPUT_PARAMETER};
+use crate::grammar::free_variables::FreeVariables;
+use crate::grammar::repr::*;
+use unicode_xid::UnicodeXID;
+
+#[cfg(test)]
+mod test;
+
+pub fn find(grammar: &Grammar) -> Vec<NormError> {
+    // the code of the actions, where parameters are used by name, and
+    // the patterns of the `extern` declaration
+    let conversions: Vec<String> = grammar
+        .conversions
+        .values()
+        .map(|p| p.to_string())
+        .collect();
+    let code: Vec<&str> = grammar
+        .action_fn_defns
+        .iter()
+        .filter_map(|defn| match defn.kind {
+            ActionFnDefnKind::User(ref data) | ActionFnDefnKind::Guard(ref data) => {
+                Some(&data.code[..])
+            }
+            ActionFnDefnKind::Inline(_) | ActionFnDefnKind::Lookaround(_) => None,
+        })
+        .chain(conversions.iter().map(|c| &c[..]))
+        .collect();
+
+    // the types, written out, since a path like `T::Error` refers to
+    // `T` without it being a free variable
+    let types: Vec<String> = types(grammar).iter().map(|ty| ty.to_string()).collect();
+
+    let bounding: Vec<_> = grammar
+        .where_clauses
+        .iter()
+        .flat_map(|wc| bounding(wc, &grammar.type_parameters))
+        .collect();
+
+    // with an internal tokenizer, `'input` and `input` are declared by
+    // LALRPOP itself, and used by the tokenizer
+    let internal = grammar.intern_token.is_some();
+
+    let mut warnings = vec![];
+    for type_parameter in &grammar.type_parameters {
+        let name = type_parameter.to_string();
+        let used = (internal && name == INPUT_LIFETIME)
+            || bounding.contains(type_parameter)
+            || code
+                .iter()
+                .copied()
+                .chain(types.iter().map(|ty| &ty[..]))
+                .any(|text| mentions(text, &name));
+        if !used {
+            warnings.push(NormError {
+                message: format!("the type parameter `{}` of the grammar is never used", name),
+                span: grammar.span,
+            });
+        }
+    }
+    for parameter in &grammar.parameters {
+        let used = (internal && &*parameter.name == INPUT_PARAMETER)
+            || code.iter().any(|text| mentions(text, &parameter.name));
+        if !used {
+            warnings.push(NormError {
+                message: format!(
+                    "the parameter `{}` of the grammar is never used",
+                    parameter.name
+                ),
+                span: grammar.span,
+            });
+        }
+    }
+    warnings
+}
+
+/// The types of the grammar: those of its terminals and nonterminals,
+/// of its parameters and type aliases, and of the `extern` declaration.
+fn types(grammar: &Grammar) -> Vec<TypeRepr> {
+    let types = &grammar.types;
+    let mut tys = vec![types.terminal_token_type().clone(), types.error_type()];
+    tys.extend(types.opt_terminal_loc_type().cloned());
+    tys.extend(types.terminal_types());
+    tys.extend(types.nonterminal_types());
+    tys.extend(grammar.parameters.iter().map(|p| p.ty.clone()));
+    tys.extend(grammar.type_aliases.iter().map(|a| a.type_repr.clone()));
+    tys
+}
+
+/// The type parameters in the bounds of `where_clause` that are not
+/// among those it bounds, like `U` in `T: Into<U>`.
+fn bounding(where_clause: &WhereClause, type_parameters: &[TypeParameter]) -> Vec<TypeParameter> {
+    match *where_clause {
+        WhereClause::Forall { ref clause, .. } => bounding(clause, type_parameters),
+        WhereClause::Bound {
+            ref subject,
+            ref bound,
+        } => {
+            let subject = subject.free_variables(type_parameters);
+            bound
+                .free_variables(type_parameters)
+                .into_iter()
+                .filter(|tp| !subject.contains(tp))
+                .collect()
+        }
+    }
+}
+
+/// Whether `name` occurs in `text` as a whole identifier or lifetime.
+fn mentions(text: &str, name: &str) -> bool {
+    let is_continue = |c: char| UnicodeXID::is_xid_continue(c) || c == '_';
+    text.match_indices(name).any(|(index, _)| {
+        let before = text[..index].chars().next_back();
+        let after = text[index + name.len()..].chars().next();
+        !before.is_some_and(is_continue) && !after.is_some_and(is_continue)
+    })
+}
@@ -0,0 +1,78 @@
+use crate::test_util::{check_norm_err, normalized_grammar};
+
+use super::find;
+
+#[test]
+fn unused_parameter() {
+    let grammar = r#"grammar(scale: u32, offset: u32); pub Num: u32 = "x" => scale;"#;
+    let mut warnings = find(&normalized_grammar(grammar));
+    assert_eq!(warnings.len(), 1);
+    check_norm_err(
+        r#"the parameter `offset` of the grammar is never used"#,
+        r#"~~~~~~~                                                        "#,
+        warnings.pop().unwrap(),
+    );
+}
+
+#[test]
+fn unused_type_parameter() {
+    let grammar = r#"grammar<'a, T, U>(x: &'a T); pub A: () = "x" => ();"#;
+    let mut warnings = find(&normalized_grammar(grammar));
+    assert_eq!(warnings.len(), 2);
+    check_norm_err(
+        r#"the parameter `x` of the grammar is never used"#,
+        r#"~~~~~~~                                        "#,
+        warnings.pop().unwrap(),
+    );
+    check_norm_err(
+        r#"the type parameter `U` of the grammar is never used"#,
+        r#"~~~~~~~                                             "#,
+        warnings.pop().unwrap(),
+    );
+}
+
+#[test]
+fn used_in_types() {
+    let grammar = r#"
+grammar<'a, T>;
+extern { type Location = Loc<'a>; enum Tok { "x" => Tok::X } }
+pub A: Vec<T> = "x" => vec![];
+"#;
+    assert!(find(&normalized_grammar(grammar)).is_empty());
+}
+
+#[test]
+fn used_in_actions() {
+    let grammar =
+        r#"grammar<T>(counter: &mut u32); pub A: () = "x" => { *counter += 1; T::reset() };"#;
+    assert!(find(&normalized_grammar(grammar)).is_empty());
+}
+
+#[test]
+fn used_in_bound() {
+    let grammar = r#"grammar<T, U> where T: Into<U>; pub A: T = "x" => panic!();"#;
+    assert!(find(&normalized_grammar(grammar)).is_empty());
+}
+
+#[test]
+fn prefix_of_another_name() {
+    // `count` is not used by `counter`
+    let grammar = r#"grammar(count: u32); pub A: () = "x" => { let counter = 1; };"#;
+    assert_eq!(find(&normalized_grammar(grammar)).len(), 1);
+}
+
+#[test]
+fn internal_tokenizer() {
+    let grammar = r#"grammar; pub A: &'input str = "x";"#;
+    assert!(find(&normalized_grammar(grammar)).is_empty());
+}
+
+#[test]
+fn used_in_associated_type() {
+    let grammar = r#"
+grammar<L> where L: Lexer;
+extern { type Error = L::Error; enum Tok { "x" => Tok::X } }
+pub A: () = "x";
+"#;
+    assert!(find(&normalized_grammar(grammar)).is_empty());
+}
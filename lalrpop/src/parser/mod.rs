@@ -25,6 +25,7 @@ pub enum Top {
     MatchMapping(MatchMapping),
     TypeRef(TypeRef),
     GrammarWhereClauses(Vec<WhereClause<TypeRef>>),
+    Include(Vec<GrammarItem>),
 }
 
 pub type ParseError<'input> = lalrpop_util::ParseError<usize, tok::Tok<'input>, tok::Error>;
@@ -54,21 +55,29 @@ pub fn parse_grammar<'input>(input: &'input str) -> Result<Grammar, ParseError<'
     Ok(grammar)
 }
 
-fn parse_pattern<'input>(
+pub fn parse_pattern<'input>(
     input: &'input str,
     offset: usize,
 ) -> Result<Pattern<TypeRef>, ParseError<'input>> {
     parser!(input, offset, Pattern, StartPattern)
 }
 
-fn parse_match_mapping<'input>(
+pub fn parse_match_mapping<'input>(
     input: &'input str,
     offset: usize,
 ) -> Result<MatchMapping, ParseError<'input>> {
     parser!(input, offset, MatchMapping, StartMatchMapping)
 }
 
-#[cfg(test)]
+/// Parses the items of a file included into a grammar, whose text
+/// starts at `offset` in the spans of the grammar.
+pub fn parse_include<'input>(
+    input: &'input str,
+    offset: usize,
+) -> Result<Vec<GrammarItem>, ParseError<'input>> {
+    parser!(input, offset, Include, StartInclude)
+}
+
 pub fn parse_type_ref<'input>(input: &'input str) -> Result<TypeRef, ParseError<'input>> {
     parser!(input, 0, TypeRef, StartTypeRef)
 }
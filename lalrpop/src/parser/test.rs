@@ -52,7 +52,7 @@ fn match_complex() {
             // r"(?i)begin" => "BEGIN"
             let item00 = contents0.items.get(0).unwrap();
             match *item00 {
-                MatchItem::Mapped(ref sym, ref mapping, _) => {
+                MatchItem::Mapped(ref sym, ref mapping, _, _) => {
                     assert_eq!(format!("{:?}", sym), "r#\"(?i)begin\"#");
                     assert_eq!(format!("{}", mapping), "\"BEGIN\"");
                 }
@@ -61,7 +61,7 @@ fn match_complex() {
             // r"(?i)end" => "END",
             let item01 = contents0.items.get(1).unwrap();
             match *item01 {
-                MatchItem::Mapped(ref sym, ref mapping, _) => {
+                MatchItem::Mapped(ref sym, ref mapping, _, _) => {
                     assert_eq!(format!("{:?}", sym), "r#\"(?i)end\"#");
                     assert_eq!(format!("{}", mapping), "\"END\"");
                 }
@@ -72,7 +72,7 @@ fn match_complex() {
             // r"[a-zA-Z_][a-zA-Z0-9_]*" => IDENTIFIER,
             let item10 = contents1.items.get(0).unwrap();
             match *item10 {
-                MatchItem::Mapped(ref sym, ref mapping, _) => {
+                MatchItem::Mapped(ref sym, ref mapping, _, _) => {
                     assert_eq!(format!("{:?}", sym), "r#\"[a-zA-Z_][a-zA-Z0-9_]*\"#");
                     assert_eq!(format!("{}", mapping), "IDENTIFIER");
                 }
@@ -83,7 +83,7 @@ fn match_complex() {
             // "other",
             let item20 = contents2.items.get(0).unwrap();
             match *item20 {
-                MatchItem::Unmapped(ref sym, _) => {
+                MatchItem::Unmapped(ref sym, _, _) => {
                     assert_eq!(format!("{:?}", sym), "\"other\"");
                 }
                 _ => panic!("expected MatchItem::Unmapped, but was: {:?}", item20),
@@ -143,6 +143,9 @@ grammar<F>(methods: &mut F) where F: for<'a> FnMut(&'a usize) -> bool;
 "###,
         r###"
 grammar<F>(logger: &mut F) where F: for<'a> FnMut(&'a str);
+"###,
+        r###"
+grammar<'a, 'b: 'a, T: 'a + Debug, U>(methods: &mut T) where U: Display;
 "###,
     ];
 
@@ -151,6 +154,16 @@ grammar<F>(logger: &mut F) where F: for<'a> FnMut(&'a str);
     }
 }
 
+#[test]
+fn inline_type_parameter_bounds() {
+    let inline = parser::parse_grammar("grammar<'a, 'b: 'a, T: 'a + Debug, U> where U: Display;");
+    let clauses =
+        parser::parse_grammar("grammar<'a, 'b, T, U> where 'b: 'a, T: 'a + Debug, U: Display;");
+    let (inline, clauses) = (inline.unwrap(), clauses.unwrap());
+    assert_eq!(inline.type_parameters, clauses.type_parameters);
+    assert_eq!(inline.where_clauses, clauses.where_clauses);
+}
+
 #[test]
 fn optional_semicolon() {
     // Semi after block is optional
@@ -169,3 +182,27 @@ Bar: () = "bar"
 "#;
     assert!(parser::parse_grammar(g).is_err());
 }
+
+#[test]
+fn bare_named_symbols() {
+    // `name:Symbol` is the same as `<name:Symbol>`
+    let symbols = |g: &str| -> Vec<String> {
+        let grammar = parser::parse_grammar(g).unwrap();
+        let nonterminal = grammar.items[0].as_nonterminal().unwrap();
+        let expr = &nonterminal.alternatives[0].expr;
+        expr.symbols.iter().map(|s| s.to_string()).collect()
+    };
+    assert_eq!(
+        symbols(r#"grammar; A = name:Id "=" value:Expr* => Assign { <> };"#),
+        symbols(r#"grammar; A = <name:Id> "=" <value:Expr*> => Assign { <> };"#),
+    );
+}
+
+#[test]
+fn double_references() {
+    // `&&` is one token, for conditions, but still two references
+    assert_eq!(
+        parser::parse_type_ref("&&'a mut str").unwrap(),
+        parser::parse_type_ref("& &'a mut str").unwrap(),
+    );
+}
@@ -0,0 +1,177 @@
+//! Railroad diagrams of the nonterminals of a normalized grammar, as
+//! SVG. Each production is a row of boxes, rounded for terminals and
+//! square for nonterminals, and the rows are the branches of a choice
+//! between the entry and the exit of the diagram. An empty production
+//! is a row without boxes, which makes the nonterminal optional.
+//!
+//! Macros and `+` are expanded by normalization, so their uses are
+//! boxes with the name of the generated nonterminal, like `Comma<Expr>`
+//! or `Expr+`, which have diagrams of their own. Inlined nonterminals,
+//! like those for `?` and `*`, are expanded into the rows of their
+//! uses, one with the symbol and one without.
+
+use crate::grammar::repr::*;
+use std::fmt::Write;
+
+#[cfg(test)]
+mod test;
+
+const CHAR_WIDTH: usize = 8;
+const BOX_HEIGHT: usize = 24;
+const BOX_PADDING: usize = 10;
+const GAP: usize = 16;
+const ROW_HEIGHT: usize = 40;
+const RAIL: usize = 20;
+const MARGIN: usize = 10;
+const MARKER: usize = 8;
+
+static STYLE: &str = "<style>\
+path { fill: none; stroke: black; stroke-width: 1.5; } \
+circle { fill: black; } \
+rect { fill: #ffd; stroke: black; stroke-width: 1.5; } \
+rect.terminal { fill: #dfd; } \
+text { font: 13px monospace; text-anchor: middle; }\
+</style>";
+
+/// The railroad diagram of `data`.
+pub fn diagram(data: &NonterminalData) -> String {
+    let rows: Vec<Vec<(String, bool, usize)>> = data
+        .productions
+        .iter()
+        .map(|production| {
+            production
+                .symbols
+                .iter()
+                .map(|symbol| {
+                    let text = symbol.to_string();
+                    let width = text.chars().count() * CHAR_WIDTH + 2 * BOX_PADDING;
+                    let terminal = matches!(symbol, Symbol::Terminal(_));
+                    (text, terminal, width)
+                })
+                .collect()
+        })
+        .collect();
+    let content_width = rows
+        .iter()
+        .map(|row| row.iter().map(|&(_, _, width)| width + GAP).sum::<usize>() + GAP)
+        .max()
+        .unwrap_or(GAP);
+
+    let top = MARGIN + BOX_HEIGHT / 2;
+    let split = MARGIN + MARKER + GAP;
+    let merge = split + 2 * RAIL + content_width;
+    let width = merge + GAP + MARKER + MARGIN;
+    let height = top + ROW_HEIGHT * rows.len().saturating_sub(1) + BOX_HEIGHT / 2 + MARGIN;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+        width, height
+    );
+    let _ = write!(svg, "<title>{}</title>{}", escape(&data.name), STYLE);
+
+    // the entry and the exit
+    let _ = write!(
+        svg,
+        "<circle cx=\"{}\" cy=\"{}\" r=\"4\"/><path d=\"M{} {}H{}\"/>",
+        MARGIN + MARKER / 2,
+        top,
+        MARGIN + MARKER,
+        top,
+        split
+    );
+    let _ = write!(
+        svg,
+        "<path d=\"M{} {}H{}\"/><circle cx=\"{}\" cy=\"{}\" r=\"4\"/>",
+        merge,
+        top,
+        merge + GAP,
+        merge + GAP + MARKER / 2,
+        top
+    );
+
+    for (index, row) in rows.iter().enumerate() {
+        let y = top + index * ROW_HEIGHT;
+        let start = split + RAIL;
+        let end = merge - RAIL;
+        if index == 0 {
+            let _ = write!(svg, "<path d=\"M{} {}H{}\"/>", split, y, start);
+            let _ = write!(svg, "<path d=\"M{} {}H{}\"/>", end, y, merge);
+        } else {
+            // curve down from the rail to the row, and back up after it
+            let half = RAIL / 2;
+            let _ = write!(
+                svg,
+                "<path d=\"M{} {}Q{} {} {} {}V{}Q{} {} {} {}\"/>",
+                split,
+                top,
+                split + half,
+                top,
+                split + half,
+                top + half,
+                y - half,
+                split + half,
+                y,
+                start,
+                y
+            );
+            let _ = write!(
+                svg,
+                "<path d=\"M{} {}Q{} {} {} {}V{}Q{} {} {} {}\"/>",
+                end,
+                y,
+                end + half,
+                y,
+                end + half,
+                y - half,
+                top + half,
+                end + half,
+                top,
+                merge,
+                top
+            );
+        }
+
+        let mut x = start;
+        for (text, terminal, box_width) in row {
+            let _ = write!(svg, "<path d=\"M{} {}H{}\"/>", x, y, x + GAP);
+            x += GAP;
+            let _ = write!(
+                svg,
+                "<rect{} x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/>",
+                if *terminal { " class=\"terminal\"" } else { "" },
+                x,
+                y - BOX_HEIGHT / 2,
+                box_width,
+                BOX_HEIGHT,
+                if *terminal { BOX_HEIGHT / 2 } else { 0 }
+            );
+            let _ = write!(
+                svg,
+                "<text x=\"{}\" y=\"{}\">{}</text>",
+                x + box_width / 2,
+                y + 4,
+                escape(text)
+            );
+            x += box_width;
+        }
+        let _ = write!(svg, "<path d=\"M{} {}H{}\"/>", x, y, end);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape<T: ToString + ?Sized>(text: &T) -> String {
+    let mut escaped = String::new();
+    for c in text.to_string().chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
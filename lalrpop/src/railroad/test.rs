@@ -0,0 +1,55 @@
+use crate::build::railroad_diagrams;
+
+static GRAMMAR: &str = r#"
+grammar;
+
+pub Expr: () = {
+    Expr "+" Term,
+    Term,
+};
+
+Term: () = {
+    "<" Num? ">",
+    "[" Num* "]",
+    Num,
+};
+
+Num: () = r"[0-9]+";
+"#;
+
+#[test]
+fn nonterminals() {
+    let diagrams = railroad_diagrams(GRAMMAR).unwrap();
+    let names: Vec<_> = diagrams.keys().map(|name| &name[..]).collect();
+    assert_eq!(names, vec!["Expr", "Num", "Num+", "Term"]);
+}
+
+#[test]
+fn boxes() {
+    let diagrams = railroad_diagrams(GRAMMAR).unwrap();
+    let expr = &diagrams["Expr"];
+    assert!(expr.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(expr.contains("<title>Expr</title>"));
+    assert_eq!(expr.matches("<rect").count(), 4);
+    assert_eq!(expr.matches("<rect class=\"terminal\"").count(), 1);
+    assert!(expr.contains(">&quot;+&quot;</text>"));
+    assert!(expr.contains(">Term</text>"));
+
+    let term = &diagrams["Term"];
+    assert!(term.contains(">&quot;&lt;&quot;</text>"));
+    // `Num?` and `Num*` are inlined: one row with `Num` or `Num+`, and
+    // one without
+    assert_eq!(term.matches(">Num</text>").count(), 2);
+    assert_eq!(term.matches(">Num+</text>").count(), 1);
+    assert_eq!(term.matches("<rect").count(), 11);
+}
+
+#[test]
+fn repetition() {
+    let diagrams = railroad_diagrams(GRAMMAR).unwrap();
+    let repeated = &diagrams["Num+"];
+    assert_eq!(repeated.matches("<rect").count(), 3);
+    assert!(repeated.contains(">Num+</text>"));
+    // the curves to the second row
+    assert_eq!(repeated.matches("Q").count(), 4);
+}
@@ -3,6 +3,7 @@
 
 use crate::grammar::parse_tree::Visibility;
 use crate::grammar::repr::{self, Grammar};
+use crate::session::Edition;
 use crate::tls::Tls;
 use std::fmt::{self, Display};
 use std::io::{self, Write};
@@ -137,10 +138,24 @@ impl<'me, W: Write> RustWrite<W> {
             }
         }
 
+        // the type aliases are declared in the outermost module, and
+        // imported by the modules within it
+        for alias in &grammar.type_aliases {
+            if super_prefix.is_empty() {
+                rust!(self, "{};", alias);
+            } else {
+                rust!(self, "use {}{};", super_prefix, alias.name);
+            }
+        }
+
         self.write_standard_uses(&grammar.prefix)
     }
 
     pub fn write_standard_uses(&mut self, prefix: &str) -> io::Result<()> {
+        if Tls::session().edition >= Edition::Rust2018 {
+            return self.write_standard_uses_2018(prefix);
+        }
+
         // Stuff that we plan to use.
         // Occasionally we happen to not use it after all, hence the allow.
         rust!(self, "#[allow(unused_extern_crates)]");
@@ -160,11 +175,31 @@ impl<'me, W: Write> RustWrite<W> {
 
         Ok(())
     }
+
+    /// Like `write_standard_uses`, without the `extern crate`s that
+    /// `rust_2018_idioms` flags. `core` and `lalrpop_util` are in the
+    /// extern prelude, but `alloc` is only there if the crate root
+    /// declares it.
+    fn write_standard_uses_2018(&mut self, prefix: &str) -> io::Result<()> {
+        rust!(self, "#[allow(unused_imports)]");
+        rust!(self, "use ::lalrpop_util as {p}lalrpop_util;", p = prefix);
+        rust!(self, "#[allow(unused_imports)]");
+        rust!(
+            self,
+            "use self::{p}lalrpop_util::state_machine as {p}state_machine;",
+            p = prefix,
+        );
+        rust!(self, "#[allow(unused_extern_crates)]");
+        rust!(self, "extern crate alloc;");
+
+        Ok(())
+    }
 }
 
 pub struct FnHeader<'me, W: Write + 'me> {
     write: &'me mut RustWrite<W>,
     visibility: &'me Visibility,
+    unsafety: &'static str,
     name: String,
     type_parameters: Vec<String>,
     parameters: Vec<String>,
@@ -177,6 +212,7 @@ impl<'me, W: Write> FnHeader<'me, W> {
         FnHeader {
             write,
             visibility,
+            unsafety: "",
             name,
             type_parameters: vec![],
             parameters: vec![],
@@ -222,6 +258,12 @@ impl<'me, W: Write> FnHeader<'me, W> {
         self
     }
 
+    /// Declare the function `unsafe` if `is_unsafe` is true.
+    pub fn with_unsafe(mut self, is_unsafe: bool) -> Self {
+        self.unsafety = if is_unsafe { "unsafe " } else { "" };
+        self
+    }
+
     /// Add where clauses to the list.
     pub fn with_return_type(mut self, rt: impl Display) -> Self {
         self.return_type = format!("{}", rt);
@@ -231,7 +273,13 @@ impl<'me, W: Write> FnHeader<'me, W> {
     /// Emit fn header -- everything up to the opening `{` for the
     /// body.
     pub fn emit(self) -> io::Result<()> {
-        rust!(self.write, "{}fn {}<", self.visibility, self.name);
+        rust!(
+            self.write,
+            "{}{}fn {}<",
+            self.visibility,
+            self.unsafety,
+            self.name
+        );
 
         for type_parameter in &self.type_parameters {
             rust!(self.write, "{0:1$}{2},", "", TAB, type_parameter);
@@ -2,10 +2,13 @@
 //! to `configuration::Configuration`, but it is not exported outside the
 //! crate. Note that all fields are public and so forth for convenience.
 
+use crate::diagnostic::Diagnostic;
 use crate::log::{Level, Log};
 use std::collections::BTreeSet;
 use std::default::Default;
 use std::path;
+use std::str::FromStr;
+use std::rc::Rc;
 use crate::style::{self, Style};
 
 // These two, ubiquitous types are defined here so that their fields can be private
@@ -23,6 +26,28 @@ pub enum ColorConfig {
     IfTty,
 }
 
+/// The Rust edition of the crate that includes the generated code; see
+/// `Configuration::set_edition`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edition {
+    #[default]
+    Rust2015,
+    Rust2018,
+    Rust2021,
+}
+
+/// What the generated code is tuned for; see
+/// `Configuration::set_profile`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Profile {
+    #[default]
+    Default,
+
+    /// Small code for `wasm32` targets: compressed tables, no
+    /// formatting on panics, and errors without expected tokens.
+    Wasm,
+}
+
 /// Various options to control debug output. Although this struct is
 /// technically part of LALRPOP's exported interface, it is not
 /// considered part of the semver guarantees as end-users are not
@@ -50,8 +75,63 @@ pub struct Session {
     /// Emit report file about generated code
     pub emit_report: bool,
 
+    /// Emit a report on the states of the generated parser as an HTML
+    /// page.
+    pub emit_html_report: bool,
+
+    /// Write the normalized grammar as JSON next to the generated
+    /// code; see `json`.
+    pub emit_grammar_json: bool,
+
+    /// Index the tables of table-driven parsers without bounds checks,
+    /// except with debug assertions on.
+    pub unchecked_tables: bool,
+
+    /// Take values out of the symbols on the stack of table-driven
+    /// parsers without checking their variant, except with debug
+    /// assertions on.
+    pub unchecked_downcasts: bool,
+
+    /// Write the tables of table-driven parsers to a file of their
+    /// own, which the generated code includes.
+    pub split_tables: bool,
+
+    /// The most states that the stacks of table-driven parsers may
+    /// hold, if limited.
+    pub max_stack_depth: Option<usize>,
+
+    /// Keep the first entries of the stacks of table-driven parsers
+    /// inline, in a `lalrpop_util::state_machine::InlineStack`.
+    pub inline_stacks: bool,
+
+    /// The most LR states that building the parser of a nonterminal may
+    /// create, if limited.
+    pub max_states: Option<usize>,
+
+    /// Count how often the action of each rule runs, for coverage
+    /// reports.
+    pub coverage: bool,
+
+    /// Give table-driven parsers a `parse_traced` method, which records
+    /// the shifts and reductions of the parse.
+    pub trace: bool,
+
+    /// Mark the code of each action and reduction with the location of
+    /// its alternative in the grammar; see `source_map`.
+    pub emit_source_map: bool,
+
+    /// Emit `extern "C"` functions for parsing with the generated
+    /// parsers from other languages.
+    pub emit_c_abi: bool,
+
     pub color_config: ColorConfig,
 
+    /// The edition to generate code for.
+    pub edition: Edition,
+
+    /// What the generated code is tuned for.
+    pub profile: Profile,
+
     /// Stop after you find `max_errors` errors. If this value is 0,
     /// report *all* errors. Note that we MAY always report more than
     /// this value if we so choose.
@@ -88,6 +168,13 @@ pub struct Session {
 
     /// Features used for conditional compilation
     pub features: Option<BTreeSet<String>>,
+
+    /// Flags set for `#[cfg(flag = "..")]`
+    pub flags: BTreeSet<String>,
+
+    /// Receives errors and warnings instead of them being printed; see
+    /// `Configuration::set_diagnostic_handler`.
+    pub diagnostic_handler: Option<Rc<dyn Fn(&Diagnostic)>>,
 }
 
 impl Session {
@@ -101,7 +188,21 @@ impl Session {
             emit_comments: false,
             emit_whitespace: true,
             emit_report: false,
+            emit_html_report: false,
+            emit_grammar_json: false,
+            unchecked_tables: false,
+            unchecked_downcasts: false,
+            split_tables: false,
+            max_stack_depth: None,
+            inline_stacks: false,
+            max_states: None,
+            coverage: false,
+            trace: false,
+            emit_source_map: false,
+            emit_c_abi: false,
             color_config: ColorConfig::default(),
+            edition: Edition::default(),
+            profile: Profile::default(),
             max_errors: 1,
             heading: style::FG_WHITE.with(style::BOLD),
             ambig_symbols: style::FG_WHITE,
@@ -113,6 +214,8 @@ impl Session {
             hint_text: style::FG_BRIGHT_MAGENTA.with(style::BOLD),
             unit_test: false,
             features: Default::default(),
+            flags: Default::default(),
+            diagnostic_handler: None,
         }
     }
 
@@ -128,7 +231,21 @@ impl Session {
             emit_comments: false,
             emit_whitespace: true,
             emit_report: false,
+            emit_html_report: false,
+            emit_grammar_json: false,
+            unchecked_tables: false,
+            unchecked_downcasts: false,
+            split_tables: false,
+            max_stack_depth: None,
+            inline_stacks: false,
+            max_states: None,
+            coverage: false,
+            trace: false,
+            emit_source_map: false,
+            emit_c_abi: false,
             color_config: ColorConfig::IfTty,
+            edition: Edition::default(),
+            profile: Profile::default(),
             max_errors: 1,
             heading: Style::new(),
             ambig_symbols: Style::new(),
@@ -140,6 +257,8 @@ impl Session {
             hint_text: Style::new(),
             unit_test: true,
             features: Default::default(),
+            flags: Default::default(),
+            diagnostic_handler: None,
         }
     }
 
@@ -156,6 +275,18 @@ impl Session {
         self.log.log(level, message)
     }
 
+    /// Passes `diagnostic` to the diagnostic handler, returning `false`
+    /// if there is none, in which case the caller prints it instead.
+    pub fn emit_diagnostic(&self, diagnostic: Diagnostic) -> bool {
+        match self.diagnostic_handler {
+            Some(ref handler) => {
+                handler(&diagnostic);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn emit_rerun_directive(&self, path: &path::Path) {
         if self.emit_rerun_directives {
             if let Some(display) = path.to_str() {
@@ -178,3 +309,28 @@ impl Default for ColorConfig {
         ColorConfig::IfTty
     }
 }
+
+impl FromStr for Edition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "2015" => Ok(Edition::Rust2015),
+            "2018" => Ok(Edition::Rust2018),
+            "2021" => Ok(Edition::Rust2021),
+            x => Err(format!("Unknown edition {}", x)),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Profile::Default),
+            "wasm" => Ok(Profile::Wasm),
+            x => Err(format!("Unknown profile {}", x)),
+        }
+    }
+}
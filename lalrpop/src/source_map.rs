@@ -0,0 +1,79 @@
+//! Mapping locations in generated code back to the grammar.
+//!
+//! With `Configuration::emit_source_map`, the generated code has a
+//! comment like
+//!
+//! ```text
+//! // lalrpop-source: src/calculator.lalrpop:12:5
+//! ```
+//!
+//! at the start of the body of each action fn and each reduction,
+//! naming the alternative of the grammar it comes from. The comment
+//! covers the lines after it up to the end of the block it is in.
+//! `translate_locations` uses them to rewrite the locations in a panic
+//! backtrace or a compiler error from the generated file into the
+//! grammar.
+
+use crate::collections::Map;
+use crate::grammar::parse_tree::Span;
+use crate::tls::Tls;
+use regex::{Captures, Regex};
+use std::fs;
+
+const MARKER: &str = "// lalrpop-source: ";
+
+/// The comment that ties the code after it to `span` of the grammar
+/// being processed.
+pub fn marker(span: Span) -> String {
+    format!("{}{}", MARKER, Tls::file_text().location(span))
+}
+
+/// Rewrites each `file.rs:line` or `file.rs:line:column` in `text`
+/// that points into code covered by a `lalrpop-source` comment into
+/// the location in the grammar that the comment names. Other locations,
+/// including those in files that cannot be read, are left as they are.
+pub fn translate_locations(text: &str) -> String {
+    let location = Regex::new(r"([^\s:()<>'`]+\.rs):(\d+)(?::(\d+))?").unwrap();
+    let mut files: Map<String, Option<Vec<String>>> = Map::new();
+    location
+        .replace_all(text, |captures: &Captures| {
+            let lines = files.entry(captures[1].to_string()).or_insert_with(|| {
+                fs::read_to_string(&captures[1])
+                    .ok()
+                    .map(|text| text.lines().map(String::from).collect())
+            });
+            let line: usize = captures[2].parse().unwrap_or(0);
+            lines
+                .as_ref()
+                .and_then(|lines| source_of_line(lines, line))
+                .unwrap_or_else(|| captures[0].to_string())
+        })
+        .into_owned()
+}
+
+/// The grammar location of the 1-based `line` of `lines`: that of the
+/// closest `lalrpop-source` comment before it, unless the block that
+/// the comment is in ends in between.
+fn source_of_line(lines: &[String], line: usize) -> Option<String> {
+    if line == 0 || line > lines.len() {
+        return None;
+    }
+    let (marker_line, source) = lines[..line]
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, text)| text.trim().strip_prefix(MARKER).map(|source| (i, source)))?;
+
+    let mut depth = 0;
+    for text in lines.iter().take(line - 1).skip(marker_line + 1) {
+        for c in text.chars() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => return None,
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    Some(source.to_string())
+}
@@ -51,10 +51,14 @@ pub enum Tok<'input> {
     Where,
     For,
     Dyn,
+    Impl,
 
     // Special keywords: these are accompanied by a series of
     // uninterpreted strings representing imports and stuff.
     Use(&'input str),
+    When(&'input str),    // `when { .. }`, excluding the braces
+    Code(&'input str),    // `code { .. }`, excluding the braces
+    Include(&'input str), // `include "..."`, the text between the quotes
 
     // Identifiers of various kinds:
     Escape(&'input str),
@@ -67,6 +71,7 @@ pub enum Tok<'input> {
 
     // Symbols:
     Ampersand,
+    AmpersandAmpersand,
     BangEquals,
     BangTilde,
     Colon,
@@ -88,6 +93,7 @@ pub enum Tok<'input> {
     Lookahead,  // @L
     Lookbehind, // @R
     MinusGreaterThan,
+    PipePipe,
     Plus,
     Question,
     RightBrace,
@@ -108,6 +114,7 @@ pub enum Tok<'input> {
     StartGrammarWhereClauses,
     #[allow(dead_code)]
     StartTypeRef,
+    StartInclude,
 }
 
 pub struct Tokenizer<'input> {
@@ -133,6 +140,7 @@ const KEYWORDS: &[(&str, Tok<'static>)] = &[
     ("where", Where),
     ("for", For),
     ("dyn", Dyn),
+    ("impl", Impl),
 ];
 
 // Helper for backtracking.
@@ -220,10 +228,13 @@ impl<'input> Tokenizer<'input> {
     fn next_unshifted(&mut self) -> Option<Result<Spanned<Tok<'input>>, Error>> {
         loop {
             return match self.lookahead {
-                Some((idx0, '&')) => {
-                    self.bump();
-                    Some(Ok((idx0, Ampersand, idx0 + 1)))
-                }
+                Some((idx0, '&')) => match self.bump() {
+                    Some((idx1, '&')) => {
+                        self.bump();
+                        Some(Ok((idx0, AmpersandAmpersand, idx1 + 1)))
+                    }
+                    _ => Some(Ok((idx0, Ampersand, idx0 + 1))),
+                },
                 Some((idx0, '!')) => match self.bump() {
                     Some((idx1, '=')) => {
                         self.bump();
@@ -329,6 +340,13 @@ impl<'input> Tokenizer<'input> {
                     self.bump();
                     Some(Ok((idx0, Star, idx0 + 1)))
                 }
+                Some((idx0, '|')) => match self.bump() {
+                    Some((idx1, '|')) => {
+                        self.bump();
+                        Some(Ok((idx0, PipePipe, idx1 + 1)))
+                    }
+                    _ => Some(error(UnrecognizedToken, idx0)),
+                },
                 Some((idx0, '~')) => match self.bump() {
                     Some((idx1, '~')) => {
                         self.bump();
@@ -493,6 +511,22 @@ impl<'input> Tokenizer<'input> {
         }
     }
 
+    /// Scans the code following the `{` at `idx0`, returning the
+    /// index of the matching `}`. Unlike in `code`, a `,` or `;` does
+    /// not end the code.
+    fn braced_code(&mut self, idx0: usize) -> Result<usize, Error> {
+        loop {
+            let idx1 = self.code(idx0, "([{", "}])")?;
+            match self.lookahead {
+                Some((_, '}')) => return Ok(idx1),
+                Some((_, ',')) | Some((_, ';')) => {
+                    self.bump();
+                }
+                _ => return error(UnterminatedCode, idx0),
+            }
+        }
+    }
+
     fn escape(&mut self, idx0: usize) -> Result<Spanned<Tok<'input>>, Error> {
         match self.take_until(|c| c == '`') {
             Some(idx1) => {
@@ -644,6 +678,50 @@ impl<'input> Tokenizer<'input> {
             return Ok((start, Tok::Use(code), code_end));
         }
 
+        // `when` is only a keyword when a guard follows, so that it
+        // can still be used as a name elsewhere
+        if word == "when" {
+            let fallback_state = (self.chars.clone(), self.lookahead);
+            self.take_while(char::is_whitespace);
+            if let Some((idx1, '{')) = self.lookahead {
+                self.bump();
+                let idx2 = self.braced_code(idx1)?;
+                self.bump();
+                return Ok((start, Tok::When(&self.text[idx1 + 1..idx2]), idx2 + 1));
+            }
+            self.chars = fallback_state.0;
+            self.lookahead = fallback_state.1;
+        }
+
+        // so is `code`, when a block of code follows
+        if word == "code" {
+            let fallback_state = (self.chars.clone(), self.lookahead);
+            self.take_while(char::is_whitespace);
+            if let Some((idx1, '{')) = self.lookahead {
+                self.bump();
+                let idx2 = self.braced_code(idx1)?;
+                self.bump();
+                return Ok((start, Tok::Code(&self.text[idx1 + 1..idx2]), idx2 + 1));
+            }
+            self.chars = fallback_state.0;
+            self.lookahead = fallback_state.1;
+        }
+
+        // `include` is only a keyword when the path of a file follows
+        if word == "include" {
+            let fallback_state = (self.chars.clone(), self.lookahead);
+            self.take_while(char::is_whitespace);
+            if let Some((idx1, '"')) = self.lookahead {
+                self.bump();
+                return match self.string_or_char_literal(idx1, '"', Include) {
+                    Some((_, tok, end)) => Ok((start, tok, end)),
+                    None => error(UnterminatedStringLiteral, idx1),
+                };
+            }
+            self.chars = fallback_state.0;
+            self.lookahead = fallback_state.1;
+        }
+
         let tok =
             // search for a keyword first; if none are found, this is
             // either a MacroId or an Id, depending on whether there
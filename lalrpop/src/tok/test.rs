@@ -574,6 +574,84 @@ fn use2() {
     );
 }
 
+#[test]
+fn when1() {
+    test(
+        r#"when { f(a, b); a } => a"#,
+        vec![
+            (r#"~~~~~~~~~~~~~~~~~~~     "#, When(" f(a, b); a ")),
+            (r#"                    ~~~~"#, EqualsGreaterThanCode(" a")),
+        ],
+    );
+}
+
+#[test]
+fn when2() {
+    test(
+        r#"<when:X> when"#,
+        vec![
+            (r#"~            "#, LessThan),
+            (r#" ~~~~        "#, Id("when")),
+            (r#"     ~       "#, Colon),
+            (r#"      ~      "#, Id("X")),
+            (r#"       ~     "#, GreaterThan),
+            (r#"         ~~~~"#, Id("when")),
+        ],
+    );
+}
+
+#[test]
+fn code_block1() {
+    test(
+        r#"code { impl X { } } ;"#,
+        vec![
+            (r#"~~~~~~~~~~~~~~~~~~~  "#, Code(" impl X { } ")),
+            (r#"                    ~"#, Semi),
+        ],
+    );
+}
+
+#[test]
+fn code_block2() {
+    test(
+        r#"<code:X> => code"#,
+        vec![
+            (r#"~               "#, LessThan),
+            (r#" ~~~~           "#, Id("code")),
+            (r#"     ~          "#, Colon),
+            (r#"      ~         "#, Id("X")),
+            (r#"       ~        "#, GreaterThan),
+            (r#"         ~~~~~~~"#, EqualsGreaterThanCode(" code")),
+        ],
+    );
+}
+
+#[test]
+fn include1() {
+    test(
+        r#"include "tokens.lalrpop";"#,
+        vec![
+            (r#"~~~~~~~~~~~~~~~~~~~~~~~~ "#, Include("tokens.lalrpop")),
+            (r#"                        ~"#, Semi),
+        ],
+    );
+}
+
+#[test]
+fn include2() {
+    test(
+        r#"<include:X> include"#,
+        vec![
+            (r#"~                  "#, LessThan),
+            (r#" ~~~~~~~           "#, Id("include")),
+            (r#"        ~          "#, Colon),
+            (r#"         ~         "#, Id("X")),
+            (r#"          ~        "#, GreaterThan),
+            (r#"            ~~~~~~~"#, Id("include")),
+        ],
+    );
+}
+
 #[test]
 fn where1() {
     test(
@@ -740,3 +818,23 @@ fn string_escapes() {
         })
     );
 }
+
+#[test]
+fn condition_operators() {
+    test(
+        r#"!(T == "a") && U || V"#,
+        vec![
+            (r#"~                    "#, Bang),
+            (r#" ~                   "#, LeftParen),
+            (r#"  ~                  "#, Id("T")),
+            (r#"    ~~               "#, EqualsEquals),
+            (r#"       ~~~           "#, StringLiteral("a")),
+            (r#"          ~          "#, RightParen),
+            (r#"            ~~       "#, AmpersandAmpersand),
+            (r#"               ~     "#, Id("U")),
+            (r#"                 ~~  "#, PipePipe),
+            (r#"                    ~"#, Id("V")),
+        ],
+    );
+    test_err("|", ("~", ErrorCode::UnrecognizedToken));
+}
@@ -17,6 +17,9 @@ impl<'a, S: Display> Display for Sep<&'a Vec<S>> {
     }
 }
 
+/// Displays `S` as a Rust identifier. Distinct strings always give
+/// distinct identifiers: `_` is doubled, and any other character that
+/// is not alphanumeric becomes `_`, its code point in hex, and `_`.
 pub struct Escape<S>(pub S);
 
 impl<S: Display> Display for Escape<S> {
@@ -26,7 +29,7 @@ impl<S: Display> Display for Escape<S> {
             match c {
                 'a'..='z' | '0'..='9' | 'A'..='Z' => write!(fmt, "{}", c)?,
                 '_' => write!(fmt, "__")?,
-                _ => write!(fmt, "_{:x}", c as usize)?,
+                _ => write!(fmt, "_{:x}_", c as usize)?,
             }
         }
         Ok(())